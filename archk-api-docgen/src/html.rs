@@ -0,0 +1,288 @@
+//! Single self-contained HTML page with all endpoints.
+
+use std::fmt::Write;
+
+use archk::v1::{
+    api,
+    docs::{DocumentationField, DocumentationObject, Endpoint, EndpointAuth},
+};
+
+use crate::{describe, describe_tagging, display_ty, is_empty};
+
+const STYLE: &str = r#"
+* { box-sizing: border-box; }
+body { margin: 0; font-family: system-ui, sans-serif; line-height: 1.5; color: #1f2328; }
+code { font-family: ui-monospace, monospace; font-size: 0.9em; background: #f0f1f3; padding: 0.1em 0.3em; border-radius: 4px; }
+nav { position: fixed; top: 0; bottom: 0; left: 0; width: 22rem; overflow-y: auto; padding: 1rem; background: #f6f8fa; border-right: 1px solid #d0d7de; }
+nav h2 { font-size: 0.9rem; text-transform: uppercase; margin: 1rem 0 0.25rem; color: #57606a; }
+nav ul { list-style: none; margin: 0; padding: 0; }
+nav a { display: block; padding: 0.1rem 0; color: inherit; text-decoration: none; font-size: 0.85rem; white-space: nowrap; overflow: hidden; text-overflow: ellipsis; }
+nav a:hover { text-decoration: underline; }
+main { margin-left: 22rem; padding: 1rem 2rem; max-width: 70rem; }
+section { border-bottom: 1px solid #d0d7de; padding-bottom: 1rem; }
+section h2 code { background: none; padding: 0; }
+table { border-collapse: collapse; margin: 0.5rem 0; }
+th, td { border: 1px solid #d0d7de; padding: 0.25rem 0.5rem; text-align: left; vertical-align: top; }
+th { background: #f6f8fa; }
+details { margin: 0.5rem 0; }
+summary { cursor: pointer; }
+.method { display: inline-block; min-width: 4rem; font-weight: bold; }
+.GET { color: #1a7f37; } .POST { color: #0969da; } .PUT { color: #9a6700; }
+.PATCH { color: #8250df; } .DELETE { color: #cf222e; }
+"#;
+
+/// Escape text to be placed into HTML.
+fn escape(s: &str) -> String {
+    let mut res = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => res.push_str("&amp;"),
+            '<' => res.push_str("&lt;"),
+            '>' => res.push_str("&gt;"),
+            '"' => res.push_str("&quot;"),
+            '\'' => res.push_str("&#39;"),
+            c => res.push(c),
+        }
+    }
+    res
+}
+
+/// Render description. Only inline code (`` `code` ``) is supported from markdown.
+fn description(s: &str) -> String {
+    escape(s)
+        .split('`')
+        .enumerate()
+        .map(|(i, v)| {
+            if i % 2 == 1 {
+                format!("<code>{v}</code>")
+            } else {
+                v.to_string()
+            }
+        })
+        .collect()
+}
+
+/// Resource endpoint belongs to, used to group endpoints in sidebar.
+fn group(endpoint: &Endpoint) -> &'static str {
+    match endpoint.path.trim_start_matches('/').split('/').next() {
+        Some("auth" | "user" | "users") => "user",
+        Some("space") => "space",
+        Some("service") => "service",
+        Some("admin") => "admin",
+        _ => "other",
+    }
+}
+
+/// Anchor of endpoint, eg. `get-space-space_id`.
+fn anchor(endpoint: &Endpoint) -> String {
+    let mut res = endpoint.method.to_string().to_lowercase();
+    for part in endpoint
+        .path
+        .split(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+    {
+        if !part.is_empty() {
+            res.push('-');
+            res.push_str(part);
+        }
+    }
+    res
+}
+
+fn fields_table(out: &mut String, fields: &[DocumentationField]) {
+    out.push_str("<table><tr><th>Name</th><th>Type</th><th>Description</th></tr>");
+    for field in fields {
+        let _ = write!(
+            out,
+            "<tr><td><code>{}</code></td><td><code>{}</code></td><td>{}</td></tr>",
+            escape(field.name),
+            escape(&display_ty(&field.documentation).to_string()),
+            description(&describe(&field.documentation)),
+        );
+    }
+    out.push_str("</table>");
+}
+
+/// Render body or response of endpoint. Types of fields are collected into `later_types`.
+fn object<'a>(
+    out: &mut String,
+    title: &str,
+    object: &'a DocumentationObject,
+    later_types: &mut Vec<&'a DocumentationObject>,
+) {
+    let _ = write!(out, "<h3>{title}</h3>");
+    if let Some(one_of) = &object.one_of {
+        let _ = write!(
+            out,
+            "<p>One of variants. {}.</p>",
+            description(&describe_tagging(one_of.tagging))
+        );
+        for variant in one_of.variants {
+            let ty = &variant.documentation;
+            let _ = write!(
+                out,
+                "<h4>Variant <code>{}</code></h4><p>{}</p>",
+                escape(variant.name),
+                description(ty.description)
+            );
+            if !ty.fields.is_empty() {
+                fields_table(out, ty.fields);
+                object_types(ty, later_types);
+            } else if ty.name != "Unit" {
+                let _ = write!(
+                    out,
+                    "<p>Content type is <code>{}</code>.</p>",
+                    escape(&display_ty(ty).to_string())
+                );
+            }
+        }
+        return;
+    }
+    if object.fields.is_empty() {
+        let _ = write!(
+            out,
+            "<p>{title} type is <code>{}</code>.</p>",
+            escape(&display_ty(object).to_string())
+        );
+        return;
+    }
+    fields_table(out, object.fields);
+    object_types(object, later_types);
+}
+
+/// Collect nested struct types of `object` fields, each type once.
+fn object_types<'a>(
+    object: &'a DocumentationObject,
+    later_types: &mut Vec<&'a DocumentationObject>,
+) {
+    for field in object.fields {
+        let ty = &field.documentation;
+        if !ty.fields.is_empty() && !later_types.iter().any(|v| v.name == ty.name) {
+            later_types.push(ty);
+            object_types(ty, later_types);
+        }
+    }
+}
+
+fn endpoint(out: &mut String, endpoint: &Endpoint) {
+    let _ = write!(
+        out,
+        r##"<section id="{id}"><h2><a href="#{id}"><span class="method {method}">{method}</span></a> <code>/api/v1{path}</code></h2>"##,
+        id = anchor(endpoint),
+        method = endpoint.method,
+        path = escape(endpoint.path),
+    );
+
+    for paragraph in endpoint.description.split("\n\n") {
+        if !paragraph.trim().is_empty() {
+            let _ = write!(out, "<p>{}</p>", description(paragraph.trim()));
+        }
+    }
+
+    let auth = match endpoint.auth {
+        EndpointAuth::None => "not required",
+        EndpointAuth::User => "user token",
+        EndpointAuth::Service => "service token",
+    };
+    let _ = write!(out, "<p><strong>Auth</strong>: {auth}</p>");
+    if !endpoint.permissions.is_empty() {
+        let permissions: Vec<_> = endpoint
+            .permissions
+            .iter()
+            .map(|v| format!("<code>{}</code>", escape(v)))
+            .collect();
+        let _ = write!(
+            out,
+            "<p><strong>Permissions</strong>: {}</p>",
+            permissions.join(", ")
+        );
+    }
+
+    if let Some(params) = &endpoint.params {
+        out.push_str("<h3>Path parameters</h3>");
+        fields_table(out, params.fields);
+    }
+    if let Some(query) = &endpoint.query {
+        out.push_str("<h3>Query</h3>");
+        fields_table(out, query.fields);
+    }
+
+    let mut later_types = Vec::new();
+    if let Some(body) = endpoint.body.as_ref().filter(|v| !is_empty(v)) {
+        object(out, "Body", body, &mut later_types);
+    }
+    if let Some(response) = endpoint.response.as_ref().filter(|v| !is_empty(v)) {
+        object(out, "Response", response, &mut later_types);
+    }
+    for ty in later_types {
+        let _ = write!(
+            out,
+            "<details><summary>Type: <code>{}</code></summary>",
+            escape(ty.name)
+        );
+        fields_table(out, ty.fields);
+        out.push_str("</details>");
+    }
+
+    out.push_str("</section>");
+}
+
+fn errors(out: &mut String) {
+    out.push_str(r#"<h1 id="errors">errors</h1>"#);
+    out.push_str("<table><tr><th>Code</th><th>HTTP</th><th>Name</th><th>Description</th></tr>");
+    for err in api::Error::ALL {
+        let _ = write!(
+            out,
+            "<tr><td><code>{}</code></td><td><code>{}</code></td><td><code>{}</code></td><td>{}</td></tr>",
+            *err as u16,
+            err.http_code(),
+            err.name(),
+            description(err.description()),
+        );
+    }
+    out.push_str("</table>");
+}
+
+/// Render all endpoints into HTML page.
+pub fn render(endpoints: &[Endpoint]) -> String {
+    let mut groups: Vec<(&str, Vec<&Endpoint>)> = Vec::new();
+    for endpoint in endpoints {
+        let name = group(endpoint);
+        match groups.iter_mut().find(|(v, _)| *v == name) {
+            Some((_, group)) => group.push(endpoint),
+            None => groups.push((name, vec![endpoint])),
+        }
+    }
+
+    let mut out = String::new();
+    let _ = write!(
+        out,
+        r#"<!DOCTYPE html><html lang="en"><head><meta charset="utf-8"><meta name="viewport" content="width=device-width, initial-scale=1"><title>archk API v1</title><style>{STYLE}</style></head><body>"#
+    );
+
+    out.push_str("<nav><h1>archk API v1</h1>");
+    for (name, group) in &groups {
+        let _ = write!(out, "<h2>{name}</h2><ul>");
+        for endpoint in group {
+            let _ = write!(
+                out,
+                r##"<li><a href="#{}"><span class="method {method}">{method}</span> {}</a></li>"##,
+                anchor(endpoint),
+                escape(endpoint.path),
+                method = endpoint.method,
+            );
+        }
+        out.push_str("</ul>");
+    }
+    out.push_str(r##"<h2><a href="#errors">errors</a></h2></nav><main>"##);
+
+    for (name, group) in &groups {
+        let _ = write!(out, r#"<h1 id="{name}">{name}</h1>"#);
+        for v in group {
+            self::endpoint(&mut out, v);
+        }
+    }
+    errors(&mut out);
+
+    out.push_str("</main></body></html>");
+    out
+}
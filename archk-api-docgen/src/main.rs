@@ -1,17 +1,24 @@
-use archk::v1::docs::DocumentationObject;
+use archk::v1::docs::{
+    self, DocumentationField, DocumentationObject, DocumentationOneOf, DocumentationTagging,
+    EndpointAuth,
+};
 use clap::builder::TypedValueParser;
 use clap::Parser;
 
+mod html;
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum Format {
-    JSON,
+    Json,
     Markdown,
+    Html,
 }
 impl From<String> for Format {
     fn from(value: String) -> Self {
         match value.as_str() {
-            "json" => Self::JSON,
+            "json" => Self::Json,
             "markdown" => Self::Markdown,
+            "html" => Self::Html,
             _ => panic!("invalid format value"),
         }
     }
@@ -19,8 +26,9 @@ impl From<String> for Format {
 impl std::fmt::Display for Format {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Self::JSON => write!(f, "json"),
+            Self::Json => write!(f, "json"),
             Self::Markdown => write!(f, "markdown"),
+            Self::Html => write!(f, "html"),
         }
     }
 }
@@ -32,21 +40,49 @@ struct Args {
     /// Name of the person to greet
     #[arg(
         long,
-        default_value_t = Format::JSON,
-        value_parser = clap::builder::PossibleValuesParser::new(["json", "markdown"])
+        default_value_t = Format::Json,
+        value_parser = clap::builder::PossibleValuesParser::new(["json", "markdown", "html"])
             .map(|s| Format::from(s)),
     )]
     format: Format,
+    /// Check that every endpoint has documented path parameters, body and response
+    /// instead of generating documentation. Exits with non-zero code on failure
+    #[arg(long)]
+    check: bool,
+}
+
+/// Is `b` [`docs::Empty`] marker (no body or JSON response)? Such sections are omitted.
+pub(crate) fn is_empty(b: &DocumentationObject) -> bool {
+    *b == <docs::Empty as docs::Documentation>::DOCUMENTATION_OBJECT
 }
 
-fn display_ty<'a>(b: &'a DocumentationObject) -> impl std::fmt::Display + 'a {
+pub(crate) fn display_ty<'a>(b: &'a DocumentationObject) -> impl std::fmt::Display + 'a {
     struct Container<'a>(&'a DocumentationObject);
     impl<'a> std::fmt::Display for Container<'a> {
         fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
             if self.0.is_may_ignored {
                 write!(f, "?")?;
             }
-            write!(f, "{}", self.0.name)?;
+            if self.0.items.is_empty() {
+                write!(f, "{}", self.0.name)?;
+            } else {
+                // `Tuple` is rendered as `(A, B)`, other containers as `Map<V>`
+                let (open, close) = match self.0.name {
+                    "Tuple" => ("(", ")"),
+                    name => {
+                        write!(f, "{name}")?;
+                        ("<", ">")
+                    }
+                };
+                write!(f, "{open}")?;
+                for (i, item) in self.0.items.iter().enumerate() {
+                    if i != 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", Container(item))?;
+                }
+                write!(f, "{close}")?;
+            }
             if self.0.is_option {
                 write!(f, "?")?;
             }
@@ -59,16 +95,98 @@ fn display_ty<'a>(b: &'a DocumentationObject) -> impl std::fmt::Display + 'a {
     Container(b)
 }
 
+/// Field description with constraints and example, eg. ``Username (min `3`, max `31`, example `greg.b42`)``.
+pub(crate) fn describe(b: &DocumentationObject) -> String {
+    let mut extra = Vec::new();
+    if let Some(min) = b.min {
+        extra.push(format!("min `{min}`"));
+    }
+    if let Some(max) = b.max {
+        extra.push(format!("max `{max}`"));
+    }
+    if let Some(example) = b.example {
+        extra.push(format!("example `{example}`"));
+    }
+
+    if extra.is_empty() {
+        b.description.to_string()
+    } else {
+        format!("{} ({})", b.description, extra.join(", "))
+    }
+}
+
+/// How union variants are represented in JSON.
+pub(crate) fn describe_tagging(tagging: DocumentationTagging) -> String {
+    match tagging {
+        DocumentationTagging::External => "Object with single key named as variant and content of variant as value, variants without content are just strings".into(),
+        DocumentationTagging::Internal { tag } => format!("Object with `{tag}` field set to name of variant and fields of variant"),
+        DocumentationTagging::Adjacent { tag, content } => format!("Object with `{tag}` field set to name of variant and `{content}` field with content of variant"),
+        DocumentationTagging::Untagged => "Content of any variant as is".into(),
+    }
+}
+
+fn print_one_of<'a>(one_of: &'a DocumentationOneOf, later_types: &mut Vec<&'a DocumentationField>) {
+    println!("One of variants. {}.", describe_tagging(one_of.tagging));
+    for variant in one_of.variants {
+        let ty = &variant.documentation;
+        println!("#### Variant `{}`", variant.name);
+        println!("{}", ty.description);
+        if ty.fields.is_empty() {
+            if ty.name != "Unit" {
+                println!("Content type is `{}`.", display_ty(ty));
+            }
+            continue;
+        }
+        println!("| Name | Type | Description |");
+        println!("|------|------|-------------|");
+        for field in ty.fields {
+            println!(
+                "| `{}` | `{}` | {} |",
+                field.name,
+                display_ty(&field.documentation),
+                describe(&field.documentation)
+            );
+            if !field.documentation.fields.is_empty() {
+                later_types.push(field);
+            }
+        }
+    }
+}
+
 fn main() {
     let args = Args::parse();
 
     let endpoints = archk_api::v1::routes::ENDPOINTS;
 
+    if args.check {
+        let mut failed = false;
+        for endpoint in endpoints {
+            let missing = endpoint.missing_documentation();
+            if !missing.is_empty() {
+                failed = true;
+                eprintln!(
+                    "{} {}: undocumented {}",
+                    endpoint.method,
+                    endpoint.path,
+                    missing.join(", ")
+                );
+            }
+        }
+        if failed {
+            std::process::exit(1);
+        }
+        eprintln!("All {} endpoints are documented", endpoints.len());
+        return;
+    }
+
     match args.format {
-        Format::JSON => {
+        Format::Json => {
             let res = serde_json::to_string_pretty(endpoints).expect("json");
             println!("{res}");
         }
+        Format::Html => {
+            println!("{}", html::render(endpoints));
+        }
         Format::Markdown => {
             let mut later_types = Vec::new();
             for endpoint in endpoints {
@@ -76,9 +194,53 @@ fn main() {
                 println!("## {} `/api/v1{}`", endpoint.method, endpoint.path);
                 println!("{}", endpoint.description);
 
-                if let Some(body) = &endpoint.body {
+                match endpoint.auth {
+                    EndpointAuth::None => println!("**Auth**: not required"),
+                    EndpointAuth::User => println!("**Auth**: user token"),
+                    EndpointAuth::Service => println!("**Auth**: service token"),
+                }
+                if !endpoint.permissions.is_empty() {
+                    let permissions: Vec<_> = endpoint
+                        .permissions
+                        .iter()
+                        .map(|v| format!("`{v}`"))
+                        .collect();
+                    println!("\n**Permissions**: {}", permissions.join(", "));
+                }
+
+                if let Some(params) = &endpoint.params {
+                    println!("### Path parameters");
+                    println!("| Name | Type | Description |");
+                    println!("|------|------|-------------|");
+                    for field in params.fields {
+                        println!(
+                            "| `{}` | `{}` | {} |",
+                            field.name,
+                            display_ty(&field.documentation),
+                            describe(&field.documentation)
+                        );
+                    }
+                }
+
+                if let Some(query) = &endpoint.query {
+                    println!("### Query");
+                    println!("| Name | Type | Description |");
+                    println!("|------|------|-------------|");
+                    for field in query.fields {
+                        println!(
+                            "| `{}` | `{}` | {} |",
+                            field.name,
+                            display_ty(&field.documentation),
+                            describe(&field.documentation)
+                        );
+                    }
+                }
+
+                if let Some(body) = endpoint.body.as_ref().filter(|v| !is_empty(v)) {
                     println!("### Body");
-                    if body.fields.is_empty() {
+                    if let Some(one_of) = &body.one_of {
+                        print_one_of(one_of, &mut later_types);
+                    } else if body.fields.is_empty() {
                         println!("Body type is `{}`.", display_ty(body));
                     } else {
                         println!("| Name | Type | Description |");
@@ -88,7 +250,7 @@ fn main() {
                                 "| `{}` | `{}` | {} |",
                                 field.name,
                                 display_ty(&field.documentation),
-                                field.documentation.description
+                                describe(&field.documentation)
                             );
                             if !field.documentation.fields.is_empty() {
                                 later_types.push(field);
@@ -97,9 +259,11 @@ fn main() {
                     }
                 }
 
-                if let Some(response) = &endpoint.response {
+                if let Some(response) = endpoint.response.as_ref().filter(|v| !is_empty(v)) {
                     println!("### Response");
-                    if response.fields.is_empty() {
+                    if let Some(one_of) = &response.one_of {
+                        print_one_of(one_of, &mut later_types);
+                    } else if response.fields.is_empty() {
                         println!("Response type is `{}`.", display_ty(response));
                     } else {
                         println!("| Name | Type | Description |");
@@ -109,7 +273,7 @@ fn main() {
                                 "| `{}` | `{}` | {} |",
                                 field.name,
                                 display_ty(&field.documentation),
-                                field.documentation.description
+                                describe(&field.documentation)
                             );
                             if !field.documentation.fields.is_empty() {
                                 later_types.push(field);
@@ -136,11 +300,24 @@ fn main() {
                             "| `{}` | `{}` | {} |",
                             field.name,
                             display_ty(&field.documentation),
-                            field.documentation.description
+                            describe(&field.documentation)
                         );
                     }
                 }
             }
+
+            println!("## Errors");
+            println!("| Code | HTTP | Name | Description |");
+            println!("|------|------|------|-------------|");
+            for err in archk::v1::api::Error::ALL {
+                println!(
+                    "| `{}` | `{}` | `{}` | {} |",
+                    *err as u16,
+                    err.http_code(),
+                    err.name(),
+                    err.description()
+                );
+            }
         }
     }
 }
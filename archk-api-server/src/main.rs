@@ -1,65 +1,473 @@
-use std::{fs, net::SocketAddrV4};
+use std::{
+    fmt::Write,
+    fs,
+    net::SocketAddr,
+    os::unix::fs::{FileTypeExt, PermissionsExt},
+    path::Path,
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
 
-use archk_api::app::{AppConfig, AppConfigServerPublishOnPort, AppState};
-use axum::{routing::get, Router};
-use sqlx::SqlitePool;
+use arc_swap::ArcSwap;
+use archk_api::{
+    app::{
+        AppConfig, AppConfigServerPublishOn, AppConfigServerPublishOnPort, AppConfigServerTls,
+        AppState, ProxyAuth,
+    },
+    cache::Cache,
+    notify::Notifier,
+    oidc::Oidc,
+    roles::UserRoles,
+    storage::Attachments,
+};
+use axum::{http::HeaderName, routing::get, Router};
+use axum_server::tls_rustls::RustlsConfig;
+use hyper_util::{
+    rt::{TokioExecutor, TokioIo},
+    server::conn::auto,
+    service::TowerToHyperService,
+};
+use socket2::{Domain, Protocol, Socket, Type};
+use tokio::{
+    net::UnixListener,
+    signal::unix::{signal, SignalKind},
+    task::JoinSet,
+};
 
-#[tokio::main]
-async fn main() {
-    tracing_subscriber::fmt::init();
+/// Prefix of environment variables overriding config options.
+const ENV_OVERRIDE_PREFIX: &str = "ARCHK__";
 
-    let config = {
-        let cfg_path = std::env::var("CONFIG_PATH").unwrap_or("config.yml".into());
-        let cfg = match fs::read_to_string(&cfg_path) {
-            Ok(cfg) => cfg,
-            Err(e) => {
-                eprintln!(
-                    "Failed to read config `{cfg_path}` (got from `$CONFIG_PATH` variable): {e}"
-                );
-                eprintln!("help: config example located in source repository `config.example.yml`");
-                panic!("failed to read config: {e}");
-            }
+/// Override config options with environment variables like `ARCHK__SERVER__DATABASE`.
+/// Path segments are separated by `__` and lowercased, numeric segments index into lists
+/// (`ARCHK__SERVER__ROLES__0__LEVEL`). Value is parsed as YAML, so `8000` is number and
+/// `[a, b]` is list.
+fn apply_env_overrides(
+    config: &mut serde_yaml::Value,
+    vars: impl Iterator<Item = (String, String)>,
+) -> Result<(), String> {
+    use serde_yaml::Value;
+
+    for (name, value) in vars {
+        let Some(path) = name.strip_prefix(ENV_OVERRIDE_PREFIX) else {
+            continue;
         };
-        let cfg: AppConfig = match serde_yaml::from_str(&cfg) {
+
+        let mut node = &mut *config;
+        for segment in path.split("__").map(str::to_lowercase) {
+            if node.is_null() {
+                *node = Value::Mapping(Default::default());
+            }
+            node = match node {
+                Value::Mapping(map) => map.entry(Value::String(segment)).or_insert(Value::Null),
+                Value::Sequence(seq) => match segment.parse::<usize>().ok() {
+                    Some(i) if i < seq.len() => &mut seq[i],
+                    _ => return Err(format!("`{name}`: no list item `{segment}` in config")),
+                },
+                _ => return Err(format!("`{name}`: `{segment}` is not an option in config")),
+            };
+        }
+
+        // block mappings are not expected in env values, so things like `::` (IPv6 address)
+        // are kept as strings
+        *node = match serde_yaml::from_str(&value) {
+            Ok(Value::Mapping(_)) if !value.trim_start().starts_with('{') => Value::String(value),
             Ok(v) => v,
-            Err(e) => {
-                eprintln!("Failed to deserialize config `{cfg_path}` (got from `$CONFIG_PATH$` variable): {e}");
-                eprintln!("help: config example located in source repository `config.example.yml`");
-                if let Some(loc) = e.location() {
-                    let (line, col) = (loc.line(), loc.column());
-                    eprintln!("help: failed on line {line} column {col}");
-                    let line_no_str = line.to_string();
-                    let line_str = cfg.lines().skip(line - 1).next().unwrap_or_default();
-                    eprintln!(" {line_no_str} | {line_str}");
-                    (0..line_no_str.len() + 3 + col).for_each(|_| eprint!(" "));
-                    eprintln!("^ {e}");
+            Err(_) => Value::String(value),
+        };
+    }
+
+    Ok(())
+}
+
+/// Read and deserialize config, then apply environment overrides (see
+/// [`apply_env_overrides`]). `Err` contains human-readable report of error.
+fn read_config(cfg_path: &str) -> Result<AppConfig, String> {
+    let cfg = match fs::read_to_string(cfg_path) {
+        Ok(cfg) => cfg,
+        Err(e) => {
+            return Err(format!(
+                "Failed to read config `{cfg_path}` (got from `$CONFIG_PATH` variable): {e}\n\
+                help: config example located in source repository `config.example.yml`"
+            ))
+        }
+    };
+
+    let mut value: serde_yaml::Value = serde_yaml::from_str(&cfg).map_err(|e| {
+        let mut report = format!(
+            "Failed to deserialize config `{cfg_path}` (got from `$CONFIG_PATH$` variable): {e}\n\
+            help: config example located in source repository `config.example.yml`"
+        );
+        if let Some(loc) = e.location() {
+            let (line, col) = (loc.line(), loc.column());
+            let line_no_str = line.to_string();
+            let line_str = cfg.lines().nth(line - 1).unwrap_or_default();
+            let padding = " ".repeat(line_no_str.len() + 3 + col);
+            let _ = write!(
+                report,
+                "\nhelp: failed on line {line} column {col}\n {line_no_str} | {line_str}\n{padding}^ {e}"
+            );
+        }
+        report
+    })?;
+
+    let vars = std::env::vars_os()
+        .filter_map(|(name, value)| Some((name.into_string().ok()?, value.into_string().ok()?)));
+    apply_env_overrides(&mut value, vars)
+        .map_err(|e| format!("Failed to apply config override from environment {e}"))?;
+
+    serde_yaml::from_value(value).map_err(|e| {
+        format!(
+            "Failed to deserialize config `{cfg_path}` with environment overrides: {e}\n\
+            help: config example located in source repository `config.example.yml`"
+        )
+    })
+}
+
+/// Re-read config on `SIGHUP` and swap user roles. Other options require restart.
+fn spawn_reload_on_sighup(cfg_path: String, roles: Arc<ArcSwap<UserRoles>>) {
+    let mut sighup = signal(SignalKind::hangup()).expect("SIGHUP handler");
+
+    tokio::spawn(async move {
+        while sighup.recv().await.is_some() {
+            match read_config(&cfg_path) {
+                Ok(cfg) => {
+                    roles.store(Arc::new(cfg.server.roles));
+                    tracing::info!(path = cfg_path, "Reloaded user roles from config");
+                }
+                Err(report) => {
+                    tracing::error!(path = cfg_path, "Config reload failed, keeping old roles");
+                    eprintln!("{report}");
                 }
-                panic!("failed to deserialize config: {e}");
+            }
+        }
+    });
+}
+
+/// Interval of checking TLS certificate files for changes
+const TLS_RELOAD_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Reload TLS certificate when its files change, eg. after renewal.
+fn spawn_tls_reload(tls: AppConfigServerTls, config: RustlsConfig) {
+    fn modified(tls: &AppConfigServerTls) -> Option<(SystemTime, SystemTime)> {
+        let cert = fs::metadata(&tls.cert).and_then(|v| v.modified()).ok()?;
+        let key = fs::metadata(&tls.key).and_then(|v| v.modified()).ok()?;
+        Some((cert, key))
+    }
+
+    tokio::spawn(async move {
+        let mut last = modified(&tls);
+        let mut interval = tokio::time::interval(TLS_RELOAD_INTERVAL);
+        loop {
+            interval.tick().await;
+            let current = modified(&tls);
+            if current.is_none() || current == last {
+                continue;
+            }
+
+            // on failure `last` is kept, so reload is retried on next tick
+            // (eg. if certificate was written before key)
+            match config.reload_from_pem_file(&tls.cert, &tls.key).await {
+                Ok(()) => {
+                    last = current;
+                    tracing::info!("Reloaded TLS certificate");
+                }
+                Err(err) => tracing::error!(%err, "Failed to reload TLS certificate"),
+            }
+        }
+    });
+}
+
+/// Bind TCP listener. IPv6 socket is IPv6-only unless `dual_stack` is set.
+fn bind_tcp(addr: SocketAddr, dual_stack: bool) -> std::io::Result<std::net::TcpListener> {
+    let socket = Socket::new(Domain::for_address(addr), Type::STREAM, Some(Protocol::TCP))?;
+    if addr.is_ipv6() {
+        socket.set_only_v6(!dual_stack)?;
+    }
+    socket.set_reuse_address(true)?;
+    socket.set_nonblocking(true)?;
+    socket.bind(&addr.into())?;
+    socket.listen(1024)?;
+
+    Ok(socket.into())
+}
+
+/// Bind unix socket at `path`, removing stale socket file and setting its permissions.
+fn bind_unix(path: &Path, mode: Option<&str>) -> UnixListener {
+    let mode = mode.map(|mode| match u32::from_str_radix(mode, 8) {
+        Ok(v) => v,
+        Err(err) => {
+            eprintln!("Invalid unix socket mode `{mode}`, expected octal like \"660\": {err}");
+            panic!("invalid unix socket mode: {err}");
+        }
+    });
+
+    if fs::symlink_metadata(path)
+        .map(|v| v.file_type().is_socket())
+        .unwrap_or(false)
+    {
+        if let Err(err) = fs::remove_file(path) {
+            eprintln!(
+                "Failed to remove stale unix socket `{}`: {err}",
+                path.display()
+            );
+            panic!("failed to remove stale socket: {err}");
+        }
+    }
+
+    let listener = match UnixListener::bind(path) {
+        Ok(v) => v,
+        Err(err) => {
+            eprintln!("Failed to bind to unix socket `{}`: {err}", path.display());
+            panic!("failed to bind: {err}");
+        }
+    };
+
+    if let Some(mode) = mode {
+        if let Err(err) = fs::set_permissions(path, fs::Permissions::from_mode(mode)) {
+            eprintln!(
+                "Failed to set permissions {mode:o} of unix socket `{}`: {err}",
+                path.display()
+            );
+            panic!("failed to set socket permissions: {err}");
+        }
+    }
+
+    listener
+}
+
+/// Serve `app` on unix socket. [`axum::serve`] supports only TCP, so connections are
+/// served by hyper directly.
+async fn serve_unix(listener: UnixListener, app: Router) {
+    loop {
+        let (socket, _) = match listener.accept().await {
+            Ok(v) => v,
+            Err(err) => {
+                tracing::error!(%err, "Failed to accept unix socket connection");
+                continue;
             }
         };
-        cfg.server
+
+        let service = TowerToHyperService::new(app.clone());
+        tokio::spawn(async move {
+            if let Err(err) = auto::Builder::new(TokioExecutor::new())
+                .serve_connection_with_upgrades(TokioIo::new(socket), service)
+                .await
+            {
+                tracing::debug!(%err, "Failed to serve unix socket connection");
+            }
+        });
+    }
+}
+
+#[tokio::main]
+async fn main() {
+    let cfg_path = std::env::var("CONFIG_PATH").unwrap_or("config.yml".into());
+    let AppConfig {
+        server: config,
+        auth,
+        bootstrap,
+    } = match read_config(&cfg_path) {
+        Ok(cfg) => cfg,
+        Err(report) => {
+            eprintln!("{report}");
+            panic!("failed to load config");
+        }
     };
 
-    let db = SqlitePool::connect(&config.database)
+    // exports traces until server stops
+    let _tracer_provider = match archk_api::telemetry::init(config.tracing.as_ref()) {
+        Ok(v) => v,
+        Err(e) => {
+            eprintln!("Invalid `server.tracing` option in config: {e}");
+            panic!("invalid tracing config: {e}");
+        }
+    };
+
+    let db = config
+        .sqlite
+        .connect(&config.database)
         .await
         .expect("db connection");
 
-    if let Err(err) = archk_api::apply_migrations(&db).await {
-        eprintln!("Failed to migrate on `{}`: {err}", config.database);
-        panic!("failed to migrate: {err}");
+    let migrations = match archk_api::migrations(&db).await {
+        Ok(v) => v,
+        Err(err) => {
+            eprintln!("Failed to read migrations of `{}`: {err}", config.database);
+            panic!("failed to read migrations: {err}");
+        }
+    };
+    // pre-flight check: report pending migrations and exit without starting
+    if std::env::args().any(|v| v == "--check-migrations") {
+        let pending: Vec<_> = migrations.iter().filter(|v| v.is_pending()).collect();
+        for v in &pending {
+            println!("pending {} {}", v.version, v.description);
+        }
+        std::process::exit(if pending.is_empty() { 0 } else { 1 });
+    }
+
+    // explicit migration: apply pending migrations and exit without starting
+    let migrate = std::env::args().any(|v| v == "--migrate");
+
+    match archk_api::check_migrations(migrations, config.auto_migrate || migrate) {
+        Ok(false) => {}
+        Ok(true) => {
+            if let Err(err) = archk_api::apply_migrations(&db).await {
+                eprintln!("Failed to migrate on `{}`: {err}", config.database);
+                panic!("failed to migrate: {err}");
+            }
+        }
+        Err(err) => {
+            eprintln!("Refusing to start on `{}`: {err}", config.database);
+            panic!("pending migrations");
+        }
+    }
+    if migrate {
+        std::process::exit(0);
+    }
+
+    if let Some(bootstrap) = bootstrap {
+        match archk_api::bootstrap::run(&db, &bootstrap, &config.roles).await {
+            Ok(true) => tracing::info!(
+                username = bootstrap.admin.username,
+                "Created initial admin from `bootstrap` config"
+            ),
+            Ok(false) => {}
+            Err(e) => {
+                eprintln!("Invalid `bootstrap` option in config: {e}");
+                panic!("invalid bootstrap config: {e}");
+            }
+        }
     }
 
+    let db_read = match &config.database_read {
+        Some(url) => Some(
+            config
+                .sqlite
+                .connect_read_only(url)
+                .await
+                .expect("read db connection"),
+        ),
+        None => None,
+    };
+
+    let oidc = auth.oidc.map(|oidc| match Oidc::new(oidc) {
+        Ok(v) => Arc::new(v),
+        Err(e) => {
+            eprintln!("Invalid `auth.oidc` option in config: {e}");
+            panic!("invalid oidc config: {e}");
+        }
+    });
+
+    let proxy_auth = auth.proxy.map(|proxy| match ProxyAuth::new(proxy) {
+        Ok(v) => Arc::new(v),
+        Err(e) => {
+            eprintln!("Invalid `auth.proxy` option in config: {e}");
+            panic!("invalid proxy auth config: {e}");
+        }
+    });
+
+    let client_cert_header = config
+        .client_cert_header
+        .map(|v| match HeaderName::try_from(&v) {
+            Ok(v) => v,
+            Err(e) => {
+                eprintln!("Invalid `server.client_cert_header` option in config: {e}");
+                panic!("invalid client cert header: {e}");
+            }
+        });
+
+    let attachments = config
+        .attachments
+        .map(|attachments| match Attachments::new(attachments) {
+            Ok(v) => Arc::new(v),
+            Err(e) => {
+                eprintln!("Invalid `server.attachments` option in config: {e}");
+                panic!("invalid attachments config: {e}");
+            }
+        });
+
+    let notifier = config
+        .notifications
+        .map(|notifications| match Notifier::new(notifications) {
+            Ok(v) => Arc::new(v),
+            Err(e) => {
+                eprintln!("Invalid `server.notifications` option in config: {e}");
+                panic!("invalid notifications config: {e}");
+            }
+        });
+
     let state = AppState {
         db,
-        roles: Box::leak(Box::new(config.roles)),
+        db_read,
+        roles: Arc::new(ArcSwap::from_pointee(config.roles)),
+        oidc,
+        lockout: auth.lockout,
+        token_format: auth.token_format,
+        proxy_auth,
+        invite_waves: config.invite_waves,
+        services: config.services,
+        cache: Arc::new(Cache::new(&config.cache)),
+        client_cert_header,
+        trusted_proxies: config.trusted_proxies.into(),
+        attachments,
+        notifier: notifier.clone(),
     };
 
+    spawn_reload_on_sighup(cfg_path, state.roles.clone());
+
+    archk_api::jobs::spawn(state.clone());
+
+    if let Some(notifier) = notifier {
+        archk_api::notify::spawn(state.db.clone(), notifier);
+    }
+
+    if let Some(mqtt) = config.mqtt {
+        let address = match mqtt.address() {
+            Ok(v) => v,
+            Err(e) => {
+                eprintln!("Invalid `server.mqtt` option in config: {e}");
+                panic!("invalid mqtt config: {e}");
+            }
+        };
+        archk_api::v1::mqtt::spawn(state.clone(), mqtt, address);
+    }
+
+    let cors = config.cors.map(|cors| match cors.layer() {
+        Ok(v) => v,
+        Err(e) => {
+            eprintln!("Invalid `server.cors` option in config: {e}");
+            panic!("invalid cors config: {e}");
+        }
+    });
+
     let app = Router::new()
-        .nest("/api/v1", archk_api::v1::get_routes())
+        .nest(
+            "/api/v1",
+            archk_api::v1::get_routes(state.clone(), cors, config.body_limit, config.compression),
+        )
         .route("/", get(|| async { String::from("hi") }))
         .with_state(state);
 
-    let port = match config.publish_on.port {
+    let (ips, port, dual_stack) = match config.publish_on {
+        AppConfigServerPublishOn::Tcp {
+            ip,
+            port,
+            dual_stack,
+        } => (ip.to_vec(), port, dual_stack),
+        AppConfigServerPublishOn::Unix { unix, mode } => {
+            if config.tls.is_some() {
+                eprintln!("TLS is not supported on unix socket, remove `server.tls` option and terminate TLS on proxy");
+                panic!("TLS on unix socket");
+            }
+
+            let listener = bind_unix(&unix, mode.as_deref());
+            tracing::info!(path = %unix.display(), "Starting server");
+            serve_unix(listener, app).await;
+            return;
+        }
+    };
+
+    let port = match port {
         AppConfigServerPublishOnPort::Port(v) => v,
         AppConfigServerPublishOnPort::ObtainFromEnv => {
             match std::env::var("PORT").map(|v| v.parse()) {
@@ -72,23 +480,66 @@ async fn main() {
         }
     };
 
-    let listener =
-        tokio::net::TcpListener::bind(SocketAddrV4::new(config.publish_on.ip, port)).await;
-    let listener = match listener {
-        Ok(v) => v,
-        Err(err) => {
-            eprintln!(
-                "Failed to bind to address `{}:{}`: {err}",
-                config.publish_on.ip, port
-            );
-            panic!("failed to bind: {err}");
-        }
+    let tls = match config.tls {
+        Some(tls) => match RustlsConfig::from_pem_file(&tls.cert, &tls.key).await {
+            Ok(rustls) => {
+                spawn_tls_reload(tls, rustls.clone());
+                Some(rustls)
+            }
+            Err(err) => {
+                eprintln!(
+                    "Failed to load TLS certificate `{}` and key `{}`: {err}",
+                    tls.cert.display(),
+                    tls.key.display()
+                );
+                panic!("failed to load TLS certificate: {err}");
+            }
+        },
+        None => None,
     };
 
-    tracing::info!(
-        ip = config.publish_on.ip.to_string(),
-        port = port,
-        "Starting server"
-    );
-    axum::serve(listener, app).await.unwrap();
+    if ips.is_empty() {
+        eprintln!("Expected at least one address in `server.publish_on.ip`");
+        panic!("no addresses to bind");
+    }
+
+    let mut servers = JoinSet::new();
+    for ip in ips {
+        let addr = SocketAddr::new(ip, port);
+        let listener = match bind_tcp(addr, dual_stack) {
+            Ok(v) => v,
+            Err(err) => {
+                eprintln!("Failed to bind to address `{addr}`: {err}");
+                panic!("failed to bind: {err}");
+            }
+        };
+
+        tracing::info!(
+            ip = ip.to_string(),
+            port = port,
+            tls = tls.is_some(),
+            "Starting server"
+        );
+        let app = app.clone();
+        match tls.clone() {
+            Some(rustls) => servers.spawn(async move {
+                axum_server::from_tcp_rustls(listener, rustls)
+                    .serve(app.into_make_service_with_connect_info::<SocketAddr>())
+                    .await
+            }),
+            None => servers.spawn(async move {
+                let listener = tokio::net::TcpListener::from_std(listener)?;
+                axum::serve(
+                    listener,
+                    app.into_make_service_with_connect_info::<SocketAddr>(),
+                )
+                .await
+            }),
+        };
+    }
+
+    // servers run forever, so any finished one is an error
+    if let Some(res) = servers.join_next().await {
+        res.expect("server task").unwrap();
+    }
 }
@@ -12,6 +12,7 @@ async fn main() {
         .read(true)
         .write(true)
         .create(true)
+        .truncate(false)
         .open("../archk.db")
         .map(drop);
 
@@ -0,0 +1,2 @@
+ALTER TABLE service_tokens ADD COLUMN label TEXT DEFAULT NULL;
+ALTER TABLE service_tokens ADD COLUMN last_used_at INTEGER DEFAULT NULL;
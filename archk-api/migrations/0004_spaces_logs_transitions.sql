@@ -0,0 +1,5 @@
+ALTER TABLE spaces_logs ADD COLUMN ref_id TEXT DEFAULT NULL;
+ALTER TABLE spaces_logs ADD COLUMN detail TEXT DEFAULT NULL;
+
+CREATE INDEX idx_spaces_logs_ref_id ON spaces_logs(ref_id);
+CREATE INDEX idx_spaces_logs_space_act ON spaces_logs(space_id, act);
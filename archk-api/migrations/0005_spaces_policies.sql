@@ -0,0 +1,8 @@
+CREATE TABLE spaces_policies (
+    space_id TEXT NOT NULL PRIMARY KEY,
+
+    require_keycard INTEGER NOT NULL DEFAULT 1,
+    deny_on_open_reports INTEGER NOT NULL DEFAULT 0,
+
+    FOREIGN KEY(space_id) REFERENCES spaces(id) ON DELETE CASCADE
+);
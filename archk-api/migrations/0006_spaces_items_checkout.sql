@@ -0,0 +1,4 @@
+ALTER TABLE spaces_items ADD COLUMN current_holder TEXT DEFAULT NULL;
+ALTER TABLE spaces_items ADD COLUMN due_at INTEGER DEFAULT NULL;
+
+CREATE INDEX idx_spaces_items_due_at ON spaces_items(space_id, due_at);
@@ -0,0 +1,3 @@
+ALTER TABLE spaces ADD COLUMN logs_retention_days INTEGER DEFAULT NULL;
+
+CREATE INDEX idx_spaces_logs_created_at ON spaces_logs(space_id, created_at);
@@ -0,0 +1 @@
+ALTER TABLE spaces_accounts ADD COLUMN active BOOLEAN NOT NULL DEFAULT 1;
@@ -0,0 +1,2 @@
+ALTER TABLE spaces_items ADD COLUMN metadata TEXT NOT NULL DEFAULT '{}';
+ALTER TABLE spaces_accounts ADD COLUMN metadata TEXT NOT NULL DEFAULT '{}';
@@ -0,0 +1,19 @@
+CREATE TABLE spaces_tags (
+    id TEXT NOT NULL PRIMARY KEY,
+    space_id TEXT NOT NULL,
+    title TEXT NOT NULL,
+
+    UNIQUE(space_id, title),
+    FOREIGN KEY(space_id) REFERENCES spaces(id) ON DELETE CASCADE
+);
+
+CREATE TABLE spaces_items_tags (
+    item_id TEXT NOT NULL,
+    tag_id TEXT NOT NULL,
+
+    PRIMARY KEY(item_id, tag_id),
+    FOREIGN KEY(item_id) REFERENCES spaces_items(id) ON DELETE CASCADE,
+    FOREIGN KEY(tag_id) REFERENCES spaces_tags(id) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_spaces_items_tags_tag_id ON spaces_items_tags(tag_id);
@@ -0,0 +1,23 @@
+ALTER TABLE spaces ADD COLUMN version INTEGER NOT NULL DEFAULT 1;
+ALTER TABLE spaces_items ADD COLUMN version INTEGER NOT NULL DEFAULT 1;
+ALTER TABLE spaces_accounts ADD COLUMN version INTEGER NOT NULL DEFAULT 1;
+
+-- Any update of record bumps its version (unless version updated explicitly)
+CREATE TRIGGER spaces_bump_version AFTER UPDATE ON spaces
+    FOR EACH ROW WHEN NEW.version = OLD.version
+BEGIN
+    UPDATE spaces SET version = OLD.version + 1 WHERE id = NEW.id;
+END;
+
+CREATE TRIGGER spaces_items_bump_version AFTER UPDATE ON spaces_items
+    FOR EACH ROW WHEN NEW.version = OLD.version
+BEGIN
+    UPDATE spaces_items SET version = OLD.version + 1 WHERE id = NEW.id;
+END;
+
+CREATE TRIGGER spaces_accounts_bump_version AFTER UPDATE ON spaces_accounts
+    FOR EACH ROW WHEN NEW.version = OLD.version
+BEGIN
+    UPDATE spaces_accounts SET version = OLD.version + 1
+    WHERE pl_id = NEW.pl_id AND space_id = NEW.space_id;
+END;
@@ -0,0 +1,13 @@
+CREATE TABLE idempotency (
+    scope TEXT NOT NULL,
+    key TEXT NOT NULL,
+    request_hash TEXT NOT NULL,
+    created_at INTEGER NOT NULL,
+
+    status INTEGER NOT NULL,
+    response BLOB NOT NULL,
+
+    PRIMARY KEY(scope, key)
+);
+
+CREATE INDEX idx_idempotency_created_at ON idempotency(created_at);
@@ -0,0 +1,12 @@
+CREATE TABLE oidc_states (
+    state TEXT NOT NULL PRIMARY KEY,
+    invite TEXT DEFAULT NULL,
+    created_at INTEGER NOT NULL
+);
+
+CREATE TABLE oidc_identities (
+    subject TEXT NOT NULL PRIMARY KEY,
+    user_id TEXT NOT NULL,
+
+    FOREIGN KEY(user_id) REFERENCES users(id) ON DELETE CASCADE
+);
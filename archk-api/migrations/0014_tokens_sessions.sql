@@ -0,0 +1,3 @@
+ALTER TABLE tokens ADD COLUMN user_agent TEXT DEFAULT NULL;
+ALTER TABLE tokens ADD COLUMN ip TEXT DEFAULT NULL;
+ALTER TABLE tokens ADD COLUMN last_used_at INTEGER DEFAULT NULL;
@@ -0,0 +1,11 @@
+CREATE TABLE auth_failures (
+    username TEXT NOT NULL,
+    -- empty if unknown
+    ip TEXT NOT NULL,
+    failures INTEGER NOT NULL,
+    last_failure_at INTEGER NOT NULL,
+
+    PRIMARY KEY(username, ip)
+);
+
+CREATE INDEX idx_auth_failures_last_failure_at ON auth_failures(last_failure_at);
@@ -0,0 +1,14 @@
+-- no foreign keys, entries should outlive users
+CREATE TABLE audit_log (
+    id TEXT NOT NULL PRIMARY KEY,
+    created_at INTEGER NOT NULL,
+
+    actor_id TEXT DEFAULT NULL,
+    act INTEGER NOT NULL,
+    target_id TEXT DEFAULT NULL,
+    detail TEXT DEFAULT NULL
+);
+
+CREATE INDEX idx_audit_log_created_at ON audit_log(created_at);
+
+ALTER TABLE users ADD COLUMN login_locked_until INTEGER DEFAULT NULL;
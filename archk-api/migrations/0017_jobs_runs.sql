@@ -0,0 +1,4 @@
+CREATE TABLE jobs_runs (
+    name TEXT NOT NULL PRIMARY KEY,
+    last_run_at INTEGER NOT NULL
+);
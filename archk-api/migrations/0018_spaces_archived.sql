@@ -0,0 +1 @@
+ALTER TABLE spaces ADD COLUMN archived BOOLEAN NOT NULL DEFAULT 0;
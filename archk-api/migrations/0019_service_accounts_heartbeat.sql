@@ -0,0 +1,2 @@
+ALTER TABLE service_accounts ADD COLUMN last_seen_at INTEGER DEFAULT NULL;
+ALTER TABLE service_accounts ADD COLUMN status TEXT DEFAULT NULL;
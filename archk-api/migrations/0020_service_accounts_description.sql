@@ -0,0 +1 @@
+ALTER TABLE service_accounts ADD COLUMN description TEXT DEFAULT NULL;
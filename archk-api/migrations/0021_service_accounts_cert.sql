@@ -0,0 +1,3 @@
+ALTER TABLE service_accounts ADD COLUMN cert_fingerprint TEXT DEFAULT NULL;
+
+CREATE UNIQUE INDEX idx_service_accounts_cert_fingerprint ON service_accounts(cert_fingerprint);
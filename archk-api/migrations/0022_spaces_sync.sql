@@ -0,0 +1,73 @@
+-- change timestamps for `GET /space/:space_id/sync`, maintained by triggers so
+-- every write path is covered
+ALTER TABLE spaces_items ADD COLUMN updated_at INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE spaces_accounts ADD COLUMN updated_at INTEGER NOT NULL DEFAULT 0;
+
+CREATE INDEX idx_spaces_items_updated_at ON spaces_items(space_id, updated_at);
+CREATE INDEX idx_spaces_accounts_updated_at ON spaces_accounts(space_id, updated_at);
+
+CREATE TRIGGER spaces_items_inserted AFTER INSERT ON spaces_items
+BEGIN
+    UPDATE spaces_items
+    SET updated_at = CAST((julianday('now') - 2440587.5) * 86400000 AS INTEGER)
+    WHERE id = NEW.id;
+END;
+
+-- version triggers also set change timestamp, separate triggers would fire each
+-- other. Updates setting timestamp explicitly (insert triggers) keep version
+DROP TRIGGER spaces_items_bump_version;
+CREATE TRIGGER spaces_items_bump_version AFTER UPDATE ON spaces_items
+    FOR EACH ROW WHEN NEW.version = OLD.version AND NEW.updated_at = OLD.updated_at
+BEGIN
+    UPDATE spaces_items SET
+        version = OLD.version + 1,
+        updated_at = CAST((julianday('now') - 2440587.5) * 86400000 AS INTEGER)
+    WHERE id = NEW.id;
+END;
+
+CREATE TRIGGER spaces_accounts_inserted AFTER INSERT ON spaces_accounts
+BEGIN
+    UPDATE spaces_accounts
+    SET updated_at = CAST((julianday('now') - 2440587.5) * 86400000 AS INTEGER)
+    WHERE space_id = NEW.space_id AND pl_id = NEW.pl_id;
+END;
+
+DROP TRIGGER spaces_accounts_bump_version;
+CREATE TRIGGER spaces_accounts_bump_version AFTER UPDATE ON spaces_accounts
+    FOR EACH ROW WHEN NEW.version = OLD.version AND NEW.updated_at = OLD.updated_at
+BEGIN
+    UPDATE spaces_accounts SET
+        version = OLD.version + 1,
+        updated_at = CAST((julianday('now') - 2440587.5) * 86400000 AS INTEGER)
+    WHERE pl_id = NEW.pl_id AND space_id = NEW.space_id;
+END;
+
+-- no foreign keys, entries are written while space is deleted and removed by
+-- background jobs
+CREATE TABLE spaces_deletions (
+    space_id TEXT NOT NULL,
+    -- `item` or `account`
+    object_ty TEXT NOT NULL,
+    object_id TEXT NOT NULL,
+    deleted_at INTEGER NOT NULL
+);
+
+CREATE INDEX idx_spaces_deletions_deleted_at ON spaces_deletions(space_id, deleted_at);
+
+CREATE TRIGGER spaces_items_deleted AFTER DELETE ON spaces_items
+BEGIN
+    INSERT INTO spaces_deletions (space_id, object_ty, object_id, deleted_at)
+    VALUES (
+        OLD.space_id, 'item', OLD.id,
+        CAST((julianday('now') - 2440587.5) * 86400000 AS INTEGER)
+    );
+END;
+
+CREATE TRIGGER spaces_accounts_deleted AFTER DELETE ON spaces_accounts
+BEGIN
+    INSERT INTO spaces_deletions (space_id, object_ty, object_id, deleted_at)
+    VALUES (
+        OLD.space_id, 'account', OLD.pl_id,
+        CAST((julianday('now') - 2440587.5) * 86400000 AS INTEGER)
+    );
+END;
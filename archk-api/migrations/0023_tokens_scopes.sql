@@ -0,0 +1,4 @@
+-- personal tokens restricted to one space, see `PUT /user/tokens`
+ALTER TABLE tokens ADD COLUMN space_id TEXT DEFAULT NULL REFERENCES spaces(id) ON DELETE CASCADE;
+-- scopes separated by space
+ALTER TABLE tokens ADD COLUMN scopes TEXT DEFAULT NULL;
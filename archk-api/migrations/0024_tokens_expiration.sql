@@ -0,0 +1,3 @@
+-- short-lived tokens issued by `POST /user/@:user_id/impersonate`
+ALTER TABLE tokens ADD COLUMN expires_at INTEGER DEFAULT NULL;
+ALTER TABLE tokens ADD COLUMN impersonated_by TEXT DEFAULT NULL;
@@ -0,0 +1,30 @@
+CREATE TABLE spaces_attachments (
+    id TEXT NOT NULL PRIMARY KEY,
+    space_id TEXT NOT NULL,
+    item_id TEXT NOT NULL,
+    name TEXT NOT NULL,
+    mime TEXT NOT NULL,
+    size INTEGER NOT NULL,
+    created_at INTEGER NOT NULL,
+
+    FOREIGN KEY(space_id) REFERENCES spaces(id) ON DELETE CASCADE,
+    FOREIGN KEY(item_id) REFERENCES spaces_items(id) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_spaces_attachments_item_id ON spaces_attachments(item_id);
+
+-- files of deleted attachments (including removed with items and spaces) waiting
+-- for removal from storage by background jobs
+CREATE TABLE attachments_deletions (
+    storage_key TEXT NOT NULL PRIMARY KEY,
+    deleted_at INTEGER NOT NULL
+);
+
+CREATE TRIGGER spaces_attachments_deleted AFTER DELETE ON spaces_attachments
+BEGIN
+    INSERT OR IGNORE INTO attachments_deletions(storage_key, deleted_at)
+    VALUES (
+        OLD.space_id || '/' || OLD.id,
+        CAST((julianday('now') - 2440587.5) * 86400000 AS INTEGER)
+    );
+END;
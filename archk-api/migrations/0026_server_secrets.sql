@@ -0,0 +1,6 @@
+-- random keys generated on first use, eg. `share` key signing links of
+-- `POST /space/:space_id/item/:item_id/share`
+CREATE TABLE server_secrets (
+    name TEXT NOT NULL PRIMARY KEY,
+    value BLOB NOT NULL
+);
@@ -0,0 +1,13 @@
+CREATE TABLE users_notifications (
+    user_id TEXT NOT NULL PRIMARY KEY,
+
+    telegram_chat_id TEXT DEFAULT NULL,
+    email TEXT DEFAULT NULL,
+    webhook_url TEXT DEFAULT NULL,
+
+    report_filed INTEGER NOT NULL DEFAULT 1,
+    unlock_denied INTEGER NOT NULL DEFAULT 1,
+    item_overdue INTEGER NOT NULL DEFAULT 1,
+
+    FOREIGN KEY(user_id) REFERENCES users(id) ON DELETE CASCADE
+);
@@ -0,0 +1,9 @@
+CREATE TABLE users_telegram_auths (
+    id TEXT NOT NULL PRIMARY KEY,
+    user_id TEXT NOT NULL,
+    issued_at INTEGER NOT NULL,
+
+    FOREIGN KEY(user_id) REFERENCES users(id) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_users_telegram_auths_user_id ON users_telegram_auths(user_id);
@@ -0,0 +1,17 @@
+CREATE TABLE spaces_logs_comments (
+    id TEXT NOT NULL PRIMARY KEY,
+    space_id TEXT NOT NULL,
+    log_id TEXT NOT NULL,
+    created_at INTEGER NOT NULL,
+    -- author, exactly one of them is set until author is deleted
+    user_id TEXT,
+    service_id TEXT,
+    text TEXT NOT NULL,
+
+    FOREIGN KEY(space_id) REFERENCES spaces(id) ON DELETE CASCADE,
+    FOREIGN KEY(log_id) REFERENCES spaces_logs(id) ON DELETE CASCADE,
+    FOREIGN KEY(user_id) REFERENCES users(id) ON DELETE SET NULL,
+    FOREIGN KEY(service_id) REFERENCES service_accounts(id) ON DELETE SET NULL
+);
+
+CREATE INDEX idx_spaces_logs_comments_log_id ON spaces_logs_comments(log_id, created_at);
@@ -0,0 +1,11 @@
+-- unlock allow and deny lists, accounts without row follow unlock policy
+CREATE TABLE spaces_accounts_access (
+    space_id TEXT NOT NULL,
+    pl_id TEXT NOT NULL,
+    allow BOOLEAN NOT NULL,
+    reason TEXT DEFAULT NULL,
+    updated_at INTEGER NOT NULL,
+
+    PRIMARY KEY(space_id, pl_id),
+    FOREIGN KEY(pl_id, space_id) REFERENCES spaces_accounts(pl_id, space_id) ON DELETE CASCADE
+);
@@ -0,0 +1,18 @@
+-- weekly windows when unlock is allowed, for one account (`pl_id`) or whole space
+CREATE TABLE spaces_access_windows (
+    id TEXT NOT NULL PRIMARY KEY,
+    space_id TEXT NOT NULL,
+    pl_id TEXT DEFAULT NULL,
+    -- bit N set for N-th day of week starting from Monday
+    days INTEGER NOT NULL,
+    -- minutes since local midnight
+    start_min INTEGER NOT NULL,
+    end_min INTEGER NOT NULL,
+    timezone TEXT NOT NULL,
+    created_at INTEGER NOT NULL,
+
+    FOREIGN KEY(space_id) REFERENCES spaces(id) ON DELETE CASCADE,
+    FOREIGN KEY(pl_id, space_id) REFERENCES spaces_accounts(pl_id, space_id) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_spaces_access_windows_space_id ON spaces_access_windows(space_id, pl_id);
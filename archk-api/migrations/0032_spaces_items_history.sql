@@ -0,0 +1,17 @@
+-- changes of item fields made by `PATCH /space/:space_id/item/:item_id`, one row per field
+CREATE TABLE spaces_items_history (
+    id TEXT NOT NULL PRIMARY KEY,
+    space_id TEXT NOT NULL,
+    item_id TEXT NOT NULL,
+    changed_at INTEGER NOT NULL,
+    user_id TEXT DEFAULT NULL,
+    field TEXT NOT NULL,
+    old_value TEXT DEFAULT NULL,
+    new_value TEXT DEFAULT NULL,
+
+    FOREIGN KEY(space_id) REFERENCES spaces(id) ON DELETE CASCADE,
+    FOREIGN KEY(item_id) REFERENCES spaces_items(id) ON DELETE CASCADE,
+    FOREIGN KEY(user_id) REFERENCES users(id) ON DELETE SET NULL
+);
+
+CREATE INDEX idx_spaces_items_history_item_id ON spaces_items_history(item_id, changed_at);
@@ -0,0 +1,46 @@
+-- tokens are looked up by SHA-256 of token string, `rnd` is not stored anymore.
+-- Tokens issued before keep `rnd` and get `hash` (and lose `rnd`) on first use.
+CREATE TABLE tokens_hashed (
+    iat INTEGER NOT NULL,
+    rnd INTEGER DEFAULT NULL,
+    hash BLOB DEFAULT NULL UNIQUE,
+
+    user_id TEXT NOT NULL,
+    user_agent TEXT DEFAULT NULL,
+    ip TEXT DEFAULT NULL,
+    last_used_at INTEGER DEFAULT NULL,
+    space_id TEXT DEFAULT NULL REFERENCES spaces(id) ON DELETE CASCADE,
+    scopes TEXT DEFAULT NULL,
+    expires_at INTEGER DEFAULT NULL,
+    impersonated_by TEXT DEFAULT NULL,
+
+    UNIQUE(iat, rnd),
+    FOREIGN KEY(user_id) REFERENCES users(id) ON DELETE CASCADE
+);
+INSERT INTO tokens_hashed(
+    iat, rnd, user_id, user_agent, ip, last_used_at, space_id, scopes, expires_at,
+    impersonated_by
+)
+SELECT
+    iat, rnd, user_id, user_agent, ip, last_used_at, space_id, scopes, expires_at,
+    impersonated_by
+FROM tokens;
+DROP TABLE tokens;
+ALTER TABLE tokens_hashed RENAME TO tokens;
+
+CREATE TABLE service_tokens_hashed (
+    iat INTEGER NOT NULL,
+    rnd INTEGER DEFAULT NULL,
+    hash BLOB DEFAULT NULL UNIQUE,
+
+    service_id TEXT NOT NULL,
+    label TEXT DEFAULT NULL,
+    last_used_at INTEGER DEFAULT NULL,
+
+    UNIQUE(iat, rnd),
+    FOREIGN KEY(service_id) REFERENCES service_accounts(id) ON DELETE CASCADE
+);
+INSERT INTO service_tokens_hashed(iat, rnd, service_id, label, last_used_at)
+SELECT iat, rnd, service_id, label, last_used_at FROM service_tokens;
+DROP TABLE service_tokens;
+ALTER TABLE service_tokens_hashed RENAME TO service_tokens;
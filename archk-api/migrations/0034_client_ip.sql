@@ -0,0 +1,3 @@
+-- address of client resolved through trusted proxies
+ALTER TABLE audit_log ADD COLUMN ip TEXT DEFAULT NULL;
+ALTER TABLE service_tokens ADD COLUMN last_used_ip TEXT DEFAULT NULL;
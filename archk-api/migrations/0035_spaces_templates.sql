@@ -0,0 +1,2 @@
+-- templates can be duplicated by any user allowed to create spaces
+ALTER TABLE spaces ADD COLUMN template BOOLEAN NOT NULL DEFAULT 0;
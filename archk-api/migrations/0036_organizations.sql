@@ -0,0 +1,21 @@
+CREATE TABLE organizations (
+    id TEXT NOT NULL PRIMARY KEY,
+    title TEXT NOT NULL,
+    created_at INTEGER NOT NULL
+);
+
+-- `role` is `archk::v1::org::OrgRole`
+CREATE TABLE organizations_members (
+    org_id TEXT NOT NULL,
+    user_id TEXT NOT NULL,
+    role INTEGER NOT NULL DEFAULT 0,
+
+    PRIMARY KEY(org_id, user_id),
+    FOREIGN KEY(org_id) REFERENCES organizations(id) ON DELETE CASCADE,
+    FOREIGN KEY(user_id) REFERENCES users(id) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_organizations_members_user_id ON organizations_members(user_id);
+
+-- space still has owner, organization admins get access to it
+ALTER TABLE spaces ADD COLUMN org_id TEXT DEFAULT NULL REFERENCES organizations(id) ON DELETE SET NULL;
@@ -0,0 +1,7 @@
+-- read-only mode of API, enabled while table has a row, see `POST /admin/maintenance`
+CREATE TABLE maintenance (
+    id INTEGER NOT NULL PRIMARY KEY CHECK (id = 0),
+    message TEXT DEFAULT NULL,
+    enabled_at INTEGER NOT NULL,
+    enabled_by TEXT DEFAULT NULL REFERENCES users(id) ON DELETE SET NULL
+);
@@ -0,0 +1,13 @@
+-- item status, one of statuses of space (see `spaces_items_statuses`)
+ALTER TABLE spaces_items ADD COLUMN status TEXT NOT NULL DEFAULT 'available';
+
+CREATE INDEX idx_spaces_items_status ON spaces_items(space_id, status);
+
+-- item statuses of space as JSON array of `ItemStatus`, spaces without row use
+-- default statuses
+CREATE TABLE spaces_items_statuses (
+    space_id TEXT NOT NULL PRIMARY KEY,
+    statuses TEXT NOT NULL,
+
+    FOREIGN KEY(space_id) REFERENCES spaces(id) ON DELETE CASCADE
+);
@@ -0,0 +1,17 @@
+-- future-dated reservations of items for accounts, see `PUT /space/:space_id/item/:item_id/reservations`
+CREATE TABLE spaces_items_reservations (
+    id TEXT NOT NULL PRIMARY KEY,
+    space_id TEXT NOT NULL,
+    item_id TEXT NOT NULL,
+    pl_id TEXT NOT NULL,
+    -- timestamps in milliseconds, `end_at` is exclusive
+    start_at INTEGER NOT NULL,
+    end_at INTEGER NOT NULL,
+    note TEXT DEFAULT NULL,
+    created_at INTEGER NOT NULL,
+
+    FOREIGN KEY(item_id) REFERENCES spaces_items(id) ON DELETE CASCADE,
+    FOREIGN KEY(pl_id, space_id) REFERENCES spaces_accounts(pl_id, space_id) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_spaces_items_reservations_item_id ON spaces_items_reservations(item_id, start_at);
@@ -0,0 +1,3 @@
+-- signed into calendar feed tokens, incremented to revoke issued tokens
+-- (`DELETE /space/:space_id/calendar/token`)
+ALTER TABLE spaces ADD COLUMN calendar_nonce INTEGER NOT NULL DEFAULT 0;
@@ -0,0 +1,24 @@
+-- at most one decision of unlock request and one resolution of report, so concurrent
+-- managers can't decide same entry twice. Duplicates left by such races before are
+-- kept in history, but marked as superseded by the first decision
+ALTER TABLE spaces_logs ADD COLUMN superseded BOOLEAN NOT NULL DEFAULT 0;
+
+UPDATE spaces_logs SET superseded = 1
+WHERE act IN (501, 502) AND ref_id IS NOT NULL AND rowid != (
+    SELECT first.rowid FROM spaces_logs AS first
+    WHERE first.act IN (501, 502) AND first.ref_id = spaces_logs.ref_id
+    ORDER BY first.created_at, first.rowid
+    LIMIT 1
+);
+UPDATE spaces_logs SET superseded = 1
+WHERE act = 601 AND ref_id IS NOT NULL AND rowid != (
+    SELECT first.rowid FROM spaces_logs AS first
+    WHERE first.act = 601 AND first.ref_id = spaces_logs.ref_id
+    ORDER BY first.created_at, first.rowid
+    LIMIT 1
+);
+
+CREATE UNIQUE INDEX idx_spaces_logs_unlock_decisions ON spaces_logs(ref_id)
+    WHERE act IN (501, 502) AND NOT superseded;
+CREATE UNIQUE INDEX idx_spaces_logs_report_resolutions ON spaces_logs(ref_id)
+    WHERE act = 601 AND NOT superseded;
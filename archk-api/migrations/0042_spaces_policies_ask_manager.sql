@@ -0,0 +1,2 @@
+-- leave unlock requests failing keycard or open reports checks to `SpaceManager` services
+ALTER TABLE spaces_policies ADD COLUMN ask_manager INTEGER NOT NULL DEFAULT 0;
@@ -1,17 +1,221 @@
-use std::net::Ipv4Addr;
+use std::{
+    net::IpAddr,
+    path::PathBuf,
+    str::FromStr,
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
 
+use arc_swap::ArcSwap;
+use archk::v1::auth::TokenFormat;
+use axum::http::{
+    header::{InvalidHeaderName, AUTHORIZATION, CONTENT_TYPE, ETAG, IF_MATCH, IF_NONE_MATCH},
+    HeaderName, HeaderValue, Method,
+};
+use ipnet::IpNet;
 use serde::Deserialize;
-use sqlx::SqlitePool;
+use sqlx::{
+    sqlite::{self, SqliteConnectOptions, SqlitePoolOptions},
+    SqlitePool,
+};
+use tower_http::cors::{AllowHeaders, AllowMethods, AllowOrigin, CorsLayer};
 
-use crate::roles::UserRoles;
+use crate::{
+    cache::Cache,
+    notify::Notifier,
+    oidc::Oidc,
+    roles::UserRoles,
+    storage::Attachments,
+    v1::idempotency::{IDEMPOTENCY_KEY, IDEMPOTENCY_REPLAYED},
+};
 
 /// Default bcrypt cost for passwords
-pub(crate) const BCRYPT_COST: u32 = 13;
+pub const BCRYPT_COST: u32 = 13;
 
 #[derive(Deserialize)]
 pub struct AppConfig {
     /// Server config
     pub server: AppConfigServer,
+
+    /// Authentication config
+    #[serde(default)]
+    pub auth: AppConfigAuth,
+
+    /// Initial data of empty database, see [`crate::bootstrap`]
+    #[serde(default)]
+    pub bootstrap: Option<AppConfigBootstrap>,
+}
+
+/// Data created on first run against empty database
+#[derive(Deserialize, Clone)]
+pub struct AppConfigBootstrap {
+    /// Initial admin
+    pub admin: AppConfigBootstrapAdmin,
+    /// Number of invites admin can create
+    #[serde(default)]
+    pub invites: i64,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct AppConfigBootstrapAdmin {
+    pub username: String,
+    /// Bcrypt hash of password, eg. `$2y$13$...`
+    pub password_hash: String,
+    /// Name of role, highest one by default
+    #[serde(default)]
+    pub role: Option<String>,
+}
+
+#[derive(Deserialize, Default)]
+pub struct AppConfigAuth {
+    /// Login with external OpenID Connect provider
+    #[serde(default)]
+    pub oidc: Option<AppConfigAuthOidc>,
+
+    /// Lockout after failed logins
+    #[serde(default)]
+    pub lockout: AppConfigAuthLockout,
+
+    /// Format of issued tokens, `v2` by default. Tokens of both formats are accepted
+    #[serde(default)]
+    pub token_format: TokenFormat,
+
+    /// Authentication by trusted reverse proxy
+    #[serde(default)]
+    pub proxy: Option<AppConfigAuthProxy>,
+}
+
+/// Lockout of username from IP after failed logins. Each failure after `max_failures`
+/// doubles lockout until `max_lockout_secs`. Failures are forgotten after
+/// `max_lockout_secs` without new ones.
+#[derive(Deserialize, Clone, Copy)]
+pub struct AppConfigAuthLockout {
+    /// Number of failed logins before lockout. `0` disables lockout
+    #[serde(default = "default_lockout_max_failures")]
+    pub max_failures: i64,
+    /// First lockout duration in seconds
+    #[serde(default = "default_lockout_secs")]
+    pub lockout_secs: i64,
+    /// Maximum lockout duration in seconds
+    #[serde(default = "default_max_lockout_secs")]
+    pub max_lockout_secs: i64,
+}
+
+impl Default for AppConfigAuthLockout {
+    fn default() -> Self {
+        Self {
+            max_failures: default_lockout_max_failures(),
+            lockout_secs: default_lockout_secs(),
+            max_lockout_secs: default_max_lockout_secs(),
+        }
+    }
+}
+
+impl AppConfigAuthLockout {
+    /// Lockout duration in milliseconds after `failures` failed logins, if any.
+    pub fn lockout_ms(&self, failures: i64) -> Option<i64> {
+        if self.max_failures <= 0 || failures < self.max_failures {
+            return None;
+        }
+        let exp = (failures - self.max_failures).min(32) as u32;
+        let secs = self
+            .lockout_secs
+            .saturating_mul(1 << exp)
+            .min(self.max_lockout_secs);
+        Some(secs * 1000)
+    }
+}
+
+fn default_lockout_max_failures() -> i64 {
+    5
+}
+
+fn default_lockout_secs() -> i64 {
+    30
+}
+
+fn default_max_lockout_secs() -> i64 {
+    60 * 60
+}
+
+/// Authentication by reverse proxy (eg. oauth2-proxy, Authelia). Requests without
+/// bearer token coming from `trusted_proxies` are authenticated as existing user named
+/// in `header`. Proxy must strip this header from client requests.
+#[derive(Deserialize)]
+pub struct AppConfigAuthProxy {
+    /// Header with username
+    #[serde(default = "default_proxy_header")]
+    pub header: String,
+    /// Networks of proxies, eg. `10.0.0.0/8`. Requests on unix socket have no client
+    /// address and are always trusted
+    pub trusted_proxies: Vec<IpNet>,
+}
+
+fn default_proxy_header() -> String {
+    "X-Forwarded-User".into()
+}
+
+/// Parsed [`AppConfigAuthProxy`].
+#[derive(Debug)]
+pub struct ProxyAuth {
+    pub header: HeaderName,
+    pub trusted_proxies: Vec<IpNet>,
+}
+
+impl ProxyAuth {
+    pub fn new(config: AppConfigAuthProxy) -> Result<Self, InvalidHeaderName> {
+        Ok(Self {
+            header: HeaderName::try_from(config.header)?,
+            trusted_proxies: config.trusted_proxies,
+        })
+    }
+
+    /// Is request from `addr` (`None` on unix socket) coming from trusted proxy?
+    pub fn trusts(&self, addr: Option<IpAddr>) -> bool {
+        addr.is_none_or(|addr| is_trusted(&self.trusted_proxies, addr))
+    }
+}
+
+/// Is `addr` in one of `networks`? IPv4-mapped IPv6 addresses are matched as IPv4.
+pub fn is_trusted(networks: &[IpNet], addr: IpAddr) -> bool {
+    let addr = match addr {
+        IpAddr::V6(v) => v.to_ipv4_mapped().map_or(addr, IpAddr::V4),
+        v4 => v4,
+    };
+    networks.iter().any(|net| net.contains(&addr))
+}
+
+#[derive(Deserialize)]
+pub struct AppConfigAuthOidc {
+    /// Authorization endpoint of provider, eg. `https://id.example.com/authorize`
+    pub authorization_endpoint: String,
+    /// Token endpoint of provider, eg. `https://id.example.com/token`
+    pub token_endpoint: String,
+    /// Userinfo endpoint of provider, eg. `https://id.example.com/userinfo`
+    pub userinfo_endpoint: String,
+    /// Client ID registered at provider
+    pub client_id: String,
+    /// Client secret registered at provider
+    pub client_secret: String,
+    /// Public URL of `/api/v1/auth/oidc/callback` registered at provider
+    pub redirect_url: String,
+    /// Requested scopes
+    #[serde(default = "default_oidc_scopes")]
+    pub scopes: Vec<String>,
+    /// Userinfo claim used as username of new users
+    #[serde(default = "default_oidc_username_claim")]
+    pub username_claim: String,
+    /// Register new users on first login without invite
+    #[serde(default)]
+    pub auto_register: bool,
+}
+
+fn default_oidc_scopes() -> Vec<String> {
+    vec!["openid".into(), "profile".into()]
+}
+
+fn default_oidc_username_claim() -> String {
+    "preferred_username".into()
 }
 
 #[derive(Deserialize)]
@@ -22,14 +226,593 @@ pub struct AppConfigServer {
     /// Database url
     pub database: String,
 
+    /// Url of read-only replica of database (eg. on LiteFS), used by listings and logs
+    #[serde(default)]
+    pub database_read: Option<String>,
+
+    /// Connection pool and pragmas of database
+    #[serde(default)]
+    pub sqlite: AppConfigServerSqlite,
+
+    /// Apply pending migrations on start, disabled by default: server refuses to start
+    /// if database has pending migrations, see [`crate::check_migrations`]
+    #[serde(default)]
+    pub auto_migrate: bool,
+
     /// User roles
     pub roles: UserRoles,
+
+    /// Serve HTTPS with given certificate instead of plain HTTP
+    #[serde(default)]
+    pub tls: Option<AppConfigServerTls>,
+
+    /// Allow cross-origin requests from browsers
+    #[serde(default)]
+    pub cors: Option<AppConfigServerCors>,
+
+    /// Gzip responses for clients accepting it by `tower_http` compression layer.
+    /// Disabled if not set
+    #[serde(default)]
+    pub compression: Option<AppConfigServerCompression>,
+
+    /// Maximum size of request body in bytes
+    #[serde(default = "default_body_limit")]
+    pub body_limit: usize,
+
+    /// Run invite waves automatically
+    #[serde(default)]
+    pub invite_waves: Option<AppConfigServerInviteWaves>,
+
+    /// Limits of service accounts
+    #[serde(default)]
+    pub services: AppConfigServerServices,
+
+    /// In-memory cache of tokens and owners of spaces, see [`crate::cache`]
+    #[serde(default)]
+    pub cache: AppConfigServerCache,
+
+    /// Header with SHA-256 fingerprint of client certificate set by TLS terminating
+    /// proxy, eg. `X-Client-Cert-Fingerprint`. Services bound to certificate are
+    /// authenticated by it without bearer token. Header is accepted only on unix socket
+    /// and from `trusted_proxies`, which must strip it from client requests
+    #[serde(default)]
+    pub client_cert_header: Option<String>,
+
+    /// Networks of reverse proxies, eg. `10.0.0.0/8`. Client address is taken from
+    /// `Forwarded` or `X-Forwarded-For` headers of requests coming from them
+    #[serde(default)]
+    pub trusted_proxies: Vec<IpNet>,
+
+    /// Bridge space events to MQTT broker, see [`crate::v1::mqtt`]
+    #[serde(default)]
+    pub mqtt: Option<AppConfigServerMqtt>,
+
+    /// File attachments of items, disabled if not set
+    #[serde(default)]
+    pub attachments: Option<AppConfigServerAttachments>,
+
+    /// Notifications of users about space events, see [`crate::notify`]. Disabled
+    /// if not set
+    #[serde(default)]
+    pub notifications: Option<AppConfigServerNotifications>,
+
+    /// Export traces of requests and database queries over OTLP, see
+    /// [`crate::telemetry`]. Disabled if not set
+    #[serde(default)]
+    pub tracing: Option<AppConfigServerTracing>,
+}
+
+/// OpenTelemetry collector receiving traces, eg. Jaeger or Tempo
+#[derive(Deserialize, Clone)]
+pub struct AppConfigServerTracing {
+    /// OTLP/HTTP traces endpoint, eg. `http://localhost:4318/v1/traces`
+    pub otlp_endpoint: String,
+    /// Name of service in traces, `archk-api` by default
+    #[serde(default = "default_tracing_service_name")]
+    pub service_name: String,
+}
+
+fn default_tracing_service_name() -> String {
+    "archk-api".into()
+}
+
+/// Channels of notifications. Users can choose only configured channels
+#[derive(Deserialize, Clone)]
+pub struct AppConfigServerNotifications {
+    /// Messages from Telegram bot to chat ID set by user
+    #[serde(default)]
+    pub telegram: Option<AppConfigNotificationsTelegram>,
+    /// Emails sent via SMTP relay
+    #[serde(default)]
+    pub email: Option<AppConfigNotificationsEmail>,
+    /// JSON `POST` requests to URL set by user. Server must be able to reach only
+    /// URLs users are allowed to notify
+    #[serde(default)]
+    pub webhooks: bool,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct AppConfigNotificationsTelegram {
+    /// Bot token given by @BotFather
+    pub bot_token: String,
+    /// Bot API server, `https://api.telegram.org` by default
+    #[serde(default = "default_telegram_api_url")]
+    pub api_url: String,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct AppConfigNotificationsEmail {
+    /// SMTP relay, `host:port`. TLS is not supported, so it should be local relay
+    /// (eg. postfix) forwarding mail further
+    pub smtp: String,
+    /// Sender address, eg. `archk@example.com`
+    pub from: String,
+    /// Username if relay requires authentication (`AUTH PLAIN`)
+    #[serde(default)]
+    pub username: Option<String>,
+    /// Password if relay requires authentication
+    #[serde(default)]
+    pub password: Option<String>,
+}
+
+fn default_telegram_api_url() -> String {
+    "https://api.telegram.org".into()
+}
+
+#[derive(Deserialize, Clone)]
+pub struct AppConfigServerAttachments {
+    /// Where files are stored
+    pub storage: AppConfigStorage,
+    /// Maximum size of file in bytes. Not limited by `body_limit`
+    #[serde(default = "default_attachments_max_size")]
+    pub max_size: usize,
+    /// Allowed MIME types of files. `type/*` allows any subtype and `*` allows any type
+    #[serde(default = "default_attachments_mime_types")]
+    pub mime_types: Vec<String>,
+    /// Maximum number of attachments of one item. `0` disables limit
+    #[serde(default = "default_attachments_max_per_item")]
+    pub max_per_item: u64,
+}
+
+/// Storage backend of attachments, see [`crate::storage::Storage`]
+#[derive(Deserialize, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AppConfigStorage {
+    /// Files in local directory
+    Local {
+        /// Directory of files, created if not exists
+        path: PathBuf,
+    },
+    /// Bucket of S3-compatible storage (AWS S3, MinIO, Garage, etc...)
+    S3 {
+        /// Endpoint URL, eg. `https://s3.eu-central-1.amazonaws.com`
+        endpoint: String,
+        /// Bucket name
+        bucket: String,
+        /// Region used in request signatures, `us-east-1` by default
+        #[serde(default = "default_s3_region")]
+        region: String,
+        /// Access key ID
+        access_key: String,
+        /// Secret access key
+        secret_key: String,
+        /// Address bucket as `<endpoint>/<bucket>` instead of `<bucket>.<endpoint host>`.
+        /// Enabled by default, most self-hosted storages support only this style
+        #[serde(default = "default_s3_path_style")]
+        path_style: bool,
+    },
+}
+
+fn default_attachments_max_size() -> usize {
+    16 * 1024 * 1024
+}
+
+fn default_attachments_mime_types() -> Vec<String> {
+    vec![
+        "image/*".into(),
+        "application/pdf".into(),
+        "text/plain".into(),
+    ]
+}
+
+fn default_attachments_max_per_item() -> u64 {
+    16
+}
+
+fn default_s3_region() -> String {
+    "us-east-1".into()
+}
+
+fn default_s3_path_style() -> bool {
+    true
+}
+
+#[derive(Deserialize, Clone)]
+pub struct AppConfigServerMqtt {
+    /// Broker URL, eg. `mqtt://broker.local:1883`. TLS is not supported
+    pub broker: String,
+    /// Prefix of topics, `archk` by default
+    #[serde(default = "default_mqtt_topic_prefix")]
+    pub topic_prefix: String,
+    /// Client ID, `archk` by default
+    #[serde(default = "default_mqtt_client_id")]
+    pub client_id: String,
+    /// Username if broker requires authentication
+    #[serde(default)]
+    pub username: Option<String>,
+    /// Password if broker requires authentication
+    #[serde(default)]
+    pub password: Option<String>,
+}
+
+impl AppConfigServerMqtt {
+    /// Address of broker to connect, `host:port`. `Err` contains description of
+    /// invalid URL.
+    pub fn address(&self) -> Result<String, String> {
+        let v = match self.broker.split_once("://") {
+            Some(("mqtt" | "tcp", v)) => v,
+            Some((scheme, _)) => {
+                return Err(format!(
+                    "unsupported scheme `{scheme}` of mqtt broker, expected `mqtt://`"
+                ))
+            }
+            None => &self.broker,
+        };
+        let v = v.trim_end_matches('/');
+        if v.is_empty() {
+            return Err("empty address of mqtt broker".into());
+        }
+
+        let has_port = match v.rfind(']') {
+            Some(i) => v[i..].contains(':'),
+            None => v.contains(':'),
+        };
+        Ok(if has_port {
+            v.to_owned()
+        } else {
+            format!("{v}:1883")
+        })
+    }
+}
+
+fn default_mqtt_topic_prefix() -> String {
+    "archk".into()
+}
+
+fn default_mqtt_client_id() -> String {
+    "archk".into()
+}
+
+/// Connection pool and pragmas applied to every connection of database.
+#[derive(Deserialize, Clone, Copy)]
+pub struct AppConfigServerSqlite {
+    /// Maximum number of connections in pool
+    #[serde(default = "default_sqlite_max_connections")]
+    pub max_connections: u32,
+    /// How long to wait for lock of database held by other connection, in milliseconds
+    #[serde(default = "default_sqlite_busy_timeout_ms")]
+    pub busy_timeout_ms: u64,
+    /// `PRAGMA journal_mode`, `wal` by default so readers don't block writer
+    #[serde(default = "default_sqlite_journal_mode")]
+    pub journal_mode: SqliteJournalMode,
+    /// `PRAGMA synchronous`, `normal` by default which is safe with `wal`
+    #[serde(default = "default_sqlite_synchronous")]
+    pub synchronous: SqliteSynchronous,
+}
+
+#[derive(Deserialize, Clone, Copy, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum SqliteJournalMode {
+    Delete,
+    Truncate,
+    Persist,
+    Memory,
+    Wal,
+    Off,
+}
+
+#[derive(Deserialize, Clone, Copy, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum SqliteSynchronous {
+    Off,
+    Normal,
+    Full,
+    Extra,
+}
+
+impl Default for AppConfigServerSqlite {
+    fn default() -> Self {
+        Self {
+            max_connections: default_sqlite_max_connections(),
+            busy_timeout_ms: default_sqlite_busy_timeout_ms(),
+            journal_mode: default_sqlite_journal_mode(),
+            synchronous: default_sqlite_synchronous(),
+        }
+    }
+}
+
+impl AppConfigServerSqlite {
+    /// Connect to database `url` with these options.
+    pub async fn connect(&self, url: &str) -> Result<SqlitePool, sqlx::Error> {
+        let options = SqliteConnectOptions::from_str(url)?
+            .journal_mode(match self.journal_mode {
+                SqliteJournalMode::Delete => sqlite::SqliteJournalMode::Delete,
+                SqliteJournalMode::Truncate => sqlite::SqliteJournalMode::Truncate,
+                SqliteJournalMode::Persist => sqlite::SqliteJournalMode::Persist,
+                SqliteJournalMode::Memory => sqlite::SqliteJournalMode::Memory,
+                SqliteJournalMode::Wal => sqlite::SqliteJournalMode::Wal,
+                SqliteJournalMode::Off => sqlite::SqliteJournalMode::Off,
+            })
+            .synchronous(match self.synchronous {
+                SqliteSynchronous::Off => sqlite::SqliteSynchronous::Off,
+                SqliteSynchronous::Normal => sqlite::SqliteSynchronous::Normal,
+                SqliteSynchronous::Full => sqlite::SqliteSynchronous::Full,
+                SqliteSynchronous::Extra => sqlite::SqliteSynchronous::Extra,
+            });
+        self.pool(options).await
+    }
+
+    /// Connect to read-only replica `url`. Journal mode and synchronous are left to
+    /// primary, as they cannot be changed without writing.
+    pub async fn connect_read_only(&self, url: &str) -> Result<SqlitePool, sqlx::Error> {
+        let options = SqliteConnectOptions::from_str(url)?.read_only(true);
+        self.pool(options).await
+    }
+
+    async fn pool(&self, options: SqliteConnectOptions) -> Result<SqlitePool, sqlx::Error> {
+        SqlitePoolOptions::new()
+            .max_connections(self.max_connections)
+            .connect_with(options.busy_timeout(Duration::from_millis(self.busy_timeout_ms)))
+            .await
+    }
+}
+
+fn default_sqlite_max_connections() -> u32 {
+    10
+}
+
+fn default_sqlite_busy_timeout_ms() -> u64 {
+    5000
+}
+
+fn default_sqlite_journal_mode() -> SqliteJournalMode {
+    SqliteJournalMode::Wal
+}
+
+fn default_sqlite_synchronous() -> SqliteSynchronous {
+    SqliteSynchronous::Normal
+}
+
+/// Limits of service accounts. `0` disables limit.
+#[derive(Deserialize, Clone, Copy)]
+pub struct AppConfigServerServices {
+    /// Maximum number of tokens of one service
+    #[serde(default = "default_max_tokens_per_service")]
+    pub max_tokens_per_service: u64,
+    /// Maximum number of services bound to one space
+    #[serde(default = "default_max_services_per_space")]
+    pub max_services_per_space: u64,
+}
+
+impl Default for AppConfigServerServices {
+    fn default() -> Self {
+        Self {
+            max_tokens_per_service: default_max_tokens_per_service(),
+            max_services_per_space: default_max_services_per_space(),
+        }
+    }
+}
+
+fn default_max_tokens_per_service() -> u64 {
+    16
+}
+
+fn default_max_services_per_space() -> u64 {
+    32
+}
+
+/// In-memory cache of hot lookups. `ttl_secs: 0` disables cache.
+#[derive(Deserialize, Clone, Copy)]
+pub struct AppConfigServerCache {
+    /// How long entries are kept, in seconds
+    #[serde(default = "default_cache_ttl_secs")]
+    pub ttl_secs: u64,
+    /// Maximum number of entries of each kind
+    #[serde(default = "default_cache_max_entries")]
+    pub max_entries: usize,
+}
+
+impl Default for AppConfigServerCache {
+    fn default() -> Self {
+        Self {
+            ttl_secs: default_cache_ttl_secs(),
+            max_entries: default_cache_max_entries(),
+        }
+    }
+}
+
+fn default_cache_ttl_secs() -> u64 {
+    30
+}
+
+fn default_cache_max_entries() -> usize {
+    10000
+}
+
+#[derive(Deserialize, Clone, Copy)]
+pub struct AppConfigServerInviteWaves {
+    /// Interval between waves in hours. Waves are checked by hourly background jobs
+    pub interval_hours: u64,
+    /// Minimum level of users getting invites
+    #[serde(default)]
+    pub min_level: i64,
+}
+
+fn default_body_limit() -> usize {
+    2 * 1024 * 1024
+}
+
+/// Gzip compression of text responses, including streamed exports. Server-sent events
+/// are never compressed.
+#[derive(Deserialize, Clone)]
+pub struct AppConfigServerCompression {
+    /// Minimum size of response body in bytes to compress, larger values are treated as
+    /// 65535. Streamed bodies of unknown size are always compressed
+    #[serde(default = "default_compression_min_size")]
+    pub min_size: usize,
+    /// Endpoints not compressed, eg. `/space/:space_id/items`. Paths are relative
+    /// to `/api/v1`, `:param` segments match any segment and nested paths match too
+    #[serde(default)]
+    pub exclude: Vec<String>,
+}
+
+impl AppConfigServerCompression {
+    /// Whether `path` relative to `/api/v1` is in [`Self::exclude`].
+    pub fn excludes(&self, path: &str) -> bool {
+        self.exclude.iter().any(|pattern| {
+            let mut segments = path.trim_matches('/').split('/');
+            pattern.trim_matches('/').split('/').all(|v| {
+                segments
+                    .next()
+                    .is_some_and(|s| v.starts_with(':') || v == s)
+            })
+        })
+    }
+}
+
+fn default_compression_min_size() -> usize {
+    1024
+}
+
+#[derive(Deserialize)]
+pub struct AppConfigServerCors {
+    /// Allowed origins, eg. `https://dashboard.example.com`. `*` allows any origin
+    pub origins: Vec<String>,
+    /// Allowed request headers. `*` allows any. Defaults to headers used by API
+    #[serde(default)]
+    pub headers: Option<Vec<String>>,
+    /// Allowed methods. `*` allows any. Defaults to methods used by API
+    #[serde(default)]
+    pub methods: Option<Vec<String>>,
+    /// How long in seconds browser may cache preflight response
+    #[serde(default)]
+    pub max_age: Option<u64>,
+}
+
+impl AppConfigServerCors {
+    /// Build [`CorsLayer`]. `Err` contains description of invalid option.
+    pub fn layer(&self) -> Result<CorsLayer, String> {
+        let is_any = |v: &[String]| v.iter().any(|v| v == "*");
+
+        let origins = if is_any(&self.origins) {
+            AllowOrigin::any()
+        } else {
+            self.origins
+                .iter()
+                .map(|v| {
+                    HeaderValue::from_str(v).map_err(|e| format!("invalid cors origin `{v}`: {e}"))
+                })
+                .collect::<Result<Vec<_>, _>>()?
+                .into()
+        };
+
+        let headers = match &self.headers {
+            Some(v) if is_any(v) => AllowHeaders::any(),
+            Some(v) => v
+                .iter()
+                .map(|v| {
+                    HeaderName::try_from(v).map_err(|e| format!("invalid cors header `{v}`: {e}"))
+                })
+                .collect::<Result<Vec<_>, _>>()?
+                .into(),
+            None => vec![
+                AUTHORIZATION,
+                CONTENT_TYPE,
+                IF_MATCH,
+                IF_NONE_MATCH,
+                IDEMPOTENCY_KEY.clone(),
+            ]
+            .into(),
+        };
+
+        let methods = match &self.methods {
+            Some(v) if is_any(v) => AllowMethods::any(),
+            Some(v) => v
+                .iter()
+                .map(|v| {
+                    Method::try_from(v.as_str())
+                        .map_err(|e| format!("invalid cors method `{v}`: {e}"))
+                })
+                .collect::<Result<Vec<_>, _>>()?
+                .into(),
+            None => vec![
+                Method::GET,
+                Method::PUT,
+                Method::POST,
+                Method::PATCH,
+                Method::DELETE,
+            ]
+            .into(),
+        };
+
+        let mut layer = CorsLayer::new()
+            .allow_origin(origins)
+            .allow_headers(headers)
+            .allow_methods(methods)
+            .expose_headers([ETAG, IDEMPOTENCY_REPLAYED.clone()]);
+        if let Some(max_age) = self.max_age {
+            layer = layer.max_age(Duration::from_secs(max_age));
+        }
+
+        Ok(layer)
+    }
 }
 
 #[derive(Deserialize)]
-pub struct AppConfigServerPublishOn {
-    pub ip: Ipv4Addr,
-    pub port: AppConfigServerPublishOnPort,
+pub struct AppConfigServerTls {
+    /// Path to PEM certificate chain
+    pub cert: PathBuf,
+    /// Path to PEM private key
+    pub key: PathBuf,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+pub enum AppConfigServerPublishOn {
+    /// Listen on TCP address
+    Tcp {
+        /// One or many IPv4/IPv6 addresses
+        ip: AppConfigServerPublishOnIp,
+        port: AppConfigServerPublishOnPort,
+        /// Accept IPv4 connections on IPv6 addresses too (eg. on `::`). Otherwise IPv6
+        /// sockets are IPv6-only, so same port can be bound on `0.0.0.0` and `::`
+        #[serde(default)]
+        dual_stack: bool,
+    },
+    /// Listen on unix domain socket
+    Unix {
+        /// Path to socket. Stale socket left from previous run is removed
+        unix: PathBuf,
+        /// Permissions of socket file in octal, eg. `"660"`
+        #[serde(default)]
+        mode: Option<String>,
+    },
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+pub enum AppConfigServerPublishOnIp {
+    One(IpAddr),
+    Many(Vec<IpAddr>),
+}
+
+impl AppConfigServerPublishOnIp {
+    pub fn to_vec(&self) -> Vec<IpAddr> {
+        match self {
+            Self::One(v) => vec![*v],
+            Self::Many(v) => v.clone(),
+        }
+    }
 }
 
 #[derive(Deserialize)]
@@ -44,5 +827,141 @@ pub enum AppConfigServerPublishOnPort {
 #[derive(Clone)]
 pub struct AppState {
     pub db: SqlitePool,
-    pub roles: &'static UserRoles,
+    /// Read-only replica of database, if configured. Use [`AppState::read_db`]
+    pub db_read: Option<SqlitePool>,
+    /// User roles. Swapped on config reload, so load it once per request
+    pub roles: Arc<ArcSwap<UserRoles>>,
+    /// OpenID Connect provider if login with it is configured
+    pub oidc: Option<Arc<Oidc>>,
+    /// Lockout after failed logins
+    pub lockout: AppConfigAuthLockout,
+    /// Format of issued tokens
+    pub token_format: TokenFormat,
+    /// Authentication by trusted reverse proxy, if enabled
+    pub proxy_auth: Option<Arc<ProxyAuth>>,
+    /// Automatic invite waves, if enabled
+    pub invite_waves: Option<AppConfigServerInviteWaves>,
+    /// Limits of service accounts
+    pub services: AppConfigServerServices,
+    /// Cache of tokens and owners of spaces
+    pub cache: Arc<Cache>,
+    /// Header with client certificate fingerprint, if trusted
+    pub client_cert_header: Option<HeaderName>,
+    /// Networks of proxies trusted to pass client address, see [`crate::v1::extra::ClientIp`]
+    pub trusted_proxies: Arc<[IpNet]>,
+    /// Storage and limits of item attachments, if enabled
+    pub attachments: Option<Arc<Attachments>>,
+    /// Channels of notifications, if enabled
+    pub notifier: Option<Arc<Notifier>>,
+}
+
+impl AppState {
+    /// Pool for read-only queries: replica if configured, primary otherwise. Replica
+    /// may lag behind, so never read from it what was just written.
+    pub fn read_db(&self) -> &SqlitePool {
+        self.db_read.as_ref().unwrap_or(&self.db)
+    }
+}
+
+/// Begin database transaction. Transaction is rolled back on drop unless committed,
+/// so handlers running several statements can return early without partial changes.
+pub(crate) async fn begin(db: &SqlitePool) -> sqlx::Transaction<'static, sqlx::Sqlite> {
+    db.begin().await.expect("database")
+}
+
+/// Current time as timestamp in milliseconds, as stored in database.
+pub(crate) fn now_ms() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("Current system time less than UNIX epoch")
+        .as_millis() as i64
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn lockout_doubles_until_max() {
+        let lockout = AppConfigAuthLockout {
+            max_failures: 3,
+            lockout_secs: 30,
+            max_lockout_secs: 100,
+        };
+        assert_eq!(lockout.lockout_ms(2), None);
+        assert_eq!(lockout.lockout_ms(3), Some(30_000));
+        assert_eq!(lockout.lockout_ms(4), Some(60_000));
+        assert_eq!(lockout.lockout_ms(5), Some(100_000));
+        assert_eq!(lockout.lockout_ms(1000), Some(100_000));
+
+        let disabled = AppConfigAuthLockout {
+            max_failures: 0,
+            ..lockout
+        };
+        assert_eq!(disabled.lockout_ms(1000), None);
+    }
+
+    #[test]
+    fn compression_exclusions() {
+        let config: AppConfigServerCompression =
+            serde_yaml::from_str(r#"exclude: ["/space/:space_id/items", "/user/"]"#).unwrap();
+        assert_eq!(config.min_size, 1024);
+        assert!(config.excludes("/space/x/items"));
+        assert!(config.excludes("/space/x/items/csv"));
+        assert!(config.excludes("/user/@me"));
+        assert!(!config.excludes("/space/x/item/y"));
+        assert!(!config.excludes("/space/x"));
+        assert!(!config.excludes("/users"));
+    }
+
+    #[test]
+    fn no_auto_migrate_by_default() {
+        let config = |extra: &str| -> AppConfigServer {
+            let yaml = format!(
+                "publish_on: {{ unix: /run/archk.sock }}\ndatabase: sqlite://archk.db\nroles: []\n{extra}"
+            );
+            serde_yaml::from_str(&yaml).unwrap()
+        };
+        assert!(!config("").auto_migrate);
+        assert!(config("auto_migrate: true").auto_migrate);
+    }
+
+    #[test]
+    fn proxy_trust() {
+        let config = serde_yaml::from_str(r#"trusted_proxies: ["10.0.0.0/8", "::1/128"]"#);
+        let proxy = ProxyAuth::new(config.unwrap()).unwrap();
+        assert_eq!(proxy.header, "x-forwarded-user");
+        let trusts = |addr: &str| proxy.trusts(Some(addr.parse().unwrap()));
+
+        assert!(trusts("10.1.2.3"));
+        assert!(trusts("::ffff:10.1.2.3"));
+        assert!(trusts("::1"));
+        assert!(!trusts("11.1.2.3"));
+        assert!(!trusts("127.0.0.1"));
+        // unix socket
+        assert!(proxy.trusts(None));
+    }
+
+    #[test]
+    fn mqtt_broker_address() {
+        let address = |broker: &str| {
+            AppConfigServerMqtt {
+                broker: broker.into(),
+                topic_prefix: default_mqtt_topic_prefix(),
+                client_id: default_mqtt_client_id(),
+                username: None,
+                password: None,
+            }
+            .address()
+        };
+        assert_eq!(
+            address("mqtt://broker.local"),
+            Ok("broker.local:1883".into())
+        );
+        assert_eq!(address("tcp://10.0.0.1:1884/"), Ok("10.0.0.1:1884".into()));
+        assert_eq!(address("[::1]"), Ok("[::1]:1883".into()));
+        assert_eq!(address("mqtt://[::1]:1884"), Ok("[::1]:1884".into()));
+        assert!(address("mqtts://broker.local").is_err());
+        assert!(address("mqtt://").is_err());
+    }
 }
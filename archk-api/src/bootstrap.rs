@@ -0,0 +1,47 @@
+//! Initial admin created from `bootstrap` section of config on first run against
+//! empty database, instead of registering first user without invite.
+
+use archk::v1::{user::UserID, validate};
+use sqlx::SqlitePool;
+
+use crate::{app::AppConfigBootstrap, roles::UserRoles};
+
+/// Create initial admin if database has no users. Returns whether admin was created,
+/// `Err` contains human-readable description of invalid config.
+pub async fn run(
+    db: &SqlitePool,
+    config: &AppConfigBootstrap,
+    roles: &UserRoles,
+) -> Result<bool, String> {
+    let admin = &config.admin;
+    validate::username(&admin.username).map_err(|e| format!("`admin.username`: {e}"))?;
+    if bcrypt::verify("", &admin.password_hash).is_err() {
+        return Err("`admin.password_hash` is not a bcrypt hash".into());
+    }
+    let level = match &admin.role {
+        Some(name) => match roles.get_by_name(name) {
+            Some(v) => v.level,
+            None => return Err(format!("`admin.role`: no role named `{name}`")),
+        },
+        None => roles.get_max().level,
+    };
+
+    let id = UserID::new();
+    let id: &str = &id;
+    // checked by insert itself, so several servers starting at once create one admin
+    let res = sqlx::query!(
+        "INSERT INTO users(id, name, invites, level, password_hash)
+        SELECT ?, ?, ?, ?, ?
+        WHERE NOT EXISTS (SELECT 1 FROM users)",
+        id,
+        admin.username,
+        config.invites,
+        level,
+        admin.password_hash
+    )
+    .execute(db)
+    .await
+    .map_err(|e| format!("failed to create admin: {e}"))?;
+
+    Ok(res.rows_affected() > 0)
+}
@@ -0,0 +1,242 @@
+//! In-memory cache of lookups done by extractors on every request: tokens and owners
+//! of spaces. Entries live for `server.cache.ttl_secs` and are dropped earlier by
+//! handlers revoking tokens or changing spaces. Cache is local to process, so
+//! several servers sharing database may see revoked tokens until TTL expires.
+
+use std::{
+    borrow::Borrow,
+    collections::HashMap,
+    hash::Hash,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use crate::app::AppConfigServerCache;
+
+/// Personal token, see `tokens` table.
+#[derive(Clone, Debug)]
+pub struct CachedToken {
+    pub user_id: String,
+    /// Space of restricted token
+    pub space_id: Option<String>,
+    pub scopes: Option<String>,
+    pub expires_at: Option<i64>,
+}
+
+/// Service account of service token.
+#[derive(Clone, Debug)]
+pub struct CachedService {
+    pub id: String,
+    pub space_id: Option<String>,
+    pub ty: i64,
+}
+
+/// Owner and state of space.
+#[derive(Clone, Debug)]
+pub struct CachedSpace {
+    pub owner_id: String,
+    pub archived: bool,
+    /// Organization of space, its admins have access to space
+    pub org_id: Option<String>,
+}
+
+struct TtlMap<K, V>(Mutex<HashMap<K, (Instant, V)>>);
+
+impl<K: Eq + Hash, V: Clone> TtlMap<K, V> {
+    fn new() -> Self {
+        Self(Mutex::new(HashMap::new()))
+    }
+
+    fn get<Q>(&self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        let map = self.0.lock().expect("cache lock");
+        map.get(key)
+            .filter(|(expires_at, _)| *expires_at > Instant::now())
+            .map(|(_, v)| v.clone())
+    }
+
+    fn insert(&self, key: K, value: V, ttl: Duration, max_entries: usize) {
+        let now = Instant::now();
+        let mut map = self.0.lock().expect("cache lock");
+        if map.len() >= max_entries {
+            map.retain(|_, (expires_at, _)| *expires_at > now);
+            if map.len() >= max_entries {
+                map.clear();
+            }
+        }
+        map.insert(key, (now + ttl, value));
+    }
+
+    fn retain(&self, mut f: impl FnMut(&K, &V) -> bool) {
+        self.0
+            .lock()
+            .expect("cache lock")
+            .retain(|k, (_, v)| f(k, v));
+    }
+
+    fn clear(&self) {
+        self.0.lock().expect("cache lock").clear();
+    }
+
+    fn purge(&self) {
+        let now = Instant::now();
+        self.0
+            .lock()
+            .expect("cache lock")
+            .retain(|_, (expires_at, _)| *expires_at > now);
+    }
+}
+
+pub struct Cache {
+    ttl: Duration,
+    max_entries: usize,
+    /// Personal tokens by hash, see [`crate::tokens::hash`]
+    tokens: TtlMap<Vec<u8>, CachedToken>,
+    /// Services by hashes of their tokens
+    services: TtlMap<Vec<u8>, CachedService>,
+    spaces: TtlMap<String, CachedSpace>,
+}
+
+impl Cache {
+    pub fn new(config: &AppConfigServerCache) -> Self {
+        Self {
+            ttl: Duration::from_secs(config.ttl_secs),
+            max_entries: config.max_entries,
+            tokens: TtlMap::new(),
+            services: TtlMap::new(),
+            spaces: TtlMap::new(),
+        }
+    }
+
+    fn enabled(&self) -> bool {
+        !self.ttl.is_zero() && self.max_entries > 0
+    }
+
+    pub fn token(&self, hash: &[u8]) -> Option<CachedToken> {
+        self.tokens.get(hash)
+    }
+
+    pub fn insert_token(&self, hash: Vec<u8>, token: CachedToken) {
+        if self.enabled() {
+            self.tokens.insert(hash, token, self.ttl, self.max_entries);
+        }
+    }
+
+    /// Drop all tokens of user. Call after any token of user is deleted.
+    pub fn revoke_user_tokens(&self, user_id: &str) {
+        self.tokens.retain(|_, v| v.user_id != user_id);
+    }
+
+    pub fn service(&self, hash: &[u8]) -> Option<CachedService> {
+        self.services.get(hash)
+    }
+
+    pub fn insert_service(&self, hash: Vec<u8>, service: CachedService) {
+        if self.enabled() {
+            self.services
+                .insert(hash, service, self.ttl, self.max_entries);
+        }
+    }
+
+    /// Drop all tokens of service. Call after service or any of its tokens is deleted.
+    pub fn revoke_service(&self, service_id: &str) {
+        self.services.retain(|_, v| v.id != service_id);
+    }
+
+    pub fn space(&self, space_id: &str) -> Option<CachedSpace> {
+        self.spaces.get(space_id)
+    }
+
+    pub fn insert_space(&self, space_id: &str, space: CachedSpace) {
+        if self.enabled() {
+            self.spaces
+                .insert(space_id.into(), space, self.ttl, self.max_entries);
+        }
+    }
+
+    /// Drop space and services bound to it. Call after space is archived or deleted.
+    pub fn invalidate_space(&self, space_id: &str) {
+        self.spaces.retain(|k, _| k != space_id);
+        self.services
+            .retain(|_, v| v.space_id.as_deref() != Some(space_id));
+    }
+
+    /// Drop everything, eg. after spaces of deleted user are transferred.
+    pub fn clear(&self) {
+        self.tokens.clear();
+        self.services.clear();
+        self.spaces.clear();
+    }
+
+    /// Remove expired entries.
+    pub fn purge(&self) {
+        self.tokens.purge();
+        self.services.purge();
+        self.spaces.purge();
+    }
+}
+
+impl Default for Cache {
+    fn default() -> Self {
+        Self::new(&AppConfigServerCache::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cache(ttl_secs: u64, max_entries: usize) -> Cache {
+        Cache::new(&AppConfigServerCache {
+            ttl_secs,
+            max_entries,
+        })
+    }
+
+    fn space(owner_id: &str) -> CachedSpace {
+        CachedSpace {
+            owner_id: owner_id.into(),
+            archived: false,
+            org_id: None,
+        }
+    }
+
+    #[test]
+    fn invalidate_space_drops_its_services() {
+        let cache = cache(60, 16);
+        cache.insert_space("a", space("u"));
+        for (rnd, space_id) in [(1, Some("a")), (2, Some("b")), (3, None)] {
+            cache.insert_service(
+                vec![rnd],
+                CachedService {
+                    id: format!("s{rnd}"),
+                    space_id: space_id.map(Into::into),
+                    ty: 1001,
+                },
+            );
+        }
+
+        cache.invalidate_space("a");
+        assert!(cache.space("a").is_none());
+        assert!(cache.service(&[1]).is_none());
+        assert!(cache.service(&[2]).is_some());
+        assert!(cache.service(&[3]).is_some());
+    }
+
+    #[test]
+    fn disabled_and_full() {
+        let disabled = cache(0, 16);
+        disabled.insert_space("a", space("u"));
+        assert!(disabled.space("a").is_none());
+
+        let full = cache(60, 2);
+        full.insert_space("a", space("u"));
+        full.insert_space("b", space("u"));
+        full.insert_space("c", space("u"));
+        assert!(full.space("a").is_none());
+        assert_eq!(full.space("c").map(|v| v.owner_id).as_deref(), Some("u"));
+    }
+}
@@ -0,0 +1,268 @@
+//! Background jobs running along with server.
+
+use std::time::Duration;
+
+use archk::v1::user::UserTelegramAuth;
+use sqlx::{SqliteConnection, SqlitePool};
+
+use crate::{
+    app::{self, AppConfigServerInviteWaves, AppState},
+    oidc::OIDC_STATE_TTL_MS,
+    roles::UserRoles,
+    storage::Attachments,
+    v1::{idempotency::IDEMPOTENCY_TTL_MS, purge_file, SYNC_DELETIONS_TTL_MS},
+};
+
+/// How often jobs are run
+const JOBS_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+const DAY_MS: i64 = 1000 * 60 * 60 * 24;
+
+/// Spawn background jobs runner.
+pub fn spawn(state: AppState) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(JOBS_INTERVAL);
+        loop {
+            interval.tick().await;
+            run(&state).await;
+        }
+    });
+}
+
+/// Run all jobs once.
+pub async fn run(state: &AppState) {
+    match cleanup_logs(&state.db).await {
+        Ok(0) => (),
+        Ok(removed) => tracing::info!(removed, "Removed old space logs"),
+        Err(err) => tracing::warn!(%err, "Failed to remove old space logs"),
+    }
+    match cleanup_idempotency(&state.db).await {
+        Ok(0) => (),
+        Ok(removed) => tracing::info!(removed, "Removed expired idempotency keys"),
+        Err(err) => tracing::warn!(%err, "Failed to remove expired idempotency keys"),
+    }
+    match cleanup_sync_deletions(&state.db).await {
+        Ok(0) => (),
+        Ok(removed) => tracing::info!(removed, "Removed old deletions of space objects"),
+        Err(err) => tracing::warn!(%err, "Failed to remove old deletions of space objects"),
+    }
+    match cleanup_oidc_states(&state.db).await {
+        Ok(0) => (),
+        Ok(removed) => tracing::info!(removed, "Removed expired OpenID Connect logins"),
+        Err(err) => tracing::warn!(%err, "Failed to remove expired OpenID Connect logins"),
+    }
+    match cleanup_expired_tokens(&state.db).await {
+        Ok(0) => (),
+        Ok(removed) => tracing::info!(removed, "Removed expired tokens"),
+        Err(err) => tracing::warn!(%err, "Failed to remove expired tokens"),
+    }
+    match cleanup_telegram_auths(&state.db).await {
+        Ok(0) => (),
+        Ok(removed) => tracing::info!(removed, "Removed expired Telegram link codes"),
+        Err(err) => tracing::warn!(%err, "Failed to remove expired Telegram link codes"),
+    }
+    if let Some(attachments) = &state.attachments {
+        match cleanup_attachments(&state.db, attachments).await {
+            Ok(0) => (),
+            Ok(removed) => tracing::info!(removed, "Removed files of deleted attachments"),
+            Err(err) => tracing::warn!(%err, "Failed to remove files of deleted attachments"),
+        }
+    }
+    state.cache.purge();
+    match cleanup_auth_failures(&state.db, state.lockout.max_lockout_secs).await {
+        Ok(0) => (),
+        Ok(removed) => tracing::info!(removed, "Removed forgotten failed logins"),
+        Err(err) => tracing::warn!(%err, "Failed to remove forgotten failed logins"),
+    }
+    if let Some(waves) = state.invite_waves {
+        match auto_invite_wave(&state.db, &state.roles.load(), waves).await {
+            Ok(None) => (),
+            Ok(Some(users)) => tracing::info!(users, "Gave invites by automatic invite wave"),
+            Err(err) => tracing::warn!(%err, "Failed to run automatic invite wave"),
+        }
+    }
+}
+
+/// Give invites to users with level of at least `min_level` by their roles (see
+/// `invites_per_wave` and `max_invites` of [`crate::roles::UserRole`]). Returns number
+/// of users got invites.
+pub async fn invite_wave(
+    db: &mut SqliteConnection,
+    roles: &UserRoles,
+    min_level: i64,
+) -> Result<u64, sqlx::Error> {
+    let mut users = 0;
+    for (role, next) in roles.ranges() {
+        if role.invites_per_wave <= 0 || next.is_some_and(|v| v <= min_level) {
+            continue;
+        }
+        let from = role.level.max(min_level);
+        let res = sqlx::query!(
+            r#"
+            UPDATE users SET invites = CASE
+                WHEN ?2 IS NULL THEN invites + ?1
+                ELSE MIN(invites + ?1, ?2)
+            END
+            WHERE level >= ?3 AND (?4 IS NULL OR level < ?4) AND (?2 IS NULL OR invites < ?2)
+            "#,
+            role.invites_per_wave,
+            role.max_invites,
+            from,
+            next
+        )
+        .execute(&mut *db)
+        .await?;
+        users += res.rows_affected();
+    }
+
+    Ok(users)
+}
+
+/// Run [`invite_wave`] if `interval_hours` passed since previous automatic wave.
+async fn auto_invite_wave(
+    db: &SqlitePool,
+    roles: &UserRoles,
+    waves: AppConfigServerInviteWaves,
+) -> Result<Option<u64>, sqlx::Error> {
+    let now = app::now_ms();
+    let since = now - waves.interval_hours as i64 * 60 * 60 * 1000;
+
+    let mut tx = db.begin().await?;
+    let last_run_at = sqlx::query!("SELECT last_run_at FROM jobs_runs WHERE name = 'invite_wave'")
+        .fetch_optional(&mut *tx)
+        .await?
+        .map(|v| v.last_run_at);
+    if last_run_at.is_some_and(|v| v > since) {
+        return Ok(None);
+    }
+
+    let users = invite_wave(&mut tx, roles, waves.min_level).await?;
+    sqlx::query!(
+        "INSERT INTO jobs_runs(name, last_run_at) VALUES ('invite_wave', ?)
+        ON CONFLICT(name) DO UPDATE SET last_run_at = excluded.last_run_at",
+        now
+    )
+    .execute(&mut *tx)
+    .await?;
+    tx.commit().await?;
+
+    Ok(Some(users))
+}
+
+/// Remove logs older than space retention period.
+async fn cleanup_logs(db: &SqlitePool) -> Result<u64, sqlx::Error> {
+    let now = app::now_ms();
+
+    let res = sqlx::query!(
+        r#"
+        DELETE FROM spaces_logs
+        WHERE created_at < ? - ? * (
+            SELECT logs_retention_days FROM spaces WHERE spaces.id = spaces_logs.space_id
+        )"#,
+        now,
+        DAY_MS
+    )
+    .execute(db)
+    .await?;
+
+    Ok(res.rows_affected())
+}
+
+/// Remove stored responses of expired idempotency keys.
+async fn cleanup_idempotency(db: &SqlitePool) -> Result<u64, sqlx::Error> {
+    let now = app::now_ms();
+    let since = now - IDEMPOTENCY_TTL_MS;
+
+    let res = sqlx::query!("DELETE FROM idempotency WHERE created_at < ?", since)
+        .execute(db)
+        .await?;
+
+    Ok(res.rows_affected())
+}
+
+/// Remove deletions of items and accounts no longer needed for sync, including
+/// ones of deleted spaces.
+async fn cleanup_sync_deletions(db: &SqlitePool) -> Result<u64, sqlx::Error> {
+    let now = app::now_ms();
+    let since = now - SYNC_DELETIONS_TTL_MS;
+
+    let res = sqlx::query!(
+        "
+        DELETE FROM spaces_deletions
+        WHERE deleted_at < ? OR space_id NOT IN (SELECT id FROM spaces)",
+        since
+    )
+    .execute(db)
+    .await?;
+
+    Ok(res.rows_affected())
+}
+
+/// Remove states of OpenID Connect logins which were never finished.
+async fn cleanup_oidc_states(db: &SqlitePool) -> Result<u64, sqlx::Error> {
+    let now = app::now_ms();
+    let since = now - OIDC_STATE_TTL_MS;
+
+    let res = sqlx::query!("DELETE FROM oidc_states WHERE created_at < ?", since)
+        .execute(db)
+        .await?;
+
+    Ok(res.rows_affected())
+}
+
+/// Remove expired tokens, eg. issued by impersonation.
+async fn cleanup_expired_tokens(db: &SqlitePool) -> Result<u64, sqlx::Error> {
+    let now = app::now_ms();
+
+    let res = sqlx::query!("DELETE FROM tokens WHERE expires_at <= ?", now)
+        .execute(db)
+        .await?;
+
+    Ok(res.rows_affected())
+}
+
+/// Remove codes of Telegram linking which were not used in time.
+async fn cleanup_telegram_auths(db: &SqlitePool) -> Result<u64, sqlx::Error> {
+    let since = app::now_ms() - UserTelegramAuth::WAIT_TIME_MS as i64;
+
+    let res = sqlx::query!(
+        "DELETE FROM users_telegram_auths WHERE issued_at < ?",
+        since
+    )
+    .execute(db)
+    .await?;
+
+    Ok(res.rows_affected())
+}
+
+/// Remove files of deleted attachments from storage, including attachments removed
+/// with their items and spaces.
+async fn cleanup_attachments(
+    db: &SqlitePool,
+    attachments: &Attachments,
+) -> Result<u64, std::io::Error> {
+    let keys = sqlx::query_scalar!("SELECT storage_key FROM attachments_deletions LIMIT 1000")
+        .fetch_all(db)
+        .await
+        .map_err(std::io::Error::other)?;
+
+    let mut removed = 0;
+    for key in keys {
+        purge_file(attachments, db, &key).await?;
+        removed += 1;
+    }
+
+    Ok(removed)
+}
+
+/// Remove counters of failed logins older than maximum lockout, they are ignored anyway.
+async fn cleanup_auth_failures(db: &SqlitePool, max_lockout_secs: i64) -> Result<u64, sqlx::Error> {
+    let now = app::now_ms();
+    let since = now - max_lockout_secs * 1000;
+
+    let res = sqlx::query!("DELETE FROM auth_failures WHERE last_failure_at < ?", since)
+        .execute(db)
+        .await?;
+
+    Ok(res.rows_affected())
+}
@@ -1,9 +1,162 @@
+use archk::Documentation;
+use serde::Serialize;
 use sqlx::SqlitePool;
 
 pub mod app;
+pub mod bootstrap;
+pub mod cache;
+pub mod jobs;
+pub mod notify;
+pub mod oidc;
+pub mod qr;
 pub mod roles;
+pub mod storage;
+pub mod telemetry;
+pub mod tokens;
 pub mod v1;
 
 pub async fn apply_migrations(db: &SqlitePool) -> Result<(), sqlx::migrate::MigrateError> {
     sqlx::migrate!().run(db).await
 }
+
+/// Database has pending migrations while `server.auto_migrate` is disabled, see
+/// [`check_migrations`].
+#[derive(Debug)]
+pub struct PendingMigrations(pub Vec<Migration>);
+
+impl std::fmt::Display for PendingMigrations {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "database has {} pending migrations:", self.0.len())?;
+        for v in &self.0 {
+            writeln!(f, "  {} {}", v.version, v.description)?;
+        }
+        write!(
+            f,
+            "help: back up database and run `archk-api-server --migrate`, \
+            or set `server.auto_migrate: true` to apply them on start"
+        )
+    }
+}
+
+impl std::error::Error for PendingMigrations {}
+
+/// Pre-flight check on start: returns whether there are pending migrations to apply, or
+/// refuses to start with them unless `auto_migrate` is enabled.
+pub fn check_migrations(
+    migrations: Vec<Migration>,
+    auto_migrate: bool,
+) -> Result<bool, PendingMigrations> {
+    let pending: Vec<_> = migrations.into_iter().filter(|v| v.is_pending()).collect();
+    match (pending.is_empty(), auto_migrate) {
+        (true, _) => Ok(false),
+        (false, true) => Ok(true),
+        (false, false) => Err(PendingMigrations(pending)),
+    }
+}
+
+/// Migration of database, see [`migrations`].
+#[derive(Serialize, Documentation, Debug)]
+pub struct Migration {
+    /// Version, eg. `37`
+    pub version: i64,
+    /// Description, eg. `maintenance`
+    pub description: String,
+    /// Timestamp in milliseconds of applying, `null` if pending
+    pub applied_at: Option<i64>,
+    /// Applied migration differs from one of server
+    pub modified: bool,
+    /// Applied migration is unknown to server, eg. database was migrated by newer version
+    pub missing: bool,
+}
+
+impl Migration {
+    pub fn is_pending(&self) -> bool {
+        self.applied_at.is_none()
+    }
+}
+
+/// Migrations known to server and applied to database, ordered by version.
+pub async fn migrations(db: &SqlitePool) -> Result<Vec<Migration>, sqlx::Error> {
+    let exists = sqlx::query_scalar!(
+        r#"SELECT COUNT(*) AS "count!: i64" FROM sqlite_master
+        WHERE type = 'table' AND name = '_sqlx_migrations'"#
+    )
+    .fetch_one(db)
+    .await?
+        > 0;
+    let applied = if exists {
+        sqlx::query!(
+            r#"
+            SELECT
+                version AS "version!: i64", description, checksum,
+                CAST(strftime('%s', installed_on) AS INTEGER) * 1000 AS "applied_at!: i64"
+            FROM _sqlx_migrations
+            WHERE success"#
+        )
+        .fetch_all(db)
+        .await?
+    } else {
+        Vec::new()
+    };
+
+    let migrator = sqlx::migrate!();
+    let known: Vec<_> = migrator
+        .iter()
+        .filter(|v| !v.migration_type.is_down_migration())
+        .collect();
+    let mut res: Vec<_> = known
+        .iter()
+        .map(|m| {
+            let applied = applied.iter().find(|v| v.version == m.version);
+            Migration {
+                version: m.version,
+                description: m.description.to_string(),
+                applied_at: applied.map(|v| v.applied_at),
+                modified: applied.is_some_and(|v| v.checksum != *m.checksum),
+                missing: false,
+            }
+        })
+        .collect();
+    res.extend(
+        applied
+            .into_iter()
+            .filter(|v| !known.iter().any(|m| m.version == v.version))
+            .map(|v| Migration {
+                version: v.version,
+                description: v.description,
+                applied_at: Some(v.applied_at),
+                modified: false,
+                missing: true,
+            }),
+    );
+    res.sort_by_key(|v| v.version);
+
+    Ok(res)
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::sqlite::SqlitePoolOptions;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn refuses_pending_migrations() {
+        let db = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .expect("database");
+
+        let err = check_migrations(migrations(&db).await.unwrap(), false).unwrap_err();
+        assert!(!err.0.is_empty());
+        let msg = err.to_string();
+        assert!(msg.contains("--migrate"), "{msg}");
+        assert!(msg.contains("auto_migrate: true"), "{msg}");
+
+        assert!(check_migrations(migrations(&db).await.unwrap(), true).unwrap());
+
+        apply_migrations(&db).await.expect("migrations");
+        assert!(!check_migrations(migrations(&db).await.unwrap(), false).unwrap());
+    }
+}
@@ -0,0 +1,534 @@
+//! Notifications of space owners about space events: filed reports, denied unlocks
+//! and overdue items. Channels are configured by `server.notifications` option in
+//! config, users choose where to receive messages with `PATCH /user/notifications`.
+//!
+//! Events are read from space logs like [`crate::v1::mqtt`] bridge does, so events
+//! from any source (HTTP, MQTT, gRPC) are delivered.
+
+use std::{io, sync::Arc, time::Duration};
+
+use archk::v1::space::SpaceLogAction;
+use axum::async_trait;
+use base64::{prelude::BASE64_STANDARD, Engine};
+use serde::Serialize;
+use sqlx::SqlitePool;
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    net::TcpStream,
+};
+
+use crate::{
+    app::{
+        self, AppConfigNotificationsEmail, AppConfigNotificationsTelegram,
+        AppConfigServerNotifications,
+    },
+    v1::LogCursor,
+};
+
+/// How often new log entries are checked
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+/// How often overdue items are checked
+const OVERDUE_INTERVAL: Duration = Duration::from_secs(60);
+/// Timeout of delivery to one channel
+const SEND_TIMEOUT: Duration = Duration::from_secs(10);
+/// Maximum number of log entries handled per poll
+const LOGS_LIMIT: i64 = 100;
+
+/// Event users are notified about
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum NotificationEvent {
+    /// [`SpaceLogAction::ReportFiled`]
+    ReportFiled,
+    /// [`SpaceLogAction::UnlockDenied`]
+    UnlockDenied,
+    /// Due date of taken item passed
+    ItemOverdue,
+}
+
+/// Notification message. Sent to webhooks as JSON
+#[derive(Serialize, Clone, Debug)]
+pub struct Notification {
+    pub event: NotificationEvent,
+    pub space_id: String,
+    pub space_title: String,
+    /// Item of report or overdue item
+    pub item_id: Option<String>,
+    pub item_title: Option<String>,
+    /// Account of event, eg. holder of overdue item
+    pub pl_id: Option<String>,
+    /// Report text or reason of denial
+    pub detail: Option<String>,
+    /// Timestamp in milliseconds of event
+    pub created_at: i64,
+}
+
+impl Notification {
+    /// One line summary, used as email subject.
+    pub fn subject(&self) -> String {
+        match self.event {
+            NotificationEvent::ReportFiled => format!("Report filed in {}", self.space_title),
+            NotificationEvent::UnlockDenied => format!("Unlock of {} denied", self.space_title),
+            NotificationEvent::ItemOverdue => format!(
+                "{} in {} is overdue",
+                self.item_title.as_deref().unwrap_or("Item"),
+                self.space_title
+            ),
+        }
+    }
+
+    /// Plain text of message.
+    pub fn text(&self) -> String {
+        let mut res = self.subject();
+        if let Some(item_title) = &self.item_title {
+            res.push_str(&format!("\nItem: {item_title}"));
+        }
+        if let Some(pl_id) = &self.pl_id {
+            res.push_str(&format!("\nAccount: {pl_id}"));
+        }
+        if let Some(detail) = &self.detail {
+            res.push_str(&format!("\n\n{detail}"));
+        }
+        res
+    }
+}
+
+/// Channel delivering notifications to target chosen by user, eg. chat ID or email.
+#[async_trait]
+pub trait Channel: Send + Sync {
+    async fn send(&self, target: &str, notification: &Notification) -> io::Result<()>;
+}
+
+/// Configured channels.
+pub struct Notifier {
+    pub telegram: Option<Arc<dyn Channel>>,
+    pub email: Option<Arc<dyn Channel>>,
+    pub webhooks: Option<Arc<dyn Channel>>,
+}
+
+impl Notifier {
+    /// Create channels from config. `Err` contains description of invalid option.
+    pub fn new(config: AppConfigServerNotifications) -> Result<Self, String> {
+        let http = reqwest::Client::builder()
+            .timeout(SEND_TIMEOUT)
+            .build()
+            .map_err(|e| format!("failed to create http client: {e}"))?;
+
+        let telegram = config
+            .telegram
+            .map(|v| Arc::new(TelegramChannel::new(http.clone(), v)) as Arc<dyn Channel>);
+        let email = match config.email {
+            Some(v) => {
+                if !v.smtp.contains(':') {
+                    return Err(format!("expected `host:port` in `smtp`, got `{}`", v.smtp));
+                }
+                check_email(&v.from).map_err(|e| format!("invalid `from`: {e}"))?;
+                Some(Arc::new(EmailChannel { config: v }) as Arc<dyn Channel>)
+            }
+            None => None,
+        };
+        let webhooks = config
+            .webhooks
+            .then(|| Arc::new(WebhookChannel { http }) as Arc<dyn Channel>);
+
+        Ok(Self {
+            telegram,
+            email,
+            webhooks,
+        })
+    }
+}
+
+/// Check email address set by user. `Err` contains description of invalid address.
+pub fn check_email(v: &str) -> Result<(), &'static str> {
+    let Some((local, domain)) = v.split_once('@') else {
+        return Err("expected `user@domain`");
+    };
+    if local.is_empty() || domain.is_empty() || v.len() > 254 {
+        return Err("expected `user@domain`");
+    }
+    if !v.is_ascii() || v.contains(|c: char| c.is_ascii_whitespace() || "<>()\",;".contains(c)) {
+        return Err("address contains forbidden characters");
+    }
+    Ok(())
+}
+
+/// Check Telegram chat ID set by user: numeric ID or `@channelusername`.
+pub fn check_telegram_chat_id(v: &str) -> Result<(), &'static str> {
+    let valid = match v.strip_prefix('@') {
+        Some(v) => !v.is_empty() && v.chars().all(|c| c.is_ascii_alphanumeric() || c == '_'),
+        None => v.parse::<i64>().is_ok(),
+    };
+    if valid {
+        Ok(())
+    } else {
+        Err("expected numeric chat ID or `@channelusername`")
+    }
+}
+
+/// Check webhook URL set by user.
+pub fn check_webhook_url(v: &str) -> Result<(), &'static str> {
+    match reqwest::Url::parse(v) {
+        Ok(url) if matches!(url.scheme(), "http" | "https") && url.has_host() => Ok(()),
+        _ => Err("expected `http://` or `https://` url"),
+    }
+}
+
+struct TelegramChannel {
+    http: reqwest::Client,
+    /// URL of `sendMessage` method
+    url: String,
+}
+
+impl TelegramChannel {
+    fn new(http: reqwest::Client, config: AppConfigNotificationsTelegram) -> Self {
+        Self {
+            http,
+            url: format!(
+                "{}/bot{}/sendMessage",
+                config.api_url.trim_end_matches('/'),
+                config.bot_token
+            ),
+        }
+    }
+}
+
+#[async_trait]
+impl Channel for TelegramChannel {
+    async fn send(&self, target: &str, notification: &Notification) -> io::Result<()> {
+        let res = self
+            .http
+            .post(&self.url)
+            .json(&serde_json::json!({
+                "chat_id": target,
+                "text": notification.text(),
+            }))
+            .send()
+            .await
+            // error contains URL with bot token
+            .map_err(|e| io::Error::other(e.without_url()))?;
+        match res.status() {
+            v if v.is_success() => Ok(()),
+            v => Err(io::Error::other(format!("telegram responded with {v}"))),
+        }
+    }
+}
+
+struct WebhookChannel {
+    http: reqwest::Client,
+}
+
+#[async_trait]
+impl Channel for WebhookChannel {
+    async fn send(&self, target: &str, notification: &Notification) -> io::Result<()> {
+        let res = self
+            .http
+            .post(target)
+            .json(notification)
+            .send()
+            .await
+            .map_err(io::Error::other)?;
+        match res.status() {
+            v if v.is_success() => Ok(()),
+            v => Err(io::Error::other(format!("webhook responded with {v}"))),
+        }
+    }
+}
+
+/// Minimal SMTP client sending one message per connection.
+struct EmailChannel {
+    config: AppConfigNotificationsEmail,
+}
+
+#[async_trait]
+impl Channel for EmailChannel {
+    async fn send(&self, target: &str, notification: &Notification) -> io::Result<()> {
+        tokio::time::timeout(SEND_TIMEOUT, self.deliver(target, notification))
+            .await
+            .map_err(|_| io::Error::from(io::ErrorKind::TimedOut))?
+    }
+}
+
+impl EmailChannel {
+    async fn deliver(&self, to: &str, notification: &Notification) -> io::Result<()> {
+        let config = &self.config;
+        let mut stream = BufReader::new(TcpStream::connect(&config.smtp).await?);
+
+        smtp_reply(&mut stream, 220).await?;
+        smtp_command(&mut stream, "EHLO archk", 250).await?;
+        if let Some(username) = &config.username {
+            let password = config.password.as_deref().unwrap_or_default();
+            let credentials = BASE64_STANDARD.encode(format!("\0{username}\0{password}"));
+            smtp_command(&mut stream, &format!("AUTH PLAIN {credentials}"), 235).await?;
+        }
+        smtp_command(&mut stream, &format!("MAIL FROM:<{}>", config.from), 250).await?;
+        smtp_command(&mut stream, &format!("RCPT TO:<{to}>"), 250).await?;
+        smtp_command(&mut stream, "DATA", 354).await?;
+        let message = email_message(
+            &config.from,
+            to,
+            &notification.subject(),
+            &notification.text(),
+        );
+        stream.write_all(message.as_bytes()).await?;
+        smtp_reply(&mut stream, 250).await?;
+        smtp_command(&mut stream, "QUIT", 221).await
+    }
+}
+
+async fn smtp_command(
+    stream: &mut BufReader<TcpStream>,
+    command: &str,
+    expected: u16,
+) -> io::Result<()> {
+    stream
+        .write_all(format!("{command}\r\n").as_bytes())
+        .await?;
+    smtp_reply(stream, expected).await
+}
+
+/// Read (possibly multiline) reply and check its code.
+async fn smtp_reply(stream: &mut BufReader<TcpStream>, expected: u16) -> io::Result<()> {
+    let mut line = String::new();
+    loop {
+        line.clear();
+        if stream.read_line(&mut line).await? == 0 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        // last line of reply is `<code> <text>`, others are `<code>-<text>`
+        if line.as_bytes().get(3) != Some(&b'-') {
+            break;
+        }
+    }
+    match line.get(..3).and_then(|v| v.parse::<u16>().ok()) {
+        Some(code) if code == expected => Ok(()),
+        _ => Err(io::Error::other(format!(
+            "smtp relay responded with `{}`, expected {expected}",
+            line.trim_end()
+        ))),
+    }
+}
+
+/// Message passed after `DATA` command, including terminating `.` line.
+fn email_message(from: &str, to: &str, subject: &str, text: &str) -> String {
+    let subject = format!("=?utf-8?B?{}?=", BASE64_STANDARD.encode(subject));
+    let mut res = format!(
+        "From: <{from}>\r\nTo: <{to}>\r\nSubject: {subject}\r\n\
+        MIME-Version: 1.0\r\nContent-Type: text/plain; charset=utf-8\r\n\
+        Content-Transfer-Encoding: 8bit\r\n\r\n"
+    );
+    for line in text.lines() {
+        // lines starting with `.` are escaped by doubling it
+        if line.starts_with('.') {
+            res.push('.');
+        }
+        res.push_str(line);
+        res.push_str("\r\n");
+    }
+    res.push_str(".\r\n");
+    res
+}
+
+/// Spawn delivery of notifications. Runs until server is stopped.
+pub fn spawn(db: SqlitePool, notifier: Arc<Notifier>) {
+    tokio::spawn(async move {
+        let mut cursor = LogCursor::now();
+        let mut poll = tokio::time::interval(POLL_INTERVAL);
+        let mut overdue = tokio::time::interval(OVERDUE_INTERVAL);
+        loop {
+            tokio::select! {
+                _ = poll.tick() => match log_notifications(&db, &mut cursor).await {
+                    Ok(v) => dispatch(&db, &notifier, v).await,
+                    Err(err) => tracing::warn!(%err, "Failed to fetch space logs for notifications"),
+                },
+                _ = overdue.tick() => match overdue_notifications(&db).await {
+                    Ok(v) => dispatch(&db, &notifier, v).await,
+                    Err(err) => tracing::warn!(%err, "Failed to fetch overdue items for notifications"),
+                },
+            }
+        }
+    });
+}
+
+/// Notifications about log entries created after `cursor`.
+async fn log_notifications(
+    db: &SqlitePool,
+    cursor: &mut LogCursor,
+) -> Result<Vec<Notification>, sqlx::Error> {
+    let report_filed: i64 = SpaceLogAction::ReportFiled.into();
+    let unlock_denied: i64 = SpaceLogAction::UnlockDenied.into();
+    let limit = cursor.limit(LOGS_LIMIT);
+    let res = sqlx::query!(
+        r#"
+        SELECT l.id, l.space_id, l.created_at, l.act, l.sp_acc_id, l.sp_item_id, l.detail,
+            s.title AS space_title, i.title AS "item_title?"
+        FROM spaces_logs l
+        JOIN spaces s ON s.id = l.space_id
+        LEFT JOIN spaces_items i ON i.id = l.sp_item_id
+        WHERE l.created_at >= ? AND l.act IN (?, ?)
+        ORDER BY l.created_at
+        LIMIT ?"#,
+        cursor.created_at,
+        report_filed,
+        unlock_denied,
+        limit
+    )
+    .fetch_all(db)
+    .await?;
+
+    Ok(res
+        .into_iter()
+        .filter(|v| cursor.advance(&v.id, v.created_at))
+        .map(|v| Notification {
+            event: if v.act == report_filed {
+                NotificationEvent::ReportFiled
+            } else {
+                NotificationEvent::UnlockDenied
+            },
+            space_id: v.space_id,
+            space_title: v.space_title,
+            item_id: v.sp_item_id,
+            item_title: v.item_title,
+            pl_id: v.sp_acc_id,
+            detail: v.detail,
+            created_at: v.created_at,
+        })
+        .collect())
+}
+
+/// Notifications about items became overdue since previous check. Time of check is
+/// kept in `jobs_runs`, so items are not notified twice after restart.
+async fn overdue_notifications(db: &SqlitePool) -> Result<Vec<Notification>, sqlx::Error> {
+    let now = app::now_ms();
+
+    // not in transaction: reading before writing in one transaction fails with busy
+    // database if other writer is running, eg. background jobs
+    let last_run_at =
+        sqlx::query!("SELECT last_run_at FROM jobs_runs WHERE name = 'overdue_notifications'")
+            .fetch_optional(db)
+            .await?
+            .map(|v| v.last_run_at);
+    sqlx::query!(
+        "INSERT INTO jobs_runs(name, last_run_at) VALUES ('overdue_notifications', ?)
+        ON CONFLICT(name) DO UPDATE SET last_run_at = excluded.last_run_at",
+        now
+    )
+    .execute(db)
+    .await?;
+    // on first run only new overdue items are notified
+    let Some(last_run_at) = last_run_at else {
+        return Ok(Vec::new());
+    };
+
+    let res = sqlx::query!(
+        r#"
+        SELECT i.id, i.title, i.space_id, i.current_holder, i.due_at AS "due_at!: i64",
+            s.title AS space_title
+        FROM spaces_items i
+        JOIN spaces s ON s.id = i.space_id
+        WHERE i.current_holder IS NOT NULL AND i.due_at > ? AND i.due_at <= ?"#,
+        last_run_at,
+        now
+    )
+    .fetch_all(db)
+    .await?;
+
+    Ok(res
+        .into_iter()
+        .map(|v| Notification {
+            event: NotificationEvent::ItemOverdue,
+            space_id: v.space_id,
+            space_title: v.space_title,
+            item_id: Some(v.id),
+            item_title: Some(v.title),
+            pl_id: v.current_holder,
+            detail: None,
+            created_at: v.due_at,
+        })
+        .collect())
+}
+
+/// Send notifications to space owners who enabled their events.
+async fn dispatch(db: &SqlitePool, notifier: &Notifier, notifications: Vec<Notification>) {
+    for notification in notifications {
+        let res = sqlx::query!(
+            r#"
+            SELECT n.user_id, n.telegram_chat_id, n.email, n.webhook_url,
+                n.report_filed AS "report_filed: bool",
+                n.unlock_denied AS "unlock_denied: bool",
+                n.item_overdue AS "item_overdue: bool"
+            FROM spaces s
+            JOIN users_notifications n ON n.user_id = s.owner_id
+            WHERE s.id = ?"#,
+            notification.space_id
+        )
+        .fetch_optional(db)
+        .await;
+        let recipient = match res {
+            Ok(Some(v)) => v,
+            Ok(None) => continue,
+            Err(err) => {
+                tracing::warn!(%err, "Failed to fetch notification settings");
+                continue;
+            }
+        };
+        let enabled = match notification.event {
+            NotificationEvent::ReportFiled => recipient.report_filed,
+            NotificationEvent::UnlockDenied => recipient.unlock_denied,
+            NotificationEvent::ItemOverdue => recipient.item_overdue,
+        };
+        if !enabled {
+            continue;
+        }
+
+        let targets = [
+            ("telegram", &notifier.telegram, recipient.telegram_chat_id),
+            ("email", &notifier.email, recipient.email),
+            ("webhook", &notifier.webhooks, recipient.webhook_url),
+        ];
+        for (name, channel, target) in targets {
+            let (Some(channel), Some(target)) = (channel.clone(), target) else {
+                continue;
+            };
+            let notification = notification.clone();
+            let user_id = recipient.user_id.clone();
+            // slow channels shouldn't delay other notifications
+            tokio::spawn(async move {
+                if let Err(err) = channel.send(&target, &notification).await {
+                    tracing::warn!(%err, user_id, channel = name, "Failed to send notification");
+                }
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn email_message_escapes_dots() {
+        let message = email_message(
+            "archk@example.com",
+            "owner@example.com",
+            "Report filed",
+            "Report filed in Lab\n.\n..hidden",
+        );
+        assert!(message.contains("Subject: =?utf-8?B?UmVwb3J0IGZpbGVk?=\r\n"));
+        assert!(message.ends_with("\r\n\r\nReport filed in Lab\r\n..\r\n...hidden\r\n.\r\n"));
+    }
+
+    #[test]
+    fn targets() {
+        assert!(check_email("owner@example.com").is_ok());
+        assert!(check_email("owner@").is_err());
+        assert!(check_email("a@b.c>\r\nRCPT TO:<x@y.z").is_err());
+
+        assert!(check_telegram_chat_id("-1001234567890").is_ok());
+        assert!(check_telegram_chat_id("@archk_lab").is_ok());
+        assert!(check_telegram_chat_id("chat").is_err());
+
+        assert!(check_webhook_url("https://hooks.example.com/archk").is_ok());
+        assert!(check_webhook_url("file:///etc/passwd").is_err());
+    }
+}
@@ -0,0 +1,149 @@
+//! Login with external OpenID Connect provider (authorization code flow).
+
+use archk::v1::api;
+use reqwest::Url;
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::app::AppConfigAuthOidc;
+
+/// How long login started by `/auth/oidc/login` can be finished in milliseconds
+pub const OIDC_STATE_TTL_MS: i64 = 10 * 60 * 1000;
+
+/// Client of OpenID Connect provider.
+pub struct Oidc {
+    pub config: AppConfigAuthOidc,
+    http: reqwest::Client,
+}
+
+/// User authenticated by provider.
+pub struct OidcUser {
+    /// Subject identifier, unique for provider
+    pub subject: String,
+    /// Value of `username_claim` if provider returned it
+    pub username: Option<String>,
+}
+
+/// Error of communication with provider.
+#[derive(Debug)]
+pub enum OidcError {
+    /// Provider rejected authorization code
+    Rejected(String),
+    /// Provider is unreachable or returned malformed response
+    Unavailable(String),
+}
+
+impl std::fmt::Display for OidcError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Rejected(e) => write!(f, "identity provider rejected login: {e}"),
+            Self::Unavailable(e) => write!(f, "identity provider is unavailable: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for OidcError {}
+
+impl From<OidcError> for api::ErrorData {
+    fn from(value: OidcError) -> Self {
+        let code = match value {
+            OidcError::Rejected(_) => api::Error::Unauthorized,
+            OidcError::Unavailable(_) => api::Error::ServiceUnavailable,
+        };
+        code.detail(value.to_string().into())
+    }
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: String,
+}
+
+impl Oidc {
+    /// Create client. `Err` contains description of invalid option.
+    pub fn new(config: AppConfigAuthOidc) -> Result<Self, String> {
+        for (name, url) in [
+            ("authorization_endpoint", &config.authorization_endpoint),
+            ("token_endpoint", &config.token_endpoint),
+            ("userinfo_endpoint", &config.userinfo_endpoint),
+            ("redirect_url", &config.redirect_url),
+        ] {
+            Url::parse(url).map_err(|e| format!("invalid `{name}` url `{url}`: {e}"))?;
+        }
+
+        Ok(Self {
+            config,
+            http: reqwest::Client::new(),
+        })
+    }
+
+    /// URL of provider login page. Provider redirects back to `redirect_url` with
+    /// authorization code and `state`.
+    pub fn authorize_url(&self, state: &str) -> String {
+        let scopes = self.config.scopes.join(" ");
+        Url::parse_with_params(
+            &self.config.authorization_endpoint,
+            [
+                ("response_type", "code"),
+                ("client_id", &self.config.client_id),
+                ("redirect_uri", &self.config.redirect_url),
+                ("scope", &scopes),
+                ("state", state),
+            ],
+        )
+        .expect("checked by `Oidc::new`")
+        .into()
+    }
+
+    /// Exchange authorization code for access token and fetch user info with it.
+    pub async fn user(&self, code: &str) -> Result<OidcUser, OidcError> {
+        let res = self
+            .http
+            .post(&self.config.token_endpoint)
+            .form(&[
+                ("grant_type", "authorization_code"),
+                ("code", code),
+                ("redirect_uri", &self.config.redirect_url),
+                ("client_id", &self.config.client_id),
+                ("client_secret", &self.config.client_secret),
+            ])
+            .send()
+            .await
+            .map_err(|e| OidcError::Unavailable(e.to_string()))?;
+        if res.status().is_client_error() {
+            let body = res.text().await.unwrap_or_default();
+            return Err(OidcError::Rejected(body));
+        }
+        let TokenResponse { access_token } = res
+            .error_for_status()
+            .map_err(|e| OidcError::Unavailable(e.to_string()))?
+            .json()
+            .await
+            .map_err(|e| OidcError::Unavailable(e.to_string()))?;
+
+        let info: Value = self
+            .http
+            .get(&self.config.userinfo_endpoint)
+            .bearer_auth(access_token)
+            .send()
+            .await
+            .and_then(|v| v.error_for_status())
+            .map_err(|e| OidcError::Unavailable(e.to_string()))?
+            .json()
+            .await
+            .map_err(|e| OidcError::Unavailable(e.to_string()))?;
+
+        let Some(subject) = info.get("sub").and_then(Value::as_str) else {
+            return Err(OidcError::Unavailable("userinfo has no `sub` claim".into()));
+        };
+        let username = info
+            .get(&self.config.username_claim)
+            .and_then(Value::as_str)
+            .map(String::from);
+
+        Ok(OidcUser {
+            subject: subject.into(),
+            username,
+        })
+    }
+}
@@ -0,0 +1,542 @@
+//! QR code encoder for item labels: byte mode, error correction level M, versions 1
+//! to 10 (up to 213 bytes), rendered as SVG or PNG.
+
+/// Width of light border around code in modules
+const QUIET_ZONE: usize = 4;
+/// Maximum supported version
+const MAX_VERSION: usize = 10;
+/// Error correction codewords per block of level M, by version
+const ECC_PER_BLOCK: [usize; MAX_VERSION + 1] = [0, 10, 16, 26, 18, 24, 16, 18, 22, 22, 26];
+/// Number of error correction blocks of level M, by version
+const NUM_BLOCKS: [usize; MAX_VERSION + 1] = [0, 1, 1, 1, 2, 2, 4, 4, 4, 5, 5];
+/// Format bits of level M
+const ECL_M: u32 = 0b00;
+
+/// QR code modules, `true` is dark.
+pub struct QrCode {
+    size: usize,
+    modules: Vec<bool>,
+    /// Function patterns that are not masked
+    function: Vec<bool>,
+}
+
+impl QrCode {
+    /// Encode `data`. `None` if it does not fit into version 10.
+    pub fn encode(data: &[u8]) -> Option<Self> {
+        let version = (1..=MAX_VERSION).find(|&v| {
+            let count_bits = if v < 10 { 8 } else { 16 };
+            data.len() < (1 << count_bits)
+                && 4 + count_bits + data.len() * 8 <= data_codewords(v) * 8
+        })?;
+
+        let mut bits = Bits::default();
+        bits.push(0b0100, 4);
+        bits.push(data.len() as u32, if version < 10 { 8 } else { 16 });
+        for &b in data {
+            bits.push(b as u32, 8);
+        }
+        let capacity = data_codewords(version) * 8;
+        bits.push(0, (capacity - bits.len).min(4));
+        bits.push(0, (8 - bits.len % 8) % 8);
+        for pad in [0xec, 0x11].into_iter().cycle() {
+            if bits.len >= capacity {
+                break;
+            }
+            bits.push(pad, 8);
+        }
+
+        let mut qr = Self::new(version);
+        qr.draw_codewords(&add_ecc_and_interleave(version, &bits.bytes));
+
+        let mask = (0..8)
+            .min_by_key(|&mask| {
+                qr.apply_mask(mask);
+                qr.draw_format_bits(mask);
+                let penalty = qr.penalty();
+                qr.apply_mask(mask);
+                penalty
+            })
+            .expect("masks");
+        qr.apply_mask(mask);
+        qr.draw_format_bits(mask);
+
+        Some(qr)
+    }
+
+    /// Number of modules on side, without quiet zone.
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Is module at column `x` and row `y` dark?
+    pub fn get(&self, x: usize, y: usize) -> bool {
+        self.modules[y * self.size + x]
+    }
+
+    /// Render as SVG with one unit per module.
+    pub fn to_svg(&self) -> String {
+        let side = self.size + QUIET_ZONE * 2;
+        let mut path = String::new();
+        for y in 0..self.size {
+            for x in 0..self.size {
+                if self.get(x, y) {
+                    if !path.is_empty() {
+                        path.push(' ');
+                    }
+                    path.push_str(&format!("M{},{}h1v1h-1z", x + QUIET_ZONE, y + QUIET_ZONE));
+                }
+            }
+        }
+        format!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+            <svg xmlns=\"http://www.w3.org/2000/svg\" version=\"1.1\" viewBox=\"0 0 {side} {side}\" shape-rendering=\"crispEdges\">\n\
+            <rect width=\"100%\" height=\"100%\" fill=\"#ffffff\"/>\n\
+            <path d=\"{path}\" fill=\"#000000\"/>\n\
+            </svg>\n"
+        )
+    }
+
+    /// Render as black and white PNG with `scale` pixels per module.
+    pub fn to_png(&self, scale: usize) -> Vec<u8> {
+        let side = (self.size + QUIET_ZONE * 2) * scale;
+        let row_len = side.div_ceil(8);
+
+        // 1-bit grayscale scanlines, each prefixed with filter type `0`
+        let mut raw = Vec::with_capacity((row_len + 1) * side);
+        for py in 0..side {
+            raw.push(0);
+            let start = raw.len();
+            raw.resize(start + row_len, 0);
+            for px in 0..side {
+                let (x, y) = (px / scale, py / scale);
+                let dark = (QUIET_ZONE..QUIET_ZONE + self.size).contains(&x)
+                    && (QUIET_ZONE..QUIET_ZONE + self.size).contains(&y)
+                    && self.get(x - QUIET_ZONE, y - QUIET_ZONE);
+                if !dark {
+                    raw[start + px / 8] |= 0x80 >> (px % 8);
+                }
+            }
+        }
+
+        let mut header = Vec::with_capacity(13);
+        header.extend_from_slice(&(side as u32).to_be_bytes());
+        header.extend_from_slice(&(side as u32).to_be_bytes());
+        // bit depth 1, grayscale, deflate, no filter, no interlace
+        header.extend_from_slice(&[1, 0, 0, 0, 0]);
+
+        let mut png = b"\x89PNG\r\n\x1a\n".to_vec();
+        png_chunk(&mut png, b"IHDR", &header);
+        png_chunk(&mut png, b"IDAT", &zlib_stored(&raw));
+        png_chunk(&mut png, b"IEND", &[]);
+        png
+    }
+
+    /// Empty code of `version` with function patterns.
+    fn new(version: usize) -> Self {
+        let size = version * 4 + 17;
+        let mut qr = Self {
+            size,
+            modules: vec![false; size * size],
+            function: vec![false; size * size],
+        };
+
+        for i in 0..size {
+            qr.set_function(6, i, i % 2 == 0);
+            qr.set_function(i, 6, i % 2 == 0);
+        }
+
+        for (cx, cy) in [(3, 3), (size - 4, 3), (3, size - 4)] {
+            for dy in -4isize..=4 {
+                for dx in -4isize..=4 {
+                    let (x, y) = (cx as isize + dx, cy as isize + dy);
+                    if (0..size as isize).contains(&x) && (0..size as isize).contains(&y) {
+                        let dist = dx.abs().max(dy.abs());
+                        qr.set_function(x as usize, y as usize, dist != 2 && dist != 4);
+                    }
+                }
+            }
+        }
+
+        let positions = alignment_positions(version);
+        let last = positions.len().saturating_sub(1);
+        for (i, &cx) in positions.iter().enumerate() {
+            for (j, &cy) in positions.iter().enumerate() {
+                // overlap with finder patterns
+                if (i == 0 && (j == 0 || j == last)) || (i == last && j == 0) {
+                    continue;
+                }
+                for dy in -2isize..=2 {
+                    for dx in -2isize..=2 {
+                        let dist = dx.abs().max(dy.abs());
+                        qr.set_function(
+                            (cx as isize + dx) as usize,
+                            (cy as isize + dy) as usize,
+                            dist != 1,
+                        );
+                    }
+                }
+            }
+        }
+
+        // reserve format bits, they are drawn after masking
+        qr.draw_format_bits(0);
+
+        if version >= 7 {
+            let mut rem = version as u32;
+            for _ in 0..12 {
+                rem = (rem << 1) ^ ((rem >> 11) * 0x1f25);
+            }
+            let bits = (version as u32) << 12 | rem;
+            for i in 0..18 {
+                let bit = (bits >> i) & 1 != 0;
+                let (a, b) = (size - 11 + i % 3, i / 3);
+                qr.set_function(a, b, bit);
+                qr.set_function(b, a, bit);
+            }
+        }
+
+        qr
+    }
+
+    fn set_function(&mut self, x: usize, y: usize, dark: bool) {
+        self.modules[y * self.size + x] = dark;
+        self.function[y * self.size + x] = true;
+    }
+
+    fn draw_format_bits(&mut self, mask: u32) {
+        let data = ECL_M << 3 | mask;
+        let mut rem = data;
+        for _ in 0..10 {
+            rem = (rem << 1) ^ ((rem >> 9) * 0x537);
+        }
+        let bits = (data << 10 | rem) ^ 0x5412;
+        let bit = |i: usize| (bits >> i) & 1 != 0;
+        let size = self.size;
+
+        for i in 0..6 {
+            self.set_function(8, i, bit(i));
+        }
+        self.set_function(8, 7, bit(6));
+        self.set_function(8, 8, bit(7));
+        self.set_function(7, 8, bit(8));
+        for i in 9..15 {
+            self.set_function(14 - i, 8, bit(i));
+        }
+
+        for i in 0..8 {
+            self.set_function(size - 1 - i, 8, bit(i));
+        }
+        for i in 8..15 {
+            self.set_function(8, size - 15 + i, bit(i));
+        }
+        self.set_function(8, size - 8, true);
+    }
+
+    /// Place data bits in zigzag order over non-function modules.
+    fn draw_codewords(&mut self, data: &[u8]) {
+        let size = self.size;
+        let mut i = 0;
+        let mut right = size - 1;
+        loop {
+            if right == 6 {
+                right = 5;
+            }
+            let upward = (right + 1) & 2 == 0;
+            for vert in 0..size {
+                let y = if upward { size - 1 - vert } else { vert };
+                for x in [right, right - 1] {
+                    if !self.function[y * size + x] && i < data.len() * 8 {
+                        self.modules[y * size + x] = (data[i >> 3] >> (7 - (i & 7))) & 1 != 0;
+                        i += 1;
+                    }
+                }
+            }
+            if right < 3 {
+                break;
+            }
+            right -= 2;
+        }
+    }
+
+    /// XOR data modules with mask pattern, so applying it twice reverts it.
+    fn apply_mask(&mut self, mask: u32) {
+        for y in 0..self.size {
+            for x in 0..self.size {
+                let invert = match mask {
+                    0 => (x + y) % 2 == 0,
+                    1 => y % 2 == 0,
+                    2 => x % 3 == 0,
+                    3 => (x + y) % 3 == 0,
+                    4 => (x / 3 + y / 2) % 2 == 0,
+                    5 => x * y % 2 + x * y % 3 == 0,
+                    6 => (x * y % 2 + x * y % 3) % 2 == 0,
+                    _ => ((x + y) % 2 + x * y % 3) % 2 == 0,
+                };
+                let i = y * self.size + x;
+                self.modules[i] ^= invert && !self.function[i];
+            }
+        }
+    }
+
+    /// Penalty score of current modules, lower is easier to scan.
+    fn penalty(&self) -> usize {
+        const FINDER_LIKE: [bool; 11] = [
+            true, false, true, true, true, false, true, false, false, false, false,
+        ];
+        let size = self.size;
+        let mut penalty = 0;
+
+        for transpose in [false, true] {
+            let get = |a: usize, b: usize| {
+                if transpose {
+                    self.get(b, a)
+                } else {
+                    self.get(a, b)
+                }
+            };
+            for b in 0..size {
+                let mut run = 1;
+                for a in 1..size {
+                    if get(a, b) == get(a - 1, b) {
+                        run += 1;
+                        if run == 5 {
+                            penalty += 3;
+                        } else if run > 5 {
+                            penalty += 1;
+                        }
+                    } else {
+                        run = 1;
+                    }
+                }
+                for a in 0..size.saturating_sub(10) {
+                    let forward = (0..11).all(|i| get(a + i, b) == FINDER_LIKE[i]);
+                    let backward = (0..11).all(|i| get(a + i, b) == FINDER_LIKE[10 - i]);
+                    penalty += 40 * (forward as usize + backward as usize);
+                }
+            }
+        }
+
+        for y in 0..size - 1 {
+            for x in 0..size - 1 {
+                let v = self.get(x, y);
+                if v == self.get(x + 1, y) && v == self.get(x, y + 1) && v == self.get(x + 1, y + 1)
+                {
+                    penalty += 3;
+                }
+            }
+        }
+
+        let total = size * size;
+        let dark = self.modules.iter().filter(|v| **v).count();
+        penalty + (dark * 20).abs_diff(total * 10) / total * 10
+    }
+}
+
+#[derive(Default)]
+struct Bits {
+    bytes: Vec<u8>,
+    len: usize,
+}
+
+impl Bits {
+    fn push(&mut self, value: u32, count: usize) {
+        for i in (0..count).rev() {
+            if self.len.is_multiple_of(8) {
+                self.bytes.push(0);
+            }
+            if (value >> i) & 1 != 0 {
+                *self.bytes.last_mut().expect("pushed") |= 0x80 >> (self.len % 8);
+            }
+            self.len += 1;
+        }
+    }
+}
+
+/// Number of codewords of `version`, data and error correction.
+fn total_codewords(version: usize) -> usize {
+    let mut modules = (16 * version + 128) * version + 64;
+    if version >= 2 {
+        let align = version / 7 + 2;
+        modules -= (25 * align - 10) * align - 55;
+        if version >= 7 {
+            modules -= 36;
+        }
+    }
+    modules / 8
+}
+
+fn data_codewords(version: usize) -> usize {
+    total_codewords(version) - ECC_PER_BLOCK[version] * NUM_BLOCKS[version]
+}
+
+/// Centers of alignment patterns on both axes.
+fn alignment_positions(version: usize) -> Vec<usize> {
+    if version == 1 {
+        return Vec::new();
+    }
+    let align = version / 7 + 2;
+    let step = (version * 4 + align * 2 + 1) / (align * 2 - 2) * 2;
+    let mut res = vec![6];
+    let mut pos = version * 4 + 10;
+    let mut tail = Vec::new();
+    for _ in 0..align - 1 {
+        tail.push(pos);
+        pos -= step;
+    }
+    res.extend(tail.into_iter().rev());
+    res
+}
+
+/// Split data into blocks, append error correction codewords to each and interleave
+/// them.
+fn add_ecc_and_interleave(version: usize, data: &[u8]) -> Vec<u8> {
+    let blocks_count = NUM_BLOCKS[version];
+    let ecc_len = ECC_PER_BLOCK[version];
+    let total = total_codewords(version);
+    let short_blocks = blocks_count - total % blocks_count;
+    let short_len = total / blocks_count;
+    let divisor = rs_divisor(ecc_len);
+
+    let mut blocks = Vec::with_capacity(blocks_count);
+    let mut k = 0;
+    for i in 0..blocks_count {
+        let len = short_len - ecc_len + usize::from(i >= short_blocks);
+        let mut block = data[k..k + len].to_vec();
+        k += len;
+        let ecc = rs_remainder(&block, &divisor);
+        if i < short_blocks {
+            // placeholder skipped on interleaving
+            block.push(0);
+        }
+        block.extend(ecc);
+        blocks.push(block);
+    }
+
+    let mut res = Vec::with_capacity(total);
+    for i in 0..blocks[0].len() {
+        for (j, block) in blocks.iter().enumerate() {
+            if i != short_len - ecc_len || j >= short_blocks {
+                res.push(block[i]);
+            }
+        }
+    }
+    res
+}
+
+/// Multiply in GF(2^8) modulo `x^8 + x^4 + x^3 + x^2 + 1`.
+fn gf_mul(x: u8, y: u8) -> u8 {
+    let mut z: u8 = 0;
+    for i in (0..8).rev() {
+        z = (z << 1) ^ ((z >> 7) * 0x1d);
+        z ^= ((y >> i) & 1) * x;
+    }
+    z
+}
+
+/// Reed-Solomon generator polynomial of `degree`, without leading coefficient.
+fn rs_divisor(degree: usize) -> Vec<u8> {
+    let mut res = vec![0; degree];
+    res[degree - 1] = 1;
+    let mut root = 1;
+    for _ in 0..degree {
+        for j in 0..degree {
+            res[j] = gf_mul(res[j], root);
+            if j + 1 < degree {
+                res[j] ^= res[j + 1];
+            }
+        }
+        root = gf_mul(root, 0x02);
+    }
+    res
+}
+
+fn rs_remainder(data: &[u8], divisor: &[u8]) -> Vec<u8> {
+    let mut res = vec![0; divisor.len()];
+    for &b in data {
+        let factor = b ^ res.remove(0);
+        res.push(0);
+        for (v, &coef) in res.iter_mut().zip(divisor) {
+            *v ^= gf_mul(coef, factor);
+        }
+    }
+    res
+}
+
+fn png_chunk(png: &mut Vec<u8>, ty: &[u8; 4], data: &[u8]) {
+    png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    let start = png.len();
+    png.extend_from_slice(ty);
+    png.extend_from_slice(data);
+    let crc = crc32fast::hash(&png[start..]);
+    png.extend_from_slice(&crc.to_be_bytes());
+}
+
+/// Zlib stream of uncompressed deflate blocks. Codes are small, so compression is
+/// not worth it.
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    let mut res = vec![0x78, 0x01];
+    let mut chunks = data.chunks(0xffff).peekable();
+    while let Some(chunk) = chunks.next() {
+        res.push(u8::from(chunks.peek().is_none()));
+        let len = chunk.len() as u16;
+        res.extend_from_slice(&len.to_le_bytes());
+        res.extend_from_slice(&(!len).to_le_bytes());
+        res.extend_from_slice(chunk);
+    }
+
+    let (mut a, mut b) = (1u32, 0u32);
+    for &v in data {
+        a = (a + v as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    res.extend_from_slice(&(b << 16 | a).to_be_bytes());
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reed_solomon() {
+        // "01234567" in numeric mode, version 1-M example of ISO/IEC 18004
+        let data = [
+            0x10, 0x20, 0x0c, 0x56, 0x61, 0x80, 0xec, 0x11, 0xec, 0x11, 0xec, 0x11, 0xec, 0x11,
+            0xec, 0x11,
+        ];
+        assert_eq!(
+            rs_remainder(&data, &rs_divisor(10)),
+            [0xa5, 0x24, 0xd4, 0xc1, 0xed, 0x36, 0xc7, 0x87, 0x2c, 0x55]
+        );
+    }
+
+    #[test]
+    fn versions() {
+        assert_eq!(
+            (1..=MAX_VERSION).map(data_codewords).collect::<Vec<_>>(),
+            [16, 28, 44, 64, 86, 108, 124, 154, 182, 216]
+        );
+        assert_eq!(alignment_positions(2), [6, 18]);
+        assert_eq!(alignment_positions(7), [6, 22, 38]);
+        assert_eq!(alignment_positions(10), [6, 28, 50]);
+
+        assert_eq!(QrCode::encode(b"SN-0001").unwrap().size(), 21);
+        assert_eq!(QrCode::encode(&[b'x'; 213]).unwrap().size(), 57);
+        assert!(QrCode::encode(&[b'x'; 214]).is_none());
+    }
+
+    #[test]
+    fn format_bits() {
+        let mut qr = QrCode::new(1);
+        qr.draw_format_bits(0);
+        // level M, mask 0
+        let bits: String = (0..6)
+            .map(|i| qr.get(8, i))
+            .chain([qr.get(8, 7), qr.get(8, 8), qr.get(7, 8)])
+            .chain((9..15).map(|i| qr.get(14 - i, 8)))
+            .rev()
+            .map(|v| if v { '1' } else { '0' })
+            .collect();
+        assert_eq!(bits, "101010000010010");
+    }
+}
@@ -1,6 +1,64 @@
-use archk::Documentation;
+use archk::{
+    v1::{
+        docs::{self, DocumentationObject},
+        service::ServiceAccountTy,
+    },
+    Documentation,
+};
 use serde::{Deserialize, Serialize};
 
+/// Names of permissions. Name consists of segments separated by `.`, role may
+/// also grant many permissions at once with wildcard like `space.*` or `*`.
+pub mod perm {
+    /// Promote users to current role or demote if role less than current.
+    pub const USER_PROMOTE: &str = "user.promote";
+    /// Make new invite waves (give invites to many/all users)
+    pub const USER_WAVE: &str = "user.wave";
+    /// Reset users passwords and drop users
+    pub const USER_MANAGE: &str = "user.manage";
+
+    /// Create spaces
+    pub const SPACE_CREATE: &str = "space.create";
+    /// Manage spaces of others
+    pub const SPACE_MANAGE: &str = "space.manage";
+    /// Read logs of all spaces
+    pub const SPACE_LOGS_READ: &str = "space.logs.read";
+    /// Read logs and change logs retention of all spaces
+    pub const SPACE_LOGS_MANAGE: &str = "space.logs.manage";
+
+    /// Create organizations
+    pub const ORG_CREATE: &str = "org.create";
+
+    /// Create and manage space-related services
+    pub const SERVICE_CREATE: &str = "service.create";
+    /// Manage all services and create admin services
+    pub const SERVICE_MANAGE: &str = "service.manage";
+
+    /// Download snapshots of database
+    pub const BACKUP: &str = "backup";
+    /// Read instance-wide statistics
+    pub const STATS: &str = "stats";
+    /// Toggle maintenance mode and make changes while it is enabled
+    pub const MAINTENANCE: &str = "maintenance";
+
+    /// All known permissions
+    pub const ALL: &[&str] = &[
+        USER_PROMOTE,
+        USER_WAVE,
+        USER_MANAGE,
+        SPACE_CREATE,
+        SPACE_MANAGE,
+        SPACE_LOGS_READ,
+        SPACE_LOGS_MANAGE,
+        ORG_CREATE,
+        SERVICE_CREATE,
+        SERVICE_MANAGE,
+        BACKUP,
+        STATS,
+        MAINTENANCE,
+    ];
+}
+
 #[derive(Deserialize)]
 pub struct UserRoles(pub Vec<UserRole>);
 
@@ -16,6 +74,11 @@ impl UserRoles {
         max.expect("No user roles")
     }
 
+    /// Get role by name, case-insensitive
+    pub fn get_by_name(&self, name: &str) -> Option<&UserRole> {
+        self.0.iter().find(|v| v.name.eq_ignore_ascii_case(name))
+    }
+
     /// Get maximum role by current level
     pub fn get_current(&self, level: i64) -> Option<&UserRole> {
         let mut max: Option<&UserRole> = None;
@@ -26,39 +89,383 @@ impl UserRoles {
         }
         max
     }
+
+    /// Roles with ranges of levels they are current for, ordered by level. Range is
+    /// `level..next`, where `next` is level of next role (`None` for max role).
+    pub fn ranges(&self) -> Vec<(&UserRole, Option<i64>)> {
+        let mut roles: Vec<&UserRole> = Vec::with_capacity(self.0.len());
+        for role in self.0.iter() {
+            // same as in `get_current`, later role of same level wins
+            match roles.iter_mut().find(|v| v.level == role.level) {
+                Some(v) => *v = role,
+                None => roles.push(role),
+            }
+        }
+        roles.sort_by_key(|v| v.level);
+
+        let next = roles.iter().skip(1).map(|v| Some(v.level)).chain([None]);
+        roles.iter().copied().zip(next).collect()
+    }
+
+    /// Quota of current role of `level`, unlimited if there is no role.
+    pub fn quota(&self, level: i64) -> RoleQuota {
+        self.get_current(level).map(|v| v.quota).unwrap_or_default()
+    }
+
+    /// Has current role of `level` permission `perm`? See [`perm`] for names.
+    pub fn has(&self, level: i64, perm: &str) -> bool {
+        self.get_current(level)
+            .map(|v| v.permissions.has(perm))
+            .unwrap_or(false)
+    }
+
+    /// Known permissions granted to current role of `level`, with wildcards expanded.
+    pub fn permissions(&self, level: i64) -> Vec<&'static str> {
+        perm::ALL
+            .iter()
+            .copied()
+            .filter(|v| self.has(level, v))
+            .collect()
+    }
+
+    /// May user of level `actor` change level of user of level `target` to `to`?
+    /// `is_self` is set if user changes own level.
+    pub fn check_promotion(
+        &self,
+        actor: i64,
+        target: i64,
+        to: i64,
+        is_self: bool,
+    ) -> Result<(), PromotionError> {
+        if !self.has(actor, perm::USER_PROMOTE) {
+            return Err(PromotionError::NoPermission);
+        }
+        if to > actor {
+            return Err(PromotionError::AboveOwnLevel);
+        }
+        if is_self {
+            // otherwise nobody may be left to promote users
+            return match self.has(to, perm::USER_PROMOTE) {
+                true => Ok(()),
+                false => Err(PromotionError::SelfDemotion),
+            };
+        }
+        if target >= actor {
+            return Err(PromotionError::TargetLevel);
+        }
+        Ok(())
+    }
 }
 
-#[derive(Serialize, Deserialize, Documentation)]
+/// Reason of denied level change, see [`UserRoles::check_promotion`]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PromotionError {
+    /// Role has no `user.promote` permission
+    NoPermission,
+    /// New level is above own level
+    AboveOwnLevel,
+    /// User has own level or higher
+    TargetLevel,
+    /// Own new level has no `user.promote` permission
+    SelfDemotion,
+}
+
+impl std::fmt::Display for PromotionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NoPermission => write!(f, "role has no `user.promote` permission"),
+            Self::AboveOwnLevel => write!(f, "can't promote above own level"),
+            Self::TargetLevel => write!(f, "can't change level of users of own level or higher"),
+            Self::SelfDemotion => write!(
+                f,
+                "can't demote self to role without `user.promote` permission"
+            ),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Documentation)]
 pub struct UserRole {
     pub name: String,
     pub level: i64,
     #[serde(default)]
     pub permissions: RolePermissions,
+    /// Invites given to users of role by invite wave
+    #[serde(default = "default_invites_per_wave")]
+    pub invites_per_wave: i64,
+    /// Invite waves do not give invites above this number, if any
+    #[serde(default)]
+    pub max_invites: Option<i64>,
+    /// Service types role may create, eg. `["space_actor"]`. Any type if not set
+    #[serde(default)]
+    pub service_types: Option<RoleServiceTypes>,
+    /// Limits of resources of users
+    #[serde(default)]
+    pub quota: RoleQuota,
 }
 
-#[derive(Serialize, Deserialize, Default, Clone, Documentation)]
-pub struct RolePermissions {
-    /// Promote users to current role or demote if role less than current.
+/// Limits of resources owned by users of role. Resource is not limited if its limit
+/// is not set. Limits of spaces contents are taken from role of space owner.
+#[derive(Serialize, Deserialize, Default, Clone, Copy, Debug, Documentation)]
+pub struct RoleQuota {
+    /// Spaces owned by user
     #[serde(default)]
-    pub promote: bool,
-    /// Access to make new invite waves (give invites to many/all users)
+    pub max_spaces: Option<u64>,
+    /// Items in each space owned by user
     #[serde(default)]
-    pub wave: bool,
-    /// Access to reset users passwords and drop users
+    pub max_items_per_space: Option<u64>,
+    /// Services bound to each space owned by user
     #[serde(default)]
-    pub manage: bool,
+    pub max_services_per_space: Option<u64>,
+    /// SSH keys of user
+    #[serde(default)]
+    pub max_ssh_keys: Option<u64>,
+}
+
+fn default_invites_per_wave() -> i64 {
+    1
+}
+
+/// Set of permissions granted to role, eg. `["space.*", "user.wave"]`.
+///
+/// For compatibility it also can be deserialized from old format with boolean
+/// flags, eg. `{ "spaces": true, "spaces_manage": true }`.
+#[derive(Serialize, Deserialize, Default, Clone, Debug, PartialEq)]
+#[serde(try_from = "RawRolePermissions")]
+pub struct RolePermissions(Vec<String>);
+
+impl RolePermissions {
+    /// Has role permission `perm`, directly or by wildcard?
+    pub fn has(&self, perm: &str) -> bool {
+        self.0.iter().any(|v| matches_permission(v, perm))
+    }
+}
+
+impl docs::Documentation for RolePermissions {
+    const DOCUMENTATION_OBJECT: DocumentationObject =
+        <Vec<String> as docs::Documentation>::DOCUMENTATION_OBJECT;
+}
+
+/// Service types allowed to role by their names, see [`ServiceAccountTy::name`].
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(try_from = "Vec<String>")]
+pub struct RoleServiceTypes(Vec<String>);
+
+impl RoleServiceTypes {
+    /// Is service type `ty` allowed?
+    pub fn allows(&self, ty: ServiceAccountTy) -> bool {
+        self.0.iter().any(|v| v == ty.name())
+    }
+}
+
+impl TryFrom<Vec<String>> for RoleServiceTypes {
+    type Error = String;
+
+    fn try_from(value: Vec<String>) -> Result<Self, Self::Error> {
+        if let Some(v) = value
+            .iter()
+            .find(|v| ServiceAccountTy::from_name(v).is_none())
+        {
+            return Err(format!("unknown service type `{v}`"));
+        }
+        Ok(Self(value))
+    }
+}
 
-    /// Can create spaces?
+impl docs::Documentation for RoleServiceTypes {
+    const DOCUMENTATION_OBJECT: DocumentationObject =
+        <Vec<String> as docs::Documentation>::DOCUMENTATION_OBJECT;
+}
+
+/// Is permission `perm` matches `pattern`: exact name, `*` or `prefix.*`.
+fn matches_permission(pattern: &str, perm: &str) -> bool {
+    match pattern.strip_suffix('*') {
+        Some("") => true,
+        Some(prefix) if prefix.ends_with('.') => perm.starts_with(prefix),
+        _ => pattern == perm,
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RawRolePermissions {
+    Set(Vec<String>),
+    Legacy(LegacyRolePermissions),
+}
+
+/// Old boolean flags format of [`RolePermissions`].
+#[derive(Deserialize)]
+struct LegacyRolePermissions {
     #[serde(default)]
-    pub spaces: bool,
-    /// Can manage spaces?
+    promote: bool,
     #[serde(default)]
-    pub spaces_manage: bool,
-
-    /// Can create and manage space-related services?
+    wave: bool,
+    #[serde(default)]
+    manage: bool,
+    #[serde(default)]
+    spaces: bool,
     #[serde(default)]
-    pub services: bool,
-    /// Can manage all services and create admin services?
+    spaces_manage: bool,
     #[serde(default)]
-    pub services_manage: bool,
+    spaces_logs_read: bool,
+    #[serde(default)]
+    spaces_logs_manage: bool,
+    #[serde(default)]
+    services: bool,
+    #[serde(default)]
+    services_manage: bool,
+    #[serde(default)]
+    backup: bool,
+}
+
+impl TryFrom<RawRolePermissions> for RolePermissions {
+    type Error = String;
+
+    fn try_from(value: RawRolePermissions) -> Result<Self, Self::Error> {
+        let perms = match value {
+            RawRolePermissions::Set(perms) => perms,
+            RawRolePermissions::Legacy(v) => [
+                (v.promote, perm::USER_PROMOTE),
+                (v.wave, perm::USER_WAVE),
+                (v.manage, perm::USER_MANAGE),
+                (v.spaces, perm::SPACE_CREATE),
+                (v.spaces_manage, perm::SPACE_MANAGE),
+                (v.spaces_logs_read, perm::SPACE_LOGS_READ),
+                (v.spaces_logs_manage, perm::SPACE_LOGS_MANAGE),
+                (v.services, perm::SERVICE_CREATE),
+                (v.services_manage, perm::SERVICE_MANAGE),
+                (v.backup, perm::BACKUP),
+            ]
+            .into_iter()
+            .filter(|(enabled, _)| *enabled)
+            .map(|(_, name)| name.to_string())
+            .collect(),
+        };
+
+        if let Some(v) = perms
+            .iter()
+            .find(|v| !perm::ALL.iter().any(|perm| matches_permission(v, perm)))
+        {
+            return Err(format!("unknown permission `{v}`"));
+        }
+
+        Ok(Self(perms))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(json: &str) -> Result<RolePermissions, serde_json::Error> {
+        serde_json::from_str(json)
+    }
+
+    #[test]
+    fn wildcards() {
+        let perms = parse(r#"["space.*", "user.wave"]"#).unwrap();
+        assert!(perms.has(perm::SPACE_CREATE));
+        assert!(perms.has(perm::SPACE_LOGS_READ));
+        assert!(perms.has(perm::USER_WAVE));
+        assert!(!perms.has(perm::USER_MANAGE));
+
+        let perms = parse(r#"["*"]"#).unwrap();
+        assert!(perm::ALL.iter().all(|v| perms.has(v)));
+    }
+
+    #[test]
+    fn legacy_format() {
+        let perms = parse(r#"{ "spaces": true, "spaces_manage": true, "wave": false }"#).unwrap();
+        assert_eq!(
+            perms,
+            RolePermissions(vec![perm::SPACE_CREATE.into(), perm::SPACE_MANAGE.into()])
+        );
+    }
+
+    #[test]
+    fn promotion_policy() {
+        let roles: UserRoles = serde_json::from_str(
+            r#"[
+                { "name": "Admin", "level": 100, "permissions": ["*"] },
+                { "name": "Moderator", "level": 50, "permissions": ["user.promote"] },
+                { "name": "Default", "level": 0 }
+            ]"#,
+        )
+        .unwrap();
+
+        assert_eq!(roles.check_promotion(100, 0, 50, false), Ok(()));
+        assert_eq!(roles.check_promotion(50, 0, 50, false), Ok(()));
+        assert_eq!(
+            roles.check_promotion(0, 0, 0, false),
+            Err(PromotionError::NoPermission)
+        );
+        assert_eq!(
+            roles.check_promotion(50, 0, 100, false),
+            Err(PromotionError::AboveOwnLevel)
+        );
+        assert_eq!(
+            roles.check_promotion(50, 50, 0, false),
+            Err(PromotionError::TargetLevel)
+        );
+
+        // self
+        assert_eq!(
+            roles.check_promotion(50, 50, 100, true),
+            Err(PromotionError::AboveOwnLevel)
+        );
+        assert_eq!(roles.check_promotion(100, 100, 50, true), Ok(()));
+        assert_eq!(
+            roles.check_promotion(100, 100, 10, true),
+            Err(PromotionError::SelfDemotion)
+        );
+    }
+
+    #[test]
+    fn role_ranges() {
+        let roles: UserRoles = serde_json::from_str(
+            r#"[
+                { "name": "Admin", "level": 100 },
+                { "name": "Default", "level": 0 },
+                { "name": "Spaces", "level": 10 },
+                { "name": "Other", "level": 10 }
+            ]"#,
+        )
+        .unwrap();
+        let ranges: Vec<_> = roles
+            .ranges()
+            .into_iter()
+            .map(|(role, next)| (role.name.as_str(), role.level, next))
+            .collect();
+        assert_eq!(
+            ranges,
+            [
+                ("Default", 0, Some(10)),
+                ("Other", 10, Some(100)),
+                ("Admin", 100, None)
+            ]
+        );
+        assert_eq!(
+            roles.get_current(50).map(|v| v.name.as_str()),
+            Some("Other")
+        );
+    }
+
+    #[test]
+    fn service_types() {
+        let role: UserRole = serde_json::from_str(
+            r#"{ "name": "Kiosks", "level": 5, "service_types": ["space_actor"] }"#,
+        )
+        .unwrap();
+        let types = role.service_types.unwrap();
+        assert!(types.allows(ServiceAccountTy::SpaceActor));
+        assert!(!types.allows(ServiceAccountTy::SpaceManager));
+
+        assert!(serde_json::from_str::<RoleServiceTypes>(r#"["space_actr"]"#).is_err());
+    }
+
+    #[test]
+    fn unknown_permission() {
+        assert!(parse(r#"["space.craete"]"#).is_err());
+        assert!(parse(r#"["spaces.*"]"#).is_err());
+    }
 }
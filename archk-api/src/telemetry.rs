@@ -0,0 +1,63 @@
+//! Logging and optional export of traces to OpenTelemetry collector.
+//!
+//! Request spans of `TraceLayer` and events of sqlx queries inside them are exported
+//! over OTLP/HTTP, so bug reports with trace ID of error (see [`trace_id`]) can be
+//! looked up in Jaeger or Tempo.
+
+use opentelemetry::trace::{TraceContextExt, TracerProvider as _};
+use opentelemetry_otlp::WithExportConfig;
+use opentelemetry_sdk::{trace::SdkTracerProvider, Resource};
+use tracing::level_filters::LevelFilter;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+use tracing_subscriber::{filter::Targets, layer::SubscriberExt, util::SubscriberInitExt, Layer};
+
+use crate::app::AppConfigServerTracing;
+
+/// Install global subscriber logging to stdout and, if `config` is set, exporting
+/// traces. Keep returned provider alive while server runs.
+pub fn init(
+    config: Option<&AppConfigServerTracing>,
+) -> Result<Option<SdkTracerProvider>, opentelemetry_otlp::ExporterBuildError> {
+    let fmt = tracing_subscriber::fmt::layer().with_filter(LevelFilter::INFO);
+
+    let Some(config) = config else {
+        tracing_subscriber::registry().with(fmt).init();
+        return Ok(None);
+    };
+
+    let exporter = opentelemetry_otlp::SpanExporter::builder()
+        .with_http()
+        .with_endpoint(&config.otlp_endpoint)
+        .build()?;
+    let provider = SdkTracerProvider::builder()
+        .with_batch_exporter(exporter)
+        .with_resource(
+            Resource::builder()
+                .with_service_name(config.service_name.clone())
+                .build(),
+        )
+        .build();
+
+    // request spans of `TraceLayer` and sqlx query events are on debug level
+    let otel = tracing_opentelemetry::layer()
+        .with_tracer(provider.tracer("archk-api"))
+        .with_filter(
+            Targets::new()
+                .with_default(LevelFilter::INFO)
+                .with_target("tower_http", LevelFilter::DEBUG)
+                .with_target("sqlx", LevelFilter::DEBUG),
+        );
+    tracing_subscriber::registry().with(fmt).with(otel).init();
+
+    Ok(Some(provider))
+}
+
+/// Trace ID of current span in hex, if traces are exported.
+pub fn trace_id() -> Option<String> {
+    let context = tracing::Span::current().context();
+    let span = context.span();
+    let span_context = span.span_context();
+    span_context
+        .is_valid()
+        .then(|| span_context.trace_id().to_string())
+}
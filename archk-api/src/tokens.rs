@@ -0,0 +1,81 @@
+//! Storage of tokens. Database keeps only SHA-256 of token string (`hash` column of
+//! `tokens` and `service_tokens`), so leaked database does not give usable tokens.
+//!
+//! Tokens issued before hashing are stored as `(iat, rnd)` and converted by
+//! [`upgrade_personal`] and [`upgrade_service`] on first use.
+
+use archk::v1::auth::{Token, TokenFormat};
+use sha2::{Digest, Sha256};
+use sqlx::SqlitePool;
+use subtle::ConstantTimeEq;
+
+/// Hash of token stored in database.
+pub fn hash(token: &Token) -> Vec<u8> {
+    Sha256::digest(token.to_string().as_bytes()).to_vec()
+}
+
+/// Compare stored hash with token in constant time.
+pub fn matches(hash: &[u8], token: &Token) -> bool {
+    self::hash(token).ct_eq(hash).into()
+}
+
+/// Replace `rnd` of personal token issued before hashing with its hash. Does nothing
+/// if there is no such token.
+pub async fn upgrade_personal(db: &SqlitePool, token: &Token, hash: &[u8]) {
+    // only `v1` tokens were issued before hashing
+    if token.format != TokenFormat::V1 {
+        return;
+    }
+    let iat = token.iat as i64;
+    let rnd = token.rnd as i64;
+    sqlx::query!(
+        "UPDATE tokens SET hash = ?, rnd = NULL WHERE iat = ? AND rnd = ? AND hash IS NULL",
+        hash,
+        iat,
+        rnd
+    )
+    .execute(db)
+    .await
+    .expect("database");
+}
+
+/// Same as [`upgrade_personal`], but for service tokens.
+pub async fn upgrade_service(db: &SqlitePool, token: &Token, hash: &[u8]) {
+    if token.format != TokenFormat::V1 {
+        return;
+    }
+    let iat = token.iat as i64;
+    let rnd = token.rnd as i64;
+    sqlx::query!(
+        "UPDATE service_tokens SET hash = ?, rnd = NULL
+        WHERE iat = ? AND rnd = ? AND hash IS NULL",
+        hash,
+        iat,
+        rnd
+    )
+    .execute(db)
+    .await
+    .expect("database");
+}
+
+#[cfg(test)]
+mod test {
+    use archk::v1::auth::TokenTy;
+
+    use super::*;
+
+    #[test]
+    fn hash_matches_only_own_token() {
+        let token = Token::new(TokenTy::Personal);
+        let other = Token {
+            rnd: token.rnd.wrapping_add(1),
+            ..token.clone()
+        };
+        let hash = hash(&token);
+
+        assert_eq!(hash.len(), 32);
+        assert!(matches(&hash, &token));
+        assert!(!matches(&hash, &other));
+        assert!(!matches(&hash[..16], &token));
+    }
+}
@@ -0,0 +1,382 @@
+//! Weekly access windows limiting when accounts may unlock space, see [`AccessWindow`].
+
+use archk::{
+    v1::{
+        api::{self, Response},
+        models::MayIgnored,
+        space::{AccessWindow, SpaceID, TimeOfDay, Weekday},
+    },
+    Documentation,
+};
+use axum::extract::{Query, State};
+use chrono::{DateTime, Datelike, Timelike};
+use serde::{Deserialize, Serialize};
+
+use crate::app::{self, AppState};
+
+use super::{
+    extra::{Json, Path, SpaceAccess},
+    space::{archived_conflict, Paging},
+};
+
+/// Maximum number of access windows in space, including windows of accounts
+const MAX_ACCESS_WINDOWS: i64 = 100;
+
+#[derive(Deserialize, Documentation)]
+pub struct SpaceAccessWindowPath {
+    /// Space ID
+    #[allow(dead_code)] // read by `SpaceAccess`
+    pub space_id: SpaceID,
+    /// Access window ID
+    pub window_id: String,
+}
+
+#[derive(Serialize, Documentation)]
+pub struct SpaceAccessWindow {
+    /// Access window ID
+    pub id: String,
+    /// Platform ID of account if window is attached to account, otherwise window
+    /// applies to all accounts without own windows
+    pub pl_id: Option<String>,
+    /// Window rule
+    pub window: AccessWindow,
+    /// Creation timestamp in milliseconds
+    pub created_at: i64,
+}
+
+#[derive(Deserialize, Documentation)]
+pub struct CreateAccessWindowBody {
+    /// Platform ID of account to attach window to. Omit to attach to whole space
+    #[serde(default)]
+    pub pl_id: Option<String>,
+    /// Window rule
+    pub window: AccessWindow,
+}
+
+#[derive(Deserialize, Documentation)]
+pub struct PatchAccessWindowBody {
+    /// Days of week window starts on
+    #[serde(default, skip_serializing_if = "MayIgnored::is_ignored")]
+    pub days: MayIgnored<Vec<Weekday>>,
+    /// Local start time, eg. `08:00`
+    #[serde(default, skip_serializing_if = "MayIgnored::is_ignored")]
+    pub start: MayIgnored<TimeOfDay>,
+    /// Local end time (exclusive), eg. `20:00`
+    #[serde(default, skip_serializing_if = "MayIgnored::is_ignored")]
+    pub end: MayIgnored<TimeOfDay>,
+    /// IANA name of timezone, eg. `Europe/Berlin`
+    #[serde(default, skip_serializing_if = "MayIgnored::is_ignored")]
+    pub timezone: MayIgnored<String>,
+}
+
+fn days_mask(days: &[Weekday]) -> i64 {
+    days.iter().fold(0, |acc, &day| acc | 1 << u8::from(day))
+}
+
+fn days_from_mask(mask: i64) -> Vec<Weekday> {
+    Weekday::ALL
+        .into_iter()
+        .filter(|&day| mask & 1 << u8::from(day) != 0)
+        .collect()
+}
+
+fn check_window(window: &AccessWindow) -> Result<(), api::ErrorData> {
+    if window.days.is_empty() {
+        return Err(api::Error::MalformedData.detail("`days` should not be empty".into()));
+    }
+    if window.timezone.parse::<chrono_tz::Tz>().is_err() {
+        return Err(api::Error::MalformedData.detail("`timezone` is unknown".into()));
+    }
+    Ok(())
+}
+
+/// Is timestamp in milliseconds `now` within window, in timezone of window?
+fn window_contains(window: &AccessWindow, now: i64) -> bool {
+    // timezone is checked on creation, but may be removed from newer tz database
+    let tz: chrono_tz::Tz = window.timezone.parse().unwrap_or(chrono_tz::UTC);
+    let Some(now) = DateTime::from_timestamp_millis(now) else {
+        return false;
+    };
+    let now = now.with_timezone(&tz);
+    let day = Weekday::ALL[now.weekday().num_days_from_monday() as usize];
+    let time =
+        TimeOfDay::from_minutes((now.hour() * 60 + now.minute()) as u16).expect("time of day");
+    window.contains(day, time)
+}
+
+/// Does account have access windows (or space, if account has none) and none of them
+/// contains timestamp in milliseconds `now`? See `UnlockFacts::outside_access_windows`.
+pub(crate) async fn outside_access_windows(
+    db: &sqlx::SqlitePool,
+    space_id: &str,
+    pl_id: &str,
+    now: i64,
+) -> Result<bool, sqlx::Error> {
+    let res = sqlx::query!(
+        r#"
+        SELECT pl_id IS NOT NULL AS "own!: bool", days, start_min, end_min, timezone
+        FROM spaces_access_windows
+        WHERE space_id = ? AND (pl_id IS NULL OR pl_id = ?)"#,
+        space_id,
+        pl_id
+    )
+    .fetch_all(db)
+    .await?;
+
+    // own windows of account replace windows of space
+    let own = res.iter().any(|v| v.own);
+    let mut windows = res
+        .into_iter()
+        .filter(|v| v.own == own)
+        .map(|v| AccessWindow {
+            days: days_from_mask(v.days),
+            start: TimeOfDay::from_minutes(v.start_min as u16).expect("database time"),
+            end: TimeOfDay::from_minutes(v.end_min as u16).expect("database time"),
+            timezone: v.timezone,
+        });
+
+    Ok(match windows.next() {
+        Some(first) => !window_contains(&first, now) && !windows.any(|v| window_contains(&v, now)),
+        None => false,
+    })
+}
+
+pub async fn get_access_windows(
+    SpaceAccess { space_id, .. }: SpaceAccess,
+    Query(Paging { page }): Query<Paging>,
+    State(AppState { db, .. }): State<AppState>,
+) -> Response<Vec<SpaceAccessWindow>> {
+    let space_id: &str = &space_id;
+    let limit = 50;
+    let offset = (page as i64) * limit;
+    let res = sqlx::query!(
+        r#"
+        SELECT id, pl_id, days, start_min, end_min, timezone, created_at
+        FROM spaces_access_windows
+        WHERE space_id = ?
+        ORDER BY created_at
+        LIMIT ? OFFSET ?"#,
+        space_id,
+        limit,
+        offset
+    )
+    .fetch_all(&db)
+    .await
+    .expect("database");
+
+    Response::Success(
+        res.into_iter()
+            .map(|v| SpaceAccessWindow {
+                id: v.id,
+                pl_id: v.pl_id,
+                window: AccessWindow {
+                    days: days_from_mask(v.days),
+                    start: TimeOfDay::from_minutes(v.start_min as u16).expect("database time"),
+                    end: TimeOfDay::from_minutes(v.end_min as u16).expect("database time"),
+                    timezone: v.timezone,
+                },
+                created_at: v.created_at,
+            })
+            .collect(),
+    )
+}
+
+pub async fn create_access_window(
+    SpaceAccess {
+        space_id, archived, ..
+    }: SpaceAccess,
+    State(AppState { db, .. }): State<AppState>,
+    Json(CreateAccessWindowBody { pl_id, window }): Json<CreateAccessWindowBody>,
+) -> Response<SpaceAccessWindow> {
+    if archived {
+        return Response::Failture(archived_conflict());
+    }
+    if let Err(e) = check_window(&window) {
+        return Response::Failture(e);
+    }
+
+    let space_id: &str = &space_id;
+    let count = sqlx::query_scalar!(
+        r#"SELECT COUNT(1) AS "count!: i64" FROM spaces_access_windows WHERE space_id = ?"#,
+        space_id
+    )
+    .fetch_one(&db)
+    .await
+    .expect("database");
+    if count >= MAX_ACCESS_WINDOWS {
+        return Response::Failture(api::Error::Conflict.detail(
+            format!("space can't have more than {MAX_ACCESS_WINDOWS} access windows").into(),
+        ));
+    }
+
+    let res = SpaceAccessWindow {
+        id: cuid2::create_id(),
+        pl_id,
+        created_at: app::now_ms(),
+        window,
+    };
+    let days = days_mask(&res.window.days);
+    let start_min = res.window.start.minutes();
+    let end_min = res.window.end.minutes();
+    let insert = sqlx::query!(
+        r#"
+        INSERT INTO spaces_access_windows(id, space_id, pl_id, days, start_min, end_min, timezone, created_at)
+        VALUES (?, ?, ?, ?, ?, ?, ?, ?)"#,
+        res.id,
+        space_id,
+        res.pl_id,
+        days,
+        start_min,
+        end_min,
+        res.window.timezone,
+        res.created_at
+    )
+    .execute(&db)
+    .await;
+
+    match insert {
+        Ok(_) => Response::Success(res),
+        Err(sqlx::Error::Database(err)) if err.is_foreign_key_violation() => {
+            Response::Failture(api::Error::ObjectNotFound.detail("account does not exists".into()))
+        }
+        Err(e) => panic!("database error: {e}"),
+    }
+}
+
+pub async fn patch_access_window(
+    Path(SpaceAccessWindowPath { window_id, .. }): Path<SpaceAccessWindowPath>,
+    SpaceAccess {
+        space_id, archived, ..
+    }: SpaceAccess,
+    State(AppState { db, .. }): State<AppState>,
+    Json(PatchAccessWindowBody {
+        days,
+        start,
+        end,
+        timezone,
+    }): Json<PatchAccessWindowBody>,
+) -> Response<SpaceAccessWindow> {
+    if archived {
+        return Response::Failture(archived_conflict());
+    }
+    if days.is_ignored() && start.is_ignored() && end.is_ignored() && timezone.is_ignored() {
+        return Response::Failture(
+            api::Error::MalformedData.detail("expected at least one subject to change".into()),
+        );
+    }
+
+    let space_id: &str = &space_id;
+    let res = sqlx::query!(
+        r#"
+        SELECT id, pl_id, days, start_min, end_min, timezone, created_at
+        FROM spaces_access_windows
+        WHERE id = ? AND space_id = ?"#,
+        window_id,
+        space_id
+    )
+    .fetch_optional(&db)
+    .await
+    .expect("database");
+    let Some(v) = res else {
+        return Response::Failture(api::Error::ObjectNotFound.into());
+    };
+
+    let window = AccessWindow {
+        days: days.ok().unwrap_or_else(|| days_from_mask(v.days)),
+        start: start
+            .ok()
+            .unwrap_or_else(|| TimeOfDay::from_minutes(v.start_min as u16).expect("database time")),
+        end: end
+            .ok()
+            .unwrap_or_else(|| TimeOfDay::from_minutes(v.end_min as u16).expect("database time")),
+        timezone: timezone.ok().unwrap_or(v.timezone),
+    };
+    if let Err(e) = check_window(&window) {
+        return Response::Failture(e);
+    }
+
+    let days = days_mask(&window.days);
+    let start_min = window.start.minutes();
+    let end_min = window.end.minutes();
+    sqlx::query!(
+        r#"
+        UPDATE spaces_access_windows
+        SET days = ?, start_min = ?, end_min = ?, timezone = ?
+        WHERE id = ? AND space_id = ?"#,
+        days,
+        start_min,
+        end_min,
+        window.timezone,
+        window_id,
+        space_id
+    )
+    .execute(&db)
+    .await
+    .expect("database");
+
+    Response::Success(SpaceAccessWindow {
+        id: v.id,
+        pl_id: v.pl_id,
+        window,
+        created_at: v.created_at,
+    })
+}
+
+pub async fn delete_access_window(
+    Path(SpaceAccessWindowPath { window_id, .. }): Path<SpaceAccessWindowPath>,
+    SpaceAccess {
+        space_id, archived, ..
+    }: SpaceAccess,
+    State(AppState { db, .. }): State<AppState>,
+) -> Response<u64> {
+    if archived {
+        return Response::Failture(archived_conflict());
+    }
+    let space_id: &str = &space_id;
+    let res = sqlx::query!(
+        "DELETE FROM spaces_access_windows WHERE id = ? AND space_id = ?",
+        window_id,
+        space_id
+    )
+    .execute(&db)
+    .await
+    .expect("database")
+    .rows_affected();
+
+    if res == 0 {
+        Response::Failture(api::Error::ObjectNotFound.into())
+    } else {
+        Response::Success(res)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn window_in_timezone() {
+        let time = |v: &str| TimeOfDay::try_from(v.to_string()).unwrap();
+        let window = AccessWindow {
+            days: vec![
+                Weekday::Mon,
+                Weekday::Tue,
+                Weekday::Wed,
+                Weekday::Thu,
+                Weekday::Fri,
+            ],
+            start: time("08:00"),
+            end: time("20:00"),
+            timezone: "Asia/Tokyo".to_string(),
+        };
+        assert_eq!(days_from_mask(days_mask(&window.days)), window.days);
+
+        // 2024-01-01 (Monday) 00:00 UTC is 09:00 in Tokyo
+        let monday = 1704067200000;
+        assert!(window_contains(&window, monday));
+        // 12:00 UTC is 21:00 in Tokyo
+        assert!(!window_contains(&window, monday + 12 * 3600 * 1000));
+        // Sunday 23:30 UTC is Monday 08:30 in Tokyo
+        assert!(window_contains(&window, monday - 1800 * 1000));
+    }
+}
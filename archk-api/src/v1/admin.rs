@@ -0,0 +1,336 @@
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use archk::{
+    v1::{
+        api,
+        audit::{AuditAction, AuditLog},
+        service::ServiceAccountTy,
+    },
+    Documentation,
+};
+use axum::{
+    body::Body,
+    extract::{Query, State},
+    http::header::{CONTENT_DISPOSITION, CONTENT_TYPE},
+    response::IntoResponse,
+};
+use serde::{Deserialize, Serialize};
+use tokio::io::AsyncReadExt;
+use tokio_stream::wrappers::ReceiverStream;
+use uuid::Uuid;
+
+use crate::{
+    app::{self, AppState},
+    roles::perm,
+    Migration,
+};
+
+use super::{
+    extra::{AuthenticatedUser, DbUser, ReadDb},
+    user::Paging,
+};
+
+/// Size of chunks snapshot is streamed by
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Maximum number of days in [`InstanceStats::events_per_day`]
+const MAX_STATS_DAYS: u32 = 366;
+
+const DAY_MS: i64 = 24 * 60 * 60 * 1000;
+
+#[derive(Deserialize, Documentation)]
+pub struct StatsQuery {
+    /// Number of days to count events for, `30` by default, up to `366`
+    #[serde(default = "default_stats_days")]
+    pub days: u32,
+}
+
+fn default_stats_days() -> u32 {
+    30
+}
+
+#[derive(Serialize, Documentation)]
+pub struct InstanceStats {
+    /// Number of users
+    pub users: u64,
+    /// Number of personal tokens (sessions)
+    pub tokens: u64,
+    /// Number of service tokens
+    pub service_tokens: u64,
+    /// Number of spaces, including archived
+    pub spaces: u64,
+    /// Number of archived spaces
+    pub archived_spaces: u64,
+    /// Number of items in all spaces
+    pub items: u64,
+    /// Number of services by type
+    pub services: Vec<ServiceTypeStats>,
+    /// Number of space log entries per day, oldest first. Days without events are omitted
+    pub events_per_day: Vec<DailyEvents>,
+    /// Database statistics
+    pub database: DatabaseStats,
+}
+
+#[derive(Serialize, Documentation)]
+pub struct ServiceTypeStats {
+    /// Service type, see `archk::v1::service::ServiceAccountTy`
+    pub ty: ServiceAccountTy,
+    /// Number of services
+    pub count: u64,
+}
+
+#[derive(Serialize, Documentation)]
+pub struct DailyEvents {
+    /// Timestamp in milliseconds of day start (UTC)
+    pub day: i64,
+    /// Number of space log entries
+    pub count: u64,
+}
+
+#[derive(Serialize, Documentation)]
+pub struct DatabaseStats {
+    /// Size of database file in bytes
+    pub size: u64,
+    /// Size of unused pages in bytes, can be reclaimed by `VACUUM`
+    pub free: u64,
+    /// SQLite journal mode, eg. `wal`
+    pub journal_mode: String,
+    /// Time in milliseconds spent to collect these statistics. Grows with
+    /// database size and load, so can be used as a rough latency indicator
+    pub query_ms: u64,
+}
+
+/// Make consistent snapshot of database with `VACUUM INTO` and stream it as file.
+/// Snapshot is written to temporary file, which is removed right after opening.
+pub async fn backup(
+    AuthenticatedUser {
+        user: DbUser { level, .. },
+        ..
+    }: AuthenticatedUser<DbUser>,
+    State(AppState { db, roles, .. }): State<AppState>,
+) -> axum::response::Response {
+    if !roles.load().has(level, perm::BACKUP) {
+        return api::Response::<api::NeverSerialize>::Failture(api::Error::Forbidden.into())
+            .into_response();
+    }
+
+    let path = std::env::temp_dir().join(format!("archk-backup-{}.db", Uuid::new_v4()));
+    let path_str = path.to_string_lossy().into_owned();
+
+    sqlx::query("VACUUM INTO ?")
+        .bind(&path_str)
+        .execute(&db)
+        .await
+        .expect("database");
+
+    let file = tokio::fs::File::open(&path).await;
+    let _ = tokio::fs::remove_file(&path).await;
+    let mut file = file.expect("backup file");
+
+    let (tx, rx) = tokio::sync::mpsc::channel::<Result<Vec<u8>, std::io::Error>>(4);
+    tokio::spawn(async move {
+        loop {
+            let mut chunk = vec![0; CHUNK_SIZE];
+            let chunk = match file.read(&mut chunk).await {
+                Ok(0) => return,
+                Ok(n) => {
+                    chunk.truncate(n);
+                    Ok(chunk)
+                }
+                Err(e) => Err(e),
+            };
+            let failed = chunk.is_err();
+            if tx.send(chunk).await.is_err() || failed {
+                return;
+            }
+        }
+    });
+
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("Current system time less than UNIX epoch")
+        .as_millis();
+
+    (
+        [
+            (CONTENT_TYPE, "application/vnd.sqlite3".to_string()),
+            (
+                CONTENT_DISPOSITION,
+                format!("attachment; filename=\"archk-{now}.db\""),
+            ),
+        ],
+        Body::from_stream(ReceiverStream::new(rx)),
+    )
+        .into_response()
+}
+
+/// Get integer value of SQLite `PRAGMA`.
+async fn pragma(db: &sqlx::SqlitePool, name: &str) -> i64 {
+    sqlx::query_scalar(&format!("PRAGMA {name}"))
+        .fetch_one(db)
+        .await
+        .expect("database")
+}
+
+pub async fn get_stats(
+    Query(StatsQuery { days }): Query<StatsQuery>,
+    AuthenticatedUser {
+        user: DbUser { level, .. },
+        ..
+    }: AuthenticatedUser<DbUser>,
+    State(AppState { db, roles, .. }): State<AppState>,
+) -> api::Response<InstanceStats> {
+    if !roles.load().has(level, perm::STATS) {
+        return api::Response::Failture(api::Error::Forbidden.into());
+    }
+
+    let started = Instant::now();
+    let totals = sqlx::query!(
+        r#"
+        SELECT
+            (SELECT COUNT(1) FROM users) AS "users!: i64",
+            (SELECT COUNT(1) FROM tokens) AS "tokens!: i64",
+            (SELECT COUNT(1) FROM service_tokens) AS "service_tokens!: i64",
+            (SELECT COUNT(1) FROM spaces) AS "spaces!: i64",
+            (SELECT COUNT(1) FROM spaces WHERE archived) AS "archived_spaces!: i64",
+            (SELECT COUNT(1) FROM spaces_items) AS "items!: i64"
+        "#
+    )
+    .fetch_one(&db)
+    .await
+    .expect("database");
+
+    let services = sqlx::query!(
+        r#"SELECT ty, COUNT(1) AS "count!: i64" FROM service_accounts GROUP BY ty ORDER BY ty"#
+    )
+    .fetch_all(&db)
+    .await
+    .expect("database")
+    .into_iter()
+    .map(|v| ServiceTypeStats {
+        ty: ServiceAccountTy::try_from(v.ty).expect("invalid service type in database"),
+        count: v.count as u64,
+    })
+    .collect();
+
+    let now = app::now_ms();
+    let since = (now / DAY_MS - days.min(MAX_STATS_DAYS) as i64 + 1) * DAY_MS;
+    let events_per_day = sqlx::query!(
+        r#"
+        SELECT created_at / ?1 AS "day!: i64", COUNT(1) AS "count!: i64"
+        FROM spaces_logs
+        WHERE created_at >= ?2
+        GROUP BY 1
+        ORDER BY 1"#,
+        DAY_MS,
+        since
+    )
+    .fetch_all(&db)
+    .await
+    .expect("database")
+    .into_iter()
+    .map(|v| DailyEvents {
+        day: v.day * DAY_MS,
+        count: v.count as u64,
+    })
+    .collect();
+
+    let page_size = pragma(&db, "page_size").await as u64;
+    let size = pragma(&db, "page_count").await as u64 * page_size;
+    let free = pragma(&db, "freelist_count").await as u64 * page_size;
+    let journal_mode = sqlx::query_scalar::<_, String>("PRAGMA journal_mode")
+        .fetch_one(&db)
+        .await
+        .expect("database");
+
+    api::Response::Success(InstanceStats {
+        users: totals.users as u64,
+        tokens: totals.tokens as u64,
+        service_tokens: totals.service_tokens as u64,
+        spaces: totals.spaces as u64,
+        archived_spaces: totals.archived_spaces as u64,
+        items: totals.items as u64,
+        services,
+        events_per_day,
+        database: DatabaseStats {
+            size,
+            free,
+            journal_mode,
+            query_ms: started.elapsed().as_millis() as u64,
+        },
+    })
+}
+
+pub async fn get_migrations(
+    AuthenticatedUser {
+        user: DbUser { level, .. },
+        ..
+    }: AuthenticatedUser<DbUser>,
+    State(AppState { db, roles, .. }): State<AppState>,
+) -> api::Response<Vec<Migration>> {
+    if !roles.load().has(level, perm::STATS) {
+        return api::Response::Failture(api::Error::Forbidden.into());
+    }
+
+    api::Response::Success(crate::migrations(&db).await.expect("database"))
+}
+
+pub(crate) async fn insert_audit<'e, E>(db: E, log: &AuditLog) -> Result<(), sqlx::Error>
+where
+    E: sqlx::Executor<'e, Database = sqlx::Sqlite>,
+{
+    let act: i64 = log.act.into();
+
+    sqlx::query!(
+        r#"
+        INSERT INTO audit_log(id, created_at, actor_id, act, target_id, detail, ip)
+        VALUES (?, ?, ?, ?, ?, ?, ?)
+        "#,
+        log.id,
+        log.created_at,
+        log.actor_id,
+        act,
+        log.target_id,
+        log.detail,
+        log.ip
+    )
+    .execute(db)
+    .await
+    .map(drop)
+}
+
+pub async fn get_audit_log(
+    Query(Paging { page }): Query<Paging>,
+    AuthenticatedUser {
+        user: DbUser { level, .. },
+        ..
+    }: AuthenticatedUser<DbUser>,
+    State(AppState { roles, .. }): State<AppState>,
+    ReadDb(db): ReadDb,
+) -> api::Response<Vec<AuditLog>> {
+    if !roles.load().has(level, perm::USER_MANAGE) {
+        return api::Response::Failture(api::Error::Forbidden.into());
+    }
+
+    let (offset, limit) = ((page as i64) * 50, 50);
+    let res = sqlx::query!(
+        "SELECT * FROM audit_log ORDER BY created_at DESC, id LIMIT ? OFFSET ?",
+        limit,
+        offset
+    )
+    .fetch_all(&db)
+    .await
+    .expect("database")
+    .into_iter()
+    .map(|v| AuditLog {
+        id: v.id,
+        created_at: v.created_at,
+        actor_id: v.actor_id,
+        act: AuditAction::try_from(v.act).expect("invalid audit action in database"),
+        target_id: v.target_id,
+        detail: v.detail,
+        ip: v.ip,
+    });
+
+    api::Response::Success(res.collect())
+}
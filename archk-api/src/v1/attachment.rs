@@ -0,0 +1,370 @@
+use archk::{
+    v1::{
+        api::{self, Response},
+        space::{SpaceID, SpaceItemID},
+    },
+    Documentation,
+};
+use axum::{
+    body::Body,
+    extract::{Query, State},
+    http::{
+        header::{CONTENT_DISPOSITION, CONTENT_TYPE, X_CONTENT_TYPE_OPTIONS},
+        HeaderMap, HeaderValue,
+    },
+    response::IntoResponse,
+};
+use http_body_util::{BodyExt, LengthLimitError};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app::{self, AppState},
+    storage::Attachments,
+};
+
+use super::{
+    extra::{Path, SpaceAccess},
+    space::{archived_conflict, SpaceItemPath},
+};
+
+#[derive(Deserialize, Documentation)]
+pub struct SpaceAttachmentPath {
+    /// Space ID
+    #[allow(dead_code)] // read by `SpaceAccess`
+    pub space_id: SpaceID,
+    /// Item ID
+    pub item_id: SpaceItemID,
+    /// Attachment ID
+    pub attachment_id: String,
+}
+
+#[derive(Deserialize, Documentation)]
+pub struct UploadAttachmentQuery {
+    /// File name, eg. `manual.pdf`. Shouldn't contain `/`, `\`, `"` and control
+    /// characters
+    #[doc_min = 1]
+    #[doc_max = 255]
+    pub name: String,
+}
+
+#[derive(Serialize, Documentation)]
+pub struct SpaceAttachment {
+    /// Attachment ID
+    pub id: String,
+    /// ID of item
+    pub item_id: String,
+    /// File name
+    pub name: String,
+    /// MIME type of file
+    pub mime: String,
+    /// Size of file in bytes
+    pub size: i64,
+    /// Timestamp in milliseconds of upload
+    pub created_at: i64,
+}
+
+fn attachments_not_configured() -> api::ErrorData {
+    api::Error::ObjectNotFound.detail("attachments are not configured".into())
+}
+
+fn storage_error(err: std::io::Error) -> api::ErrorData {
+    tracing::error!(%err, "Attachments storage failed");
+    api::Error::ServiceUnavailable.detail("attachments storage is unavailable".into())
+}
+
+/// Storage key of attachment file.
+fn storage_key(space_id: &str, attachment_id: &str) -> String {
+    format!("{space_id}/{attachment_id}")
+}
+
+fn check_name(name: &str) -> Result<(), api::ErrorData> {
+    if name.is_empty() || name.len() > 255 {
+        return Err(
+            api::Error::MalformedData.detail("`name` should be from 1 to 255 bytes long".into())
+        );
+    }
+    if name.contains(['/', '\\', '"']) || name.chars().any(char::is_control) {
+        return Err(api::Error::MalformedData
+            .detail("`name` shouldn't contain `/`, `\\`, `\"` and control characters".into()));
+    }
+    Ok(())
+}
+
+/// Remove file of deleted attachment from storage. On failure file is left for
+/// background jobs, see [`crate::jobs`].
+pub(crate) async fn purge_file(
+    attachments: &Attachments,
+    db: &sqlx::SqlitePool,
+    key: &str,
+) -> std::io::Result<()> {
+    attachments.storage.delete(key).await?;
+    sqlx::query!(
+        "DELETE FROM attachments_deletions WHERE storage_key = ?",
+        key
+    )
+    .execute(db)
+    .await
+    .expect("database");
+    Ok(())
+}
+
+pub async fn get_attachments(
+    Path(SpaceItemPath { item_id, .. }): Path<SpaceItemPath>,
+    SpaceAccess { space_id, .. }: SpaceAccess,
+    State(AppState { db, .. }): State<AppState>,
+) -> Response<Vec<SpaceAttachment>> {
+    let item_id: &str = &item_id;
+    let space_id: &str = &space_id;
+    let res = sqlx::query_as!(
+        SpaceAttachment,
+        "
+        SELECT id, item_id, name, mime, size, created_at FROM spaces_attachments
+        WHERE space_id = ? AND item_id = ?
+        ORDER BY created_at",
+        space_id,
+        item_id
+    )
+    .fetch_all(&db)
+    .await
+    .expect("database");
+
+    Response::Success(res)
+}
+
+pub async fn upload_attachment(
+    Path(SpaceItemPath { item_id, .. }): Path<SpaceItemPath>,
+    SpaceAccess {
+        space_id, archived, ..
+    }: SpaceAccess,
+    State(AppState {
+        db, attachments, ..
+    }): State<AppState>,
+    Query(UploadAttachmentQuery { name }): Query<UploadAttachmentQuery>,
+    headers: HeaderMap,
+    body: Body,
+) -> Response<SpaceAttachment> {
+    let item_id: &str = &item_id;
+    let Some(attachments) = attachments else {
+        return Response::Failture(attachments_not_configured());
+    };
+    if archived {
+        return Response::Failture(archived_conflict());
+    }
+    if let Err(e) = check_name(&name) {
+        return Response::Failture(e);
+    }
+    let mime = headers
+        .get(CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| {
+            v.split(';')
+                .next()
+                .unwrap_or_default()
+                .trim()
+                .to_ascii_lowercase()
+        })
+        .unwrap_or_default();
+    if !attachments.allows_mime(&mime) {
+        return Response::Failture(api::Error::MalformedData.detail(
+            format!("files of type `{mime}` are not allowed, pass type in `Content-Type`").into(),
+        ));
+    }
+
+    let space_id: &str = &space_id;
+    let res = sqlx::query!(
+        r#"
+        SELECT (SELECT COUNT(*) FROM spaces_attachments WHERE item_id = spaces_items.id) AS "count!: i64"
+        FROM spaces_items
+        WHERE id = ? AND space_id = ?"#,
+        item_id,
+        space_id
+    )
+    .fetch_optional(&db)
+    .await
+    .expect("database");
+    let Some(res) = res else {
+        return Response::Failture(api::Error::ObjectNotFound.into());
+    };
+    if attachments.max_per_item != 0 && res.count as u64 >= attachments.max_per_item {
+        return Response::Failture(
+            api::Error::Conflict.detail(
+                format!(
+                    "item already has maximum of {} attachments",
+                    attachments.max_per_item
+                )
+                .into(),
+            ),
+        );
+    }
+
+    // body is limited to `max_size` by `limit_body` middleware
+    let data = match body.collect().await {
+        Ok(v) => v.to_bytes(),
+        Err(err) => {
+            let too_large =
+                std::iter::successors(Some(&err as &(dyn std::error::Error + 'static)), |e| {
+                    e.source()
+                })
+                .any(|e| e.is::<LengthLimitError>());
+            return Response::Failture(if too_large {
+                api::Error::PayloadTooLarge
+                    .detail(format!("file is larger than {} bytes", attachments.max_size).into())
+            } else {
+                api::Error::MalformedData.detail(err.to_string().into())
+            });
+        }
+    };
+    if data.is_empty() {
+        return Response::Failture(api::Error::MalformedData.detail("file is empty".into()));
+    }
+
+    let id = cuid2::create_id();
+    let key = storage_key(space_id, &id);
+    let size = data.len() as i64;
+    if let Err(err) = attachments.storage.put(&key, &mime, data).await {
+        return Response::Failture(storage_error(err));
+    }
+
+    let created_at = app::now_ms();
+    // item may be deleted during upload
+    let res = sqlx::query!(
+        "
+        INSERT INTO spaces_attachments(id, space_id, item_id, name, mime, size, created_at)
+        SELECT ?, space_id, id, ?, ?, ?, ? FROM spaces_items WHERE id = ? AND space_id = ?",
+        id,
+        name,
+        mime,
+        size,
+        created_at,
+        item_id,
+        space_id
+    )
+    .execute(&db)
+    .await
+    .expect("database");
+    if res.rows_affected() == 0 {
+        if let Err(err) = attachments.storage.delete(&key).await {
+            tracing::warn!(%err, key, "Failed to remove file of attachment of deleted item");
+        }
+        return Response::Failture(api::Error::ObjectNotFound.into());
+    }
+
+    Response::Success(SpaceAttachment {
+        id,
+        item_id: item_id.into(),
+        name,
+        mime,
+        size,
+        created_at,
+    })
+}
+
+pub async fn download_attachment(
+    Path(SpaceAttachmentPath {
+        item_id,
+        attachment_id,
+        ..
+    }): Path<SpaceAttachmentPath>,
+    SpaceAccess { space_id, .. }: SpaceAccess,
+    State(AppState {
+        db, attachments, ..
+    }): State<AppState>,
+) -> axum::response::Response {
+    let item_id: &str = &item_id;
+    let Some(attachments) = attachments else {
+        return Response::<api::NeverSerialize>::Failture(attachments_not_configured())
+            .into_response();
+    };
+    let space_id: &str = &space_id;
+    let res = sqlx::query!(
+        "
+        SELECT name, mime FROM spaces_attachments
+        WHERE id = ? AND item_id = ? AND space_id = ?",
+        attachment_id,
+        item_id,
+        space_id
+    )
+    .fetch_optional(&db)
+    .await
+    .expect("database");
+    let Some(res) = res else {
+        return Response::<api::NeverSerialize>::Failture(api::Error::ObjectNotFound.into())
+            .into_response();
+    };
+
+    let data = match attachments
+        .storage
+        .get(&storage_key(space_id, &attachment_id))
+        .await
+    {
+        Ok(Some(v)) => v,
+        Ok(None) => {
+            tracing::error!(attachment_id, "File of attachment is missing in storage");
+            return Response::<api::NeverSerialize>::Failture(
+                api::Error::Gone.detail("file is missing in storage".into()),
+            )
+            .into_response();
+        }
+        Err(err) => {
+            return Response::<api::NeverSerialize>::Failture(storage_error(err)).into_response()
+        }
+    };
+
+    let content_type = HeaderValue::from_str(&res.mime)
+        .unwrap_or(HeaderValue::from_static("application/octet-stream"));
+    // name is checked on upload, so it can be quoted as is
+    let disposition = HeaderValue::from_str(&format!("inline; filename=\"{}\"", res.name))
+        .unwrap_or(HeaderValue::from_static("inline"));
+    (
+        [
+            (CONTENT_TYPE, content_type),
+            (CONTENT_DISPOSITION, disposition),
+            (X_CONTENT_TYPE_OPTIONS, HeaderValue::from_static("nosniff")),
+        ],
+        data,
+    )
+        .into_response()
+}
+
+pub async fn delete_attachment(
+    Path(SpaceAttachmentPath {
+        item_id,
+        attachment_id,
+        ..
+    }): Path<SpaceAttachmentPath>,
+    SpaceAccess {
+        space_id, archived, ..
+    }: SpaceAccess,
+    State(AppState {
+        db, attachments, ..
+    }): State<AppState>,
+) -> Response<u64> {
+    let item_id: &str = &item_id;
+    let Some(attachments) = attachments else {
+        return Response::Failture(attachments_not_configured());
+    };
+    if archived {
+        return Response::Failture(archived_conflict());
+    }
+    let space_id: &str = &space_id;
+    let res = sqlx::query!(
+        "DELETE FROM spaces_attachments WHERE id = ? AND item_id = ? AND space_id = ?",
+        attachment_id,
+        item_id,
+        space_id
+    )
+    .execute(&db)
+    .await
+    .expect("database")
+    .rows_affected();
+    if res == 0 {
+        return Response::Failture(api::Error::ObjectNotFound.into());
+    }
+
+    let key = storage_key(space_id, &attachment_id);
+    if let Err(err) = purge_file(&attachments, &db, &key).await {
+        tracing::warn!(%err, key, "Failed to remove file of attachment, retrying in background");
+    }
+
+    Response::Success(res)
+}
@@ -1,19 +1,34 @@
 use archk::{
     v1::{
         api::{self, Response},
-        auth::{Token, TokenTy},
-        user::is_valid_username,
+        auth::{Token, TokenFormat, TokenTy},
+        user::{UserID, UserTelegramAuth, UserTelegramAuthID},
+        validate,
     },
     Documentation,
 };
-use axum::{extract::State, Json};
+use axum::{
+    extract::{Query, State},
+    http::header::RETRY_AFTER,
+    response::{IntoResponse, Redirect},
+};
+use once_cell::sync::Lazy;
 use serde::{Deserialize, Serialize};
+use sqlx::{Executor, Sqlite, SqlitePool};
+
+use crate::{
+    app::{self, AppConfigAuthLockout, AppState},
+    notify,
+    oidc::OIDC_STATE_TTL_MS,
+    tokens,
+};
 
-use crate::app::AppState;
+use super::extra::{scope, ClientIp, Json};
 
 #[derive(Deserialize, Documentation)]
 pub struct AuthorizationRequestData {
     /// User name
+    #[doc_example = "greg.b42"]
     pub username: String,
     /// User password
     pub password: String,
@@ -25,47 +40,460 @@ pub struct AuthorizationResponse {
     pub token: String,
 }
 
+#[derive(Deserialize, Documentation)]
+pub struct OidcLoginQuery {
+    /// Invite to register new user with. Not required for registered users
+    /// or if registration without invite is enabled
+    #[serde(default)]
+    pub invite: Option<String>,
+}
+
+#[derive(Deserialize, Documentation)]
+pub struct OidcCallbackQuery {
+    /// Authorization code given by provider
+    #[serde(default)]
+    pub code: Option<String>,
+    /// State given to provider by `/auth/oidc/login`
+    pub state: String,
+    /// Error code given by provider instead of authorization code
+    #[serde(default)]
+    pub error: Option<String>,
+}
+
+#[derive(Deserialize, Documentation)]
+pub struct TelegramAuthorizationRequest {
+    /// Code given by `POST /user/telegram`
+    pub code: String,
+    /// ID of Telegram chat with user, notifications are sent there
+    pub chat_id: String,
+}
+
+/// Create personal token of user, restricted to `scopes` separated by space if any.
+async fn issue_token<'e, E>(
+    db: E,
+    user_id: &str,
+    format: TokenFormat,
+    scopes: Option<&str>,
+) -> Result<Token, sqlx::Error>
+where
+    E: Executor<'e, Database = Sqlite>,
+{
+    let token = Token::with_format(TokenTy::Personal, format);
+    let iat = token.iat as i64;
+    let hash = tokens::hash(&token);
+    sqlx::query!(
+        "INSERT INTO tokens(iat, hash, user_id, scopes) VALUES (?, ?, ?, ?)",
+        iat,
+        hash,
+        user_id,
+        scopes
+    )
+    .execute(db)
+    .await?;
+
+    Ok(token)
+}
+
+/// Hash verified instead of missing or invalid (eg. of users registered with OpenID
+/// Connect) one, so verification takes the same time.
+static DUMMY_HASH: Lazy<String> = Lazy::new(|| bcrypt::hash("", app::BCRYPT_COST).expect("bcrypt"));
+
+/// Verify `password` against `password_hash` of user. Always runs bcrypt, so time does
+/// not depend on existence of user or their password.
+fn verify_password(password: &str, password_hash: Option<&str>) -> bool {
+    let password_hash = password_hash.filter(|v| v.parse::<bcrypt::HashParts>().is_ok());
+    let valid = bcrypt::verify(password, password_hash.unwrap_or(&DUMMY_HASH)).unwrap_or(false);
+    valid && password_hash.is_some()
+}
+
+/// Error if login of user is locked by admin until `locked_until`.
+fn login_locked(locked_until: Option<i64>, now: i64) -> Option<api::ErrorData> {
+    let locked_until = locked_until.filter(|v| *v > now)?;
+    Some(
+        api::Error::Forbidden.detail(
+            format!("Login is locked until {locked_until} (timestamp in milliseconds)").into(),
+        ),
+    )
+}
+
+/// Remaining lockout of `username` from `ip` in milliseconds, if any.
+async fn locked_for(
+    db: &SqlitePool,
+    lockout: &AppConfigAuthLockout,
+    username: &str,
+    ip: &str,
+    now: i64,
+) -> Option<i64> {
+    let forget_before = now - lockout.max_lockout_secs * 1000;
+    let res = sqlx::query!(
+        "SELECT failures, last_failure_at FROM auth_failures WHERE username = ? AND ip = ? AND last_failure_at >= ?",
+        username,
+        ip,
+        forget_before
+    )
+    .fetch_optional(db)
+    .await
+    .expect("database")?;
+
+    let remaining = res.last_failure_at + lockout.lockout_ms(res.failures)? - now;
+    (remaining > 0).then_some(remaining)
+}
+
+/// Count failed login of `username` from `ip`.
+async fn record_failure(
+    db: &SqlitePool,
+    lockout: &AppConfigAuthLockout,
+    username: &str,
+    ip: &str,
+    now: i64,
+) {
+    let forget_before = now - lockout.max_lockout_secs * 1000;
+    sqlx::query!(
+        "INSERT INTO auth_failures(username, ip, failures, last_failure_at) VALUES (?, ?, 1, ?)
+        ON CONFLICT(username, ip) DO UPDATE SET
+            failures = CASE WHEN last_failure_at < ? THEN 1 ELSE failures + 1 END,
+            last_failure_at = excluded.last_failure_at",
+        username,
+        ip,
+        now,
+        forget_before
+    )
+    .execute(db)
+    .await
+    .expect("database");
+}
+
 pub async fn authorize(
-    State(AppState { db, .. }): State<AppState>,
+    ClientIp(ip): ClientIp,
+    State(AppState {
+        db,
+        lockout,
+        token_format,
+        ..
+    }): State<AppState>,
     Json(AuthorizationRequestData { username, password }): Json<AuthorizationRequestData>,
-) -> Response<AuthorizationResponse> {
-    if !is_valid_username(&username) {
-        return Response::Failture(api::Error::MalformedData.detail("Invalid username".into()));
+) -> axum::response::Response {
+    if let Err(e) = validate::username(&username) {
+        return Response::<AuthorizationResponse>::Failture(e.into()).into_response();
     }
 
-    let (id, password_hash) = {
-        let stmt = sqlx::query!(
-            "SELECT id, password_hash FROM users WHERE name = ?",
-            username
+    let ip = ip.unwrap_or_default();
+    let now = app::now_ms();
+    if let Some(remaining) = locked_for(&db, &lockout, &username, &ip, now).await {
+        let secs = (remaining + 999) / 1000;
+        let err = api::Error::RateLimited
+            .detail(format!("Too many failed logins, retry after {secs} seconds").into());
+        return (
+            [(RETRY_AFTER, secs.to_string())],
+            Response::<AuthorizationResponse>::Failture(err),
         )
-        .fetch_one(&db)
-        .await;
-        match stmt {
-            Ok(v) => (v.id, v.password_hash),
-            Err(_) => return Response::Failture(api::Error::ObjectNotFound.into()),
-        }
-    };
+            .into_response();
+    }
 
-    if !bcrypt::verify(&password, &password_hash).unwrap_or(false) {
-        return Response::Failture(api::Error::ObjectNotFound.into());
+    let user = sqlx::query!(
+        "SELECT id, password_hash, login_locked_until FROM users WHERE name = ?",
+        username
+    )
+    .fetch_optional(&db)
+    .await
+    .expect("database");
+
+    // unknown user and invalid password should be indistinguishable, so both run bcrypt
+    // and fail with same error
+    let valid = verify_password(&password, user.as_ref().map(|v| v.password_hash.as_str()));
+    let Some(user) = user.filter(|_| valid) else {
+        record_failure(&db, &lockout, &username, &ip, now).await;
+        return Response::<AuthorizationResponse>::Failture(api::Error::ObjectNotFound.into())
+            .into_response();
+    };
+    if let Some(err) = login_locked(user.login_locked_until, now) {
+        return Response::<AuthorizationResponse>::Failture(err).into_response();
     }
+    let id = user.id;
 
-    let token = Token::new(TokenTy::Personal);
-    let iat = token.iat as i64;
-    let rnd = token.rnd as i64;
-    let stmt = sqlx::query!(
-        "INSERT INTO tokens(iat, rnd, user_id) VALUES (?, ?, ?)",
-        iat,
-        rnd,
-        id
+    sqlx::query!(
+        "DELETE FROM auth_failures WHERE username = ? AND ip = ?",
+        username,
+        ip
     )
     .execute(&db)
-    .await;
+    .await
+    .expect("database");
 
-    match stmt {
-        Ok(_) => Response::Success(AuthorizationResponse {
+    match issue_token(&db, &id, token_format, None).await {
+        Ok(token) => Response::Success(AuthorizationResponse {
             token: token.to_string(),
         }),
         Err(_) => Response::Failture(api::Error::Internal.into()),
     }
+    .into_response()
+}
+
+pub async fn telegram_authorize(
+    State(AppState {
+        db, token_format, ..
+    }): State<AppState>,
+    Json(TelegramAuthorizationRequest { code, chat_id }): Json<TelegramAuthorizationRequest>,
+) -> Response<AuthorizationResponse> {
+    if let Err(e) = notify::check_telegram_chat_id(&chat_id) {
+        return Response::Failture(
+            api::Error::MalformedData.detail(format!("`chat_id`: {e}").into()),
+        );
+    }
+
+    // code is removed in same transaction, so it can't be used twice
+    let mut tx = app::begin(&db).await;
+    let res = sqlx::query!(
+        "
+        SELECT users_telegram_auths.user_id, users_telegram_auths.issued_at,
+            users.login_locked_until
+        FROM users_telegram_auths
+        JOIN users ON users.id = users_telegram_auths.user_id
+        WHERE users_telegram_auths.id = ?",
+        code
+    )
+    .fetch_optional(&mut *tx)
+    .await
+    .expect("database");
+    let (Some(res), Some(id)) = (res, UserTelegramAuthID::from(code)) else {
+        return Response::Failture(api::Error::ObjectNotFound.into());
+    };
+    let id_str: &str = &id;
+    sqlx::query!("DELETE FROM users_telegram_auths WHERE id = ?", id_str)
+        .execute(&mut *tx)
+        .await
+        .expect("database");
+
+    let Some(user_id) = UserID::from(res.user_id) else {
+        return Response::Failture(api::Error::ObjectNotFound.into());
+    };
+    let auth = UserTelegramAuth {
+        id,
+        user_id,
+        issued_at: res.issued_at as u64,
+    };
+    if !auth.is_actual() {
+        tx.commit().await.expect("database");
+        return Response::Failture(api::Error::Gone.detail("code is expired".into()));
+    }
+    if let Some(err) = login_locked(res.login_locked_until, app::now_ms()) {
+        return Response::Failture(err);
+    }
+
+    // bot only sends notifications and decides unlock requests
+    let user_id: &str = &auth.user_id;
+    let scopes = format!("{} {}", scope::UNLOCKS, scope::NOTIFICATIONS);
+    let token = issue_token(&mut *tx, user_id, token_format, Some(&scopes))
+        .await
+        .expect("database");
+    sqlx::query!(
+        "
+        INSERT INTO users_notifications(user_id, telegram_chat_id) VALUES (?, ?)
+        ON CONFLICT(user_id) DO UPDATE SET telegram_chat_id = excluded.telegram_chat_id",
+        user_id,
+        chat_id
+    )
+    .execute(&mut *tx)
+    .await
+    .expect("database");
+    tx.commit().await.expect("database");
+
+    Response::Success(AuthorizationResponse {
+        token: token.to_string(),
+    })
+}
+
+fn oidc_not_configured() -> api::ErrorData {
+    api::Error::ObjectNotFound.detail("OpenID Connect login is not configured".into())
+}
+
+pub async fn oidc_login(
+    State(AppState { db, oidc, .. }): State<AppState>,
+    Query(OidcLoginQuery { invite }): Query<OidcLoginQuery>,
+) -> axum::response::Response {
+    let Some(oidc) = oidc else {
+        return Response::<api::NeverSerialize>::Failture(oidc_not_configured()).into_response();
+    };
+
+    let state = uuid::Uuid::new_v4().simple().to_string();
+    let created_at = app::now_ms();
+    sqlx::query!(
+        "INSERT INTO oidc_states(state, invite, created_at) VALUES (?, ?, ?)",
+        state,
+        invite,
+        created_at
+    )
+    .execute(&db)
+    .await
+    .expect("database");
+
+    Redirect::to(&oidc.authorize_url(&state)).into_response()
+}
+
+pub async fn oidc_callback(
+    State(AppState {
+        db,
+        oidc,
+        token_format,
+        ..
+    }): State<AppState>,
+    Query(OidcCallbackQuery { code, state, error }): Query<OidcCallbackQuery>,
+) -> Response<AuthorizationResponse> {
+    let Some(oidc) = oidc else {
+        return Response::Failture(oidc_not_configured());
+    };
+
+    // state is single use, so drop it before anything else
+    let Some(login) = sqlx::query!(
+        "DELETE FROM oidc_states WHERE state = ? RETURNING invite, created_at",
+        state
+    )
+    .fetch_optional(&db)
+    .await
+    .expect("database") else {
+        return Response::Failture(api::Error::Unauthorized.detail("Invalid `state`".into()));
+    };
+    if login.created_at < app::now_ms() - OIDC_STATE_TTL_MS {
+        return Response::Failture(api::Error::Gone.detail("Login session expired".into()));
+    }
+    if let Some(error) = error {
+        return Response::Failture(
+            api::Error::Unauthorized
+                .detail(format!("Identity provider returned error `{error}`").into()),
+        );
+    }
+    let Some(code) = code else {
+        return Response::Failture(api::Error::MalformedData.detail("`code` is required".into()));
+    };
+
+    let user = match oidc.user(&code).await {
+        Ok(v) => v,
+        Err(e) => return Response::Failture(e.into()),
+    };
+
+    let mut tx = app::begin(&db).await;
+
+    let identity = sqlx::query!(
+        "SELECT user_id, login_locked_until FROM oidc_identities
+            INNER JOIN users ON users.id = oidc_identities.user_id
+        WHERE subject = ?",
+        user.subject
+    )
+    .fetch_optional(&mut *tx)
+    .await
+    .expect("database");
+
+    let user_id = match identity {
+        Some(v) => {
+            if let Some(err) = login_locked(v.login_locked_until, app::now_ms()) {
+                return Response::Failture(err);
+            }
+            v.user_id
+        }
+        None => {
+            let invited_by = match &login.invite {
+                Some(invite) => {
+                    let owner = sqlx::query!(
+                        "DELETE FROM invites WHERE id = ? RETURNING owner_id",
+                        invite
+                    )
+                    .fetch_optional(&mut *tx)
+                    .await
+                    .expect("database");
+                    match owner {
+                        Some(v) => v.owner_id,
+                        None => {
+                            return Response::Failture(
+                                api::Error::ObjectNotFound.detail("Invalid invite".into()),
+                            )
+                        }
+                    }
+                }
+                None if oidc.config.auto_register => None,
+                None => {
+                    return Response::Failture(
+                        api::Error::Forbidden.detail("Registration requires invite".into()),
+                    )
+                }
+            };
+
+            let Some(username) = user.username else {
+                return Response::Failture(
+                    api::Error::MalformedData.detail(
+                        format!(
+                            "Identity provider returned no `{}` claim",
+                            oidc.config.username_claim
+                        )
+                        .into(),
+                    ),
+                );
+            };
+            if let Err(e) = validate::username(&username) {
+                return Response::Failture(e.into());
+            }
+
+            let user_id = UserID::new();
+            let user_id_str: &str = &user_id;
+            // empty hash never matches, so password login is disabled
+            let res = sqlx::query!(
+                "INSERT INTO users(id, name, invited_by, password_hash) VALUES (?, ?, ?, '')",
+                user_id_str,
+                username,
+                invited_by
+            )
+            .execute(&mut *tx)
+            .await;
+            match res {
+                Err(sqlx::Error::Database(v)) if v.is_unique_violation() => {
+                    return Response::Failture(
+                        api::Error::Conflict.detail("`username` should be unique".into()),
+                    )
+                }
+                _ => res.expect("database"),
+            };
+
+            sqlx::query!(
+                "INSERT INTO oidc_identities(subject, user_id) VALUES (?, ?)",
+                user.subject,
+                user_id_str
+            )
+            .execute(&mut *tx)
+            .await
+            .expect("database");
+
+            user_id.to_string()
+        }
+    };
+
+    let token = issue_token(&mut *tx, &user_id, token_format, None)
+        .await
+        .expect("database");
+    tx.commit().await.expect("database");
+
+    Response::Success(AuthorizationResponse {
+        token: token.to_string(),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn dummy_hash_has_same_cost() {
+        let parts: bcrypt::HashParts = DUMMY_HASH.parse().expect("bcrypt hash");
+        assert_eq!(parts.get_cost(), app::BCRYPT_COST);
+    }
+
+    #[test]
+    fn verify_password_fails_uniformly() {
+        let hash = bcrypt::hash("password", 4).expect("bcrypt");
+
+        assert!(verify_password("password", Some(&hash)));
+        assert!(!verify_password("invalid", Some(&hash)));
+        // password of unknown user or user without password never matches, even
+        // password of dummy hash
+        assert!(!verify_password("", None));
+        assert!(!verify_password("", Some("")));
+    }
 }
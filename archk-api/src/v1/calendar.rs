@@ -0,0 +1,344 @@
+//! iCalendar (RFC 5545) feed of item reservations and due dates, so space owners can
+//! subscribe to space from calendar apps. Calendar apps can't send `Authorization`
+//! header, so feed is authorized by `?token=<expires_at>.<signature>` signed with
+//! HMAC-SHA256 by server key, like share links. Signature covers nonce of space, so
+//! owner revokes all issued tokens by changing it.
+
+use archk::{
+    v1::api::{self, Response},
+    Documentation,
+};
+use axum::{
+    extract::{Query, State},
+    http::header::CONTENT_TYPE,
+    response::IntoResponse,
+};
+use base64::{prelude::BASE64_URL_SAFE_NO_PAD, Engine};
+use chrono::DateTime;
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+
+use crate::app::{self, AppState};
+
+use super::{
+    extra::{Json, Path, SpaceAccess},
+    share::server_key,
+    space::SpacePath,
+};
+
+/// Maximum lifetime of feed token in seconds
+const MAX_TOKEN_SECS: i64 = 366 * 24 * 60 * 60;
+/// Ended reservations are kept in feed for 30 days
+const PAST_EVENTS_MS: i64 = 30 * 24 * 60 * 60 * 1000;
+/// Maximum number of events of each kind in feed
+const MAX_EVENTS: i64 = 1000;
+
+#[derive(Deserialize, Documentation)]
+pub struct CalendarTokenBody {
+    /// Lifetime of token in seconds, up to 366 days. Default is 366 days
+    #[serde(default = "default_token_secs")]
+    #[doc_min = 1]
+    #[doc_max = 31622400]
+    pub duration_secs: i64,
+}
+
+fn default_token_secs() -> i64 {
+    MAX_TOKEN_SECS
+}
+
+#[derive(Serialize, Documentation)]
+pub struct CalendarTokenResponse {
+    /// Feed token
+    pub token: String,
+    /// Path of feed relative to API root, eg. `/api/v1/space/<space_id>/calendar.ics?token=<token>`
+    pub path: String,
+    /// Timestamp in milliseconds of token expiration
+    pub expires_at: i64,
+}
+
+#[derive(Deserialize, Documentation)]
+pub struct CalendarQuery {
+    /// Feed token, see `POST /space/:space_id/calendar/token`
+    pub token: String,
+}
+
+fn mac(key: &[u8], space_id: &str, nonce: i64, expires_at: i64) -> Hmac<Sha256> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("hmac accepts any key");
+    mac.update(format!("calendar:{space_id}:{nonce}:{expires_at}").as_bytes());
+    mac
+}
+
+/// Expiration of valid token of space with `nonce`.
+fn verify(key: &[u8], space_id: &str, nonce: i64, token: &str) -> Option<i64> {
+    let (expires_at, signature) = token.split_once('.')?;
+    let expires_at: i64 = expires_at.parse().ok()?;
+    let signature = BASE64_URL_SAFE_NO_PAD.decode(signature).ok()?;
+    mac(key, space_id, nonce, expires_at)
+        .verify_slice(&signature)
+        .ok()?;
+    Some(expires_at)
+}
+
+/// Timestamp in milliseconds as UTC date-time, eg. `20240131T080000Z`.
+fn ics_time(ms: i64) -> String {
+    DateTime::from_timestamp_millis(ms)
+        .unwrap_or_default()
+        .format("%Y%m%dT%H%M%SZ")
+        .to_string()
+}
+
+/// Escape TEXT value.
+fn ics_text(v: &str) -> String {
+    let mut out = String::with_capacity(v.len());
+    for c in v.chars() {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            ';' => out.push_str("\\;"),
+            ',' => out.push_str("\\,"),
+            '\n' => out.push_str("\\n"),
+            '\r' => {}
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Append content line, folded to 75 octets per line.
+fn push_line(out: &mut String, line: &str) {
+    let mut len = 0;
+    for c in line.chars() {
+        if len + c.len_utf8() > 75 {
+            out.push_str("\r\n ");
+            len = 1;
+        }
+        out.push(c);
+        len += c.len_utf8();
+    }
+    out.push_str("\r\n");
+}
+
+struct Event {
+    uid: String,
+    start: i64,
+    end: Option<i64>,
+    summary: String,
+    description: Option<String>,
+}
+
+fn write_calendar(title: &str, events: &[Event], stamp: i64) -> String {
+    let mut out = String::new();
+    push_line(&mut out, "BEGIN:VCALENDAR");
+    push_line(&mut out, "VERSION:2.0");
+    push_line(&mut out, "PRODID:-//archk//calendar//EN");
+    push_line(&mut out, &format!("X-WR-CALNAME:{}", ics_text(title)));
+    for event in events {
+        push_line(&mut out, "BEGIN:VEVENT");
+        push_line(&mut out, &format!("UID:{}", event.uid));
+        push_line(&mut out, &format!("DTSTAMP:{}", ics_time(stamp)));
+        push_line(&mut out, &format!("DTSTART:{}", ics_time(event.start)));
+        if let Some(end) = event.end {
+            push_line(&mut out, &format!("DTEND:{}", ics_time(end)));
+        }
+        push_line(&mut out, &format!("SUMMARY:{}", ics_text(&event.summary)));
+        if let Some(description) = &event.description {
+            push_line(&mut out, &format!("DESCRIPTION:{}", ics_text(description)));
+        }
+        push_line(&mut out, "END:VEVENT");
+    }
+    push_line(&mut out, "END:VCALENDAR");
+    out
+}
+
+pub async fn create_calendar_token(
+    SpaceAccess { space_id, .. }: SpaceAccess,
+    State(AppState { db, .. }): State<AppState>,
+    Json(CalendarTokenBody { duration_secs }): Json<CalendarTokenBody>,
+) -> Response<CalendarTokenResponse> {
+    if !(1..=MAX_TOKEN_SECS).contains(&duration_secs) {
+        return Response::Failture(
+            api::Error::MalformedData
+                .detail(format!("`duration_secs` should be between 1 and {MAX_TOKEN_SECS}").into()),
+        );
+    }
+
+    let space_id: &str = &space_id;
+    let nonce = sqlx::query_scalar!("SELECT calendar_nonce FROM spaces WHERE id = ?", space_id)
+        .fetch_one(&db)
+        .await
+        .expect("database");
+    let expires_at = app::now_ms() + duration_secs * 1000;
+    let key = server_key(&db, "calendar").await;
+    let signature = mac(&key, space_id, nonce, expires_at)
+        .finalize()
+        .into_bytes();
+    let token = format!("{expires_at}.{}", BASE64_URL_SAFE_NO_PAD.encode(signature));
+
+    Response::Success(CalendarTokenResponse {
+        path: format!("/api/v1/space/{space_id}/calendar.ics?token={token}"),
+        token,
+        expires_at,
+    })
+}
+
+pub async fn revoke_calendar_tokens(
+    SpaceAccess { space_id, .. }: SpaceAccess,
+    State(AppState { db, .. }): State<AppState>,
+) -> Response<u64> {
+    let space_id: &str = &space_id;
+    let res = sqlx::query!(
+        "UPDATE spaces SET calendar_nonce = calendar_nonce + 1 WHERE id = ?",
+        space_id
+    )
+    .execute(&db)
+    .await
+    .expect("database");
+
+    Response::Success(res.rows_affected())
+}
+
+pub async fn get_calendar(
+    Path(SpacePath { space_id }): Path<SpacePath>,
+    Query(CalendarQuery { token }): Query<CalendarQuery>,
+    State(AppState { db, .. }): State<AppState>,
+) -> axum::response::Response {
+    let space_id: &str = &space_id;
+    let space = sqlx::query!(
+        "SELECT title, calendar_nonce FROM spaces WHERE id = ?",
+        space_id
+    )
+    .fetch_optional(&db)
+    .await
+    .expect("database");
+    let Some(space) = space else {
+        return Response::<api::NeverSerialize>::Failture(api::Error::ObjectNotFound.into())
+            .into_response();
+    };
+    let key = server_key(&db, "calendar").await;
+    let Some(expires_at) = verify(&key, space_id, space.calendar_nonce, &token) else {
+        return Response::<api::NeverSerialize>::Failture(api::Error::ObjectNotFound.into())
+            .into_response();
+    };
+    let now = app::now_ms();
+    if expires_at <= now {
+        return Response::<api::NeverSerialize>::Failture(
+            api::Error::Gone.detail("token is expired".into()),
+        )
+        .into_response();
+    }
+
+    let since = now - PAST_EVENTS_MS;
+    let reservations = sqlx::query!(
+        r#"
+        SELECT
+            spaces_items_reservations.id,
+            spaces_items_reservations.start_at,
+            spaces_items_reservations.end_at,
+            spaces_items_reservations.note,
+            spaces_items.title,
+            COALESCE(spaces_accounts.pl_displayname, spaces_accounts.pl_name, spaces_accounts.pl_id) AS "account!: String"
+        FROM spaces_items_reservations
+            INNER JOIN spaces_items ON spaces_items.id = spaces_items_reservations.item_id
+            INNER JOIN spaces_accounts
+                ON spaces_accounts.pl_id = spaces_items_reservations.pl_id
+                    AND spaces_accounts.space_id = spaces_items_reservations.space_id
+        WHERE spaces_items_reservations.space_id = ? AND spaces_items_reservations.end_at > ?
+        ORDER BY spaces_items_reservations.start_at
+        LIMIT ?"#,
+        space_id,
+        since,
+        MAX_EVENTS
+    )
+    .fetch_all(&db)
+    .await
+    .expect("database");
+    let due = sqlx::query!(
+        r#"
+        SELECT
+            spaces_items.id,
+            spaces_items.title,
+            spaces_items.due_at AS "due_at!: i64",
+            COALESCE(spaces_accounts.pl_displayname, spaces_accounts.pl_name, spaces_items.current_holder) AS "holder!: String"
+        FROM spaces_items
+            LEFT JOIN spaces_accounts
+                ON spaces_accounts.pl_id = spaces_items.current_holder
+                    AND spaces_accounts.space_id = spaces_items.space_id
+        WHERE spaces_items.space_id = ?
+            AND spaces_items.current_holder IS NOT NULL
+            AND spaces_items.due_at IS NOT NULL
+        ORDER BY spaces_items.due_at
+        LIMIT ?"#,
+        space_id,
+        MAX_EVENTS
+    )
+    .fetch_all(&db)
+    .await
+    .expect("database");
+
+    let events: Vec<_> = reservations
+        .into_iter()
+        .map(|v| Event {
+            uid: format!("reservation-{}@archk", v.id),
+            start: v.start_at,
+            end: Some(v.end_at),
+            summary: format!("{} reserved by {}", v.title, v.account),
+            description: v.note,
+        })
+        .chain(due.into_iter().map(|v| Event {
+            uid: format!("due-{}-{}@archk", v.id, v.due_at),
+            start: v.due_at,
+            end: None,
+            summary: format!("{} due from {}", v.title, v.holder),
+            description: None,
+        }))
+        .collect();
+
+    (
+        [(CONTENT_TYPE, "text/calendar; charset=utf-8")],
+        write_calendar(&space.title, &events, now),
+    )
+        .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn signed_tokens() {
+        let key = b"secret";
+        let signature = mac(key, "space", 0, 1000).finalize().into_bytes();
+        let token = format!("1000.{}", BASE64_URL_SAFE_NO_PAD.encode(signature));
+
+        assert_eq!(verify(key, "space", 0, &token), Some(1000));
+        assert_eq!(verify(key, "other", 0, &token), None);
+        assert_eq!(verify(key, "space", 1, &token), None);
+        assert_eq!(verify(b"other", "space", 0, &token), None);
+        assert_eq!(
+            verify(key, "space", 0, &token.replace("1000.", "2000.")),
+            None
+        );
+    }
+
+    #[test]
+    fn calendar_format() {
+        assert_eq!(ics_text("a, b; c\\d\nok"), "a\\, b\\; c\\\\d\\nok");
+        assert_eq!(ics_time(0), "19700101T000000Z");
+
+        let mut out = String::new();
+        push_line(&mut out, &"x".repeat(80));
+        assert_eq!(out, format!("{}\r\n {}\r\n", "x".repeat(75), "x".repeat(5)));
+
+        let events = [Event {
+            uid: "r@archk".into(),
+            start: 0,
+            end: Some(3_600_000),
+            summary: "Drill reserved by Greg".into(),
+            description: None,
+        }];
+        let ics = write_calendar("Lab", &events, 0);
+        assert!(ics.starts_with("BEGIN:VCALENDAR\r\n"));
+        assert!(ics.contains("\r\nDTEND:19700101T010000Z\r\n"));
+        assert!(ics.ends_with("END:VEVENT\r\nEND:VCALENDAR\r\n"));
+    }
+}
@@ -0,0 +1,5 @@
+use archk::v1::api::{self, ErrorDescription, Response};
+
+pub async fn get_errors() -> Response<Vec<ErrorDescription>> {
+    Response::Success(api::Error::ALL.iter().map(|v| v.describe()).collect())
+}
@@ -0,0 +1,174 @@
+use archk::v1::{
+    api,
+    docs::{self, DocumentationObject},
+};
+use axum::{
+    async_trait,
+    body::Body,
+    extract::{FromRequestParts, Query},
+    http::{
+        header::{ACCEPT, CONTENT_TYPE},
+        request::Parts,
+    },
+    response::{IntoResponse, Response},
+};
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
+use tokio_stream::{wrappers::ReceiverStream, Stream, StreamExt};
+
+/// Format of exported listings
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ExportFormat {
+    /// JSON object per line
+    #[default]
+    Jsonl,
+    /// Comma separated values with header
+    Csv,
+}
+
+impl docs::Documentation for ExportFormat {
+    const DOCUMENTATION_OBJECT: DocumentationObject =
+        <String as docs::Documentation>::DOCUMENTATION_OBJECT;
+}
+
+impl ExportFormat {
+    pub fn content_type(self) -> &'static str {
+        match self {
+            Self::Jsonl => "application/x-ndjson",
+            Self::Csv => "text/csv",
+        }
+    }
+}
+
+/// Row of exported listing.
+pub trait ExportRow: Serialize {
+    /// Header of CSV export
+    const CSV_HEADER: &'static [&'static str];
+
+    /// Fields of CSV row in order of [`Self::CSV_HEADER`]
+    fn csv_fields(&self) -> Vec<Option<String>>;
+}
+
+/// CSV of `rows` (after header if `header`), lines end with `\n`. `None` fields are
+/// left empty.
+fn csv_rows<'a, T: ExportRow + 'a>(header: bool, rows: impl IntoIterator<Item = &'a T>) -> Vec<u8> {
+    let mut csv = csv::WriterBuilder::new()
+        .terminator(csv::Terminator::Any(b'\n'))
+        .from_writer(Vec::new());
+    if header {
+        csv.write_record(T::CSV_HEADER).expect("csv");
+    }
+    for row in rows {
+        let fields = row.csv_fields();
+        csv.write_record(fields.iter().map(|v| v.as_deref().unwrap_or_default()))
+            .expect("csv");
+    }
+    csv.into_inner().expect("csv")
+}
+
+/// Format of list endpoints: JSON response (default) or CSV of the same rows, selected
+/// by `?format=csv` query param or `Accept: text/csv` header. Query param wins.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ListFormat {
+    #[default]
+    Json,
+    Csv,
+}
+
+#[derive(Deserialize)]
+struct ListFormatQuery {
+    format: Option<String>,
+}
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for ListFormat {
+    type Rejection = api::Response;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let query = Query::<ListFormatQuery>::try_from_uri(&parts.uri)
+            .ok()
+            .and_then(|Query(v)| v.format);
+        match query.as_deref() {
+            Some("json") => return Ok(Self::Json),
+            Some("csv") => return Ok(Self::Csv),
+            Some(_) => {
+                return Err(api::Response::Failture(
+                    api::Error::MalformedData.detail("`format` should be `json` or `csv`".into()),
+                ))
+            }
+            None => {}
+        }
+
+        let accepts_csv = parts
+            .headers
+            .get_all(ACCEPT)
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .any(|v| v.contains("text/csv"));
+        Ok(if accepts_csv { Self::Csv } else { Self::Json })
+    }
+}
+
+/// Response of list endpoint in [`ListFormat`] of request. Errors are always JSON.
+pub struct Listing<T>(pub ListFormat, pub api::Response<Vec<T>>);
+
+impl<T: ExportRow> IntoResponse for Listing<T> {
+    fn into_response(self) -> Response {
+        match self {
+            Listing(ListFormat::Csv, api::Response::Success(rows)) => (
+                [(CONTENT_TYPE, ExportFormat::Csv.content_type())],
+                csv_rows(true, &rows),
+            )
+                .into_response(),
+            Listing(_, res) => res.into_response(),
+        }
+    }
+}
+
+/// Sending half of [`stream`]ed export.
+pub struct ExportSender {
+    format: ExportFormat,
+    tx: mpsc::Sender<Result<Vec<u8>, sqlx::Error>>,
+}
+
+/// Start export streamed as response body. Rows are passed to [`ExportSender::send_rows`]
+/// in spawned task as they are fetched, so listing is never held in memory.
+pub fn stream(format: ExportFormat) -> (ExportSender, Response) {
+    let (tx, rx) = mpsc::channel(64);
+    let res = (
+        [(CONTENT_TYPE, format.content_type())],
+        Body::from_stream(ReceiverStream::new(rx)),
+    )
+        .into_response();
+    (ExportSender { format, tx }, res)
+}
+
+impl ExportSender {
+    /// Send rows of `sqlx` fetch stream, stopping on first error or if client went away.
+    pub async fn send_rows<T: ExportRow>(
+        self,
+        mut rows: impl Stream<Item = Result<T, sqlx::Error>> + Unpin,
+    ) {
+        if self.format == ExportFormat::Csv
+            && self.tx.send(Ok(csv_rows::<T>(true, []))).await.is_err()
+        {
+            return;
+        }
+
+        while let Some(row) = rows.next().await {
+            let failed = row.is_err();
+            let line = row.map(|v| match self.format {
+                ExportFormat::Jsonl => {
+                    let mut line = serde_json::to_vec(&v).expect("json");
+                    line.push(b'\n');
+                    line
+                }
+                ExportFormat::Csv => csv_rows(false, [&v]),
+            });
+            if self.tx.send(line).await.is_err() || failed {
+                return;
+            }
+        }
+    }
+}
@@ -1,17 +1,86 @@
+use std::{
+    marker::PhantomData,
+    net::{IpAddr, SocketAddr},
+};
+
 use archk::v1::{
     api,
     auth::{Token, TokenTy},
+    org::OrgRole,
     service::{ServiceAccountID, ServiceAccountTy},
     space::SpaceID,
     user::UserID,
 };
 use axum::{
     async_trait,
-    extract::FromRequestParts,
-    http::{header::AUTHORIZATION, request::Parts, HeaderMap},
+    extract::{
+        rejection::{JsonRejection, PathRejection},
+        ConnectInfo, FromRequest, FromRequestParts, Request,
+    },
+    http::{
+        header::{AUTHORIZATION, FORWARDED, USER_AGENT},
+        request::Parts,
+        HeaderMap, Method,
+    },
+    response::IntoResponse,
+};
+use ipnet::IpNet;
+use serde::{de::DeserializeOwned, Deserialize};
+use sqlx::SqlitePool;
+
+use crate::{
+    app::{self, is_trusted, AppState},
+    cache::{CachedService, CachedSpace, CachedToken},
+    roles::{perm, RolePermissions},
+    tokens,
 };
 
-use crate::app::AppState;
+use super::org::org_role;
+
+/// JSON body extractor. Same as [`axum::Json`], but rejects with
+/// [`api::Error::MalformedData`] containing deserialization error instead of plain text.
+pub struct Json<T>(pub T);
+
+#[async_trait]
+impl<T: DeserializeOwned, S: Send + Sync> FromRequest<S> for Json<T> {
+    type Rejection = axum::response::Response;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        match axum::Json::<T>::from_request(req, state).await {
+            Ok(axum::Json(v)) => Ok(Self(v)),
+            Err(err @ (JsonRejection::JsonDataError(_) | JsonRejection::JsonSyntaxError(_))) => {
+                Err(api::Response::<api::NeverSerialize>::Failture(
+                    api::Error::MalformedData.detail(err.body_text().into()),
+                )
+                .into_response())
+            }
+            // other rejections (eg. too large body) keep their status
+            Err(err) => Err(err.into_response()),
+        }
+    }
+}
+
+/// Path parameters extractor. Same as [`axum::extract::Path`], but rejects invalid
+/// parameters (eg. malformed IDs) with [`api::Error::MalformedData`].
+pub struct Path<T>(pub T);
+
+#[async_trait]
+impl<T: DeserializeOwned + Send, S: Send + Sync> FromRequestParts<S> for Path<T> {
+    type Rejection = axum::response::Response;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        match axum::extract::Path::<T>::from_request_parts(parts, state).await {
+            Ok(axum::extract::Path(v)) => Ok(Self(v)),
+            Err(PathRejection::FailedToDeserializePathParams(err)) => {
+                Err(api::Response::<api::NeverSerialize>::Failture(
+                    api::Error::MalformedData.detail(err.body_text().into()),
+                )
+                .into_response())
+            }
+            Err(err) => Err(err.into_response()),
+        }
+    }
+}
 
 #[derive(Debug)]
 pub struct DbUser {
@@ -21,6 +90,38 @@ pub struct DbUser {
     pub invited_by: Option<String>,
     pub level: i64,
     pub password_hash: String,
+    pub login_locked_until: Option<i64>,
+}
+
+/// User authenticated by any personal token, including ones restricted to space or
+/// scopes. Used by [`SpaceAccess`] and endpoints checking [`ScopedUser::require`],
+/// other endpoints accept only unrestricted tokens.
+#[derive(Debug)]
+pub struct ScopedUser {
+    pub user: DbUser,
+    /// Restriction of token, if any
+    pub scope: Option<TokenScope>,
+}
+
+#[derive(Debug)]
+pub struct TokenScope {
+    /// Space token is restricted to, `None` if token is for all spaces of user (eg. of
+    /// Telegram bot)
+    pub space_id: Option<SpaceID>,
+    pub scopes: Vec<String>,
+}
+
+impl ScopedUser {
+    /// Rejects token restricted to space or without `scope`. Unrestricted tokens have
+    /// every scope.
+    pub fn require(&self, scope: &str) -> Result<(), api::ErrorData> {
+        match &self.scope {
+            Some(v) if v.space_id.is_some() || !has_scope(&v.scopes, scope) => {
+                Err(api::Error::Forbidden.detail("Token scopes do not allow this request".into()))
+            }
+            _ => Ok(()),
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -29,63 +130,291 @@ pub struct DbService {
     pub id: ServiceAccountID,
     pub space_id: Option<SpaceID>,
     pub ty: ServiceAccountTy,
+    /// Is space of service archived? Such services can't submit events
+    pub space_archived: bool,
+}
+
+/// Space from `:space_id` path segment accessible by authenticated user. User has access if
+/// they own space or role permissions allow it by [`SpacePermission`]. Space existence and
+/// access are resolved in one query, rejects with [`api::Error::ObjectNotFound`] otherwise.
+///
+/// Archived spaces are still accessible, mutating handlers should check `archived`.
+#[derive(Debug)]
+pub struct SpaceAccess<P: SpacePermission = ManageSpace> {
+    pub space_id: SpaceID,
+    pub archived: bool,
+    _permission: PhantomData<P>,
+}
+
+/// Scopes of personal tokens restricted to one space, see `PUT /user/tokens`.
+pub mod scope {
+    /// Read space, its accounts, items and tags (`GET` requests)
+    pub const SPACE_READ: &str = "space.read";
+    /// Read and change space, its accounts, items and tags
+    pub const SPACE_WRITE: &str = "space.write";
+    /// Read logs of space
+    pub const LOGS_READ: &str = "space.logs.read";
+    /// Read logs and change logs retention of space
+    pub const LOGS_MANAGE: &str = "space.logs.manage";
+    /// Read and decide unlock requests of space. Tokens for all spaces (eg. of Telegram
+    /// bot) may also list own spaces
+    pub const UNLOCKS: &str = "space.unlocks";
+    /// Read own profile, change notifications and revoke this token. Only for tokens
+    /// for all spaces
+    pub const NOTIFICATIONS: &str = "user.notifications";
+
+    /// All known scopes of tokens restricted to space
+    pub const ALL: &[&str] = &[SPACE_READ, SPACE_WRITE, LOGS_READ, LOGS_MANAGE, UNLOCKS];
+}
+
+/// Role permission that gives access to spaces of other users.
+pub trait SpacePermission: Send {
+    fn allowed(permissions: &RolePermissions) -> bool;
+
+    /// Is access allowed to token with `scopes` for request with `method`?
+    fn scoped(scopes: &[String], method: &Method) -> bool;
+}
+
+fn has_scope(scopes: &[String], scope: &str) -> bool {
+    scopes.iter().any(|v| v == scope)
+}
+
+/// Access to everything in space, requires `space.manage`.
+#[derive(Debug)]
+pub struct ManageSpace;
+
+/// Read-only access to space logs, requires `space.logs.read` or any of
+/// `space.logs.manage`, `space.manage`.
+#[derive(Debug)]
+pub struct ReadSpaceLogs;
+
+/// Access to space logs settings, requires `space.logs.manage` or `space.manage`.
+#[derive(Debug)]
+pub struct ManageSpaceLogs;
+
+/// Access to unlock requests of space, requires `space.manage`. Also allowed to tokens
+/// with `space.unlocks` scope.
+#[derive(Debug)]
+pub struct DecideUnlocks;
+
+impl SpacePermission for ManageSpace {
+    fn allowed(permissions: &RolePermissions) -> bool {
+        permissions.has(perm::SPACE_MANAGE)
+    }
+
+    fn scoped(scopes: &[String], method: &Method) -> bool {
+        has_scope(scopes, scope::SPACE_WRITE)
+            || matches!(*method, Method::GET | Method::HEAD) && has_scope(scopes, scope::SPACE_READ)
+    }
+}
+
+impl SpacePermission for ReadSpaceLogs {
+    fn allowed(permissions: &RolePermissions) -> bool {
+        permissions.has(perm::SPACE_LOGS_READ)
+            || permissions.has(perm::SPACE_LOGS_MANAGE)
+            || permissions.has(perm::SPACE_MANAGE)
+    }
+
+    fn scoped(scopes: &[String], _method: &Method) -> bool {
+        has_scope(scopes, scope::LOGS_READ) || has_scope(scopes, scope::LOGS_MANAGE)
+    }
+}
+
+impl SpacePermission for ManageSpaceLogs {
+    fn allowed(permissions: &RolePermissions) -> bool {
+        permissions.has(perm::SPACE_LOGS_MANAGE) || permissions.has(perm::SPACE_MANAGE)
+    }
+
+    fn scoped(scopes: &[String], _method: &Method) -> bool {
+        has_scope(scopes, scope::LOGS_MANAGE)
+    }
+}
+
+impl SpacePermission for DecideUnlocks {
+    fn allowed(permissions: &RolePermissions) -> bool {
+        ManageSpace::allowed(permissions)
+    }
+
+    fn scoped(scopes: &[String], method: &Method) -> bool {
+        has_scope(scopes, scope::UNLOCKS) || ManageSpace::scoped(scopes, method)
+    }
 }
 
 #[async_trait]
-pub trait AuthenticatedUserParam: Sized {
+pub trait AuthenticatedUserParam: Sized + Send {
     async fn verify(token: &Token, state: &AppState) -> Option<Self>;
+
+    /// Verify client certificate by its normalized SHA-256 fingerprint (see
+    /// [`cert_fingerprint`]). Only services can be bound to certificates.
+    async fn verify_certificate(_fingerprint: &str, _state: &AppState) -> Option<Self> {
+        None
+    }
+
+    /// Verify username given by trusted reverse proxy (see [`crate::app::ProxyAuth`]).
+    /// Only users can be authenticated by proxy.
+    async fn verify_proxy_user(_username: &str, _state: &AppState) -> Option<Self> {
+        None
+    }
 }
 
 pub struct AuthenticatedUser<U: AuthenticatedUserParam = UserID> {
-    pub token: Token,
+    /// Bearer token, `None` if authenticated by client certificate or proxy
+    pub token: Option<Token>,
     pub user: U,
 }
 
+/// Normalize SHA-256 fingerprint of certificate: lowercase hex without separators.
+/// Returns `None` if `v` is not a SHA-256 fingerprint.
+pub(crate) fn cert_fingerprint(v: &str) -> Option<String> {
+    let v: String = v
+        .trim()
+        .chars()
+        .filter(|c| *c != ':')
+        .map(|c| c.to_ascii_lowercase())
+        .collect();
+    (v.len() == 64 && v.bytes().all(|c| c.is_ascii_hexdigit())).then_some(v)
+}
+
 #[async_trait]
 impl AuthenticatedUserParam for UserID {
     async fn verify(token: &Token, state: &AppState) -> Option<Self> {
-        if token.ty != TokenTy::Personal {
-            return None;
-        }
+        let res = personal_token(token, state)
+            .await
+            .filter(|v| v.space_id.is_none() && v.scopes.is_none())?;
 
-        let iat = token.iat as i64;
-        let rnd = token.rnd as i64;
-        let res = sqlx::query!(
-            "SELECT user_id FROM tokens WHERE iat = ? AND rnd = ?",
-            iat,
-            rnd
+        Some(
+            UserID::from(res.user_id)
+                .expect("Invalid user id from database in AuthenticatedUser::from_request_parts"),
         )
-        .fetch_optional(&state.db)
-        .await
-        .expect("database");
+    }
 
-        res.map(|v| {
-            UserID::from(v.user_id)
-                .expect("Invalid user id from database in AuthenticatedUser::from_request_parts")
-        })
+    async fn verify_proxy_user(username: &str, state: &AppState) -> Option<Self> {
+        let user = proxy_user(username, state).await?;
+        Some(
+            UserID::from(user.id)
+                .expect("Invalid user id from database in AuthenticatedUser::from_request_parts"),
+        )
     }
 }
 
 #[async_trait]
 impl AuthenticatedUserParam for DbUser {
     async fn verify(token: &Token, state: &AppState) -> Option<Self> {
-        if token.ty != TokenTy::Personal {
-            return None;
+        let res = personal_token(token, state)
+            .await
+            .filter(|v| v.space_id.is_none() && v.scopes.is_none())?;
+
+        db_user(&res.user_id, state).await
+    }
+
+    async fn verify_proxy_user(username: &str, state: &AppState) -> Option<Self> {
+        proxy_user(username, state).await
+    }
+}
+
+#[async_trait]
+impl AuthenticatedUserParam for ScopedUser {
+    async fn verify(token: &Token, state: &AppState) -> Option<Self> {
+        let res = personal_token(token, state).await?;
+
+        let scope = match (res.space_id, res.scopes) {
+            (None, None) => None,
+            (space_id, scopes) => Some(TokenScope {
+                space_id: match space_id {
+                    Some(v) => Some(SpaceID::from(v)?),
+                    None => None,
+                },
+                scopes: scopes
+                    .unwrap_or_default()
+                    .split_whitespace()
+                    .map(Into::into)
+                    .collect(),
+            }),
+        };
+
+        Some(Self {
+            user: db_user(&res.user_id, state).await?,
+            scope,
+        })
+    }
+
+    async fn verify_proxy_user(username: &str, state: &AppState) -> Option<Self> {
+        Some(Self {
+            user: proxy_user(username, state).await?,
+            scope: None,
+        })
+    }
+}
+
+/// Resolve personal token, from cache if possible. Returns `None` if token is unknown
+/// or expired.
+async fn personal_token(token: &Token, state: &AppState) -> Option<CachedToken> {
+    if token.ty != TokenTy::Personal {
+        return None;
+    }
+
+    let hash = tokens::hash(token);
+    let res = match state.cache.token(&hash) {
+        Some(v) => v,
+        None => {
+            tokens::upgrade_personal(&state.db, token, &hash).await;
+            let v = sqlx::query!(
+                "SELECT hash AS \"hash!\", user_id, space_id, scopes, expires_at
+                FROM tokens WHERE hash = ?",
+                hash
+            )
+            .fetch_optional(&state.db)
+            .await
+            .expect("database")
+            .filter(|v| tokens::matches(&v.hash, token))
+            .map(|v| CachedToken {
+                user_id: v.user_id,
+                space_id: v.space_id,
+                scopes: v.scopes,
+                expires_at: v.expires_at,
+            })?;
+            state.cache.insert_token(hash, v.clone());
+            v
         }
+    };
 
-        let iat = token.iat as i64;
-        let rnd = token.rnd as i64;
+    let now = app::now_ms();
+    res.expires_at.is_none_or(|v| v > now).then_some(res)
+}
 
-        sqlx::query_as!(
-            DbUser,
-            "SELECT users.* FROM users INNER JOIN tokens ON tokens.user_id = users.id WHERE tokens.iat = ? AND tokens.rnd = ?",
-            iat,
-            rnd
-        )
+/// User named by trusted proxy. Proxy login is still login, so locked users are rejected.
+async fn proxy_user(name: &str, state: &AppState) -> Option<DbUser> {
+    let now = app::now_ms();
+    sqlx::query_as!(DbUser, "SELECT * FROM users WHERE name = ?", name)
+        .fetch_optional(&state.db)
+        .await
+        .expect("database")
+        .filter(|v| v.login_locked_until.is_none_or(|v| v <= now))
+}
+
+async fn db_user(id: &str, state: &AppState) -> Option<DbUser> {
+    sqlx::query_as!(DbUser, "SELECT * FROM users WHERE id = ?", id)
         .fetch_optional(&state.db)
         .await
         .expect("database")
+}
+
+/// Owner and state of space, from cache if possible.
+async fn space(space_id: &str, state: &AppState) -> Option<CachedSpace> {
+    if let Some(v) = state.cache.space(space_id) {
+        return Some(v);
     }
+
+    let v = sqlx::query_as!(
+        CachedSpace,
+        "SELECT owner_id, archived, org_id FROM spaces WHERE id = ?",
+        space_id
+    )
+    .fetch_optional(&state.db)
+    .await
+    .expect("database")?;
+    state.cache.insert_space(space_id, v.clone());
+    Some(v)
 }
 
 #[async_trait]
@@ -95,21 +424,79 @@ impl AuthenticatedUserParam for DbService {
             return None;
         }
 
-        let iat = token.iat as i64;
-        let rnd = token.rnd as i64;
+        let hash = tokens::hash(token);
+
+        let res = match state.cache.service(&hash) {
+            Some(v) => v,
+            None => {
+                tokens::upgrade_service(&state.db, token, &hash).await;
+                let v = sqlx::query!(
+                    r#"
+                    SELECT
+                        service_tokens.hash AS "hash!",
+                        service_accounts.id,
+                        service_accounts.space_id,
+                        service_accounts.ty
+                    FROM service_tokens
+                        INNER JOIN service_accounts
+                            ON service_tokens.service_id = service_accounts.id
+                    WHERE service_tokens.hash = ?"#,
+                    hash
+                )
+                .fetch_optional(&state.db)
+                .await
+                .expect("database")
+                .filter(|v| tokens::matches(&v.hash, token))
+                .map(|v| CachedService {
+                    id: v.id,
+                    space_id: v.space_id,
+                    ty: v.ty,
+                })?;
+                state.cache.insert_service(hash.clone(), v.clone());
+                v
+            }
+        };
+        let space_archived = match &res.space_id {
+            Some(space_id) => space(space_id, state).await.is_some_and(|v| v.archived),
+            None => false,
+        };
+
+        // usage is recorded at most once per `SESSION_TRACK_INTERVAL_MS`, like sessions of
+        // personal tokens, so busy services don't write on every request
+        let now = app::now_ms();
+        let since = now - SESSION_TRACK_INTERVAL_MS;
+        sqlx::query!(
+            "UPDATE service_tokens SET last_used_at = ?
+            WHERE hash = ? AND (last_used_at IS NULL OR last_used_at <= ?)",
+            now,
+            hash,
+            since
+        )
+        .execute(&state.db)
+        .await
+        .expect("database");
+
+        Some(Self {
+            id: ServiceAccountID::from(res.id)?,
+            space_id: res.space_id.and_then(SpaceID::from),
+            ty: ServiceAccountTy::try_from(res.ty).ok()?,
+            space_archived,
+        })
+    }
 
+    async fn verify_certificate(fingerprint: &str, state: &AppState) -> Option<Self> {
         let res = sqlx::query!(
             "
                 SELECT
-                    service_tokens.service_id as id,
+                    service_accounts.id,
                     service_accounts.space_id,
-                    service_accounts.ty
-                FROM service_tokens
-                    INNER JOIN service_accounts
-                        ON service_tokens.service_id = service_accounts.id
-                WHERE service_tokens.iat = ? AND service_tokens.rnd = ?",
-            iat,
-            rnd
+                    service_accounts.ty,
+                    COALESCE(spaces.archived, 0) AS \"space_archived!: bool\"
+                FROM service_accounts
+                    LEFT JOIN spaces
+                        ON service_accounts.space_id = spaces.id
+                WHERE service_accounts.cert_fingerprint = ?",
+            fingerprint
         )
         .fetch_optional(&state.db)
         .await
@@ -117,8 +504,9 @@ impl AuthenticatedUserParam for DbService {
 
         Some(Self {
             id: ServiceAccountID::from(res.id)?,
-            space_id: res.space_id.map(SpaceID::from).flatten(),
+            space_id: res.space_id.and_then(SpaceID::from),
             ty: ServiceAccountTy::try_from(res.ty).ok()?,
+            space_archived: res.space_archived,
         })
     }
 }
@@ -141,6 +529,47 @@ impl<U: AuthenticatedUserParam> FromRequestParts<AppState> for AuthenticatedUser
             .filter(|v| v.starts_with("Bearer "))
             .map(|v| &v[("Bearer ".len())..]);
 
+        let peer = parts
+            .extensions
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(addr)| addr.ip());
+
+        // certificate is used only if there is no bearer token and header is set by
+        // trusted proxy, otherwise anyone could send fingerprint of bound certificate
+        let fingerprint = state
+            .client_cert_header
+            .as_ref()
+            .filter(|_| token_str.is_none())
+            .filter(|_| peer.is_none_or(|v| is_trusted(&state.trusted_proxies, v)))
+            .and_then(|v| headers.get(v))
+            .and_then(|v| v.to_str().ok())
+            .and_then(cert_fingerprint);
+        if let Some(fingerprint) = fingerprint {
+            return match U::verify_certificate(&fingerprint, state).await {
+                Some(user) => Ok(Self { token: None, user }),
+                None => Err(api::Response::Failture(
+                    api::Error::Unauthorized.detail("Unknown client certificate".into()),
+                )),
+            };
+        }
+
+        // same for username given by proxy
+        let proxy_user = state
+            .proxy_auth
+            .as_ref()
+            .filter(|_| token_str.is_none())
+            .filter(|v| v.trusts(peer))
+            .and_then(|v| headers.get(&v.header))
+            .and_then(|v| v.to_str().ok());
+        if let Some(username) = proxy_user {
+            return match U::verify_proxy_user(username, state).await {
+                Some(user) => Ok(Self { token: None, user }),
+                None => Err(api::Response::Failture(
+                    api::Error::Unauthorized.detail("Unknown user given by proxy".into()),
+                )),
+            };
+        }
+
         let Some(Ok(token)) = token_str.map(Token::parse) else {
             return Err(api::Response::Failture(api::Error::Unauthorized.detail(
                 "Expected valid user token in header `Authorization: Bearer <TOKEN>`".into(),
@@ -150,10 +579,277 @@ impl<U: AuthenticatedUserParam> FromRequestParts<AppState> for AuthenticatedUser
         let user = <U as AuthenticatedUserParam>::verify(&token, state).await;
 
         match user {
-            Some(user) => Ok(Self { token, user }),
+            Some(user) => {
+                let ClientIp(ip) = ClientIp::from_request_parts(parts, state)
+                    .await
+                    .map_err(|err| match err {})?;
+                match token.ty {
+                    TokenTy::Personal => track_session(&token, &headers, ip, state).await,
+                    TokenTy::Service => track_service_ip(&token, ip, state).await,
+                }
+                Ok(Self {
+                    token: Some(token),
+                    user,
+                })
+            }
             None => Err(api::Response::Failture(
                 api::Error::Unauthorized.detail("Unknown token".into()),
             )),
         }
     }
 }
+
+/// How often session info of personal token and usage time of service token are
+/// updated in milliseconds
+const SESSION_TRACK_INTERVAL_MS: i64 = 60 * 1000;
+
+/// IP address of client. Taken from connection or, if connection comes from unix socket or
+/// one of [`AppState::trusted_proxies`], from `Forwarded` or `X-Forwarded-For` header.
+pub struct ClientIp(pub Option<String>);
+
+#[async_trait]
+impl FromRequestParts<AppState> for ClientIp {
+    type Rejection = std::convert::Infallible;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        let peer = parts
+            .extensions
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(addr)| addr.ip());
+        let ip = client_ip(peer, &parts.headers, &state.trusted_proxies);
+        Ok(Self(ip.map(|v| v.to_string())))
+    }
+}
+
+/// Resolve client address. `peer` is address of connection, `None` for unix socket.
+///
+/// Hops of forwarding header are walked from the nearest one, skipping trusted proxies.
+/// First untrusted hop is client. If header ends (or has malformed hop) before it, the
+/// farthest known address is used.
+fn client_ip(peer: Option<IpAddr>, headers: &HeaderMap, trusted: &[IpNet]) -> Option<IpAddr> {
+    if peer.is_some_and(|v| !is_trusted(trusted, v)) {
+        return peer;
+    }
+
+    let hops: Vec<&str> = match headers.get(FORWARDED).and_then(|v| v.to_str().ok()) {
+        Some(forwarded) => forwarded
+            .split(',')
+            .filter_map(|v| {
+                v.split(';').find_map(|pair| {
+                    let (k, v) = pair.split_once('=')?;
+                    k.trim().eq_ignore_ascii_case("for").then_some(v.trim())
+                })
+            })
+            .collect(),
+        None => headers
+            .get_all("x-forwarded-for")
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .flat_map(|v| v.split(','))
+            .map(str::trim)
+            .collect(),
+    };
+
+    let mut client = peer;
+    for hop in hops.into_iter().rev() {
+        let Some(ip) = parse_hop(hop) else {
+            break;
+        };
+        client = Some(ip);
+        if !is_trusted(trusted, ip) {
+            break;
+        }
+    }
+    client
+}
+
+/// Parse address of forwarding hop: `1.2.3.4`, `1.2.3.4:80`, `"[::1]:80"` or `::1`.
+fn parse_hop(hop: &str) -> Option<IpAddr> {
+    let hop = hop.trim_matches('"');
+    if let Ok(ip) = hop.parse() {
+        return Some(ip);
+    }
+    if let Some(hop) = hop.strip_prefix('[') {
+        return hop.split_once(']')?.0.parse().ok();
+    }
+    hop.parse::<SocketAddr>().ok().map(|v| v.ip())
+}
+
+/// Record IP of last service token usage. Row is written only when address changes.
+async fn track_service_ip(token: &Token, ip: Option<String>, state: &AppState) {
+    let hash = tokens::hash(token);
+    sqlx::query!(
+        "UPDATE service_tokens SET last_used_ip = ? WHERE hash = ? AND last_used_ip IS NOT ?",
+        ip,
+        hash,
+        ip
+    )
+    .execute(&state.db)
+    .await
+    .expect("database");
+}
+
+/// Pool for read-only queries of listings and logs, see [`AppState::read_db`].
+pub struct ReadDb(pub SqlitePool);
+
+#[async_trait]
+impl FromRequestParts<AppState> for ReadDb {
+    type Rejection = std::convert::Infallible;
+
+    async fn from_request_parts(_: &mut Parts, state: &AppState) -> Result<Self, Self::Rejection> {
+        Ok(Self(state.read_db().clone()))
+    }
+}
+
+/// Record user agent, IP and usage time of personal token, at most once per
+/// [`SESSION_TRACK_INTERVAL_MS`].
+async fn track_session(token: &Token, headers: &HeaderMap, ip: Option<String>, state: &AppState) {
+    let now = app::now_ms();
+    let hash = tokens::hash(token);
+
+    let last_used_at = sqlx::query!("SELECT last_used_at FROM tokens WHERE hash = ?", hash)
+        .fetch_optional(&state.db)
+        .await
+        .expect("database")
+        .and_then(|v| v.last_used_at);
+    if last_used_at.is_some_and(|v| now - v < SESSION_TRACK_INTERVAL_MS) {
+        return;
+    }
+
+    let user_agent = headers.get(USER_AGENT).and_then(|v| v.to_str().ok());
+
+    sqlx::query!(
+        "UPDATE tokens SET user_agent = ?, ip = ?, last_used_at = ? WHERE hash = ?",
+        user_agent,
+        ip,
+        now,
+        hash
+    )
+    .execute(&state.db)
+    .await
+    .expect("database");
+}
+
+#[derive(Deserialize)]
+struct SpaceAccessPath {
+    space_id: SpaceID,
+}
+
+#[async_trait]
+impl<P: SpacePermission> FromRequestParts<AppState> for SpaceAccess<P> {
+    type Rejection = api::Response;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        let axum::extract::Path(SpaceAccessPath { space_id }) =
+            axum::extract::Path::from_request_parts(parts, state)
+                .await
+                .map_err(|err| {
+                    api::Response::Failture(
+                        api::Error::MalformedData.detail(err.body_text().into()),
+                    )
+                })?;
+        let AuthenticatedUser {
+            user: ScopedUser { user, scope },
+            ..
+        } = AuthenticatedUser::<ScopedUser>::from_request_parts(parts, state).await?;
+
+        if let Some(scope) = scope {
+            if scope.space_id.as_ref().is_some_and(|v| *v != space_id) {
+                return Err(api::Response::Failture(api::Error::ObjectNotFound.into()));
+            }
+            if !P::scoped(&scope.scopes, &parts.method) {
+                return Err(api::Response::Failture(
+                    api::Error::Forbidden.detail("Token scopes do not allow this request".into()),
+                ));
+            }
+        }
+
+        let allowed = state
+            .roles
+            .load()
+            .get_current(user.level)
+            .map(|v| P::allowed(&v.permissions))
+            .unwrap_or(false);
+
+        let Some(space) = space(&space_id, state).await else {
+            return Err(api::Response::Failture(api::Error::ObjectNotFound.into()));
+        };
+        let org_admin = match &space.org_id {
+            Some(org_id) if !allowed && space.owner_id != user.id => {
+                org_role(&state.db, org_id, &user.id)
+                    .await
+                    .is_some_and(|v| v >= OrgRole::Admin)
+            }
+            _ => false,
+        };
+
+        if allowed || org_admin || space.owner_id == user.id {
+            Ok(Self {
+                space_id,
+                archived: space.archived,
+                _permission: PhantomData,
+            })
+        } else {
+            Err(api::Response::Failture(api::Error::ObjectNotFound.into()))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use axum::http::HeaderValue;
+
+    use super::*;
+
+    fn resolve(peer: Option<&str>, header: (&'static str, &'static str)) -> Option<IpAddr> {
+        let trusted = ["10.0.0.0/8".parse().unwrap()];
+        let mut headers = HeaderMap::new();
+        headers.insert(header.0, HeaderValue::from_static(header.1));
+        client_ip(peer.map(|v| v.parse().unwrap()), &headers, &trusted)
+    }
+
+    #[test]
+    fn client_ip_through_proxies() {
+        let ip = |v: &str| Some(v.parse::<IpAddr>().unwrap());
+        let xff = "x-forwarded-for";
+
+        // untrusted peer can't pass address
+        assert_eq!(resolve(Some("1.1.1.1"), (xff, "2.2.2.2")), ip("1.1.1.1"));
+        // trusted hops are skipped, spoofed left part is ignored
+        assert_eq!(
+            resolve(Some("10.0.0.1"), (xff, "3.3.3.3, 2.2.2.2, 10.0.0.2")),
+            ip("2.2.2.2")
+        );
+        // unix socket is trusted
+        assert_eq!(resolve(None, (xff, "2.2.2.2")), ip("2.2.2.2"));
+        // malformed hop stops walking
+        assert_eq!(
+            resolve(Some("10.0.0.1"), (xff, "2.2.2.2, unknown, 10.0.0.2")),
+            ip("10.0.0.2")
+        );
+        assert_eq!(
+            resolve(
+                Some("10.0.0.1"),
+                (
+                    "forwarded",
+                    r#"for=2.2.2.2, for="[2001:db8::1]:80";proto=https"#
+                )
+            ),
+            ip("2001:db8::1")
+        );
+        assert_eq!(
+            resolve(
+                Some("10.0.0.1"),
+                ("forwarded", "for=2.2.2.2:443;by=10.0.0.1")
+            ),
+            ip("2.2.2.2")
+        );
+        assert_eq!(resolve(None, ("forwarded", "for=_hidden")), None);
+    }
+}
@@ -0,0 +1,165 @@
+//! `Idempotency-Key` support for `PUT` and `POST` endpoints.
+//!
+//! Response to request with `Idempotency-Key` header is stored for [`IDEMPOTENCY_TTL_MS`]
+//! and replayed on retries with same key, method, path and credentials. Reusing key
+//! with different body is rejected.
+
+use archk::v1::api;
+use axum::{
+    body::{self, Body},
+    extract::{Request, State},
+    http::{
+        header::{AUTHORIZATION, CONTENT_TYPE},
+        HeaderName, HeaderValue, Method, StatusCode,
+    },
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use base64::{prelude::BASE64_STANDARD_NO_PAD, Engine};
+use sha2::{Digest, Sha256};
+
+use crate::app::{self, AppState};
+
+/// How long responses are stored
+pub const IDEMPOTENCY_TTL_MS: i64 = 1000 * 60 * 60 * 24;
+
+/// Maximum length of `Idempotency-Key` value
+const MAX_KEY_LEN: usize = 255;
+
+pub static IDEMPOTENCY_KEY: HeaderName = HeaderName::from_static("idempotency-key");
+pub static IDEMPOTENCY_REPLAYED: HeaderName = HeaderName::from_static("idempotency-replayed");
+
+fn hash(parts: &[&[u8]]) -> String {
+    let mut hasher = Sha256::new();
+    for part in parts {
+        hasher.update((part.len() as u64).to_le_bytes());
+        hasher.update(part);
+    }
+    BASE64_STANDARD_NO_PAD.encode(hasher.finalize())
+}
+
+fn failture(err: api::ErrorData) -> Response {
+    api::Response::<api::NeverSerialize>::Failture(err).into_response()
+}
+
+pub async fn idempotency(
+    State(AppState { db, .. }): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if request.method() != Method::PUT && request.method() != Method::POST {
+        return next.run(request).await;
+    }
+    let Some(key) = request.headers().get(&IDEMPOTENCY_KEY) else {
+        return next.run(request).await;
+    };
+    let Some(key) = key
+        .to_str()
+        .ok()
+        .filter(|v| !v.is_empty() && v.len() <= MAX_KEY_LEN)
+        .map(String::from)
+    else {
+        return failture(api::Error::MalformedData.detail(
+            format!("`Idempotency-Key` should be visible ASCII up to {MAX_KEY_LEN} bytes").into(),
+        ));
+    };
+
+    let (parts, body) = request.into_parts();
+    // size of body is limited by `RequestBodyLimitLayer` of router
+    let body = match body::to_bytes(body, usize::MAX).await {
+        Ok(v) => v,
+        Err(err) => {
+            return failture(
+                api::Error::PayloadTooLarge
+                    .detail(format!("unable to read request body: {err}").into()),
+            )
+        }
+    };
+
+    let authorization = parts
+        .headers
+        .get(AUTHORIZATION)
+        .map(HeaderValue::as_bytes)
+        .unwrap_or_default();
+    let scope = hash(&[
+        authorization,
+        parts.method.as_str().as_bytes(),
+        parts.uri.path().as_bytes(),
+    ]);
+    let request_hash = hash(&[&body]);
+
+    let now = app::now_ms();
+    let since = now - IDEMPOTENCY_TTL_MS;
+
+    let stored = sqlx::query!(
+        r#"
+        SELECT request_hash, status, response FROM idempotency
+        WHERE scope = ? AND key = ? AND created_at >= ?"#,
+        scope,
+        key,
+        since
+    )
+    .fetch_optional(&db)
+    .await
+    .expect("database");
+
+    if let Some(stored) = stored {
+        if stored.request_hash != request_hash {
+            return failture(
+                api::Error::Conflict
+                    .detail("`Idempotency-Key` already used with different request".into()),
+            );
+        }
+
+        let status = u16::try_from(stored.status)
+            .ok()
+            .and_then(|v| StatusCode::from_u16(v).ok())
+            .unwrap_or(StatusCode::OK);
+        let mut response = Response::new(Body::from(stored.response));
+        *response.status_mut() = status;
+        response
+            .headers_mut()
+            .insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+        response.headers_mut().insert(
+            IDEMPOTENCY_REPLAYED.clone(),
+            HeaderValue::from_static("true"),
+        );
+        return response;
+    }
+
+    let response = next.run(Request::from_parts(parts, Body::from(body))).await;
+
+    let is_json = response
+        .headers()
+        .get(CONTENT_TYPE)
+        .map(|v| v == "application/json")
+        .unwrap_or(false);
+    // Server errors are not stored so request can be retried
+    if !is_json || response.status().is_server_error() {
+        return response;
+    }
+
+    let (parts, body) = response.into_parts();
+    let Ok(body) = body::to_bytes(body, usize::MAX).await else {
+        return failture(api::Error::Internal.detail("unable to read response body".into()));
+    };
+
+    let status = parts.status.as_u16();
+    let response_body: &[u8] = &body;
+    sqlx::query!(
+        r#"
+        INSERT OR REPLACE INTO idempotency(scope, key, request_hash, created_at, status, response)
+        VALUES (?, ?, ?, ?, ?, ?)"#,
+        scope,
+        key,
+        request_hash,
+        now,
+        status,
+        response_body
+    )
+    .execute(&db)
+    .await
+    .expect("database");
+
+    Response::from_parts(parts, Body::from(body))
+}
@@ -0,0 +1,191 @@
+//! Configurable item statuses of space and transitions between them, see [`ItemStatus`].
+
+use archk::{
+    v1::{
+        api::{self, Response},
+        space::{ItemStatus, SpaceLog, SpaceLogAction},
+        validate,
+    },
+    Documentation,
+};
+use axum::extract::State;
+use serde::{Deserialize, Serialize};
+
+use crate::app::{self, AppState};
+
+use super::{
+    extra::{Json, Path, SpaceAccess},
+    space::{archived_conflict, insert_log, SpaceItemPath, SpaceLogEntry, MAX_LOG_COMMENT_LEN},
+};
+
+#[derive(Serialize, Deserialize, Documentation)]
+pub struct ItemStatusBody {
+    /// New status, should be in `next` of current status
+    pub status: String,
+    /// Reason of change if any, stored in space logs
+    #[serde(default)]
+    pub reason: Option<String>,
+}
+
+/// Item statuses of space, [`ItemStatus::defaults`] if space has no own ones.
+pub(crate) async fn fetch_statuses<'e, E>(db: E, space_id: &str) -> Vec<ItemStatus>
+where
+    E: sqlx::Executor<'e, Database = sqlx::Sqlite>,
+{
+    let res = sqlx::query_scalar!(
+        r#"SELECT statuses AS "statuses: sqlx::types::Json<Vec<ItemStatus>>"
+        FROM spaces_items_statuses WHERE space_id = ?"#,
+        space_id
+    )
+    .fetch_optional(db)
+    .await
+    .expect("database");
+
+    res.map(|v| v.0).unwrap_or_else(ItemStatus::defaults)
+}
+
+pub async fn get_item_statuses(
+    SpaceAccess { space_id, .. }: SpaceAccess,
+    State(AppState { db, .. }): State<AppState>,
+) -> Response<Vec<ItemStatus>> {
+    Response::Success(fetch_statuses(&db, &space_id).await)
+}
+
+pub async fn put_item_statuses(
+    SpaceAccess {
+        space_id, archived, ..
+    }: SpaceAccess,
+    State(AppState { db, .. }): State<AppState>,
+    Json(statuses): Json<Vec<ItemStatus>>,
+) -> Response<Vec<ItemStatus>> {
+    if archived {
+        return Response::Failture(archived_conflict());
+    }
+    if let Err(e) = validate::item_statuses(&statuses) {
+        return Response::Failture(e.into());
+    }
+
+    let space_id: &str = &space_id;
+    let names =
+        serde_json::to_string(&statuses.iter().map(|v| &v.name).collect::<Vec<_>>()).expect("json");
+    let statuses_str = serde_json::to_string(&statuses).expect("json");
+    let mut tx = app::begin(&db).await;
+
+    // items can't be left in removed statuses
+    let orphaned = sqlx::query_scalar!(
+        r#"
+        SELECT DISTINCT status FROM spaces_items
+        WHERE space_id = ? AND status NOT IN (SELECT value FROM json_each(?))
+        ORDER BY status"#,
+        space_id,
+        names
+    )
+    .fetch_all(&mut *tx)
+    .await
+    .expect("database");
+    if !orphaned.is_empty() {
+        return Response::Failture(
+            api::Error::Conflict
+                .detail(format!("items are in removed statuses: {}", orphaned.join(", ")).into()),
+        );
+    }
+
+    sqlx::query!(
+        r#"
+        INSERT INTO spaces_items_statuses(space_id, statuses) VALUES (?1, ?2)
+        ON CONFLICT(space_id) DO UPDATE SET statuses = ?2"#,
+        space_id,
+        statuses_str
+    )
+    .execute(&mut *tx)
+    .await
+    .expect("database");
+    tx.commit().await.expect("database");
+
+    Response::Success(statuses)
+}
+
+pub async fn patch_item_status(
+    Path(SpaceItemPath { item_id, .. }): Path<SpaceItemPath>,
+    SpaceAccess {
+        space_id, archived, ..
+    }: SpaceAccess,
+    State(AppState { db, .. }): State<AppState>,
+    Json(ItemStatusBody { status, reason }): Json<ItemStatusBody>,
+) -> Response<SpaceLogEntry> {
+    if archived {
+        return Response::Failture(archived_conflict());
+    }
+    let reason = reason
+        .map(|v| v.trim().to_string())
+        .filter(|v| !v.is_empty());
+    if reason
+        .as_ref()
+        .is_some_and(|v| v.chars().count() > MAX_LOG_COMMENT_LEN)
+    {
+        return Response::Failture(api::Error::MalformedData.detail(
+            format!("`reason` should be at most {MAX_LOG_COMMENT_LEN} characters").into(),
+        ));
+    }
+
+    let item_id_str: &str = &item_id;
+    let space_id_str: &str = &space_id;
+    let mut tx = app::begin(&db).await;
+
+    let current = sqlx::query_scalar!(
+        "SELECT status FROM spaces_items WHERE id = ? AND space_id = ?",
+        item_id_str,
+        space_id_str
+    )
+    .fetch_optional(&mut *tx)
+    .await
+    .expect("database");
+    let Some(current) = current else {
+        return Response::Failture(api::Error::ObjectNotFound.into());
+    };
+
+    let statuses = fetch_statuses(&mut *tx, space_id_str).await;
+    if !statuses.iter().any(|v| v.name == status) {
+        return Response::Failture(
+            api::Error::ObjectNotFound.detail(format!("unknown item status `{status}`").into()),
+        );
+    }
+    let allowed = statuses
+        .iter()
+        .find(|v| v.name == current)
+        .is_some_and(|v| v.allows(&status));
+    if !allowed {
+        return Response::Failture(api::Error::Conflict.detail(
+            format!("item status can't be changed from `{current}` to `{status}`").into(),
+        ));
+    }
+
+    let res = sqlx::query!(
+        "UPDATE spaces_items SET status = ? WHERE id = ? AND space_id = ? AND status = ?",
+        status,
+        item_id_str,
+        space_id_str,
+        current
+    )
+    .execute(&mut *tx)
+    .await
+    .expect("database")
+    .rows_affected();
+    if res == 0 {
+        return Response::Failture(
+            api::Error::Conflict.detail("item status was changed concurrently".into()),
+        );
+    }
+
+    let detail = match reason {
+        Some(reason) => format!("{current} -> {status}: {reason}"),
+        None => format!("{current} -> {status}"),
+    };
+    let log = SpaceLog::new(space_id.clone(), SpaceLogAction::ItemStatusChanged)
+        .with_item(item_id)
+        .with_detail(detail);
+    insert_log(&mut *tx, &log).await.expect("database");
+    tx.commit().await.expect("database");
+
+    Response::Success(log.into())
+}
@@ -0,0 +1,170 @@
+//! Read-only maintenance mode, eg. for migrations and backups.
+//!
+//! Mode is stored in `maintenance` table and checked by [`maintenance`] middleware on
+//! every request that may change something, so it applies to all servers sharing database.
+
+use archk::{
+    v1::{
+        api,
+        audit::{AuditAction, AuditLog},
+    },
+    Documentation,
+};
+use axum::{
+    extract::{FromRequestParts, Request, State},
+    http::Method,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
+
+use crate::{
+    app::{self, AppState},
+    roles::perm,
+};
+
+use super::{
+    admin::insert_audit,
+    extra::{AuthenticatedUser, ClientIp, DbUser, Json},
+};
+
+/// `detail` of rejected requests if mode has no message
+const DEFAULT_MESSAGE: &str = "server is in maintenance mode, try again later";
+
+/// Maximum length of [`MaintenanceBody::message`]
+const MAX_MESSAGE_LEN: usize = 1024;
+
+#[derive(Serialize, Documentation)]
+pub struct Maintenance {
+    /// Message returned in `detail` of rejected requests
+    pub message: Option<String>,
+    /// Timestamp in milliseconds of enabling
+    pub enabled_at: i64,
+    /// User enabled mode, `null` if deleted
+    pub enabled_by: Option<String>,
+}
+
+#[derive(Deserialize, Documentation)]
+pub struct MaintenanceBody {
+    /// Enable or disable mode
+    pub enabled: bool,
+    /// Message for clients, eg. `"database migration, back in 10 minutes"`. Up to 1024 bytes
+    #[serde(default)]
+    pub message: Option<String>,
+}
+
+async fn fetch(db: &SqlitePool) -> Option<Maintenance> {
+    sqlx::query_as!(
+        Maintenance,
+        "SELECT message, enabled_at, enabled_by FROM maintenance WHERE id = 0"
+    )
+    .fetch_optional(db)
+    .await
+    .expect("database")
+}
+
+/// Is request allowed in maintenance mode regardless of user? Read-only requests and
+/// logins (`/auth...`), so users with `maintenance` permission can obtain tokens.
+fn exempt(request: &Request) -> bool {
+    let path = request.uri().path();
+    matches!(
+        *request.method(),
+        Method::GET | Method::HEAD | Method::OPTIONS
+    ) || path == "/auth"
+        || path.starts_with("/auth/")
+}
+
+/// Rejects requests other than `GET`, `HEAD`, `OPTIONS` and logins with
+/// `503 Service Unavailable` while maintenance mode is enabled, unless user has
+/// `maintenance` permission.
+pub async fn maintenance(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    if exempt(&request) {
+        return next.run(request).await;
+    }
+    let Some(mode) = fetch(&state.db).await else {
+        return next.run(request).await;
+    };
+
+    let (mut parts, body) = request.into_parts();
+    let allowed = match AuthenticatedUser::<DbUser>::from_request_parts(&mut parts, &state).await {
+        Ok(AuthenticatedUser { user, .. }) => state.roles.load().has(user.level, perm::MAINTENANCE),
+        Err(_) => false,
+    };
+    if allowed {
+        return next.run(Request::from_parts(parts, body)).await;
+    }
+
+    let message = mode.message.unwrap_or_else(|| DEFAULT_MESSAGE.into());
+    api::Response::<api::NeverSerialize>::Failture(
+        api::Error::ServiceUnavailable.detail(message.into()),
+    )
+    .into_response()
+}
+
+pub async fn get_maintenance(
+    AuthenticatedUser {
+        user: DbUser { level, .. },
+        ..
+    }: AuthenticatedUser<DbUser>,
+    State(AppState { db, roles, .. }): State<AppState>,
+) -> api::Response<Option<Maintenance>> {
+    if !roles.load().has(level, perm::MAINTENANCE) {
+        return api::Response::Failture(api::Error::Forbidden.into());
+    }
+
+    api::Response::Success(fetch(&db).await)
+}
+
+pub async fn set_maintenance(
+    AuthenticatedUser {
+        user: DbUser { id, level, .. },
+        ..
+    }: AuthenticatedUser<DbUser>,
+    ClientIp(ip): ClientIp,
+    State(AppState { db, roles, .. }): State<AppState>,
+    Json(MaintenanceBody { enabled, message }): Json<MaintenanceBody>,
+) -> api::Response<Option<Maintenance>> {
+    if !roles.load().has(level, perm::MAINTENANCE) {
+        return api::Response::Failture(api::Error::Forbidden.into());
+    }
+    if message.as_ref().is_some_and(|v| v.len() > MAX_MESSAGE_LEN) {
+        return api::Response::Failture(
+            api::Error::MalformedData
+                .detail(format!("message is longer than {MAX_MESSAGE_LEN} bytes").into()),
+        );
+    }
+
+    let mut tx = crate::app::begin(&db).await;
+    let log = if enabled {
+        let now = app::now_ms();
+        sqlx::query!(
+            r#"
+            INSERT INTO maintenance(id, message, enabled_at, enabled_by) VALUES (0, ?, ?, ?)
+            ON CONFLICT (id) DO UPDATE SET message = excluded.message"#,
+            message,
+            now,
+            id
+        )
+        .execute(&mut *tx)
+        .await
+        .expect("database");
+        let log = AuditLog::new(id, AuditAction::MaintenanceEnabled);
+        match message {
+            Some(message) => log.with_detail(message),
+            None => log,
+        }
+    } else {
+        sqlx::query!("DELETE FROM maintenance")
+            .execute(&mut *tx)
+            .await
+            .expect("database");
+        AuditLog::new(id, AuditAction::MaintenanceDisabled)
+    };
+    insert_audit(&mut *tx, &log.with_ip(ip))
+        .await
+        .expect("database");
+    tx.commit().await.expect("database");
+
+    api::Response::Success(fetch(&db).await)
+}
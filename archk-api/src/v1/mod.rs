@@ -1,35 +1,116 @@
-use std::{any::Any, mem};
+use std::{any::Any, mem, sync::Arc};
 
 use archk::v1::api;
 use axum::{
-    body::Body,
-    extract::Request,
-    http::header::CONTENT_TYPE,
+    body::{Body, HttpBody},
+    extract::{DefaultBodyLimit, Request, State},
+    http::{
+        header::{CONTENT_LENGTH, CONTENT_TYPE, ETAG, IF_NONE_MATCH, UPGRADE},
+        HeaderValue, Method, StatusCode,
+    },
     middleware::{self, Next},
     response::{IntoResponse, Response},
     routing::{patch, post, put},
     Router,
 };
-use http_body_util::BodyExt;
+use http_body_util::{BodyExt, Limited};
 use tower::ServiceBuilder;
-use tower_http::{catch_panic::CatchPanicLayer, trace::TraceLayer};
+use tower_http::{
+    catch_panic::CatchPanicLayer,
+    compression::{
+        predicate::{Predicate, SizeAbove},
+        CompressionLayer,
+    },
+    cors::CorsLayer,
+    trace::TraceLayer,
+};
 
-use crate::app::AppState;
+use crate::app::{AppConfigServerCompression, AppState};
 
+mod access_window;
+mod admin;
+mod attachment;
 mod auth;
+mod calendar;
+mod errors;
+mod export;
 mod extra;
+pub mod idempotency;
+mod item_status;
+mod maintenance;
+pub mod mqtt;
+mod org;
+mod quota;
+mod reservation;
+mod route_check;
 pub mod routes;
 mod service;
+mod share;
 mod space;
 mod user;
 
-pub fn get_routes() -> Router<AppState> {
-    routes::get_routes().fallback(fallback).layer(
+pub(crate) use attachment::purge_file;
+pub(crate) use space::{LogCursor, SYNC_DELETIONS_TTL_MS};
+
+/// Routes of API v1 with all middlewares. Pass `cors` to allow cross-origin requests,
+/// see [`crate::app::AppConfigServerCors::layer`]. Requests with body larger than
+/// `body_limit` bytes are rejected with `413 Payload Too Large`, except uploads of
+/// attachments limited by their `max_size`. Responses are gzipped by [`CompressionLayer`]
+/// if `compression` passed, see [`Compressible`].
+pub fn get_routes(
+    state: AppState,
+    cors: Option<CorsLayer>,
+    body_limit: usize,
+    compression: Option<AppConfigServerCompression>,
+) -> Router<AppState> {
+    let limits = BodyLimits {
+        body: body_limit,
+        attachments: state
+            .attachments
+            .as_ref()
+            .map_or(body_limit, |v| v.max_size),
+    };
+    let router = routes::get_routes().fallback(fallback).layer(
         ServiceBuilder::new()
             .layer(TraceLayer::new_for_http())
+            .layer(middleware::from_fn(trace_id))
             .layer(CatchPanicLayer::custom(catch_panic))
-            .layer(middleware::from_fn(catch_error)),
-    )
+            .layer(middleware::from_fn(catch_error))
+            .layer(middleware::from_fn(etag))
+            .layer(middleware::from_fn_with_state(limits, limit_body))
+            .layer(DefaultBodyLimit::max(body_limit))
+            .layer(middleware::from_fn_with_state(
+                state.clone(),
+                maintenance::maintenance,
+            ))
+            .layer(middleware::from_fn_with_state(
+                state,
+                idempotency::idempotency,
+            )),
+    );
+
+    // gzip bodies of at least `min_size` or unknown size, see `Compressible`
+    let router = match compression {
+        Some(config) => {
+            let min_size = u16::try_from(config.min_size).unwrap_or(u16::MAX);
+            router.layer(
+                ServiceBuilder::new()
+                    .layer(
+                        CompressionLayer::new()
+                            .compress_when(SizeAbove::new(min_size).and(Compressible)),
+                    )
+                    .layer(middleware::from_fn_with_state(
+                        Arc::new(config),
+                        exclude_compression,
+                    )),
+            )
+        }
+        None => router,
+    };
+    match cors {
+        Some(cors) => router.layer(cors),
+        None => router,
+    }
 }
 
 async fn fallback() -> api::Response {
@@ -60,10 +141,15 @@ async fn catch_error(request: Request, next: Next) -> Response {
         let detail = detail.to_bytes();
         let detail = String::from_utf8_lossy(&detail);
 
-        let mut new_response = api::Response::<api::NeverSerialize>::Failture(
-            api::Error::ProcessingError.detail(detail.into_owned().into()),
-        )
-        .into_response();
+        let code = match response.status() {
+            StatusCode::PAYLOAD_TOO_LARGE => api::Error::PayloadTooLarge,
+            StatusCode::TOO_MANY_REQUESTS => api::Error::RateLimited,
+            StatusCode::SERVICE_UNAVAILABLE => api::Error::ServiceUnavailable,
+            _ => api::Error::ProcessingError,
+        };
+        let mut new_response =
+            api::Response::<api::NeverSerialize>::Failture(code.detail(detail.into_owned().into()))
+                .into_response();
         *new_response.status_mut() = response.status();
 
         new_response
@@ -72,6 +158,177 @@ async fn catch_error(request: Request, next: Next) -> Response {
     }
 }
 
+/// Appends trace ID to `detail` of errors if traces are exported, see
+/// [`crate::telemetry`], so users can attach it to bug reports.
+async fn trace_id(request: Request, next: Next) -> Response {
+    let response = next.run(request).await;
+    if !(response.status().is_client_error() || response.status().is_server_error()) {
+        return response;
+    }
+    let Some(trace_id) = crate::telemetry::trace_id() else {
+        return response;
+    };
+
+    let (mut parts, body) = response.into_parts();
+    let Ok(body) = body.collect().await else {
+        return api::Response::<api::NeverSerialize>::Failture(
+            api::Error::Internal.detail("unable to read response body".into()),
+        )
+        .into_response();
+    };
+    let body = body.to_bytes();
+    let Ok(api::Response::<api::NeverSerialize>::Failture(mut error)) =
+        serde_json::from_slice(&body)
+    else {
+        return Response::from_parts(parts, Body::from(body));
+    };
+
+    error.detail = Some(match error.detail {
+        Some(detail) => format!("{detail} (trace ID {trace_id})").into(),
+        None => format!("trace ID {trace_id}").into(),
+    });
+    let body =
+        serde_json::to_vec(&api::Response::<api::NeverSerialize>::Failture(error)).expect("json");
+    parts.headers.remove(CONTENT_LENGTH);
+    Response::from_parts(parts, Body::from(body))
+}
+
+#[derive(Clone, Copy)]
+struct BodyLimits {
+    body: usize,
+    attachments: usize,
+}
+
+/// Rejects requests with `Content-Length` larger than limit and limits bodies
+/// without it, so extractors fail with `413 Payload Too Large` once limit is reached.
+async fn limit_body(State(limits): State<BodyLimits>, request: Request, next: Next) -> Response {
+    let limit = if request.method() == Method::PUT && request.uri().path().ends_with("/attachments")
+    {
+        limits.attachments
+    } else {
+        limits.body
+    };
+    let content_length = request
+        .headers()
+        .get(CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<usize>().ok());
+    if content_length.map(|v| v > limit).unwrap_or(false) {
+        return api::Response::<api::NeverSerialize>::Failture(
+            api::Error::PayloadTooLarge
+                .detail(format!("request body is larger than {limit} bytes").into()),
+        )
+        .into_response();
+    }
+
+    let (parts, body) = request.into_parts();
+    next.run(Request::from_parts(
+        parts,
+        Body::new(Limited::new(body, limit)),
+    ))
+    .await
+}
+
+/// Adds weak `ETag` to successful JSON responses of `GET /space...` and `GET /user...`
+/// endpoints and responds with `304 Not Modified` if it matches `If-None-Match`.
+async fn etag(request: Request, next: Next) -> Response {
+    let path = request.uri().path();
+    if request.method() != Method::GET || !(path.starts_with("/space") || path.starts_with("/user"))
+    {
+        return next.run(request).await;
+    }
+
+    let if_none_match = request.headers().get(IF_NONE_MATCH).cloned();
+    let response = next.run(request).await;
+
+    let is_json = response
+        .headers()
+        .get(CONTENT_TYPE)
+        .map(|v| v == "application/json")
+        .unwrap_or(false);
+    if response.status() != StatusCode::OK || !is_json {
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    let Ok(body) = body.collect().await else {
+        return api::Response::<api::NeverSerialize>::Failture(
+            api::Error::Internal.detail("unable to read response body".into()),
+        )
+        .into_response();
+    };
+    let body = body.to_bytes();
+
+    let tag = format!("W/\"{:x}-{:08x}\"", body.len(), crc32fast::hash(&body));
+    let matches = if_none_match
+        .as_ref()
+        .and_then(|v| v.to_str().ok())
+        .map(|v| {
+            v.split(',')
+                .map(str::trim)
+                .any(|v| v == "*" || v.trim_start_matches("W/") == tag.trim_start_matches("W/"))
+        })
+        .unwrap_or(false);
+
+    parts.headers.insert(
+        ETAG,
+        HeaderValue::from_str(&tag).expect("etag is valid header value"),
+    );
+
+    if matches {
+        parts.status = StatusCode::NOT_MODIFIED;
+        parts.headers.remove(CONTENT_TYPE);
+        Response::from_parts(parts, Body::empty())
+    } else {
+        Response::from_parts(parts, Body::from(body))
+    }
+}
+
+/// Marks responses of connection upgrades and [`AppConfigServerCompression::exclude`]
+/// endpoints as [`Uncompressed`].
+async fn exclude_compression(
+    State(config): State<Arc<AppConfigServerCompression>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let excluded = request.headers().contains_key(UPGRADE) || config.excludes(request.uri().path());
+    let mut response = next.run(request).await;
+    if excluded {
+        response.extensions_mut().insert(Uncompressed);
+    }
+    response
+}
+
+/// Extension of response which should not be compressed, see [`exclude_compression`].
+#[derive(Clone, Copy)]
+struct Uncompressed;
+
+/// Compresses text and JSON responses, but not server-sent events and [`Uncompressed`]
+/// ones. Used with [`SizeAbove`], so streamed bodies of unknown size are compressed.
+#[derive(Clone, Copy)]
+struct Compressible;
+
+impl Predicate for Compressible {
+    fn should_compress<B: HttpBody>(&self, response: &axum::http::Response<B>) -> bool {
+        let content_type = response
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or_default();
+        let essence = content_type.split(';').next().unwrap_or_default().trim();
+        let text = match essence.split_once('/') {
+            Some(("text", subtype)) => subtype != "event-stream",
+            Some((_, subtype)) => {
+                matches!(subtype, "json" | "x-ndjson" | "xml" | "javascript")
+                    || subtype.ends_with("+json")
+                    || subtype.ends_with("+xml")
+            }
+            None => false,
+        };
+        text && response.extensions().get::<Uncompressed>().is_none()
+    }
+}
+
 fn catch_panic(_err: Box<dyn Any + Send + 'static>) -> Response {
     api::Response::<api::NeverSerialize>::Failture(api::Error::Internal.into()).into_response()
 }
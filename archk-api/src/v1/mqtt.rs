@@ -0,0 +1,375 @@
+//! MQTT bridge for space services. Minimal MQTT 3.1.1 client with QoS 0 only.
+//!
+//! Topics (`archk` is default `topic_prefix`):
+//! - `archk/spaces/<space_id>/logs`: new log entries of space ([`SpaceLogEntry`])
+//! - `archk/events`: actor events, eg.
+//!   `{ "token": "<service token>", "id": "1", "event": { "unlock": { "pl_id": "..." } } }`,
+//!   same as `POST /service/_/space/events`
+//! - `archk/services/<service_id>/responses`: responses to events with `id` of event
+//!
+//! Broker must restrict access to these topics.
+
+use std::{io, time::Duration};
+
+use archk::v1::{
+    api::{self, Response},
+    auth::Token,
+};
+use axum::extract::State;
+use serde::{Deserialize, Serialize};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{
+        tcp::{OwnedReadHalf, OwnedWriteHalf},
+        TcpStream,
+    },
+};
+
+use crate::app::{AppConfigServerMqtt, AppState};
+
+use super::{
+    extra::{AuthenticatedUser, AuthenticatedUserParam, DbService, Json},
+    service::actor::{self, ActorEvent, ActorEventResponse},
+    space::{LogCursor, SpaceLogEntry},
+};
+
+/// Delay before reconnecting to broker
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+/// How often new log entries are published
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+/// Keep alive sent to broker in seconds, pings are sent twice as often
+const KEEP_ALIVE: u16 = 60;
+/// Maximum size of packet received from broker
+const MAX_PACKET_LEN: usize = 1024 * 1024;
+/// Maximum number of log entries published per poll
+const LOGS_LIMIT: i64 = 100;
+
+const CONNECT: u8 = 1;
+const CONNACK: u8 = 2;
+const PUBLISH: u8 = 3;
+const SUBSCRIBE: u8 = 8;
+const PINGREQ: u8 = 12;
+
+#[derive(Deserialize)]
+struct EventMessage {
+    /// Service token
+    token: String,
+    /// ID of event echoed in response
+    #[serde(default)]
+    id: Option<String>,
+    event: serde_json::Value,
+}
+
+#[derive(Serialize)]
+struct EventReply {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    id: Option<String>,
+    #[serde(flatten)]
+    response: Response<ActorEventResponse>,
+}
+
+/// Spawn MQTT bridge. Bridge reconnects to broker until server is stopped.
+pub fn spawn(state: AppState, config: AppConfigServerMqtt, address: String) {
+    tokio::spawn(async move {
+        let mut cursor = LogCursor::now();
+        loop {
+            if let Err(err) = run(&state, &config, &address, &mut cursor).await {
+                tracing::warn!(%err, address, "MQTT bridge disconnected");
+            }
+            tokio::time::sleep(RECONNECT_DELAY).await;
+        }
+    });
+}
+
+async fn run(
+    state: &AppState,
+    config: &AppConfigServerMqtt,
+    address: &str,
+    cursor: &mut LogCursor,
+) -> io::Result<()> {
+    let (mut reader, mut writer) = TcpStream::connect(address).await?.into_split();
+    let mut buf = Vec::new();
+
+    writer.write_all(&connect_packet(config)).await?;
+    let (header, body) = next_packet(&mut reader, &mut buf).await?;
+    match body.get(1) {
+        Some(0) if header >> 4 == CONNACK => (),
+        code => {
+            return Err(io::Error::other(format!(
+                "connection refused by broker, code {code:?}"
+            )))
+        }
+    }
+
+    let events_topic = format!("{}/events", config.topic_prefix);
+    let mut subscribe = Vec::new();
+    subscribe.extend_from_slice(&1u16.to_be_bytes());
+    write_str(&mut subscribe, &events_topic);
+    subscribe.push(0);
+    writer
+        .write_all(&packet(SUBSCRIBE << 4 | 0b0010, &subscribe))
+        .await?;
+    tracing::info!(address, "MQTT bridge connected");
+
+    let mut poll = tokio::time::interval(POLL_INTERVAL);
+    let mut ping = tokio::time::interval(Duration::from_secs(KEEP_ALIVE as u64 / 2));
+    loop {
+        tokio::select! {
+            // `read_buf` is cancel safe, partially read packets stay in `buf`
+            n = reader.read_buf(&mut buf) => {
+                if n? == 0 {
+                    return Err(io::ErrorKind::UnexpectedEof.into());
+                }
+                while let Some((header, body)) = parse_packet(&mut buf)? {
+                    if header >> 4 != PUBLISH {
+                        continue;
+                    }
+                    let Some((topic, payload)) = parse_publish(header, &body) else {
+                        continue;
+                    };
+                    if topic != events_topic {
+                        continue;
+                    }
+                    if let Some((service_id, reply)) = handle_event(state, payload).await {
+                        let topic =
+                            format!("{}/services/{service_id}/responses", config.topic_prefix);
+                        publish(&mut writer, &topic, &reply).await?;
+                    }
+                }
+            }
+            _ = poll.tick() => {
+                publish_logs(state, &mut writer, &config.topic_prefix, cursor).await?;
+            }
+            _ = ping.tick() => writer.write_all(&[PINGREQ << 4, 0]).await?,
+        }
+    }
+}
+
+/// Handle actor event. Returns ID of service and reply to it, events with invalid
+/// token are ignored.
+async fn handle_event(state: &AppState, payload: &[u8]) -> Option<(String, Vec<u8>)> {
+    let message: EventMessage = match serde_json::from_slice(payload) {
+        Ok(v) => v,
+        Err(err) => {
+            tracing::debug!(%err, "Invalid MQTT event message");
+            return None;
+        }
+    };
+    let token = Token::parse(&message.token).ok()?;
+    let user = DbService::verify(&token, state).await?;
+    let service_id = user.id.to_string();
+
+    let response = match serde_json::from_value::<ActorEvent>(message.event) {
+        Ok(event) => {
+            actor::submit_event(
+                AuthenticatedUser {
+                    token: Some(token),
+                    user,
+                },
+                State(state.clone()),
+                Json(event),
+            )
+            .await
+        }
+        Err(e) => Response::Failture(api::Error::MalformedData.detail(e.to_string().into())),
+    };
+
+    let reply = EventReply {
+        id: message.id,
+        response,
+    };
+    Some((service_id, serde_json::to_vec(&reply).expect("json")))
+}
+
+/// Publish log entries created after `cursor`.
+async fn publish_logs(
+    state: &AppState,
+    writer: &mut OwnedWriteHalf,
+    prefix: &str,
+    cursor: &mut LogCursor,
+) -> io::Result<()> {
+    let limit = cursor.limit(LOGS_LIMIT);
+    let res = sqlx::query!(
+        "
+        SELECT id, space_id, created_at, act, sp_acc_id, sp_item_id, ref_id, detail
+        FROM spaces_logs
+        WHERE created_at >= ?
+        ORDER BY created_at
+        LIMIT ?",
+        cursor.created_at,
+        limit
+    )
+    .fetch_all(&state.db)
+    .await;
+    let res = match res {
+        Ok(v) => v,
+        Err(err) => {
+            tracing::warn!(%err, "Failed to fetch space logs for MQTT bridge");
+            return Ok(());
+        }
+    };
+
+    for v in res {
+        if !cursor.advance(&v.id, v.created_at) {
+            continue;
+        }
+
+        let topic = format!("{prefix}/spaces/{}/logs", v.space_id);
+        let entry = SpaceLogEntry {
+            id: v.id,
+            created_at: v.created_at,
+            act: v.act.into(),
+            sp_acc_id: v.sp_acc_id,
+            sp_item_id: v.sp_item_id,
+            ref_id: v.ref_id,
+            detail: v.detail,
+        };
+        publish(writer, &topic, &serde_json::to_vec(&entry).expect("json")).await?;
+    }
+
+    Ok(())
+}
+
+async fn publish(writer: &mut OwnedWriteHalf, topic: &str, payload: &[u8]) -> io::Result<()> {
+    let mut body = Vec::with_capacity(topic.len() + payload.len() + 2);
+    write_str(&mut body, topic);
+    body.extend_from_slice(payload);
+    writer.write_all(&packet(PUBLISH << 4, &body)).await
+}
+
+fn connect_packet(config: &AppConfigServerMqtt) -> Vec<u8> {
+    let mut body = Vec::new();
+    write_str(&mut body, "MQTT");
+    // protocol level of MQTT 3.1.1
+    body.push(4);
+    let mut flags = 0b0000_0010; // clean session
+    if config.username.is_some() {
+        flags |= 0b1000_0000;
+    }
+    if config.password.is_some() {
+        flags |= 0b0100_0000;
+    }
+    body.push(flags);
+    body.extend_from_slice(&KEEP_ALIVE.to_be_bytes());
+    write_str(&mut body, &config.client_id);
+    if let Some(username) = &config.username {
+        write_str(&mut body, username);
+    }
+    if let Some(password) = &config.password {
+        write_str(&mut body, password);
+    }
+    packet(CONNECT << 4, &body)
+}
+
+fn packet(header: u8, body: &[u8]) -> Vec<u8> {
+    let mut res = Vec::with_capacity(body.len() + 5);
+    res.push(header);
+    let mut len = body.len();
+    loop {
+        let mut byte = (len % 128) as u8;
+        len /= 128;
+        if len > 0 {
+            byte |= 0x80;
+        }
+        res.push(byte);
+        if len == 0 {
+            break;
+        }
+    }
+    res.extend_from_slice(body);
+    res
+}
+
+fn write_str(buf: &mut Vec<u8>, v: &str) {
+    buf.extend_from_slice(&(v.len() as u16).to_be_bytes());
+    buf.extend_from_slice(v.as_bytes());
+}
+
+/// Take first complete packet from `buf`: its fixed header byte and body.
+fn parse_packet(buf: &mut Vec<u8>) -> io::Result<Option<(u8, Vec<u8>)>> {
+    let mut len = 0;
+    let mut pos = 1;
+    loop {
+        let Some(&byte) = buf.get(pos) else {
+            return Ok(None);
+        };
+        len |= ((byte & 0x7f) as usize) << (7 * (pos - 1));
+        pos += 1;
+        if byte & 0x80 == 0 {
+            break;
+        }
+        if pos > 4 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "malformed packet length",
+            ));
+        }
+    }
+    if len > MAX_PACKET_LEN {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "packet is too large",
+        ));
+    }
+    if buf.len() < pos + len {
+        return Ok(None);
+    }
+
+    let header = buf[0];
+    let body = buf[pos..pos + len].to_vec();
+    buf.drain(..pos + len);
+    Ok(Some((header, body)))
+}
+
+async fn next_packet(reader: &mut OwnedReadHalf, buf: &mut Vec<u8>) -> io::Result<(u8, Vec<u8>)> {
+    loop {
+        if let Some(packet) = parse_packet(buf)? {
+            return Ok(packet);
+        }
+        if reader.read_buf(buf).await? == 0 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+    }
+}
+
+/// Split body of `PUBLISH` packet into topic and payload.
+fn parse_publish(header: u8, body: &[u8]) -> Option<(&str, &[u8])> {
+    let len = u16::from_be_bytes([*body.first()?, *body.get(1)?]) as usize;
+    let topic = std::str::from_utf8(body.get(2..2 + len)?).ok()?;
+    // packet identifier is present only with QoS 1 and 2
+    let offset = if (header >> 1) & 0b11 == 0 { 0 } else { 2 };
+    Some((topic, body.get(2 + len + offset..)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn packets_roundtrip() {
+        let mut body = Vec::new();
+        write_str(&mut body, "archk/events");
+        body.extend_from_slice(&[b'x'; 200]);
+
+        let mut buf = packet(PUBLISH << 4, &body);
+        // remaining length 214 takes two bytes
+        assert_eq!(&buf[1..3], &[0xd6, 0x01]);
+        let tail = buf.split_off(100);
+
+        assert_eq!(parse_packet(&mut buf).unwrap(), None);
+        buf.extend_from_slice(&tail);
+        buf.extend_from_slice(&[PINGREQ << 4, 0]);
+
+        let (header, body) = parse_packet(&mut buf).unwrap().unwrap();
+        assert_eq!(header >> 4, PUBLISH);
+        let (topic, payload) = parse_publish(header, &body).unwrap();
+        assert_eq!(topic, "archk/events");
+        assert_eq!(payload, &[b'x'; 200]);
+
+        assert_eq!(
+            parse_packet(&mut buf).unwrap(),
+            Some((PINGREQ << 4, vec![]))
+        );
+        assert!(buf.is_empty());
+    }
+}
@@ -0,0 +1,459 @@
+//! Organizations grouping spaces and users, see [`Organization`]. Spaces keep their
+//! owner, admins of organization get same access to content of its spaces.
+
+use archk::{
+    v1::{
+        api::{self, Response},
+        org::{OrgID, OrgMember, OrgRole, Organization},
+        space::{Space, SpaceID},
+        user::UserID,
+        validate,
+    },
+    Documentation,
+};
+use axum::extract::State;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app::{self, AppState},
+    roles::perm,
+};
+
+use super::extra::{AuthenticatedUser, DbUser, Json, Path, ReadDb};
+
+#[derive(Deserialize, Documentation)]
+pub struct OrgPath {
+    /// Organization ID
+    pub org_id: OrgID,
+}
+
+#[derive(Deserialize, Documentation)]
+pub struct OrgMemberPath {
+    /// Organization ID
+    pub org_id: OrgID,
+    /// User ID
+    pub user_id: UserID,
+}
+
+#[derive(Deserialize, Documentation)]
+pub struct OrgSpacePath {
+    /// Organization ID
+    pub org_id: OrgID,
+    /// Space ID
+    pub space_id: SpaceID,
+}
+
+#[derive(Deserialize, Documentation)]
+pub struct CreateOrgBody {
+    /// Organization title
+    pub title: String,
+}
+
+#[derive(Deserialize, Documentation)]
+pub struct PutOrgMemberBody {
+    /// User ID
+    pub user_id: UserID,
+    /// Role of user, `Member` by default
+    #[serde(default = "default_role")]
+    pub role: OrgRole,
+}
+
+fn default_role() -> OrgRole {
+    OrgRole::Member
+}
+
+#[derive(Serialize, Documentation)]
+pub struct UserOrgResponse {
+    /// Organization object
+    pub org: Organization,
+    /// Role of current user in organization
+    pub role: OrgRole,
+}
+
+/// Role of user in organization, `None` if user is not member.
+pub(crate) async fn org_role<'e, E>(db: E, org_id: &str, user_id: &str) -> Option<OrgRole>
+where
+    E: sqlx::Executor<'e, Database = sqlx::Sqlite>,
+{
+    sqlx::query_scalar!(
+        "SELECT role FROM organizations_members WHERE org_id = ? AND user_id = ?",
+        org_id,
+        user_id
+    )
+    .fetch_optional(db)
+    .await
+    .expect("database")
+    .map(|v| OrgRole::try_from(v).expect("invalid organization role in database"))
+}
+
+/// Can member with role `actor` give or take role `target` of other member? Owners
+/// manage everyone, others only members with lower role.
+fn can_manage(actor: OrgRole, target: OrgRole) -> bool {
+    actor == OrgRole::Owner || (actor >= OrgRole::Admin && target < actor)
+}
+
+pub async fn create_org(
+    AuthenticatedUser {
+        user: DbUser {
+            id: user_id, level, ..
+        },
+        ..
+    }: AuthenticatedUser<DbUser>,
+    State(AppState { db, roles, .. }): State<AppState>,
+    Json(CreateOrgBody { title }): Json<CreateOrgBody>,
+) -> Response<Organization> {
+    if !roles.load().has(level, perm::ORG_CREATE) {
+        return Response::Failture(api::Error::Forbidden.into());
+    }
+    if let Err(e) = validate::title(&title) {
+        return Response::Failture(e.into());
+    }
+
+    let org = Organization {
+        id: OrgID::new(),
+        title,
+        created_at: app::now_ms(),
+    };
+    let id: &str = &org.id;
+    let owner: i64 = OrgRole::Owner.into();
+    let mut tx = crate::app::begin(&db).await;
+    sqlx::query!(
+        "INSERT INTO organizations(id, title, created_at) VALUES (?, ?, ?)",
+        id,
+        org.title,
+        org.created_at
+    )
+    .execute(&mut *tx)
+    .await
+    .expect("database");
+    sqlx::query!(
+        "INSERT INTO organizations_members(org_id, user_id, role) VALUES (?, ?, ?)",
+        id,
+        user_id,
+        owner
+    )
+    .execute(&mut *tx)
+    .await
+    .expect("database");
+    tx.commit().await.expect("database");
+
+    Response::Success(org)
+}
+
+pub async fn get_orgs(
+    AuthenticatedUser { user, .. }: AuthenticatedUser,
+    ReadDb(db): ReadDb,
+) -> Response<Vec<UserOrgResponse>> {
+    let user: &str = &user;
+    let res = sqlx::query!(
+        "SELECT organizations.id, organizations.title, organizations.created_at,
+            organizations_members.role
+        FROM organizations
+            INNER JOIN organizations_members
+                ON organizations_members.org_id = organizations.id
+        WHERE organizations_members.user_id = ?
+        ORDER BY organizations.title",
+        user
+    )
+    .fetch_all(&db)
+    .await
+    .expect("database")
+    .into_iter()
+    .map(|v| UserOrgResponse {
+        org: Organization {
+            id: OrgID::from(v.id).expect("organization id from database"),
+            title: v.title,
+            created_at: v.created_at,
+        },
+        role: OrgRole::try_from(v.role).expect("invalid organization role in database"),
+    });
+
+    Response::Success(res.collect())
+}
+
+pub async fn get_org(
+    Path(OrgPath { org_id }): Path<OrgPath>,
+    AuthenticatedUser { user, .. }: AuthenticatedUser,
+    ReadDb(db): ReadDb,
+) -> Response<UserOrgResponse> {
+    let Some(role) = org_role(&db, &org_id, &user).await else {
+        return Response::Failture(api::Error::ObjectNotFound.into());
+    };
+
+    let id: &str = &org_id;
+    let res = sqlx::query!(
+        "SELECT title, created_at FROM organizations WHERE id = ?",
+        id
+    )
+    .fetch_one(&db)
+    .await
+    .expect("database");
+
+    Response::Success(UserOrgResponse {
+        org: Organization {
+            id: org_id,
+            title: res.title,
+            created_at: res.created_at,
+        },
+        role,
+    })
+}
+
+pub async fn delete_org(
+    Path(OrgPath { org_id }): Path<OrgPath>,
+    AuthenticatedUser { user, .. }: AuthenticatedUser,
+    State(AppState { db, cache, .. }): State<AppState>,
+) -> Response<u64> {
+    match org_role(&db, &org_id, &user).await {
+        None => return Response::Failture(api::Error::ObjectNotFound.into()),
+        Some(OrgRole::Owner) => (),
+        Some(_) => return Response::Failture(api::Error::Forbidden.into()),
+    }
+
+    let org_id: &str = &org_id;
+    let res = sqlx::query!("DELETE FROM organizations WHERE id = ?", org_id)
+        .execute(&db)
+        .await
+        .expect("database")
+        .rows_affected();
+    // spaces of organization lost it
+    cache.clear();
+
+    Response::Success(res)
+}
+
+pub async fn get_members(
+    Path(OrgPath { org_id }): Path<OrgPath>,
+    AuthenticatedUser { user, .. }: AuthenticatedUser,
+    ReadDb(db): ReadDb,
+) -> Response<Vec<OrgMember>> {
+    if org_role(&db, &org_id, &user).await.is_none() {
+        return Response::Failture(api::Error::ObjectNotFound.into());
+    }
+
+    let org_id: &str = &org_id;
+    let res = sqlx::query!(
+        "SELECT users.id, users.name, organizations_members.role
+        FROM organizations_members
+            INNER JOIN users ON users.id = organizations_members.user_id
+        WHERE organizations_members.org_id = ?
+        ORDER BY organizations_members.role DESC, users.name",
+        org_id
+    )
+    .fetch_all(&db)
+    .await
+    .expect("database")
+    .into_iter()
+    .map(|v| OrgMember {
+        user_id: UserID::from(v.id).expect("user id from database"),
+        name: v.name,
+        role: OrgRole::try_from(v.role).expect("invalid organization role in database"),
+    });
+
+    Response::Success(res.collect())
+}
+
+pub async fn put_member(
+    Path(OrgPath { org_id }): Path<OrgPath>,
+    AuthenticatedUser { user, .. }: AuthenticatedUser,
+    State(AppState { db, .. }): State<AppState>,
+    Json(PutOrgMemberBody { user_id, role }): Json<PutOrgMemberBody>,
+) -> Response<u64> {
+    let mut tx = crate::app::begin(&db).await;
+    let Some(actor) = org_role(&mut *tx, &org_id, &user).await else {
+        return Response::Failture(api::Error::ObjectNotFound.into());
+    };
+    let current = org_role(&mut *tx, &org_id, &user_id).await;
+    if !can_manage(actor, role) || current.is_some_and(|v| !can_manage(actor, v)) {
+        return Response::Failture(api::Error::Forbidden.into());
+    }
+
+    let (org_id, user_id): (&str, &str) = (&org_id, &user_id);
+    if current == Some(OrgRole::Owner)
+        && role != OrgRole::Owner
+        && owners(&mut tx, org_id).await <= 1
+    {
+        return Response::Failture(last_owner_conflict());
+    }
+    let exists = sqlx::query!("SELECT id FROM users WHERE id = ?", user_id)
+        .fetch_optional(&mut *tx)
+        .await
+        .expect("database")
+        .is_some();
+    if !exists {
+        return Response::Failture(api::Error::ObjectNotFound.detail("user not found".into()));
+    }
+
+    let role: i64 = role.into();
+    let res = sqlx::query!(
+        "INSERT INTO organizations_members(org_id, user_id, role) VALUES (?1, ?2, ?3)
+        ON CONFLICT(org_id, user_id) DO UPDATE SET role = ?3",
+        org_id,
+        user_id,
+        role
+    )
+    .execute(&mut *tx)
+    .await
+    .expect("database")
+    .rows_affected();
+    tx.commit().await.expect("database");
+
+    Response::Success(res)
+}
+
+pub async fn delete_member(
+    Path(OrgMemberPath { org_id, user_id }): Path<OrgMemberPath>,
+    AuthenticatedUser { user, .. }: AuthenticatedUser,
+    State(AppState { db, .. }): State<AppState>,
+) -> Response<u64> {
+    let mut tx = crate::app::begin(&db).await;
+    let Some(actor) = org_role(&mut *tx, &org_id, &user).await else {
+        return Response::Failture(api::Error::ObjectNotFound.into());
+    };
+    let Some(current) = org_role(&mut *tx, &org_id, &user_id).await else {
+        return Response::Failture(api::Error::ObjectNotFound.into());
+    };
+    // anyone may leave organization
+    if user_id != user && !can_manage(actor, current) {
+        return Response::Failture(api::Error::Forbidden.into());
+    }
+
+    let (org_id, user_id): (&str, &str) = (&org_id, &user_id);
+    if current == OrgRole::Owner && owners(&mut tx, org_id).await <= 1 {
+        return Response::Failture(last_owner_conflict());
+    }
+
+    let res = sqlx::query!(
+        "DELETE FROM organizations_members WHERE org_id = ? AND user_id = ?",
+        org_id,
+        user_id
+    )
+    .execute(&mut *tx)
+    .await
+    .expect("database")
+    .rows_affected();
+    tx.commit().await.expect("database");
+
+    Response::Success(res)
+}
+
+async fn owners(db: &mut sqlx::SqliteConnection, org_id: &str) -> i64 {
+    let owner: i64 = OrgRole::Owner.into();
+    sqlx::query_scalar!(
+        r#"SELECT COUNT(*) AS "count!: i64" FROM organizations_members WHERE org_id = ? AND role = ?"#,
+        org_id,
+        owner
+    )
+    .fetch_one(db)
+    .await
+    .expect("database")
+}
+
+fn last_owner_conflict() -> api::ErrorData {
+    api::Error::Conflict
+        .detail("organization should have at least one owner, delete organization instead".into())
+}
+
+pub async fn get_org_spaces(
+    Path(OrgPath { org_id }): Path<OrgPath>,
+    AuthenticatedUser { user, .. }: AuthenticatedUser,
+    ReadDb(db): ReadDb,
+) -> Response<Vec<Space>> {
+    if org_role(&db, &org_id, &user).await.is_none() {
+        return Response::Failture(api::Error::ObjectNotFound.into());
+    }
+
+    let org_id: &str = &org_id;
+    let res = sqlx::query!(
+        "SELECT id, title, owner_id, logs_retention_days, archived, template, version
+        FROM spaces
+        WHERE org_id = ?
+        ORDER BY title",
+        org_id
+    )
+    .fetch_all(&db)
+    .await
+    .expect("database")
+    .into_iter()
+    .map(|v| Space {
+        id: SpaceID::from(v.id).expect("space id from database"),
+        title: v.title,
+        owner_id: UserID::from(v.owner_id).expect("user id from database"),
+        logs_retention_days: v.logs_retention_days.map(|v| v as u32),
+        archived: v.archived,
+        template: v.template,
+        version: v.version,
+    });
+
+    Response::Success(res.collect())
+}
+
+pub async fn attach_space(
+    Path(OrgSpacePath { org_id, space_id }): Path<OrgSpacePath>,
+    AuthenticatedUser {
+        user: DbUser {
+            id: user_id, level, ..
+        },
+        ..
+    }: AuthenticatedUser<DbUser>,
+    State(AppState {
+        db, roles, cache, ..
+    }): State<AppState>,
+) -> Response<u64> {
+    if org_role(&db, &org_id, &user_id)
+        .await
+        .is_none_or(|v| v < OrgRole::Admin)
+    {
+        return Response::Failture(api::Error::ObjectNotFound.into());
+    }
+    let can_manage_spaces = roles.load().has(level, perm::SPACE_MANAGE);
+
+    let (org_id, space_id): (&str, &str) = (&org_id, &space_id);
+    let res = sqlx::query!(
+        "UPDATE spaces SET org_id = ?1 WHERE id = ?2 AND (?3 OR owner_id = ?4)",
+        org_id,
+        space_id,
+        can_manage_spaces,
+        user_id
+    )
+    .execute(&db)
+    .await
+    .expect("database")
+    .rows_affected();
+    cache.invalidate_space(space_id);
+
+    match res {
+        0 => Response::Failture(api::Error::ObjectNotFound.into()),
+        v => Response::Success(v),
+    }
+}
+
+pub async fn detach_space(
+    Path(OrgSpacePath { org_id, space_id }): Path<OrgSpacePath>,
+    AuthenticatedUser { user, .. }: AuthenticatedUser,
+    State(AppState { db, cache, .. }): State<AppState>,
+) -> Response<u64> {
+    // owner of space may detach it too
+    let org_admin = org_role(&db, &org_id, &user)
+        .await
+        .is_some_and(|v| v >= OrgRole::Admin);
+
+    let (org_id, space_id, user): (&str, &str, &str) = (&org_id, &space_id, &user);
+    let res = sqlx::query!(
+        "UPDATE spaces SET org_id = NULL WHERE id = ?1 AND org_id = ?2 AND (?3 OR owner_id = ?4)",
+        space_id,
+        org_id,
+        org_admin,
+        user
+    )
+    .execute(&db)
+    .await
+    .expect("database")
+    .rows_affected();
+    cache.invalidate_space(space_id);
+
+    match res {
+        0 => Response::Failture(api::Error::ObjectNotFound.into()),
+        v => Response::Success(v),
+    }
+}
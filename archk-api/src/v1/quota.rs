@@ -0,0 +1,202 @@
+//! Limits of resources per role, see [`RoleQuota`]. Limits are checked by create
+//! handlers before insert and fail with [`api::Error::QuotaExceeded`].
+
+use archk::{
+    v1::{
+        api::{self, Response},
+        space::SpaceID,
+    },
+    Documentation,
+};
+use axum::extract::State;
+use serde::Serialize;
+use sqlx::SqlitePool;
+
+use crate::{
+    app::AppState,
+    roles::{RoleQuota, UserRoles},
+};
+
+use super::extra::{AuthenticatedUser, DbUser, ReadDb};
+
+#[derive(Serialize, Documentation)]
+pub struct QuotaUsage {
+    /// Used amount
+    pub used: u64,
+    /// Limit of role, `null` if not limited
+    pub max: Option<u64>,
+}
+
+#[derive(Serialize, Documentation)]
+pub struct SpaceQuotaUsage {
+    /// Space ID
+    pub space_id: SpaceID,
+    /// Items of space
+    pub items: QuotaUsage,
+    /// Services bound to space
+    pub services: QuotaUsage,
+}
+
+#[derive(Serialize, Documentation)]
+pub struct QuotaResponse {
+    /// Owned spaces
+    pub spaces: QuotaUsage,
+    /// SSH keys
+    pub ssh_keys: QuotaUsage,
+    /// Usage of each owned space
+    pub spaces_usage: Vec<SpaceQuotaUsage>,
+}
+
+fn exceeded(what: &str, max: u64) -> api::ErrorData {
+    api::Error::QuotaExceeded.detail(format!("quota of {max} {what} exceeded").into())
+}
+
+/// Fail if `used + adding` is above `max`.
+fn check(what: &str, max: Option<u64>, used: i64, adding: u64) -> Result<(), api::ErrorData> {
+    match max {
+        Some(max) if used as u64 + adding > max => Err(exceeded(what, max)),
+        _ => Ok(()),
+    }
+}
+
+/// Quota of role of space owner, it limits contents of space.
+pub(crate) async fn space_quota(db: &SqlitePool, roles: &UserRoles, space_id: &str) -> RoleQuota {
+    let level = sqlx::query_scalar!(
+        "SELECT users.level FROM spaces
+            INNER JOIN users ON users.id = spaces.owner_id
+        WHERE spaces.id = ?",
+        space_id
+    )
+    .fetch_optional(db)
+    .await
+    .expect("database");
+    level.map(|v| roles.quota(v)).unwrap_or_default()
+}
+
+/// Check that user may own `adding` more spaces.
+pub(crate) async fn check_spaces<'e, E>(
+    db: E,
+    quota: RoleQuota,
+    user_id: &str,
+    adding: u64,
+) -> Result<(), api::ErrorData>
+where
+    E: sqlx::Executor<'e, Database = sqlx::Sqlite>,
+{
+    let Some(max) = quota.max_spaces else {
+        return Ok(());
+    };
+    let used = sqlx::query_scalar!(
+        r#"SELECT COUNT(*) AS "count!: i64" FROM spaces WHERE owner_id = ?"#,
+        user_id
+    )
+    .fetch_one(db)
+    .await
+    .expect("database");
+    check("spaces", Some(max), used, adding)
+}
+
+/// Check that space may have `adding` more items.
+pub(crate) async fn check_items<'e, E>(
+    db: E,
+    quota: RoleQuota,
+    space_id: &str,
+    adding: u64,
+) -> Result<(), api::ErrorData>
+where
+    E: sqlx::Executor<'e, Database = sqlx::Sqlite>,
+{
+    let Some(max) = quota.max_items_per_space else {
+        return Ok(());
+    };
+    let used = sqlx::query_scalar!(
+        r#"SELECT COUNT(*) AS "count!: i64" FROM spaces_items WHERE space_id = ?"#,
+        space_id
+    )
+    .fetch_one(db)
+    .await
+    .expect("database");
+    check("items per space", Some(max), used, adding)
+}
+
+/// Check that user may upload one more SSH key.
+pub(crate) async fn check_ssh_keys(
+    db: &SqlitePool,
+    quota: RoleQuota,
+    user_id: &str,
+) -> Result<(), api::ErrorData> {
+    let Some(max) = quota.max_ssh_keys else {
+        return Ok(());
+    };
+    let used = sqlx::query_scalar!(
+        r#"SELECT COUNT(*) AS "count!: i64" FROM users_ssh_keys WHERE owner_id = ?"#,
+        user_id
+    )
+    .fetch_one(db)
+    .await
+    .expect("database");
+    check("SSH keys", Some(max), used, 1)
+}
+
+/// Error of services quota, see `create_service`.
+pub(crate) fn services_exceeded(max: u64) -> api::ErrorData {
+    exceeded("services per space", max)
+}
+
+pub async fn get_quota(
+    AuthenticatedUser {
+        user: DbUser { id, level, .. },
+        ..
+    }: AuthenticatedUser<DbUser>,
+    State(AppState { roles, .. }): State<AppState>,
+    ReadDb(db): ReadDb,
+) -> Response<QuotaResponse> {
+    let quota = roles.load().quota(level);
+
+    let ssh_keys = sqlx::query_scalar!(
+        r#"SELECT COUNT(*) AS "count!: i64" FROM users_ssh_keys WHERE owner_id = ?"#,
+        id
+    )
+    .fetch_one(&db)
+    .await
+    .expect("database");
+    let spaces_usage: Vec<_> = sqlx::query!(
+        r#"
+        SELECT
+            id,
+            (SELECT COUNT(*) FROM spaces_items WHERE space_id = spaces.id) AS "items!: i64",
+            (SELECT COUNT(*) FROM service_accounts WHERE space_id = spaces.id) AS "services!: i64"
+        FROM spaces
+        WHERE owner_id = ?
+        ORDER BY id"#,
+        id
+    )
+    .fetch_all(&db)
+    .await
+    .expect("database")
+    .into_iter()
+    .map(|v| SpaceQuotaUsage {
+        space_id: SpaceID::from(v.id).expect("space id from database"),
+        items: QuotaUsage {
+            used: v.items as u64,
+            max: quota.max_items_per_space,
+        },
+        services: QuotaUsage {
+            used: v.services as u64,
+            max: quota.max_services_per_space,
+        },
+    })
+    .collect();
+
+    Response::Success(QuotaResponse {
+        spaces: QuotaUsage {
+            used: spaces_usage.len() as u64,
+            max: quota.max_spaces,
+        },
+        ssh_keys: QuotaUsage {
+            used: ssh_keys as u64,
+            max: quota.max_ssh_keys,
+        },
+        spaces_usage,
+    })
+}
@@ -0,0 +1,385 @@
+//! Future-dated reservations of items and their availability.
+
+use archk::{
+    v1::{
+        api::{self, Response},
+        space::{SpaceID, SpaceItemID},
+    },
+    Documentation,
+};
+use axum::extract::{Query, State};
+use serde::{Deserialize, Serialize};
+
+use crate::app::{self, AppState};
+
+use super::{
+    extra::{Json, Path, ReadDb, SpaceAccess},
+    item_status,
+    space::{archived_conflict, Paging, SpaceItemPath, MAX_LOG_COMMENT_LEN},
+};
+
+/// Maximum number of not ended reservations of item
+const MAX_RESERVATIONS: i64 = 100;
+/// Maximum range of availability query, 366 days
+const MAX_AVAILABILITY_RANGE_MS: i64 = 1000 * 60 * 60 * 24 * 366;
+
+#[derive(Deserialize, Documentation)]
+pub struct SpaceReservationPath {
+    /// Space ID
+    #[allow(dead_code)] // read by `SpaceAccess`
+    pub space_id: SpaceID,
+    /// Item ID
+    pub item_id: SpaceItemID,
+    /// Reservation ID
+    pub reservation_id: String,
+}
+
+#[derive(Serialize, Documentation)]
+pub struct Reservation {
+    /// Reservation ID
+    pub id: String,
+    /// Item ID
+    pub item_id: String,
+    /// Platform ID of account item is reserved for
+    pub acc_id: String,
+    /// Start timestamp in milliseconds
+    pub start: i64,
+    /// End timestamp in milliseconds (exclusive)
+    pub end: i64,
+    /// Note, eg. purpose of reservation
+    pub note: Option<String>,
+    /// Creation timestamp in milliseconds
+    pub created_at: i64,
+}
+
+#[derive(Deserialize, Documentation)]
+pub struct CreateReservationBody {
+    /// Platform ID of account item is reserved for
+    pub acc_id: String,
+    /// Start timestamp in milliseconds
+    pub start: i64,
+    /// End timestamp in milliseconds (exclusive), should be after `start` and in future
+    pub end: i64,
+    /// Note, eg. purpose of reservation
+    #[serde(default)]
+    pub note: Option<String>,
+}
+
+#[derive(Deserialize, Documentation)]
+pub struct AvailabilityQuery {
+    /// Start of range, timestamp in milliseconds
+    pub from: i64,
+    /// End of range, timestamp in milliseconds. Range is up to 366 days
+    pub to: i64,
+}
+
+#[derive(Serialize, Documentation, PartialEq, Debug)]
+pub struct FreeWindow {
+    /// Start timestamp in milliseconds
+    pub start: i64,
+    /// End timestamp in milliseconds (exclusive)
+    pub end: i64,
+}
+
+/// Windows of `[from, to)` not covered by `busy` intervals.
+fn free_windows(mut busy: Vec<(i64, i64)>, from: i64, to: i64) -> Vec<FreeWindow> {
+    busy.sort_unstable();
+    let mut windows = Vec::new();
+    let mut start = from;
+    for (busy_start, busy_end) in busy {
+        if busy_start >= to {
+            break;
+        }
+        if busy_start > start {
+            windows.push(FreeWindow {
+                start,
+                end: busy_start,
+            });
+        }
+        start = start.max(busy_end);
+    }
+    if start < to {
+        windows.push(FreeWindow { start, end: to });
+    }
+    windows
+}
+
+/// Interval item is checked out for: from now until `due_at`, or forever if item has
+/// no due date. `None` if item is not taken.
+fn checkout_interval(
+    current_holder: Option<String>,
+    due_at: Option<i64>,
+    now: i64,
+) -> Option<(i64, i64)> {
+    current_holder.map(|_| (now, due_at.unwrap_or(i64::MAX).max(now)))
+}
+
+pub async fn get_reservations(
+    Path(SpaceItemPath { item_id, .. }): Path<SpaceItemPath>,
+    SpaceAccess { space_id, .. }: SpaceAccess,
+    Query(Paging { page }): Query<Paging>,
+    ReadDb(db): ReadDb,
+) -> Response<Vec<Reservation>> {
+    let item_id: &str = &item_id;
+    let space_id: &str = &space_id;
+    let now = app::now_ms();
+    let limit = 50;
+    let offset = (page as i64) * limit;
+    let res = sqlx::query_as!(
+        Reservation,
+        r#"
+        SELECT id, item_id, pl_id AS acc_id, start_at AS start, end_at AS end, note, created_at
+        FROM spaces_items_reservations
+        WHERE item_id = ? AND space_id = ? AND end_at > ?
+        ORDER BY start_at
+        LIMIT ? OFFSET ?"#,
+        item_id,
+        space_id,
+        now,
+        limit,
+        offset
+    )
+    .fetch_all(&db)
+    .await
+    .expect("database");
+
+    Response::Success(res)
+}
+
+pub async fn create_reservation(
+    Path(SpaceItemPath { item_id, .. }): Path<SpaceItemPath>,
+    SpaceAccess {
+        space_id, archived, ..
+    }: SpaceAccess,
+    State(AppState { db, .. }): State<AppState>,
+    Json(CreateReservationBody {
+        acc_id,
+        start,
+        end,
+        note,
+    }): Json<CreateReservationBody>,
+) -> Response<Reservation> {
+    if archived {
+        return Response::Failture(archived_conflict());
+    }
+    let now = app::now_ms();
+    if start >= end {
+        return Response::Failture(
+            api::Error::MalformedData.detail("`end` should be after `start`".into()),
+        );
+    }
+    if end <= now {
+        return Response::Failture(
+            api::Error::MalformedData.detail("`end` should be in future".into()),
+        );
+    }
+    let note = note.map(|v| v.trim().to_string()).filter(|v| !v.is_empty());
+    if note
+        .as_ref()
+        .is_some_and(|v| v.chars().count() > MAX_LOG_COMMENT_LEN)
+    {
+        return Response::Failture(
+            api::Error::MalformedData.detail(
+                format!("`note` should be at most {MAX_LOG_COMMENT_LEN} characters").into(),
+            ),
+        );
+    }
+
+    let item_id: &str = &item_id;
+    let space_id: &str = &space_id;
+    let mut tx = app::begin(&db).await;
+
+    let item = sqlx::query!(
+        "SELECT status, current_holder, due_at FROM spaces_items WHERE id = ? AND space_id = ?",
+        item_id,
+        space_id
+    )
+    .fetch_optional(&mut *tx)
+    .await
+    .expect("database");
+    let Some(item) = item else {
+        return Response::Failture(api::Error::ObjectNotFound.into());
+    };
+    let statuses = item_status::fetch_statuses(&mut *tx, space_id).await;
+    if !statuses.iter().any(|v| v.name == item.status && v.takeable) {
+        return Response::Failture(
+            api::Error::Conflict
+                .detail(format!("item is `{}` and can't be reserved", item.status).into()),
+        );
+    }
+    if let Some((_, due_at)) = checkout_interval(item.current_holder, item.due_at, now) {
+        if start < due_at {
+            return Response::Failture(
+                api::Error::Conflict.detail("item is checked out at that time".into()),
+            );
+        }
+    }
+
+    let reservations = sqlx::query!(
+        r#"
+        SELECT
+            COUNT(1) AS "count!: i64",
+            COALESCE(SUM(start_at < ?1 AND end_at > ?2), 0) AS "overlapping!: i64"
+        FROM spaces_items_reservations
+        WHERE item_id = ?3 AND end_at > ?4"#,
+        end,
+        start,
+        item_id,
+        now
+    )
+    .fetch_one(&mut *tx)
+    .await
+    .expect("database");
+    if reservations.overlapping > 0 {
+        return Response::Failture(
+            api::Error::Conflict.detail("item is already reserved at that time".into()),
+        );
+    }
+    if reservations.count >= MAX_RESERVATIONS {
+        return Response::Failture(
+            api::Error::Conflict.detail(
+                format!("item can't have more than {MAX_RESERVATIONS} reservations").into(),
+            ),
+        );
+    }
+
+    let res = Reservation {
+        id: cuid2::create_id(),
+        item_id: item_id.into(),
+        acc_id,
+        start,
+        end,
+        note,
+        created_at: now,
+    };
+    let insert = sqlx::query!(
+        r#"
+        INSERT INTO spaces_items_reservations(id, space_id, item_id, pl_id, start_at, end_at, note, created_at)
+        VALUES (?, ?, ?, ?, ?, ?, ?, ?)"#,
+        res.id,
+        space_id,
+        res.item_id,
+        res.acc_id,
+        res.start,
+        res.end,
+        res.note,
+        res.created_at
+    )
+    .execute(&mut *tx)
+    .await;
+
+    match insert {
+        Ok(_) => {
+            tx.commit().await.expect("database");
+            Response::Success(res)
+        }
+        Err(sqlx::Error::Database(err)) if err.is_foreign_key_violation() => {
+            Response::Failture(api::Error::ObjectNotFound.detail("account does not exists".into()))
+        }
+        Err(e) => panic!("database error: {e}"),
+    }
+}
+
+pub async fn delete_reservation(
+    Path(SpaceReservationPath {
+        item_id,
+        reservation_id,
+        ..
+    }): Path<SpaceReservationPath>,
+    SpaceAccess {
+        space_id, archived, ..
+    }: SpaceAccess,
+    State(AppState { db, .. }): State<AppState>,
+) -> Response<u64> {
+    if archived {
+        return Response::Failture(archived_conflict());
+    }
+    let item_id: &str = &item_id;
+    let space_id: &str = &space_id;
+    let res = sqlx::query!(
+        "DELETE FROM spaces_items_reservations WHERE id = ? AND item_id = ? AND space_id = ?",
+        reservation_id,
+        item_id,
+        space_id
+    )
+    .execute(&db)
+    .await
+    .expect("database")
+    .rows_affected();
+
+    match res {
+        0 => Response::Failture(api::Error::ObjectNotFound.into()),
+        n => Response::Success(n),
+    }
+}
+
+pub async fn get_availability(
+    Path(SpaceItemPath { item_id, .. }): Path<SpaceItemPath>,
+    SpaceAccess { space_id, .. }: SpaceAccess,
+    Query(AvailabilityQuery { from, to }): Query<AvailabilityQuery>,
+    ReadDb(db): ReadDb,
+) -> Response<Vec<FreeWindow>> {
+    if from >= to {
+        return Response::Failture(
+            api::Error::MalformedData.detail("`to` should be after `from`".into()),
+        );
+    }
+    if to - from > MAX_AVAILABILITY_RANGE_MS {
+        return Response::Failture(
+            api::Error::MalformedData.detail("range should be at most 366 days".into()),
+        );
+    }
+
+    let item_id: &str = &item_id;
+    let space_id: &str = &space_id;
+    let item = sqlx::query!(
+        "SELECT current_holder, due_at FROM spaces_items WHERE id = ? AND space_id = ?",
+        item_id,
+        space_id
+    )
+    .fetch_optional(&db)
+    .await
+    .expect("database");
+    let Some(item) = item else {
+        return Response::Failture(api::Error::ObjectNotFound.into());
+    };
+
+    let mut busy: Vec<_> = sqlx::query!(
+        r#"
+        SELECT start_at, end_at FROM spaces_items_reservations
+        WHERE item_id = ? AND start_at < ? AND end_at > ?"#,
+        item_id,
+        to,
+        from
+    )
+    .fetch_all(&db)
+    .await
+    .expect("database")
+    .into_iter()
+    .map(|v| (v.start_at, v.end_at))
+    .collect();
+    busy.extend(checkout_interval(
+        item.current_holder,
+        item.due_at,
+        app::now_ms(),
+    ));
+
+    Response::Success(free_windows(busy, from, to))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn free_windows_between_busy() {
+        let w = |start, end| FreeWindow { start, end };
+        assert_eq!(free_windows(Vec::new(), 0, 10), vec![w(0, 10)]);
+        assert_eq!(
+            free_windows(vec![(6, 8), (2, 4), (3, 5)], 0, 10),
+            vec![w(0, 2), w(5, 6), w(8, 10)]
+        );
+        assert_eq!(free_windows(vec![(-5, 3), (9, 20)], 0, 10), vec![w(3, 9)]);
+        assert_eq!(free_windows(vec![(0, i64::MAX)], 0, 10), vec![]);
+    }
+}
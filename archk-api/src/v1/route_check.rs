@@ -0,0 +1,137 @@
+//! Compile-time checks of [`routes!`](super::routes) entries: path parameters of
+//! literal (`:space_id`) must match fields of `params(...)` type, and handler must
+//! extract that type by [`Path`] (or [`SpaceAccess`] for [`SpacePath`]). Otherwise
+//! route would fail only at runtime, when path is deserialized.
+
+use std::marker::PhantomData;
+
+use archk::v1::docs::DocumentationField;
+use axum::handler::Handler;
+
+use crate::app::AppState;
+
+use super::{
+    extra::{Path, SpaceAccess},
+    space::SpacePath,
+};
+
+/// Are parameters of `path` (segments `:name` or `@:name`) same as `fields`, in order?
+pub const fn path_params_match(path: &str, fields: &[DocumentationField]) -> bool {
+    let path = path.as_bytes();
+    let mut field = 0;
+    let mut i = 0;
+    while i < path.len() {
+        // at start of segment
+        if path[i] == b'/' {
+            i += 1;
+            continue;
+        }
+        if path[i] == b'@' {
+            i += 1;
+        }
+        if i < path.len() && path[i] == b':' {
+            i += 1;
+            if field == fields.len() {
+                return false;
+            }
+            let name = fields[field].name.as_bytes();
+            let mut j = 0;
+            while i < path.len() && path[i] != b'/' {
+                if j == name.len() || name[j] != path[i] {
+                    return false;
+                }
+                i += 1;
+                j += 1;
+            }
+            if j != name.len() {
+                return false;
+            }
+            field += 1;
+        }
+        while i < path.len() && path[i] != b'/' {
+            i += 1;
+        }
+    }
+    field == fields.len()
+}
+
+/// Extractors `T` of handler (as in [`Handler<T, _>`]) include path extractor of `P`.
+/// `I` is inferred to position of extractor.
+pub trait ExtractsPath<P, I> {}
+
+/// Marker of [`Path<P>`] after extractors `B`
+pub struct ViaPath<B>(PhantomData<B>);
+/// Marker of [`SpaceAccess`] after extractors `B`, which extracts [`SpacePath`]
+pub struct ViaSpaceAccess<B>(PhantomData<B>);
+
+macro_rules! impl_extracts_path {
+    ([$($before:ident)*] []) => {};
+    ([$($before:ident)*] [$current:ident $($after:ident)*]) => {
+        impl<P, M, $($before,)* $($after,)*> ExtractsPath<P, ViaPath<($($before,)*)>>
+            for (M, $($before,)* Path<P>, $($after,)*)
+        {
+        }
+        impl<X, M, $($before,)* $($after,)*> ExtractsPath<SpacePath, ViaSpaceAccess<($($before,)*)>>
+            for (M, $($before,)* SpaceAccess<X>, $($after,)*)
+        where
+            X: super::extra::SpacePermission,
+        {
+        }
+        impl_extracts_path!([$($before)* $current] [$($after)*]);
+    };
+}
+
+impl_extracts_path!([][T1]);
+impl_extracts_path!([] [T1 T2]);
+impl_extracts_path!([] [T1 T2 T3]);
+impl_extracts_path!([] [T1 T2 T3 T4]);
+impl_extracts_path!([] [T1 T2 T3 T4 T5]);
+impl_extracts_path!([] [T1 T2 T3 T4 T5 T6]);
+impl_extracts_path!([] [T1 T2 T3 T4 T5 T6 T7]);
+impl_extracts_path!([] [T1 T2 T3 T4 T5 T6 T7 T8]);
+impl_extracts_path!([] [T1 T2 T3 T4 T5 T6 T7 T8 T9]);
+impl_extracts_path!([] [T1 T2 T3 T4 T5 T6 T7 T8 T9 T10]);
+impl_extracts_path!([] [T1 T2 T3 T4 T5 T6 T7 T8 T9 T10 T11]);
+impl_extracts_path!([] [T1 T2 T3 T4 T5 T6 T7 T8 T9 T10 T11 T12]);
+impl_extracts_path!([] [T1 T2 T3 T4 T5 T6 T7 T8 T9 T10 T11 T12 T13]);
+impl_extracts_path!([] [T1 T2 T3 T4 T5 T6 T7 T8 T9 T10 T11 T12 T13 T14]);
+impl_extracts_path!([] [T1 T2 T3 T4 T5 T6 T7 T8 T9 T10 T11 T12 T13 T14 T15]);
+impl_extracts_path!([] [T1 T2 T3 T4 T5 T6 T7 T8 T9 T10 T11 T12 T13 T14 T15 T16]);
+
+/// Fails to compile unless `handler` extracts path into `P`. Never called.
+pub fn handler_extracts_path<P, T, I, H>(_handler: H)
+where
+    H: Handler<T, AppState>,
+    T: ExtractsPath<P, I>,
+{
+}
+
+#[cfg(test)]
+mod tests {
+    use archk::v1::docs::Documentation;
+
+    use super::*;
+    use crate::v1::{space::SpaceItemPath, user::UserIDPath};
+
+    #[test]
+    fn path_params() {
+        let fields = SpaceItemPath::DOCUMENTATION_OBJECT.fields;
+        assert!(path_params_match("/space/:space_id/item/:item_id", fields));
+        assert!(path_params_match(
+            "/space/:space_id/item/:item_id/qr",
+            fields
+        ));
+        assert!(!path_params_match("/space/:space_id/item/:id", fields));
+        assert!(!path_params_match(
+            "/space/:space_id/item/:item_ids",
+            fields
+        ));
+        assert!(!path_params_match("/space/:space_id/item", fields));
+        assert!(!path_params_match("/space/:space_id/:item_id/:x", fields));
+        assert!(path_params_match(
+            "/user/@:user_id",
+            UserIDPath::DOCUMENTATION_OBJECT.fields
+        ));
+        assert!(path_params_match("/users", &[]));
+    }
+}
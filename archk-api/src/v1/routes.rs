@@ -12,7 +12,9 @@ macro_rules! routes {
     (@method PUT $handler:path) => { put($handler) };
     (@method PATCH $handler:path) => { patch($handler) };
     (@method DELETE $handler:path) => { delete($handler) };
-    ( $( $(#[doc = $d:literal])* $method:ident $path:literal => $handler:path $( : $( body($body:path) )? $( res($res:path) )? )? ),* $(,)? ) => {
+    (@fields) => { &[] };
+    (@fields $params:path) => { <$params as docs::Documentation>::DOCUMENTATION_OBJECT.fields };
+    ( $( $(#[doc = $d:literal])* $method:ident $path:literal => $handler:path $( : $( params($params:path) )? $( auth($auth:ident) )? $( perms($($perm:ident),+) )? $( query($query:path) )? $( body($body:path) )? $( res($res:path) )? )? ),* $(,)? ) => {
         /// Get [`axum::Router`] to all endpoints without any fallback or layer.
         /// Use `v1::get_routes()` to include services and fallback
         // $(
@@ -24,79 +26,254 @@ macro_rules! routes {
                 $( .route($path, routes!(@method $method $handler)) )*
         }
 
+        #[allow(clippy::needless_update)]
         pub const ENDPOINTS: &[docs::Endpoint] = &[$(
             docs::Endpoint {
                 method: docs::EndpointMethod::$method,
                 path: $path,
                 description: concat!( $($d, "\n",)* ),
                 $(
+                    $( auth: docs::EndpointAuth::$auth, )?
+                    $( permissions: &[$(crate::roles::perm::$perm),+], )?
+                    $( params: Some( <$params as docs::Documentation>::DOCUMENTATION_OBJECT ), )?
+                    $( query: Some( <$query as docs::Documentation>::DOCUMENTATION_OBJECT ), )?
                     $( body: Some( <$body as docs::Documentation>::DOCUMENTATION_OBJECT ), )?
                     $( response: Some( <$res as docs::Documentation>::DOCUMENTATION_OBJECT ), )?
                 )?
-                ..docs::_EMPTY_ENDPOINT // fills `body` and `response` with `None`
+                // fills `params`, `query`, `body` and `response` with `None`, `auth` with `User` and no `permissions`
+                ..docs::_EMPTY_ENDPOINT
             }
         ),*
         ];
+
+        $(
+            const _: () = assert!(
+                route_check::path_params_match($path, routes!(@fields $( $( $params )? )?)),
+                concat!("parameters of `", $path, "` do not match its `params`")
+            );
+        )*
+
+        /// Fails to compile if handler does not extract `params` of its route.
+        #[allow(dead_code)]
+        fn handlers_extract_params() {
+            $( $( $( route_check::handler_extracts_path::<$params, _, _, _>($handler); )? )? )*
+        }
     };
 }
 
 use super::*;
 
 routes! {
-    /// Authorize and obtain token.
+    /// Authorize and obtain token. Fails with `RateLimited` and `Retry-After` header
+    /// while username is locked out after failed logins.
     POST "/auth" => auth::authorize
-        :   body(auth::AuthorizationRequestData)
+        :   auth(None)
+            body(auth::AuthorizationRequestData)
+            res(auth::AuthorizationResponse),
+
+    /// Start login with external OpenID Connect provider. Redirects to provider,
+    /// which redirects back to `/auth/oidc/callback`.
+    GET "/auth/oidc/login" => auth::oidc_login
+        :   auth(None)
+            query(auth::OidcLoginQuery)
+            res(docs::Empty),
+    /// Finish login with OpenID Connect provider and obtain token. New users are registered
+    /// with invite passed to `/auth/oidc/login` or without it if `auth.oidc.auto_register`
+    /// is enabled in config.
+    GET "/auth/oidc/callback" => auth::oidc_callback
+        :   auth(None)
+            query(auth::OidcCallbackQuery)
+            res(auth::AuthorizationResponse),
+
+    /// Obtain token by code of `POST /user/telegram`, used by Telegram bot linking
+    /// chat to user. Code is valid for 10 minutes and only once. Notifications of user
+    /// are sent to `chat_id`. Token has only `space.unlocks` and `user.notifications`
+    /// scopes
+    POST "/auth/telegram" => auth::telegram_authorize
+        :   auth(None)
+            body(auth::TelegramAuthorizationRequest)
             res(auth::AuthorizationResponse),
 
+    /// Get limited info of item shared by link. Fails with gone if link is expired
+    GET "/shared/:token" => share::get_shared_item
+        :   params(share::SharedTokenPath)
+            auth(None)
+            res(share::SharedItem),
+
+    /// Get catalogue of all error codes with their HTTP status codes.
+    GET "/errors" => errors::get_errors
+        :   auth(None)
+            res(Vec<archk::v1::api::ErrorDescription>),
+
+    /// Download consistent snapshot of database as SQLite file.
+    /// Available to roles with `backup` permission
+    POST "/admin/backup" => admin::backup
+        :   perms(BACKUP)
+            body(docs::Empty)
+            res(docs::Empty),
+    /// Get instance statistics: totals of users, tokens, spaces and services,
+    /// space events per day for last `days` and database size.
+    /// Available to roles with `stats` permission
+    GET "/admin/stats" => admin::get_stats
+        :   perms(STATS)
+            query(admin::StatsQuery)
+            res(admin::InstanceStats),
+    /// Get maintenance mode, `null` if disabled
+    GET "/admin/maintenance" => maintenance::get_maintenance
+        :   perms(MAINTENANCE)
+            res(Option<maintenance::Maintenance>),
+    /// Enable or disable maintenance mode. While it is enabled, API is read-only:
+    /// requests other than `GET`, `HEAD`, `OPTIONS` and logins (`/auth...`) fail with
+    /// `503 Service Unavailable` and `message` in `detail`, except ones of roles with
+    /// `maintenance` permission.
+    /// Mode is stored in database, so it survives restarts and applies to all servers
+    POST "/admin/maintenance" => maintenance::set_maintenance
+        :   perms(MAINTENANCE)
+            body(maintenance::MaintenanceBody)
+            res(Option<maintenance::Maintenance>),
+    /// Get migrations known to server and applied to database, with pending ones
+    /// (`applied_at` is `null`). Available to roles with `stats` permission
+    GET "/admin/migrations" => admin::get_migrations
+        :   perms(STATS)
+            res(Vec<crate::Migration>),
+    /// Get audit log of administrative actions on users, newest first. Supports paging
+    GET "/admin/audit" => admin::get_audit_log
+        :   perms(USER_MANAGE)
+            query(user::Paging)
+            res(Vec<archk::v1::audit::AuditLog>),
+
     /// Get all users. Supports paging.
     /// Can be accessed by any user.
     GET "/users" => user::get_users
-        :   res(Vec<archk::v1::user::User>),
+        :   query(user::Paging)
+            res(Vec<archk::v1::user::User>),
     /// Get all possible roles on current instance.
     /// Can be accessed by any user.
     GET "/users/roles" => user::get_all_roles
         :   res(Vec<crate::roles::UserRole>),
+    /// Get invite tree built from `invited_by` of users. Entries are ordered depth-first,
+    /// so invitees follow their inviter
+    GET "/users/invite-tree" => user::get_invite_tree
+        :   perms(USER_MANAGE)
+            query(user::InviteTreeQuery)
+            res(Vec<user::InviteTreeEntry>),
 
     /// Get current user
     GET   "/user" => user::get_self
         :   res(user::SelfResponse),
     /// Register new user
     PUT   "/user" => user::register
-        :   body(user::RegisterRequestData)
+        :   auth(None)
+            body(user::RegisterRequestData)
             res(user::RegisterResponse),
+    /// Delete current user and erase their data. Owned spaces are deleted unless
+    /// `transfer_spaces_to` passed. With `?dry_run=true` only counts rows to delete
+    DELETE "/user" => user::delete_self
+        :   query(user::DeleteUserQuery)
+            res(user::DeleteUserReport),
     /// Update user password
     PATCH "/user" => user::patch_user
         :   body(user::PatchUser)
             res(u64),
-    /// Get own spaces. Supports paging
+    /// Get permissions granted to own level or to `level`, eg. to find out why action
+    /// is forbidden. Wildcards of roles are expanded
+    GET   "/user/permissions" => user::get_permissions
+        :   query(user::PermissionsQuery)
+            res(user::PermissionsResponse),
+    /// Get own spaces. Supports paging. Archived spaces are shown only with `?archived=true`
     GET   "/user/spaces" => user::get_spaces
-        :   res(Vec<user::UserSpaceResponse>),
+        :   query(user::SpacesQuery)
+            res(Vec<user::UserSpaceResponse>),
     /// Get other user by their ID
     GET   "/user/@:user_id" => user::get_user
-        :   res(archk::v1::user::User),
+        :   params(user::UserIDPath)
+            res(archk::v1::user::User),
     /// Reset other user password
     PATCH "/user/@:user_id" => user::reset_user_password
-        :   res(user::ResetPasswordResponse),
+        :   params(user::UserIDPath)
+            perms(USER_MANAGE)
+            body(docs::Empty)
+            res(user::ResetPasswordResponse),
+    /// Delete user and erase their data. Owned spaces are deleted unless
+    /// `transfer_spaces_to` passed. With `?dry_run=true` only counts rows to delete
+    DELETE "/user/@:user_id" => user::delete_user
+        :   params(user::UserIDPath)
+            perms(USER_MANAGE)
+            query(user::DeleteUserQuery)
+            res(user::DeleteUserReport),
     /// Get user role (by level)
     GET   "/user/@:user_id/role" => user::get_user_role
-        :   res(crate::roles::UserRole),
-    /// Promote user to role or level
+        :   params(user::UserIDPath)
+            perms(USER_PROMOTE)
+            res(crate::roles::UserRole),
+    /// Get permissions granted to level of user
+    GET   "/user/@:user_id/permissions" => user::get_user_permissions
+        :   params(user::UserIDPath)
+            perms(USER_PROMOTE)
+            res(user::PermissionsResponse),
+    /// Promote user to role (by name) or level, returns new level and its role. Users
+    /// of own level or higher can't be changed, new level can't be above own one and
+    /// own level can't be lowered to role without `user.promote`
     PATCH "/user/@:user_id/role" => user::promote_user
-        :   body(user::PromoteUserBody)
+        :   params(user::UserIDPath)
+            perms(USER_PROMOTE)
+            body(user::PromoteUserBody)
+            res(user::PromoteUserResponse),
+    /// Get users invited by user. Supports paging
+    GET   "/user/@:user_id/invitees" => user::get_invitees
+        :   params(user::UserIDPath)
+            query(user::Paging)
+            res(Vec<archk::v1::user::User>),
+    /// Revoke all tokens of user without resetting password
+    POST  "/user/@:user_id/logout" => user::logout_user
+        :   params(user::UserIDPath)
+            perms(USER_MANAGE)
+            body(docs::Empty)
             res(u64),
-    /// Get user spaces
+    /// Temporarily lock login of user. Returns timestamp in milliseconds of unlock.
+    /// Existing tokens are not revoked, see `POST /user/@:user_id/logout`
+    PUT   "/user/@:user_id/lock" => user::lock_user
+        :   params(user::UserIDPath)
+            perms(USER_MANAGE)
+            body(user::LockUserBody)
+            res(i64),
+    /// Unlock login of user
+    DELETE "/user/@:user_id/lock" => user::unlock_user
+        :   params(user::UserIDPath)
+            perms(USER_MANAGE)
+            res(u64),
+    /// Issue short-lived personal token of user for support. Target user should have
+    /// lower level than admin. Token is listed in sessions of user and can be revoked
+    POST  "/user/@:user_id/impersonate" => user::impersonate_user
+        :   params(user::UserIDPath)
+            perms(USER_MANAGE)
+            body(user::ImpersonateBody)
+            res(user::ImpersonateResponse),
+    /// Get sessions (personal tokens) of user with their last user agent, IP and usage time
+    GET   "/user/@:user_id/sessions" => user::get_user_sessions
+        :   params(user::UserIDPath)
+            perms(USER_MANAGE)
+            res(Vec<user::SessionResponse>),
+    /// Get user spaces. Supports paging. Archived spaces are shown only with `?archived=true`
     GET   "/user/@:user_id/spaces" => user::get_user_spaces
-        :   res(Vec<user::UserSpaceResponse>),
+        :   params(user::UserIDPath)
+            perms(SPACE_MANAGE)
+            query(user::SpacesQuery)
+            res(Vec<user::UserSpaceResponse>),
     /// Get invites
     GET   "/user/invites" => user::get_invites
         :   res(Vec<String>),
     /// Create invite
     PUT   "/user/invites" => user::create_invite
-        :   res(String),
+        :   body(docs::Empty)
+            res(String),
     /// Give every user one invite. If query param `min_level` set, gives
     /// only to users with level `min_level` or higher
     POST  "/user/invites/wave" => user::invite_wave
-        :   res(u64),
+        :   perms(USER_WAVE)
+            query(user::InviteWaveData)
+            body(docs::Empty)
+            res(u64),
 
     /// Get own SSH keys
     GET "/user/ssh-keys" => user::get_ssh_keys
@@ -107,61 +284,680 @@ routes! {
             res(archk::v1::user::ssh::UserSSHKey),
     /// Delete ssh key by their CUID
     DELETE "/user/ssh-keys/:key_id" => user::delete_ssh_key
-        :   res(u64),
+        :   params(user::SSHKeyPath)
+            res(u64),
+
+    /// Get usage of own resources and limits of role quota. Limits of items and
+    /// services in spaces are ones of role of space owner
+    GET "/user/quota" => quota::get_quota
+        :   res(quota::QuotaResponse),
+
+    /// Get own sessions (personal tokens) with their last user agent, IP and usage time
+    GET "/user/sessions" => user::get_sessions
+        :   res(Vec<user::SessionResponse>),
+    /// Create personal token restricted to one space and scopes, eg. for dashboards.
+    /// Such token is accepted only by `/space/:space_id/...` endpoints of accounts, items,
+    /// tags, policy and logs, and never gives more than user has. Revoke it as session
+    PUT "/user/tokens" => user::put_scoped_token
+        :   body(user::ScopedTokenBody)
+            res(user::ScopedTokenResponse),
+    /// Create code linking Telegram chat to user, see `POST /auth/telegram`.
+    /// Previous code of user becomes invalid
+    POST "/user/telegram" => user::create_telegram_auth
+        :   body(docs::Empty)
+            res(user::TelegramAuthResponse),
+    /// Get own notification settings. Notifications are sent about events in owned
+    /// spaces: filed reports, denied unlocks and overdue items
+    GET "/user/notifications" => user::get_notifications
+        :   res(user::UserNotifications),
+    /// Change own notification settings. Only channels configured on server can be set
+    PATCH "/user/notifications" => user::patch_notifications
+        :   body(user::PatchNotificationsBody)
+            res(user::UserNotifications),
+    /// Revoke session by `iat` of its token
+    DELETE "/user/sessions/:iat" => user::revoke_session
+        :   params(user::SessionPath)
+            res(u64),
 
     /// Create space
-    PUT   "/space" => space::create_space,
+    PUT   "/space" => space::create_space
+        :   perms(SPACE_CREATE)
+            body(space::PatchSpace)
+            res(archk::v1::space::Space),
 
-    GET    "/space/:space_id" => space::get_space,
-    PATCH  "/space/:space_id" => space::patch_space,
-    DELETE "/space/:space_id" => space::delete_space,
+    GET    "/space/:space_id" => space::get_space
+        :   params(space::SpacePath)
+            perms(SPACE_MANAGE)
+            res(space::GetSpaceResponse),
+    /// Update space. Pass record version in `If-Match` header or `expected_version`
+    /// field to fail with conflict if space was changed
+    PATCH  "/space/:space_id" => space::patch_space
+        :   params(space::SpacePath)
+            perms(SPACE_MANAGE)
+            body(space::PatchSpace)
+            res(u64),
+    /// Delete space with its accounts, items, logs and services. Returns number of deleted
+    /// rows. With `?dry_run=true` only counts them
+    DELETE "/space/:space_id" => space::delete_space
+        :   params(space::SpacePath)
+            perms(SPACE_MANAGE)
+            query(space::DryRunQuery)
+            res(space::DeleteSpaceReport),
+    /// Archive space. Archived space is read-only, hidden from space listings by default
+    /// and its services can't submit events. Fails with conflict if already archived
+    POST   "/space/:space_id/archive" => space::archive_space
+        :   params(space::SpacePath)
+            perms(SPACE_MANAGE)
+            body(docs::Empty)
+            res(u64),
+    /// Unarchive space. Fails with conflict if space is not archived
+    POST   "/space/:space_id/unarchive" => space::unarchive_space
+        :   params(space::SpacePath)
+            perms(SPACE_MANAGE)
+            body(docs::Empty)
+            res(u64),
+
+    /// Mark space as template. Templates are listed by `GET /space/templates` and can be
+    /// duplicated by any user allowed to create spaces
+    POST   "/space/:space_id/template" => space::mark_template
+        :   params(space::SpacePath)
+            perms(SPACE_MANAGE)
+            body(docs::Empty)
+            res(u64),
+    /// Unmark space as template
+    DELETE "/space/:space_id/template" => space::unmark_template
+        :   params(space::SpacePath)
+            perms(SPACE_MANAGE)
+            res(u64),
+    /// Create space owned by current user from own space or template. Copies title,
+    /// logs retention, policy and tags, and with `items` also items without owner.
+    /// Accounts, logs and services are not copied
+    POST   "/space/:space_id/duplicate" => space::duplicate_space
+        :   params(space::SpacePath)
+            perms(SPACE_CREATE)
+            body(space::DuplicateSpaceBody)
+            res(archk::v1::space::Space),
+    /// Get spaces marked as templates
+    GET    "/space/templates" => space::get_templates
+        :   perms(SPACE_CREATE)
+            res(Vec<archk::v1::space::Space>),
 
-    GET "/space/:space_id/account" => space::get_accounts,
-    PUT "/space/:space_id/account" => space::create_account,
+    /// Get accounts of space. Supports paging and `?meta.key=value` metadata filters.
+    /// Page is returned as CSV with `?format=csv` or `Accept: text/csv`
+    GET "/space/:space_id/account" => space::get_accounts
+        :   params(space::SpacePath)
+            perms(SPACE_MANAGE)
+            query(space::Paging)
+            res(Vec<space::SpaceAccountWithoutSpaceID>),
+    PUT "/space/:space_id/account" => space::create_account
+        :   params(space::SpacePath)
+            perms(SPACE_MANAGE)
+            body(space::SpaceAccountWithoutSpaceID)
+            res(archk::v1::space::SpaceAccount),
+    /// Synchronize accounts with full list of platform accounts in one transaction.
+    /// Creates new accounts, updates (and reactivates) changed ones and, if
+    /// `deactivate_missing` set, deactivates accounts absent in list
+    POST "/space/:space_id/account/sync" => space::sync_accounts
+        :   params(space::SpacePath)
+            perms(SPACE_MANAGE)
+            body(space::SyncAccountsBody) res(space::SyncAccountsResponse),
+    /// Export all accounts of space as stream. Query param `format` is `jsonl` (default)
+    /// or `csv`, metadata is exported as JSON
+    GET "/space/:space_id/account/export" => space::export_accounts
+        :   params(space::SpacePath)
+            perms(SPACE_MANAGE)
+            query(space::ExportQuery)
+            res(docs::Empty),
 
-    GET    "/space/:space_id/account/:acc_id" => space::get_account_by_id,
-    PATCH  "/space/:space_id/account/:acc_id" => space::patch_account_by_id,
-    DELETE "/space/:space_id/account/:acc_id" => space::delete_account_by_id,
+    GET    "/space/:space_id/account/:acc_id" => space::get_account_by_id
+        :   params(space::SpaceAccountPath)
+            perms(SPACE_MANAGE)
+            res(archk::v1::space::SpaceAccount),
+    /// Update account. Supports `If-Match`/`expected_version` like space update
+    PATCH  "/space/:space_id/account/:acc_id" => space::patch_account_by_id
+        :   params(space::SpaceAccountPath)
+            perms(SPACE_MANAGE)
+            body(space::PatchAccountBody)
+            res(u64),
+    DELETE "/space/:space_id/account/:acc_id" => space::delete_account_by_id
+        :   params(space::SpaceAccountPath)
+            perms(SPACE_MANAGE)
+            res(u64),
 
-    GET "/space/:space_id/account/:acc_id/items" => space::get_items_of_account,
+    /// Get unlock allow/deny list of space containing account
+    GET    "/space/:space_id/account/:acc_id/access" => space::get_account_access
+        :   params(space::SpaceAccountPath)
+            perms(SPACE_MANAGE)
+            res(space::AccountAccessResponse),
+    /// Put account in unlock allow or deny list of space, or remove it from them with
+    /// `"access": null`. Lists are consulted before keycard and reports checks of unlock
+    /// policy. Change and its reason are recorded in space logs
+    PUT    "/space/:space_id/account/:acc_id/access" => space::put_account_access
+        :   params(space::SpaceAccountPath)
+            perms(SPACE_MANAGE)
+            body(space::AccountAccessBody)
+            res(space::SpaceLogEntry),
 
-    GET "/space/:space_id/item" => space::get_items,
-    PUT "/space/:space_id/item" => space::create_item,
+    /// Get items owned by account. Supports paging, `?meta.key=value` metadata filters
+    /// and `?status=<name>` filter.
+    GET "/space/:space_id/account/:acc_id/items" => space::get_items_of_account
+        :   params(space::SpaceAccountPath)
+            perms(SPACE_MANAGE)
+            query(space::Paging)
+            res(Vec<space::SpaceItemWithoutSpaceID>),
 
-    GET    "/space/:space_id/item/:item_id" => space::get_item_by_id,
-    PATCH  "/space/:space_id/item/:item_id" => space::patch_item,
-    DELETE "/space/:space_id/item/:item_id" => space::delete_item,
+    /// Get items of space. Supports paging, `?meta.key=value` metadata filters,
+    /// `?tag=<tag_id>` and `?status=<name>` filters. Page is returned as CSV with
+    /// `?format=csv` or `Accept: text/csv`
+    GET "/space/:space_id/item" => space::get_items
+        :   params(space::SpacePath)
+            perms(SPACE_MANAGE)
+            query(space::Paging)
+            res(Vec<space::SpaceItemWithoutSpaceID>),
+    PUT "/space/:space_id/item" => space::create_item
+        :   params(space::SpacePath)
+            perms(SPACE_MANAGE)
+            body(space::CreateSpaceItemBody)
+            res(archk::v1::space::SpaceItem),
+    /// Create many items at once in single transaction. Body is JSON array of items or
+    /// CSV (`Content-Type: text/csv`) with header `title,ty,pl_serial,owner_id`.
+    /// If any row fails, nothing is created and errors of rows are returned. With
+    /// `?dry_run=true` rows are only validated
+    PUT "/space/:space_id/item/bulk" => space::create_items_bulk
+        :   params(space::SpacePath)
+            perms(SPACE_MANAGE)
+            query(space::DryRunQuery)
+            body(Vec<space::CreateSpaceItemBody>)
+            res(space::BulkItemsResponse),
+    /// Get taken items that should already be returned. Supports paging.
+    GET "/space/:space_id/item/overdue" => space::get_overdue_items
+        :   params(space::SpacePath)
+            perms(SPACE_MANAGE)
+            query(space::Paging)
+            res(Vec<space::SpaceItemWithoutSpaceID>),
+    /// Export all items of space as stream. Query param `format` is `jsonl` (default)
+    /// or `csv`, metadata is exported as JSON
+    GET "/space/:space_id/item/export" => space::export_items
+        :   params(space::SpacePath)
+            perms(SPACE_MANAGE)
+            query(space::ExportQuery)
+            res(docs::Empty),
+
+    GET    "/space/:space_id/item/:item_id" => space::get_item_by_id
+        :   params(space::SpaceItemPath)
+            perms(SPACE_MANAGE)
+            res(space::GetSpaceItemResponse),
+    /// Update item. Supports `If-Match`/`expected_version` like space update. Changes of
+    /// `title`, `ty` and `owner_id` are recorded to item history
+    PATCH  "/space/:space_id/item/:item_id" => space::patch_item
+        :   params(space::SpaceItemPath)
+            perms(SPACE_MANAGE)
+            body(space::PatchItemBody)
+            res(u64),
+    /// Get changes of item `title`, `ty` and `owner_id`, newest first. Supports paging
+    GET    "/space/:space_id/item/:item_id/history" => space::get_item_history
+        :   params(space::SpaceItemPath)
+            perms(SPACE_MANAGE)
+            query(space::Paging)
+            res(Vec<space::SpaceItemChange>),
+    DELETE "/space/:space_id/item/:item_id" => space::delete_item
+        :   params(space::SpaceItemPath)
+            perms(SPACE_MANAGE)
+            res(u64),
+    /// Get QR code of item `pl_serial` for printing labels. Query param `format` is
+    /// `svg` (default) or `png`. Responds with image instead of JSON
+    GET    "/space/:space_id/item/:item_id/qr" => space::get_item_qr
+        :   params(space::SpaceItemPath)
+            perms(SPACE_MANAGE)
+            query(space::ItemQrQuery)
+            res(docs::Empty),
+    /// Create read-only link to item for outside parties, see `GET /shared/:token`.
+    /// Links can't be revoked before expiration except by deleting item
+    POST   "/space/:space_id/item/:item_id/share" => share::share_item
+        :   params(space::SpaceItemPath)
+            perms(SPACE_MANAGE)
+            body(share::ShareItemBody)
+            res(share::ShareItemResponse),
+    /// Get attachments of item, oldest first
+    GET    "/space/:space_id/item/:item_id/attachments" => attachment::get_attachments
+        :   params(space::SpaceItemPath)
+            perms(SPACE_MANAGE)
+            res(Vec<attachment::SpaceAttachment>),
+    /// Upload file attached to item (photo, manual, etc...). Body is raw file with its
+    /// type in `Content-Type`, allowed types and size are limited by server config.
+    /// Fails with not found if attachments are not configured
+    PUT    "/space/:space_id/item/:item_id/attachments" => attachment::upload_attachment
+        :   params(space::SpaceItemPath)
+            perms(SPACE_MANAGE)
+            query(attachment::UploadAttachmentQuery)
+            body(docs::Empty)
+            res(attachment::SpaceAttachment),
+    /// Download file of attachment. Responds with file itself instead of JSON
+    GET    "/space/:space_id/item/:item_id/attachments/:attachment_id" => attachment::download_attachment
+        :   params(attachment::SpaceAttachmentPath)
+            perms(SPACE_MANAGE)
+            res(docs::Empty),
+    /// Delete attachment. Attachments are also deleted with their items
+    DELETE "/space/:space_id/item/:item_id/attachments/:attachment_id" => attachment::delete_attachment
+        :   params(attachment::SpaceAttachmentPath)
+            perms(SPACE_MANAGE)
+            res(u64),
+    /// Attach tag to item. Fails with conflict if tag already attached
+    PUT    "/space/:space_id/item/:item_id/tag/:tag_id" => space::attach_tag
+        :   params(space::SpaceItemTagPath)
+            perms(SPACE_MANAGE)
+            body(docs::Empty)
+            res(u64),
+    /// Detach tag from item
+    DELETE "/space/:space_id/item/:item_id/tag/:tag_id" => space::detach_tag
+        :   params(space::SpaceItemTagPath)
+            perms(SPACE_MANAGE)
+            res(u64),
+    /// Get reservations of item which are not ended yet, by start. Supports paging
+    GET    "/space/:space_id/item/:item_id/reservations" => reservation::get_reservations
+        :   params(space::SpaceItemPath)
+            perms(SPACE_MANAGE)
+            query(space::Paging)
+            res(Vec<reservation::Reservation>),
+    /// Reserve item for account. Fails with conflict if item is reserved or checked
+    /// out at that time, or its status is not takeable. While reservation is active,
+    /// item can be taken only by its account
+    PUT    "/space/:space_id/item/:item_id/reservations" => reservation::create_reservation
+        :   params(space::SpaceItemPath)
+            perms(SPACE_MANAGE)
+            body(reservation::CreateReservationBody)
+            res(reservation::Reservation),
+    /// Cancel reservation
+    DELETE "/space/:space_id/item/:item_id/reservations/:reservation_id" => reservation::delete_reservation
+        :   params(reservation::SpaceReservationPath)
+            perms(SPACE_MANAGE)
+            res(u64),
+    /// Get windows between `from` and `to` when item is neither reserved nor checked
+    /// out. Item taken without due date is busy until returned
+    GET    "/space/:space_id/item/:item_id/availability" => reservation::get_availability
+        :   params(space::SpaceItemPath)
+            perms(SPACE_MANAGE)
+            query(reservation::AvailabilityQuery)
+            res(Vec<reservation::FreeWindow>),
+    /// Change item status and record it to space logs. Fails with conflict if new status
+    /// is not in `next` of current one
+    PATCH "/space/:space_id/item/:item_id/status" => item_status::patch_item_status
+        :   params(space::SpaceItemPath)
+            perms(SPACE_MANAGE)
+            body(item_status::ItemStatusBody)
+            res(space::SpaceLogEntry),
+    /// Check out item to account. Fails with conflict if item already taken or its
+    /// status is not takeable
+    POST "/space/:space_id/item/:item_id/take" => space::post_take_item
+        :   params(space::SpaceItemPath)
+            perms(SPACE_MANAGE)
+            body(space::TakeItemBody)
+            res(space::SpaceLogEntry),
+    /// Return checked out item
+    POST "/space/:space_id/item/:item_id/return" => space::post_return_item
+        :   params(space::SpaceItemPath)
+            perms(SPACE_MANAGE)
+            body(docs::Empty)
+            res(space::SpaceLogEntry),
 
-    /// Get services bound to space. Supports pagging.
+    /// Get space logs, newest first, with account and item data. Supports paging.
+    /// Query params (all optional): `act`, `from` and `to` (timestamps in milliseconds),
+    /// `acc_id`, `item_id`. Available to space owner and roles with `space.logs.read`
+    GET   "/space/:space_id/logs" => space::get_logs
+        :   params(space::SpacePath)
+            perms(SPACE_LOGS_READ, SPACE_LOGS_MANAGE, SPACE_MANAGE)
+            query(space::LogsQuery)
+            res(Vec<space::SpaceLogDetailedEntry>),
+    /// Comment log entry, eg. to document why unlock was approved or report resolved.
+    /// Comments are returned with entry by `GET /space/:space_id/logs`
+    POST  "/space/:space_id/logs/:log_id/comments" => space::add_log_comment
+        :   params(space::SpaceLogPath)
+            perms(SPACE_LOGS_MANAGE, SPACE_MANAGE)
+            body(space::LogCommentBody)
+            res(space::SpaceLogComment),
+    /// Export full space log as stream. Query param `format` is `jsonl` (default) or `csv`
+    GET   "/space/:space_id/logs/export" => space::export_logs
+        :   params(space::SpacePath)
+            perms(SPACE_LOGS_READ, SPACE_LOGS_MANAGE, SPACE_MANAGE)
+            query(space::ExportQuery)
+            res(docs::Empty),
+    /// Get accounts, items and log entries changed since log entry `since` in one
+    /// response, so devices can keep local cache of space. Pass `cursor` of response
+    /// as `since` next time. Also available to `SpaceActor` and `SpaceManager`
+    /// services of space
+    GET   "/space/:space_id/sync" => space::sync_space
+        :   params(space::SpacePath)
+            perms(SPACE_MANAGE)
+            query(space::SyncQuery)
+            res(space::SyncResponse),
+    /// Stream new log entries of space as Server-Sent Events (`text/event-stream`).
+    /// Each entry is sent as `log` event with entry ID as event ID, entries related
+    /// to item are followed by `item` event with current state of item. Pass
+    /// `Last-Event-ID` header to resume after entry
+    GET   "/space/:space_id/events/sse" => space::space_events
+        :   params(space::SpacePath)
+            perms(SPACE_LOGS_READ, SPACE_LOGS_MANAGE, SPACE_MANAGE)
+            res(docs::Empty),
+    /// Set logs retention of space in days. Old logs are removed periodically.
+    /// Available to space owner and roles with `space.logs.manage`
+    PATCH "/space/:space_id/logs/retention" => space::patch_logs_retention
+        :   params(space::SpacePath)
+            perms(SPACE_LOGS_MANAGE, SPACE_MANAGE)
+            body(space::PatchRetentionBody)
+            res(u64),
+
+    /// Create token of iCalendar feed of space, see `GET /space/:space_id/calendar.ics`.
+    /// Tokens are valid until expiration or `DELETE /space/:space_id/calendar/token`
+    POST  "/space/:space_id/calendar/token" => calendar::create_calendar_token
+        :   params(space::SpacePath)
+            perms(SPACE_MANAGE)
+            body(calendar::CalendarTokenBody)
+            res(calendar::CalendarTokenResponse),
+    /// Revoke all issued tokens of iCalendar feed of space, eg. when feed URL leaked.
+    /// Tokens created after it are valid
+    DELETE "/space/:space_id/calendar/token" => calendar::revoke_calendar_tokens
+        :   params(space::SpacePath)
+            perms(SPACE_MANAGE)
+            res(u64),
+    /// iCalendar (`text/calendar`) feed of item reservations and due dates of taken
+    /// items, for subscribing from calendar apps. Authorized by `?token=` instead of
+    /// `Authorization` header. Fails with gone if token is expired
+    GET   "/space/:space_id/calendar.ics" => calendar::get_calendar
+        :   params(space::SpacePath)
+            auth(None)
+            query(calendar::CalendarQuery)
+            res(docs::Empty),
+
+    /// Get tags of space. Supports paging.
+    GET    "/space/:space_id/tag" => space::get_tags
+        :   params(space::SpacePath)
+            perms(SPACE_MANAGE)
+            query(space::Paging)
+            res(Vec<space::SpaceTagWithoutSpaceID>),
+    /// Create tag in space. Fails with conflict if tag with same title exists
+    PUT    "/space/:space_id/tag" => space::create_tag
+        :   params(space::SpacePath)
+            perms(SPACE_MANAGE)
+            body(space::CreateTagBody)
+            res(archk::v1::space::SpaceTag),
+    /// Delete tag. Tag is detached from all items
+    DELETE "/space/:space_id/tag/:tag_id" => space::delete_tag
+        :   params(space::SpaceTagPath)
+            perms(SPACE_MANAGE)
+            res(u64),
+
+    /// Get unlock policy of space
+    GET   "/space/:space_id/policy" => space::get_policy
+        :   params(space::SpacePath)
+            perms(SPACE_MANAGE)
+            res(archk::v1::space::UnlockPolicy),
+    /// Update unlock policy of space
+    PATCH "/space/:space_id/policy" => space::patch_policy
+        :   params(space::SpacePath)
+            perms(SPACE_MANAGE)
+            body(space::PatchPolicyBody)
+            res(archk::v1::space::UnlockPolicy),
+    /// Get item statuses of space. Spaces without own statuses use `available`,
+    /// `maintenance` and `lost`
+    GET   "/space/:space_id/item-statuses" => item_status::get_item_statuses
+        :   params(space::SpacePath)
+            perms(SPACE_MANAGE)
+            res(Vec<archk::v1::space::ItemStatus>),
+    /// Replace item statuses of space. New items get first status. Fails with conflict
+    /// if some items are in removed statuses
+    PUT   "/space/:space_id/item-statuses" => item_status::put_item_statuses
+        :   params(space::SpacePath)
+            perms(SPACE_MANAGE)
+            body(Vec<archk::v1::space::ItemStatus>)
+            res(Vec<archk::v1::space::ItemStatus>),
+    /// Get access windows of space and its accounts. Supports paging
+    GET    "/space/:space_id/access-windows" => access_window::get_access_windows
+        :   params(space::SpacePath)
+            perms(SPACE_MANAGE)
+            query(space::Paging)
+            res(Vec<access_window::SpaceAccessWindow>),
+    /// Create weekly access window, eg. weekdays from `08:00` to `20:00` in given
+    /// timezone. Unlock is denied outside of windows of account or, if account has no
+    /// own windows, outside of windows of space. Allow list overrides windows
+    PUT    "/space/:space_id/access-windows" => access_window::create_access_window
+        :   params(space::SpacePath)
+            perms(SPACE_MANAGE)
+            body(access_window::CreateAccessWindowBody)
+            res(access_window::SpaceAccessWindow),
+    /// Update access window
+    PATCH  "/space/:space_id/access-windows/:window_id" => access_window::patch_access_window
+        :   params(access_window::SpaceAccessWindowPath)
+            perms(SPACE_MANAGE)
+            body(access_window::PatchAccessWindowBody)
+            res(access_window::SpaceAccessWindow),
+    /// Delete access window
+    DELETE "/space/:space_id/access-windows/:window_id" => access_window::delete_access_window
+        :   params(access_window::SpaceAccessWindowPath)
+            perms(SPACE_MANAGE)
+            res(u64),
+    /// Get undecided unlock requests of space. Supports paging
+    GET   "/space/:space_id/unlock-requests" => space::get_unlock_requests
+        :   params(space::SpacePath)
+            perms(SPACE_MANAGE)
+            query(space::Paging)
+            res(Vec<space::SpaceLogEntry>),
+    /// Approve or deny unlock request, same as `SpaceManager` services do
+    POST  "/space/:space_id/unlock-requests/:log_id" => space::decide_unlock_request
+        :   params(space::SpaceLogPath)
+            perms(SPACE_MANAGE)
+            body(service::manager::UnlockDecisionBody)
+            res(space::SpaceLogEntry),
+
+    /// Get services bound to space with their last heartbeat. Supports pagging.
+    /// Available to space owner and roles with both `service.manage` and `space.manage`
     GET "/space/:space_id/services" => service::get_space_services
-        :   res(Vec<service::ServiceAccountResponse>),
+        :   params(space::SpacePath)
+            perms(SERVICE_MANAGE, SPACE_MANAGE)
+            query(space::Paging)
+            res(Vec<service::ServiceAccountResponse>),
+
+    /// Create organization, current user becomes its owner
+    PUT    "/org" => org::create_org
+        :   perms(ORG_CREATE)
+            body(org::CreateOrgBody)
+            res(archk::v1::org::Organization),
+    /// Get organizations of current user with their roles
+    GET    "/org" => org::get_orgs
+        :   res(Vec<org::UserOrgResponse>),
+    /// Get organization. Only members see organization
+    GET    "/org/:org_id" => org::get_org
+        :   params(org::OrgPath)
+            res(org::UserOrgResponse),
+    /// Delete organization. Requires `Owner` role, spaces are kept without organization
+    DELETE "/org/:org_id" => org::delete_org
+        :   params(org::OrgPath)
+            res(u64),
+    /// Get members of organization
+    GET    "/org/:org_id/members" => org::get_members
+        :   params(org::OrgPath)
+            res(Vec<archk::v1::org::OrgMember>),
+    /// Add user to organization or change their role. Admins manage members with lower
+    /// role, owners manage everyone
+    PUT    "/org/:org_id/members" => org::put_member
+        :   params(org::OrgPath)
+            body(org::PutOrgMemberBody)
+            res(u64),
+    /// Remove user from organization. Members may leave by themselves, last owner
+    /// can't leave
+    DELETE "/org/:org_id/members/:user_id" => org::delete_member
+        :   params(org::OrgMemberPath)
+            res(u64),
+    /// Get spaces of organization
+    GET    "/org/:org_id/spaces" => org::get_org_spaces
+        :   params(org::OrgPath)
+            res(Vec<archk::v1::space::Space>),
+    /// Add own space to organization. Requires `Admin` role in organization. Admins of
+    /// organization get same access to content of space as its owner
+    PUT    "/org/:org_id/spaces/:space_id" => org::attach_space
+        :   params(org::OrgSpacePath)
+            perms(SPACE_MANAGE)
+            body(docs::Empty)
+            res(u64),
+    /// Remove space from organization. Allowed to admins of organization and owner of space
+    DELETE "/org/:org_id/spaces/:space_id" => org::detach_space
+        :   params(org::OrgSpacePath)
+            res(u64),
 
     /// Get admin services. If query param `?all=true` passed shows all services including from spaces.
     /// Supports paging.
     GET "/service" => service::get_services
-        :   res(Vec<service::ServiceAccountResponse>),
-    /// Creates new service.
+        :   perms(SERVICE_MANAGE)
+            query(service::ServiceFetchOptions)
+            res(Vec<service::ServiceAccountResponse>),
+    /// Creates new service. Fails with forbidden if role can't create services of this type
+    /// and with conflict if space has too many services (`server.services` in config)
     PUT "/service" => service::create_service
-        // FIXME: real return type is `archk::v1::service::ServiceAccount`
-        // FIXME: uncomment body() when spaces will be documentated
-        :   //body(service::CreateServiceBody)
-            res(service::ServiceAccountResponse),
+        :   perms(SERVICE_CREATE, SERVICE_MANAGE, SPACE_MANAGE)
+            body(service::CreateServiceBody)
+            res(archk::v1::service::ServiceAccount),
+    /// Update service name or description
+    PATCH "/service/:service_account_id" => service::patch_service
+        :   params(service::ServiceAccountPath)
+            perms(SERVICE_MANAGE)
+            body(service::PatchServiceBody)
+            res(u64),
     /// Delete service account
     DELETE "/service/:service_account_id" => service::delete_service
-        :   res(u64),
+        :   params(service::ServiceAccountPath)
+            perms(SERVICE_MANAGE)
+            res(u64),
 
-    /// Get tokens count for service
+    /// Get service tokens with their labels and last usage time
     GET "/service/:service_account_id/tokens" => service::get_tokens
-        :   res(i32),
-    /// Issue new service token
+        :   params(service::ServiceAccountPath)
+            perms(SERVICE_CREATE, SERVICE_MANAGE)
+            res(Vec<service::ServiceTokenInfo>),
+    /// Issue new service token. Body is optional.
+    /// Fails with conflict if service has too many tokens (`server.services` in config)
     PUT "/service/:service_account_id/tokens" => service::put_token
-        :   res(service::ServiceTokenResponse),
+        :   params(service::ServiceAccountPath)
+            perms(SERVICE_MANAGE)
+            body(service::PutTokenBody)
+            res(service::ServiceTokenResponse),
     /// Revoke all tokens
     DELETE "/service/:service_account_id/tokens" => service::revoke_all_tokens
-        :   res(u64),
+        :   params(service::ServiceAccountPath)
+            perms(SERVICE_MANAGE)
+            res(u64),
+    /// Revoke single token. `iat` and `rnd` are fields of the token itself
+    /// (see `archk::v1::auth::Token`), `rnd` is never returned by listing
+    DELETE "/service/:service_account_id/tokens/:iat/:rnd" => service::revoke_token
+        :   params(service::ServiceTokenPath)
+            perms(SERVICE_MANAGE)
+            res(u64),
+    /// Bind client certificate to service. If `server.client_cert_header` is configured,
+    /// service may authenticate by this certificate instead of bearer token.
+    /// Replaces previously bound certificate
+    PUT "/service/:service_account_id/certificate" => service::put_certificate
+        :   params(service::ServiceAccountPath)
+            perms(SERVICE_MANAGE)
+            body(service::CertificateBody)
+            res(u64),
+    /// Unbind client certificate from service
+    DELETE "/service/:service_account_id/certificate" => service::delete_certificate
+        :   params(service::ServiceAccountPath)
+            perms(SERVICE_MANAGE)
+            res(u64),
+
+    /// Report that service is alive with optional status. Returns timestamp in milliseconds
+    /// saved as `last_seen_at` of service. Body is optional
+    POST "/service/_/heartbeat" => service::heartbeat
+        :   auth(Service)
+            body(service::HeartbeatBody)
+            res(i64),
+
+    /// Submit actor event, eg. `{ "unlock": { "pl_id": ... } }`.
+    /// Response is tagged with same variant as event.
+    /// Unlock events are decided by space unlock policy, or left pending for `SpaceManager`
+    /// services if policy has `ask_manager`. Only for `SpaceActor` services.
+    POST "/service/_/space/events" => service::actor::submit_event
+        :   auth(Service)
+            body(service::actor::ActorEvent)
+            res(service::actor::ActorEventResponse),
+
+    /// Submit events buffered by actor (eg. while offline) at once, at most 1000.
+    /// Only report events can be batched, as others need immediate decision. All reports
+    /// are filed or none, entries are returned in order of events. Only for `SpaceActor`
+    /// services.
+    POST "/service/_/space/events/batch" => service::actor::submit_events_batch
+        :   auth(Service)
+            body(Vec<service::actor::ActorEvent>)
+            res(Vec<space::SpaceLogEntry>),
+
+    /// Ask space owner to register new item. Only for `SpaceManager` services.
+    POST "/service/_/space/items" => service::manager::request_item_registration
+        :   auth(Service)
+            body(service::manager::ItemRegistrationBody)
+            res(space::SpaceLogEntry),
+    /// Get undecided unlock requests of space. Only for `SpaceManager` services.
+    /// Supports paging.
+    GET "/service/_/space/unlock-requests" => service::manager::get_unlock_requests
+        :   auth(Service)
+            query(space::Paging)
+            res(Vec<space::SpaceLogEntry>),
+    /// Approve or deny unlock request. Only for `SpaceManager` services.
+    POST "/service/_/space/unlock-requests/:log_id" => service::manager::decide_unlock_request
+        :   params(service::manager::LogPath)
+            auth(Service)
+            body(service::manager::UnlockDecisionBody)
+            res(space::SpaceLogEntry),
+    /// Get reports of space. If query param `?open=true` passed shows only unresolved reports.
+    /// Only for `SpaceManager` services. Supports paging.
+    GET "/service/_/space/reports" => service::manager::get_reports
+        :   auth(Service)
+            query(service::manager::ReportsQuery)
+            res(Vec<service::manager::ReportResponse>),
+    /// Mark report as resolved. Only for `SpaceManager` services.
+    POST "/service/_/space/reports/:log_id" => service::manager::resolve_report
+        :   params(service::manager::LogPath)
+            auth(Service)
+            body(service::manager::ResolveReportBody)
+            res(space::SpaceLogEntry),
+    /// Comment log entry of space. Only for `SpaceManager` services.
+    POST "/service/_/space/logs/:log_id/comments" => service::manager::add_log_comment
+        :   params(service::manager::LogPath)
+            auth(Service)
+            body(space::LogCommentBody)
+            res(space::SpaceLogComment),
+
+    /// Get log entries of space in chronological order, up to 50 per request.
+    /// Only for `SpaceEventWatcher` services.
+    GET "/service/_/space/logs" => service::watcher::get_logs
+        :   auth(Service)
+            query(service::watcher::WatchLogsQuery)
+            res(Vec<space::SpaceLogEntry>),
 
     /// Get all ssh keys matching fingerprint. Returns error no one key matches.
     POST "/service/_/ssh-keys" => service::ssh::fetch_ssh_keys_by_fingerprint
-        :   body(service::ssh::FingerprintBody)
+        :   auth(Service)
+            body(service::ssh::FingerprintBody)
             res(Vec<service::ssh::SSHKeyResponse>),
 }
+
+#[cfg(test)]
+mod tests {
+    use super::ENDPOINTS;
+
+    #[test]
+    fn path_params_documented() {
+        for endpoint in ENDPOINTS {
+            let segments: Vec<_> = endpoint
+                .path
+                .split('/')
+                .filter_map(|v| v.trim_start_matches('@').strip_prefix(':'))
+                .collect();
+            let params: Vec<_> = endpoint
+                .params
+                .iter()
+                .flat_map(|v| v.fields)
+                .map(|v| v.name)
+                .collect();
+            assert_eq!(segments, params, "{} {}", endpoint.method, endpoint.path);
+        }
+    }
+}
@@ -1,36 +1,44 @@
 use archk::{
     v1::{
         api::{self, Response},
-        auth::{Token, TokenTy},
+        auth::{Token, TokenFormat, TokenTy},
+        models::MayIgnored,
         service::{ServiceAccount, ServiceAccountID, ServiceAccountTy},
         space::SpaceID,
+        validate,
     },
     Documentation,
 };
 use axum::{
-    extract::{Path, Query, State},
-    Json,
+    body::Bytes,
+    extract::{Query, State},
 };
 use serde::{Deserialize, Serialize};
 
-use crate::app::AppState;
+use crate::{
+    app::{self, AppState},
+    roles::perm,
+    tokens,
+};
 
 use super::{
-    extra::{AuthenticatedUser, DbService, DbUser},
+    extra::{cert_fingerprint, AuthenticatedUser, DbService, DbUser, Json, Path},
+    quota,
     space::SpacePath,
 };
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Documentation)]
 pub struct ServiceFetchOptions {
+    /// Page number starting from `0`, page contains up to 50 entries
     #[serde(default)]
     pub page: u32,
 
+    /// Show all services including services of spaces
     #[serde(default)]
     pub all: bool,
 }
 
-#[derive(Deserialize)]
-// FIXME: #[derive(Documentation)]
+#[derive(Deserialize, Documentation)]
 pub struct CreateServiceBody {
     /// Service type.
     pub ty: ServiceAccountTy,
@@ -38,10 +46,24 @@ pub struct CreateServiceBody {
     pub space_id: Option<SpaceID>,
     /// Service name
     pub name: String,
+    /// Service description, eg. where device is placed
+    #[serde(default)]
+    pub description: Option<String>,
+}
+
+#[derive(Deserialize, Documentation)]
+pub struct PatchServiceBody {
+    /// Service name
+    #[serde(default, skip_serializing_if = "MayIgnored::is_ignored")]
+    pub name: MayIgnored<String>,
+    /// Service description, set to `null` to remove
+    #[serde(default, skip_serializing_if = "MayIgnored::is_ignored")]
+    pub description: MayIgnored<Option<String>>,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Documentation)]
 pub struct ServiceAccountPath {
+    /// Service ID
     pub service_account_id: String,
 }
 
@@ -51,10 +73,52 @@ pub struct ServiceAccountResponse {
     pub id: String,
     /// Service name
     pub name: String,
+    /// Service description if any
+    pub description: Option<String>,
     /// Space ID service belongs to
     pub space_id: Option<String>,
     /// Service type
     pub ty: i64,
+    /// Timestamp in milliseconds of last heartbeat, if any
+    pub last_seen_at: Option<i64>,
+    /// Status reported with last heartbeat, if any
+    pub status: Option<String>,
+    /// SHA-256 fingerprint of bound client certificate, if any
+    pub cert_fingerprint: Option<String>,
+}
+
+/// Maximum length of status in [`HeartbeatBody`] in bytes
+const MAX_STATUS_LEN: usize = 1024;
+
+#[derive(Deserialize, Documentation)]
+pub struct HeartbeatBody {
+    /// Free-form status of service (eg. `"ok"` or JSON), replaces previous one.
+    /// Up to 1024 bytes
+    #[serde(default)]
+    pub status: Option<String>,
+}
+
+#[derive(Deserialize, Documentation)]
+pub struct ServiceTokenPath {
+    /// Service ID
+    pub service_account_id: String,
+    /// "Issued at" of token, see `iat` in token list
+    pub iat: i64,
+    /// Random part of token payload, 32 bits for `v1` tokens and 128 bits for `v2`
+    pub rnd: u128,
+}
+
+#[derive(Deserialize, Documentation)]
+pub struct PutTokenBody {
+    /// Human readable token label (eg. device name). Optional
+    #[serde(default)]
+    pub label: Option<String>,
+}
+
+#[derive(Deserialize, Documentation)]
+pub struct CertificateBody {
+    /// SHA-256 fingerprint of client certificate in hex, colons are allowed
+    pub fingerprint: String,
 }
 
 #[derive(Serialize, Documentation)]
@@ -63,6 +127,18 @@ pub struct ServiceTokenResponse {
     pub token: String,
 }
 
+#[derive(Serialize, Documentation)]
+pub struct ServiceTokenInfo {
+    /// "Issued at", timestamp in milliseconds
+    pub iat: i64,
+    /// Token label, if any
+    pub label: Option<String>,
+    /// Timestamp in milliseconds of last token usage, if any
+    pub last_used_at: Option<i64>,
+    /// IP address of last token usage, if known
+    pub last_used_ip: Option<String>,
+}
+
 pub async fn get_services(
     Query(ServiceFetchOptions { page, all }): Query<ServiceFetchOptions>,
     AuthenticatedUser {
@@ -71,11 +147,7 @@ pub async fn get_services(
     }: AuthenticatedUser<DbUser>,
     State(AppState { db, roles, .. }): State<AppState>,
 ) -> Response<Vec<ServiceAccountResponse>> {
-    if roles
-        .get_current(level)
-        .filter(|v| v.permissions.services_manage)
-        .is_none()
-    {
+    if !roles.load().has(level, perm::SERVICE_MANAGE) {
         return Response::Failture(api::Error::Forbidden.into());
     }
 
@@ -115,10 +187,8 @@ pub async fn get_space_services(
     }: AuthenticatedUser<DbUser>,
     State(AppState { db, roles, .. }): State<AppState>,
 ) -> Response<Vec<ServiceAccountResponse>> {
-    let is_admin = roles
-        .get_current(level)
-        .filter(|v| v.permissions.services_manage && v.permissions.spaces_manage)
-        .is_some();
+    let is_admin = roles.load().has(level, perm::SERVICE_MANAGE)
+        && roles.load().has(level, perm::SPACE_MANAGE);
 
     let (limit, offset) = (50, 50 * page as i64);
 
@@ -130,8 +200,12 @@ pub async fn get_space_services(
             SELECT
                 service_accounts.id,
                 service_accounts.name,
+                service_accounts.description,
                 service_accounts.ty,
-                service_accounts.space_id
+                service_accounts.space_id,
+                service_accounts.last_seen_at,
+                service_accounts.status,
+                service_accounts.cert_fingerprint
             FROM service_accounts
             WHERE service_accounts.space_id = ?
             LIMIT ? OFFSET ?",
@@ -148,8 +222,12 @@ pub async fn get_space_services(
             SELECT
                 service_accounts.id,
                 service_accounts.name,
+                service_accounts.description,
                 service_accounts.ty,
-                service_accounts.space_id
+                service_accounts.space_id,
+                service_accounts.last_seen_at,
+                service_accounts.status,
+                service_accounts.cert_fingerprint
             FROM service_accounts
                 INNER JOIN spaces ON
                     service_accounts.space_id = spaces.id
@@ -174,18 +252,43 @@ pub async fn create_service(
         },
         ..
     }: AuthenticatedUser<DbUser>,
-    State(AppState { db, roles, .. }): State<AppState>,
-    Json(CreateServiceBody { ty, space_id, name }): Json<CreateServiceBody>,
+    State(AppState {
+        db,
+        roles,
+        services,
+        ..
+    }): State<AppState>,
+    Json(CreateServiceBody {
+        ty,
+        space_id,
+        name,
+        description,
+    }): Json<CreateServiceBody>,
 ) -> Response<ServiceAccount> {
-    let perms = roles
-        .get_current(level)
-        .map(|v| &v.permissions)
-        .cloned()
-        .unwrap_or_default();
+    if let Err(e) = validate::title(&name) {
+        return Response::Failture(e.into());
+    }
+    if let Some(Err(e)) = description.as_deref().map(validate::description) {
+        return Response::Failture(e.into());
+    }
 
-    if !perms.services || (ty.is_admin() && !perms.services_manage) {
+    if !roles.load().has(level, perm::SERVICE_CREATE)
+        || (ty.is_admin() && !roles.load().has(level, perm::SERVICE_MANAGE))
+    {
         return Response::Failture(api::Error::Forbidden.into());
     }
+    let type_allowed = roles
+        .load()
+        .get_current(level)
+        .and_then(|v| v.service_types.as_ref())
+        .map(|v| v.allows(ty))
+        .unwrap_or(true);
+    if !type_allowed {
+        return Response::Failture(
+            api::Error::Forbidden
+                .detail(format!("role can't create services of type `{}`", ty.name()).into()),
+        );
+    }
 
     if space_id.is_none() && ty.is_space_required() {
         return Response::Failture(
@@ -195,8 +298,8 @@ pub async fn create_service(
     }
 
     if let Some(ref space_id) = space_id {
-        if !perms.spaces_manage {
-            let space_id: &str = &space_id;
+        if !roles.load().has(level, perm::SPACE_MANAGE) {
+            let space_id: &str = space_id;
             let res = sqlx::query!("SELECT owner_id FROM spaces WHERE id = ?", space_id)
                 .fetch_optional(&db)
                 .await
@@ -214,12 +317,38 @@ pub async fn create_service(
     let space_id_ref = space_id.as_deref();
     let ty_idx: i64 = ty.into();
 
+    // limit of instance and quota of space owner role, lowest one applies
+    let quota = match space_id_ref {
+        Some(space_id) => {
+            quota::space_quota(&db, &roles.load(), space_id)
+                .await
+                .max_services_per_space
+        }
+        None => None,
+    };
+    let by_quota = quota
+        .filter(|&v| services.max_services_per_space == 0 || v < services.max_services_per_space);
+    let limit = match by_quota {
+        Some(v) => v as i64,
+        None => services.max_services_per_space as i64,
+    };
+    // zero quota forbids services, but zero `limit` is unlimited
+    if by_quota == Some(0) {
+        return Response::Failture(quota::services_exceeded(0));
+    }
+
+    // limit is checked by insert itself, so concurrent requests can't exceed it
     let res = sqlx::query!(
-        "INSERT INTO service_accounts(id, name, ty, space_id) VALUES (?, ?, ?, ?)",
+        r#"
+        INSERT INTO service_accounts(id, name, description, ty, space_id)
+        SELECT ?1, ?2, ?3, ?4, ?5
+        WHERE ?6 = 0 OR (SELECT COUNT(1) FROM service_accounts WHERE space_id = ?5) < ?6"#,
         id_str,
         name,
+        description,
         ty_idx,
-        space_id_ref
+        space_id_ref,
+        limit
     )
     .execute(&db)
     .await;
@@ -228,12 +357,25 @@ pub async fn create_service(
         Err(sqlx::Error::Database(e)) if e.is_foreign_key_violation() => {
             Response::Failture(api::Error::ObjectNotFound.into())
         }
-        Ok(_) => Response::Success(ServiceAccount { id, ty, space_id }),
+        Ok(v) if v.rows_affected() == 0 && by_quota.is_some() => {
+            Response::Failture(quota::services_exceeded(limit as u64))
+        }
+        Ok(v) if v.rows_affected() == 0 => Response::Failture(
+            api::Error::Conflict
+                .detail(format!("space already has {limit} services, delete unused ones").into()),
+        ),
+        Ok(_) => Response::Success(ServiceAccount {
+            id,
+            space_id,
+            ty,
+            name,
+            description,
+        }),
         Err(e) => panic!("database error: {e}"),
     }
 }
 
-pub async fn delete_service(
+pub async fn patch_service(
     Path(ServiceAccountPath { service_account_id }): Path<ServiceAccountPath>,
     AuthenticatedUser {
         user: DbUser {
@@ -242,12 +384,82 @@ pub async fn delete_service(
         ..
     }: AuthenticatedUser<DbUser>,
     State(AppState { db, roles, .. }): State<AppState>,
+    Json(PatchServiceBody { name, description }): Json<PatchServiceBody>,
 ) -> Response<u64> {
-    if roles
-        .get_current(level)
-        .filter(|v| v.permissions.services_manage)
-        .is_none()
-    {
+    if name.is_ignored() && description.is_ignored() {
+        return Response::Failture(
+            api::Error::MalformedData.detail("expected at least one subject to change".into()),
+        );
+    }
+    if let MayIgnored::Value(name) = &name {
+        if let Err(e) = validate::title(name) {
+            return Response::Failture(e.into());
+        }
+    }
+    if let MayIgnored::Value(Some(description)) = &description {
+        if let Err(e) = validate::description(description) {
+            return Response::Failture(e.into());
+        }
+    }
+
+    if !roles.load().has(level, perm::SERVICE_MANAGE) {
+        let res = sqlx::query!(
+            "
+            SELECT spaces.owner_id
+            FROM service_accounts
+                INNER JOIN spaces ON spaces.id = service_accounts.space_id
+            WHERE service_accounts.id = ?",
+            service_account_id
+        )
+        .fetch_optional(&db)
+        .await
+        .expect("database")
+        .filter(|v| v.owner_id == user_id);
+
+        if res.is_none() {
+            return Response::Failture(api::Error::ObjectNotFound.into());
+        }
+    }
+
+    let set_description = !description.is_ignored();
+    let name = name.ok();
+    let description = description.ok().flatten();
+    let res = sqlx::query!(
+        r#"
+        UPDATE service_accounts SET
+            name = COALESCE(?1, name),
+            description = CASE WHEN ?2 THEN ?3 ELSE description END
+        WHERE id = ?4"#,
+        name,
+        set_description,
+        description,
+        service_account_id
+    )
+    .execute(&db)
+    .await
+    .expect("database")
+    .rows_affected();
+
+    if res == 0 {
+        Response::Failture(api::Error::ObjectNotFound.into())
+    } else {
+        Response::Success(res)
+    }
+}
+
+pub async fn delete_service(
+    Path(ServiceAccountPath { service_account_id }): Path<ServiceAccountPath>,
+    AuthenticatedUser {
+        user: DbUser {
+            id: user_id, level, ..
+        },
+        ..
+    }: AuthenticatedUser<DbUser>,
+    State(AppState {
+        db, roles, cache, ..
+    }): State<AppState>,
+) -> Response<u64> {
+    if !roles.load().has(level, perm::SERVICE_MANAGE) {
         let res = sqlx::query!(
             "
             SELECT spaces.owner_id
@@ -274,6 +486,7 @@ pub async fn delete_service(
     .await
     .expect("database")
     .rows_affected();
+    cache.revoke_service(&service_account_id);
 
     if res == 0 {
         Response::Failture(api::Error::ObjectNotFound.into())
@@ -291,41 +504,43 @@ pub async fn get_tokens(
         ..
     }: AuthenticatedUser<DbUser>,
     State(AppState { db, roles, .. }): State<AppState>,
-) -> Response<i32> {
-    let (permission_services, permission_services_manage) = roles
-        .get_current(level)
-        .map(|v| (v.permissions.services, v.permissions.services_manage))
-        .unwrap_or_default();
+) -> Response<Vec<ServiceTokenInfo>> {
+    let permission_services = roles.load().has(level, perm::SERVICE_CREATE);
+    let permission_services_manage = roles.load().has(level, perm::SERVICE_MANAGE);
 
     if !permission_services {
         return Response::Failture(api::Error::Forbidden.into());
     }
 
-    let (is_none, count, owner_id) = sqlx::query!(
-        "
-        SELECT
-            (service_accounts.id IS NULL) AS is_none,
-            COUNT(service_tokens.service_id) as count,
-            spaces.owner_id AS owner_id
+    let res = sqlx::query!(
+        "SELECT spaces.owner_id
         FROM service_accounts
-            INNER JOIN service_tokens
-                ON service_tokens.service_id = service_accounts.id
-            LEFT JOIN spaces
-                ON spaces.id = service_accounts.space_id
-        WHERE service_accounts.id = ?
-        ",
+            LEFT JOIN spaces ON spaces.id = service_accounts.space_id
+        WHERE service_accounts.id = ?",
         service_account_id
     )
-    .fetch_one(&db)
+    .fetch_optional(&db)
     .await
-    .map(|v| (v.is_none == 1, v.count, v.owner_id))
-    .expect("database");
+    .expect("database")
+    .filter(|v| permission_services_manage || v.owner_id == Some(user_id));
 
-    if is_none || (owner_id != Some(user_id) && !permission_services_manage) {
+    if res.is_none() {
         return Response::Failture(api::Error::ObjectNotFound.into());
     }
 
-    Response::Success(count)
+    let res = sqlx::query_as!(
+        ServiceTokenInfo,
+        "SELECT iat, label, last_used_at, last_used_ip
+        FROM service_tokens
+        WHERE service_id = ?
+        ORDER BY iat",
+        service_account_id
+    )
+    .fetch_all(&db)
+    .await
+    .expect("database");
+
+    Response::Success(res)
 }
 
 pub async fn put_token(
@@ -336,12 +551,17 @@ pub async fn put_token(
         },
         ..
     }: AuthenticatedUser<DbUser>,
-    State(AppState { db, roles, .. }): State<AppState>,
+    State(AppState {
+        db,
+        roles,
+        services,
+        token_format,
+        ..
+    }): State<AppState>,
+    body: Option<Json<PutTokenBody>>,
 ) -> Response<ServiceTokenResponse> {
-    let services_manage = roles
-        .get_current(level)
-        .filter(|v| v.permissions.services_manage)
-        .is_some();
+    let label = body.and_then(|Json(v)| v.label);
+    let services_manage = roles.load().has(level, perm::SERVICE_MANAGE);
 
     let res = sqlx::query!(
         "SELECT spaces.owner_id
@@ -360,20 +580,31 @@ pub async fn put_token(
         return Response::Failture(api::Error::ObjectNotFound.into());
     }
 
-    let token = Token::new(TokenTy::Service);
+    let token = Token::with_format(TokenTy::Service, token_format);
     let iat = token.iat as i64;
-    let rnd = token.rnd as i64;
+    let hash = tokens::hash(&token);
+
+    let limit = services.max_tokens_per_service as i64;
 
     let res = sqlx::query!(
-        "INSERT INTO service_tokens(iat, rnd, service_id) VALUES (?, ?, ?)",
+        r#"
+        INSERT INTO service_tokens(iat, hash, service_id, label)
+        SELECT ?1, ?2, ?3, ?4
+        WHERE ?5 = 0 OR (SELECT COUNT(1) FROM service_tokens WHERE service_id = ?3) < ?5"#,
         iat,
-        rnd,
-        service_account_id
+        hash,
+        service_account_id,
+        label,
+        limit
     )
     .execute(&db)
     .await;
 
     match res {
+        Ok(v) if v.rows_affected() == 0 => Response::Failture(
+            api::Error::Conflict
+                .detail(format!("service already has {limit} tokens, revoke unused ones").into()),
+        ),
         Ok(_) => Response::Success(ServiceTokenResponse {
             token: token.to_string(),
         }),
@@ -396,13 +627,11 @@ pub async fn revoke_all_tokens(
         },
         ..
     }: AuthenticatedUser<DbUser>,
-    State(AppState { db, roles, .. }): State<AppState>,
+    State(AppState {
+        db, roles, cache, ..
+    }): State<AppState>,
 ) -> Response<u64> {
-    if roles
-        .get_current(level)
-        .filter(|v| v.permissions.services_manage)
-        .is_none()
-    {
+    if !roles.load().has(level, perm::SERVICE_MANAGE) {
         let res = sqlx::query!(
             "
             SELECT spaces.owner_id
@@ -428,10 +657,226 @@ pub async fn revoke_all_tokens(
     .execute(&db)
     .await
     .expect("database");
+    cache.revoke_service(&service_account_id);
 
     Response::Success(res.rows_affected())
 }
 
+pub async fn revoke_token(
+    Path(ServiceTokenPath {
+        service_account_id,
+        iat,
+        rnd,
+    }): Path<ServiceTokenPath>,
+    AuthenticatedUser {
+        user: DbUser {
+            id: user_id, level, ..
+        },
+        ..
+    }: AuthenticatedUser<DbUser>,
+    State(AppState {
+        db, roles, cache, ..
+    }): State<AppState>,
+) -> Response<u64> {
+    if !roles.load().has(level, perm::SERVICE_MANAGE) {
+        let res = sqlx::query!(
+            "
+            SELECT spaces.owner_id
+            FROM service_accounts
+                INNER JOIN spaces ON spaces.id = service_accounts.space_id
+            WHERE service_accounts.id = ?",
+            service_account_id
+        )
+        .fetch_optional(&db)
+        .await
+        .expect("database")
+        .filter(|v| v.owner_id == user_id);
+
+        if res.is_none() {
+            return Response::Failture(api::Error::ObjectNotFound.into());
+        }
+    }
+
+    // token is known to caller, so its hash can be restored. Format is unknown, but
+    // `rnd` of `v1` tokens is 32 bits
+    let hash = |format| {
+        tokens::hash(&Token {
+            ty: TokenTy::Service,
+            format,
+            iat: iat as u64,
+            rnd,
+        })
+    };
+    let rnd_v1 = u32::try_from(rnd).ok().map(i64::from);
+    let hash_v1 = rnd_v1.map(|_| hash(TokenFormat::V1));
+    let hash_v2 = hash(TokenFormat::V2);
+    let res = sqlx::query!(
+        "DELETE FROM service_tokens
+        WHERE service_id = ? AND (hash IN (?, ?) OR (iat = ? AND rnd = ?))",
+        service_account_id,
+        hash_v1,
+        hash_v2,
+        iat,
+        rnd_v1
+    )
+    .execute(&db)
+    .await
+    .expect("database")
+    .rows_affected();
+    cache.revoke_service(&service_account_id);
+
+    if res == 0 {
+        Response::Failture(api::Error::ObjectNotFound.into())
+    } else {
+        Response::Success(res)
+    }
+}
+
+pub async fn put_certificate(
+    Path(ServiceAccountPath { service_account_id }): Path<ServiceAccountPath>,
+    AuthenticatedUser {
+        user: DbUser {
+            id: user_id, level, ..
+        },
+        ..
+    }: AuthenticatedUser<DbUser>,
+    State(AppState { db, roles, .. }): State<AppState>,
+    Json(CertificateBody { fingerprint }): Json<CertificateBody>,
+) -> Response<u64> {
+    let Some(fingerprint) = cert_fingerprint(&fingerprint) else {
+        return Response::Failture(
+            api::Error::MalformedData.detail("expected SHA-256 fingerprint in hex".into()),
+        );
+    };
+
+    if !roles.load().has(level, perm::SERVICE_MANAGE) {
+        let res = sqlx::query!(
+            "
+            SELECT spaces.owner_id
+            FROM service_accounts
+                INNER JOIN spaces ON spaces.id = service_accounts.space_id
+            WHERE service_accounts.id = ?",
+            service_account_id
+        )
+        .fetch_optional(&db)
+        .await
+        .expect("database")
+        .filter(|v| v.owner_id == user_id);
+
+        if res.is_none() {
+            return Response::Failture(api::Error::ObjectNotFound.into());
+        }
+    }
+
+    let res = sqlx::query!(
+        "UPDATE service_accounts SET cert_fingerprint = ? WHERE id = ?",
+        fingerprint,
+        service_account_id
+    )
+    .execute(&db)
+    .await;
+
+    match res {
+        Ok(v) if v.rows_affected() == 0 => Response::Failture(api::Error::ObjectNotFound.into()),
+        Ok(v) => Response::Success(v.rows_affected()),
+        Err(sqlx::Error::Database(err)) if err.is_unique_violation() => Response::Failture(
+            api::Error::Conflict.detail("certificate is bound to another service".into()),
+        ),
+        Err(e) => panic!("database error: {e}"),
+    }
+}
+
+pub async fn delete_certificate(
+    Path(ServiceAccountPath { service_account_id }): Path<ServiceAccountPath>,
+    AuthenticatedUser {
+        user: DbUser {
+            id: user_id, level, ..
+        },
+        ..
+    }: AuthenticatedUser<DbUser>,
+    State(AppState { db, roles, .. }): State<AppState>,
+) -> Response<u64> {
+    if !roles.load().has(level, perm::SERVICE_MANAGE) {
+        let res = sqlx::query!(
+            "
+            SELECT spaces.owner_id
+            FROM service_accounts
+                INNER JOIN spaces ON spaces.id = service_accounts.space_id
+            WHERE service_accounts.id = ?",
+            service_account_id
+        )
+        .fetch_optional(&db)
+        .await
+        .expect("database")
+        .filter(|v| v.owner_id == user_id);
+
+        if res.is_none() {
+            return Response::Failture(api::Error::ObjectNotFound.into());
+        }
+    }
+
+    let res = sqlx::query!(
+        "
+        UPDATE service_accounts SET cert_fingerprint = NULL
+        WHERE id = ? AND cert_fingerprint IS NOT NULL",
+        service_account_id
+    )
+    .execute(&db)
+    .await
+    .expect("database")
+    .rows_affected();
+
+    if res == 0 {
+        Response::Failture(api::Error::ObjectNotFound.into())
+    } else {
+        Response::Success(res)
+    }
+}
+
+pub async fn heartbeat(
+    AuthenticatedUser {
+        user: DbService { id, .. },
+        ..
+    }: AuthenticatedUser<DbService>,
+    State(AppState { db, .. }): State<AppState>,
+    body: Bytes,
+) -> Response<i64> {
+    // body is optional, but invalid one should not silently clear status
+    let status = match body.is_empty() {
+        true => None,
+        false => match serde_json::from_slice::<HeartbeatBody>(&body) {
+            Ok(v) => v.status,
+            Err(e) => {
+                return Response::Failture(api::Error::MalformedData.detail(e.to_string().into()))
+            }
+        },
+    };
+    if status.as_ref().is_some_and(|v| v.len() > MAX_STATUS_LEN) {
+        return Response::Failture(
+            api::Error::MalformedData
+                .detail(format!("`status` should be at most {MAX_STATUS_LEN} bytes").into()),
+        );
+    }
+
+    let now = app::now_ms();
+    let id: &str = &id;
+    sqlx::query!(
+        "UPDATE service_accounts SET last_seen_at = ?, status = ? WHERE id = ?",
+        now,
+        status,
+        id
+    )
+    .execute(&db)
+    .await
+    .expect("database");
+
+    Response::Success(now)
+}
+
+pub mod actor;
+pub mod manager;
+pub mod watcher;
+
 pub mod ssh {
     use archk::v1::user::ssh::SSHKeyTy;
 
@@ -0,0 +1,328 @@
+//! Endpoints for [`ServiceAccountTy::SpaceActor`] services.
+
+use archk::{
+    v1::{
+        api::{self, Response},
+        service::ServiceAccountTy,
+        space::{
+            AccountAccess, SpaceID, SpaceItemID, SpaceItemTy, SpaceLog, SpaceLogAction,
+            SpaceLogStore, UnlockDecision, UnlockFacts, UnlockReason,
+        },
+    },
+    Documentation,
+};
+use axum::extract::State;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app::{self, AppState},
+    v1::{
+        access_window::outside_access_windows,
+        extra::{AuthenticatedUser, DbService, Json},
+        space::{
+            archived_conflict, fetch_policy, insert_log, return_item, take_item, DbLogStore,
+            SpaceLogEntry,
+        },
+    },
+};
+
+/// Event submitted by actor, eg. `{ "unlock": { "pl_id": "..." } }`.
+#[derive(Deserialize, Documentation)]
+#[serde(rename_all = "snake_case")]
+pub enum ActorEvent {
+    /// Someone asks to unlock space
+    Unlock {
+        /// Platform ID of account
+        pl_id: String,
+    },
+    /// Someone files report (eg. broken item)
+    Report {
+        /// Platform ID of account filed report if any
+        #[serde(default)]
+        pl_id: Option<String>,
+        /// Item ID if report related to item
+        #[serde(default)]
+        item_id: Option<String>,
+        /// Report text
+        detail: String,
+    },
+    /// Account takes item
+    Take {
+        /// Platform ID of account
+        pl_id: String,
+        /// Item ID
+        item_id: String,
+        /// Timestamp in milliseconds when item should be returned, if any
+        #[serde(default)]
+        due_at: Option<i64>,
+    },
+    /// Item returned
+    Return {
+        /// Item ID
+        item_id: String,
+    },
+}
+
+#[derive(Serialize, Documentation)]
+pub struct UnlockResponse {
+    /// Decision: `allow`, `deny` or `pending` if request is left for `SpaceManager` service
+    pub decision: UnlockDecision,
+    /// Reason code of decision
+    pub reason: UnlockReason,
+    /// ID of log entry with decision, or of unlock request if decision is `pending`
+    pub log_id: String,
+}
+
+#[derive(Serialize, Documentation)]
+#[serde(rename_all = "snake_case")]
+pub enum ActorEventResponse {
+    /// Decision on unlock request
+    Unlock(UnlockResponse),
+    /// Filed report
+    Report(SpaceLogEntry),
+    /// Log entry of taken item
+    Take(SpaceLogEntry),
+    /// Log entry of returned item
+    Return(SpaceLogEntry),
+}
+
+/// Maximum number of events in [`submit_events_batch`]
+const MAX_BATCH_EVENTS: usize = 1000;
+
+/// Returns space of service if it is [`ServiceAccountTy::SpaceActor`].
+fn actor_space(service: DbService) -> Option<SpaceID> {
+    match service {
+        DbService {
+            ty: ServiceAccountTy::SpaceActor,
+            space_id,
+            ..
+        } => space_id,
+        _ => None,
+    }
+}
+
+/// Collect [`UnlockFacts`] of account in one query.
+pub(crate) async fn fetch_unlock_facts(
+    db: &sqlx::SqlitePool,
+    space_id: &str,
+    pl_id: &str,
+) -> Result<UnlockFacts, sqlx::Error> {
+    let keycard: i64 = SpaceItemTy::Keycard.into();
+    let filed: i64 = SpaceLogAction::ReportFiled.into();
+    let resolved: i64 = SpaceLogAction::ReportResolved.into();
+
+    let res = sqlx::query!(
+        r#"
+        SELECT
+            EXISTS (
+                SELECT 1 FROM spaces_accounts WHERE space_id = ?1 AND pl_id = ?2 AND active
+            ) AS "account_exists!: bool",
+            (
+                SELECT allow FROM spaces_accounts_access WHERE space_id = ?1 AND pl_id = ?2
+            ) AS "allow?: bool",
+            (
+                SELECT COUNT(1) FROM spaces_items
+                WHERE space_id = ?1 AND owner_id = ?2 AND ty = ?3
+            ) AS "keycards!: i64",
+            (
+                SELECT COUNT(1) FROM spaces_logs AS report
+                    INNER JOIN spaces_items AS item ON item.id = report.sp_item_id
+                WHERE report.space_id = ?1 AND report.act = ?4
+                    AND (item.owner_id = ?2 OR item.current_holder = ?2)
+                    AND NOT EXISTS (
+                        SELECT 1 FROM spaces_logs AS resolution
+                        WHERE resolution.ref_id = report.id AND resolution.act = ?5
+                    )
+            ) AS "open_reports!: i64"
+        "#,
+        space_id,
+        pl_id,
+        keycard,
+        filed,
+        resolved
+    )
+    .fetch_one(db)
+    .await?;
+    let now = app::now_ms();
+
+    Ok(UnlockFacts {
+        account_exists: res.account_exists,
+        access: res.allow.map(|allow| match allow {
+            true => AccountAccess::Allow,
+            false => AccountAccess::Deny,
+        }),
+        outside_access_windows: outside_access_windows(db, space_id, pl_id, now).await?,
+        keycards: res.keycards as u64,
+        open_reports: res.open_reports as u64,
+    })
+}
+
+/// Builds log entry of filed report, checking that item (if any) belongs to space.
+async fn report_log(
+    db: &sqlx::SqlitePool,
+    space_id: &SpaceID,
+    pl_id: Option<String>,
+    item_id: Option<String>,
+    detail: String,
+) -> Result<SpaceLog, api::ErrorData> {
+    let mut log = SpaceLog::new(space_id.clone(), SpaceLogAction::ReportFiled).with_detail(detail);
+
+    if let Some(item_id) = item_id {
+        let space_id: &str = space_id;
+        let res = sqlx::query!(
+            "SELECT id FROM spaces_items WHERE id = ? AND space_id = ?",
+            item_id,
+            space_id
+        )
+        .fetch_optional(db)
+        .await
+        .expect("database")
+        .and_then(|v| SpaceItemID::from(v.id));
+
+        let Some(item_id) = res else {
+            return Err(api::Error::ObjectNotFound.detail("item does not exists".into()));
+        };
+        log = log.with_item(item_id);
+    }
+    if let Some(pl_id) = pl_id {
+        log = log.with_account(pl_id);
+    }
+
+    Ok(log)
+}
+
+pub async fn submit_event(
+    AuthenticatedUser { user, .. }: AuthenticatedUser<DbService>,
+    State(AppState { db, .. }): State<AppState>,
+    Json(event): Json<ActorEvent>,
+) -> Response<ActorEventResponse> {
+    let archived = user.space_archived;
+    let Some(space_id) = actor_space(user) else {
+        return Response::Failture(api::Error::Forbidden.into());
+    };
+    if archived {
+        return Response::Failture(archived_conflict());
+    }
+
+    match event {
+        ActorEvent::Unlock { pl_id } => {
+            let facts = fetch_unlock_facts(&db, &space_id, &pl_id)
+                .await
+                .expect("database");
+            let policy = fetch_policy(&db, &space_id).await.expect("database");
+            let (decision, reason) = policy.decide(&facts);
+
+            let request = SpaceLog::new(space_id.clone(), SpaceLogAction::UnlockRequested)
+                .with_account(pl_id.clone());
+            let act = match decision {
+                UnlockDecision::Allow => SpaceLogAction::UnlockApproved,
+                UnlockDecision::Deny => SpaceLogAction::UnlockDenied,
+                // left for `SpaceManager` service, which decides request by its ID
+                UnlockDecision::Pending => {
+                    let request = request.with_detail(reason.code().into());
+                    insert_log(&db, &request).await.expect("database");
+                    return Response::Success(ActorEventResponse::Unlock(UnlockResponse {
+                        decision,
+                        reason,
+                        log_id: request.id,
+                    }));
+                }
+            };
+            let log = SpaceLog::new(space_id, act)
+                .with_account(pl_id)
+                .with_ref(request.id.clone())
+                .with_detail(reason.code().into());
+
+            let logs = [request, log];
+            let mut conn = db.acquire().await.expect("database");
+            DbLogStore(&mut conn)
+                .insert_many(&logs)
+                .await
+                .expect("database");
+            let [_, log] = logs;
+
+            Response::Success(ActorEventResponse::Unlock(UnlockResponse {
+                decision,
+                reason,
+                log_id: log.id,
+            }))
+        }
+        ActorEvent::Report {
+            pl_id,
+            item_id,
+            detail,
+        } => {
+            let log = match report_log(&db, &space_id, pl_id, item_id, detail).await {
+                Ok(log) => log,
+                Err(e) => return Response::Failture(e),
+            };
+            insert_log(&db, &log).await.expect("database");
+
+            Response::Success(ActorEventResponse::Report(log.into()))
+        }
+        ActorEvent::Take {
+            pl_id,
+            item_id,
+            due_at,
+        } => match take_item(&db, &space_id, &item_id, &pl_id, due_at).await {
+            Ok(log) => Response::Success(ActorEventResponse::Take(log.into())),
+            Err(e) => Response::Failture(e),
+        },
+        ActorEvent::Return { item_id } => match return_item(&db, &space_id, &item_id).await {
+            Ok(log) => Response::Success(ActorEventResponse::Return(log.into())),
+            Err(e) => Response::Failture(e),
+        },
+    }
+}
+
+pub async fn submit_events_batch(
+    AuthenticatedUser { user, .. }: AuthenticatedUser<DbService>,
+    State(AppState { db, .. }): State<AppState>,
+    Json(events): Json<Vec<ActorEvent>>,
+) -> Response<Vec<SpaceLogEntry>> {
+    let archived = user.space_archived;
+    let Some(space_id) = actor_space(user) else {
+        return Response::Failture(api::Error::Forbidden.into());
+    };
+    if archived {
+        return Response::Failture(archived_conflict());
+    }
+    if events.len() > MAX_BATCH_EVENTS {
+        return Response::Failture(
+            api::Error::MalformedData.detail(
+                format!(
+                    "expected at most {MAX_BATCH_EVENTS} events, got {}",
+                    events.len()
+                )
+                .into(),
+            ),
+        );
+    }
+
+    let mut logs = Vec::with_capacity(events.len());
+    for (i, event) in events.into_iter().enumerate() {
+        let ActorEvent::Report {
+            pl_id,
+            item_id,
+            detail,
+        } = event
+        else {
+            return Response::Failture(
+                api::Error::MalformedData
+                    .detail(format!("event {i}: only report events can be batched").into()),
+            );
+        };
+        match report_log(&db, &space_id, pl_id, item_id, detail).await {
+            Ok(log) => logs.push(log),
+            Err(e) => return Response::Failture(e),
+        }
+    }
+
+    let mut conn = db.acquire().await.expect("database");
+    DbLogStore(&mut conn)
+        .insert_many(&logs)
+        .await
+        .expect("database");
+
+    Response::Success(logs.into_iter().map(Into::into).collect())
+}
@@ -0,0 +1,396 @@
+//! Endpoints for [`ServiceAccountTy::SpaceManager`] services.
+
+use archk::{
+    v1::{
+        api::{self, Response},
+        service::ServiceAccountTy,
+        space::{SpaceID, SpaceItemID, SpaceItemTy, SpaceLog, SpaceLogAction},
+    },
+    Documentation,
+};
+use axum::extract::{Query, State};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app::AppState,
+    v1::{
+        extra::{AuthenticatedUser, DbService, Json, Path},
+        space::{
+            archived_conflict, insert_log, insert_log_comment, CommentAuthor, LogActionCode,
+            LogCommentBody, Paging, SpaceLogComment, SpaceLogEntry,
+        },
+    },
+};
+
+#[derive(Serialize, Deserialize, Documentation)]
+pub struct ItemRegistrationBody {
+    /// Item title
+    pub title: String,
+    /// Item type
+    #[serde(default)]
+    pub ty: SpaceItemTy,
+    /// Serial ID of item given by platform
+    pub pl_serial: String,
+    /// Platform ID of owner account if any
+    #[serde(default)]
+    pub owner_id: Option<String>,
+}
+
+#[derive(Deserialize, Documentation)]
+pub struct UnlockDecisionBody {
+    /// Approve (`true`) or deny (`false`) unlock request
+    pub approve: bool,
+    /// Reason of decision if any
+    #[serde(default)]
+    pub reason: Option<String>,
+}
+
+#[derive(Deserialize, Documentation)]
+pub struct ResolveReportBody {
+    /// Resolution comment if any
+    #[serde(default)]
+    pub comment: Option<String>,
+}
+
+#[derive(Deserialize, Documentation)]
+pub struct ReportsQuery {
+    /// Page number starting from `0`, page contains up to 50 entries
+    #[serde(default)]
+    pub page: u32,
+    /// Show only unresolved reports
+    #[serde(default)]
+    pub open: bool,
+}
+
+#[derive(Deserialize, Documentation)]
+pub struct LogPath {
+    /// ID of log entry
+    pub log_id: String,
+}
+
+#[derive(Serialize, Documentation)]
+pub struct ReportResponse {
+    /// Report log entry
+    pub report: SpaceLogEntry,
+    /// Is report resolved?
+    pub resolved: bool,
+}
+
+/// Returns space of service if it is [`ServiceAccountTy::SpaceManager`].
+fn manager_space(service: DbService) -> Option<SpaceID> {
+    match service {
+        DbService {
+            ty: ServiceAccountTy::SpaceManager,
+            space_id,
+            ..
+        } => space_id,
+        _ => None,
+    }
+}
+
+pub async fn request_item_registration(
+    AuthenticatedUser { user, .. }: AuthenticatedUser<DbService>,
+    State(AppState { db, .. }): State<AppState>,
+    Json(body): Json<ItemRegistrationBody>,
+) -> Response<SpaceLogEntry> {
+    let archived = user.space_archived;
+    let Some(space_id) = manager_space(user) else {
+        return Response::Failture(api::Error::Forbidden.into());
+    };
+    if archived {
+        return Response::Failture(archived_conflict());
+    }
+
+    let mut log = SpaceLog::new(space_id, SpaceLogAction::ItemRegistrationRequested)
+        .with_detail(serde_json::to_string(&body).expect("json"));
+    if let Some(owner_id) = body.owner_id {
+        log = log.with_account(owner_id);
+    }
+
+    insert_log(&db, &log).await.expect("database");
+
+    Response::Success(log.into())
+}
+
+pub async fn get_unlock_requests(
+    Query(Paging { page }): Query<Paging>,
+    AuthenticatedUser { user, .. }: AuthenticatedUser<DbService>,
+    State(AppState { db, .. }): State<AppState>,
+) -> Response<Vec<SpaceLogEntry>> {
+    let Some(space_id) = manager_space(user) else {
+        return Response::Failture(api::Error::Forbidden.into());
+    };
+
+    Response::Success(unlock_requests(&db, &space_id, page).await)
+}
+
+/// Undecided unlock requests of space, up to 50 entries per page.
+pub(crate) async fn unlock_requests(
+    db: &sqlx::SqlitePool,
+    space_id: &str,
+    page: u32,
+) -> Vec<SpaceLogEntry> {
+    let requested: i64 = SpaceLogAction::UnlockRequested.into();
+    let approved: i64 = SpaceLogAction::UnlockApproved.into();
+    let denied: i64 = SpaceLogAction::UnlockDenied.into();
+    let limit = 50;
+    let offset = (page as i64) * limit;
+
+    sqlx::query_as!(
+        SpaceLogEntry,
+        r#"
+        SELECT id, created_at, act AS "act: LogActionCode", sp_acc_id, sp_item_id, ref_id, detail
+        FROM spaces_logs
+        WHERE space_id = ? AND act = ? AND NOT EXISTS (
+            SELECT 1 FROM spaces_logs AS decision
+            WHERE decision.ref_id = spaces_logs.id AND decision.act IN (?, ?)
+        )
+        ORDER BY created_at
+        LIMIT ? OFFSET ?
+        "#,
+        space_id,
+        requested,
+        approved,
+        denied,
+        limit,
+        offset
+    )
+    .fetch_all(db)
+    .await
+    .expect("database")
+}
+
+pub async fn decide_unlock_request(
+    Path(LogPath { log_id }): Path<LogPath>,
+    AuthenticatedUser { user, .. }: AuthenticatedUser<DbService>,
+    State(AppState { db, .. }): State<AppState>,
+    Json(body): Json<UnlockDecisionBody>,
+) -> Response<SpaceLogEntry> {
+    let archived = user.space_archived;
+    let Some(space_id) = manager_space(user) else {
+        return Response::Failture(api::Error::Forbidden.into());
+    };
+    if archived {
+        return Response::Failture(archived_conflict());
+    }
+
+    decide_unlock(&db, space_id, log_id, body).await
+}
+
+/// Record approval or denial of undecided unlock request `log_id`.
+pub(crate) async fn decide_unlock(
+    db: &sqlx::SqlitePool,
+    space_id: SpaceID,
+    log_id: String,
+    UnlockDecisionBody { approve, reason }: UnlockDecisionBody,
+) -> Response<SpaceLogEntry> {
+    let space_id_str: &str = &space_id;
+    let requested: i64 = SpaceLogAction::UnlockRequested.into();
+    let approved: i64 = SpaceLogAction::UnlockApproved.into();
+    let denied: i64 = SpaceLogAction::UnlockDenied.into();
+
+    let res = sqlx::query!(
+        r#"
+        SELECT
+            spaces_logs.sp_acc_id,
+            (SELECT COUNT(1) FROM spaces_logs AS decision
+                WHERE decision.ref_id = spaces_logs.id AND decision.act IN (?, ?)) AS "decisions!: i64"
+        FROM spaces_logs
+        WHERE id = ? AND space_id = ? AND act = ?
+        "#,
+        approved,
+        denied,
+        log_id,
+        space_id_str,
+        requested
+    )
+    .fetch_optional(db)
+    .await
+    .expect("database");
+
+    let Some(request) = res else {
+        return Response::Failture(api::Error::ObjectNotFound.into());
+    };
+    if request.decisions != 0 {
+        return Response::Failture(
+            api::Error::Conflict.detail("unlock request already decided".into()),
+        );
+    }
+
+    let act = if approve {
+        SpaceLogAction::UnlockApproved
+    } else {
+        SpaceLogAction::UnlockDenied
+    };
+    let mut log = SpaceLog::new(space_id, act).with_ref(log_id);
+    if let Some(sp_acc_id) = request.sp_acc_id {
+        log = log.with_account(sp_acc_id);
+    }
+    if let Some(reason) = reason {
+        log = log.with_detail(reason);
+    }
+
+    // concurrent decisions of same request are rejected by unique index
+    match insert_log(db, &log).await {
+        Ok(()) => Response::Success(log.into()),
+        Err(sqlx::Error::Database(err)) if err.is_unique_violation() => {
+            Response::Failture(api::Error::Conflict.detail("unlock request already decided".into()))
+        }
+        Err(e) => panic!("database error: {e}"),
+    }
+}
+
+pub async fn get_reports(
+    Query(ReportsQuery { page, open }): Query<ReportsQuery>,
+    AuthenticatedUser { user, .. }: AuthenticatedUser<DbService>,
+    State(AppState { db, .. }): State<AppState>,
+) -> Response<Vec<ReportResponse>> {
+    let Some(space_id) = manager_space(user) else {
+        return Response::Failture(api::Error::Forbidden.into());
+    };
+
+    let space_id: &str = &space_id;
+    let filed: i64 = SpaceLogAction::ReportFiled.into();
+    let resolved: i64 = SpaceLogAction::ReportResolved.into();
+    let limit = 50;
+    let offset = (page as i64) * limit;
+
+    let res = sqlx::query!(
+        r#"
+        SELECT
+            id, created_at, act, sp_acc_id, sp_item_id, ref_id, detail,
+            EXISTS (
+                SELECT 1 FROM spaces_logs AS resolution
+                WHERE resolution.ref_id = spaces_logs.id AND resolution.act = ?
+            ) AS "resolved!: bool"
+        FROM spaces_logs
+        WHERE space_id = ? AND act = ? AND NOT (? AND EXISTS (
+            SELECT 1 FROM spaces_logs AS resolution
+            WHERE resolution.ref_id = spaces_logs.id AND resolution.act = ?
+        ))
+        ORDER BY created_at
+        LIMIT ? OFFSET ?
+        "#,
+        resolved,
+        space_id,
+        filed,
+        open,
+        resolved,
+        limit,
+        offset
+    )
+    .fetch_all(&db)
+    .await
+    .expect("database");
+
+    Response::Success(
+        res.into_iter()
+            .map(|v| ReportResponse {
+                report: SpaceLogEntry {
+                    id: v.id,
+                    created_at: v.created_at,
+                    act: v.act.into(),
+                    sp_acc_id: v.sp_acc_id,
+                    sp_item_id: v.sp_item_id,
+                    ref_id: v.ref_id,
+                    detail: v.detail,
+                },
+                resolved: v.resolved,
+            })
+            .collect(),
+    )
+}
+
+pub async fn resolve_report(
+    Path(LogPath { log_id }): Path<LogPath>,
+    AuthenticatedUser { user, .. }: AuthenticatedUser<DbService>,
+    State(AppState { db, .. }): State<AppState>,
+    Json(ResolveReportBody { comment }): Json<ResolveReportBody>,
+) -> Response<SpaceLogEntry> {
+    let archived = user.space_archived;
+    let Some(space_id) = manager_space(user) else {
+        return Response::Failture(api::Error::Forbidden.into());
+    };
+    if archived {
+        return Response::Failture(archived_conflict());
+    }
+
+    let space_id_str: &str = &space_id;
+    let filed: i64 = SpaceLogAction::ReportFiled.into();
+    let resolved: i64 = SpaceLogAction::ReportResolved.into();
+
+    let res = sqlx::query!(
+        r#"
+        SELECT
+            spaces_logs.sp_acc_id,
+            spaces_logs.sp_item_id,
+            (SELECT COUNT(1) FROM spaces_logs AS resolution
+                WHERE resolution.ref_id = spaces_logs.id AND resolution.act = ?) AS "resolutions!: i64"
+        FROM spaces_logs
+        WHERE id = ? AND space_id = ? AND act = ?
+        "#,
+        resolved,
+        log_id,
+        space_id_str,
+        filed
+    )
+    .fetch_optional(&db)
+    .await
+    .expect("database");
+
+    let Some(report) = res else {
+        return Response::Failture(api::Error::ObjectNotFound.into());
+    };
+    if report.resolutions != 0 {
+        return Response::Failture(api::Error::Conflict.detail("report already resolved".into()));
+    }
+
+    let mut log = SpaceLog::new(space_id, SpaceLogAction::ReportResolved).with_ref(log_id);
+    if let Some(sp_acc_id) = report.sp_acc_id {
+        log = log.with_account(sp_acc_id);
+    }
+    if let Some(sp_item_id) = report.sp_item_id.and_then(SpaceItemID::from) {
+        log = log.with_item(sp_item_id);
+    }
+    if let Some(comment) = comment {
+        log = log.with_detail(comment);
+    }
+
+    // concurrent resolutions of same report are rejected by unique index
+    match insert_log(&db, &log).await {
+        Ok(()) => Response::Success(log.into()),
+        Err(sqlx::Error::Database(err)) if err.is_unique_violation() => {
+            Response::Failture(api::Error::Conflict.detail("report already resolved".into()))
+        }
+        Err(e) => panic!("database error: {e}"),
+    }
+}
+
+pub async fn add_log_comment(
+    Path(LogPath { log_id }): Path<LogPath>,
+    AuthenticatedUser { user, .. }: AuthenticatedUser<DbService>,
+    State(AppState { db, .. }): State<AppState>,
+    Json(LogCommentBody { text }): Json<LogCommentBody>,
+) -> Response<SpaceLogComment> {
+    let archived = user.space_archived;
+    let service_id = user.id.clone();
+    let Some(space_id) = manager_space(user) else {
+        return Response::Failture(api::Error::Forbidden.into());
+    };
+    if archived {
+        return Response::Failture(archived_conflict());
+    }
+
+    match insert_log_comment(
+        &db,
+        &space_id,
+        log_id,
+        CommentAuthor::Service(&service_id),
+        text,
+    )
+    .await
+    {
+        Ok(v) => Response::Success(v),
+        Err(e) => Response::Failture(e),
+    }
+}
@@ -0,0 +1,73 @@
+//! Endpoints for [`ServiceAccountTy::SpaceEventWatcher`] services.
+
+use archk::{
+    v1::{
+        api::{self, Response},
+        service::ServiceAccountTy,
+        space::SpaceID,
+    },
+    Documentation,
+};
+use axum::extract::Query;
+use serde::Deserialize;
+
+use crate::v1::{
+    extra::{AuthenticatedUser, DbService, ReadDb},
+    space::{LogActionCode, SpaceLogEntry},
+};
+
+#[derive(Deserialize, Documentation)]
+pub struct WatchLogsQuery {
+    /// Timestamp in milliseconds, show only entries created after it.
+    /// Pass `created_at` of last received entry to get next ones
+    #[serde(default)]
+    pub after: i64,
+    /// ID of last received entry, also show entries created at `after` with greater ID.
+    /// Entries may share timestamp, so pass it with `after` to not skip them
+    #[serde(default)]
+    pub after_id: Option<String>,
+}
+
+/// Returns space of service if it is [`ServiceAccountTy::SpaceEventWatcher`].
+fn watcher_space(service: DbService) -> Option<SpaceID> {
+    match service {
+        DbService {
+            ty: ServiceAccountTy::SpaceEventWatcher,
+            space_id,
+            ..
+        } => space_id,
+        _ => None,
+    }
+}
+
+pub async fn get_logs(
+    Query(WatchLogsQuery { after, after_id }): Query<WatchLogsQuery>,
+    AuthenticatedUser { user, .. }: AuthenticatedUser<DbService>,
+    ReadDb(db): ReadDb,
+) -> Response<Vec<SpaceLogEntry>> {
+    let Some(space_id) = watcher_space(user) else {
+        return Response::Failture(api::Error::Forbidden.into());
+    };
+
+    let space_id: &str = &space_id;
+    let limit = 50;
+    let res = sqlx::query_as!(
+        SpaceLogEntry,
+        r#"
+        SELECT id, created_at, act AS "act: LogActionCode", sp_acc_id, sp_item_id, ref_id, detail
+        FROM spaces_logs
+        WHERE space_id = ? AND (created_at > ? OR (created_at = ? AND id > ?))
+        ORDER BY created_at, id
+        LIMIT ?"#,
+        space_id,
+        after,
+        after,
+        after_id,
+        limit
+    )
+    .fetch_all(&db)
+    .await
+    .expect("database");
+
+    Response::Success(res)
+}
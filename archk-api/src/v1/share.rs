@@ -0,0 +1,218 @@
+//! Read-only links to items for outside parties. Link token is
+//! `<item_id>.<expires_at>.<signature>`, signed with HMAC-SHA256 by server key stored
+//! in database, so links are not stored and can't be revoked before expiration
+//! except by deleting item.
+
+use archk::{
+    v1::api::{self, Response},
+    Documentation,
+};
+use axum::extract::State;
+use base64::{prelude::BASE64_URL_SAFE_NO_PAD, Engine};
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use sqlx::SqlitePool;
+
+use crate::app::{self, AppState};
+
+use super::{
+    extra::{Json, Path, SpaceAccess},
+    space::SpaceItemPath,
+};
+
+/// Maximum lifetime of link in seconds
+const MAX_SHARE_SECS: i64 = 30 * 24 * 60 * 60;
+
+#[derive(Deserialize, Documentation)]
+pub struct ShareItemBody {
+    /// Lifetime of link in seconds, up to 30 days. Default is one day
+    #[serde(default = "default_share_secs")]
+    #[doc_min = 1]
+    #[doc_max = 2592000]
+    pub duration_secs: i64,
+}
+
+fn default_share_secs() -> i64 {
+    24 * 60 * 60
+}
+
+#[derive(Deserialize, Documentation)]
+pub struct SharedTokenPath {
+    /// Token of link
+    pub token: String,
+}
+
+#[derive(Serialize, Documentation)]
+pub struct ShareItemResponse {
+    /// Token of link
+    pub token: String,
+    /// Path of link relative to API root, eg. `/api/v1/shared/<token>`
+    pub path: String,
+    /// Timestamp in milliseconds of link expiration
+    pub expires_at: i64,
+}
+
+#[derive(Serialize, Documentation)]
+pub struct SharedItem {
+    /// Item title
+    pub title: String,
+    /// Item type, see `archk::v1::space::SpaceItemTy`
+    pub ty: i64,
+    /// Serial ID of item given by platform
+    pub pl_serial: String,
+    /// Space title
+    pub space_title: String,
+    /// Is item taken by someone
+    pub taken: bool,
+    /// Timestamp in milliseconds when taken item should be returned, if any
+    pub due_at: Option<i64>,
+    /// Timestamp in milliseconds of link expiration
+    pub expires_at: i64,
+}
+
+/// Server key `name` (eg. `share` for share links), generated on first use.
+pub(crate) async fn server_key(db: &SqlitePool, name: &str) -> Vec<u8> {
+    let key = sqlx::query_scalar!("SELECT value FROM server_secrets WHERE name = ?", name)
+        .fetch_optional(db)
+        .await
+        .expect("database");
+    if let Some(key) = key {
+        return key;
+    }
+
+    let mut key = vec![0; 32];
+    rand::thread_rng().fill_bytes(&mut key);
+    // other request may generate key first, then its key is used
+    sqlx::query_scalar!(
+        "INSERT INTO server_secrets(name, value) VALUES (?, ?)
+        ON CONFLICT(name) DO UPDATE SET value = value RETURNING value",
+        name,
+        key
+    )
+    .fetch_one(db)
+    .await
+    .expect("database")
+}
+
+fn mac(key: &[u8], item_id: &str, expires_at: i64) -> Hmac<Sha256> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("hmac accepts any key");
+    mac.update(format!("share:{item_id}:{expires_at}").as_bytes());
+    mac
+}
+
+/// Item ID and expiration of valid token.
+fn verify(key: &[u8], token: &str) -> Option<(String, i64)> {
+    let mut parts = token.splitn(3, '.');
+    let item_id = parts.next()?;
+    let expires_at: i64 = parts.next()?.parse().ok()?;
+    let signature = BASE64_URL_SAFE_NO_PAD.decode(parts.next()?).ok()?;
+    mac(key, item_id, expires_at)
+        .verify_slice(&signature)
+        .ok()?;
+    Some((item_id.to_owned(), expires_at))
+}
+
+pub async fn share_item(
+    Path(SpaceItemPath { item_id, .. }): Path<SpaceItemPath>,
+    SpaceAccess { space_id, .. }: SpaceAccess,
+    State(AppState { db, .. }): State<AppState>,
+    Json(ShareItemBody { duration_secs }): Json<ShareItemBody>,
+) -> Response<ShareItemResponse> {
+    let item_id: &str = &item_id;
+    if !(1..=MAX_SHARE_SECS).contains(&duration_secs) {
+        return Response::Failture(
+            api::Error::MalformedData
+                .detail("`duration_secs` should be between 1 and 2592000".into()),
+        );
+    }
+    let space_id: &str = &space_id;
+    let res = sqlx::query!(
+        "SELECT id FROM spaces_items WHERE id = ? AND space_id = ?",
+        item_id,
+        space_id
+    )
+    .fetch_optional(&db)
+    .await
+    .expect("database");
+    if res.is_none() {
+        return Response::Failture(api::Error::ObjectNotFound.into());
+    }
+
+    let expires_at = app::now_ms() + duration_secs * 1000;
+    let key = server_key(&db, "share").await;
+    let signature = mac(&key, item_id, expires_at).finalize().into_bytes();
+    let token = format!(
+        "{item_id}.{expires_at}.{}",
+        BASE64_URL_SAFE_NO_PAD.encode(signature)
+    );
+
+    Response::Success(ShareItemResponse {
+        path: format!("/api/v1/shared/{token}"),
+        token,
+        expires_at,
+    })
+}
+
+pub async fn get_shared_item(
+    Path(SharedTokenPath { token }): Path<SharedTokenPath>,
+    State(AppState { db, .. }): State<AppState>,
+) -> Response<SharedItem> {
+    let key = server_key(&db, "share").await;
+    let Some((item_id, expires_at)) = verify(&key, &token) else {
+        return Response::Failture(api::Error::ObjectNotFound.into());
+    };
+    if expires_at <= app::now_ms() {
+        return Response::Failture(api::Error::Gone.detail("link is expired".into()));
+    }
+
+    let res = sqlx::query!(
+        r#"
+        SELECT
+            spaces_items.title,
+            spaces_items.ty,
+            spaces_items.pl_serial,
+            spaces_items.current_holder,
+            spaces_items.due_at,
+            spaces.title AS space_title
+        FROM spaces_items
+            INNER JOIN spaces ON spaces.id = spaces_items.space_id
+        WHERE spaces_items.id = ?"#,
+        item_id
+    )
+    .fetch_optional(&db)
+    .await
+    .expect("database");
+
+    match res {
+        Some(v) => Response::Success(SharedItem {
+            title: v.title,
+            ty: v.ty,
+            pl_serial: v.pl_serial,
+            space_title: v.space_title,
+            taken: v.current_holder.is_some(),
+            due_at: v.due_at,
+            expires_at,
+        }),
+        None => Response::Failture(api::Error::ObjectNotFound.into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn signed_tokens() {
+        let key = b"secret";
+        let signature = mac(key, "item", 1000).finalize().into_bytes();
+        let token = format!("item.1000.{}", BASE64_URL_SAFE_NO_PAD.encode(signature));
+
+        assert_eq!(verify(key, &token), Some(("item".into(), 1000)));
+        assert_eq!(verify(b"other", &token), None);
+        assert_eq!(verify(key, &token.replace(".1000.", ".2000.")), None);
+        assert_eq!(verify(key, &token.replace("item.", "meti.")), None);
+        assert_eq!(verify(key, "item.1000"), None);
+    }
+}
@@ -1,91 +1,851 @@
+use std::{
+    collections::{HashMap, HashSet},
+    convert::Infallible,
+    time::Duration,
+};
+
 use archk::v1::{
     api::{self, Response},
     models::MayIgnored,
-    space::{Space, SpaceAccount, SpaceID, SpaceItem, SpaceItemID, SpaceItemTy},
+    service::ServiceAccountTy,
+    space::{
+        AccountAccess, LogAction, Metadata, Space, SpaceAccount, SpaceID, SpaceItem, SpaceItemID,
+        SpaceItemTy, SpaceLog, SpaceLogAction, SpaceLogStore, SpaceTag, SpaceTagID, UnlockPolicy,
+        DEFAULT_ITEM_STATUS,
+    },
     user::{User, UserID},
+    validate::{self, Valid, Validate},
+};
+use archk::{
+    v1::docs::{self, DocumentationObject},
+    Documentation, Validate,
 };
 use axum::{
-    extract::{Path, Query, State},
-    Json,
+    body::Bytes,
+    extract::{Query, State},
+    http::{
+        header::{CONTENT_TYPE, IF_MATCH},
+        HeaderMap,
+    },
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        IntoResponse,
+    },
 };
 use serde::{Deserialize, Serialize};
+use tokio_stream::wrappers::ReceiverStream;
+
+use crate::{
+    app::{self, AppState},
+    cache::Cache,
+    qr::QrCode,
+    roles::perm,
+};
+
+use super::{
+    export::{self, ExportFormat, ExportRow, ListFormat, Listing},
+    extra::{
+        AuthenticatedUser, DbService, DbUser, DecideUnlocks, Json, ManageSpaceLogs, Path, ReadDb,
+        ReadSpaceLogs, SpaceAccess,
+    },
+    item_status, quota,
+    service::manager::{self, UnlockDecisionBody},
+};
 
-use crate::app::AppState;
+/// How long deletions of items and accounts are kept for [`sync_space`], older
+/// cursors get full state of space
+pub(crate) const SYNC_DELETIONS_TTL_MS: i64 = 1000 * 60 * 60 * 24 * 30;
+/// Maximum number of log entries returned by [`sync_space`]
+const SYNC_LOGS_LIMIT: i64 = 500;
 
-use super::extra::{AuthenticatedUser, DbUser};
+/// How often new log entries are checked by [`space_events`]
+const SSE_POLL_INTERVAL: Duration = Duration::from_secs(1);
+/// Maximum number of log entries sent per check by [`space_events`]
+const SSE_LOGS_LIMIT: i64 = 100;
+/// Maximum length of log comment in characters
+pub(crate) const MAX_LOG_COMMENT_LEN: usize = 1000;
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Documentation)]
 pub struct SpacePath {
+    /// Space ID
     pub space_id: SpaceID,
 }
-#[derive(Deserialize)]
+#[derive(Deserialize, Documentation)]
 pub struct SpaceAccountPath {
+    /// Space ID
+    #[allow(dead_code)] // read by `SpaceAccess`
     pub space_id: SpaceID,
+    /// Platform ID of account
     pub acc_id: String,
 }
-#[derive(Deserialize)]
+#[derive(Deserialize, Documentation)]
 pub struct SpaceItemPath {
+    /// Space ID
+    #[allow(dead_code)] // read by `SpaceAccess`
+    pub space_id: SpaceID,
+    /// Item ID
+    pub item_id: SpaceItemID,
+}
+#[derive(Deserialize, Documentation)]
+pub struct SpaceTagPath {
+    /// Space ID
+    #[allow(dead_code)] // read by `SpaceAccess`
+    pub space_id: SpaceID,
+    /// Tag ID
+    pub tag_id: SpaceTagID,
+}
+#[derive(Deserialize, Documentation)]
+pub struct SpaceLogPath {
+    /// Space ID
+    #[allow(dead_code)] // read by `SpaceAccess`
+    pub space_id: SpaceID,
+    /// ID of log entry
+    pub log_id: String,
+}
+#[derive(Deserialize, Documentation)]
+pub struct SpaceItemTagPath {
+    /// Space ID
+    #[allow(dead_code)] // read by `SpaceAccess`
     pub space_id: SpaceID,
-    pub item_id: String,
+    /// Item ID
+    pub item_id: SpaceItemID,
+    /// Tag ID
+    pub tag_id: SpaceTagID,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Documentation, Validate)]
 pub struct PatchSpace {
+    /// Space title
+    #[validate(with = validate::title)]
     pub title: String,
+    /// Fail with conflict if space version differs. Same as `If-Match` header
+    #[serde(default)]
+    pub expected_version: Option<i64>,
+}
+
+#[derive(Deserialize, Documentation)]
+pub struct DuplicateSpaceBody {
+    /// Title of new space, same as of source space by default
+    #[serde(default)]
+    pub title: Option<String>,
+    /// Also copy items without owner with their tags. Items of accounts are never copied
+    #[serde(default)]
+    pub items: bool,
+}
+
+#[derive(Deserialize, Documentation)]
+pub struct DryRunQuery {
+    /// Only report what would be changed, nothing is committed
+    #[serde(default)]
+    pub dry_run: bool,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Documentation)]
 pub struct Paging {
+    /// Page number starting from `0`, page contains up to 50 entries
     #[serde(default)]
     pub page: u32,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Documentation)]
 pub struct PatchAccountBody {
+    /// Formal name given by platform
     #[serde(default, skip_serializing_if = "MayIgnored::is_ignored")]
     pub pl_name: MayIgnored<Option<String>>,
+    /// Display name given by platform
     #[serde(default, skip_serializing_if = "MayIgnored::is_ignored")]
     pub pl_displayname: MayIgnored<Option<String>>,
+    /// Custom key/value data, replaces existing one
+    #[serde(default, skip_serializing_if = "MayIgnored::is_ignored")]
+    pub metadata: MayIgnored<Metadata>,
+    /// Fail with conflict if account version differs. Same as `If-Match` header
+    #[serde(default)]
+    pub expected_version: Option<i64>,
+}
+#[derive(Serialize, Deserialize, Documentation)]
+pub struct AccountAccessBody {
+    /// Put account in allow or deny list, `null` removes it from lists so unlock
+    /// policy applies again
+    pub access: Option<AccountAccess>,
+    /// Reason of change if any, stored in space logs
+    #[serde(default)]
+    pub reason: Option<String>,
+}
+#[derive(Serialize, Documentation)]
+pub struct AccountAccessResponse {
+    /// Allow or deny list containing account, `null` if account follows unlock policy
+    pub access: Option<AccountAccess>,
+    /// Reason of last change if any
+    pub reason: Option<String>,
+    /// Timestamp in milliseconds of last change if account is in list
+    pub updated_at: Option<i64>,
 }
-#[derive(Deserialize)]
+#[derive(Deserialize, Documentation)]
 pub struct PatchItemBody {
+    /// Item title
     #[serde(default, skip_serializing_if = "MayIgnored::is_ignored")]
     pub title: MayIgnored<String>,
+    /// Item type. Keycards should have owner
+    #[serde(default, skip_serializing_if = "MayIgnored::is_ignored")]
+    pub ty: MayIgnored<SpaceItemTy>,
+    /// Platform ID of owner account, `null` to remove owner
+    #[serde(default, skip_serializing_if = "MayIgnored::is_ignored")]
+    pub owner_id: MayIgnored<Option<String>>,
+    /// Custom key/value data, replaces existing one
+    #[serde(default, skip_serializing_if = "MayIgnored::is_ignored")]
+    pub metadata: MayIgnored<Metadata>,
+    /// Fail with conflict if item version differs. Same as `If-Match` header
+    #[serde(default)]
+    pub expected_version: Option<i64>,
+}
+
+#[derive(Deserialize, Documentation)]
+pub struct PatchPolicyBody {
+    /// Require account to own at least one keycard item
+    #[serde(default, skip_serializing_if = "MayIgnored::is_ignored")]
+    pub require_keycard: MayIgnored<bool>,
+    /// Deny unlock while items owned or held by account have unresolved reports
+    #[serde(default, skip_serializing_if = "MayIgnored::is_ignored")]
+    pub deny_on_open_reports: MayIgnored<bool>,
+    /// Leave requests failing keycard or open reports checks pending, so `SpaceManager`
+    /// service decides them instead of denying
+    #[serde(default, skip_serializing_if = "MayIgnored::is_ignored")]
+    pub ask_manager: MayIgnored<bool>,
+}
+
+#[derive(Deserialize, Documentation)]
+pub struct PatchRetentionBody {
+    /// Remove logs older than this number of days. Set to `null` to keep logs forever
+    pub days: Option<u32>,
+}
+
+#[derive(Deserialize, Documentation)]
+pub struct LogsQuery {
+    /// Page number starting from `0`, page contains up to 50 entries
+    #[serde(default)]
+    pub page: u32,
+    /// Action code
+    #[serde(default)]
+    pub act: Option<i64>,
+    /// Minimum timestamp (inclusive)
+    #[serde(default)]
+    pub from: Option<i64>,
+    /// Maximum timestamp (exclusive)
+    #[serde(default)]
+    pub to: Option<i64>,
+    /// Platform ID of account
+    #[serde(default)]
+    pub acc_id: Option<String>,
+    /// Item ID
+    #[serde(default)]
+    pub item_id: Option<String>,
+}
+
+#[derive(Deserialize, Documentation)]
+pub struct ExportQuery {
+    /// `jsonl` (default) or `csv`
+    #[serde(default)]
+    pub format: ExportFormat,
+}
+
+/// Image format of item QR code
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum QrFormat {
+    #[default]
+    Svg,
+    Png,
+}
+
+impl docs::Documentation for QrFormat {
+    const DOCUMENTATION_OBJECT: DocumentationObject =
+        <String as docs::Documentation>::DOCUMENTATION_OBJECT;
+}
+
+#[derive(Deserialize, Documentation)]
+pub struct ItemQrQuery {
+    /// `svg` (default) or `png`
+    #[serde(default)]
+    pub format: QrFormat,
+    /// Pixels per module of PNG. Default is 8
+    #[serde(default = "default_qr_scale")]
+    #[doc_min = 1]
+    #[doc_max = 32]
+    pub scale: u32,
 }
 
-#[derive(Deserialize)]
+fn default_qr_scale() -> u32 {
+    8
+}
+
+#[derive(Deserialize, Documentation)]
+pub struct TakeItemBody {
+    /// Platform ID of account who takes item
+    pub acc_id: String,
+    /// Timestamp in milliseconds when item should be returned, if any
+    #[serde(default)]
+    pub due_at: Option<i64>,
+}
+
+/// Maximum number of accounts in [`sync_accounts`]
+const MAX_SYNC_ACCOUNTS: usize = 10000;
+
+/// Maximum number of items in [`create_items_bulk`]
+const MAX_BULK_ITEMS: usize = 1000;
+
+#[derive(Deserialize, Documentation, Validate)]
 pub struct CreateSpaceItemBody {
+    /// Item title
+    #[validate(with = validate::title)]
     pub title: String,
+    /// Item type, see `archk::v1::space::SpaceItemTy`
     #[serde(default)]
     pub ty: SpaceItemTy,
+    /// Serial ID of item given by platform
+    #[validate(with = validate::pl_serial)]
     pub pl_serial: String,
+    /// Platform ID of owner account if any
     #[serde(default)]
     pub owner_id: Option<String>,
+    /// Custom key/value data
+    #[serde(default)]
+    #[validate(with = validate::metadata)]
+    pub metadata: Metadata,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, Documentation)]
 pub struct GetSpaceResponse {
+    /// Space object
     pub space: Space,
+    /// Owner of space
     pub owner: User,
 }
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Documentation, Validate)]
 pub struct SpaceAccountWithoutSpaceID {
+    /// Account unique ID given by platform
+    #[doc_min = 1]
+    #[doc_max = 128]
+    #[validate(with = validate::pl_id)]
     pub pl_id: String,
+    /// Formal name given by platform
     pub pl_name: Option<String>,
+    /// Display name given by platform
     pub pl_displayname: Option<String>,
+    /// Custom key/value data
+    #[serde(default)]
+    pub metadata: MetadataJson,
+    /// Record version, incremented on every change. Ignored in request bodies
+    #[serde(default)]
+    pub version: i64,
 }
-#[derive(Serialize)]
+#[derive(Serialize, Documentation)]
 pub struct SpaceItemWithoutSpaceID {
+    /// Item ID
     pub id: String,
+    /// Item title
     pub title: String,
+    /// Item type, see `archk::v1::space::SpaceItemTy`
     pub ty: i64,
+    /// Serial ID of item given by platform
     pub pl_serial: String,
+    /// Platform ID of owner account if any
     pub owner_id: Option<String>,
+    /// Platform ID of account currently holding item if taken
+    pub current_holder: Option<String>,
+    /// Timestamp in milliseconds when taken item should be returned, if any
+    pub due_at: Option<i64>,
+    /// Custom key/value data
+    pub metadata: MetadataJson,
+    /// Item status, see `GET /space/:space_id/item-statuses`
+    pub status: String,
+    /// Record version, incremented on every change
+    pub version: i64,
+}
+
+/// [`Metadata`] stored as JSON text in database.
+#[derive(Serialize, Deserialize, sqlx::Type, Default, PartialEq, Debug)]
+#[serde(transparent)]
+#[sqlx(transparent)]
+pub struct MetadataJson(pub sqlx::types::Json<Metadata>);
+
+impl docs::Documentation for MetadataJson {
+    const DOCUMENTATION_OBJECT: DocumentationObject =
+        <Metadata as docs::Documentation>::DOCUMENTATION_OBJECT;
+}
+
+impl MetadataJson {
+    fn to_json(&self) -> String {
+        serde_json::to_string(&self.0 .0).expect("json")
+    }
+}
+
+impl ExportRow for SpaceAccountWithoutSpaceID {
+    const CSV_HEADER: &'static [&'static str] =
+        &["pl_id", "pl_name", "pl_displayname", "metadata", "version"];
+
+    fn csv_fields(&self) -> Vec<Option<String>> {
+        vec![
+            Some(self.pl_id.clone()),
+            self.pl_name.clone(),
+            self.pl_displayname.clone(),
+            Some(self.metadata.to_json()),
+            Some(self.version.to_string()),
+        ]
+    }
+}
+
+impl ExportRow for SpaceItemWithoutSpaceID {
+    const CSV_HEADER: &'static [&'static str] = &[
+        "id",
+        "title",
+        "ty",
+        "pl_serial",
+        "owner_id",
+        "current_holder",
+        "due_at",
+        "metadata",
+        "status",
+        "version",
+    ];
+
+    fn csv_fields(&self) -> Vec<Option<String>> {
+        vec![
+            Some(self.id.clone()),
+            Some(self.title.clone()),
+            Some(self.ty.to_string()),
+            Some(self.pl_serial.clone()),
+            self.owner_id.clone(),
+            self.current_holder.clone(),
+            self.due_at.map(|v| v.to_string()),
+            Some(self.metadata.to_json()),
+            Some(self.status.clone()),
+            Some(self.version.to_string()),
+        ]
+    }
+}
+
+/// Get expected version of record from `If-Match` header (`"3"`, `W/"3"` or `3`)
+/// or `expected_version` field of body.
+fn if_match_version(headers: &HeaderMap, body: Option<i64>) -> Result<Option<i64>, api::ErrorData> {
+    let Some(header) = headers.get(IF_MATCH) else {
+        return Ok(body);
+    };
+    let header = header
+        .to_str()
+        .ok()
+        .map(|v| v.trim().trim_start_matches("W/").trim_matches('"'))
+        .and_then(|v| v.parse().ok())
+        .ok_or_else(|| {
+            api::Error::MalformedData.detail("`If-Match` should contain record version".into())
+        })?;
+
+    match body {
+        Some(body) if body != header => Err(api::Error::MalformedData
+            .detail("`If-Match` and `expected_version` are different".into())),
+        _ => Ok(Some(header)),
+    }
+}
+
+/// Error returned when record version differs from expected one.
+fn version_conflict(current: i64) -> api::ErrorData {
+    api::Error::Conflict.detail(format!("record was changed, current version is {current}").into())
+}
+
+/// Error returned on attempt to change archived space.
+pub(crate) fn archived_conflict() -> api::ErrorData {
+    api::Error::Conflict.detail("space is archived".into())
+}
+
+/// Collect `?meta.key=value` query params into JSON object matched against
+/// `metadata` column by list endpoints.
+fn meta_filter(query: &HashMap<String, String>) -> Result<String, api::ErrorData> {
+    let mut filter = Metadata::new();
+    for (key, value) in query {
+        let Some(key) = key.strip_prefix("meta.") else {
+            continue;
+        };
+        validate::metadata_key(key)?;
+        filter.insert(key.into(), value.clone());
+    }
+    Ok(serde_json::to_string(&filter).expect("json"))
+}
+#[derive(Deserialize, Documentation)]
+pub struct SyncAccountsBody {
+    /// Full list of platform accounts
+    pub accounts: Vec<SpaceAccountWithoutSpaceID>,
+    /// Deactivate accounts that are not in `accounts`
+    #[serde(default)]
+    pub deactivate_missing: bool,
 }
-#[derive(Serialize)]
+#[derive(Serialize, Documentation)]
+pub struct SyncAccountsResponse {
+    /// Platform IDs of created accounts
+    pub created: Vec<String>,
+    /// Platform IDs of updated (or reactivated) accounts
+    pub updated: Vec<String>,
+    /// Platform IDs of deactivated accounts
+    pub deactivated: Vec<String>,
+    /// Number of accounts left as is
+    pub unchanged: u64,
+}
+#[derive(Serialize, Documentation)]
+pub struct SpaceItemChange {
+    /// Change ID
+    pub id: String,
+    /// Timestamp in milliseconds of change
+    pub changed_at: i64,
+    /// ID of user who made change, if user still exists
+    pub user_id: Option<String>,
+    /// Changed field: `title`, `ty` or `owner_id`
+    pub field: String,
+    /// Value before change, `null` if field was not set
+    pub old_value: Option<String>,
+    /// Value after change, `null` if field was unset
+    pub new_value: Option<String>,
+}
+#[derive(Serialize, Documentation)]
 pub struct GetSpaceItemResponse {
+    /// Item object
     pub item: SpaceItemWithoutSpaceID,
+    /// Owner account if any
     pub owner: Option<SpaceAccountWithoutSpaceID>,
+    /// Tags attached to item
+    pub tags: Vec<SpaceTagWithoutSpaceID>,
+}
+#[derive(Serialize, Documentation)]
+pub struct SpaceTagWithoutSpaceID {
+    /// Tag ID
+    pub id: String,
+    /// Tag title
+    pub title: String,
+}
+#[derive(Deserialize, Documentation)]
+pub struct CreateTagBody {
+    /// Tag title, should be unique in space
+    pub title: String,
+}
+
+#[derive(Serialize, Documentation)]
+pub struct BulkRowError {
+    /// Index of row in request (without CSV header)
+    pub index: u64,
+    /// Error of row
+    pub error: api::ErrorData,
+}
+
+/// Number of rows deleted with space
+#[derive(Serialize, Documentation)]
+pub struct DeleteSpaceReport {
+    /// Deleted accounts
+    pub accounts: u64,
+    /// Deleted items
+    pub items: u64,
+    /// Deleted tags
+    pub tags: u64,
+    /// Deleted attachments, their files are removed later
+    pub attachments: u64,
+    /// Deleted log entries
+    pub logs: u64,
+    /// Deleted comments of log entries
+    pub comments: u64,
+    /// Deleted service accounts
+    pub services: u64,
+    /// Revoked tokens of service accounts
+    pub service_tokens: u64,
+    /// Revoked personal tokens restricted to space
+    pub scoped_tokens: u64,
+}
+
+#[derive(Serialize, Documentation)]
+pub struct BulkItemsResponse {
+    /// Are items created? If any row fails or on dry run nothing is created
+    pub committed: bool,
+    /// Created items
+    pub items: Vec<SpaceItem>,
+    /// Errors of failed rows
+    pub errors: Vec<BulkRowError>,
+}
+
+#[derive(Serialize, Documentation)]
+pub struct SpaceLogEntry {
+    /// Log entry ID
+    pub id: String,
+    /// Creation timestamp in milliseconds
+    pub created_at: i64,
+    /// Action code and name
+    pub act: LogActionCode,
+    /// Account platform ID if any
+    pub sp_acc_id: Option<String>,
+    /// Item ID if any
+    pub sp_item_id: Option<String>,
+    /// ID of log entry this entry refers to if any
+    pub ref_id: Option<String>,
+    /// Free-form details if any
+    pub detail: Option<String>,
+}
+
+/// [`LogAction`] stored as integer code in database.
+#[derive(Serialize, Debug)]
+#[serde(transparent)]
+pub struct LogActionCode(pub LogAction);
+
+impl docs::Documentation for LogActionCode {
+    const DOCUMENTATION_OBJECT: DocumentationObject =
+        <LogAction as docs::Documentation>::DOCUMENTATION_OBJECT;
+}
+
+impl sqlx::Type<sqlx::Sqlite> for LogActionCode {
+    fn type_info() -> sqlx::sqlite::SqliteTypeInfo {
+        <i64 as sqlx::Type<sqlx::Sqlite>>::type_info()
+    }
+
+    fn compatible(ty: &sqlx::sqlite::SqliteTypeInfo) -> bool {
+        <i64 as sqlx::Type<sqlx::Sqlite>>::compatible(ty)
+    }
+}
+
+impl<'r> sqlx::Decode<'r, sqlx::Sqlite> for LogActionCode {
+    fn decode(value: sqlx::sqlite::SqliteValueRef<'r>) -> Result<Self, sqlx::error::BoxDynError> {
+        let code = <i64 as sqlx::Decode<sqlx::Sqlite>>::decode(value)?;
+        Ok(Self(code.into()))
+    }
+}
+
+impl From<i64> for LogActionCode {
+    fn from(code: i64) -> Self {
+        Self(code.into())
+    }
+}
+
+impl ExportRow for SpaceLogEntry {
+    const CSV_HEADER: &'static [&'static str] = &[
+        "id",
+        "created_at",
+        "act",
+        "act_name",
+        "sp_acc_id",
+        "sp_item_id",
+        "ref_id",
+        "detail",
+    ];
+
+    fn csv_fields(&self) -> Vec<Option<String>> {
+        vec![
+            Some(self.id.clone()),
+            Some(self.created_at.to_string()),
+            Some(self.act.0.code().to_string()),
+            Some(self.act.0.name().into()),
+            self.sp_acc_id.clone(),
+            self.sp_item_id.clone(),
+            self.ref_id.clone(),
+            self.detail.clone(),
+        ]
+    }
+}
+
+#[derive(Serialize, Documentation)]
+pub struct SpaceLogItem {
+    /// Item ID
+    pub id: String,
+    /// Item title
+    pub title: String,
+    /// Serial ID of item given by platform
+    pub pl_serial: String,
+}
+
+#[derive(Serialize, Documentation)]
+pub struct SpaceLogDetailedEntry {
+    /// Log entry
+    pub log: SpaceLogEntry,
+    /// Account of entry if any and if it still exists
+    pub account: Option<SpaceAccountWithoutSpaceID>,
+    /// Item of entry if any and if it still exists
+    pub item: Option<SpaceLogItem>,
+    /// Comments on entry, oldest first
+    pub comments: Vec<SpaceLogComment>,
+}
+
+#[derive(Serialize, Documentation)]
+pub struct SpaceLogComment {
+    /// Comment ID
+    pub id: String,
+    /// ID of commented log entry
+    pub log_id: String,
+    /// Creation timestamp in milliseconds
+    pub created_at: i64,
+    /// ID of user who wrote comment, if written by user that still exists
+    pub user_id: Option<String>,
+    /// ID of service that wrote comment, if written by service that still exists
+    pub service_id: Option<String>,
+    /// Comment text
+    pub text: String,
+}
+
+#[derive(Deserialize, Documentation)]
+pub struct LogCommentBody {
+    /// Comment text, up to 1000 characters
+    pub text: String,
+}
+
+impl From<SpaceLog> for SpaceLogEntry {
+    fn from(v: SpaceLog) -> Self {
+        Self {
+            id: v.id,
+            created_at: v.created_at,
+            act: LogActionCode(v.act.into()),
+            sp_acc_id: v.sp_acc_id,
+            sp_item_id: v.sp_item_id.map(Into::into),
+            ref_id: v.ref_id,
+            detail: v.detail,
+        }
+    }
+}
+
+#[derive(Deserialize, Documentation)]
+pub struct SyncQuery {
+    /// ID of log entry, `cursor` of previous sync. Omit to get full state of space
+    #[serde(default)]
+    pub since: Option<String>,
+}
+
+#[derive(Serialize, Documentation)]
+pub struct SyncResponse {
+    /// Is it full state of space? Client should replace its cache then. Returned if
+    /// `since` is omitted, unknown or older than 30 days
+    pub full: bool,
+    /// ID of log entry to pass as `since` next time, if space has logs
+    pub cursor: Option<String>,
+    /// Are there more log entries after `cursor`? Sync again immediately then
+    pub more: bool,
+    /// Accounts created or changed since cursor
+    pub accounts: Vec<SpaceAccount>,
+    /// Items created or changed since cursor
+    pub items: Vec<SpaceItemWithoutSpaceID>,
+    /// Platform IDs of accounts deleted since cursor
+    pub deleted_accounts: Vec<String>,
+    /// IDs of items deleted since cursor
+    pub deleted_items: Vec<String>,
+    /// Log entries after cursor in chronological order, up to 500. Empty if `full`
+    pub logs: Vec<SpaceLogEntry>,
+}
+
+/// Position in space logs for streaming new entries. Entries with same timestamp are
+/// remembered, so none of them is skipped or streamed twice.
+pub(crate) struct LogCursor {
+    pub created_at: i64,
+    seen: Vec<String>,
+}
+
+impl LogCursor {
+    /// Cursor at current time.
+    pub fn now() -> Self {
+        Self::at(app::now_ms())
+    }
+
+    pub fn at(created_at: i64) -> Self {
+        Self {
+            created_at,
+            seen: Vec::new(),
+        }
+    }
+
+    /// Limit of query fetching entries with `created_at >= cursor.created_at`, so
+    /// at least `limit` new entries are fetched.
+    pub fn limit(&self, limit: i64) -> i64 {
+        limit + self.seen.len() as i64
+    }
+
+    /// Move cursor to entry. Returns `false` if entry was already streamed.
+    pub fn advance(&mut self, id: &str, created_at: i64) -> bool {
+        if created_at < self.created_at
+            || created_at == self.created_at && self.seen.iter().any(|v| v == id)
+        {
+            return false;
+        }
+        if created_at > self.created_at {
+            self.created_at = created_at;
+            self.seen.clear();
+        }
+        self.seen.push(id.to_owned());
+        true
+    }
+}
+
+/// Insert log entry into `spaces_logs`.
+pub(crate) async fn insert_log<'e, E>(db: E, log: &SpaceLog) -> Result<(), sqlx::Error>
+where
+    E: sqlx::Executor<'e, Database = sqlx::Sqlite>,
+{
+    let space_id: &str = &log.space_id;
+    let act: i64 = log.act.into();
+    let sp_item_id = log.sp_item_id.as_deref();
+
+    sqlx::query!(
+        r#"
+        INSERT INTO spaces_logs(id, space_id, created_at, act, sp_acc_id, sp_item_id, ref_id, detail)
+        VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+        "#,
+        log.id,
+        space_id,
+        log.created_at,
+        act,
+        log.sp_acc_id,
+        sp_item_id,
+        log.ref_id,
+        log.detail
+    )
+    .execute(db)
+    .await
+    .map(drop)
+}
+
+/// Maximum number of bound parameters in one statement. SQLite before 3.32 limits it
+/// to 999 by default (`SQLITE_MAX_VARIABLE_NUMBER`), newer versions allow more.
+const SQLITE_MAX_VARIABLE_NUMBER: usize = 999;
+
+/// Maximum number of entries inserted by one statement of [`insert_logs`], as each entry
+/// binds 8 parameters.
+const INSERT_LOGS_CHUNK: usize = SQLITE_MAX_VARIABLE_NUMBER / 8;
+
+/// Insert log entries into `spaces_logs` by multi-row statements. Run it in transaction
+/// to insert all entries or none. Returns number of inserted entries.
+pub(crate) async fn insert_logs(
+    tx: &mut sqlx::SqliteConnection,
+    logs: &[SpaceLog],
+) -> Result<u64, sqlx::Error> {
+    let mut inserted = 0;
+    for chunk in logs.chunks(INSERT_LOGS_CHUNK) {
+        let mut query = sqlx::QueryBuilder::new(
+            "INSERT INTO spaces_logs(id, space_id, created_at, act, sp_acc_id, sp_item_id, ref_id, detail) ",
+        );
+        query.push_values(chunk, |mut row, log| {
+            row.push_bind(&log.id)
+                .push_bind(&*log.space_id)
+                .push_bind(log.created_at)
+                .push_bind(i64::from(log.act))
+                .push_bind(&log.sp_acc_id)
+                .push_bind(log.sp_item_id.as_deref())
+                .push_bind(&log.ref_id)
+                .push_bind(&log.detail);
+        });
+        inserted += query.build().execute(&mut *tx).await?.rows_affected();
+    }
+    Ok(inserted)
+}
+
+/// [`SpaceLogStore`] over database connection. Entries are inserted in own transaction,
+/// or savepoint if connection is in transaction already.
+pub struct DbLogStore<'c>(pub &'c mut sqlx::SqliteConnection);
+
+impl SpaceLogStore for DbLogStore<'_> {
+    type Error = sqlx::Error;
+
+    async fn insert_many(&mut self, logs: &[SpaceLog]) -> Result<u64, sqlx::Error> {
+        let mut tx = sqlx::Connection::begin(&mut *self.0).await?;
+        let inserted = insert_logs(&mut tx, logs).await?;
+        tx.commit().await?;
+        Ok(inserted)
+    }
 }
 
 pub async fn create_space(
@@ -96,16 +856,16 @@ pub async fn create_space(
         ..
     }: AuthenticatedUser<DbUser>,
     State(AppState { db, roles, .. }): State<AppState>,
-    Json(PatchSpace { title }): Json<PatchSpace>,
+    Json(Valid(PatchSpace { title, .. })): Json<Valid<PatchSpace>>,
 ) -> Response<Space> {
-    let can_create_spaces = roles
-        .get_current(level)
-        .map(|v| v.permissions.spaces)
-        .unwrap_or(false);
+    let can_create_spaces = roles.load().has(level, perm::SPACE_CREATE);
 
     if !can_create_spaces {
         return Response::Failture(api::Error::Forbidden.into());
     }
+    if let Err(e) = quota::check_spaces(&db, roles.load().quota(level), &user_id, 1).await {
+        return Response::Failture(e);
+    }
 
     let space_id = SpaceID::new();
     let id: &str = &space_id;
@@ -123,6 +883,10 @@ pub async fn create_space(
         id: space_id,
         title,
         owner_id: UserID::from(user_id).expect("user id from database"),
+        logs_retention_days: None,
+        archived: false,
+        template: false,
+        version: 1,
     })
 }
 
@@ -136,10 +900,7 @@ pub async fn get_space(
     }: AuthenticatedUser<DbUser>,
     State(AppState { db, roles, .. }): State<AppState>,
 ) -> Response<GetSpaceResponse> {
-    let can_manage_spaces = roles
-        .get_current(level)
-        .map(|v| v.permissions.spaces_manage)
-        .unwrap_or(false);
+    let can_manage_spaces = roles.load().has(level, perm::SPACE_MANAGE);
 
     let space_id: &str = &space_id;
     let res = sqlx::query!(
@@ -148,6 +909,10 @@ pub async fn get_space(
             spaces.id as sp_id,
             spaces.title as sp_title,
             spaces.owner_id as user_id,
+            spaces.logs_retention_days as sp_logs_retention_days,
+            spaces.archived as sp_archived,
+            spaces.template as sp_template,
+            spaces.version as sp_version,
             users.name as user_name,
             users.invited_by as user_invited_by
         FROM spaces
@@ -175,6 +940,10 @@ pub async fn get_space(
                     id: SpaceID::from(res.sp_id).unwrap(),
                     title: res.sp_title,
                     owner_id: user_id.clone(),
+                    logs_retention_days: res.sp_logs_retention_days.map(|v| v as u32),
+                    archived: res.sp_archived,
+                    template: res.sp_template,
+                    version: res.sp_version,
                 },
                 owner: User {
                     id: user_id,
@@ -195,120 +964,349 @@ pub async fn patch_space(
         ..
     }: AuthenticatedUser<DbUser>,
     State(AppState { db, roles, .. }): State<AppState>,
-    Json(PatchSpace { title }): Json<PatchSpace>,
+    headers: HeaderMap,
+    Json(Valid(PatchSpace {
+        title,
+        expected_version,
+    })): Json<Valid<PatchSpace>>,
 ) -> Response<u64> {
-    let can_manage_spaces = roles
-        .get_current(level)
-        .map(|v| v.permissions.spaces_manage)
-        .unwrap_or(false);
+    let expected = match if_match_version(&headers, expected_version) {
+        Ok(v) => v,
+        Err(e) => return Response::Failture(e),
+    };
+
+    let can_manage_spaces = roles.load().has(level, perm::SPACE_MANAGE);
 
     let space_id: &str = &space_id;
-    let stmt = if can_manage_spaces {
-        sqlx::query!("UPDATE spaces SET title = ? WHERE id = ?", title, space_id)
-    } else {
-        sqlx::query!(
-            "UPDATE spaces SET title = ? WHERE id = ? AND owner_id = ?",
-            title,
-            space_id,
-            user_id
-        )
-    };
+    let res = sqlx::query!(
+        r#"
+        UPDATE spaces SET title = ?1
+        WHERE id = ?2 AND (?3 OR owner_id = ?4) AND (?5 IS NULL OR version = ?5) AND NOT archived"#,
+        title,
+        space_id,
+        can_manage_spaces,
+        user_id,
+        expected
+    )
+    .execute(&db)
+    .await
+    .expect("database")
+    .rows_affected();
 
-    let res = stmt.execute(&db).await.expect("database").rows_affected();
+    if res != 0 {
+        return Response::Success(res);
+    }
 
-    if res == 0 {
-        Response::Failture(api::Error::ObjectNotFound.into())
-    } else {
-        Response::Success(res)
+    let current = sqlx::query!(
+        "SELECT version, archived FROM spaces WHERE id = ? AND (? OR owner_id = ?)",
+        space_id,
+        can_manage_spaces,
+        user_id
+    )
+    .fetch_optional(&db)
+    .await
+    .expect("database");
+
+    match current {
+        Some(v) if v.archived => Response::Failture(archived_conflict()),
+        Some(v) if expected.is_some() => Response::Failture(version_conflict(v.version)),
+        _ => Response::Failture(api::Error::ObjectNotFound.into()),
     }
 }
 
 pub async fn delete_space(
     Path(SpacePath { space_id }): Path<SpacePath>,
+    Query(DryRunQuery { dry_run }): Query<DryRunQuery>,
     AuthenticatedUser {
         user: DbUser {
             id: user_id, level, ..
         },
         ..
     }: AuthenticatedUser<DbUser>,
-    State(AppState { db, roles, .. }): State<AppState>,
-) -> Response<u64> {
-    let can_manage_spaces = roles
-        .get_current(level)
-        .map(|v| v.permissions.spaces_manage)
-        .unwrap_or(false);
+    State(AppState {
+        db, roles, cache, ..
+    }): State<AppState>,
+) -> Response<DeleteSpaceReport> {
+    let can_manage_spaces = roles.load().has(level, perm::SPACE_MANAGE);
 
     let space_id: &str = &space_id;
-    let stmt = if can_manage_spaces {
-        sqlx::query!("DELETE FROM spaces WHERE id = ?", space_id)
-    } else {
-        sqlx::query!(
-            "DELETE FROM spaces WHERE id = ? AND owner_id = ?",
-            space_id,
-            user_id
-        )
-    };
-
-    let res = stmt.execute(&db).await.expect("database").rows_affected();
+    let mut tx = app::begin(&db).await;
+    let exists = sqlx::query!("SELECT owner_id FROM spaces WHERE id = ?", space_id)
+        .fetch_optional(&mut *tx)
+        .await
+        .expect("database")
+        .is_some_and(|v| can_manage_spaces || v.owner_id == user_id);
+    if !exists {
+        return Response::Failture(api::Error::ObjectNotFound.into());
+    }
 
-    if res == 0 {
-        Response::Failture(api::Error::ObjectNotFound.into())
-    } else {
-        Response::Success(res)
+    let report = erase_space(&mut tx, space_id).await;
+    if dry_run {
+        tx.rollback().await.expect("database");
+        return Response::Success(report);
     }
-}
+    tx.commit().await.expect("database");
+    cache.invalidate_space(space_id);
 
-pub async fn get_accounts(
-    Path(SpacePath { space_id }): Path<SpacePath>,
-    Query(Paging { page }): Query<Paging>,
-    AuthenticatedUser {
-        user: DbUser {
-            id: user_id, level, ..
-        },
-        ..
-    }: AuthenticatedUser<DbUser>,
-    State(AppState { db, roles, .. }): State<AppState>,
-) -> Response<Vec<SpaceAccountWithoutSpaceID>> {
-    let can_manage_spaces = roles
-        .get_current(level)
-        .map(|v| v.permissions.spaces_manage)
-        .unwrap_or(false);
+    Response::Success(report)
+}
 
-    let space_id: &str = &space_id;
-    let limit = 50;
-    let offset = (page as i64) * limit;
-    let stmt = if can_manage_spaces {
-        sqlx::query_as!(
-            SpaceAccountWithoutSpaceID,
-            "SELECT pl_id, pl_name, pl_displayname FROM spaces_accounts WHERE space_id = ? LIMIT ? OFFSET ?",
-            space_id, limit, offset
-        )
-        .fetch_all(&db)
+/// Delete space and everything in it, children first. Deleted items and accounts are
+/// recorded to `spaces_deletions` by triggers, files of attachments are queued to
+/// `attachments_deletions`.
+pub(crate) async fn erase_space(
+    tx: &mut sqlx::SqliteConnection,
+    space_id: &str,
+) -> DeleteSpaceReport {
+    let comments = sqlx::query!(
+        "DELETE FROM spaces_logs_comments WHERE space_id = ?",
+        space_id
+    )
+    .execute(&mut *tx)
+    .await
+    .expect("database")
+    .rows_affected();
+    let logs = sqlx::query!("DELETE FROM spaces_logs WHERE space_id = ?", space_id)
+        .execute(&mut *tx)
         .await
-    } else {
-        sqlx::query_as!(
-            SpaceAccountWithoutSpaceID,
-            r#"SELECT pl_id, pl_name, pl_displayname
-            FROM spaces_accounts
-                INNER JOIN spaces ON spaces.id = spaces_accounts.space_id
-            WHERE
-                spaces_accounts.space_id = ? AND spaces.owner_id = ?
-            LIMIT ? OFFSET ?"#,
-            space_id,
-            user_id,
-            limit,
-            offset
-        )
-        .fetch_all(&db)
+        .expect("database")
+        .rows_affected();
+    let attachments = sqlx::query!(
+        "DELETE FROM spaces_attachments WHERE space_id = ?",
+        space_id
+    )
+    .execute(&mut *tx)
+    .await
+    .expect("database")
+    .rows_affected();
+    sqlx::query!(
+        "DELETE FROM spaces_items_history WHERE space_id = ?",
+        space_id
+    )
+    .execute(&mut *tx)
+    .await
+    .expect("database");
+    sqlx::query!(
+        "DELETE FROM spaces_items_tags
+        WHERE item_id IN (SELECT id FROM spaces_items WHERE space_id = ?)",
+        space_id
+    )
+    .execute(&mut *tx)
+    .await
+    .expect("database");
+    sqlx::query!(
+        "DELETE FROM spaces_items_reservations WHERE space_id = ?",
+        space_id
+    )
+    .execute(&mut *tx)
+    .await
+    .expect("database");
+    let items = sqlx::query!("DELETE FROM spaces_items WHERE space_id = ?", space_id)
+        .execute(&mut *tx)
         .await
-    };
+        .expect("database")
+        .rows_affected();
+    let tags = sqlx::query!("DELETE FROM spaces_tags WHERE space_id = ?", space_id)
+        .execute(&mut *tx)
+        .await
+        .expect("database")
+        .rows_affected();
+
+    sqlx::query!(
+        "DELETE FROM spaces_access_windows WHERE space_id = ?",
+        space_id
+    )
+    .execute(&mut *tx)
+    .await
+    .expect("database");
+    sqlx::query!(
+        "DELETE FROM spaces_accounts_access WHERE space_id = ?",
+        space_id
+    )
+    .execute(&mut *tx)
+    .await
+    .expect("database");
+    let accounts = sqlx::query!("DELETE FROM spaces_accounts WHERE space_id = ?", space_id)
+        .execute(&mut *tx)
+        .await
+        .expect("database")
+        .rows_affected();
+    sqlx::query!("DELETE FROM spaces_policies WHERE space_id = ?", space_id)
+        .execute(&mut *tx)
+        .await
+        .expect("database");
+    sqlx::query!(
+        "DELETE FROM spaces_items_statuses WHERE space_id = ?",
+        space_id
+    )
+    .execute(&mut *tx)
+    .await
+    .expect("database");
 
-    let res = stmt.expect("database");
+    let service_tokens = sqlx::query!(
+        "DELETE FROM service_tokens
+        WHERE service_id IN (SELECT id FROM service_accounts WHERE space_id = ?)",
+        space_id
+    )
+    .execute(&mut *tx)
+    .await
+    .expect("database")
+    .rows_affected();
+    let services = sqlx::query!("DELETE FROM service_accounts WHERE space_id = ?", space_id)
+        .execute(&mut *tx)
+        .await
+        .expect("database")
+        .rows_affected();
+    let scoped_tokens = sqlx::query!("DELETE FROM tokens WHERE space_id = ?", space_id)
+        .execute(&mut *tx)
+        .await
+        .expect("database")
+        .rows_affected();
 
-    Response::Success(res)
+    sqlx::query!("DELETE FROM spaces WHERE id = ?", space_id)
+        .execute(&mut *tx)
+        .await
+        .expect("database");
+
+    DeleteSpaceReport {
+        accounts,
+        items,
+        tags,
+        attachments,
+        logs,
+        comments,
+        services,
+        service_tokens,
+        scoped_tokens,
+    }
 }
 
-pub async fn create_account(
+/// Set `archived` flag of space. Fails with conflict if space already in that state.
+async fn set_archived(
+    db: &sqlx::SqlitePool,
+    cache: &Cache,
+    space_id: &str,
+    archived: bool,
+) -> Response<u64> {
+    let res = sqlx::query!(
+        "UPDATE spaces SET archived = ?1 WHERE id = ?2 AND archived != ?1",
+        archived,
+        space_id
+    )
+    .execute(db)
+    .await
+    .expect("database")
+    .rows_affected();
+    cache.invalidate_space(space_id);
+
+    match res {
+        0 if archived => {
+            Response::Failture(api::Error::Conflict.detail("space already archived".into()))
+        }
+        0 => Response::Failture(api::Error::Conflict.detail("space is not archived".into())),
+        v => Response::Success(v),
+    }
+}
+
+pub async fn archive_space(
+    SpaceAccess { space_id, .. }: SpaceAccess,
+    State(AppState { db, cache, .. }): State<AppState>,
+) -> Response<u64> {
+    set_archived(&db, &cache, &space_id, true).await
+}
+
+pub async fn unarchive_space(
+    SpaceAccess { space_id, .. }: SpaceAccess,
+    State(AppState { db, cache, .. }): State<AppState>,
+) -> Response<u64> {
+    set_archived(&db, &cache, &space_id, false).await
+}
+
+pub async fn get_templates(
+    AuthenticatedUser {
+        user: DbUser { level, .. },
+        ..
+    }: AuthenticatedUser<DbUser>,
+    State(AppState { roles, .. }): State<AppState>,
+    ReadDb(db): ReadDb,
+) -> Response<Vec<Space>> {
+    if !roles.load().has(level, perm::SPACE_CREATE) {
+        return Response::Failture(api::Error::Forbidden.into());
+    }
+
+    let res = sqlx::query!(
+        "SELECT id, title, owner_id, logs_retention_days, archived, version
+        FROM spaces
+        WHERE template
+        ORDER BY title"
+    )
+    .fetch_all(&db)
+    .await
+    .expect("database")
+    .into_iter()
+    .map(|v| Space {
+        id: SpaceID::from(v.id).expect("space id from database"),
+        title: v.title,
+        owner_id: UserID::from(v.owner_id).expect("user id from database"),
+        logs_retention_days: v.logs_retention_days.map(|v| v as u32),
+        archived: v.archived,
+        template: true,
+        version: v.version,
+    });
+
+    Response::Success(res.collect())
+}
+
+/// Set `template` flag of space. Only users with `space.manage` can publish templates.
+async fn set_template(
+    space_id: &str,
+    level: i64,
+    state: &AppState,
+    template: bool,
+) -> Response<u64> {
+    if !state.roles.load().has(level, perm::SPACE_MANAGE) {
+        return Response::Failture(api::Error::Forbidden.into());
+    }
+
+    let res = sqlx::query!(
+        "UPDATE spaces SET template = ?1 WHERE id = ?2",
+        template,
+        space_id
+    )
+    .execute(&state.db)
+    .await
+    .expect("database")
+    .rows_affected();
+
+    match res {
+        0 => Response::Failture(api::Error::ObjectNotFound.into()),
+        v => Response::Success(v),
+    }
+}
+
+pub async fn mark_template(
+    Path(SpacePath { space_id }): Path<SpacePath>,
+    AuthenticatedUser {
+        user: DbUser { level, .. },
+        ..
+    }: AuthenticatedUser<DbUser>,
+    State(state): State<AppState>,
+) -> Response<u64> {
+    set_template(&space_id, level, &state, true).await
+}
+
+pub async fn unmark_template(
+    Path(SpacePath { space_id }): Path<SpacePath>,
+    AuthenticatedUser {
+        user: DbUser { level, .. },
+        ..
+    }: AuthenticatedUser<DbUser>,
+    State(state): State<AppState>,
+) -> Response<u64> {
+    set_template(&space_id, level, &state, false).await
+}
+
+pub async fn duplicate_space(
     Path(SpacePath { space_id }): Path<SpacePath>,
     AuthenticatedUser {
         user: DbUser {
@@ -317,36 +1315,222 @@ pub async fn create_account(
         ..
     }: AuthenticatedUser<DbUser>,
     State(AppState { db, roles, .. }): State<AppState>,
-    Json(SpaceAccountWithoutSpaceID {
+    Json(DuplicateSpaceBody { title, items }): Json<DuplicateSpaceBody>,
+) -> Response<Space> {
+    let roles = roles.load();
+    if !roles.has(level, perm::SPACE_CREATE) {
+        return Response::Failture(api::Error::Forbidden.into());
+    }
+    if let Some(Err(e)) = title.as_deref().map(validate::title) {
+        return Response::Failture(e.into());
+    }
+    let can_manage_spaces = roles.has(level, perm::SPACE_MANAGE);
+
+    let source: &str = &space_id;
+    let mut tx = app::begin(&db).await;
+    let res = sqlx::query!(
+        "SELECT title, logs_retention_days FROM spaces
+        WHERE id = ?1 AND (template OR ?2 OR owner_id = ?3)",
+        source,
+        can_manage_spaces,
+        user_id
+    )
+    .fetch_optional(&mut *tx)
+    .await
+    .expect("database");
+    let Some(from) = res else {
+        return Response::Failture(api::Error::ObjectNotFound.into());
+    };
+    let quota = roles.quota(level);
+    if let Err(e) = quota::check_spaces(&mut *tx, quota, &user_id, 1).await {
+        return Response::Failture(e);
+    }
+
+    let space_id = SpaceID::new();
+    let id: &str = &space_id;
+    let title = title.unwrap_or(from.title);
+    sqlx::query!(
+        "INSERT INTO spaces(id, title, owner_id, logs_retention_days) VALUES (?, ?, ?, ?)",
+        id,
+        title,
+        user_id,
+        from.logs_retention_days
+    )
+    .execute(&mut *tx)
+    .await
+    .expect("database");
+    sqlx::query!(
+        "INSERT INTO spaces_policies(space_id, require_keycard, deny_on_open_reports, ask_manager)
+        SELECT ?, require_keycard, deny_on_open_reports, ask_manager
+        FROM spaces_policies WHERE space_id = ?",
+        id,
+        source
+    )
+    .execute(&mut *tx)
+    .await
+    .expect("database");
+    sqlx::query!(
+        "INSERT INTO spaces_items_statuses(space_id, statuses)
+        SELECT ?, statuses FROM spaces_items_statuses WHERE space_id = ?",
+        id,
+        source
+    )
+    .execute(&mut *tx)
+    .await
+    .expect("database");
+
+    // old tag ID -> new tag ID
+    let mut tags = HashMap::new();
+    let res = sqlx::query!(
+        "SELECT id, title FROM spaces_tags WHERE space_id = ?",
+        source
+    )
+    .fetch_all(&mut *tx)
+    .await
+    .expect("database");
+    for tag in res {
+        let tag_id = SpaceTagID::new();
+        let tag_id_str: &str = &tag_id;
+        sqlx::query!(
+            "INSERT INTO spaces_tags(id, space_id, title) VALUES (?, ?, ?)",
+            tag_id_str,
+            id,
+            tag.title
+        )
+        .execute(&mut *tx)
+        .await
+        .expect("database");
+        tags.insert(tag.id, tag_id);
+    }
+
+    if items {
+        let res = sqlx::query!(
+            "SELECT id, title, ty, pl_serial, metadata, status FROM spaces_items
+            WHERE space_id = ? AND owner_id IS NULL",
+            source
+        )
+        .fetch_all(&mut *tx)
+        .await
+        .expect("database");
+        if let Err(e) = quota::check_items(&mut *tx, quota, id, res.len() as u64).await {
+            return Response::Failture(e);
+        }
+        for item in res {
+            let item_id = SpaceItemID::new();
+            let item_id_str: &str = &item_id;
+            sqlx::query!(
+                "INSERT INTO spaces_items(id, title, ty, pl_serial, space_id, metadata, status)
+                VALUES (?, ?, ?, ?, ?, ?, ?)",
+                item_id_str,
+                item.title,
+                item.ty,
+                item.pl_serial,
+                id,
+                item.metadata,
+                item.status
+            )
+            .execute(&mut *tx)
+            .await
+            .expect("database");
+
+            let item_tags = sqlx::query_scalar!(
+                "SELECT tag_id FROM spaces_items_tags WHERE item_id = ?",
+                item.id
+            )
+            .fetch_all(&mut *tx)
+            .await
+            .expect("database");
+            for tag_id in item_tags.iter().filter_map(|v| tags.get(v)) {
+                let tag_id: &str = tag_id;
+                sqlx::query!(
+                    "INSERT INTO spaces_items_tags(item_id, tag_id) VALUES (?, ?)",
+                    item_id_str,
+                    tag_id
+                )
+                .execute(&mut *tx)
+                .await
+                .expect("database");
+            }
+        }
+    }
+    tx.commit().await.expect("database");
+
+    Response::Success(Space {
+        id: space_id,
+        title,
+        owner_id: UserID::from(user_id).expect("user id from database"),
+        logs_retention_days: from.logs_retention_days.map(|v| v as u32),
+        archived: false,
+        template: false,
+        version: 1,
+    })
+}
+
+pub async fn get_accounts(
+    SpaceAccess { space_id, .. }: SpaceAccess,
+    Query(Paging { page }): Query<Paging>,
+    Query(query): Query<HashMap<String, String>>,
+    format: ListFormat,
+    ReadDb(db): ReadDb,
+) -> Listing<SpaceAccountWithoutSpaceID> {
+    let meta = match meta_filter(&query) {
+        Ok(v) => v,
+        Err(e) => return Listing(format, Response::Failture(e)),
+    };
+
+    let space_id: &str = &space_id;
+    let limit = 50;
+    let offset = (page as i64) * limit;
+    let res = sqlx::query_as!(
+        SpaceAccountWithoutSpaceID,
+        r#"SELECT pl_id, pl_name, pl_displayname, metadata AS "metadata: MetadataJson", version
+        FROM spaces_accounts
+        WHERE space_id = ? AND NOT EXISTS (
+            SELECT 1 FROM json_each(?) AS f
+            WHERE json_extract(spaces_accounts.metadata, '$."' || f.key || '"') IS NOT f.value
+        )
+        LIMIT ? OFFSET ?"#,
+        space_id,
+        meta,
+        limit,
+        offset
+    )
+    .fetch_all(&db)
+    .await
+    .expect("database");
+
+    Listing(format, Response::Success(res))
+}
+
+pub async fn create_account(
+    SpaceAccess {
+        space_id, archived, ..
+    }: SpaceAccess,
+    State(AppState { db, .. }): State<AppState>,
+    Json(Valid(SpaceAccountWithoutSpaceID {
         pl_id,
         pl_name,
         pl_displayname,
-    }): Json<SpaceAccountWithoutSpaceID>,
+        metadata: MetadataJson(sqlx::types::Json(metadata)),
+        ..
+    })): Json<Valid<SpaceAccountWithoutSpaceID>>,
 ) -> Response<SpaceAccount> {
-    let can_manage_spaces = roles
-        .get_current(level)
-        .map(|v| v.permissions.spaces_manage)
-        .unwrap_or(false);
-
-    let space_id_str: &str = &space_id;
-    if !can_manage_spaces {
-        // TODO: via one query if possible
-        let res = sqlx::query!("SELECT owner_id FROM spaces WHERE id = ?", space_id_str)
-            .fetch_optional(&db)
-            .await
-            .expect("database")
-            .map(|v| v.owner_id);
-        if res != Some(user_id) {
-            return Response::Failture(api::Error::ObjectNotFound.into());
-        }
+    if archived {
+        return Response::Failture(archived_conflict());
+    }
+    if let Err(e) = validate::metadata(&metadata) {
+        return Response::Failture(e.into());
     }
 
+    let space_id_str: &str = &space_id;
+    let metadata_str = serde_json::to_string(&metadata).expect("json");
     let res = sqlx::query!(
-        "INSERT INTO spaces_accounts(pl_id, space_id, pl_name, pl_displayname) VALUES (?, ?, ?, ?)",
+        "INSERT INTO spaces_accounts(pl_id, space_id, pl_name, pl_displayname, metadata) VALUES (?, ?, ?, ?, ?)",
         pl_id,
         space_id_str,
         pl_name,
-        pl_displayname
+        pl_displayname,
+        metadata_str
     )
     .execute(&db)
     .await;
@@ -357,6 +1541,9 @@ pub async fn create_account(
             pl_name,
             pl_displayname,
             space_id,
+            active: true,
+            metadata,
+            version: 1,
         }),
         Err(sqlx::Error::Database(err)) if err.is_foreign_key_violation() => {
             Response::Failture(api::Error::ObjectNotFound.into())
@@ -368,28 +1555,147 @@ pub async fn create_account(
     }
 }
 
-pub async fn get_account_by_id(
-    Path(SpaceAccountPath { space_id, acc_id }): Path<SpaceAccountPath>,
-    AuthenticatedUser {
-        user: DbUser {
-            id: user_id, level, ..
-        },
+pub async fn sync_accounts(
+    SpaceAccess {
+        space_id, archived, ..
+    }: SpaceAccess,
+    State(AppState { db, .. }): State<AppState>,
+    Json(SyncAccountsBody {
+        accounts,
+        deactivate_missing,
+    }): Json<SyncAccountsBody>,
+) -> Response<SyncAccountsResponse> {
+    if archived {
+        return Response::Failture(archived_conflict());
+    }
+    let space_id: &str = &space_id;
+    if accounts.len() > MAX_SYNC_ACCOUNTS {
+        return Response::Failture(
+            api::Error::MalformedData.detail(
+                format!(
+                    "expected at most {MAX_SYNC_ACCOUNTS} accounts, got {}",
+                    accounts.len()
+                )
+                .into(),
+            ),
+        );
+    }
+    let mut seen = HashSet::with_capacity(accounts.len());
+    if let Some(v) = accounts.iter().find(|v| !seen.insert(v.pl_id.clone())) {
+        return Response::Failture(
+            api::Error::MalformedData.detail(format!("duplicate `pl_id` {:?}", v.pl_id).into()),
+        );
+    }
+
+    if let Some(e) = accounts.iter().find_map(|v| v.validate().err()) {
+        return Response::Failture(e.into());
+    }
+    if let Some(e) = accounts
+        .iter()
+        .find_map(|v| validate::metadata(&v.metadata.0).err())
+    {
+        return Response::Failture(e.into());
+    }
+
+    let mut tx = app::begin(&db).await;
+
+    let existing: HashMap<_, _> = sqlx::query!(
+        r#"SELECT pl_id, pl_name, pl_displayname, active, metadata AS "metadata: MetadataJson"
+        FROM spaces_accounts WHERE space_id = ?"#,
+        space_id
+    )
+    .fetch_all(&mut *tx)
+    .await
+    .expect("database")
+    .into_iter()
+    .map(|v| (v.pl_id, (v.pl_name, v.pl_displayname, v.active, v.metadata)))
+    .collect();
+
+    let mut diff = SyncAccountsResponse {
+        created: Vec::new(),
+        updated: Vec::new(),
+        deactivated: Vec::new(),
+        unchanged: 0,
+    };
+
+    for SpaceAccountWithoutSpaceID {
+        pl_id,
+        pl_name,
+        pl_displayname,
+        metadata,
         ..
-    }: AuthenticatedUser<DbUser>,
-    State(AppState { db, roles, .. }): State<AppState>,
-) -> Response<SpaceAccount> {
-    let can_manage_spaces = roles
-        .get_current(level)
-        .map(|v| v.permissions.spaces_manage)
-        .unwrap_or(false);
+    } in accounts
+    {
+        match existing.get(&pl_id) {
+            None => {
+                let metadata = serde_json::to_string(&metadata).expect("json");
+                sqlx::query!(
+                    "INSERT INTO spaces_accounts(pl_id, space_id, pl_name, pl_displayname, metadata) VALUES (?, ?, ?, ?, ?)",
+                    pl_id,
+                    space_id,
+                    pl_name,
+                    pl_displayname,
+                    metadata
+                )
+                .execute(&mut *tx)
+                .await
+                .expect("database");
+                diff.created.push(pl_id);
+            }
+            Some((name, displayname, true, meta))
+                if *name == pl_name && *displayname == pl_displayname && *meta == metadata =>
+            {
+                diff.unchanged += 1;
+            }
+            Some(_) => {
+                let metadata = serde_json::to_string(&metadata).expect("json");
+                sqlx::query!(
+                    "UPDATE spaces_accounts SET pl_name = ?, pl_displayname = ?, metadata = ?, active = 1 WHERE pl_id = ? AND space_id = ?",
+                    pl_name,
+                    pl_displayname,
+                    metadata,
+                    pl_id,
+                    space_id
+                )
+                .execute(&mut *tx)
+                .await
+                .expect("database");
+                diff.updated.push(pl_id);
+            }
+        }
+    }
+
+    if deactivate_missing {
+        for (pl_id, (_, _, active, _)) in existing {
+            if !active || seen.contains(&pl_id) {
+                continue;
+            }
+            sqlx::query!(
+                "UPDATE spaces_accounts SET active = 0 WHERE pl_id = ? AND space_id = ?",
+                pl_id,
+                space_id
+            )
+            .execute(&mut *tx)
+            .await
+            .expect("database");
+            diff.deactivated.push(pl_id);
+        }
+    }
+
+    tx.commit().await.expect("database");
 
+    Response::Success(diff)
+}
+
+pub async fn get_account_by_id(
+    Path(SpaceAccountPath { acc_id, .. }): Path<SpaceAccountPath>,
+    SpaceAccess { space_id, .. }: SpaceAccess,
+    State(AppState { db, .. }): State<AppState>,
+) -> Response<SpaceAccount> {
     let space_id_ref: &str = &space_id;
     let res = sqlx::query!(
         r#"
-        SELECT spaces_accounts.*, spaces.owner_id as space_owner_id
-        FROM spaces_accounts
-            INNER JOIN spaces ON spaces.id = spaces_accounts.space_id
-        WHERE space_id = ? AND pl_id = ?"#,
+        SELECT * FROM spaces_accounts WHERE space_id = ? AND pl_id = ?"#,
         space_id_ref,
         acc_id
     )
@@ -398,434 +1704,980 @@ pub async fn get_account_by_id(
     .expect("database");
 
     match res {
-        Some(v) if can_manage_spaces || v.space_owner_id == user_id => {
-            Response::Success(SpaceAccount {
-                pl_id: v.pl_id,
-                space_id,
-                pl_name: v.pl_name,
-                pl_displayname: v.pl_displayname,
-            })
-        }
-        _ => Response::Failture(api::Error::ObjectNotFound.into()),
+        Some(v) => Response::Success(SpaceAccount {
+            pl_id: v.pl_id,
+            space_id,
+            pl_name: v.pl_name,
+            pl_displayname: v.pl_displayname,
+            active: v.active,
+            metadata: serde_json::from_str(&v.metadata).expect("database metadata"),
+            version: v.version,
+        }),
+        None => Response::Failture(api::Error::ObjectNotFound.into()),
     }
 }
 
-pub async fn patch_account_by_id(
-    Path(SpaceAccountPath { space_id, acc_id }): Path<SpaceAccountPath>,
-    AuthenticatedUser {
-        user: DbUser {
-            id: user_id, level, ..
-        },
-        ..
-    }: AuthenticatedUser<DbUser>,
-    State(AppState { db, roles, .. }): State<AppState>,
+pub async fn get_account_access(
+    Path(SpaceAccountPath { acc_id, .. }): Path<SpaceAccountPath>,
+    SpaceAccess { space_id, .. }: SpaceAccess,
+    State(AppState { db, .. }): State<AppState>,
+) -> Response<AccountAccessResponse> {
+    let space_id: &str = &space_id;
+    let res = sqlx::query!(
+        r#"
+        SELECT
+            spaces_accounts_access.allow AS "allow?: bool",
+            spaces_accounts_access.reason,
+            spaces_accounts_access.updated_at AS "updated_at?"
+        FROM spaces_accounts
+            LEFT JOIN spaces_accounts_access
+                ON spaces_accounts_access.space_id = spaces_accounts.space_id
+                    AND spaces_accounts_access.pl_id = spaces_accounts.pl_id
+        WHERE spaces_accounts.space_id = ? AND spaces_accounts.pl_id = ?"#,
+        space_id,
+        acc_id
+    )
+    .fetch_optional(&db)
+    .await
+    .expect("database");
+
+    match res {
+        Some(v) => Response::Success(AccountAccessResponse {
+            access: v.allow.map(|allow| match allow {
+                true => AccountAccess::Allow,
+                false => AccountAccess::Deny,
+            }),
+            reason: v.reason,
+            updated_at: v.updated_at,
+        }),
+        None => Response::Failture(api::Error::ObjectNotFound.into()),
+    }
+}
+
+pub async fn put_account_access(
+    Path(SpaceAccountPath { acc_id, .. }): Path<SpaceAccountPath>,
+    SpaceAccess {
+        space_id, archived, ..
+    }: SpaceAccess,
+    State(AppState { db, .. }): State<AppState>,
+    Json(AccountAccessBody { access, reason }): Json<AccountAccessBody>,
+) -> Response<SpaceLogEntry> {
+    if archived {
+        return Response::Failture(archived_conflict());
+    }
+    let reason = reason
+        .map(|v| v.trim().to_string())
+        .filter(|v| !v.is_empty());
+    if reason
+        .as_ref()
+        .is_some_and(|v| v.chars().count() > MAX_LOG_COMMENT_LEN)
+    {
+        return Response::Failture(api::Error::MalformedData.detail(
+            format!("`reason` should be at most {MAX_LOG_COMMENT_LEN} characters").into(),
+        ));
+    }
+
+    let log = SpaceLog::new(space_id.clone(), SpaceLogAction::AccountAccessChanged)
+        .with_account(acc_id.clone())
+        .with_detail(match (access, &reason) {
+            (Some(access), Some(reason)) => format!("{}: {reason}", access.code()),
+            (Some(access), None) => access.code().to_string(),
+            (None, Some(reason)) => format!("default: {reason}"),
+            (None, None) => "default".to_string(),
+        });
+    let space_id: &str = &space_id;
+    let mut tx = app::begin(&db).await;
+
+    let exists = sqlx::query!(
+        "SELECT pl_id FROM spaces_accounts WHERE space_id = ? AND pl_id = ?",
+        space_id,
+        acc_id
+    )
+    .fetch_optional(&mut *tx)
+    .await
+    .expect("database");
+    if exists.is_none() {
+        return Response::Failture(api::Error::ObjectNotFound.into());
+    }
+
+    match access {
+        Some(access) => {
+            let allow = access == AccountAccess::Allow;
+            sqlx::query!(
+                r#"
+                INSERT INTO spaces_accounts_access(space_id, pl_id, allow, reason, updated_at)
+                VALUES (?1, ?2, ?3, ?4, ?5)
+                ON CONFLICT(space_id, pl_id)
+                    DO UPDATE SET allow = ?3, reason = ?4, updated_at = ?5"#,
+                space_id,
+                acc_id,
+                allow,
+                reason,
+                log.created_at
+            )
+            .execute(&mut *tx)
+            .await
+            .expect("database");
+        }
+        None => {
+            sqlx::query!(
+                "DELETE FROM spaces_accounts_access WHERE space_id = ? AND pl_id = ?",
+                space_id,
+                acc_id
+            )
+            .execute(&mut *tx)
+            .await
+            .expect("database");
+        }
+    }
+
+    insert_log(&mut *tx, &log).await.expect("database");
+    tx.commit().await.expect("database");
+
+    Response::Success(log.into())
+}
+
+pub async fn patch_account_by_id(
+    Path(SpaceAccountPath { acc_id, .. }): Path<SpaceAccountPath>,
+    SpaceAccess {
+        space_id, archived, ..
+    }: SpaceAccess,
+    State(AppState { db, .. }): State<AppState>,
+    headers: HeaderMap,
     Json(PatchAccountBody {
         pl_name,
         pl_displayname,
+        metadata,
+        expected_version,
     }): Json<PatchAccountBody>,
 ) -> Response<u64> {
-    if pl_name.is_ignored() && pl_displayname.is_ignored() {
+    if archived {
+        return Response::Failture(archived_conflict());
+    }
+    if pl_name.is_ignored() && pl_displayname.is_ignored() && metadata.is_ignored() {
         return Response::Failture(
             api::Error::MalformedData.detail("Expected at least one subject to change".into()),
         );
     }
-
-    let can_manage_spaces = roles
-        .get_current(level)
-        .map(|v| v.permissions.spaces_manage)
-        .unwrap_or(false);
-
-    if !can_manage_spaces {
-        let space_id: &str = &space_id;
-        let res = sqlx::query!("SELECT owner_id FROM spaces WHERE id = ?", space_id)
-            .fetch_optional(&db)
-            .await
-            .expect("database")
-            .map(|v| v.owner_id);
-
-        match res {
-            Some(owner_id) if owner_id == user_id => (),
-            _ => return Response::Failture(api::Error::ObjectNotFound.into()),
+    if let MayIgnored::Value(metadata) = &metadata {
+        if let Err(e) = validate::metadata(metadata) {
+            return Response::Failture(e.into());
         }
     }
+    let expected = match if_match_version(&headers, expected_version) {
+        Ok(v) => v,
+        Err(e) => return Response::Failture(e),
+    };
 
-    let mut stmt = String::from("UPDATE spaces_accounts SET ");
-    let mut params = Vec::with_capacity(2);
+    let mut columns = Vec::with_capacity(3);
+    let mut params = Vec::with_capacity(5);
 
     if let MayIgnored::Value(pl_name) = pl_name {
-        stmt.push_str("pl_name = ? ");
+        columns.push("pl_name = ?");
         params.push(pl_name);
     }
     if let MayIgnored::Value(pl_displayname) = pl_displayname {
-        stmt.push_str("pl_displayname = ? ");
+        columns.push("pl_displayname = ?");
         params.push(pl_displayname);
     }
+    if let MayIgnored::Value(metadata) = metadata {
+        columns.push("metadata = ?");
+        params.push(Some(serde_json::to_string(&metadata).expect("json")));
+    }
 
-    stmt.push_str("WHERE pl_id = ? AND space_id = ?");
-    params.push(Some(acc_id));
-    params.push(Some(space_id.into()));
+    let stmt = format!(
+        "UPDATE spaces_accounts SET {} WHERE pl_id = ? AND space_id = ? AND (? IS NULL OR version = ?)",
+        columns.join(", ")
+    );
+    params.push(Some(acc_id.clone()));
+    params.push(Some(space_id.to_string()));
 
     let mut res: sqlx::query::Query<sqlx::Sqlite, _> = sqlx::query(&stmt);
     for param in params {
         res = res.bind(param);
     }
+    res = res.bind(expected).bind(expected);
 
     let res = res.execute(&db).await.expect("database").rows_affected();
 
     if res != 0 {
-        Response::Success(res)
-    } else {
-        Response::Failture(api::Error::ObjectNotFound.into())
+        return Response::Success(res);
+    }
+
+    let space_id: &str = &space_id;
+    let current = sqlx::query!(
+        "SELECT version FROM spaces_accounts WHERE pl_id = ? AND space_id = ?",
+        acc_id,
+        space_id
+    )
+    .fetch_optional(&db)
+    .await
+    .expect("database");
+
+    match current {
+        Some(v) if expected.is_some() => Response::Failture(version_conflict(v.version)),
+        _ => Response::Failture(api::Error::ObjectNotFound.into()),
     }
 }
 
 pub async fn delete_account_by_id(
-    Path(SpaceAccountPath { space_id, acc_id }): Path<SpaceAccountPath>,
-    AuthenticatedUser {
-        user: DbUser {
-            id: user_id, level, ..
-        },
-        ..
-    }: AuthenticatedUser<DbUser>,
-    State(AppState { db, roles, .. }): State<AppState>,
+    Path(SpaceAccountPath { acc_id, .. }): Path<SpaceAccountPath>,
+    SpaceAccess {
+        space_id, archived, ..
+    }: SpaceAccess,
+    State(AppState { db, .. }): State<AppState>,
 ) -> Response<u64> {
-    let can_manage_spaces = roles
-        .get_current(level)
-        .map(|v| v.permissions.spaces_manage)
-        .unwrap_or(false);
-
-    if !can_manage_spaces {
-        let space_id: &str = &space_id;
-        let res = sqlx::query!("SELECT owner_id FROM spaces WHERE id = ?", space_id)
-            .fetch_optional(&db)
-            .await
-            .expect("database")
-            .map(|v| v.owner_id);
-
-        match res {
-            Some(owner_id) if owner_id == user_id => (),
-            _ => return Response::Failture(api::Error::ObjectNotFound.into()),
-        }
+    if archived {
+        return Response::Failture(archived_conflict());
     }
-
     let space_id: &str = &space_id;
-    let res = sqlx::query!(
-        r#"UPDATE spaces_logs SET sp_acc_id = NULL WHERE sp_acc_id = ? AND space_id = ?;
-        DELETE FROM spaces_accounts WHERE pl_id = ? AND space_id = ?"#,
+    let mut tx = app::begin(&db).await;
+
+    sqlx::query!(
+        "UPDATE spaces_logs SET sp_acc_id = NULL WHERE sp_acc_id = ? AND space_id = ?",
         acc_id,
-        space_id,
+        space_id
+    )
+    .execute(&mut *tx)
+    .await
+    .expect("database");
+
+    let res = sqlx::query!(
+        "DELETE FROM spaces_accounts WHERE pl_id = ? AND space_id = ?",
         acc_id,
-        space_id,
+        space_id
     )
-    .execute(&db)
+    .execute(&mut *tx)
     .await
     .expect("database")
     .rows_affected();
 
     if res == 0 {
-        Response::Failture(api::Error::ObjectNotFound.into())
-    } else {
-        Response::Success(res)
+        return Response::Failture(api::Error::ObjectNotFound.into());
     }
+
+    tx.commit().await.expect("database");
+
+    Response::Success(res)
 }
 
 pub async fn get_items(
-    Path(SpacePath { space_id }): Path<SpacePath>,
+    SpaceAccess { space_id, .. }: SpaceAccess,
     Query(Paging { page }): Query<Paging>,
-    AuthenticatedUser {
-        user: DbUser {
-            id: user_id, level, ..
-        },
-        ..
-    }: AuthenticatedUser<DbUser>,
-    State(AppState { db, roles, .. }): State<AppState>,
-) -> Response<Vec<SpaceItemWithoutSpaceID>> {
-    let can_manage_spaces = roles
-        .get_current(level)
-        .map(|v| v.permissions.spaces_manage)
-        .unwrap_or(false);
+    Query(query): Query<HashMap<String, String>>,
+    format: ListFormat,
+    ReadDb(db): ReadDb,
+) -> Listing<SpaceItemWithoutSpaceID> {
+    let meta = match meta_filter(&query) {
+        Ok(v) => v,
+        Err(e) => return Listing(format, Response::Failture(e)),
+    };
+    let tag = query.get("tag");
+    let status = query.get("status");
 
     let space_id: &str = &space_id;
     let limit = 50;
     let offset = (page as i64) * limit;
-    let stmt = if can_manage_spaces {
-        sqlx::query_as!(
-            SpaceItemWithoutSpaceID,
-        "SELECT id, title, ty, pl_serial, owner_id FROM spaces_items WHERE space_id = ? LIMIT ? OFFSET ?",
-        space_id, limit, offset
+    let res = sqlx::query_as!(
+        SpaceItemWithoutSpaceID,
+        r#"
+    SELECT
+        id, title, ty, pl_serial, owner_id, current_holder, due_at,
+        metadata AS "metadata: MetadataJson", status, version
+    FROM spaces_items
+    WHERE space_id = ? AND NOT EXISTS (
+            SELECT 1 FROM json_each(?) AS f
+            WHERE json_extract(spaces_items.metadata, '$."' || f.key || '"') IS NOT f.value
+        )
+        AND (? IS NULL OR EXISTS (
+            SELECT 1 FROM spaces_items_tags
+            WHERE spaces_items_tags.item_id = spaces_items.id AND spaces_items_tags.tag_id = ?
+        ))
+        AND (? IS NULL OR status = ?)
+    LIMIT ? OFFSET ?"#,
+        space_id,
+        meta,
+        tag,
+        tag,
+        status,
+        status,
+        limit,
+        offset
     )
     .fetch_all(&db)
     .await
-    } else {
-        sqlx::query_as!(
-            SpaceItemWithoutSpaceID,
-            r#"
-        SELECT
-            spaces_items.id,
-            spaces_items.title,
-            spaces_items.ty,
-            spaces_items.pl_serial,
-            spaces_items.owner_id
-        FROM spaces_items
-            INNER JOIN spaces ON spaces.id = spaces_items.space_id
-        WHERE
-            spaces_items.space_id = ? AND spaces.owner_id = ?
-        LIMIT ? OFFSET ?"#,
-            space_id,
-            user_id,
-            limit,
-            offset
-        )
-        .fetch_all(&db)
-        .await
-    };
-
-    let res = stmt.expect("database");
+    .expect("database");
 
-    Response::Success(res)
+    Listing(format, Response::Success(res))
 }
 
 pub async fn get_items_of_account(
-    Path(SpaceAccountPath { space_id, acc_id }): Path<SpaceAccountPath>,
+    Path(SpaceAccountPath { acc_id, .. }): Path<SpaceAccountPath>,
+    SpaceAccess { space_id, .. }: SpaceAccess,
     Query(Paging { page }): Query<Paging>,
-    AuthenticatedUser {
-        user: DbUser {
-            id: user_id, level, ..
-        },
-        ..
-    }: AuthenticatedUser<DbUser>,
-    State(AppState { db, roles, .. }): State<AppState>,
+    Query(query): Query<HashMap<String, String>>,
+    ReadDb(db): ReadDb,
 ) -> Response<Vec<SpaceItemWithoutSpaceID>> {
-    let can_manage_spaces = roles
-        .get_current(level)
-        .map(|v| v.permissions.spaces_manage)
-        .unwrap_or(false);
+    let meta = match meta_filter(&query) {
+        Ok(v) => v,
+        Err(e) => return Response::Failture(e),
+    };
+    let status = query.get("status");
 
     let space_id: &str = &space_id;
     let limit = 50;
     let offset = (page as i64) * limit;
-    let stmt = if can_manage_spaces {
-        sqlx::query_as!(
-            SpaceItemWithoutSpaceID,
-        "SELECT id, title, ty, pl_serial, owner_id FROM spaces_items WHERE space_id = ? AND owner_id = ? LIMIT ? OFFSET ?",
-        space_id, acc_id, limit, offset
+    let res = sqlx::query_as!(
+        SpaceItemWithoutSpaceID,
+        r#"
+    SELECT
+        id, title, ty, pl_serial, owner_id, current_holder, due_at,
+        metadata AS "metadata: MetadataJson", status, version
+    FROM spaces_items
+    WHERE space_id = ? AND owner_id = ? AND NOT EXISTS (
+            SELECT 1 FROM json_each(?) AS f
+            WHERE json_extract(spaces_items.metadata, '$."' || f.key || '"') IS NOT f.value
+        )
+        AND (? IS NULL OR status = ?)
+    LIMIT ? OFFSET ?"#,
+        space_id,
+        acc_id,
+        meta,
+        status,
+        status,
+        limit,
+        offset
     )
     .fetch_all(&db)
     .await
-    } else {
-        sqlx::query_as!(
-            SpaceItemWithoutSpaceID,
-            r#"
-        SELECT
-            spaces_items.id,
-            spaces_items.title,
-            spaces_items.ty,
-            spaces_items.pl_serial,
-            spaces_items.owner_id
-        FROM spaces_items
-            INNER JOIN spaces ON spaces.id = spaces_items.space_id
-        WHERE
-            spaces_items.space_id = ? AND spaces.owner_id = ? AND spaces_items.owner_id = ?
-        LIMIT ? OFFSET ?"#,
-            space_id,
-            user_id,
-            acc_id,
-            limit,
-            offset
-        )
-        .fetch_all(&db)
-        .await
-    };
-
-    let res = stmt.expect("database");
+    .expect("database");
 
     Response::Success(res)
 }
 
-pub async fn create_item(
-    Path(SpacePath { space_id }): Path<SpacePath>,
-    AuthenticatedUser {
-        user: DbUser {
-            id: user_id, level, ..
-        },
-        ..
-    }: AuthenticatedUser<DbUser>,
-    State(AppState { db, roles, .. }): State<AppState>,
-    Json(CreateSpaceItemBody {
+/// Insert new item into space. Used by [`create_item`] and [`create_items_bulk`].
+async fn insert_item<'e, E>(
+    executor: E,
+    space_id: &SpaceID,
+    body: CreateSpaceItemBody,
+) -> Result<SpaceItem, api::ErrorData>
+where
+    E: sqlx::Executor<'e, Database = sqlx::Sqlite>,
+{
+    if body.owner_id.is_none() && body.ty.is_owner_required() {
+        return Err(api::Error::MalformedData.detail(
+            format!("item type `ty` ({}) should belong to their owner but `owner_id` isn't specified or null", body.ty).into(),
+        ));
+    }
+    body.validate()?;
+    let CreateSpaceItemBody {
         title,
         ty,
         pl_serial,
         owner_id,
-    }): Json<CreateSpaceItemBody>,
-) -> Response<SpaceItem> {
-    if owner_id.is_none() && ty.is_owner_required() {
-        return Response::Failture(api::Error::MalformedData.detail(
-            format!("item type `ty` ({ty}) should belong to their owner but `owner_id` isn't specified or null").into(),
-        ));
-    }
-
-    let can_manage_spaces = roles
-        .get_current(level)
-        .map(|v| v.permissions.spaces_manage)
-        .unwrap_or(false);
-
-    let space_id_str: &str = &space_id;
-    if !can_manage_spaces {
-        // TODO: via one query if possible
-        let res = sqlx::query!("SELECT owner_id FROM spaces WHERE id = ?", space_id_str)
-            .fetch_optional(&db)
-            .await
-            .expect("database")
-            .map(|v| v.owner_id);
-        if res != Some(user_id) {
-            return Response::Failture(api::Error::ObjectNotFound.into());
-        }
-    }
+        metadata,
+    } = body;
 
     let id = SpaceItemID::new();
     let id_str = &id as &str;
     let ty_no: i64 = ty.into();
+    let space_id_str: &str = space_id;
+    let metadata_str = serde_json::to_string(&metadata).expect("json");
 
-    let res = sqlx::query!(
+    // new items get first status of space
+    let res = sqlx::query_scalar!(
         r#"
-        INSERT INTO spaces_items(id, title, ty, pl_serial, owner_id, space_id)
-        VALUES (?, ?, ?, ?, ?, ?)
+        INSERT INTO spaces_items(id, title, ty, pl_serial, owner_id, space_id, metadata, status)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, COALESCE(
+            (SELECT json_extract(statuses, '$[0].name') FROM spaces_items_statuses WHERE space_id = ?6),
+            ?8
+        ))
+        RETURNING status
         "#,
         id_str,
         title,
         ty_no,
         pl_serial,
         owner_id,
-        space_id_str
+        space_id_str,
+        metadata_str,
+        DEFAULT_ITEM_STATUS
     )
-    .execute(&db)
+    .fetch_one(executor)
     .await;
 
     match res {
-        Ok(_) => Response::Success(SpaceItem {
+        Ok(status) => Ok(SpaceItem {
             id,
             title,
             ty,
             pl_serial,
             owner_id,
-            space_id,
+            space_id: space_id.clone(),
+            current_holder: None,
+            due_at: None,
+            metadata,
+            status,
+            version: 1,
         }),
-        Err(sqlx::Error::Database(err)) if err.is_foreign_key_violation() => Response::Failture(
-            api::Error::ObjectNotFound
-                .detail("account with specified `owner_id` does not exists".into()),
-        ),
-        Err(sqlx::Error::Database(err)) if err.is_unique_violation() => Response::Failture(
-            api::Error::Conflict.detail("item with that `pl_serial` already exists".into()),
-        ),
+        Err(sqlx::Error::Database(err)) if err.is_foreign_key_violation() => {
+            Err(api::Error::ObjectNotFound
+                .detail("account with specified `owner_id` does not exists".into()))
+        }
+        Err(sqlx::Error::Database(err)) if err.is_unique_violation() => {
+            Err(api::Error::Conflict.detail("item with that `pl_serial` already exists".into()))
+        }
         Err(e) => panic!("database: {e}"),
     }
 }
 
-pub async fn get_item_by_id(
-    Path(SpaceItemPath { space_id, item_id }): Path<SpaceItemPath>,
-    AuthenticatedUser {
-        user: DbUser {
-            id: user_id, level, ..
-        },
-        ..
-    }: AuthenticatedUser<DbUser>,
+pub async fn create_item(
+    SpaceAccess {
+        space_id, archived, ..
+    }: SpaceAccess,
     State(AppState { db, roles, .. }): State<AppState>,
-) -> Response<GetSpaceItemResponse> {
-    let can_manage_spaces = roles
-        .get_current(level)
-        .map(|v| v.permissions.spaces_manage)
+    Json(body): Json<CreateSpaceItemBody>,
+) -> Response<SpaceItem> {
+    if archived {
+        return Response::Failture(archived_conflict());
+    }
+    let quota = quota::space_quota(&db, &roles.load(), &space_id).await;
+    if let Err(e) = quota::check_items(&db, quota, &space_id, 1).await {
+        return Response::Failture(e);
+    }
+    match insert_item(&db, &space_id, body).await {
+        Ok(v) => Response::Success(v),
+        Err(e) => Response::Failture(e),
+    }
+}
+
+/// Parse bulk items body. `Err` contains index of row that failed to parse (if known) and error.
+fn parse_bulk_items(
+    headers: &HeaderMap,
+    body: &[u8],
+) -> Result<Vec<Result<CreateSpaceItemBody, String>>, String> {
+    let is_csv = headers
+        .get(CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.starts_with("text/csv"))
         .unwrap_or(false);
 
-    let space_id: &str = &space_id;
-    let res = sqlx::query!(
-        r#"
-        SELECT
-            spaces_items.id,
-            spaces_items.title,
-            spaces_items.ty,
-            spaces_items.pl_serial,
-            spaces_items.owner_id,
-            spaces_accounts.pl_name,
-            spaces_accounts.pl_displayname,
-            spaces.owner_id as space_owner_id
-        FROM spaces_items
-            LEFT JOIN spaces_accounts
-                ON spaces_accounts.pl_id = spaces_items.owner_id
-                    AND spaces_accounts.space_id = spaces_items.space_id
-            INNER JOIN spaces
-                ON spaces.id = spaces_items.space_id
-        WHERE spaces_items.space_id = ? AND spaces_items.id = ?
-        "#,
-        space_id,
-        item_id
-    )
-    .fetch_optional(&db)
-    .await
-    .expect("database")
-    .filter(|v| can_manage_spaces || v.space_owner_id == user_id);
+    if is_csv {
+        Ok(csv::Reader::from_reader(body)
+            .deserialize()
+            .map(|v| v.map_err(|e: csv::Error| e.to_string()))
+            .collect())
+    } else {
+        serde_json::from_slice::<Vec<serde_json::Value>>(body)
+            .map(|rows| {
+                rows.into_iter()
+                    .map(|v| serde_json::from_value(v).map_err(|e| e.to_string()))
+                    .collect()
+            })
+            .map_err(|e| e.to_string())
+    }
+}
 
-    let Some(res) = res else {
-        return Response::Failture(api::Error::ObjectNotFound.into());
+pub async fn create_items_bulk(
+    SpaceAccess {
+        space_id, archived, ..
+    }: SpaceAccess,
+    Query(DryRunQuery { dry_run }): Query<DryRunQuery>,
+    State(AppState { db, roles, .. }): State<AppState>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response<BulkItemsResponse> {
+    if archived {
+        return Response::Failture(archived_conflict());
+    }
+    let rows = match parse_bulk_items(&headers, &body) {
+        Ok(v) => v,
+        Err(e) => return Response::Failture(api::Error::MalformedData.detail(e.into())),
     };
+    if rows.len() > MAX_BULK_ITEMS {
+        return Response::Failture(
+            api::Error::MalformedData.detail(
+                format!(
+                    "expected at most {MAX_BULK_ITEMS} items, got {}",
+                    rows.len()
+                )
+                .into(),
+            ),
+        );
+    }
 
-    Response::Success(GetSpaceItemResponse {
+    let quota = quota::space_quota(&db, &roles.load(), &space_id).await;
+    let mut tx = app::begin(&db).await;
+    if let Err(e) = quota::check_items(&mut *tx, quota, &space_id, rows.len() as u64).await {
+        return Response::Failture(e);
+    }
+    let mut items = Vec::with_capacity(rows.len());
+    let mut errors = Vec::new();
+
+    for (index, row) in rows.into_iter().enumerate() {
+        let res = match row {
+            Ok(row) => insert_item(&mut *tx, &space_id, row).await,
+            Err(e) => Err(api::Error::MalformedData.detail(e.into())),
+        };
+        match res {
+            Ok(item) => items.push(item),
+            Err(error) => errors.push(BulkRowError {
+                index: index as u64,
+                error,
+            }),
+        }
+    }
+
+    if errors.is_empty() && dry_run {
+        // items are returned as they would be created
+        tx.rollback().await.expect("database");
+        Response::Success(BulkItemsResponse {
+            committed: false,
+            items,
+            errors,
+        })
+    } else if errors.is_empty() {
+        tx.commit().await.expect("database");
+        Response::Success(BulkItemsResponse {
+            committed: true,
+            items,
+            errors,
+        })
+    } else {
+        tx.rollback().await.expect("database");
+        Response::Success(BulkItemsResponse {
+            committed: false,
+            items: Vec::new(),
+            errors,
+        })
+    }
+}
+
+pub async fn get_item_qr(
+    Path(SpaceItemPath { item_id, .. }): Path<SpaceItemPath>,
+    SpaceAccess { space_id, .. }: SpaceAccess,
+    Query(ItemQrQuery { format, scale }): Query<ItemQrQuery>,
+    State(AppState { db, .. }): State<AppState>,
+) -> axum::response::Response {
+    let item_id: &str = &item_id;
+    if !(1..=32).contains(&scale) {
+        return Response::<api::NeverSerialize>::Failture(
+            api::Error::MalformedData.detail("`scale` should be between 1 and 32".into()),
+        )
+        .into_response();
+    }
+    let space_id: &str = &space_id;
+    let res = sqlx::query_scalar!(
+        "SELECT pl_serial FROM spaces_items WHERE id = ? AND space_id = ?",
+        item_id,
+        space_id
+    )
+    .fetch_optional(&db)
+    .await
+    .expect("database");
+    let Some(pl_serial) = res else {
+        return Response::<api::NeverSerialize>::Failture(api::Error::ObjectNotFound.into())
+            .into_response();
+    };
+
+    let Some(qr) = QrCode::encode(pl_serial.as_bytes()) else {
+        return Response::<api::NeverSerialize>::Failture(
+            api::Error::ProcessingError.detail("`pl_serial` is too long for QR code".into()),
+        )
+        .into_response();
+    };
+    match format {
+        QrFormat::Svg => ([(CONTENT_TYPE, "image/svg+xml")], qr.to_svg()).into_response(),
+        QrFormat::Png => ([(CONTENT_TYPE, "image/png")], qr.to_png(scale as usize)).into_response(),
+    }
+}
+
+pub async fn get_item_by_id(
+    Path(SpaceItemPath { item_id, .. }): Path<SpaceItemPath>,
+    SpaceAccess { space_id, .. }: SpaceAccess,
+    State(AppState { db, .. }): State<AppState>,
+) -> Response<GetSpaceItemResponse> {
+    let item_id: &str = &item_id;
+    let space_id: &str = &space_id;
+    let res = sqlx::query!(
+        r#"
+        SELECT
+            spaces_items.id,
+            spaces_items.title,
+            spaces_items.ty,
+            spaces_items.pl_serial,
+            spaces_items.owner_id,
+            spaces_items.current_holder,
+            spaces_items.due_at,
+            spaces_items.metadata AS "metadata: MetadataJson",
+            spaces_items.status,
+            spaces_items.version,
+            spaces_accounts.pl_name,
+            spaces_accounts.pl_displayname,
+            spaces_accounts.metadata AS "owner_metadata?: MetadataJson",
+            spaces_accounts.version AS "owner_version?"
+        FROM spaces_items
+            LEFT JOIN spaces_accounts
+                ON spaces_accounts.pl_id = spaces_items.owner_id
+                    AND spaces_accounts.space_id = spaces_items.space_id
+        WHERE spaces_items.space_id = ? AND spaces_items.id = ?
+        "#,
+        space_id,
+        item_id
+    )
+    .fetch_optional(&db)
+    .await
+    .expect("database");
+
+    let Some(res) = res else {
+        return Response::Failture(api::Error::ObjectNotFound.into());
+    };
+
+    let tags = sqlx::query_as!(
+        SpaceTagWithoutSpaceID,
+        r#"
+        SELECT spaces_tags.id, spaces_tags.title
+        FROM spaces_items_tags
+            INNER JOIN spaces_tags ON spaces_tags.id = spaces_items_tags.tag_id
+        WHERE spaces_items_tags.item_id = ?
+        ORDER BY spaces_tags.title"#,
+        res.id
+    )
+    .fetch_all(&db)
+    .await
+    .expect("database");
+
+    Response::Success(GetSpaceItemResponse {
         item: SpaceItemWithoutSpaceID {
             id: res.id,
             title: res.title,
             ty: res.ty,
             pl_serial: res.pl_serial,
             owner_id: res.owner_id.clone(),
+            current_holder: res.current_holder,
+            due_at: res.due_at,
+            metadata: res.metadata,
+            status: res.status,
+            version: res.version,
         },
         owner: res.owner_id.map(|v| SpaceAccountWithoutSpaceID {
             pl_id: v,
             pl_name: res.pl_name,
             pl_displayname: res.pl_displayname,
+            metadata: res.owner_metadata.unwrap_or_default(),
+            version: res.owner_version.unwrap_or_default(),
         }),
+        tags,
     })
 }
 
 pub async fn patch_item(
-    Path(SpaceItemPath { space_id, item_id }): Path<SpaceItemPath>,
+    Path(SpaceItemPath { item_id, .. }): Path<SpaceItemPath>,
+    SpaceAccess {
+        space_id, archived, ..
+    }: SpaceAccess,
     AuthenticatedUser {
-        user: DbUser {
-            id: user_id, level, ..
-        },
+        user: DbUser { id: user_id, .. },
         ..
     }: AuthenticatedUser<DbUser>,
-    State(AppState { db, roles, .. }): State<AppState>,
-    Json(PatchItemBody { title }): Json<PatchItemBody>,
+    State(AppState { db, .. }): State<AppState>,
+    headers: HeaderMap,
+    Json(PatchItemBody {
+        title,
+        ty,
+        owner_id,
+        metadata,
+        expected_version,
+    }): Json<PatchItemBody>,
 ) -> Response<u64> {
-    if title.is_ignored() {
+    let item_id: &str = &item_id;
+    if archived {
+        return Response::Failture(archived_conflict());
+    }
+    if title.is_ignored() && ty.is_ignored() && owner_id.is_ignored() && metadata.is_ignored() {
         return Response::Failture(
             api::Error::MalformedData.detail("expected at least one subject to change".into()),
         );
     }
+    if let MayIgnored::Value(title) = &title {
+        if let Err(e) = validate::title(title) {
+            return Response::Failture(e.into());
+        }
+    }
+    if let MayIgnored::Value(metadata) = &metadata {
+        if let Err(e) = validate::metadata(metadata) {
+            return Response::Failture(e.into());
+        }
+    }
 
-    let MayIgnored::Value(title) = title else {
-        unreachable!("`title` is checked that it's not ignored");
+    let expected = match if_match_version(&headers, expected_version) {
+        Ok(v) => v,
+        Err(e) => return Response::Failture(e),
     };
 
-    let can_manage_spaces = roles
-        .get_current(level)
-        .map(|v| v.permissions.spaces_manage)
-        .unwrap_or(false);
-
     let space_id: &str = &space_id;
-    if !can_manage_spaces {
-        let res = sqlx::query!("SELECT owner_id FROM spaces WHERE id = ?", space_id)
-            .fetch_optional(&db)
-            .await
-            .expect("database");
+    let mut tx = app::begin(&db).await;
 
-        if res.filter(|v| v.owner_id == user_id).is_none() {
-            return Response::Failture(api::Error::ObjectNotFound.into());
-        }
+    let current = sqlx::query!(
+        "SELECT title, ty, owner_id, version FROM spaces_items WHERE id = ? AND space_id = ?",
+        item_id,
+        space_id
+    )
+    .fetch_optional(&mut *tx)
+    .await
+    .expect("database");
+    let Some(current) = current else {
+        return Response::Failture(api::Error::ObjectNotFound.into());
+    };
+    if expected.is_some_and(|v| v != current.version) {
+        return Response::Failture(version_conflict(current.version));
+    }
+
+    let current_ty = SpaceItemTy::try_from(current.ty).expect("database item type");
+    let ty = ty.ok().unwrap_or(current_ty);
+    let owner_id = match owner_id {
+        MayIgnored::Value(v) => v,
+        MayIgnored::Ignored => current.owner_id.clone(),
+    };
+    if owner_id.is_none() && ty.is_owner_required() {
+        return Response::Failture(
+            api::Error::MalformedData.detail(
+                format!(
+                    "item type `ty` ({ty}) should belong to their owner but `owner_id` is null"
+                )
+                .into(),
+            ),
+        );
     }
+    let title = title.ok().unwrap_or_else(|| current.title.clone());
+    let metadata = metadata
+        .ok()
+        .map(|v| serde_json::to_string(&v).expect("json"));
 
+    let ty_no: i64 = ty.into();
     let res = sqlx::query!(
-        "UPDATE spaces_items SET title = ? WHERE id = ?",
+        r#"
+        UPDATE spaces_items
+        SET title = ?, ty = ?, owner_id = ?, metadata = COALESCE(?, metadata)
+        WHERE id = ? AND space_id = ?"#,
         title,
-        item_id
+        ty_no,
+        owner_id,
+        metadata,
+        item_id,
+        space_id
+    )
+    .execute(&mut *tx)
+    .await;
+    let res = match res {
+        Ok(v) => v.rows_affected(),
+        Err(sqlx::Error::Database(err)) if err.is_foreign_key_violation() => {
+            return Response::Failture(
+                api::Error::ObjectNotFound.detail("owner account does not exists".into()),
+            )
+        }
+        Err(e) => panic!("database error: {e}"),
+    };
+
+    let changes = [
+        ("title", Some(current.title), Some(title)),
+        ("ty", Some(current_ty.to_string()), Some(ty.to_string())),
+        ("owner_id", current.owner_id, owner_id),
+    ];
+    let changed_at = app::now_ms();
+    let user_id: &str = &user_id;
+    for (field, old_value, new_value) in changes {
+        if old_value == new_value {
+            continue;
+        }
+        let id = cuid2::create_id();
+        sqlx::query!(
+            r#"
+            INSERT INTO spaces_items_history(id, space_id, item_id, changed_at, user_id, field, old_value, new_value)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?)"#,
+            id,
+            space_id,
+            item_id,
+            changed_at,
+            user_id,
+            field,
+            old_value,
+            new_value
+        )
+        .execute(&mut *tx)
+        .await
+        .expect("database");
+    }
+
+    tx.commit().await.expect("database");
+    Response::Success(res)
+}
+
+pub async fn get_item_history(
+    Path(SpaceItemPath { item_id, .. }): Path<SpaceItemPath>,
+    SpaceAccess { space_id, .. }: SpaceAccess,
+    Query(Paging { page }): Query<Paging>,
+    ReadDb(db): ReadDb,
+) -> Response<Vec<SpaceItemChange>> {
+    let item_id: &str = &item_id;
+    let space_id: &str = &space_id;
+    let limit = 50;
+    let offset = (page as i64) * limit;
+
+    let exists = sqlx::query!(
+        "SELECT id FROM spaces_items WHERE id = ? AND space_id = ?",
+        item_id,
+        space_id
+    )
+    .fetch_optional(&db)
+    .await
+    .expect("database");
+    if exists.is_none() {
+        return Response::Failture(api::Error::ObjectNotFound.into());
+    }
+
+    let res = sqlx::query_as!(
+        SpaceItemChange,
+        r#"
+        SELECT id, changed_at, user_id, field, old_value, new_value
+        FROM spaces_items_history
+        WHERE item_id = ? AND space_id = ?
+        ORDER BY changed_at DESC, rowid DESC
+        LIMIT ? OFFSET ?"#,
+        item_id,
+        space_id,
+        limit,
+        offset
+    )
+    .fetch_all(&db)
+    .await
+    .expect("database");
+
+    Response::Success(res)
+}
+
+pub async fn delete_item(
+    Path(SpaceItemPath { item_id, .. }): Path<SpaceItemPath>,
+    SpaceAccess {
+        space_id, archived, ..
+    }: SpaceAccess,
+    State(AppState { db, .. }): State<AppState>,
+) -> Response<u64> {
+    if archived {
+        return Response::Failture(archived_conflict());
+    }
+    let space_id: &str = &space_id;
+    let item_id: &str = &item_id;
+    let mut tx = app::begin(&db).await;
+
+    sqlx::query!(
+        "UPDATE spaces_logs SET sp_item_id = NULL WHERE sp_item_id = ? AND space_id = ?",
+        item_id,
+        space_id
+    )
+    .execute(&mut *tx)
+    .await
+    .expect("database");
+
+    let res = sqlx::query!(
+        "DELETE FROM spaces_items WHERE id = ? AND space_id = ?",
+        item_id,
+        space_id
+    )
+    .execute(&mut *tx)
+    .await
+    .expect("database")
+    .rows_affected();
+
+    if res == 0 {
+        return Response::Failture(api::Error::ObjectNotFound.into());
+    }
+
+    tx.commit().await.expect("database");
+
+    Response::Success(res)
+}
+
+pub async fn get_tags(
+    SpaceAccess { space_id, .. }: SpaceAccess,
+    Query(Paging { page }): Query<Paging>,
+    ReadDb(db): ReadDb,
+) -> Response<Vec<SpaceTagWithoutSpaceID>> {
+    let space_id: &str = &space_id;
+    let limit = 50;
+    let offset = (page as i64) * limit;
+    let res = sqlx::query_as!(
+        SpaceTagWithoutSpaceID,
+        r#"
+        SELECT id, title FROM spaces_tags
+        WHERE space_id = ?
+        ORDER BY title
+        LIMIT ? OFFSET ?"#,
+        space_id,
+        limit,
+        offset
+    )
+    .fetch_all(&db)
+    .await
+    .expect("database");
+
+    Response::Success(res)
+}
+
+pub async fn create_tag(
+    SpaceAccess {
+        space_id, archived, ..
+    }: SpaceAccess,
+    State(AppState { db, .. }): State<AppState>,
+    Json(CreateTagBody { title }): Json<CreateTagBody>,
+) -> Response<SpaceTag> {
+    if archived {
+        return Response::Failture(archived_conflict());
+    }
+    if let Err(e) = validate::title(&title) {
+        return Response::Failture(e.into());
+    }
+    let space_id_str: &str = &space_id;
+    let id = SpaceTagID::new();
+    let id_str: &str = &id;
+    let res = sqlx::query!(
+        "INSERT INTO spaces_tags(id, space_id, title) VALUES (?, ?, ?)",
+        id_str,
+        space_id_str,
+        title
+    )
+    .execute(&db)
+    .await;
+
+    match res {
+        Ok(_) => Response::Success(SpaceTag {
+            id,
+            title,
+            space_id,
+        }),
+        Err(sqlx::Error::Database(err)) if err.is_unique_violation() => Response::Failture(
+            api::Error::Conflict.detail("tag with that `title` already exists".into()),
+        ),
+        Err(e) => panic!("database error: {e}"),
+    }
+}
+
+pub async fn delete_tag(
+    Path(SpaceTagPath { tag_id, .. }): Path<SpaceTagPath>,
+    SpaceAccess {
+        space_id, archived, ..
+    }: SpaceAccess,
+    State(AppState { db, .. }): State<AppState>,
+) -> Response<u64> {
+    if archived {
+        return Response::Failture(archived_conflict());
+    }
+    let space_id: &str = &space_id;
+    let tag_id: &str = &tag_id;
+    let res = sqlx::query!(
+        "DELETE FROM spaces_tags WHERE id = ? AND space_id = ?",
+        tag_id,
+        space_id
     )
     .execute(&db)
     .await
@@ -839,43 +2691,69 @@ pub async fn patch_item(
     }
 }
 
-pub async fn delete_item(
-    Path(SpaceItemPath { space_id, item_id }): Path<SpaceItemPath>,
-    AuthenticatedUser {
-        user: DbUser {
-            id: user_id, level, ..
-        },
-        ..
-    }: AuthenticatedUser<DbUser>,
-    State(AppState { db, roles, .. }): State<AppState>,
+pub async fn attach_tag(
+    Path(SpaceItemTagPath {
+        item_id, tag_id, ..
+    }): Path<SpaceItemTagPath>,
+    SpaceAccess {
+        space_id, archived, ..
+    }: SpaceAccess,
+    State(AppState { db, .. }): State<AppState>,
 ) -> Response<u64> {
-    let can_manage_spaces = roles
-        .get_current(level)
-        .map(|v| v.permissions.spaces_manage)
-        .unwrap_or(false);
-
-    if !can_manage_spaces {
-        let space_id: &str = &space_id;
-        let res = sqlx::query!("SELECT owner_id FROM spaces WHERE id = ?", space_id)
-            .fetch_optional(&db)
-            .await
-            .expect("database")
-            .map(|v| v.owner_id);
+    if archived {
+        return Response::Failture(archived_conflict());
+    }
+    let space_id: &str = &space_id;
+    let item_id: &str = &item_id;
+    let tag_id: &str = &tag_id;
+    let res = sqlx::query!(
+        r#"
+        INSERT INTO spaces_items_tags(item_id, tag_id)
+        SELECT spaces_items.id, spaces_tags.id
+        FROM spaces_items
+            INNER JOIN spaces_tags ON spaces_tags.space_id = spaces_items.space_id
+        WHERE spaces_items.id = ? AND spaces_tags.id = ? AND spaces_items.space_id = ?"#,
+        item_id,
+        tag_id,
+        space_id
+    )
+    .execute(&db)
+    .await;
 
-        match res {
-            Some(owner_id) if owner_id == user_id => (),
-            _ => return Response::Failture(api::Error::ObjectNotFound.into()),
+    match res {
+        Ok(v) if v.rows_affected() == 0 => Response::Failture(api::Error::ObjectNotFound.into()),
+        Ok(v) => Response::Success(v.rows_affected()),
+        Err(sqlx::Error::Database(err)) if err.is_unique_violation() => {
+            Response::Failture(api::Error::Conflict.detail("tag already attached to item".into()))
         }
+        Err(e) => panic!("database error: {e}"),
     }
+}
 
+pub async fn detach_tag(
+    Path(SpaceItemTagPath {
+        item_id, tag_id, ..
+    }): Path<SpaceItemTagPath>,
+    SpaceAccess {
+        space_id, archived, ..
+    }: SpaceAccess,
+    State(AppState { db, .. }): State<AppState>,
+) -> Response<u64> {
+    if archived {
+        return Response::Failture(archived_conflict());
+    }
     let space_id: &str = &space_id;
+    let item_id: &str = &item_id;
+    let tag_id: &str = &tag_id;
     let res = sqlx::query!(
-        r#"UPDATE spaces_logs SET sp_item_id = NULL WHERE sp_item_id = ? AND space_id = ?;
-        DELETE FROM spaces_items WHERE id = ? AND space_id = ?"#,
-        item_id,
-        space_id,
+        r#"
+        DELETE FROM spaces_items_tags
+        WHERE item_id = ?1 AND tag_id = ?2 AND EXISTS (
+            SELECT 1 FROM spaces_tags WHERE id = ?2 AND space_id = ?3
+        )"#,
         item_id,
-        space_id,
+        tag_id,
+        space_id
     )
     .execute(&db)
     .await
@@ -888,3 +2766,1080 @@ pub async fn delete_item(
         Response::Success(res)
     }
 }
+
+/// Get unlock policy of space. Returns default policy if space has no one.
+pub(crate) async fn fetch_policy(
+    db: &sqlx::SqlitePool,
+    space_id: &str,
+) -> Result<UnlockPolicy, sqlx::Error> {
+    let res = sqlx::query!(
+        r#"
+        SELECT
+            require_keycard AS "require_keycard: bool",
+            deny_on_open_reports AS "deny_on_open_reports: bool",
+            ask_manager AS "ask_manager: bool"
+        FROM spaces_policies
+        WHERE space_id = ?"#,
+        space_id
+    )
+    .fetch_optional(db)
+    .await?;
+
+    Ok(res
+        .map(|v| UnlockPolicy {
+            require_keycard: v.require_keycard,
+            deny_on_open_reports: v.deny_on_open_reports,
+            ask_manager: v.ask_manager,
+        })
+        .unwrap_or_default())
+}
+
+pub async fn get_policy(
+    SpaceAccess { space_id, .. }: SpaceAccess,
+    State(AppState { db, .. }): State<AppState>,
+) -> Response<UnlockPolicy> {
+    Response::Success(fetch_policy(&db, &space_id).await.expect("database"))
+}
+
+pub async fn patch_policy(
+    SpaceAccess {
+        space_id, archived, ..
+    }: SpaceAccess,
+    State(AppState { db, .. }): State<AppState>,
+    Json(PatchPolicyBody {
+        require_keycard,
+        deny_on_open_reports,
+        ask_manager,
+    }): Json<PatchPolicyBody>,
+) -> Response<UnlockPolicy> {
+    if archived {
+        return Response::Failture(archived_conflict());
+    }
+    if require_keycard.is_ignored() && deny_on_open_reports.is_ignored() && ask_manager.is_ignored()
+    {
+        return Response::Failture(
+            api::Error::MalformedData.detail("expected at least one subject to change".into()),
+        );
+    }
+
+    let space_id: &str = &space_id;
+    let default = UnlockPolicy::default();
+    let require_keycard = require_keycard.ok();
+    let deny_on_open_reports = deny_on_open_reports.ok();
+    let ask_manager = ask_manager.ok();
+    sqlx::query!(
+        r#"
+        INSERT INTO spaces_policies(space_id, require_keycard, deny_on_open_reports, ask_manager)
+        VALUES (?, COALESCE(?, ?), COALESCE(?, ?), COALESCE(?, ?))
+        ON CONFLICT(space_id) DO UPDATE SET
+            require_keycard = COALESCE(?, require_keycard),
+            deny_on_open_reports = COALESCE(?, deny_on_open_reports),
+            ask_manager = COALESCE(?, ask_manager)
+        "#,
+        space_id,
+        require_keycard,
+        default.require_keycard,
+        deny_on_open_reports,
+        default.deny_on_open_reports,
+        ask_manager,
+        default.ask_manager,
+        require_keycard,
+        deny_on_open_reports,
+        ask_manager
+    )
+    .execute(&db)
+    .await
+    .expect("database");
+
+    Response::Success(fetch_policy(&db, space_id).await.expect("database"))
+}
+
+pub async fn get_unlock_requests(
+    Query(Paging { page }): Query<Paging>,
+    SpaceAccess { space_id, .. }: SpaceAccess<DecideUnlocks>,
+    State(AppState { db, .. }): State<AppState>,
+) -> Response<Vec<SpaceLogEntry>> {
+    Response::Success(manager::unlock_requests(&db, &space_id, page).await)
+}
+
+pub async fn decide_unlock_request(
+    Path(SpaceLogPath { log_id, .. }): Path<SpaceLogPath>,
+    SpaceAccess {
+        space_id, archived, ..
+    }: SpaceAccess<DecideUnlocks>,
+    State(AppState { db, .. }): State<AppState>,
+    Json(body): Json<UnlockDecisionBody>,
+) -> Response<SpaceLogEntry> {
+    if archived {
+        return Response::Failture(archived_conflict());
+    }
+
+    manager::decide_unlock(&db, space_id, log_id, body).await
+}
+
+pub async fn add_log_comment(
+    Path(SpaceLogPath { log_id, .. }): Path<SpaceLogPath>,
+    SpaceAccess {
+        space_id, archived, ..
+    }: SpaceAccess<ManageSpaceLogs>,
+    AuthenticatedUser {
+        user: DbUser { id: user_id, .. },
+        ..
+    }: AuthenticatedUser<DbUser>,
+    State(AppState { db, .. }): State<AppState>,
+    Json(LogCommentBody { text }): Json<LogCommentBody>,
+) -> Response<SpaceLogComment> {
+    if archived {
+        return Response::Failture(archived_conflict());
+    }
+
+    match insert_log_comment(&db, &space_id, log_id, CommentAuthor::User(&user_id), text).await {
+        Ok(v) => Response::Success(v),
+        Err(e) => Response::Failture(e),
+    }
+}
+
+/// Author of [`SpaceLogComment`].
+pub(crate) enum CommentAuthor<'a> {
+    User(&'a str),
+    Service(&'a str),
+}
+
+/// Attach comment to log entry `log_id` of space.
+pub(crate) async fn insert_log_comment(
+    db: &sqlx::SqlitePool,
+    space_id: &SpaceID,
+    log_id: String,
+    author: CommentAuthor<'_>,
+    text: String,
+) -> Result<SpaceLogComment, api::ErrorData> {
+    let text = text.trim().to_string();
+    if text.is_empty() || text.chars().count() > MAX_LOG_COMMENT_LEN {
+        return Err(api::Error::MalformedData.detail(
+            format!("`text` should be from 1 to {MAX_LOG_COMMENT_LEN} characters").into(),
+        ));
+    }
+
+    let space_id: &str = space_id;
+    let exists = sqlx::query!(
+        "SELECT id FROM spaces_logs WHERE id = ? AND space_id = ?",
+        log_id,
+        space_id
+    )
+    .fetch_optional(db)
+    .await
+    .expect("database");
+    if exists.is_none() {
+        return Err(api::Error::ObjectNotFound.into());
+    }
+
+    let (user_id, service_id) = match author {
+        CommentAuthor::User(id) => (Some(id.to_string()), None),
+        CommentAuthor::Service(id) => (None, Some(id.to_string())),
+    };
+    let comment = SpaceLogComment {
+        id: cuid2::create_id(),
+        log_id,
+        created_at: app::now_ms(),
+        user_id,
+        service_id,
+        text,
+    };
+    // log entry may be removed by retention meanwhile, foreign key fails then
+    let res = sqlx::query!(
+        r#"
+        INSERT INTO spaces_logs_comments(id, space_id, log_id, created_at, user_id, service_id, text)
+        VALUES (?, ?, ?, ?, ?, ?, ?)"#,
+        comment.id,
+        space_id,
+        comment.log_id,
+        comment.created_at,
+        comment.user_id,
+        comment.service_id,
+        comment.text
+    )
+    .execute(db)
+    .await;
+
+    match res {
+        Ok(_) => Ok(comment),
+        Err(sqlx::Error::Database(e)) if e.is_foreign_key_violation() => {
+            Err(api::Error::ObjectNotFound.into())
+        }
+        Err(e) => panic!("database error: {e}"),
+    }
+}
+
+/// Check out item to account and record [`SpaceLogAction::ItemTaken`].
+pub(crate) async fn take_item(
+    db: &sqlx::SqlitePool,
+    space_id: &SpaceID,
+    item_id: &str,
+    acc_id: &str,
+    due_at: Option<i64>,
+) -> Result<SpaceLog, api::ErrorData> {
+    let space_id_str: &str = space_id;
+    let mut tx = app::begin(db).await;
+
+    let account = sqlx::query!(
+        "SELECT pl_id FROM spaces_accounts WHERE space_id = ? AND pl_id = ?",
+        space_id_str,
+        acc_id
+    )
+    .fetch_optional(&mut *tx)
+    .await
+    .expect("database");
+    if account.is_none() {
+        return Err(api::Error::ObjectNotFound.detail("account does not exists".into()));
+    }
+
+    let status = sqlx::query_scalar!(
+        "SELECT status FROM spaces_items WHERE id = ? AND space_id = ?",
+        item_id,
+        space_id_str
+    )
+    .fetch_optional(&mut *tx)
+    .await
+    .expect("database");
+    let Some(status) = status else {
+        return Err(api::Error::ObjectNotFound.detail("item does not exists".into()));
+    };
+    let statuses = item_status::fetch_statuses(&mut *tx, space_id_str).await;
+    if !statuses.iter().any(|v| v.name == status && v.takeable) {
+        return Err(
+            api::Error::Conflict.detail(format!("item is `{status}` and can't be taken").into())
+        );
+    }
+
+    // reservations of other accounts win over checkouts
+    let now = app::now_ms();
+    let reserved = sqlx::query_scalar!(
+        r#"
+        SELECT COUNT(1) AS "count!: i64" FROM spaces_items_reservations
+        WHERE item_id = ? AND pl_id != ? AND start_at < COALESCE(?, ? + 1) AND end_at > ?"#,
+        item_id,
+        acc_id,
+        due_at,
+        now,
+        now
+    )
+    .fetch_one(&mut *tx)
+    .await
+    .expect("database");
+    if reserved > 0 {
+        return Err(api::Error::Conflict.detail("item is reserved by other account".into()));
+    }
+
+    let res = sqlx::query!(
+        r#"
+        UPDATE spaces_items SET current_holder = ?, due_at = ?
+        WHERE id = ? AND space_id = ? AND current_holder IS NULL AND status = ?"#,
+        acc_id,
+        due_at,
+        item_id,
+        space_id_str,
+        status
+    )
+    .execute(&mut *tx)
+    .await
+    .expect("database")
+    .rows_affected();
+
+    if res == 0 {
+        return Err(api::Error::Conflict.detail("item already taken".into()));
+    }
+
+    let log = SpaceLog::new(space_id.clone(), SpaceLogAction::ItemTaken)
+        .with_account(acc_id.into())
+        .with_item(SpaceItemID::from(item_id.into()).expect("item id from database"));
+    insert_log(&mut *tx, &log).await.expect("database");
+    tx.commit().await.expect("database");
+
+    Ok(log)
+}
+
+/// Return checked out item and record [`SpaceLogAction::ItemReturned`].
+pub(crate) async fn return_item(
+    db: &sqlx::SqlitePool,
+    space_id: &SpaceID,
+    item_id: &str,
+) -> Result<SpaceLog, api::ErrorData> {
+    let space_id_str: &str = space_id;
+    let mut tx = app::begin(db).await;
+
+    let res = sqlx::query!(
+        "SELECT current_holder FROM spaces_items WHERE id = ? AND space_id = ?",
+        item_id,
+        space_id_str
+    )
+    .fetch_optional(&mut *tx)
+    .await
+    .expect("database");
+
+    let holder = match res {
+        Some(v) => v.current_holder,
+        None => return Err(api::Error::ObjectNotFound.detail("item does not exists".into())),
+    };
+    let Some(holder) = holder else {
+        return Err(api::Error::Conflict.detail("item is not taken".into()));
+    };
+
+    let res = sqlx::query!(
+        r#"
+        UPDATE spaces_items SET current_holder = NULL, due_at = NULL
+        WHERE id = ? AND space_id = ? AND current_holder = ?"#,
+        item_id,
+        space_id_str,
+        holder
+    )
+    .execute(&mut *tx)
+    .await
+    .expect("database")
+    .rows_affected();
+
+    if res == 0 {
+        return Err(api::Error::Conflict.detail("item is not taken".into()));
+    }
+
+    let log = SpaceLog::new(space_id.clone(), SpaceLogAction::ItemReturned)
+        .with_account(holder)
+        .with_item(SpaceItemID::from(item_id.into()).expect("item id from database"));
+    insert_log(&mut *tx, &log).await.expect("database");
+    tx.commit().await.expect("database");
+
+    Ok(log)
+}
+
+pub async fn post_take_item(
+    Path(SpaceItemPath { item_id, .. }): Path<SpaceItemPath>,
+    SpaceAccess {
+        space_id, archived, ..
+    }: SpaceAccess,
+    State(AppState { db, .. }): State<AppState>,
+    Json(TakeItemBody { acc_id, due_at }): Json<TakeItemBody>,
+) -> Response<SpaceLogEntry> {
+    if archived {
+        return Response::Failture(archived_conflict());
+    }
+    match take_item(&db, &space_id, &item_id, &acc_id, due_at).await {
+        Ok(log) => Response::Success(log.into()),
+        Err(e) => Response::Failture(e),
+    }
+}
+
+pub async fn post_return_item(
+    Path(SpaceItemPath { item_id, .. }): Path<SpaceItemPath>,
+    SpaceAccess {
+        space_id, archived, ..
+    }: SpaceAccess,
+    State(AppState { db, .. }): State<AppState>,
+) -> Response<SpaceLogEntry> {
+    if archived {
+        return Response::Failture(archived_conflict());
+    }
+    match return_item(&db, &space_id, &item_id).await {
+        Ok(log) => Response::Success(log.into()),
+        Err(e) => Response::Failture(e),
+    }
+}
+
+pub async fn get_overdue_items(
+    SpaceAccess { space_id, .. }: SpaceAccess,
+    Query(Paging { page }): Query<Paging>,
+    ReadDb(db): ReadDb,
+) -> Response<Vec<SpaceItemWithoutSpaceID>> {
+    let space_id: &str = &space_id;
+    let now = app::now_ms();
+    let limit = 50;
+    let offset = (page as i64) * limit;
+
+    let res = sqlx::query_as!(
+        SpaceItemWithoutSpaceID,
+        r#"
+        SELECT
+            id, title, ty, pl_serial, owner_id, current_holder, due_at,
+            metadata AS "metadata: MetadataJson", status, version
+        FROM spaces_items
+        WHERE space_id = ? AND current_holder IS NOT NULL AND due_at < ?
+        ORDER BY due_at
+        LIMIT ? OFFSET ?"#,
+        space_id,
+        now,
+        limit,
+        offset
+    )
+    .fetch_all(&db)
+    .await
+    .expect("database");
+
+    Response::Success(res)
+}
+
+pub async fn patch_logs_retention(
+    SpaceAccess {
+        space_id, archived, ..
+    }: SpaceAccess<ManageSpaceLogs>,
+    State(AppState { db, .. }): State<AppState>,
+    Json(PatchRetentionBody { days }): Json<PatchRetentionBody>,
+) -> Response<u64> {
+    if archived {
+        return Response::Failture(archived_conflict());
+    }
+    let space_id: &str = &space_id;
+    let res = sqlx::query!(
+        "UPDATE spaces SET logs_retention_days = ? WHERE id = ?",
+        days,
+        space_id
+    )
+    .execute(&db)
+    .await
+    .expect("database")
+    .rows_affected();
+
+    if res == 0 {
+        Response::Failture(api::Error::ObjectNotFound.into())
+    } else {
+        Response::Success(res)
+    }
+}
+
+pub async fn sync_space(
+    Path(SpacePath { space_id }): Path<SpacePath>,
+    Query(SyncQuery { since }): Query<SyncQuery>,
+    service: Option<AuthenticatedUser<DbService>>,
+    access: Result<SpaceAccess, api::Response>,
+    State(AppState { db, .. }): State<AppState>,
+) -> Response<SyncResponse> {
+    // actors keep local cache for decisions when server is unreachable
+    let is_service = matches!(
+        service,
+        Some(AuthenticatedUser {
+            user: DbService {
+                ty: ServiceAccountTy::SpaceActor | ServiceAccountTy::SpaceManager,
+                space_id: Some(ref v),
+                ..
+            },
+            ..
+        }) if *v == space_id
+    );
+    if !is_service {
+        match access {
+            Ok(_) => (),
+            Err(api::Response::Failture(e)) => return Response::Failture(e),
+            Err(api::Response::Success(v)) => match v {},
+        }
+    }
+
+    let now = app::now_ms();
+    let space_id_str: &str = &space_id;
+
+    let cursor = match since {
+        Some(since) => sqlx::query!(
+            "SELECT id, created_at FROM spaces_logs WHERE id = ? AND space_id = ?",
+            since,
+            space_id_str
+        )
+        .fetch_optional(&db)
+        .await
+        .expect("database")
+        .map(|v| (v.id, v.created_at))
+        .filter(|(_, created_at)| *created_at >= now - SYNC_DELETIONS_TTL_MS),
+        None => None,
+    };
+
+    let mut tx = app::begin(&db).await;
+    let (full, changed_since) = match &cursor {
+        Some((_, created_at)) => (false, *created_at),
+        None => (true, i64::MIN),
+    };
+
+    let accounts: Vec<_> = sqlx::query!(
+        r#"
+        SELECT pl_id, pl_name, pl_displayname, active, metadata, version
+        FROM spaces_accounts
+        WHERE space_id = ? AND updated_at >= ?"#,
+        space_id_str,
+        changed_since
+    )
+    .fetch_all(&mut *tx)
+    .await
+    .expect("database")
+    .into_iter()
+    .map(|v| SpaceAccount {
+        pl_id: v.pl_id,
+        space_id: space_id.clone(),
+        pl_name: v.pl_name,
+        pl_displayname: v.pl_displayname,
+        active: v.active,
+        metadata: serde_json::from_str(&v.metadata).expect("database metadata"),
+        version: v.version,
+    })
+    .collect();
+
+    let items = sqlx::query_as!(
+        SpaceItemWithoutSpaceID,
+        r#"
+        SELECT
+            id, title, ty, pl_serial, owner_id, current_holder, due_at,
+            metadata AS "metadata: MetadataJson", status, version
+        FROM spaces_items
+        WHERE space_id = ? AND updated_at >= ?"#,
+        space_id_str,
+        changed_since
+    )
+    .fetch_all(&mut *tx)
+    .await
+    .expect("database");
+
+    let (mut deleted_accounts, mut deleted_items) = (Vec::new(), Vec::new());
+    let mut logs = Vec::new();
+    let cursor = match cursor {
+        Some((since, created_at)) => {
+            let deletions = sqlx::query!(
+                "
+                SELECT object_ty, object_id FROM spaces_deletions
+                WHERE space_id = ? AND deleted_at >= ?",
+                space_id_str,
+                created_at
+            )
+            .fetch_all(&mut *tx)
+            .await
+            .expect("database");
+            // objects may be created again with same ID after deletion
+            for v in deletions {
+                match v.object_ty.as_str() {
+                    "account" if !accounts.iter().any(|a| a.pl_id == v.object_id) => {
+                        deleted_accounts.push(v.object_id)
+                    }
+                    "item" if !items.iter().any(|i| i.id == v.object_id) => {
+                        deleted_items.push(v.object_id)
+                    }
+                    _ => (),
+                }
+            }
+
+            logs = sqlx::query_as!(
+                SpaceLogEntry,
+                r#"
+                SELECT id, created_at, act AS "act: LogActionCode", sp_acc_id, sp_item_id, ref_id, detail
+                FROM spaces_logs
+                WHERE space_id = ?1 AND (created_at > ?2 OR created_at = ?2 AND id > ?3)
+                ORDER BY created_at, id
+                LIMIT ?4"#,
+                space_id_str,
+                created_at,
+                since,
+                SYNC_LOGS_LIMIT
+            )
+            .fetch_all(&mut *tx)
+            .await
+            .expect("database");
+
+            logs.last().map(|v| v.id.clone()).or(Some(since))
+        }
+        None => sqlx::query!(
+            "
+            SELECT id FROM spaces_logs
+            WHERE space_id = ?
+            ORDER BY created_at DESC, id DESC
+            LIMIT 1",
+            space_id_str
+        )
+        .fetch_optional(&mut *tx)
+        .await
+        .expect("database")
+        .map(|v| v.id),
+    };
+
+    Response::Success(SyncResponse {
+        full,
+        cursor,
+        more: logs.len() as i64 == SYNC_LOGS_LIMIT,
+        accounts,
+        items,
+        deleted_accounts,
+        deleted_items,
+        logs,
+    })
+}
+
+pub async fn space_events(
+    SpaceAccess { space_id, .. }: SpaceAccess<ReadSpaceLogs>,
+    headers: HeaderMap,
+    State(AppState { db, .. }): State<AppState>,
+) -> axum::response::Response {
+    let last_event_id = headers
+        .get("last-event-id")
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_owned);
+
+    let (tx, rx) = tokio::sync::mpsc::channel::<Result<Event, Infallible>>(64);
+    tokio::spawn(async move {
+        let space_id: &str = &space_id;
+
+        // resume after last received entry if it still exists
+        let mut cursor = LogCursor::now();
+        if let Some(last_event_id) = last_event_id {
+            let res = sqlx::query!(
+                "SELECT created_at FROM spaces_logs WHERE id = ? AND space_id = ?",
+                last_event_id,
+                space_id
+            )
+            .fetch_optional(&db)
+            .await;
+            if let Ok(Some(res)) = res {
+                cursor = LogCursor::at(res.created_at);
+                cursor.advance(&last_event_id, res.created_at);
+            }
+        }
+
+        let mut interval = tokio::time::interval(SSE_POLL_INTERVAL);
+        loop {
+            interval.tick().await;
+            if tx.is_closed() {
+                return;
+            }
+
+            let limit = cursor.limit(SSE_LOGS_LIMIT);
+            let res = sqlx::query_as!(
+                SpaceLogEntry,
+                r#"
+                SELECT id, created_at, act AS "act: LogActionCode", sp_acc_id, sp_item_id, ref_id, detail
+                FROM spaces_logs
+                WHERE space_id = ? AND created_at >= ?
+                ORDER BY created_at
+                LIMIT ?"#,
+                space_id,
+                cursor.created_at,
+                limit
+            )
+            .fetch_all(&db)
+            .await;
+            let res = match res {
+                Ok(v) => v,
+                Err(err) => {
+                    tracing::warn!(%err, "Failed to fetch space logs for events stream");
+                    return;
+                }
+            };
+
+            for log in res {
+                if !cursor.advance(&log.id, log.created_at) {
+                    continue;
+                }
+
+                let item = match &log.sp_item_id {
+                    Some(item_id) => sqlx::query_as!(
+                        SpaceItemWithoutSpaceID,
+                        r#"
+                        SELECT
+                            id, title, ty, pl_serial, owner_id, current_holder, due_at,
+                            metadata AS "metadata: MetadataJson", status, version
+                        FROM spaces_items
+                        WHERE id = ? AND space_id = ?"#,
+                        item_id,
+                        space_id
+                    )
+                    .fetch_optional(&db)
+                    .await
+                    .ok()
+                    .flatten(),
+                    None => None,
+                };
+
+                let event = Event::default()
+                    .id(&log.id)
+                    .event("log")
+                    .json_data(&log)
+                    .expect("json");
+                if tx.send(Ok(event)).await.is_err() {
+                    return;
+                }
+                if let Some(item) = item {
+                    let event = Event::default()
+                        .event("item")
+                        .json_data(&item)
+                        .expect("json");
+                    if tx.send(Ok(event)).await.is_err() {
+                        return;
+                    }
+                }
+            }
+        }
+    });
+
+    Sse::new(ReceiverStream::new(rx))
+        .keep_alive(KeepAlive::default())
+        .into_response()
+}
+
+pub async fn export_logs(
+    SpaceAccess { space_id, .. }: SpaceAccess<ReadSpaceLogs>,
+    Query(ExportQuery { format }): Query<ExportQuery>,
+    ReadDb(db): ReadDb,
+) -> axum::response::Response {
+    let (sender, res) = export::stream(format);
+    tokio::spawn(async move {
+        let space_id: &str = &space_id;
+        let rows = sqlx::query_as!(
+            SpaceLogEntry,
+            r#"
+            SELECT id, created_at, act AS "act: LogActionCode", sp_acc_id, sp_item_id, ref_id, detail
+            FROM spaces_logs
+            WHERE space_id = ?
+            ORDER BY created_at"#,
+            space_id
+        )
+        .fetch(&db);
+        sender.send_rows(rows).await;
+    });
+    res
+}
+
+pub async fn export_accounts(
+    SpaceAccess { space_id, .. }: SpaceAccess,
+    Query(ExportQuery { format }): Query<ExportQuery>,
+    ReadDb(db): ReadDb,
+) -> axum::response::Response {
+    let (sender, res) = export::stream(format);
+    tokio::spawn(async move {
+        let space_id: &str = &space_id;
+        let rows = sqlx::query_as!(
+            SpaceAccountWithoutSpaceID,
+            r#"SELECT pl_id, pl_name, pl_displayname, metadata AS "metadata: MetadataJson", version
+            FROM spaces_accounts
+            WHERE space_id = ?
+            ORDER BY pl_id"#,
+            space_id
+        )
+        .fetch(&db);
+        sender.send_rows(rows).await;
+    });
+    res
+}
+
+pub async fn export_items(
+    SpaceAccess { space_id, .. }: SpaceAccess,
+    Query(ExportQuery { format }): Query<ExportQuery>,
+    ReadDb(db): ReadDb,
+) -> axum::response::Response {
+    let (sender, res) = export::stream(format);
+    tokio::spawn(async move {
+        let space_id: &str = &space_id;
+        let rows = sqlx::query_as!(
+            SpaceItemWithoutSpaceID,
+            r#"
+            SELECT
+                id, title, ty, pl_serial, owner_id, current_holder, due_at,
+                metadata AS "metadata: MetadataJson", status, version
+            FROM spaces_items
+            WHERE space_id = ?
+            ORDER BY id"#,
+            space_id
+        )
+        .fetch(&db);
+        sender.send_rows(rows).await;
+    });
+    res
+}
+
+pub async fn get_logs(
+    SpaceAccess { space_id, .. }: SpaceAccess<ReadSpaceLogs>,
+    Query(LogsQuery {
+        page,
+        act,
+        from,
+        to,
+        acc_id,
+        item_id,
+    }): Query<LogsQuery>,
+    ReadDb(db): ReadDb,
+) -> Response<Vec<SpaceLogDetailedEntry>> {
+    let space_id: &str = &space_id;
+    let limit = 50;
+    let offset = (page as i64) * limit;
+
+    let res = sqlx::query!(
+        r#"
+        SELECT
+            spaces_logs.id,
+            spaces_logs.created_at,
+            spaces_logs.act,
+            spaces_logs.sp_acc_id,
+            spaces_logs.sp_item_id,
+            spaces_logs.ref_id,
+            spaces_logs.detail,
+            spaces_accounts.pl_id AS "acc_pl_id?",
+            spaces_accounts.pl_name AS acc_pl_name,
+            spaces_accounts.pl_displayname AS acc_pl_displayname,
+            spaces_accounts.metadata AS "acc_metadata?: MetadataJson",
+            spaces_accounts.version AS "acc_version?",
+            spaces_items.id AS "item_id?",
+            spaces_items.title AS "item_title?",
+            spaces_items.pl_serial AS "item_pl_serial?"
+        FROM spaces_logs
+            LEFT JOIN spaces_accounts
+                ON spaces_accounts.space_id = spaces_logs.space_id
+                    AND spaces_accounts.pl_id = spaces_logs.sp_acc_id
+            LEFT JOIN spaces_items
+                ON spaces_items.id = spaces_logs.sp_item_id
+        WHERE spaces_logs.space_id = ?1
+            AND (?2 IS NULL OR spaces_logs.act = ?2)
+            AND (?3 IS NULL OR spaces_logs.created_at >= ?3)
+            AND (?4 IS NULL OR spaces_logs.created_at < ?4)
+            AND (?5 IS NULL OR spaces_logs.sp_acc_id = ?5)
+            AND (?6 IS NULL OR spaces_logs.sp_item_id = ?6)
+        ORDER BY spaces_logs.created_at DESC
+        LIMIT ?7 OFFSET ?8"#,
+        space_id,
+        act,
+        from,
+        to,
+        acc_id,
+        item_id,
+        limit,
+        offset
+    )
+    .fetch_all(&db)
+    .await
+    .expect("database");
+
+    let log_ids =
+        serde_json::to_string(&res.iter().map(|v| &v.id).collect::<Vec<_>>()).expect("json");
+    let comments = sqlx::query_as!(
+        SpaceLogComment,
+        r#"
+        SELECT id, log_id, created_at, user_id, service_id, text
+        FROM spaces_logs_comments
+        WHERE space_id = ? AND log_id IN (SELECT value FROM json_each(?))
+        ORDER BY created_at"#,
+        space_id,
+        log_ids
+    )
+    .fetch_all(&db)
+    .await
+    .expect("database");
+    let mut comments = comments
+        .into_iter()
+        .fold(HashMap::<_, Vec<_>>::new(), |mut acc, v| {
+            acc.entry(v.log_id.clone()).or_default().push(v);
+            acc
+        });
+
+    Response::Success(
+        res.into_iter()
+            .map(|v| SpaceLogDetailedEntry {
+                comments: comments.remove(&v.id).unwrap_or_default(),
+                account: v.acc_pl_id.map(|pl_id| SpaceAccountWithoutSpaceID {
+                    pl_id,
+                    pl_name: v.acc_pl_name,
+                    pl_displayname: v.acc_pl_displayname,
+                    metadata: v.acc_metadata.unwrap_or_default(),
+                    version: v.acc_version.unwrap_or_default(),
+                }),
+                item: v.item_id.zip(v.item_title).zip(v.item_pl_serial).map(
+                    |((id, title), pl_serial)| SpaceLogItem {
+                        id,
+                        title,
+                        pl_serial,
+                    },
+                ),
+                log: SpaceLogEntry {
+                    id: v.id,
+                    created_at: v.created_at,
+                    act: v.act.into(),
+                    sp_acc_id: v.sp_acc_id,
+                    sp_item_id: v.sp_item_id,
+                    ref_id: v.ref_id,
+                    detail: v.detail,
+                },
+            })
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::sqlite::SqlitePoolOptions;
+
+    use super::*;
+
+    #[test]
+    fn bodies_are_validated() {
+        let err = serde_json::from_str::<Valid<PatchSpace>>(r#"{"title": "  "}"#)
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("invalid `title`"));
+
+        let account = |pl_id: &str| SpaceAccountWithoutSpaceID {
+            pl_id: pl_id.into(),
+            pl_name: None,
+            pl_displayname: None,
+            metadata: Default::default(),
+            version: 0,
+        };
+        assert!(account("tg:42").validate().is_ok());
+        for pl_id in ["", "a/b", &"x".repeat(129)] {
+            assert_eq!(account(pl_id).validate().unwrap_err().field, "pl_id");
+        }
+    }
+
+    #[tokio::test]
+    async fn insert_logs_in_chunks() {
+        let db = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .expect("database");
+        crate::apply_migrations(&db).await.expect("migrations");
+        sqlx::query("INSERT INTO users(id, name, password_hash) VALUES ('u', 'u', '')")
+            .execute(&db)
+            .await
+            .expect("database");
+        let space_id = SpaceID::new();
+        sqlx::query("INSERT INTO spaces(id, title, owner_id) VALUES (?, 'Lab', 'u')")
+            .bind(&*space_id)
+            .execute(&db)
+            .await
+            .expect("database");
+
+        let logs: Vec<_> = (0..INSERT_LOGS_CHUNK * 2 + 1)
+            .map(|i| {
+                SpaceLog::new(space_id.clone(), SpaceLogAction::ReportFiled)
+                    .with_detail(i.to_string())
+            })
+            .collect();
+        let mut conn = db.acquire().await.expect("database");
+        let mut store = DbLogStore(&mut conn);
+        let inserted = store.insert_many(&logs).await.expect("database");
+        assert_eq!(inserted, logs.len() as u64);
+
+        let last = &logs[logs.len() - 1];
+        let detail: Option<String> =
+            sqlx::query_scalar("SELECT detail FROM spaces_logs WHERE id = ?")
+                .bind(&last.id)
+                .fetch_one(&mut *store.0)
+                .await
+                .expect("database");
+        assert_eq!(detail, last.detail);
+
+        // duplicate ID in last chunk rolls back whole batch
+        let mut batch: Vec<_> = (0..INSERT_LOGS_CHUNK)
+            .map(|_| SpaceLog::new(space_id.clone(), SpaceLogAction::ReportFiled))
+            .collect();
+        batch.push(last.clone());
+        store.insert_many(&batch).await.unwrap_err();
+        drop(conn);
+        let count: i64 = sqlx::query_scalar("SELECT COUNT(1) FROM spaces_logs")
+            .fetch_one(&db)
+            .await
+            .expect("database");
+        assert_eq!(count, logs.len() as i64);
+    }
+
+    #[tokio::test]
+    async fn one_decision_per_request() {
+        let db = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .expect("database");
+        crate::apply_migrations(&db).await.expect("migrations");
+        sqlx::query("INSERT INTO users(id, name, password_hash) VALUES ('u', 'u', '')")
+            .execute(&db)
+            .await
+            .expect("database");
+        let space_id = SpaceID::new();
+        sqlx::query("INSERT INTO spaces(id, title, owner_id) VALUES (?, 'Lab', 'u')")
+            .bind(&*space_id)
+            .execute(&db)
+            .await
+            .expect("database");
+
+        let request = SpaceLog::new(space_id.clone(), SpaceLogAction::UnlockRequested);
+        insert_log(&db, &request).await.expect("database");
+        let decision = |act| SpaceLog::new(space_id.clone(), act).with_ref(request.id.clone());
+        insert_log(&db, &decision(SpaceLogAction::UnlockApproved))
+            .await
+            .expect("database");
+        for act in [SpaceLogAction::UnlockApproved, SpaceLogAction::UnlockDenied] {
+            let err = insert_log(&db, &decision(act)).await.unwrap_err();
+            assert!(err
+                .as_database_error()
+                .is_some_and(|v| v.is_unique_violation()));
+        }
+
+        // other entries may refer to request many times
+        for _ in 0..2 {
+            insert_log(&db, &decision(SpaceLogAction::ReportFiled))
+                .await
+                .expect("database");
+        }
+    }
+
+    #[tokio::test]
+    async fn duplicate_decisions_are_superseded() {
+        let db = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .expect("database");
+        // database as before unique indexes of decisions, with duplicates left by races
+        let mut migrator = sqlx::migrate!();
+        migrator.migrations = migrator
+            .migrations
+            .iter()
+            .filter(|v| v.version < 41)
+            .cloned()
+            .collect();
+        migrator.run(&db).await.expect("migrations");
+        sqlx::query("INSERT INTO users(id, name, password_hash) VALUES ('u', 'u', '')")
+            .execute(&db)
+            .await
+            .expect("database");
+        sqlx::query("INSERT INTO spaces(id, title, owner_id) VALUES ('s', 'Lab', 'u')")
+            .execute(&db)
+            .await
+            .expect("database");
+        for (id, created_at, act) in [
+            ("request", 1, 500),
+            ("denied", 3, 502),
+            ("approved", 2, 501),
+            ("report", 1, 600),
+            ("resolved", 2, 601),
+            ("resolved-again", 2, 601),
+        ] {
+            let ref_id =
+                (act != 500 && act != 600).then_some(if act == 601 { "report" } else { "request" });
+            sqlx::query(
+                "INSERT INTO spaces_logs(id, space_id, created_at, act, ref_id) VALUES (?, 's', ?, ?, ?)",
+            )
+            .bind(id)
+            .bind(created_at)
+            .bind(act)
+            .bind(ref_id)
+            .execute(&db)
+            .await
+            .expect("database");
+        }
+
+        crate::apply_migrations(&db).await.expect("migrations");
+
+        let logs: Vec<(String, bool)> =
+            sqlx::query_as("SELECT id, superseded FROM spaces_logs ORDER BY rowid")
+                .fetch_all(&db)
+                .await
+                .expect("database");
+        assert_eq!(
+            logs,
+            [
+                ("request".into(), false),
+                ("denied".into(), true),
+                ("approved".into(), false),
+                ("report".into(), false),
+                ("resolved".into(), false),
+                ("resolved-again".into(), true),
+            ]
+        );
+    }
+}
@@ -1,32 +1,49 @@
 use archk::{
     v1::{
         api::{self, Response},
+        audit::{AuditAction, AuditLog},
         auth::{Token, TokenTy},
+        models::MayIgnored,
+        space::SpaceID,
         user::{
-            is_valid_username,
             ssh::{UserSSHKey, UserSSHKeyID},
-            User, UserID,
+            User, UserID, UserTelegramAuth,
         },
+        validate,
     },
     Documentation,
 };
-use axum::{
-    extract::{Path, Query, State},
-    Json,
-};
+use axum::extract::{Query, State};
 use rand::{distributions::Alphanumeric, Rng};
 use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
 use uuid::Uuid;
 
-use crate::{app::AppState, roles::UserRole};
+use crate::{
+    app::{self, AppState},
+    jobs,
+    notify::{self, Notifier},
+    roles::{perm, UserRole, UserRoles},
+    tokens,
+};
 
-use super::extra::{AuthenticatedUser, DbUser};
+use super::{
+    admin::insert_audit,
+    extra::{scope, AuthenticatedUser, ClientIp, DbUser, Json, Path, ReadDb, ScopedUser},
+    quota,
+    space::erase_space,
+};
 
 #[derive(Deserialize, Documentation)]
 pub struct RegisterRequestData {
-    /// Username
+    /// Username, letters, digits and dots
+    #[doc_example = "greg.b42"]
+    #[doc_min = 3]
+    #[doc_max = 31]
     pub username: String,
     /// Plain password
+    #[doc_min = 8]
+    #[doc_max = 32]
     pub password: String,
     /// Invite string or empty string (`""`) if first user
     pub invite: String,
@@ -44,27 +61,172 @@ pub struct PatchUser {
     /// Plain old password
     pub old_password: String,
     /// Plain new password
+    #[doc_min = 8]
+    #[doc_max = 32]
     pub new_password: String,
     /// Revoka all tokens. Default is `false`
     #[serde(default)]
     pub logout: bool,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Documentation)]
 pub struct UserIDPath {
-    pub user_id: String,
+    /// User ID
+    pub user_id: UserID,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Documentation)]
 pub struct Paging {
+    /// Page number starting from `0`, page contains up to 50 entries
+    #[serde(default)]
+    pub page: u32,
+}
+
+#[derive(Deserialize, Documentation)]
+pub struct SpacesQuery {
+    /// Page number starting from `0`, page contains up to 50 entries
     #[serde(default)]
     pub page: u32,
+    /// Include archived spaces
+    #[serde(default)]
+    pub archived: bool,
+}
+
+#[derive(Deserialize, Documentation)]
+pub struct PermissionsQuery {
+    /// Level to get permissions of, own level by default
+    #[serde(default)]
+    pub level: Option<i64>,
+}
+
+#[derive(Serialize, Documentation)]
+pub struct PermissionsResponse {
+    /// Level permissions are granted to
+    pub level: i64,
+    /// Name of current role of level, `null` if level is below all roles
+    pub role: Option<String>,
+    /// Granted permissions with wildcards expanded, eg. `["space.create", "space.manage"]`
+    pub permissions: Vec<String>,
+}
+
+fn permissions(roles: &UserRoles, level: i64) -> PermissionsResponse {
+    PermissionsResponse {
+        level,
+        role: roles.get_current(level).map(|v| v.name.clone()),
+        permissions: roles
+            .permissions(level)
+            .into_iter()
+            .map(String::from)
+            .collect(),
+    }
 }
 
+/// Either `level` or `role` should be set.
 #[derive(Deserialize, Documentation)]
 pub struct PromoteUserBody {
     /// Level to promote
+    #[serde(default)]
+    pub level: Option<i64>,
+    /// Name of role to promote to, eg. `"moderator"`. Case-insensitive
+    #[serde(default)]
+    pub role: Option<String>,
+}
+
+#[derive(Serialize, Documentation)]
+pub struct PromoteUserResponse {
+    /// New level of user
     pub level: i64,
+    /// Role of new level, `null` if level is below all roles
+    pub role: Option<UserRole>,
+}
+
+#[derive(Deserialize, Documentation)]
+pub struct InviteTreeQuery {
+    /// Build tree from this user only. By default tree is built from all users
+    /// without inviter
+    #[serde(default)]
+    pub root: Option<String>,
+}
+
+#[derive(Deserialize, Documentation)]
+pub struct DeleteUserQuery {
+    /// Transfer owned spaces to this user instead of deleting them
+    #[serde(default)]
+    pub transfer_spaces_to: Option<String>,
+    /// Only report what would be deleted, user is kept
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+#[derive(Deserialize, Documentation)]
+pub struct LockUserBody {
+    /// Lock duration in seconds
+    #[doc_min = 1]
+    pub duration_secs: i64,
+}
+
+#[derive(Deserialize, Documentation)]
+pub struct ImpersonateBody {
+    /// Lifetime of token in seconds, up to one hour. Default is 15 minutes
+    #[serde(default = "default_impersonation_secs")]
+    #[doc_min = 1]
+    #[doc_max = 3600]
+    pub duration_secs: i64,
+}
+
+fn default_impersonation_secs() -> i64 {
+    15 * 60
+}
+
+/// Maximum lifetime of impersonation token in seconds
+const MAX_IMPERSONATION_SECS: i64 = 60 * 60;
+
+#[derive(Serialize, Documentation)]
+pub struct UserNotifications {
+    /// Telegram chat ID (or `@channelusername`) to message by bot, if any
+    pub telegram_chat_id: Option<String>,
+    /// Email address, if any
+    pub email: Option<String>,
+    /// URL receiving JSON `POST` requests, if any
+    pub webhook_url: Option<String>,
+    /// Notify about reports filed in owned spaces
+    pub report_filed: bool,
+    /// Notify about denied unlocks of owned spaces
+    pub unlock_denied: bool,
+    /// Notify about overdue items of owned spaces
+    pub item_overdue: bool,
+    /// Channels configured on server, eg. `["telegram", "email"]`
+    pub channels: Vec<String>,
+}
+
+#[derive(Deserialize, Documentation)]
+pub struct PatchNotificationsBody {
+    /// Telegram chat ID or `@channelusername`, `null` disables channel
+    #[serde(default, skip_serializing_if = "MayIgnored::is_ignored")]
+    pub telegram_chat_id: MayIgnored<Option<String>>,
+    /// Email address, `null` disables channel
+    #[serde(default, skip_serializing_if = "MayIgnored::is_ignored")]
+    pub email: MayIgnored<Option<String>>,
+    /// `http://` or `https://` URL, `null` disables channel
+    #[serde(default, skip_serializing_if = "MayIgnored::is_ignored")]
+    pub webhook_url: MayIgnored<Option<String>>,
+    /// Notify about reports filed in owned spaces
+    #[serde(default, skip_serializing_if = "MayIgnored::is_ignored")]
+    pub report_filed: MayIgnored<bool>,
+    /// Notify about denied unlocks of owned spaces
+    #[serde(default, skip_serializing_if = "MayIgnored::is_ignored")]
+    pub unlock_denied: MayIgnored<bool>,
+    /// Notify about overdue items of owned spaces
+    #[serde(default, skip_serializing_if = "MayIgnored::is_ignored")]
+    pub item_overdue: MayIgnored<bool>,
+}
+
+#[derive(Serialize, Documentation)]
+pub struct TelegramAuthResponse {
+    /// Code to send to Telegram bot, eg. `/start <code>`
+    pub code: String,
+    /// Timestamp in milliseconds of code expiration
+    pub expires_at: i64,
 }
 
 #[derive(Deserialize, Documentation)]
@@ -73,9 +235,92 @@ pub struct UploadSSHKeyBody {
     pub pubkey: String,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Documentation)]
 pub struct SSHKeyPath {
-    pub key_id: String,
+    /// SSH key ID
+    pub key_id: UserSSHKeyID,
+}
+
+#[derive(Deserialize, Documentation)]
+pub struct SessionPath {
+    /// "Issued at" of session token, see `iat` in session list
+    pub iat: i64,
+}
+
+#[derive(Serialize, Documentation)]
+pub struct SessionResponse {
+    /// "Issued at" of token, timestamp in milliseconds
+    pub iat: i64,
+    /// Is it token of current request
+    pub current: bool,
+    /// User agent of last usage, if any
+    pub user_agent: Option<String>,
+    /// IP address of last usage, if any
+    pub ip: Option<String>,
+    /// Timestamp in milliseconds of last usage, if any. Updated at most once per minute
+    pub last_used_at: Option<i64>,
+    /// Space token is restricted to, if any (see `PUT /user/tokens`)
+    pub space_id: Option<String>,
+    /// Scopes of token restricted to space or scopes (eg. of Telegram bot)
+    pub scopes: Option<Vec<String>>,
+    /// Timestamp in milliseconds of token expiration, if any
+    pub expires_at: Option<i64>,
+    /// ID of admin who issued token, if issued by `POST /user/@:user_id/impersonate`
+    pub impersonated_by: Option<String>,
+}
+
+#[derive(Deserialize, Documentation)]
+pub struct ScopedTokenBody {
+    /// Space token is restricted to
+    pub space_id: SpaceID,
+    /// Scopes of token: `space.read`, `space.write`, `space.logs.read`,
+    /// `space.logs.manage`, `space.unlocks`
+    pub scopes: Vec<String>,
+}
+
+#[derive(Serialize, Documentation)]
+pub struct ScopedTokenResponse {
+    /// Bearer token
+    pub token: String,
+    /// "Issued at" of token, revoke token with `DELETE /user/sessions/:iat`
+    pub iat: i64,
+}
+
+#[derive(Serialize, Documentation)]
+pub struct ImpersonateResponse {
+    /// Bearer token of user
+    pub token: String,
+    /// Timestamp in milliseconds of token expiration
+    pub expires_at: i64,
+}
+
+#[derive(Serialize, Documentation)]
+pub struct InviteTreeEntry {
+    /// User object
+    pub user: User,
+    /// Depth of user in tree, `0` for roots
+    pub depth: i64,
+}
+
+/// Number of rows affected by user deletion
+#[derive(Serialize, Documentation, Default)]
+pub struct DeleteUserReport {
+    /// Revoked tokens
+    pub tokens: u64,
+    /// Deleted unused invites
+    pub invites: u64,
+    /// Deleted SSH keys
+    pub ssh_keys: u64,
+    /// Deleted OpenID Connect identities
+    pub identities: u64,
+    /// Users invited by deleted user, their `invited_by` is cleared
+    pub invitees: u64,
+    /// Audit log entries with user cleared
+    pub audit_log: u64,
+    /// Spaces transferred to other user
+    pub spaces_transferred: u64,
+    /// Deleted spaces, with their accounts, items, logs and services
+    pub spaces_deleted: u64,
 }
 
 #[derive(Serialize, Documentation)]
@@ -110,12 +355,14 @@ pub struct UserSpaceResponse {
     pub id: String,
     /// Space title
     pub title: String,
+    /// Is space archived?
+    pub archived: bool,
 }
 
 pub async fn get_users(
     _: AuthenticatedUser,
     Query(Paging { page }): Query<Paging>,
-    State(AppState { db, .. }): State<AppState>,
+    ReadDb(db): ReadDb,
 ) -> Response<Vec<User>> {
     let (offset, limit) = ((page as i64) * 50, 50);
 
@@ -141,19 +388,19 @@ pub async fn get_users(
 }
 
 pub async fn get_self(
-    AuthenticatedUser {
-        user:
-            DbUser {
-                id,
-                name,
-                invites,
-                invited_by,
-                level,
-                ..
-            },
-        ..
-    }: AuthenticatedUser<DbUser>,
+    AuthenticatedUser { user, .. }: AuthenticatedUser<ScopedUser>,
 ) -> Response<SelfResponse> {
+    if let Err(e) = user.require(scope::NOTIFICATIONS) {
+        return Response::Failture(e);
+    }
+    let DbUser {
+        id,
+        name,
+        invites,
+        invited_by,
+        level,
+        ..
+    } = user.user;
     Response::Success(SelfResponse {
         user: User {
             id: UserID::from(id).expect("checked UserID unwrap"),
@@ -170,14 +417,15 @@ pub async fn get_user(
     Path(UserIDPath { user_id }): Path<UserIDPath>,
     State(AppState { db, .. }): State<AppState>,
 ) -> Response<User> {
-    let user = sqlx::query!("SELECT name, invited_by FROM users WHERE id = ?", user_id)
+    let id: &str = &user_id;
+    let user = sqlx::query!("SELECT name, invited_by FROM users WHERE id = ?", id)
         .fetch_optional(&db)
         .await
         .expect("database");
 
     match user {
         Some(v) => Response::Success(User {
-            id: UserID::from(user_id).expect("checked(db) UserID::from"),
+            id: user_id,
             name: v.name,
             invited_by: v.invited_by,
         }),
@@ -186,7 +434,12 @@ pub async fn get_user(
 }
 
 pub async fn register(
-    State(AppState { db, roles, .. }): State<AppState>,
+    State(AppState {
+        db,
+        roles,
+        token_format,
+        ..
+    }): State<AppState>,
     Json(RegisterRequestData {
         username,
         password,
@@ -198,10 +451,11 @@ pub async fn register(
     // 3. try to create user (and check for unique keys)
     // 4. create token
     // 5. drop invite
-    if !is_valid_username(&username) || !matches!(password.len(), 8..=32) {
-        return Response::Failture(
-            api::Error::MalformedData.detail("Invalid username or password".into()),
-        );
+    if let Err(e) = validate::username(&username) {
+        return Response::Failture(e.into());
+    }
+    if let Err(e) = validate::password(&password) {
+        return Response::Failture(e.into());
     }
 
     let invited_by = if invite.is_empty() {
@@ -231,9 +485,11 @@ pub async fn register(
     let level = invite
         .is_empty()
         .then_some(0)
-        .map(|_| roles.get_max().level)
+        .map(|_| roles.load().get_max().level)
         .unwrap_or(0);
 
+    let mut tx = app::begin(&db).await;
+
     let res = sqlx::query!(
         "INSERT INTO users(id, name, invited_by, level, password_hash) VALUES (?, ?, ?, ?, ?)",
         user_id_str,
@@ -242,7 +498,7 @@ pub async fn register(
         level,
         password
     )
-    .execute(&db)
+    .execute(&mut *tx)
     .await;
 
     match res {
@@ -254,28 +510,36 @@ pub async fn register(
         _ => res.expect("database"),
     };
 
-    let token = Token::new(TokenTy::Personal);
+    let token = Token::with_format(TokenTy::Personal, token_format);
     let token_str = token.to_string();
 
     let iat = token.iat as i64;
-    let rnd = token.rnd as i64;
+    let hash = tokens::hash(&token);
     sqlx::query!(
-        "INSERT INTO tokens(iat, rnd, user_id) VALUES (?, ?, ?)",
+        "INSERT INTO tokens(iat, hash, user_id) VALUES (?, ?, ?)",
         iat,
-        rnd,
+        hash,
         user_id_str
     )
-    .execute(&db)
+    .execute(&mut *tx)
     .await
     .expect("database");
 
     if !invite.is_empty() {
-        sqlx::query!("DELETE FROM invites WHERE id = ?", invite)
-            .execute(&db)
+        let res = sqlx::query!("DELETE FROM invites WHERE id = ?", invite)
+            .execute(&mut *tx)
             .await
-            .expect("database");
+            .expect("database")
+            .rows_affected();
+
+        // invite was used by concurrent request
+        if res == 0 {
+            return Response::Failture(api::Error::ObjectNotFound.detail("Invalid invite".into()));
+        }
     }
 
+    tx.commit().await.expect("database");
+
     Response::Success(RegisterResponse {
         user: User {
             id: user_id,
@@ -295,15 +559,15 @@ pub async fn patch_user(
         },
         token,
     }: AuthenticatedUser<DbUser>,
-    State(AppState { db, .. }): State<AppState>,
+    State(AppState { db, cache, .. }): State<AppState>,
     Json(PatchUser {
         old_password,
         new_password,
         logout,
     }): Json<PatchUser>,
 ) -> Response<u64> {
-    if !matches!(new_password.len(), 3..=32) {
-        return Response::Failture(api::Error::MalformedData.detail("Invalid new password".into()));
+    if let Err(e) = validate::password(&new_password) {
+        return Response::Failture(e.into());
     }
 
     if !bcrypt::verify(old_password, &password_hash).unwrap_or(false) {
@@ -311,47 +575,57 @@ pub async fn patch_user(
     }
 
     let new_password = bcrypt::hash(new_password, crate::app::BCRYPT_COST).expect("bcrypt");
+    let mut tx = app::begin(&db).await;
     sqlx::query!(
         "UPDATE users SET password_hash = ? WHERE id = ?",
         new_password,
         user_id
     )
-    .execute(&db)
+    .execute(&mut *tx)
     .await
     .expect("database");
 
-    if logout {
-        let iat = token.iat as i64;
-        let rnd = token.rnd as i64;
-        let res = sqlx::query!(
-            "DELETE FROM tokens WHERE user_id = ? AND iat != ? AND rnd != ?",
+    let res = if logout {
+        // users are always authenticated by token
+        let hash = token.as_ref().map(tokens::hash);
+        sqlx::query!(
+            "DELETE FROM tokens WHERE user_id = ? AND hash IS NOT ?",
             user_id,
-            iat,
-            rnd
+            hash
         )
-        .execute(&db)
+        .execute(&mut *tx)
         .await
-        .expect("database");
-
-        Response::Success(res.rows_affected())
+        .expect("database")
+        .rows_affected()
     } else {
-        Response::Success(0)
+        0
+    };
+
+    tx.commit().await.expect("database");
+    if res > 0 {
+        cache.revoke_user_tokens(&user_id);
     }
+
+    Response::Success(res)
 }
 
 pub async fn reset_user_password(
     Path(UserIDPath { user_id }): Path<UserIDPath>,
     AuthenticatedUser {
-        user: DbUser { level, .. },
+        user: DbUser {
+            id: actor_id,
+            level,
+            ..
+        },
         ..
     }: AuthenticatedUser<DbUser>,
-    State(AppState { db, roles, .. }): State<AppState>,
+    ClientIp(ip): ClientIp,
+    State(AppState {
+        db, roles, cache, ..
+    }): State<AppState>,
 ) -> Response<ResetPasswordResponse> {
-    if roles
-        .get_current(level)
-        .filter(|v| v.permissions.manage)
-        .is_none()
-    {
+    let user_id: &str = &user_id;
+    if !roles.load().has(level, perm::USER_MANAGE) {
         return Response::Failture(api::Error::Forbidden.into());
     }
 
@@ -362,12 +636,13 @@ pub async fn reset_user_password(
         .collect();
 
     let password_hash = bcrypt::hash(&password, crate::app::BCRYPT_COST).expect("bcrypt");
+    let mut tx = app::begin(&db).await;
     let res = sqlx::query!(
         "UPDATE users SET password_hash = ? WHERE id = ?",
         password_hash,
         user_id
     )
-    .execute(&db)
+    .execute(&mut *tx)
     .await
     .expect("database");
 
@@ -376,10 +651,18 @@ pub async fn reset_user_password(
     }
 
     let res = sqlx::query!("DELETE FROM tokens WHERE user_id = ?", user_id)
-        .execute(&db)
+        .execute(&mut *tx)
         .await
         .expect("database");
 
+    let log = AuditLog::new(actor_id, AuditAction::PasswordReset)
+        .with_target(user_id.to_string())
+        .with_ip(ip);
+    insert_audit(&mut *tx, &log).await.expect("database");
+
+    tx.commit().await.expect("database");
+    cache.revoke_user_tokens(user_id);
+
     Response::Success(ResetPasswordResponse {
         password,
         tokens_reset: res.rows_affected(),
@@ -420,24 +703,33 @@ pub async fn create_invite(
         return Response::Failture(api::Error::Forbidden.into());
     }
 
-    let invite_id = Uuid::new_v4().to_string();
-    sqlx::query!(
-        "INSERT INTO invites(id, owner_id) VALUES (?, ?)",
-        invite_id,
+    let mut tx = app::begin(&db).await;
+
+    // `invites > 0` is checked again for concurrent requests
+    let res = sqlx::query!(
+        "UPDATE users SET invites = invites - 1 WHERE id = ? AND invites > 0",
         user_id
     )
-    .execute(&db)
+    .execute(&mut *tx)
     .await
-    .expect("database");
+    .expect("database")
+    .rows_affected();
+    if res == 0 {
+        return Response::Failture(api::Error::Forbidden.into());
+    }
 
+    let invite_id = Uuid::new_v4().to_string();
     sqlx::query!(
-        "UPDATE users SET invites = invites - 1 WHERE id = ?",
+        "INSERT INTO invites(id, owner_id) VALUES (?, ?)",
+        invite_id,
         user_id
     )
-    .execute(&db)
+    .execute(&mut *tx)
     .await
     .expect("database");
 
+    tx.commit().await.expect("database");
+
     Response::Success(invite_id)
 }
 
@@ -449,30 +741,24 @@ pub async fn invite_wave(
     }: AuthenticatedUser<DbUser>,
     State(AppState { db, roles, .. }): State<AppState>,
 ) -> Response<u64> {
-    if !roles
-        .get_current(level)
-        .map(|v| v.permissions.wave)
-        .unwrap_or(false)
-    {
+    if !roles.load().has(level, perm::USER_WAVE) {
         return Response::Failture(api::Error::Forbidden.into());
     }
 
-    let res = sqlx::query!(
-        "UPDATE users SET invites = invites + 1 WHERE level >= ?",
-        min_level
-    )
-    .execute(&db)
-    .await
-    .expect("database");
+    let mut tx = app::begin(&db).await;
+    let res = jobs::invite_wave(&mut tx, &roles.load(), min_level)
+        .await
+        .expect("database");
+    tx.commit().await.expect("database");
 
-    Response::Success(res.rows_affected())
+    Response::Success(res)
 }
 
 pub async fn get_all_roles(
     _: AuthenticatedUser, // NOTE: for all users?
     State(AppState { roles, .. }): State<AppState>,
-) -> Response<&'static Vec<UserRole>> {
-    Response::Success(&roles.0)
+) -> Response<Vec<UserRole>> {
+    Response::Success(roles.load().0.clone())
 }
 
 pub async fn get_user_role(
@@ -482,12 +768,9 @@ pub async fn get_user_role(
         ..
     }: AuthenticatedUser<DbUser>,
     State(AppState { db, roles, .. }): State<AppState>,
-) -> Response<&'static UserRole> {
-    if !roles
-        .get_current(level)
-        .map(|v| v.permissions.promote)
-        .unwrap_or(false)
-    {
+) -> Response<UserRole> {
+    let user_id: &str = &user_id;
+    if !roles.load().has(level, perm::USER_PROMOTE) {
         return Response::Failture(api::Error::Forbidden.into());
     }
 
@@ -496,35 +779,97 @@ pub async fn get_user_role(
         .await
         .expect("database");
 
-    match res.and_then(|v| roles.get_current(v.level)) {
+    match res.and_then(|v| roles.load().get_current(v.level).cloned()) {
         Some(v) => Response::Success(v),
         None => Response::Failture(api::Error::ObjectNotFound.into()),
     }
 }
 
-pub async fn promote_user(
+pub async fn get_permissions(
+    Query(PermissionsQuery { level: for_level }): Query<PermissionsQuery>,
+    AuthenticatedUser {
+        user: DbUser { level, .. },
+        ..
+    }: AuthenticatedUser<DbUser>,
+    State(AppState { roles, .. }): State<AppState>,
+) -> Response<PermissionsResponse> {
+    // roles are public (see `get_all_roles`), so any level may be simulated
+    Response::Success(permissions(&roles.load(), for_level.unwrap_or(level)))
+}
+
+pub async fn get_user_permissions(
     Path(UserIDPath { user_id }): Path<UserIDPath>,
     AuthenticatedUser {
         user: DbUser { level, .. },
         ..
     }: AuthenticatedUser<DbUser>,
     State(AppState { db, roles, .. }): State<AppState>,
-    Json(PromoteUserBody { level: to_level }): Json<PromoteUserBody>,
-) -> Response<u64> {
-    if to_level > level
-        && !roles
-            .get_current(level)
-            .map(|v| v.permissions.promote)
-            .unwrap_or(false)
-    {
+) -> Response<PermissionsResponse> {
+    let user_id: &str = &user_id;
+    let roles = roles.load();
+    if !roles.has(level, perm::USER_PROMOTE) {
         return Response::Failture(api::Error::Forbidden.into());
     }
 
+    let res = sqlx::query_scalar!("SELECT level FROM users WHERE id = ?", user_id)
+        .fetch_optional(&db)
+        .await
+        .expect("database");
+
+    match res {
+        Some(v) => Response::Success(permissions(&roles, v)),
+        None => Response::Failture(api::Error::ObjectNotFound.into()),
+    }
+}
+
+pub async fn promote_user(
+    Path(UserIDPath { user_id }): Path<UserIDPath>,
+    AuthenticatedUser {
+        user: DbUser { id, level, .. },
+        ..
+    }: AuthenticatedUser<DbUser>,
+    State(AppState { db, roles, .. }): State<AppState>,
+    Json(PromoteUserBody {
+        level: to_level,
+        role,
+    }): Json<PromoteUserBody>,
+) -> Response<PromoteUserResponse> {
+    let user_id: &str = &user_id;
+    let roles = roles.load();
+    let to_level = match (to_level, role) {
+        (Some(v), None) => v,
+        (None, Some(name)) => match roles.get_by_name(&name) {
+            Some(v) => v.level,
+            None => {
+                return Response::Failture(
+                    api::Error::ObjectNotFound.detail(format!("no role named `{name}`").into()),
+                )
+            }
+        },
+        _ => {
+            return Response::Failture(
+                api::Error::MalformedData.detail("either `level` or `role` expected".into()),
+            )
+        }
+    };
+
+    let target = sqlx::query_scalar!("SELECT level FROM users WHERE id = ?", user_id)
+        .fetch_optional(&db)
+        .await
+        .expect("database");
+    let Some(target) = target else {
+        return Response::Failture(api::Error::ObjectNotFound.into());
+    };
+    if let Err(e) = roles.check_promotion(level, target, to_level, user_id == id) {
+        return Response::Failture(api::Error::Forbidden.detail(e.to_string().into()));
+    }
+
+    // level is checked again, so concurrent change is not overwritten
     let res = sqlx::query!(
-        "UPDATE users SET level = ? WHERE id = ? AND level < ?",
+        "UPDATE users SET level = ? WHERE id = ? AND level = ?",
         to_level,
         user_id,
-        level
+        target
     )
     .execute(&db)
     .await
@@ -532,23 +877,30 @@ pub async fn promote_user(
 
     match res.rows_affected() {
         0 => Response::Failture(
-            api::Error::ObjectNotFound.detail("User does not exists or have too big level".into()),
+            api::Error::Conflict.detail("level of user was changed concurrently".into()),
         ),
-        v => Response::Success(v),
+        _ => Response::Success(PromoteUserResponse {
+            level: to_level,
+            role: roles.get_current(to_level).cloned(),
+        }),
     }
 }
 
 pub async fn get_spaces(
-    Query(Paging { page }): Query<Paging>,
-    AuthenticatedUser { user, .. }: AuthenticatedUser,
+    Query(SpacesQuery { page, archived }): Query<SpacesQuery>,
+    AuthenticatedUser { user, .. }: AuthenticatedUser<ScopedUser>,
     State(AppState { db, .. }): State<AppState>,
 ) -> Response<Vec<UserSpaceResponse>> {
+    if let Err(e) = user.require(scope::UNLOCKS) {
+        return Response::Failture(e);
+    }
     let limit = 50;
     let offset = page * limit;
-    let user_id: &str = &user;
+    let user_id: &str = &user.user.id;
     let res = sqlx::query!(
-        "SELECT * FROM spaces WHERE owner_id = ? LIMIT ? OFFSET ?",
+        "SELECT * FROM spaces WHERE owner_id = ? AND (? OR NOT archived) LIMIT ? OFFSET ?",
         user_id,
+        archived,
         limit,
         offset
     )
@@ -561,13 +913,14 @@ pub async fn get_spaces(
             .map(|v| UserSpaceResponse {
                 id: v.id,
                 title: v.title,
+                archived: v.archived,
             })
             .collect(),
     )
 }
 
 pub async fn get_user_spaces(
-    Query(Paging { page }): Query<Paging>,
+    Query(SpacesQuery { page, archived }): Query<SpacesQuery>,
     Path(UserIDPath { user_id }): Path<UserIDPath>,
     AuthenticatedUser {
         user: DbUser { level, .. },
@@ -575,11 +928,8 @@ pub async fn get_user_spaces(
     }: AuthenticatedUser<DbUser>,
     State(AppState { db, roles, .. }): State<AppState>,
 ) -> Response<Vec<UserSpaceResponse>> {
-    if !roles
-        .get_current(level)
-        .map(|v| v.permissions.spaces_manage)
-        .unwrap_or(false)
-    {
+    let user_id: &str = &user_id;
+    if !roles.load().has(level, perm::SPACE_MANAGE) {
         return Response::Failture(api::Error::Forbidden.into());
     }
 
@@ -587,8 +937,9 @@ pub async fn get_user_spaces(
     let offset = page * limit;
 
     let res = sqlx::query!(
-        "SELECT * FROM spaces WHERE owner_id = ? LIMIT ? OFFSET ?",
+        "SELECT * FROM spaces WHERE owner_id = ? AND (? OR NOT archived) LIMIT ? OFFSET ?",
         user_id,
+        archived,
         limit,
         offset
     )
@@ -601,6 +952,7 @@ pub async fn get_user_spaces(
             .map(|v| UserSpaceResponse {
                 id: v.id,
                 title: v.title,
+                archived: v.archived,
             })
             .collect(),
     )
@@ -636,8 +988,13 @@ pub async fn get_ssh_keys(
 }
 
 pub async fn upload_ssh_key(
-    AuthenticatedUser { user, .. }: AuthenticatedUser,
-    State(AppState { db, .. }): State<AppState>,
+    AuthenticatedUser {
+        user: DbUser {
+            id: user, level, ..
+        },
+        ..
+    }: AuthenticatedUser<DbUser>,
+    State(AppState { db, roles, .. }): State<AppState>,
     Json(UploadSSHKeyBody { pubkey }): Json<UploadSSHKeyBody>,
 ) -> Response<UserSSHKey> {
     let pubkey = match UserSSHKey::from_pubkey(&pubkey) {
@@ -653,6 +1010,10 @@ pub async fn upload_ssh_key(
     let pubkey_ty: i64 = pubkey.pubkey_ty.into();
     let user_id: &str = &user;
 
+    if let Err(e) = quota::check_ssh_keys(&db, roles.load().quota(level), user_id).await {
+        return Response::Failture(e);
+    }
+
     let res = sqlx::query!(
         "INSERT INTO
         users_ssh_keys(id, pubkey_ty, pubkey_val, pubkey_fingerprint, owner_id)
@@ -680,6 +1041,7 @@ pub async fn delete_ssh_key(
     AuthenticatedUser { user, .. }: AuthenticatedUser,
     State(AppState { db, .. }): State<AppState>,
 ) -> Response<u64> {
+    let key_id: &str = &key_id;
     let user: &str = &user;
     let res = sqlx::query!(
         "DELETE FROM users_ssh_keys WHERE id = ? AND owner_id = ?",
@@ -697,3 +1059,848 @@ pub async fn delete_ssh_key(
         Response::Success(res)
     }
 }
+
+pub async fn get_sessions(
+    AuthenticatedUser { user, token }: AuthenticatedUser,
+    State(AppState { db, .. }): State<AppState>,
+) -> Response<Vec<SessionResponse>> {
+    Response::Success(sessions(&db, &user, token.as_ref()).await)
+}
+
+pub async fn get_user_sessions(
+    Path(UserIDPath { user_id }): Path<UserIDPath>,
+    AuthenticatedUser {
+        user: DbUser { level, .. },
+        ..
+    }: AuthenticatedUser<DbUser>,
+    State(AppState { db, roles, .. }): State<AppState>,
+) -> Response<Vec<SessionResponse>> {
+    let user_id: &str = &user_id;
+    if !roles.load().has(level, perm::USER_MANAGE) {
+        return Response::Failture(api::Error::Forbidden.into());
+    }
+
+    let exists = sqlx::query!("SELECT id FROM users WHERE id = ?", user_id)
+        .fetch_optional(&db)
+        .await
+        .expect("database")
+        .is_some();
+    if !exists {
+        return Response::Failture(api::Error::ObjectNotFound.into());
+    }
+
+    Response::Success(sessions(&db, user_id, None).await)
+}
+
+/// Sessions of user, `current` is set for `token`.
+async fn sessions(db: &SqlitePool, user: &str, token: Option<&Token>) -> Vec<SessionResponse> {
+    sqlx::query!(
+        "
+        SELECT iat, hash, user_agent, ip, last_used_at, space_id, scopes, expires_at,
+            impersonated_by
+        FROM tokens
+        WHERE user_id = ?
+        ORDER BY iat",
+        user
+    )
+    .fetch_all(db)
+    .await
+    .expect("database")
+    .into_iter()
+    .map(|v| SessionResponse {
+        iat: v.iat,
+        current: token
+            .zip(v.hash)
+            .is_some_and(|(token, hash)| tokens::matches(&hash, token)),
+        user_agent: v.user_agent,
+        ip: v.ip,
+        last_used_at: v.last_used_at,
+        space_id: v.space_id,
+        scopes: v
+            .scopes
+            .map(|v| v.split_whitespace().map(Into::into).collect()),
+        expires_at: v.expires_at,
+        impersonated_by: v.impersonated_by,
+    })
+    .collect()
+}
+
+pub async fn put_scoped_token(
+    AuthenticatedUser {
+        user: DbUser {
+            id: user_id, level, ..
+        },
+        ..
+    }: AuthenticatedUser<DbUser>,
+    State(AppState {
+        db,
+        roles,
+        token_format,
+        ..
+    }): State<AppState>,
+    Json(ScopedTokenBody {
+        space_id,
+        mut scopes,
+    }): Json<ScopedTokenBody>,
+) -> Response<ScopedTokenResponse> {
+    if scopes.is_empty() {
+        return Response::Failture(
+            api::Error::MalformedData.detail("expected at least one scope".into()),
+        );
+    }
+    if let Some(v) = scopes.iter().find(|v| !scope::ALL.contains(&v.as_str())) {
+        return Response::Failture(
+            api::Error::MalformedData.detail(format!("unknown scope `{v}`").into()),
+        );
+    }
+    scopes.sort_unstable();
+    scopes.dedup();
+
+    // token can't give more than user has
+    let space_id: &str = &space_id;
+    let res = sqlx::query!("SELECT owner_id FROM spaces WHERE id = ?", space_id)
+        .fetch_optional(&db)
+        .await
+        .expect("database");
+    match res {
+        Some(v) if v.owner_id == user_id || roles.load().has(level, perm::SPACE_MANAGE) => (),
+        _ => return Response::Failture(api::Error::ObjectNotFound.into()),
+    }
+
+    let token = Token::with_format(TokenTy::Personal, token_format);
+    let iat = token.iat as i64;
+    let hash = tokens::hash(&token);
+    let scopes = scopes.join(" ");
+    sqlx::query!(
+        "INSERT INTO tokens(iat, hash, user_id, space_id, scopes) VALUES (?, ?, ?, ?, ?)",
+        iat,
+        hash,
+        user_id,
+        space_id,
+        scopes
+    )
+    .execute(&db)
+    .await
+    .expect("database");
+
+    Response::Success(ScopedTokenResponse {
+        token: token.to_string(),
+        iat,
+    })
+}
+
+pub async fn revoke_session(
+    Path(SessionPath { iat }): Path<SessionPath>,
+    AuthenticatedUser { token, user }: AuthenticatedUser<ScopedUser>,
+    State(AppState { db, cache, .. }): State<AppState>,
+) -> Response<u64> {
+    // scoped tokens can revoke only themselves
+    if let Err(e) = user.require(scope::NOTIFICATIONS) {
+        return Response::Failture(e);
+    }
+    if user.scope.is_some() && token.is_none_or(|v| v.iat as i64 != iat) {
+        return Response::Failture(
+            api::Error::Forbidden.detail("Scoped token can revoke only itself".into()),
+        );
+    }
+    let user: &str = &user.user.id;
+    let res = sqlx::query!(
+        "DELETE FROM tokens WHERE user_id = ? AND iat = ?",
+        user,
+        iat
+    )
+    .execute(&db)
+    .await
+    .expect("database")
+    .rows_affected();
+    cache.revoke_user_tokens(user);
+
+    if res == 0 {
+        Response::Failture(api::Error::ObjectNotFound.into())
+    } else {
+        Response::Success(res)
+    }
+}
+
+pub async fn logout_user(
+    Path(UserIDPath { user_id }): Path<UserIDPath>,
+    AuthenticatedUser {
+        user: DbUser {
+            id: actor_id,
+            level,
+            ..
+        },
+        ..
+    }: AuthenticatedUser<DbUser>,
+    ClientIp(ip): ClientIp,
+    State(AppState {
+        db, roles, cache, ..
+    }): State<AppState>,
+) -> Response<u64> {
+    let user_id: &str = &user_id;
+    if !roles.load().has(level, perm::USER_MANAGE) {
+        return Response::Failture(api::Error::Forbidden.into());
+    }
+
+    let mut tx = app::begin(&db).await;
+    let exists = sqlx::query!("SELECT id FROM users WHERE id = ?", user_id)
+        .fetch_optional(&mut *tx)
+        .await
+        .expect("database")
+        .is_some();
+    if !exists {
+        return Response::Failture(api::Error::ObjectNotFound.into());
+    }
+
+    let res = sqlx::query!("DELETE FROM tokens WHERE user_id = ?", user_id)
+        .execute(&mut *tx)
+        .await
+        .expect("database")
+        .rows_affected();
+
+    let log = AuditLog::new(actor_id, AuditAction::UserLoggedOut)
+        .with_target(user_id.to_string())
+        .with_ip(ip);
+    insert_audit(&mut *tx, &log).await.expect("database");
+
+    tx.commit().await.expect("database");
+    cache.revoke_user_tokens(user_id);
+
+    Response::Success(res)
+}
+
+pub async fn lock_user(
+    Path(UserIDPath { user_id }): Path<UserIDPath>,
+    AuthenticatedUser {
+        user: DbUser {
+            id: actor_id,
+            level,
+            ..
+        },
+        ..
+    }: AuthenticatedUser<DbUser>,
+    ClientIp(ip): ClientIp,
+    State(AppState { db, roles, .. }): State<AppState>,
+    Json(LockUserBody { duration_secs }): Json<LockUserBody>,
+) -> Response<i64> {
+    let user_id: &str = &user_id;
+    if !roles.load().has(level, perm::USER_MANAGE) {
+        return Response::Failture(api::Error::Forbidden.into());
+    }
+    if duration_secs <= 0 {
+        return Response::Failture(
+            api::Error::MalformedData.detail("`duration_secs` should be positive".into()),
+        );
+    }
+
+    let log = AuditLog::new(actor_id, AuditAction::LoginLocked)
+        .with_target(user_id.to_string())
+        .with_ip(ip);
+    let locked_until = log
+        .created_at
+        .saturating_add(duration_secs.saturating_mul(1000));
+    let log = log.with_detail(locked_until.to_string());
+
+    let mut tx = app::begin(&db).await;
+    let res = sqlx::query!(
+        "UPDATE users SET login_locked_until = ? WHERE id = ?",
+        locked_until,
+        user_id
+    )
+    .execute(&mut *tx)
+    .await
+    .expect("database");
+    if res.rows_affected() == 0 {
+        return Response::Failture(api::Error::ObjectNotFound.into());
+    }
+
+    insert_audit(&mut *tx, &log).await.expect("database");
+    tx.commit().await.expect("database");
+
+    Response::Success(locked_until)
+}
+
+pub async fn unlock_user(
+    Path(UserIDPath { user_id }): Path<UserIDPath>,
+    AuthenticatedUser {
+        user: DbUser {
+            id: actor_id,
+            level,
+            ..
+        },
+        ..
+    }: AuthenticatedUser<DbUser>,
+    ClientIp(ip): ClientIp,
+    State(AppState { db, roles, .. }): State<AppState>,
+) -> Response<u64> {
+    let user_id: &str = &user_id;
+    if !roles.load().has(level, perm::USER_MANAGE) {
+        return Response::Failture(api::Error::Forbidden.into());
+    }
+
+    let mut tx = app::begin(&db).await;
+    let res = sqlx::query!(
+        "UPDATE users SET login_locked_until = NULL WHERE id = ?",
+        user_id
+    )
+    .execute(&mut *tx)
+    .await
+    .expect("database")
+    .rows_affected();
+    if res == 0 {
+        return Response::Failture(api::Error::ObjectNotFound.into());
+    }
+
+    let log = AuditLog::new(actor_id, AuditAction::LoginUnlocked)
+        .with_target(user_id.to_string())
+        .with_ip(ip);
+    insert_audit(&mut *tx, &log).await.expect("database");
+    tx.commit().await.expect("database");
+
+    Response::Success(res)
+}
+
+pub async fn impersonate_user(
+    Path(UserIDPath { user_id }): Path<UserIDPath>,
+    AuthenticatedUser {
+        user: DbUser {
+            id: actor_id,
+            level,
+            ..
+        },
+        ..
+    }: AuthenticatedUser<DbUser>,
+    ClientIp(ip): ClientIp,
+    State(AppState {
+        db,
+        roles,
+        token_format,
+        ..
+    }): State<AppState>,
+    Json(ImpersonateBody { duration_secs }): Json<ImpersonateBody>,
+) -> Response<ImpersonateResponse> {
+    let user_id: &str = &user_id;
+    if !roles.load().has(level, perm::USER_MANAGE) {
+        return Response::Failture(api::Error::Forbidden.into());
+    }
+    if !(1..=MAX_IMPERSONATION_SECS).contains(&duration_secs) {
+        return Response::Failture(
+            api::Error::MalformedData.detail("`duration_secs` should be between 1 and 3600".into()),
+        );
+    }
+
+    let mut tx = app::begin(&db).await;
+    let res = sqlx::query!("SELECT level FROM users WHERE id = ?", user_id)
+        .fetch_optional(&mut *tx)
+        .await
+        .expect("database");
+    match res {
+        None => return Response::Failture(api::Error::ObjectNotFound.into()),
+        // admin can't act as user with same or higher level
+        Some(v) if v.level >= level => return Response::Failture(api::Error::Forbidden.into()),
+        Some(_) => (),
+    }
+
+    let token = Token::with_format(TokenTy::Personal, token_format);
+    let iat = token.iat as i64;
+    let hash = tokens::hash(&token);
+    let expires_at = iat.saturating_add(duration_secs * 1000);
+    sqlx::query!(
+        "INSERT INTO tokens(iat, hash, user_id, expires_at, impersonated_by) VALUES (?, ?, ?, ?, ?)",
+        iat,
+        hash,
+        user_id,
+        expires_at,
+        actor_id
+    )
+    .execute(&mut *tx)
+    .await
+    .expect("database");
+
+    let log = AuditLog::new(actor_id, AuditAction::UserImpersonated)
+        .with_target(user_id.to_string())
+        .with_detail(expires_at.to_string())
+        .with_ip(ip);
+    insert_audit(&mut *tx, &log).await.expect("database");
+    tx.commit().await.expect("database");
+
+    Response::Success(ImpersonateResponse {
+        token: token.to_string(),
+        expires_at,
+    })
+}
+
+pub async fn get_invitees(
+    _: AuthenticatedUser<UserID>,
+    Path(UserIDPath { user_id }): Path<UserIDPath>,
+    Query(Paging { page }): Query<Paging>,
+    State(AppState { db, .. }): State<AppState>,
+) -> Response<Vec<User>> {
+    let user_id: &str = &user_id;
+    let (offset, limit) = ((page as i64) * 50, 50);
+
+    let res = sqlx::query!(
+        "SELECT id, name, invited_by FROM users WHERE invited_by = ? ORDER BY id LIMIT ? OFFSET ?",
+        user_id,
+        limit,
+        offset
+    )
+    .fetch_all(&db)
+    .await
+    .expect("database")
+    .into_iter()
+    .map(|v| User {
+        id: UserID::from(v.id).expect("checked UserID"),
+        name: v.name,
+        invited_by: v.invited_by,
+    });
+
+    Response::Success(res.collect())
+}
+
+/// Maximum depth of invite tree, guards against cycles in `invited_by`
+const INVITE_TREE_MAX_DEPTH: i64 = 256;
+
+pub async fn get_invite_tree(
+    AuthenticatedUser {
+        user: DbUser { level, .. },
+        ..
+    }: AuthenticatedUser<DbUser>,
+    Query(InviteTreeQuery { root }): Query<InviteTreeQuery>,
+    State(AppState { db, roles, .. }): State<AppState>,
+) -> Response<Vec<InviteTreeEntry>> {
+    if !roles.load().has(level, perm::USER_MANAGE) {
+        return Response::Failture(api::Error::Forbidden.into());
+    }
+
+    // `path` of ids orders entries depth-first, so invitees follow their inviter
+    let res = sqlx::query!(
+        r#"
+        WITH RECURSIVE tree(id, name, invited_by, depth, path) AS (
+            SELECT id, name, invited_by, 0, id FROM users
+            WHERE (?1 IS NULL AND invited_by IS NULL) OR id = ?1
+            UNION ALL
+            SELECT users.id, users.name, users.invited_by, tree.depth + 1, tree.path || '/' || users.id
+            FROM users INNER JOIN tree ON users.invited_by = tree.id
+            WHERE tree.depth < ?2
+        )
+        SELECT id as "id!: String", name as "name!: String", invited_by, depth as "depth!: i64"
+        FROM tree ORDER BY path
+        "#,
+        root,
+        INVITE_TREE_MAX_DEPTH
+    )
+    .fetch_all(&db)
+    .await
+    .expect("database");
+
+    if root.is_some() && res.is_empty() {
+        return Response::Failture(api::Error::ObjectNotFound.into());
+    }
+
+    let res = res.into_iter().map(|v| InviteTreeEntry {
+        user: User {
+            id: UserID::from(v.id).expect("checked UserID"),
+            name: v.name,
+            invited_by: v.invited_by,
+        },
+        depth: v.depth,
+    });
+
+    Response::Success(res.collect())
+}
+
+/// Delete user and erase their data. Spaces of user are transferred to `transfer_to`
+/// or deleted.
+async fn erase_user(
+    tx: &mut sqlx::SqliteConnection,
+    user_id: &str,
+    transfer_to: Option<&str>,
+) -> Result<DeleteUserReport, api::ErrorData> {
+    let Some(user) = sqlx::query!("SELECT name FROM users WHERE id = ?", user_id)
+        .fetch_optional(&mut *tx)
+        .await
+        .expect("database")
+    else {
+        return Err(api::Error::ObjectNotFound.into());
+    };
+
+    let mut report = DeleteUserReport::default();
+
+    match transfer_to {
+        Some(transfer_to) => {
+            let exists = sqlx::query!("SELECT id FROM users WHERE id = ?", transfer_to)
+                .fetch_optional(&mut *tx)
+                .await
+                .expect("database")
+                .is_some();
+            if !exists || transfer_to == user_id {
+                return Err(api::Error::ObjectNotFound
+                    .detail("User of `transfer_spaces_to` does not exists".into()));
+            }
+            report.spaces_transferred = sqlx::query!(
+                "UPDATE spaces SET owner_id = ? WHERE owner_id = ?",
+                transfer_to,
+                user_id
+            )
+            .execute(&mut *tx)
+            .await
+            .expect("database")
+            .rows_affected();
+        }
+        None => {
+            let spaces = sqlx::query_scalar!("SELECT id FROM spaces WHERE owner_id = ?", user_id)
+                .fetch_all(&mut *tx)
+                .await
+                .expect("database");
+            for space_id in &spaces {
+                erase_space(&mut *tx, space_id).await;
+            }
+            report.spaces_deleted = spaces.len() as u64;
+        }
+    }
+
+    report.tokens = sqlx::query!("DELETE FROM tokens WHERE user_id = ?", user_id)
+        .execute(&mut *tx)
+        .await
+        .expect("database")
+        .rows_affected();
+    report.invites = sqlx::query!("DELETE FROM invites WHERE owner_id = ?", user_id)
+        .execute(&mut *tx)
+        .await
+        .expect("database")
+        .rows_affected();
+    report.ssh_keys = sqlx::query!("DELETE FROM users_ssh_keys WHERE owner_id = ?", user_id)
+        .execute(&mut *tx)
+        .await
+        .expect("database")
+        .rows_affected();
+    report.identities = sqlx::query!("DELETE FROM oidc_identities WHERE user_id = ?", user_id)
+        .execute(&mut *tx)
+        .await
+        .expect("database")
+        .rows_affected();
+    report.invitees = sqlx::query!(
+        "UPDATE users SET invited_by = NULL WHERE invited_by = ?",
+        user_id
+    )
+    .execute(&mut *tx)
+    .await
+    .expect("database")
+    .rows_affected();
+    report.audit_log = sqlx::query!(
+        "UPDATE audit_log SET
+            actor_id = NULLIF(actor_id, ?1),
+            target_id = NULLIF(target_id, ?1)
+        WHERE actor_id = ?1 OR target_id = ?1",
+        user_id
+    )
+    .execute(&mut *tx)
+    .await
+    .expect("database")
+    .rows_affected();
+
+    sqlx::query!("DELETE FROM auth_failures WHERE username = ?", user.name)
+        .execute(&mut *tx)
+        .await
+        .expect("database");
+    sqlx::query!("DELETE FROM users WHERE id = ?", user_id)
+        .execute(&mut *tx)
+        .await
+        .expect("database");
+
+    Ok(report)
+}
+
+pub async fn delete_self(
+    AuthenticatedUser { user, .. }: AuthenticatedUser,
+    Query(DeleteUserQuery {
+        transfer_spaces_to,
+        dry_run,
+    }): Query<DeleteUserQuery>,
+    State(AppState { db, cache, .. }): State<AppState>,
+) -> Response<DeleteUserReport> {
+    let user_id: &str = &user;
+
+    let mut tx = app::begin(&db).await;
+    let report = match erase_user(&mut tx, user_id, transfer_spaces_to.as_deref()).await {
+        Ok(v) => v,
+        Err(e) => return Response::Failture(e),
+    };
+    if dry_run {
+        tx.rollback().await.expect("database");
+        return Response::Success(report);
+    }
+    tx.commit().await.expect("database");
+    // spaces of user changed owner or were deleted
+    cache.clear();
+
+    Response::Success(report)
+}
+
+pub async fn delete_user(
+    Path(UserIDPath { user_id }): Path<UserIDPath>,
+    AuthenticatedUser {
+        user: DbUser {
+            id: actor_id,
+            level,
+            ..
+        },
+        ..
+    }: AuthenticatedUser<DbUser>,
+    ClientIp(ip): ClientIp,
+    Query(DeleteUserQuery {
+        transfer_spaces_to,
+        dry_run,
+    }): Query<DeleteUserQuery>,
+    State(AppState {
+        db, roles, cache, ..
+    }): State<AppState>,
+) -> Response<DeleteUserReport> {
+    let user_id: &str = &user_id;
+    if !roles.load().has(level, perm::USER_MANAGE) {
+        return Response::Failture(api::Error::Forbidden.into());
+    }
+
+    let mut tx = app::begin(&db).await;
+    let report = match erase_user(&mut tx, user_id, transfer_spaces_to.as_deref()).await {
+        Ok(v) => v,
+        Err(e) => return Response::Failture(e),
+    };
+    if dry_run {
+        tx.rollback().await.expect("database");
+        return Response::Success(report);
+    }
+
+    // actor may delete themselves, then entry has no actor
+    let mut log = AuditLog::new(actor_id.clone(), AuditAction::UserDeleted)
+        .with_target(user_id.to_string())
+        .with_ip(ip);
+    if actor_id == user_id {
+        log.actor_id = None;
+    }
+    insert_audit(&mut *tx, &log).await.expect("database");
+    tx.commit().await.expect("database");
+    // spaces of user changed owner or were deleted
+    cache.clear();
+
+    Response::Success(report)
+}
+
+fn notifications_not_configured() -> api::ErrorData {
+    api::Error::ObjectNotFound.detail("notifications are not configured".into())
+}
+
+/// Fetch notification settings of user, defaults if they were never changed.
+async fn fetch_notifications<'e, E>(db: E, notifier: &Notifier, user_id: &str) -> UserNotifications
+where
+    E: sqlx::Executor<'e, Database = sqlx::Sqlite>,
+{
+    let res = sqlx::query!(
+        r#"
+        SELECT telegram_chat_id, email, webhook_url,
+            report_filed AS "report_filed: bool",
+            unlock_denied AS "unlock_denied: bool",
+            item_overdue AS "item_overdue: bool"
+        FROM users_notifications
+        WHERE user_id = ?"#,
+        user_id
+    )
+    .fetch_optional(db)
+    .await
+    .expect("database");
+
+    let channels = [
+        ("telegram", &notifier.telegram),
+        ("email", &notifier.email),
+        ("webhook", &notifier.webhooks),
+    ]
+    .into_iter()
+    .filter(|(_, v)| v.is_some())
+    .map(|(name, _)| name.into())
+    .collect();
+
+    match res {
+        Some(v) => UserNotifications {
+            telegram_chat_id: v.telegram_chat_id,
+            email: v.email,
+            webhook_url: v.webhook_url,
+            report_filed: v.report_filed,
+            unlock_denied: v.unlock_denied,
+            item_overdue: v.item_overdue,
+            channels,
+        },
+        None => UserNotifications {
+            telegram_chat_id: None,
+            email: None,
+            webhook_url: None,
+            report_filed: true,
+            unlock_denied: true,
+            item_overdue: true,
+            channels,
+        },
+    }
+}
+
+pub async fn get_notifications(
+    AuthenticatedUser { user, .. }: AuthenticatedUser,
+    State(AppState { db, notifier, .. }): State<AppState>,
+) -> Response<UserNotifications> {
+    let Some(notifier) = notifier else {
+        return Response::Failture(notifications_not_configured());
+    };
+
+    Response::Success(fetch_notifications(&db, &notifier, &user).await)
+}
+
+/// Check value of channel target if it is set.
+fn check_target(
+    name: &str,
+    configured: bool,
+    value: &MayIgnored<Option<String>>,
+    check: fn(&str) -> Result<(), &'static str>,
+) -> Result<(), api::ErrorData> {
+    let MayIgnored::Value(Some(value)) = value else {
+        return Ok(());
+    };
+    if !configured {
+        return Err(api::Error::ObjectNotFound
+            .detail(format!("`{name}` channel is not configured on server").into()));
+    }
+    check(value).map_err(|e| api::Error::MalformedData.detail(format!("`{name}`: {e}").into()))
+}
+
+pub async fn patch_notifications(
+    AuthenticatedUser { user, .. }: AuthenticatedUser<ScopedUser>,
+    State(AppState { db, notifier, .. }): State<AppState>,
+    Json(PatchNotificationsBody {
+        telegram_chat_id,
+        email,
+        webhook_url,
+        report_filed,
+        unlock_denied,
+        item_overdue,
+    }): Json<PatchNotificationsBody>,
+) -> Response<UserNotifications> {
+    if let Err(e) = user.require(scope::NOTIFICATIONS) {
+        return Response::Failture(e);
+    }
+    let Some(notifier) = notifier else {
+        return Response::Failture(notifications_not_configured());
+    };
+    if telegram_chat_id.is_ignored()
+        && email.is_ignored()
+        && webhook_url.is_ignored()
+        && report_filed.is_ignored()
+        && unlock_denied.is_ignored()
+        && item_overdue.is_ignored()
+    {
+        return Response::Failture(
+            api::Error::MalformedData.detail("expected at least one subject to change".into()),
+        );
+    }
+    let checks = [
+        (
+            "telegram_chat_id",
+            notifier.telegram.is_some(),
+            &telegram_chat_id,
+            notify::check_telegram_chat_id as fn(&str) -> _,
+        ),
+        (
+            "email",
+            notifier.email.is_some(),
+            &email,
+            notify::check_email,
+        ),
+        (
+            "webhook_url",
+            notifier.webhooks.is_some(),
+            &webhook_url,
+            notify::check_webhook_url,
+        ),
+    ];
+    for (name, configured, value, check) in checks {
+        if let Err(e) = check_target(name, configured, value, check) {
+            return Response::Failture(e);
+        }
+    }
+
+    let user: &str = &user.user.id;
+    let mut tx = app::begin(&db).await;
+    let current = fetch_notifications(&mut *tx, &notifier, user).await;
+    let telegram_chat_id = telegram_chat_id.ok().unwrap_or(current.telegram_chat_id);
+    let email = email.ok().unwrap_or(current.email);
+    let webhook_url = webhook_url.ok().unwrap_or(current.webhook_url);
+    let report_filed = report_filed.ok().unwrap_or(current.report_filed);
+    let unlock_denied = unlock_denied.ok().unwrap_or(current.unlock_denied);
+    let item_overdue = item_overdue.ok().unwrap_or(current.item_overdue);
+    sqlx::query!(
+        "
+        INSERT INTO users_notifications(user_id, telegram_chat_id, email, webhook_url,
+            report_filed, unlock_denied, item_overdue)
+        VALUES (?, ?, ?, ?, ?, ?, ?)
+        ON CONFLICT(user_id) DO UPDATE SET
+            telegram_chat_id = excluded.telegram_chat_id,
+            email = excluded.email,
+            webhook_url = excluded.webhook_url,
+            report_filed = excluded.report_filed,
+            unlock_denied = excluded.unlock_denied,
+            item_overdue = excluded.item_overdue",
+        user,
+        telegram_chat_id,
+        email,
+        webhook_url,
+        report_filed,
+        unlock_denied,
+        item_overdue
+    )
+    .execute(&mut *tx)
+    .await
+    .expect("database");
+    tx.commit().await.expect("database");
+
+    Response::Success(UserNotifications {
+        telegram_chat_id,
+        email,
+        webhook_url,
+        report_filed,
+        unlock_denied,
+        item_overdue,
+        channels: current.channels,
+    })
+}
+
+pub async fn create_telegram_auth(
+    AuthenticatedUser { user, .. }: AuthenticatedUser,
+    State(AppState { db, .. }): State<AppState>,
+) -> Response<TelegramAuthResponse> {
+    let auth = UserTelegramAuth::new(user);
+    let id: &str = &auth.id;
+    let user_id: &str = &auth.user_id;
+    let issued_at = auth.issued_at as i64;
+
+    // only last code of user is valid
+    let mut tx = app::begin(&db).await;
+    sqlx::query!(
+        "DELETE FROM users_telegram_auths WHERE user_id = ?",
+        user_id
+    )
+    .execute(&mut *tx)
+    .await
+    .expect("database");
+    sqlx::query!(
+        "INSERT INTO users_telegram_auths(id, user_id, issued_at) VALUES (?, ?, ?)",
+        id,
+        user_id,
+        issued_at
+    )
+    .execute(&mut *tx)
+    .await
+    .expect("database");
+    tx.commit().await.expect("database");
+
+    Response::Success(TelegramAuthResponse {
+        code: id.to_owned(),
+        expires_at: issued_at + UserTelegramAuth::WAIT_TIME_MS as i64,
+    })
+}
@@ -0,0 +1,258 @@
+//! Administrative endpoints: maintenance mode, migrations and roles of users.
+
+mod common;
+
+use archk::v1::api;
+use axum::http::{Method, StatusCode};
+use common::{TestApp, ADMIN, GUEST, PASSWORD, USER};
+use serde_json::json;
+
+#[tokio::test]
+async fn maintenance_mode_is_read_only() {
+    let app = TestApp::new().await;
+    let admin = app.user("root", ADMIN).await;
+    let user = app.user("greg", USER).await;
+    let space = app.space(&user, "Lab").await;
+
+    let code = app
+        .err(
+            Method::POST,
+            "/admin/maintenance",
+            Some(&user.token),
+            Some(json!({ "enabled": true })),
+        )
+        .await;
+    assert_eq!(code, api::Error::Forbidden as u64);
+
+    let mode = app
+        .ok(
+            Method::POST,
+            "/admin/maintenance",
+            Some(&admin.token),
+            Some(json!({ "enabled": true, "message": "backup" })),
+        )
+        .await;
+    assert_eq!(mode["enabled_by"], admin.id.as_str());
+
+    let (status, body) = app
+        .request(
+            Method::PUT,
+            "/space",
+            Some(&user.token),
+            Some(json!({ "title": "Office" })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(body["error"]["code"], api::Error::ServiceUnavailable as u64);
+    assert_eq!(body["error"]["detail"], "backup");
+    app.ok(
+        Method::GET,
+        &format!("/space/{space}"),
+        Some(&user.token),
+        None,
+    )
+    .await;
+    app.space(&admin, "Office").await;
+
+    // users can still log in, eg. admins to disable mode
+    let res = app
+        .ok(
+            Method::POST,
+            "/auth",
+            None,
+            Some(json!({ "username": "root", "password": PASSWORD })),
+        )
+        .await;
+    assert!(res["token"].is_string());
+
+    // mode is stored in database, so it is seen by other servers too
+    let other = TestApp::with_state(app.state.clone());
+    let mode = other
+        .ok(Method::GET, "/admin/maintenance", Some(&admin.token), None)
+        .await;
+    assert_eq!(mode["message"], "backup");
+
+    let mode = app
+        .ok(
+            Method::POST,
+            "/admin/maintenance",
+            Some(&admin.token),
+            Some(json!({ "enabled": false })),
+        )
+        .await;
+    assert!(mode.is_null());
+    app.space(&user, "Office").await;
+
+    let log = app
+        .ok(Method::GET, "/admin/audit", Some(&admin.token), None)
+        .await;
+    let enabled = log
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|v| v["act"] == 106)
+        .unwrap();
+    assert_eq!(enabled["detail"], "backup");
+    assert!(log.as_array().unwrap().iter().any(|v| v["act"] == 107));
+}
+
+#[tokio::test]
+async fn migrations_status() {
+    let app = TestApp::new().await;
+    let admin = app.user("root", ADMIN).await;
+    let user = app.user("greg", USER).await;
+
+    let migrations = app
+        .ok(Method::GET, "/admin/migrations", Some(&admin.token), None)
+        .await;
+    let migrations = migrations.as_array().unwrap();
+    assert_eq!(migrations[0]["version"], 1);
+    assert!(migrations
+        .iter()
+        .all(|v| v["applied_at"].is_i64() && v["modified"] == false && v["missing"] == false));
+    let code = app
+        .err(Method::GET, "/admin/migrations", Some(&user.token), None)
+        .await;
+    assert_eq!(code, api::Error::Forbidden as u64);
+
+    // every migration of empty database is pending
+    let db = sqlx::SqlitePool::connect("sqlite::memory:").await.unwrap();
+    let pending = archk_api::migrations(&db).await.unwrap();
+    assert_eq!(pending.len(), migrations.len());
+    assert!(pending.iter().all(|v| v.is_pending()));
+}
+
+#[tokio::test]
+async fn promote_by_role_name() {
+    let app = TestApp::new().await;
+    let admin = app.user("root", ADMIN).await;
+    let user = app.user("greg", GUEST).await;
+    let uri = format!("/user/@{}/role", user.id);
+    let token = Some(admin.token.as_str());
+
+    let res = app
+        .ok(Method::PATCH, &uri, token, Some(json!({ "role": "User" })))
+        .await;
+    assert_eq!(res["level"], USER);
+    assert_eq!(res["role"]["name"], "user");
+    let res = app
+        .ok(Method::PATCH, &uri, token, Some(json!({ "level": 5 })))
+        .await;
+    assert_eq!(res["level"], 5);
+    assert_eq!(res["role"]["name"], "guest");
+
+    let code = app
+        .err(
+            Method::PATCH,
+            &uri,
+            token,
+            Some(json!({ "role": "moderator" })),
+        )
+        .await;
+    assert_eq!(code, api::Error::ObjectNotFound as u64);
+    let code = app
+        .err(
+            Method::PATCH,
+            &uri,
+            token,
+            Some(json!({ "role": "user", "level": 10 })),
+        )
+        .await;
+    assert_eq!(code, api::Error::MalformedData as u64);
+}
+
+#[tokio::test]
+async fn promotion_is_limited() {
+    let app = TestApp::new().await;
+    let admin = app.user("root", ADMIN).await;
+    let other = app.user("bob", ADMIN).await;
+    let user = app.user("greg", USER).await;
+
+    let detail = |body: serde_json::Value| body["error"]["detail"].as_str().unwrap().to_string();
+    let (status, body) = app
+        .request(
+            Method::PATCH,
+            &format!("/user/@{}/role", other.id),
+            Some(&admin.token),
+            Some(json!({ "level": GUEST })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    assert!(detail(body).contains("own level or higher"));
+
+    let (_, body) = app
+        .request(
+            Method::PATCH,
+            &format!("/user/@{}/role", admin.id),
+            Some(&admin.token),
+            Some(json!({ "role": "user" })),
+        )
+        .await;
+    assert!(detail(body).contains("demote self"));
+
+    let (_, body) = app
+        .request(
+            Method::PATCH,
+            &format!("/user/@{}/role", admin.id),
+            Some(&user.token),
+            Some(json!({ "level": GUEST })),
+        )
+        .await;
+    assert!(detail(body).contains("`user.promote`"));
+
+    // demoting others below own level is still allowed
+    app.ok(
+        Method::PATCH,
+        &format!("/user/@{}/role", user.id),
+        Some(&admin.token),
+        Some(json!({ "level": GUEST })),
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn permissions_of_level() {
+    let app = TestApp::new().await;
+    let admin = app.user("root", ADMIN).await;
+    let user = app.user("greg", USER).await;
+
+    let res = app
+        .ok(Method::GET, "/user/permissions", Some(&user.token), None)
+        .await;
+    assert_eq!(res["role"], "user");
+    assert_eq!(
+        res["permissions"],
+        json!(["space.create", "org.create", "service.create"])
+    );
+    let res = app
+        .ok(
+            Method::GET,
+            &format!("/user/permissions?level={ADMIN}"),
+            Some(&user.token),
+            None,
+        )
+        .await;
+    assert!(res["permissions"]
+        .as_array()
+        .unwrap()
+        .contains(&json!("user.promote")));
+
+    let res = app
+        .ok(
+            Method::GET,
+            &format!("/user/@{}/permissions", user.id),
+            Some(&admin.token),
+            None,
+        )
+        .await;
+    assert_eq!(res["level"], USER);
+    let code = app
+        .err(
+            Method::GET,
+            &format!("/user/@{}/permissions", admin.id),
+            Some(&user.token),
+            None,
+        )
+        .await;
+    assert_eq!(code, api::Error::Forbidden as u64);
+}
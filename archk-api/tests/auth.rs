@@ -0,0 +1,419 @@
+//! Login, sessions, token revocation, authentication by proxy and client address,
+//! initial admin.
+
+mod common;
+
+use std::{net::SocketAddr, sync::Arc};
+
+use archk::v1::{
+    api,
+    auth::{Token, TokenTy},
+};
+use archk_api::app::{
+    AppConfigAuthProxy, AppConfigBootstrap, AppConfigBootstrapAdmin, AppState, ProxyAuth,
+};
+use axum::{
+    body::Body,
+    extract::ConnectInfo,
+    http::{Method, Request, StatusCode},
+};
+use common::{TestApp, ADMIN, PASSWORD, USER};
+use http_body_util::BodyExt;
+use serde_json::{json, Value};
+use tower::ServiceExt;
+
+#[tokio::test]
+async fn login_and_logout() {
+    let app = TestApp::new().await;
+    let user = app.user("greg", USER).await;
+
+    let res = app
+        .ok(
+            Method::POST,
+            "/auth",
+            None,
+            Some(json!({ "username": "greg", "password": PASSWORD })),
+        )
+        .await;
+    let token = res["token"].as_str().unwrap();
+    assert!(token.starts_with("acp2_"), "{token}");
+
+    let me = app.ok(Method::GET, "/user", Some(token), None).await;
+    assert_eq!(me["user"]["id"], user.id.as_str());
+    assert_eq!(me["user"]["name"], "greg");
+
+    // both tokens are sessions of user
+    let sessions = app
+        .ok(Method::GET, "/user/sessions", Some(token), None)
+        .await;
+    assert_eq!(sessions.as_array().unwrap().len(), 2);
+
+    let iat = Token::parse(&user.token).unwrap().iat;
+    app.ok(
+        Method::DELETE,
+        &format!("/user/sessions/{iat}"),
+        Some(token),
+        None,
+    )
+    .await;
+    let code = app.err(Method::GET, "/user", Some(&user.token), None).await;
+    assert_eq!(code, api::Error::Unauthorized as u64);
+    app.ok(Method::GET, "/user", Some(token), None).await;
+}
+
+#[tokio::test]
+async fn invalid_credentials() {
+    let app = TestApp::new().await;
+    app.user("greg", USER).await;
+
+    let code = app
+        .err(
+            Method::POST,
+            "/auth",
+            None,
+            Some(json!({ "username": "greg", "password": "password2" })),
+        )
+        .await;
+    assert_eq!(code, api::Error::ObjectNotFound as u64);
+
+    let code = app.err(Method::GET, "/user", None, None).await;
+    assert_eq!(code, api::Error::Unauthorized as u64);
+    let code = app
+        .err(Method::GET, "/user", Some("not a token"), None)
+        .await;
+    assert_eq!(code, api::Error::Unauthorized as u64);
+}
+
+#[tokio::test]
+async fn failed_logins_are_rate_limited() {
+    let app = TestApp::new().await;
+    app.user("greg", USER).await;
+
+    for _ in 0..5 {
+        let code = app
+            .err(
+                Method::POST,
+                "/auth",
+                None,
+                Some(json!({ "username": "greg", "password": "password2" })),
+            )
+            .await;
+        assert_eq!(code, api::Error::ObjectNotFound as u64);
+    }
+
+    // even valid password is not checked during lockout
+    let body = json!({ "username": "greg", "password": PASSWORD });
+    let request = Request::post("/auth")
+        .header("Content-Type", "application/json")
+        .body(Body::from(body.to_string()))
+        .unwrap();
+    let res = app.router.clone().oneshot(request).await.unwrap();
+    assert_eq!(res.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(res.headers()["Retry-After"], "30");
+    let body = res.into_body().collect().await.unwrap().to_bytes();
+    let body: Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(body["error"]["code"], api::Error::RateLimited as u64);
+}
+
+#[tokio::test]
+async fn scoped_token_is_bound_to_space() {
+    let app = TestApp::new().await;
+    let user = app.user("greg", USER).await;
+    let space = app.space(&user, "Lab").await;
+    let other = app.space(&user, "Office").await;
+
+    let res = app
+        .ok(
+            Method::PUT,
+            "/user/tokens",
+            Some(&user.token),
+            Some(json!({ "space_id": space, "scopes": ["space.read"] })),
+        )
+        .await;
+    let token = res["token"].as_str().unwrap();
+
+    app.ok(
+        Method::GET,
+        &format!("/space/{space}/item"),
+        Some(token),
+        None,
+    )
+    .await;
+    let code = app
+        .err(
+            Method::PUT,
+            &format!("/space/{space}/item"),
+            Some(token),
+            Some(json!({ "title": "Key", "pl_serial": "k1" })),
+        )
+        .await;
+    assert_eq!(code, api::Error::Forbidden as u64);
+    let code = app
+        .err(
+            Method::GET,
+            &format!("/space/{other}/item"),
+            Some(token),
+            None,
+        )
+        .await;
+    assert_eq!(code, api::Error::ObjectNotFound as u64);
+}
+
+#[tokio::test]
+async fn legacy_token_is_hashed_on_first_use() {
+    let app = TestApp::new().await;
+    let user = app.user("greg", USER).await;
+
+    // token stored as before hashing, `iat` differs from one of user's token
+    let token = Token {
+        iat: 1,
+        ..Token::new(TokenTy::Personal)
+    };
+    let (iat, rnd) = (token.iat as i64, token.rnd as i64);
+    sqlx::query!(
+        "INSERT INTO tokens(iat, rnd, user_id) VALUES (?, ?, ?)",
+        iat,
+        rnd,
+        user.id
+    )
+    .execute(app.db())
+    .await
+    .unwrap();
+
+    let token = token.to_string();
+    app.ok(Method::GET, "/user", Some(&token), None).await;
+    let row = sqlx::query!("SELECT rnd, hash FROM tokens WHERE iat = ?", iat)
+        .fetch_one(app.db())
+        .await
+        .unwrap();
+    assert_eq!(row.rnd, None);
+    assert_eq!(
+        row.hash,
+        Some(archk_api::tokens::hash(&Token::parse(&token).unwrap()))
+    );
+    app.ok(Method::GET, "/user", Some(&token), None).await;
+}
+
+#[tokio::test]
+async fn trusted_proxy_user() {
+    let proxy = ProxyAuth::new(AppConfigAuthProxy {
+        header: "X-Forwarded-User".into(),
+        trusted_proxies: vec!["10.0.0.0/8".parse().unwrap()],
+    })
+    .unwrap();
+    let app = TestApp::with_state(AppState {
+        proxy_auth: Some(Arc::new(proxy)),
+        ..common::state().await
+    });
+    let user = app.user("greg", USER).await;
+
+    let request = |from: &str, name: &str| {
+        let mut request = Request::get("/user")
+            .header("X-Forwarded-User", name)
+            .body(Body::empty())
+            .unwrap();
+        let addr: SocketAddr = from.parse().unwrap();
+        request.extensions_mut().insert(ConnectInfo(addr));
+        app.router.clone().oneshot(request)
+    };
+
+    let res = request("10.0.0.1:4000", "greg").await.unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    let body = res.into_body().collect().await.unwrap().to_bytes();
+    let body: Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(body["response"]["user"]["id"], user.id.as_str());
+
+    let res = request("10.0.0.1:4000", "bob").await.unwrap();
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+    // header from untrusted address is ignored
+    let res = request("192.168.0.1:4000", "greg").await.unwrap();
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+
+    // locked user can't login through proxy too
+    sqlx::query!(
+        "UPDATE users SET login_locked_until = ? WHERE id = ?",
+        i64::MAX,
+        user.id
+    )
+    .execute(app.db())
+    .await
+    .unwrap();
+    let res = request("10.0.0.1:4000", "greg").await.unwrap();
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn client_ip_in_audit_log() {
+    let app = TestApp::with_state(AppState {
+        trusted_proxies: Arc::new(["10.0.0.0/8".parse().unwrap()]),
+        ..common::state().await
+    });
+    let admin = app.user("root", ADMIN).await;
+    let user = app.user("greg", USER).await;
+
+    let mut request = Request::post(format!("/user/@{}/logout", user.id))
+        .header("Authorization", format!("Bearer {}", admin.token))
+        .header("X-Forwarded-For", "203.0.113.1, 198.51.100.7, 10.0.0.2")
+        .body(Body::empty())
+        .unwrap();
+    let addr: SocketAddr = "10.0.0.1:4000".parse().unwrap();
+    request.extensions_mut().insert(ConnectInfo(addr));
+    let res = app.router.clone().oneshot(request).await.unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+
+    let log = app
+        .ok(Method::GET, "/admin/audit", Some(&admin.token), None)
+        .await;
+    assert_eq!(log[0]["target_id"], user.id.as_str());
+    assert_eq!(log[0]["ip"], "198.51.100.7");
+
+    // sessions of other users are listed only for `user.manage`
+    let sessions = app
+        .ok(
+            Method::GET,
+            &format!("/user/@{}/sessions", admin.id),
+            Some(&admin.token),
+            None,
+        )
+        .await;
+    assert_eq!(sessions[0]["ip"], "198.51.100.7");
+    assert_eq!(sessions[0]["current"], false);
+    let user = app.user("bob", USER).await;
+    let code = app
+        .err(
+            Method::GET,
+            &format!("/user/@{}/sessions", admin.id),
+            Some(&user.token),
+            None,
+        )
+        .await;
+    assert_eq!(code, api::Error::Forbidden as u64);
+}
+
+#[tokio::test]
+async fn bootstrap_admin() {
+    let app = TestApp::new().await;
+    let mut config = AppConfigBootstrap {
+        admin: AppConfigBootstrapAdmin {
+            username: "root".into(),
+            password_hash: "not a hash".into(),
+            role: None,
+        },
+        invites: 5,
+    };
+    let roles = app.state.roles.load();
+    assert!(archk_api::bootstrap::run(app.db(), &config, &roles)
+        .await
+        .is_err());
+
+    config.admin.password_hash = bcrypt::hash(PASSWORD, 4).unwrap();
+    assert_eq!(
+        archk_api::bootstrap::run(app.db(), &config, &roles).await,
+        Ok(true)
+    );
+    // database is not empty anymore
+    assert_eq!(
+        archk_api::bootstrap::run(app.db(), &config, &roles).await,
+        Ok(false)
+    );
+
+    let res = app
+        .ok(
+            Method::POST,
+            "/auth",
+            None,
+            Some(json!({ "username": "root", "password": PASSWORD })),
+        )
+        .await;
+    let me = app
+        .ok(Method::GET, "/user", res["token"].as_str(), None)
+        .await;
+    assert_eq!(me["level"], ADMIN);
+    assert_eq!(me["invites"], 5);
+
+    // first user registered without invite is not admin anymore
+    let code = app
+        .err(
+            Method::PUT,
+            "/user",
+            None,
+            Some(json!({ "username": "greg", "password": PASSWORD, "invite": "" })),
+        )
+        .await;
+    assert_eq!(code, api::Error::ObjectNotFound as u64);
+}
+
+#[tokio::test]
+async fn telegram_token_is_scoped() {
+    let app = TestApp::new().await;
+    let user = app.user("greg", USER).await;
+    let space = app.space(&user, "Lab").await;
+
+    let code = app
+        .ok(Method::POST, "/user/telegram", Some(&user.token), None)
+        .await;
+    let res = app
+        .ok(
+            Method::POST,
+            "/auth/telegram",
+            None,
+            Some(json!({ "code": code["code"], "chat_id": "12345" })),
+        )
+        .await;
+    let bot = res["token"].as_str().unwrap();
+    let token = Some(bot);
+
+    // what bot does
+    let res = app.ok(Method::GET, "/user", token, None).await;
+    assert_eq!(res["user"]["id"], user.id.as_str());
+    let spaces = app.ok(Method::GET, "/user/spaces", token, None).await;
+    assert_eq!(spaces[0]["id"], space.as_str());
+    app.ok(
+        Method::GET,
+        &format!("/space/{space}/unlock-requests"),
+        token,
+        None,
+    )
+    .await;
+
+    // and nothing else
+    let code = app
+        .err(Method::GET, &format!("/space/{space}/item"), token, None)
+        .await;
+    assert_eq!(code, api::Error::Forbidden as u64);
+    let code = app.err(Method::GET, "/user/sessions", token, None).await;
+    assert_eq!(code, api::Error::Unauthorized as u64);
+    let iat = Token::parse(&user.token).unwrap().iat;
+    let code = app
+        .err(
+            Method::DELETE,
+            &format!("/user/sessions/{iat}"),
+            token,
+            None,
+        )
+        .await;
+    assert_eq!(code, api::Error::Forbidden as u64);
+
+    let sessions = app
+        .ok(Method::GET, "/user/sessions", Some(&user.token), None)
+        .await;
+    let scopes = sessions
+        .as_array()
+        .unwrap()
+        .iter()
+        .find_map(|v| v["scopes"].as_array())
+        .unwrap();
+    assert_eq!(scopes.len(), 2);
+
+    // bot unlinks by revoking its own token
+    let iat = Token::parse(bot).unwrap().iat;
+    app.ok(
+        Method::DELETE,
+        &format!("/user/sessions/{iat}"),
+        token,
+        None,
+    )
+    .await;
+    let code = app.err(Method::GET, "/user", token, None).await;
+    assert_eq!(code, api::Error::Unauthorized as u64);
+}
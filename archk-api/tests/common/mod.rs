@@ -0,0 +1,222 @@
+//! Test harness: router of API v1 over in-memory database with helpers to create
+//! users, spaces and services. Each test gets its own database.
+
+#![allow(dead_code)] // not every test uses every helper
+
+use std::sync::Arc;
+
+use arc_swap::ArcSwap;
+use archk::v1::{
+    auth::{Token, TokenTy},
+    user::UserID,
+};
+use archk_api::{app::AppState, roles::UserRoles};
+use axum::{
+    body::Body,
+    http::{header::AUTHORIZATION, header::CONTENT_TYPE, Method, Request, StatusCode},
+    Router,
+};
+use http_body_util::BodyExt;
+use serde_json::Value;
+use sqlx::{sqlite::SqlitePoolOptions, SqlitePool};
+use tower::ServiceExt;
+
+/// Roles of test instance: levels as in `config.example.yml`, users may create services
+/// and organizations.
+const ROLES: &str = r#"
+- { name: admin, level: 100, permissions: ["*"] }
+- { name: user, level: 10, permissions: [space.create, service.create, org.create] }
+- { name: guest, level: 0 }
+"#;
+
+pub const ADMIN: i64 = 100;
+pub const USER: i64 = 10;
+pub const GUEST: i64 = 0;
+
+/// Password of users created by [`TestApp::user`].
+pub const PASSWORD: &str = "password1";
+
+/// Empty migrated in-memory database and state over it.
+pub async fn state() -> AppState {
+    // one connection, otherwise every connection gets its own database
+    let db = SqlitePoolOptions::new()
+        .max_connections(1)
+        .connect("sqlite::memory:")
+        .await
+        .expect("database");
+    archk_api::apply_migrations(&db).await.expect("migrations");
+    let roles: UserRoles = serde_yaml::from_str(ROLES).expect("roles");
+
+    AppState {
+        db,
+        db_read: None,
+        roles: Arc::new(ArcSwap::from_pointee(roles)),
+        oidc: None,
+        lockout: Default::default(),
+        token_format: Default::default(),
+        proxy_auth: None,
+        invite_waves: None,
+        services: Default::default(),
+        cache: Default::default(),
+        client_cert_header: None,
+        trusted_proxies: Arc::new([]),
+        attachments: None,
+        notifier: None,
+    }
+}
+
+/// Created user with personal token.
+pub struct TestUser {
+    pub id: String,
+    pub name: String,
+    pub token: String,
+}
+
+pub struct TestApp {
+    pub state: AppState,
+    pub router: Router,
+}
+
+impl TestApp {
+    pub async fn new() -> Self {
+        Self::with_state(state().await)
+    }
+
+    /// App over state changed by test, eg. `TestApp::with_state(AppState { .., ..state().await })`.
+    pub fn with_state(state: AppState) -> Self {
+        let router = archk_api::v1::get_routes(state.clone(), None, 1024 * 1024, None)
+            .with_state(state.clone());
+        Self { state, router }
+    }
+
+    pub fn db(&self) -> &SqlitePool {
+        &self.state.db
+    }
+
+    /// Send request, returns status and JSON body (`Null` if body is not JSON).
+    pub async fn request(
+        &self,
+        method: Method,
+        uri: &str,
+        token: Option<&str>,
+        body: Option<Value>,
+    ) -> (StatusCode, Value) {
+        let mut request = Request::builder().method(method).uri(uri);
+        if let Some(token) = token {
+            request = request.header(AUTHORIZATION, format!("Bearer {token}"));
+        }
+        let request = match body {
+            Some(body) => request
+                .header(CONTENT_TYPE, "application/json")
+                .body(Body::from(body.to_string())),
+            None => request.body(Body::empty()),
+        }
+        .unwrap();
+
+        let response = self.router.clone().oneshot(request).await.unwrap();
+        let status = response.status();
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        (status, serde_json::from_slice(&body).unwrap_or(Value::Null))
+    }
+
+    /// Same as [`TestApp::request`], but expects success and returns `response`.
+    pub async fn ok(
+        &self,
+        method: Method,
+        uri: &str,
+        token: Option<&str>,
+        body: Option<Value>,
+    ) -> Value {
+        let (status, body) = self.request(method.clone(), uri, token, body).await;
+        assert!(status.is_success(), "{method} {uri}: {status} {body}");
+        body["response"].clone()
+    }
+
+    /// Same as [`TestApp::request`], but expects API error and returns its code.
+    pub async fn err(
+        &self,
+        method: Method,
+        uri: &str,
+        token: Option<&str>,
+        body: Option<Value>,
+    ) -> u64 {
+        let (status, body) = self.request(method.clone(), uri, token, body).await;
+        assert!(
+            !status.is_success(),
+            "{method} {uri}: expected error, got {status} {body}"
+        );
+        body["error"]["code"]
+            .as_u64()
+            .unwrap_or_else(|| panic!("{method} {uri}: not API error: {body}"))
+    }
+
+    /// Insert user with [`PASSWORD`] and personal token directly into database.
+    /// Registration endpoint is not used, its bcrypt cost is too slow for tests.
+    pub async fn user(&self, name: &str, level: i64) -> TestUser {
+        let id = UserID::new().to_string();
+        let hash = bcrypt::hash(PASSWORD, 4).expect("bcrypt");
+        sqlx::query!(
+            "INSERT INTO users(id, name, level, password_hash) VALUES (?, ?, ?, ?)",
+            id,
+            name,
+            level,
+            hash
+        )
+        .execute(self.db())
+        .await
+        .expect("database");
+
+        let token = Token::new(TokenTy::Personal);
+        let (iat, hash) = (token.iat as i64, archk_api::tokens::hash(&token));
+        sqlx::query!(
+            "INSERT INTO tokens(iat, hash, user_id) VALUES (?, ?, ?)",
+            iat,
+            hash,
+            id
+        )
+        .execute(self.db())
+        .await
+        .expect("database");
+
+        TestUser {
+            id,
+            name: name.into(),
+            token: token.to_string(),
+        }
+    }
+
+    /// Create space owned by user, returns its ID.
+    pub async fn space(&self, owner: &TestUser, title: &str) -> String {
+        let space = self
+            .ok(
+                Method::PUT,
+                "/space",
+                Some(&owner.token),
+                Some(serde_json::json!({ "title": title })),
+            )
+            .await;
+        space["id"].as_str().unwrap().to_string()
+    }
+
+    /// Create service of type `ty` in space and its token, returns service ID and token.
+    pub async fn service(&self, owner: &TestUser, space_id: &str, ty: i64) -> (String, String) {
+        let service = self
+            .ok(
+                Method::PUT,
+                "/service",
+                Some(&owner.token),
+                Some(serde_json::json!({ "ty": ty, "space_id": space_id, "name": "Door" })),
+            )
+            .await;
+        let id = service["id"].as_str().unwrap().to_string();
+        let token = self
+            .ok(
+                Method::PUT,
+                &format!("/service/{id}/tokens"),
+                Some(&owner.token),
+                None,
+            )
+            .await;
+        (id, token["token"].as_str().unwrap().to_string())
+    }
+}
@@ -0,0 +1,65 @@
+//! Every documented endpoint is mounted and every mounted endpoint is documented.
+//!
+//! Router and documentation are generated by the same `routes!` macro, so this test probes
+//! router with each [`ENDPOINTS`] entry and checks that documentation is complete.
+
+mod common;
+
+use archk::v1::api;
+use archk_api::v1::routes::ENDPOINTS;
+use axum::{
+    body::Body,
+    http::{Method, Request, StatusCode},
+};
+use http_body_util::BodyExt;
+use tower::ServiceExt;
+
+#[tokio::test]
+async fn endpoints_are_mounted_and_documented() {
+    let state = common::state().await;
+    let router = archk_api::v1::get_routes(state.clone(), None, 1024, None).with_state(state);
+
+    let no_endpoint = api::Error::NoEndpoint as u16;
+    for endpoint in ENDPOINTS {
+        let missing = endpoint.missing_documentation();
+        assert!(
+            missing.is_empty(),
+            "{} {}: undocumented {}",
+            endpoint.method,
+            endpoint.path,
+            missing.join(", ")
+        );
+
+        // Parameters are replaced with placeholders, request fails on authorization or body
+        let path: Vec<_> = endpoint
+            .path
+            .split('/')
+            .map(|v| match v.find(':') {
+                Some(i) => format!("{}x", &v[..i]),
+                None => v.to_string(),
+            })
+            .collect();
+        let request = Request::builder()
+            .method(Method::from_bytes(endpoint.method.to_string().as_bytes()).unwrap())
+            .uri(path.join("/"))
+            .body(Body::empty())
+            .unwrap();
+        let response = router.clone().oneshot(request).await.unwrap();
+
+        let status = response.status();
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_ne!(
+            status,
+            StatusCode::METHOD_NOT_ALLOWED,
+            "{} {}: method is not mounted",
+            endpoint.method,
+            endpoint.path
+        );
+        assert_ne!(
+            body["error"]["code"], no_endpoint,
+            "{} {}: path is not mounted",
+            endpoint.method, endpoint.path
+        );
+    }
+}
@@ -0,0 +1,138 @@
+//! Organizations: members, their roles and access to spaces.
+
+mod common;
+
+use archk::v1::api;
+use axum::http::Method;
+use common::{TestApp, GUEST, USER};
+use serde_json::json;
+
+#[tokio::test]
+async fn org_admin_manages_org_spaces() {
+    let app = TestApp::new().await;
+    let owner = app.user("greg", USER).await;
+    let admin = app.user("alice", GUEST).await;
+    let member = app.user("bob", GUEST).await;
+
+    let org = app
+        .ok(
+            Method::PUT,
+            "/org",
+            Some(&owner.token),
+            Some(json!({ "title": "ACME" })),
+        )
+        .await;
+    let org = org["id"].as_str().unwrap();
+    for (user, role) in [(&admin, 10), (&member, 0)] {
+        app.ok(
+            Method::PUT,
+            &format!("/org/{org}/members"),
+            Some(&owner.token),
+            Some(json!({ "user_id": user.id, "role": role })),
+        )
+        .await;
+    }
+    let members = app
+        .ok(
+            Method::GET,
+            &format!("/org/{org}/members"),
+            Some(&member.token),
+            None,
+        )
+        .await;
+    assert_eq!(members.as_array().unwrap().len(), 3);
+    assert_eq!(members[0]["name"], "greg");
+
+    let space = app.space(&owner, "Lab").await;
+    app.ok(
+        Method::PUT,
+        &format!("/org/{org}/spaces/{space}"),
+        Some(&owner.token),
+        None,
+    )
+    .await;
+    let spaces = app
+        .ok(
+            Method::GET,
+            &format!("/org/{org}/spaces"),
+            Some(&member.token),
+            None,
+        )
+        .await;
+    assert_eq!(spaces[0]["id"], space.as_str());
+
+    // admin of organization has access to space, member has not
+    let items = format!("/space/{space}/item");
+    app.ok(Method::GET, &items, Some(&admin.token), None).await;
+    let code = app
+        .err(Method::GET, &items, Some(&member.token), None)
+        .await;
+    assert_eq!(code, api::Error::ObjectNotFound as u64);
+
+    // admin can't promote to own role or remove owner
+    let code = app
+        .err(
+            Method::PUT,
+            &format!("/org/{org}/members"),
+            Some(&admin.token),
+            Some(json!({ "user_id": member.id, "role": 10 })),
+        )
+        .await;
+    assert_eq!(code, api::Error::Forbidden as u64);
+    let code = app
+        .err(
+            Method::DELETE,
+            &format!("/org/{org}/members/{}", owner.id),
+            Some(&admin.token),
+            None,
+        )
+        .await;
+    assert_eq!(code, api::Error::Forbidden as u64);
+    let code = app
+        .err(
+            Method::DELETE,
+            &format!("/org/{org}/members/{}", owner.id),
+            Some(&owner.token),
+            None,
+        )
+        .await;
+    assert_eq!(code, api::Error::Conflict as u64);
+
+    // removed admin loses access
+    app.ok(
+        Method::DELETE,
+        &format!("/org/{org}/members/{}", admin.id),
+        Some(&owner.token),
+        None,
+    )
+    .await;
+    let code = app.err(Method::GET, &items, Some(&admin.token), None).await;
+    assert_eq!(code, api::Error::ObjectNotFound as u64);
+    let code = app
+        .err(
+            Method::GET,
+            &format!("/org/{org}"),
+            Some(&admin.token),
+            None,
+        )
+        .await;
+    assert_eq!(code, api::Error::ObjectNotFound as u64);
+}
+
+#[tokio::test]
+async fn org_creation_requires_permission() {
+    let app = TestApp::new().await;
+    let guest = app.user("bob", GUEST).await;
+
+    let code = app
+        .err(
+            Method::PUT,
+            "/org",
+            Some(&guest.token),
+            Some(json!({ "title": "ACME" })),
+        )
+        .await;
+    assert_eq!(code, api::Error::Forbidden as u64);
+    let orgs = app.ok(Method::GET, "/org", Some(&guest.token), None).await;
+    assert_eq!(orgs.as_array().unwrap().len(), 0);
+}
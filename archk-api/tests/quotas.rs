@@ -0,0 +1,87 @@
+//! Quotas of roles: limits of spaces, items, services and their usage.
+
+mod common;
+
+use std::sync::Arc;
+
+use arc_swap::ArcSwap;
+use archk::v1::{api, service::ServiceAccountTy};
+use archk_api::{app::AppState, roles::UserRoles};
+use axum::http::Method;
+use common::{TestApp, ADMIN, USER};
+use serde_json::json;
+
+const ROLES: &str = r#"
+- { name: admin, level: 100, permissions: ["*"] }
+- name: user
+  level: 10
+  permissions: [space.create, service.create]
+  quota: { max_spaces: 1, max_items_per_space: 2, max_services_per_space: 1 }
+"#;
+
+async fn app() -> TestApp {
+    let roles: UserRoles = serde_yaml::from_str(ROLES).unwrap();
+    TestApp::with_state(AppState {
+        roles: Arc::new(ArcSwap::from_pointee(roles)),
+        ..common::state().await
+    })
+}
+
+#[tokio::test]
+async fn quota_limits_creation() {
+    let app = app().await;
+    let user = app.user("greg", USER).await;
+    let token = Some(user.token.as_str());
+
+    let space = app.space(&user, "Lab").await;
+    let code = app
+        .err(
+            Method::PUT,
+            "/space",
+            token,
+            Some(json!({ "title": "Office" })),
+        )
+        .await;
+    assert_eq!(code, api::Error::QuotaExceeded as u64);
+
+    let item = |serial: &str| json!({ "title": "Key", "pl_serial": serial });
+    let uri = format!("/space/{space}/item");
+    app.ok(Method::PUT, &uri, token, Some(item("k1"))).await;
+    app.ok(Method::PUT, &uri, token, Some(item("k2"))).await;
+    let code = app.err(Method::PUT, &uri, token, Some(item("k3"))).await;
+    assert_eq!(code, api::Error::QuotaExceeded as u64);
+
+    let ty = ServiceAccountTy::SpaceActor as i64;
+    app.service(&user, &space, ty).await;
+    let code = app
+        .err(
+            Method::PUT,
+            "/service",
+            token,
+            Some(json!({ "ty": ty, "space_id": space, "name": "Gate" })),
+        )
+        .await;
+    assert_eq!(code, api::Error::QuotaExceeded as u64);
+
+    let quota = app.ok(Method::GET, "/user/quota", token, None).await;
+    assert_eq!(quota["spaces"], json!({ "used": 1, "max": 1 }));
+    assert_eq!(quota["ssh_keys"], json!({ "used": 0, "max": null }));
+    assert_eq!(quota["spaces_usage"][0]["space_id"], space.as_str());
+    assert_eq!(
+        quota["spaces_usage"][0]["items"],
+        json!({ "used": 2, "max": 2 })
+    );
+    assert_eq!(
+        quota["spaces_usage"][0]["services"],
+        json!({ "used": 1, "max": 1 })
+    );
+
+    // quota of space owner applies, not one of admin
+    let admin = app.user("root", ADMIN).await;
+    let code = app
+        .err(Method::PUT, &uri, Some(&admin.token), Some(item("k3")))
+        .await;
+    assert_eq!(code, api::Error::QuotaExceeded as u64);
+    app.space(&admin, "Office").await;
+    app.space(&admin, "Garage").await;
+}
@@ -0,0 +1,218 @@
+//! Reservations of items and their availability.
+
+mod common;
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use archk::v1::api;
+use axum::{
+    body::Body,
+    http::{header::CONTENT_TYPE, Method, Request, StatusCode},
+};
+use common::{TestApp, USER};
+use http_body_util::BodyExt;
+use serde_json::json;
+use tower::ServiceExt;
+
+const HOUR: i64 = 1000 * 60 * 60;
+
+fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as i64
+}
+
+#[tokio::test]
+async fn reservations_do_not_overlap() {
+    let app = TestApp::new().await;
+    let user = app.user("greg", USER).await;
+    let token = Some(user.token.as_str());
+    let space = app.space(&user, "Lab").await;
+    for pl_id in ["tg:1", "tg:2"] {
+        app.ok(
+            Method::PUT,
+            &format!("/space/{space}/account"),
+            token,
+            Some(json!({ "pl_id": pl_id, "pl_name": null, "pl_displayname": null })),
+        )
+        .await;
+    }
+    let item = app
+        .ok(
+            Method::PUT,
+            &format!("/space/{space}/item"),
+            token,
+            Some(json!({ "title": "Projector", "pl_serial": "p1" })),
+        )
+        .await;
+    let item = item["id"].as_str().unwrap();
+    let uri = format!("/space/{space}/item/{item}/reservations");
+    let base = now() + 10 * HOUR;
+    let reserve = |acc_id: &str, start: i64, end: i64| json!({ "acc_id": acc_id, "start": base + start * HOUR, "end": base + end * HOUR });
+
+    let first = app
+        .ok(Method::PUT, &uri, token, Some(reserve("tg:1", 0, 2)))
+        .await;
+    assert_eq!(first["acc_id"], "tg:1");
+    let code = app
+        .err(Method::PUT, &uri, token, Some(reserve("tg:2", 1, 3)))
+        .await;
+    assert_eq!(code, api::Error::Conflict as u64);
+    let code = app
+        .err(Method::PUT, &uri, token, Some(reserve("tg:2", 3, 3)))
+        .await;
+    assert_eq!(code, api::Error::MalformedData as u64);
+    app.ok(Method::PUT, &uri, token, Some(reserve("tg:2", 2, 4)))
+        .await;
+    let list = app.ok(Method::GET, &uri, token, None).await;
+    assert_eq!(list.as_array().unwrap().len(), 2);
+
+    let free = app
+        .ok(
+            Method::GET,
+            &format!(
+                "/space/{space}/item/{item}/availability?from={}&to={}",
+                base - HOUR,
+                base + 6 * HOUR
+            ),
+            token,
+            None,
+        )
+        .await;
+    assert_eq!(
+        free,
+        json!([
+            { "start": base - HOUR, "end": base },
+            { "start": base + 4 * HOUR, "end": base + 6 * HOUR },
+        ])
+    );
+
+    let id = first["id"].as_str().unwrap();
+    app.ok(Method::DELETE, &format!("{uri}/{id}"), token, None)
+        .await;
+    let code = app
+        .err(Method::DELETE, &format!("{uri}/{id}"), token, None)
+        .await;
+    assert_eq!(code, api::Error::ObjectNotFound as u64);
+
+    // item can't be taken over reservation of other account
+    let take = format!("/space/{space}/item/{item}/take");
+    let code = app
+        .err(
+            Method::POST,
+            &take,
+            token,
+            Some(json!({ "acc_id": "tg:1", "due_at": base + 3 * HOUR })),
+        )
+        .await;
+    assert_eq!(code, api::Error::Conflict as u64);
+    app.ok(
+        Method::POST,
+        &take,
+        token,
+        Some(json!({ "acc_id": "tg:2", "due_at": base + 5 * HOUR })),
+    )
+    .await;
+
+    // checked out item is busy until its due date
+    let code = app
+        .err(Method::PUT, &uri, token, Some(reserve("tg:1", 4, 6)))
+        .await;
+    assert_eq!(code, api::Error::Conflict as u64);
+    app.ok(Method::PUT, &uri, token, Some(reserve("tg:1", 5, 6)))
+        .await;
+}
+
+#[tokio::test]
+async fn calendar_feed() {
+    let app = TestApp::new().await;
+    let user = app.user("greg", USER).await;
+    let token = Some(user.token.as_str());
+    let space = app.space(&user, "Lab").await;
+    app.ok(
+        Method::PUT,
+        &format!("/space/{space}/account"),
+        token,
+        Some(json!({ "pl_id": "tg:1", "pl_name": "Greg", "pl_displayname": null })),
+    )
+    .await;
+    let item = |title: &str, serial: &str| json!({ "title": title, "pl_serial": serial });
+    let uri = format!("/space/{space}/item");
+    let drill = app
+        .ok(Method::PUT, &uri, token, Some(item("Drill", "d1")))
+        .await;
+    let saw = app
+        .ok(Method::PUT, &uri, token, Some(item("Saw, big", "s1")))
+        .await;
+    let base = now() + HOUR;
+    app.ok(
+        Method::PUT,
+        &format!(
+            "/space/{space}/item/{}/reservations",
+            drill["id"].as_str().unwrap()
+        ),
+        token,
+        Some(json!({ "acc_id": "tg:1", "start": base, "end": base + HOUR, "note": "repair" })),
+    )
+    .await;
+    app.ok(
+        Method::POST,
+        &format!("/space/{space}/item/{}/take", saw["id"].as_str().unwrap()),
+        token,
+        Some(json!({ "acc_id": "tg:1", "due_at": base + 2 * HOUR })),
+    )
+    .await;
+
+    let feed = app
+        .ok(
+            Method::POST,
+            &format!("/space/{space}/calendar/token"),
+            token,
+            Some(json!({})),
+        )
+        .await;
+    let get = |uri: String| {
+        let request = Request::get(uri).body(Body::empty()).unwrap();
+        app.router.clone().oneshot(request)
+    };
+
+    let res = get(feed["path"].as_str().unwrap().replace("/api/v1", ""))
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(res.headers()[CONTENT_TYPE], "text/calendar; charset=utf-8");
+    let body = res.into_body().collect().await.unwrap().to_bytes();
+    let ics = String::from_utf8(body.to_vec()).unwrap();
+    assert!(ics.contains("\r\nX-WR-CALNAME:Lab\r\n"), "{ics}");
+    assert!(
+        ics.contains("\r\nSUMMARY:Drill reserved by Greg\r\n"),
+        "{ics}"
+    );
+    assert!(ics.contains("\r\nDESCRIPTION:repair\r\n"), "{ics}");
+    assert!(
+        ics.contains("\r\nSUMMARY:Saw\\, big due from Greg\r\n"),
+        "{ics}"
+    );
+
+    // token is bound to space
+    let other = app.space(&user, "Office").await;
+    let feed_token = feed["token"].as_str().unwrap();
+    let res = get(format!("/space/{other}/calendar.ics?token={feed_token}"))
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::NOT_FOUND);
+
+    // revoked tokens stop working, new ones are valid
+    let uri = format!("/space/{space}/calendar/token");
+    app.ok(Method::DELETE, &uri, token, None).await;
+    let res = get(format!("/space/{space}/calendar.ics?token={feed_token}"))
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::NOT_FOUND);
+    let feed = app.ok(Method::POST, &uri, token, Some(json!({}))).await;
+    let res = get(feed["path"].as_str().unwrap().replace("/api/v1", ""))
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+}
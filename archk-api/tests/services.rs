@@ -0,0 +1,555 @@
+//! Services of space: actor events, watcher logs and token revocation.
+
+mod common;
+
+use std::{net::SocketAddr, sync::Arc};
+
+use archk::v1::{api, auth::Token, service::ServiceAccountTy};
+use archk_api::app::AppState;
+use axum::{
+    body::Body,
+    extract::ConnectInfo,
+    http::{HeaderName, Method, Request, StatusCode},
+};
+use common::{TestApp, USER};
+use serde_json::json;
+use tower::ServiceExt;
+
+const ACTOR: i64 = ServiceAccountTy::SpaceActor as i64;
+const WATCHER: i64 = ServiceAccountTy::SpaceEventWatcher as i64;
+const MANAGER: i64 = ServiceAccountTy::SpaceManager as i64;
+
+#[tokio::test]
+async fn actor_events_are_logged() {
+    let app = TestApp::new().await;
+    let user = app.user("greg", USER).await;
+    let space = app.space(&user, "Lab").await;
+    app.ok(
+        Method::PUT,
+        &format!("/space/{space}/account"),
+        Some(&user.token),
+        Some(json!({ "pl_id": "tg:42", "pl_name": null, "pl_displayname": null })),
+    )
+    .await;
+    let (_, actor) = app.service(&user, &space, ACTOR).await;
+    let (_, watcher) = app.service(&user, &space, WATCHER).await;
+
+    let res = app
+        .ok(
+            Method::POST,
+            "/service/_/space/events",
+            Some(&actor),
+            Some(json!({ "unlock": { "pl_id": "unknown" } })),
+        )
+        .await;
+    assert_eq!(res["unlock"]["decision"], "deny");
+    assert_eq!(res["unlock"]["reason"], "unknown_account");
+
+    let res = app
+        .ok(
+            Method::POST,
+            "/service/_/space/events",
+            Some(&actor),
+            Some(json!({ "report": { "pl_id": "tg:42", "detail": "door is broken" } })),
+        )
+        .await;
+    let report_id = res["report"]["id"].as_str().unwrap();
+
+    let logs = app
+        .ok(
+            Method::GET,
+            "/service/_/space/logs?after=0",
+            Some(&watcher),
+            None,
+        )
+        .await;
+    let ids: Vec<_> = logs
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|v| v["id"].as_str().unwrap())
+        .collect();
+    assert!(ids.contains(&report_id), "{logs}");
+
+    // watcher can not act in space
+    let code = app
+        .err(
+            Method::POST,
+            "/service/_/space/events",
+            Some(&watcher),
+            Some(json!({ "unlock": { "pl_id": "tg:42" } })),
+        )
+        .await;
+    assert_eq!(code, api::Error::Forbidden as u64);
+}
+
+#[tokio::test]
+async fn buffered_reports_are_filed_at_once() {
+    let app = TestApp::new().await;
+    let user = app.user("greg", USER).await;
+    let space = app.space(&user, "Lab").await;
+    let (_, actor) = app.service(&user, &space, ACTOR).await;
+    let (_, watcher) = app.service(&user, &space, WATCHER).await;
+    let report = |detail: &str| json!({ "report": { "pl_id": "tg:42", "detail": detail } });
+
+    // unlocks need immediate decision
+    let code = app
+        .err(
+            Method::POST,
+            "/service/_/space/events/batch",
+            Some(&actor),
+            Some(json!([report("a"), { "unlock": { "pl_id": "tg:42" } }])),
+        )
+        .await;
+    assert_eq!(code, api::Error::MalformedData as u64);
+
+    // unknown item fails whole batch
+    let code = app
+        .err(
+            Method::POST,
+            "/service/_/space/events/batch",
+            Some(&actor),
+            Some(json!([
+                report("a"),
+                { "report": { "item_id": "unknown", "detail": "b" } },
+            ])),
+        )
+        .await;
+    assert_eq!(code, api::Error::ObjectNotFound as u64);
+
+    let res = app
+        .ok(
+            Method::POST,
+            "/service/_/space/events/batch",
+            Some(&actor),
+            Some(json!([report("door is broken"), report("lamp is broken")])),
+        )
+        .await;
+    let details: Vec<_> = res
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|v| v["detail"].as_str().unwrap())
+        .collect();
+    assert_eq!(details, ["door is broken", "lamp is broken"], "{res}");
+
+    let logs = app
+        .ok(
+            Method::GET,
+            "/service/_/space/logs?after=0",
+            Some(&watcher),
+            None,
+        )
+        .await;
+    for entry in res.as_array().unwrap() {
+        assert!(logs.as_array().unwrap().contains(entry), "{logs}");
+    }
+    assert_eq!(logs.as_array().unwrap().len(), 2, "{logs}");
+}
+
+#[tokio::test]
+async fn open_reports_deny_holder_not_reporter() {
+    let app = TestApp::new().await;
+    let user = app.user("greg", USER).await;
+    let token = Some(user.token.as_str());
+    let space = app.space(&user, "Lab").await;
+    for pl_id in ["tg:1", "tg:2"] {
+        app.ok(
+            Method::PUT,
+            &format!("/space/{space}/account"),
+            token,
+            Some(json!({ "pl_id": pl_id, "pl_name": null, "pl_displayname": null })),
+        )
+        .await;
+    }
+    app.ok(
+        Method::PATCH,
+        &format!("/space/{space}/policy"),
+        token,
+        Some(json!({ "require_keycard": false, "deny_on_open_reports": true })),
+    )
+    .await;
+    let drill = app
+        .ok(
+            Method::PUT,
+            &format!("/space/{space}/item"),
+            token,
+            Some(json!({ "title": "Drill", "pl_serial": "d1" })),
+        )
+        .await;
+    let drill = drill["id"].as_str().unwrap();
+    app.ok(
+        Method::POST,
+        &format!("/space/{space}/item/{drill}/take"),
+        token,
+        Some(json!({ "acc_id": "tg:2" })),
+    )
+    .await;
+    let (_, actor) = app.service(&user, &space, ACTOR).await;
+    let (_, manager) = app.service(&user, &space, MANAGER).await;
+
+    // tg:1 reports drill held by tg:2
+    let res = app
+        .ok(
+            Method::POST,
+            "/service/_/space/events",
+            Some(&actor),
+            Some(json!({ "report": { "pl_id": "tg:1", "item_id": drill, "detail": "broken" } })),
+        )
+        .await;
+    let report_id = res["report"]["id"].as_str().unwrap();
+
+    let unlock = |pl_id: &'static str| {
+        app.ok(
+            Method::POST,
+            "/service/_/space/events",
+            Some(&actor),
+            Some(json!({ "unlock": { "pl_id": pl_id } })),
+        )
+    };
+    let res = unlock("tg:1").await;
+    assert_eq!(res["unlock"]["decision"], "allow");
+    let res = unlock("tg:2").await;
+    assert_eq!(res["unlock"]["decision"], "deny");
+    assert_eq!(res["unlock"]["reason"], "open_reports");
+
+    app.ok(
+        Method::POST,
+        &format!("/service/_/space/reports/{report_id}"),
+        Some(&manager),
+        Some(json!({})),
+    )
+    .await;
+    let res = unlock("tg:2").await;
+    assert_eq!(res["unlock"]["decision"], "allow");
+}
+
+#[tokio::test]
+async fn undecided_unlocks_are_handed_to_manager() {
+    let app = TestApp::new().await;
+    let user = app.user("greg", USER).await;
+    let token = Some(user.token.as_str());
+    let space = app.space(&user, "Lab").await;
+    for pl_id in ["tg:1", "tg:2"] {
+        app.ok(
+            Method::PUT,
+            &format!("/space/{space}/account"),
+            token,
+            Some(json!({ "pl_id": pl_id, "pl_name": null, "pl_displayname": null })),
+        )
+        .await;
+    }
+    app.ok(
+        Method::PUT,
+        &format!("/space/{space}/account/tg:2/access"),
+        token,
+        Some(json!({ "access": "deny" })),
+    )
+    .await;
+    let policy = app
+        .ok(
+            Method::PATCH,
+            &format!("/space/{space}/policy"),
+            token,
+            Some(json!({ "ask_manager": true })),
+        )
+        .await;
+    assert_eq!(policy["require_keycard"], true);
+    assert_eq!(policy["ask_manager"], true);
+    let (_, actor) = app.service(&user, &space, ACTOR).await;
+    let (_, manager) = app.service(&user, &space, MANAGER).await;
+
+    let unlock = |pl_id: &'static str| {
+        app.ok(
+            Method::POST,
+            "/service/_/space/events",
+            Some(&actor),
+            Some(json!({ "unlock": { "pl_id": pl_id } })),
+        )
+    };
+    let requests = || {
+        app.ok(
+            Method::GET,
+            "/service/_/space/unlock-requests",
+            Some(&manager),
+            None,
+        )
+    };
+
+    // deny list is definite answer, decided immediately
+    let res = unlock("tg:2").await;
+    assert_eq!(res["unlock"]["decision"], "deny");
+    assert_eq!(res["unlock"]["reason"], "deny_list");
+    assert_eq!(requests().await, json!([]));
+
+    // no keycard, left for manager
+    let res = unlock("tg:1").await;
+    assert_eq!(res["unlock"]["decision"], "pending");
+    assert_eq!(res["unlock"]["reason"], "no_keycard");
+    let request_id = res["unlock"]["log_id"].as_str().unwrap();
+    let pending = requests().await;
+    assert_eq!(pending.as_array().unwrap().len(), 1, "{pending}");
+    assert_eq!(pending[0]["id"], request_id);
+    assert_eq!(pending[0]["sp_acc_id"], "tg:1");
+
+    let decision = app
+        .ok(
+            Method::POST,
+            &format!("/service/_/space/unlock-requests/{request_id}"),
+            Some(&manager),
+            Some(json!({ "approve": true })),
+        )
+        .await;
+    assert_eq!(decision["ref_id"], request_id);
+    assert_eq!(requests().await, json!([]));
+}
+
+#[tokio::test]
+async fn watcher_pages_entries_with_same_timestamp() {
+    let app = TestApp::new().await;
+    let user = app.user("greg", USER).await;
+    let space = app.space(&user, "Lab").await;
+    let (_, watcher) = app.service(&user, &space, WATCHER).await;
+
+    // more than a page of entries created at the same millisecond
+    for i in 0..120 {
+        sqlx::query(
+            "INSERT INTO spaces_logs(id, space_id, created_at, act, detail) VALUES (?, ?, 1000, 600, ?)",
+        )
+        .bind(format!("log{i:03}"))
+        .bind(&space)
+        .bind(format!("report {i}"))
+        .execute(app.db())
+        .await
+        .unwrap();
+    }
+
+    let mut seen = Vec::new();
+    let mut uri = "/service/_/space/logs?after=0".to_string();
+    loop {
+        let logs = app.ok(Method::GET, &uri, Some(&watcher), None).await;
+        let Some(last) = logs.as_array().unwrap().last() else {
+            break;
+        };
+        uri = format!(
+            "/service/_/space/logs?after={}&after_id={}",
+            last["created_at"],
+            last["id"].as_str().unwrap()
+        );
+        seen.extend(
+            logs.as_array()
+                .unwrap()
+                .iter()
+                .map(|v| v["id"].as_str().unwrap().to_string()),
+        );
+    }
+    let expected: Vec<_> = (0..120).map(|i| format!("log{i:03}")).collect();
+    assert_eq!(seen, expected);
+}
+
+#[tokio::test]
+async fn deleted_service_is_revoked() {
+    let app = TestApp::new().await;
+    let user = app.user("greg", USER).await;
+    let stranger = app.user("bob", USER).await;
+    let space = app.space(&user, "Lab").await;
+    let (id, token) = app.service(&user, &space, WATCHER).await;
+
+    app.ok(
+        Method::GET,
+        "/service/_/space/logs?after=0",
+        Some(&token),
+        None,
+    )
+    .await;
+
+    let code = app
+        .err(
+            Method::DELETE,
+            &format!("/service/{id}"),
+            Some(&stranger.token),
+            None,
+        )
+        .await;
+    assert_eq!(code, api::Error::ObjectNotFound as u64);
+
+    app.ok(
+        Method::DELETE,
+        &format!("/service/{id}"),
+        Some(&user.token),
+        None,
+    )
+    .await;
+    let code = app
+        .err(
+            Method::GET,
+            "/service/_/space/logs?after=0",
+            Some(&token),
+            None,
+        )
+        .await;
+    assert_eq!(code, api::Error::Unauthorized as u64);
+}
+
+#[tokio::test]
+async fn token_usage_is_throttled() {
+    let app = TestApp::new().await;
+    let user = app.user("greg", USER).await;
+    let space = app.space(&user, "Lab").await;
+    let (id, token) = app.service(&user, &space, WATCHER).await;
+
+    let last_used_at = || async {
+        let tokens = app
+            .ok(
+                Method::GET,
+                &format!("/service/{id}/tokens"),
+                Some(&user.token),
+                None,
+            )
+            .await;
+        tokens[0]["last_used_at"].as_i64()
+    };
+    let logs = || app.ok(Method::GET, "/service/_/space/logs", Some(&token), None);
+
+    assert_eq!(last_used_at().await, None);
+    logs().await;
+    let first = last_used_at().await.unwrap();
+
+    // used recently, not written again
+    sqlx::query("UPDATE service_tokens SET last_used_at = ?")
+        .bind(first - 1000)
+        .execute(app.db())
+        .await
+        .unwrap();
+    logs().await;
+    assert_eq!(last_used_at().await, Some(first - 1000));
+
+    sqlx::query("UPDATE service_tokens SET last_used_at = 0")
+        .execute(app.db())
+        .await
+        .unwrap();
+    logs().await;
+    assert!(last_used_at().await.unwrap() >= first);
+}
+
+#[tokio::test]
+async fn single_token_is_revoked() {
+    let app = TestApp::new().await;
+    let user = app.user("greg", USER).await;
+    let space = app.space(&user, "Lab").await;
+    let (id, token) = app.service(&user, &space, WATCHER).await;
+    let (_, other) = app.service(&user, &space, WATCHER).await;
+
+    let Token { iat, rnd, .. } = Token::parse(&token).unwrap();
+    app.ok(
+        Method::DELETE,
+        &format!("/service/{id}/tokens/{iat}/{rnd}"),
+        Some(&user.token),
+        None,
+    )
+    .await;
+    let code = app
+        .err(
+            Method::GET,
+            "/service/_/space/logs?after=0",
+            Some(&token),
+            None,
+        )
+        .await;
+    assert_eq!(code, api::Error::Unauthorized as u64);
+    app.ok(
+        Method::GET,
+        "/service/_/space/logs?after=0",
+        Some(&other),
+        None,
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn heartbeat_status() {
+    let app = TestApp::new().await;
+    let user = app.user("greg", USER).await;
+    let space = app.space(&user, "Lab").await;
+    let (_, actor) = app.service(&user, &space, ACTOR).await;
+    let status = || async {
+        let services = app
+            .ok(
+                Method::GET,
+                &format!("/space/{space}/services"),
+                Some(&user.token),
+                None,
+            )
+            .await;
+        services[0]["status"].clone()
+    };
+
+    let seen = app
+        .ok(
+            Method::POST,
+            "/service/_/heartbeat",
+            Some(&actor),
+            Some(json!({ "status": "ok" })),
+        )
+        .await;
+    assert!(seen.as_i64().is_some(), "{seen}");
+    assert_eq!(status().await, "ok");
+
+    // malformed body keeps status
+    let code = app
+        .err(
+            Method::POST,
+            "/service/_/heartbeat",
+            Some(&actor),
+            Some(json!({ "status": 42 })),
+        )
+        .await;
+    assert_eq!(code, api::Error::MalformedData as u64);
+    assert_eq!(status().await, "ok");
+
+    // empty body clears it
+    app.ok(Method::POST, "/service/_/heartbeat", Some(&actor), None)
+        .await;
+    assert_eq!(status().await, json!(null));
+}
+
+#[tokio::test]
+async fn certificate_only_from_trusted_proxies() {
+    let app = TestApp::with_state(AppState {
+        client_cert_header: Some(HeaderName::from_static("x-client-cert-fingerprint")),
+        trusted_proxies: Arc::new(["10.0.0.0/8".parse().unwrap()]),
+        ..common::state().await
+    });
+    let user = app.user("greg", USER).await;
+    let space = app.space(&user, "Lab").await;
+    let (service, _) = app.service(&user, &space, ACTOR).await;
+    let fingerprint = "ab".repeat(32);
+    app.ok(
+        Method::PUT,
+        &format!("/service/{service}/certificate"),
+        Some(&user.token),
+        Some(json!({ "fingerprint": fingerprint })),
+    )
+    .await;
+
+    let heartbeat = |from: Option<&str>| {
+        let mut request = Request::post("/service/_/heartbeat")
+            .header("X-Client-Cert-Fingerprint", &fingerprint)
+            .body(Body::empty())
+            .unwrap();
+        if let Some(from) = from {
+            let addr: SocketAddr = from.parse().unwrap();
+            request.extensions_mut().insert(ConnectInfo(addr));
+        }
+        app.router.clone().oneshot(request)
+    };
+
+    // unix socket and trusted proxy
+    let res = heartbeat(None).await.unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    let res = heartbeat(Some("10.0.0.1:4000")).await.unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    // header sent directly by client is ignored
+    let res = heartbeat(Some("192.168.0.1:4000")).await.unwrap();
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+}
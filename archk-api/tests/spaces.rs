@@ -0,0 +1,664 @@
+//! Spaces, accounts, items and logs.
+
+mod common;
+
+use std::io::Read;
+
+use archk::v1::{api, service::ServiceAccountTy};
+use archk_api::app::AppConfigServerCompression;
+use axum::{
+    body::Body,
+    http::{
+        header::{ACCEPT, ACCEPT_ENCODING, AUTHORIZATION, CONTENT_ENCODING, CONTENT_TYPE, VARY},
+        Method, Request,
+    },
+};
+use common::{TestApp, ADMIN, GUEST, USER};
+use flate2::read::GzDecoder;
+use http_body_util::BodyExt;
+use serde_json::json;
+use tower::ServiceExt;
+
+#[tokio::test]
+async fn space_lifecycle() {
+    let app = TestApp::new().await;
+    let user = app.user("greg", USER).await;
+    let token = Some(user.token.as_str());
+
+    let space = app.space(&user, "Lab").await;
+    let res = app
+        .ok(Method::GET, &format!("/space/{space}"), token, None)
+        .await;
+    assert_eq!(res["space"]["title"], "Lab");
+    assert_eq!(res["owner"]["id"], user.id.as_str());
+
+    app.ok(
+        Method::PATCH,
+        &format!("/space/{space}"),
+        token,
+        Some(json!({ "title": "Lab 301" })),
+    )
+    .await;
+    let spaces = app.ok(Method::GET, "/user/spaces", token, None).await;
+    assert_eq!(spaces[0]["title"], "Lab 301");
+
+    app.ok(
+        Method::POST,
+        &format!("/space/{space}/archive"),
+        token,
+        None,
+    )
+    .await;
+    let code = app
+        .err(
+            Method::PUT,
+            &format!("/space/{space}/item"),
+            token,
+            Some(json!({ "title": "Key", "pl_serial": "k1" })),
+        )
+        .await;
+    assert_eq!(code, api::Error::Conflict as u64);
+
+    app.ok(Method::DELETE, &format!("/space/{space}"), token, None)
+        .await;
+    let code = app
+        .err(Method::GET, &format!("/space/{space}"), token, None)
+        .await;
+    assert_eq!(code, api::Error::ObjectNotFound as u64);
+}
+
+#[tokio::test]
+async fn spaces_are_private() {
+    let app = TestApp::new().await;
+    let owner = app.user("greg", USER).await;
+    let stranger = app.user("bob", USER).await;
+    let admin = app.user("root", ADMIN).await;
+    let guest = app.user("guest", GUEST).await;
+    let space = app.space(&owner, "Lab").await;
+
+    let code = app
+        .err(
+            Method::GET,
+            &format!("/space/{space}"),
+            Some(&stranger.token),
+            None,
+        )
+        .await;
+    assert_eq!(code, api::Error::ObjectNotFound as u64);
+    app.ok(
+        Method::GET,
+        &format!("/space/{space}"),
+        Some(&admin.token),
+        None,
+    )
+    .await;
+
+    let code = app
+        .err(
+            Method::PUT,
+            "/space",
+            Some(&guest.token),
+            Some(json!({ "title": "Lab" })),
+        )
+        .await;
+    assert_eq!(code, api::Error::Forbidden as u64);
+}
+
+#[tokio::test]
+async fn take_and_return_item() {
+    let app = TestApp::new().await;
+    let user = app.user("greg", USER).await;
+    let token = Some(user.token.as_str());
+    let space = app.space(&user, "Lab").await;
+
+    app.ok(
+        Method::PUT,
+        &format!("/space/{space}/account"),
+        token,
+        Some(json!({ "pl_id": "tg:42", "pl_name": "Greg", "pl_displayname": null })),
+    )
+    .await;
+    let item = app
+        .ok(
+            Method::PUT,
+            &format!("/space/{space}/item"),
+            token,
+            Some(json!({ "title": "Key", "pl_serial": "k1" })),
+        )
+        .await;
+    let item_id = item["id"].as_str().unwrap();
+
+    app.ok(
+        Method::POST,
+        &format!("/space/{space}/item/{item_id}/take"),
+        token,
+        Some(json!({ "acc_id": "tg:42" })),
+    )
+    .await;
+    let item = app
+        .ok(
+            Method::GET,
+            &format!("/space/{space}/item/{item_id}"),
+            token,
+            None,
+        )
+        .await;
+    assert_eq!(item["item"]["current_holder"], "tg:42");
+
+    // item is held, so it can not be taken twice
+    let code = app
+        .err(
+            Method::POST,
+            &format!("/space/{space}/item/{item_id}/take"),
+            token,
+            Some(json!({ "acc_id": "tg:42" })),
+        )
+        .await;
+    assert_eq!(code, api::Error::Conflict as u64);
+
+    app.ok(
+        Method::POST,
+        &format!("/space/{space}/item/{item_id}/return"),
+        token,
+        None,
+    )
+    .await;
+
+    let logs = app
+        .ok(Method::GET, &format!("/space/{space}/logs"), token, None)
+        .await;
+    let acts: Vec<_> = logs
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|v| v["log"]["act"]["name"].as_str().unwrap())
+        .collect();
+    assert!(acts.contains(&"item_taken"), "{acts:?}");
+    assert!(acts.contains(&"item_returned"), "{acts:?}");
+}
+
+#[tokio::test]
+async fn invalid_bodies_and_ids() {
+    let app = TestApp::new().await;
+    let user = app.user("greg", USER).await;
+    let token = Some(user.token.as_str());
+    let space = app.space(&user, "Lab").await;
+
+    let code = app
+        .err(
+            Method::PUT,
+            &format!("/space/{space}/account"),
+            token,
+            Some(json!({ "pl_id": "a/b", "pl_name": null, "pl_displayname": null })),
+        )
+        .await;
+    assert_eq!(code, api::Error::MalformedData as u64);
+
+    let code = app
+        .err(
+            Method::GET,
+            &format!("/space/{space}/item/not-a-cuid!"),
+            token,
+            None,
+        )
+        .await;
+    assert_eq!(code, api::Error::MalformedData as u64);
+}
+
+#[tokio::test]
+async fn dry_run_deletes_nothing() {
+    let app = TestApp::new().await;
+    let user = app.user("greg", USER).await;
+    let token = Some(user.token.as_str());
+    let space = app.space(&user, "Lab").await;
+    app.ok(
+        Method::PUT,
+        &format!("/space/{space}/item/bulk?dry_run=true"),
+        token,
+        Some(json!([{ "title": "Key", "pl_serial": "k1" }])),
+    )
+    .await;
+    let items = app
+        .ok(Method::GET, &format!("/space/{space}/item"), token, None)
+        .await;
+    assert_eq!(items.as_array().unwrap().len(), 0);
+
+    app.ok(
+        Method::PUT,
+        &format!("/space/{space}/item/bulk"),
+        token,
+        Some(json!([{ "title": "Key", "pl_serial": "k1" }, { "title": "Pen", "pl_serial": "p1" }])),
+    )
+    .await;
+    let report = app
+        .ok(
+            Method::DELETE,
+            &format!("/space/{space}?dry_run=true"),
+            token,
+            None,
+        )
+        .await;
+    assert_eq!(report["items"], 2);
+    app.ok(Method::GET, &format!("/space/{space}"), token, None)
+        .await;
+
+    let report = app
+        .ok(Method::DELETE, "/user?dry_run=true", token, None)
+        .await;
+    assert_eq!(report["spaces_deleted"], 1);
+    app.ok(Method::GET, "/user", token, None).await;
+}
+
+#[tokio::test]
+async fn space_deletion_cascades() {
+    let app = TestApp::new().await;
+    let user = app.user("greg", USER).await;
+    let token = Some(user.token.as_str());
+    let space = app.space(&user, "Lab").await;
+    app.ok(
+        Method::PUT,
+        &format!("/space/{space}/account"),
+        token,
+        Some(json!({ "pl_id": "tg:42", "pl_name": null, "pl_displayname": null })),
+    )
+    .await;
+    app.ok(
+        Method::PUT,
+        &format!("/space/{space}/item"),
+        token,
+        Some(json!({ "title": "Key", "pl_serial": "k1", "owner_id": "tg:42" })),
+    )
+    .await;
+    let (service, _) = app
+        .service(&user, &space, ServiceAccountTy::SpaceActor as i64)
+        .await;
+
+    let report = app
+        .ok(Method::DELETE, &format!("/space/{space}"), token, None)
+        .await;
+    assert_eq!(report["accounts"], 1);
+    assert_eq!(report["items"], 1);
+    assert_eq!(report["services"], 1);
+    assert_eq!(report["service_tokens"], 1);
+
+    let left = sqlx::query_scalar!(
+        "SELECT
+            (SELECT COUNT(*) FROM spaces_items WHERE space_id = ?1)
+            + (SELECT COUNT(*) FROM spaces_accounts WHERE space_id = ?1)
+            + (SELECT COUNT(*) FROM service_tokens WHERE service_id = ?2)",
+        space,
+        service
+    )
+    .fetch_one(app.db())
+    .await
+    .unwrap();
+    assert_eq!(left, Some(0));
+}
+
+#[tokio::test]
+async fn space_duplication_and_templates() {
+    let app = TestApp::new().await;
+    let admin = app.user("root", ADMIN).await;
+    let user = app.user("greg", USER).await;
+    let stranger = app.user("bob", USER).await;
+    let token = Some(user.token.as_str());
+    let space = app.space(&user, "Lab").await;
+    app.ok(
+        Method::PUT,
+        &format!("/space/{space}/tag"),
+        token,
+        Some(json!({ "title": "tools" })),
+    )
+    .await;
+    app.ok(
+        Method::PUT,
+        &format!("/space/{space}/item"),
+        token,
+        Some(json!({ "title": "Drill", "pl_serial": "d1" })),
+    )
+    .await;
+
+    let copy = app
+        .ok(
+            Method::POST,
+            &format!("/space/{space}/duplicate"),
+            token,
+            Some(json!({ "title": "Lab 2", "items": true })),
+        )
+        .await;
+    assert_eq!(copy["title"], "Lab 2");
+    let copy = copy["id"].as_str().unwrap();
+    let items = app
+        .ok(Method::GET, &format!("/space/{copy}/item"), token, None)
+        .await;
+    assert_eq!(items[0]["pl_serial"], "d1");
+    let tags = app
+        .ok(Method::GET, &format!("/space/{copy}/tag"), token, None)
+        .await;
+    assert_eq!(tags[0]["title"], "tools");
+
+    // space of other user can be duplicated only after it is marked as template
+    let uri = format!("/space/{space}/duplicate");
+    let code = app
+        .err(Method::POST, &uri, Some(&stranger.token), Some(json!({})))
+        .await;
+    assert_eq!(code, api::Error::ObjectNotFound as u64);
+    let code = app
+        .err(
+            Method::POST,
+            &format!("/space/{space}/template"),
+            token,
+            None,
+        )
+        .await;
+    assert_eq!(code, api::Error::Forbidden as u64);
+    app.ok(
+        Method::POST,
+        &format!("/space/{space}/template"),
+        Some(&admin.token),
+        None,
+    )
+    .await;
+    let templates = app
+        .ok(Method::GET, "/space/templates", Some(&stranger.token), None)
+        .await;
+    assert_eq!(templates[0]["id"], space.as_str());
+    let copy = app
+        .ok(Method::POST, &uri, Some(&stranger.token), Some(json!({})))
+        .await;
+    assert_eq!(copy["title"], "Lab");
+    assert_eq!(copy["owner_id"], stranger.id.as_str());
+    let items = app
+        .ok(
+            Method::GET,
+            &format!("/space/{}/item", copy["id"].as_str().unwrap()),
+            Some(&stranger.token),
+            None,
+        )
+        .await;
+    assert_eq!(items.as_array().unwrap().len(), 0);
+}
+
+#[tokio::test]
+async fn item_statuses() {
+    let app = TestApp::new().await;
+    let user = app.user("greg", USER).await;
+    let token = Some(user.token.as_str());
+    let space = app.space(&user, "Lab").await;
+    app.ok(
+        Method::PUT,
+        &format!("/space/{space}/account"),
+        token,
+        Some(json!({ "pl_id": "tg:42", "pl_name": "Greg", "pl_displayname": null })),
+    )
+    .await;
+    let item = app
+        .ok(
+            Method::PUT,
+            &format!("/space/{space}/item"),
+            token,
+            Some(json!({ "title": "Drill", "pl_serial": "d1" })),
+        )
+        .await;
+    assert_eq!(item["status"], "available");
+    let item_id = item["id"].as_str().unwrap();
+    let uri = format!("/space/{space}/item/{item_id}/status");
+
+    let log = app
+        .ok(
+            Method::PATCH,
+            &uri,
+            token,
+            Some(json!({ "status": "lost", "reason": "left in bus" })),
+        )
+        .await;
+    assert_eq!(log["act"]["name"], "item_status_changed");
+    assert_eq!(log["detail"], "available -> lost: left in bus");
+    let code = app
+        .err(
+            Method::PATCH,
+            &uri,
+            token,
+            Some(json!({ "status": "maintenance" })),
+        )
+        .await;
+    assert_eq!(code, api::Error::Conflict as u64);
+    // lost item can't be taken
+    let code = app
+        .err(
+            Method::POST,
+            &format!("/space/{space}/item/{item_id}/take"),
+            token,
+            Some(json!({ "acc_id": "tg:42" })),
+        )
+        .await;
+    assert_eq!(code, api::Error::Conflict as u64);
+
+    let items = app
+        .ok(
+            Method::GET,
+            &format!("/space/{space}/item?status=lost"),
+            token,
+            None,
+        )
+        .await;
+    assert_eq!(items[0]["id"], item_id);
+    let items = app
+        .ok(
+            Method::GET,
+            &format!("/space/{space}/item?status=available"),
+            token,
+            None,
+        )
+        .await;
+    assert_eq!(items.as_array().unwrap().len(), 0);
+
+    let statuses_uri = format!("/space/{space}/item-statuses");
+    let statuses = json!([
+        { "name": "new", "next": ["ready"] },
+        { "name": "ready", "next": ["lost"], "takeable": true },
+        { "name": "lost", "next": ["ready"] },
+    ]);
+    // `next` should refer to existing statuses
+    let code = app
+        .err(
+            Method::PUT,
+            &statuses_uri,
+            token,
+            Some(json!([{ "name": "new", "next": ["gone"] }])),
+        )
+        .await;
+    assert_eq!(code, api::Error::MalformedData as u64);
+    // item is `lost`, so it can't be removed
+    let code = app
+        .err(
+            Method::PUT,
+            &statuses_uri,
+            token,
+            Some(json!([{ "name": "new" }])),
+        )
+        .await;
+    assert_eq!(code, api::Error::Conflict as u64);
+    app.ok(Method::PUT, &statuses_uri, token, Some(statuses.clone()))
+        .await;
+    let res = app.ok(Method::GET, &statuses_uri, token, None).await;
+    assert_eq!(res[1]["takeable"], true);
+    assert_eq!(res[0]["takeable"], false);
+
+    let item = app
+        .ok(
+            Method::PUT,
+            &format!("/space/{space}/item"),
+            token,
+            Some(json!({ "title": "Saw", "pl_serial": "s1" })),
+        )
+        .await;
+    assert_eq!(item["status"], "new");
+    app.ok(
+        Method::PATCH,
+        &uri,
+        token,
+        Some(json!({ "status": "ready" })),
+    )
+    .await;
+    app.ok(
+        Method::POST,
+        &format!("/space/{space}/item/{item_id}/take"),
+        token,
+        Some(json!({ "acc_id": "tg:42" })),
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn listings_as_csv() {
+    let app = TestApp::new().await;
+    let user = app.user("greg", USER).await;
+    let token = Some(user.token.as_str());
+    let space = app.space(&user, "Lab").await;
+    app.ok(
+        Method::PUT,
+        &format!("/space/{space}/account"),
+        token,
+        Some(json!({ "pl_id": "tg:42", "pl_name": "Greg, Jr.", "pl_displayname": null })),
+    )
+    .await;
+    app.ok(
+        Method::PUT,
+        &format!("/space/{space}/item"),
+        token,
+        Some(json!({ "title": "Drill", "pl_serial": "d1" })),
+    )
+    .await;
+
+    let get = |uri: String, accept: &str| {
+        let request = Request::get(uri)
+            .header(AUTHORIZATION, format!("Bearer {}", user.token))
+            .header(ACCEPT, accept)
+            .body(Body::empty())
+            .unwrap();
+        app.router.clone().oneshot(request)
+    };
+    let text = |res: axum::response::Response| async move {
+        let body = res.into_body().collect().await.unwrap().to_bytes();
+        String::from_utf8(body.to_vec()).unwrap()
+    };
+
+    let res = get(format!("/space/{space}/account"), "text/csv")
+        .await
+        .unwrap();
+    assert_eq!(res.headers()[CONTENT_TYPE], "text/csv");
+    assert_eq!(
+        text(res).await,
+        "pl_id,pl_name,pl_displayname,metadata,version\ntg:42,\"Greg, Jr.\",,{},1\n"
+    );
+
+    // query param wins over `Accept`
+    let res = get(
+        format!("/space/{space}/item?format=csv"),
+        "application/json",
+    )
+    .await
+    .unwrap();
+    let csv = text(res).await;
+    assert!(csv.starts_with("id,title,ty,pl_serial,"), "{csv}");
+    assert!(csv.contains(",Drill,0,d1,"), "{csv}");
+    let res = get(format!("/space/{space}/item?format=json"), "text/csv")
+        .await
+        .unwrap();
+    assert_eq!(res.headers()[CONTENT_TYPE], "application/json");
+
+    let code = app
+        .err(
+            Method::GET,
+            &format!("/space/{space}/item?format=xml"),
+            token,
+            None,
+        )
+        .await;
+    assert_eq!(code, api::Error::MalformedData as u64);
+}
+
+#[tokio::test]
+async fn compressed_listings() {
+    let app = TestApp::new().await;
+    let user = app.user("greg", USER).await;
+    let token = Some(user.token.as_str());
+    let space = app.space(&user, "Lab").await;
+    for i in 0..20 {
+        app.ok(
+            Method::PUT,
+            &format!("/space/{space}/item"),
+            token,
+            Some(json!({ "title": format!("Drill {i}"), "pl_serial": format!("d{i}") })),
+        )
+        .await;
+    }
+
+    let router = |exclude: &[&str]| {
+        let config = AppConfigServerCompression {
+            min_size: 256,
+            exclude: exclude.iter().map(|v| v.to_string()).collect(),
+        };
+        archk_api::v1::get_routes(app.state.clone(), None, 1024 * 1024, Some(config))
+            .with_state(app.state.clone())
+    };
+    let get = |router: axum::Router, uri: String, accept_encoding: Option<&str>| {
+        let mut request = Request::get(uri).header(AUTHORIZATION, format!("Bearer {}", user.token));
+        if let Some(v) = accept_encoding {
+            request = request.header(ACCEPT_ENCODING, v);
+        }
+        router.oneshot(request.body(Body::empty()).unwrap())
+    };
+    let bytes = |res: axum::response::Response| async move {
+        res.into_body().collect().await.unwrap().to_bytes()
+    };
+    let gunzip = |gz: &[u8]| {
+        let mut data = Vec::new();
+        GzDecoder::new(gz).read_to_end(&mut data).unwrap();
+        data
+    };
+    let items = format!("/space/{space}/item");
+
+    let res = get(router(&[]), items.clone(), None).await.unwrap();
+    assert!(!res.headers().contains_key(CONTENT_ENCODING));
+    assert_eq!(res.headers()[VARY], "accept-encoding");
+    let plain = bytes(res).await;
+    let res = get(router(&[]), items.clone(), Some("br, gzip;q=0.8"))
+        .await
+        .unwrap();
+    assert_eq!(res.headers()[CONTENT_ENCODING], "gzip");
+    assert_eq!(res.headers()[VARY], "accept-encoding");
+    assert_eq!(res.headers()[CONTENT_TYPE], "application/json");
+    let gz = bytes(res).await;
+    assert!(gz.len() < plain.len());
+    assert_eq!(gunzip(&gz), plain);
+
+    // gzip refused, endpoint excluded or body too small
+    for (router, uri, accept_encoding) in [
+        (router(&[]), items.clone(), "gzip;q=0, *"),
+        (router(&["/space/:space_id/item"]), items.clone(), "gzip"),
+        (router(&[]), format!("/space/{space}"), "gzip"),
+    ] {
+        let res = get(router, uri, Some(accept_encoding)).await.unwrap();
+        assert!(!res.headers().contains_key(CONTENT_ENCODING));
+    }
+
+    // streamed exports are compressed too
+    let export = format!("/space/{space}/item/export?format=jsonl");
+    let plain = bytes(get(router(&[]), export.clone(), None).await.unwrap()).await;
+    let res = get(router(&[]), export, Some("gzip")).await.unwrap();
+    assert_eq!(res.headers()[CONTENT_ENCODING], "gzip");
+    assert_eq!(gunzip(&bytes(res).await), plain);
+
+    // server-sent events are streamed as is
+    let res = get(
+        router(&[]),
+        format!("/space/{space}/events/sse"),
+        Some("gzip"),
+    )
+    .await
+    .unwrap();
+    assert_eq!(res.headers()[CONTENT_TYPE], "text/event-stream");
+    assert!(!res.headers().contains_key(CONTENT_ENCODING));
+}
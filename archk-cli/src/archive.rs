@@ -0,0 +1,692 @@
+//! Instance archive: versioned NDJSON dump of all instance data, independent of
+//! database backend. First line is [`Header`], other lines are [`Record`]s ordered
+//! so referenced objects always come before records referencing them.
+//!
+//! Tokens, invites and idempotency keys are not exported: users should log in again
+//! and services should be issued new tokens after import.
+
+use std::{
+    collections::HashMap,
+    fmt,
+    io::{self, BufRead, Write},
+};
+
+use archk::v1::{
+    service::ServiceAccountID,
+    space::{SpaceID, SpaceItemID, SpaceTagID},
+    user::{ssh::UserSSHKeyID, UserID},
+};
+use serde::{Deserialize, Serialize};
+use sqlx::{Sqlite, SqlitePool, Transaction};
+use tokio_stream::StreamExt;
+use uuid::Uuid;
+
+/// Version of archive format. Increased on incompatible changes.
+pub const ARCHIVE_VERSION: u32 = 1;
+
+#[derive(Serialize, Deserialize)]
+pub struct Header {
+    pub archk_archive: u32,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Record {
+    User(User),
+    SshKey(SshKey),
+    Space(Space),
+    Policy(Policy),
+    ItemStatuses(ItemStatuses),
+    Account(Account),
+    Tag(Tag),
+    Item(Item),
+    ItemTag(ItemTag),
+    Reservation(Reservation),
+    Service(Service),
+    Log(Log),
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct User {
+    pub id: String,
+    pub name: String,
+    pub invites: i64,
+    pub invited_by: Option<String>,
+    pub level: i64,
+    pub password_hash: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SshKey {
+    pub id: String,
+    pub pubkey_ty: i64,
+    pub pubkey_val: String,
+    pub pubkey_fingerprint: String,
+    pub owner_id: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Space {
+    pub id: String,
+    pub title: String,
+    pub owner_id: String,
+    pub logs_retention_days: Option<i64>,
+    #[serde(default)]
+    pub archived: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Policy {
+    pub space_id: String,
+    pub require_keycard: i64,
+    pub deny_on_open_reports: i64,
+    #[serde(default)]
+    pub ask_manager: i64,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ItemStatuses {
+    pub space_id: String,
+    pub statuses: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Account {
+    pub pl_id: String,
+    pub space_id: String,
+    pub pl_name: Option<String>,
+    pub pl_displayname: Option<String>,
+    pub active: bool,
+    pub metadata: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Tag {
+    pub id: String,
+    pub space_id: String,
+    pub title: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Item {
+    pub id: String,
+    pub space_id: String,
+    pub title: String,
+    pub ty: i64,
+    pub pl_serial: String,
+    pub owner_id: Option<String>,
+    pub current_holder: Option<String>,
+    pub due_at: Option<i64>,
+    pub metadata: String,
+    #[serde(default = "default_item_status")]
+    pub status: String,
+}
+
+fn default_item_status() -> String {
+    archk::v1::space::DEFAULT_ITEM_STATUS.into()
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ItemTag {
+    pub item_id: String,
+    pub tag_id: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Reservation {
+    pub item_id: String,
+    pub space_id: String,
+    pub pl_id: String,
+    pub start_at: i64,
+    pub end_at: i64,
+    pub note: Option<String>,
+    pub created_at: i64,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Service {
+    pub id: String,
+    pub name: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    pub space_id: Option<String>,
+    pub ty: i64,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Log {
+    pub id: String,
+    pub space_id: String,
+    pub created_at: i64,
+    pub act: i64,
+    pub sp_acc_id: Option<String>,
+    pub sp_item_id: Option<String>,
+    pub ref_id: Option<String>,
+    pub detail: Option<String>,
+}
+
+#[derive(Debug)]
+pub enum ArchiveError {
+    Io(io::Error),
+    Database(sqlx::Error),
+    /// Invalid line of archive
+    Format {
+        line: usize,
+        message: String,
+    },
+}
+
+impl fmt::Display for ArchiveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "{e}"),
+            Self::Database(e) => write!(f, "database: {e}"),
+            Self::Format { line, message } => write!(f, "line {line}: {message}"),
+        }
+    }
+}
+
+impl From<io::Error> for ArchiveError {
+    fn from(value: io::Error) -> Self {
+        Self::Io(value)
+    }
+}
+
+impl From<sqlx::Error> for ArchiveError {
+    fn from(value: sqlx::Error) -> Self {
+        Self::Database(value)
+    }
+}
+
+fn write_line(out: &mut impl Write, value: &impl Serialize) -> io::Result<()> {
+    serde_json::to_writer(&mut *out, value)?;
+    out.write_all(b"\n")
+}
+
+/// Write all rows of query as records of `variant`.
+macro_rules! export_rows {
+    ($db:expr, $out:expr, $counter:expr, $variant:ident, $stmt:literal) => {{
+        let mut rows = sqlx::query_as!($variant, $stmt).fetch($db);
+        while let Some(row) = rows.next().await {
+            write_line($out, &Record::$variant(row?))?;
+            $counter += 1;
+        }
+    }};
+}
+
+/// Write archive of whole instance. Returns count of exported records.
+pub async fn export(db: &SqlitePool, out: &mut impl Write) -> Result<u64, ArchiveError> {
+    let mut count = 0;
+    write_line(
+        out,
+        &Header {
+            archk_archive: ARCHIVE_VERSION,
+        },
+    )?;
+
+    export_rows!(
+        db,
+        out,
+        count,
+        User,
+        "SELECT id, name, invites, invited_by, level, password_hash FROM users"
+    );
+    export_rows!(
+        db,
+        out,
+        count,
+        SshKey,
+        "SELECT id, pubkey_ty, pubkey_val, pubkey_fingerprint, owner_id FROM users_ssh_keys"
+    );
+    export_rows!(
+        db,
+        out,
+        count,
+        Space,
+        "SELECT id, title, owner_id, logs_retention_days, archived FROM spaces"
+    );
+    export_rows!(
+        db,
+        out,
+        count,
+        Policy,
+        "SELECT space_id, require_keycard, deny_on_open_reports, ask_manager FROM spaces_policies"
+    );
+    export_rows!(
+        db,
+        out,
+        count,
+        ItemStatuses,
+        "SELECT space_id, statuses FROM spaces_items_statuses"
+    );
+    export_rows!(
+        db,
+        out,
+        count,
+        Account,
+        "SELECT pl_id, space_id, pl_name, pl_displayname, active, metadata FROM spaces_accounts"
+    );
+    export_rows!(
+        db,
+        out,
+        count,
+        Tag,
+        "SELECT id, space_id, title FROM spaces_tags"
+    );
+    export_rows!(
+        db,
+        out,
+        count,
+        Item,
+        r#"
+        SELECT
+            id, space_id, title, ty, pl_serial, owner_id, current_holder, due_at, metadata, status
+        FROM spaces_items"#
+    );
+    export_rows!(
+        db,
+        out,
+        count,
+        ItemTag,
+        "SELECT item_id, tag_id FROM spaces_items_tags"
+    );
+    export_rows!(
+        db,
+        out,
+        count,
+        Reservation,
+        r#"
+        SELECT item_id, space_id, pl_id, start_at, end_at, note, created_at
+        FROM spaces_items_reservations"#
+    );
+    export_rows!(
+        db,
+        out,
+        count,
+        Service,
+        "SELECT id, name, description, space_id, ty FROM service_accounts"
+    );
+    export_rows!(
+        db,
+        out,
+        count,
+        Log,
+        r#"
+        SELECT id, space_id, created_at, act, sp_acc_id, sp_item_id, ref_id, detail
+        FROM spaces_logs
+        ORDER BY created_at"#
+    );
+
+    out.flush()?;
+    Ok(count)
+}
+
+/// New IDs of imported objects by their IDs in archive.
+#[derive(Default)]
+struct IdMap {
+    users: HashMap<String, String>,
+    spaces: HashMap<String, String>,
+    tags: HashMap<String, String>,
+    items: HashMap<String, String>,
+    logs: HashMap<String, String>,
+}
+
+/// Get new ID of object referenced by record on `line`.
+fn remap(
+    map: &HashMap<String, String>,
+    kind: &str,
+    id: &str,
+    line: usize,
+) -> Result<String, ArchiveError> {
+    map.get(id).cloned().ok_or_else(|| ArchiveError::Format {
+        line,
+        message: format!("unknown {kind} `{id}`"),
+    })
+}
+
+/// Import archive into instance in one transaction. All objects get new IDs, so
+/// archive may be imported into non-empty instance (if usernames don't collide).
+/// Returns count of imported records.
+pub async fn import(db: &SqlitePool, input: impl BufRead) -> Result<u64, ArchiveError> {
+    let mut lines = input.lines().enumerate();
+
+    let header = match lines.next() {
+        Some((_, line)) => serde_json::from_str::<Header>(&line?).ok(),
+        None => None,
+    };
+    match header {
+        Some(Header { archk_archive }) if archk_archive == ARCHIVE_VERSION => (),
+        Some(Header { archk_archive }) => {
+            return Err(ArchiveError::Format {
+                line: 1,
+                message: format!(
+                    "unsupported archive version {archk_archive}, expected {ARCHIVE_VERSION}"
+                ),
+            })
+        }
+        None => {
+            return Err(ArchiveError::Format {
+                line: 1,
+                message: "not an archk archive".into(),
+            })
+        }
+    }
+
+    let mut tx = db.begin().await?;
+    let mut map = IdMap::default();
+    // `invited_by` may reference user later in archive, so it is set after all users
+    let mut invited_by = Vec::new();
+    let mut count = 0;
+
+    for (i, text) in lines {
+        let (line, text) = (i + 1, text?);
+        if text.trim().is_empty() {
+            continue;
+        }
+
+        let record: Record = serde_json::from_str(&text).map_err(|e| ArchiveError::Format {
+            line,
+            message: e.to_string(),
+        })?;
+        import_record(&mut tx, &mut map, &mut invited_by, record, line).await?;
+        count += 1;
+    }
+
+    for (user_id, old_invited_by) in invited_by {
+        let invited_by = map.users.get(&old_invited_by);
+        sqlx::query!(
+            "UPDATE users SET invited_by = ? WHERE id = ?",
+            invited_by,
+            user_id
+        )
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    tx.commit().await?;
+    Ok(count)
+}
+
+/// Insert single record from `line` of archive.
+async fn import_record(
+    tx: &mut Transaction<'_, Sqlite>,
+    map: &mut IdMap,
+    invited_by: &mut Vec<(String, String)>,
+    record: Record,
+    line: usize,
+) -> Result<(), ArchiveError> {
+    match record {
+        Record::User(v) => {
+            let id = UserID::new().to_string();
+            sqlx::query!(
+                "INSERT INTO users(id, name, invites, level, password_hash) VALUES (?, ?, ?, ?, ?)",
+                id,
+                v.name,
+                v.invites,
+                v.level,
+                v.password_hash
+            )
+            .execute(&mut **tx)
+            .await?;
+            if let Some(old) = v.invited_by {
+                invited_by.push((id.clone(), old));
+            }
+            map.users.insert(v.id, id);
+        }
+        Record::SshKey(v) => {
+            let id = UserSSHKeyID::new().to_string();
+            let owner_id = remap(&map.users, "user", &v.owner_id, line)?;
+            sqlx::query!(
+                r#"
+                INSERT INTO users_ssh_keys(id, pubkey_ty, pubkey_val, pubkey_fingerprint, owner_id)
+                VALUES (?, ?, ?, ?, ?)"#,
+                id,
+                v.pubkey_ty,
+                v.pubkey_val,
+                v.pubkey_fingerprint,
+                owner_id
+            )
+            .execute(&mut **tx)
+            .await?;
+        }
+        Record::Space(v) => {
+            let id = SpaceID::new().to_string();
+            let owner_id = remap(&map.users, "user", &v.owner_id, line)?;
+            sqlx::query!(
+                "INSERT INTO spaces(id, title, owner_id, logs_retention_days, archived) VALUES (?, ?, ?, ?, ?)",
+                id,
+                v.title,
+                owner_id,
+                v.logs_retention_days,
+                v.archived
+            )
+            .execute(&mut **tx)
+            .await?;
+            map.spaces.insert(v.id, id);
+        }
+        Record::Policy(v) => {
+            let space_id = remap(&map.spaces, "space", &v.space_id, line)?;
+            sqlx::query!(
+                r#"
+                INSERT INTO spaces_policies(
+                    space_id, require_keycard, deny_on_open_reports, ask_manager
+                )
+                VALUES (?, ?, ?, ?)"#,
+                space_id,
+                v.require_keycard,
+                v.deny_on_open_reports,
+                v.ask_manager
+            )
+            .execute(&mut **tx)
+            .await?;
+        }
+        Record::ItemStatuses(v) => {
+            let space_id = remap(&map.spaces, "space", &v.space_id, line)?;
+            sqlx::query!(
+                "INSERT INTO spaces_items_statuses(space_id, statuses) VALUES (?, ?)",
+                space_id,
+                v.statuses
+            )
+            .execute(&mut **tx)
+            .await?;
+        }
+        Record::Account(v) => {
+            let space_id = remap(&map.spaces, "space", &v.space_id, line)?;
+            sqlx::query!(
+                r#"
+                INSERT INTO spaces_accounts(pl_id, space_id, pl_name, pl_displayname, active, metadata)
+                VALUES (?, ?, ?, ?, ?, ?)"#,
+                v.pl_id,
+                space_id,
+                v.pl_name,
+                v.pl_displayname,
+                v.active,
+                v.metadata
+            )
+            .execute(&mut **tx)
+            .await?;
+        }
+        Record::Tag(v) => {
+            let id = SpaceTagID::new().to_string();
+            let space_id = remap(&map.spaces, "space", &v.space_id, line)?;
+            sqlx::query!(
+                "INSERT INTO spaces_tags(id, space_id, title) VALUES (?, ?, ?)",
+                id,
+                space_id,
+                v.title
+            )
+            .execute(&mut **tx)
+            .await?;
+            map.tags.insert(v.id, id);
+        }
+        Record::Item(v) => {
+            let id = SpaceItemID::new().to_string();
+            let space_id = remap(&map.spaces, "space", &v.space_id, line)?;
+            sqlx::query!(
+                r#"
+                INSERT INTO spaces_items(
+                    id, space_id, title, ty, pl_serial, owner_id, current_holder, due_at, metadata,
+                    status
+                )
+                VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)"#,
+                id,
+                space_id,
+                v.title,
+                v.ty,
+                v.pl_serial,
+                v.owner_id,
+                v.current_holder,
+                v.due_at,
+                v.metadata,
+                v.status
+            )
+            .execute(&mut **tx)
+            .await?;
+            map.items.insert(v.id, id);
+        }
+        Record::ItemTag(v) => {
+            let item_id = remap(&map.items, "item", &v.item_id, line)?;
+            let tag_id = remap(&map.tags, "tag", &v.tag_id, line)?;
+            sqlx::query!(
+                "INSERT INTO spaces_items_tags(item_id, tag_id) VALUES (?, ?)",
+                item_id,
+                tag_id
+            )
+            .execute(&mut **tx)
+            .await?;
+        }
+        Record::Reservation(v) => {
+            let id = Uuid::new_v4().to_string();
+            let item_id = remap(&map.items, "item", &v.item_id, line)?;
+            let space_id = remap(&map.spaces, "space", &v.space_id, line)?;
+            sqlx::query!(
+                r#"
+                INSERT INTO spaces_items_reservations(
+                    id, space_id, item_id, pl_id, start_at, end_at, note, created_at
+                )
+                VALUES (?, ?, ?, ?, ?, ?, ?, ?)"#,
+                id,
+                space_id,
+                item_id,
+                v.pl_id,
+                v.start_at,
+                v.end_at,
+                v.note,
+                v.created_at
+            )
+            .execute(&mut **tx)
+            .await?;
+        }
+        Record::Service(v) => {
+            let id = ServiceAccountID::new().to_string();
+            let space_id = match v.space_id {
+                Some(space_id) => Some(remap(&map.spaces, "space", &space_id, line)?),
+                None => None,
+            };
+            sqlx::query!(
+                "INSERT INTO service_accounts(id, name, description, space_id, ty) VALUES (?, ?, ?, ?, ?)",
+                id,
+                v.name,
+                v.description,
+                space_id,
+                v.ty
+            )
+            .execute(&mut **tx)
+            .await?;
+        }
+        Record::Log(v) => {
+            let id = Uuid::new_v4().to_string();
+            let space_id = remap(&map.spaces, "space", &v.space_id, line)?;
+            // items may be deleted and referenced logs removed by retention
+            let sp_item_id = v.sp_item_id.and_then(|v| map.items.get(&v).cloned());
+            let ref_id = v.ref_id.and_then(|v| map.logs.get(&v).cloned());
+            sqlx::query!(
+                r#"
+                INSERT INTO spaces_logs(id, space_id, created_at, act, sp_acc_id, sp_item_id, ref_id, detail)
+                VALUES (?, ?, ?, ?, ?, ?, ?, ?)"#,
+                id,
+                space_id,
+                v.created_at,
+                v.act,
+                v.sp_acc_id,
+                sp_item_id,
+                ref_id,
+                v.detail
+            )
+            .execute(&mut **tx)
+            .await?;
+            map.logs.insert(v.id, id);
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::sqlite::SqlitePoolOptions;
+
+    use super::*;
+
+    async fn memory_db() -> SqlitePool {
+        // single connection, otherwise every connection gets own database
+        let db = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        archk_api::apply_migrations(&db).await.unwrap();
+        db
+    }
+
+    #[tokio::test]
+    async fn roundtrip() {
+        let db = memory_db().await;
+        sqlx::query(
+            r#"
+            INSERT INTO users(id, name, level, password_hash) VALUES ('u1', 'admin', 100, 'x');
+            INSERT INTO users(id, name, invited_by, password_hash) VALUES ('u2', 'user', 'u1', 'x');
+            INSERT INTO spaces(id, title, owner_id) VALUES ('s1', 'lab', 'u2');
+            INSERT INTO spaces_accounts(pl_id, space_id) VALUES ('acc', 's1');
+            INSERT INTO spaces_items(id, space_id, title, pl_serial, owner_id) VALUES ('i1', 's1', 'key', '1', 'acc');
+            INSERT INTO spaces_tags(id, space_id, title) VALUES ('t1', 's1', 'red');
+            INSERT INTO spaces_items_tags(item_id, tag_id) VALUES ('i1', 't1');
+            INSERT INTO spaces_logs(id, space_id, created_at, act, sp_item_id) VALUES ('l1', 's1', 1, 1, 'i1');
+            INSERT INTO spaces_logs(id, space_id, created_at, act, ref_id) VALUES ('l2', 's1', 2, 1, 'l1');
+            "#,
+        )
+        .execute(&db)
+        .await
+        .unwrap();
+
+        let mut archive = Vec::new();
+        assert_eq!(export(&db, &mut archive).await.unwrap(), 9);
+
+        let target = memory_db().await;
+        assert_eq!(import(&target, archive.as_slice()).await.unwrap(), 9);
+
+        let (name, invited_by): (String, String) = sqlx::query_as(
+            "SELECT u.name, i.name FROM users u JOIN users i ON u.invited_by = i.id",
+        )
+        .fetch_one(&target)
+        .await
+        .unwrap();
+        assert_eq!((name.as_str(), invited_by.as_str()), ("user", "admin"));
+
+        let refs: i64 = sqlx::query_scalar(
+            r#"
+            SELECT COUNT(1) FROM spaces_logs l
+            JOIN spaces_logs r ON l.ref_id = r.id
+            JOIN spaces_items i ON r.sp_item_id = i.id
+            JOIN spaces_items_tags t ON t.item_id = i.id
+            WHERE i.id != 'i1'"#,
+        )
+        .fetch_one(&target)
+        .await
+        .unwrap();
+        assert_eq!(refs, 1);
+
+        // same usernames can't be imported twice
+        assert!(import(&target, archive.as_slice()).await.is_err());
+    }
+}
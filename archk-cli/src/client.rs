@@ -0,0 +1,113 @@
+//! Minimal client of API v1.
+
+use std::io::Write;
+
+use reqwest::{Method, RequestBuilder};
+use serde::Deserialize;
+use serde_json::Value;
+
+/// Body of API response, see `archk::v1::api::Response`.
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum ApiResponse {
+    Response(Value),
+    Error(ApiError),
+}
+
+#[derive(Deserialize)]
+struct ApiError {
+    code: u32,
+    #[serde(default)]
+    detail: Option<String>,
+}
+
+pub struct Client {
+    http: reqwest::Client,
+    url: String,
+    token: Option<String>,
+}
+
+impl Client {
+    pub fn new(url: String, token: Option<String>) -> Self {
+        Self {
+            http: reqwest::Client::new(),
+            url: url.trim_end_matches('/').to_string(),
+            token,
+        }
+    }
+
+    fn request(&self, method: Method, path: &str) -> Result<RequestBuilder, String> {
+        let Some(token) = &self.token else {
+            return Err("token is required, pass `--token` or set `$ARCHK_TOKEN`".into());
+        };
+        Ok(self
+            .http
+            .request(method, format!("{}{path}", self.url))
+            .bearer_auth(token))
+    }
+
+    /// Send request and return `response` field of API response.
+    async fn send(&self, request: RequestBuilder) -> Result<Value, String> {
+        let res = request.send().await.map_err(|e| e.to_string())?;
+        Self::parse(res).await
+    }
+
+    async fn parse(res: reqwest::Response) -> Result<Value, String> {
+        let status = res.status();
+        let body = res.bytes().await.map_err(|e| e.to_string())?;
+
+        match serde_json::from_slice(&body) {
+            Ok(ApiResponse::Response(v)) => Ok(v),
+            Ok(ApiResponse::Error(ApiError { code, detail })) => Err(match detail {
+                Some(detail) if !detail.is_empty() => format!("API error {code}: {detail}"),
+                _ => format!("API error {code} ({status})"),
+            }),
+            Err(_) => Err(format!(
+                "unexpected response ({status}): {}",
+                String::from_utf8_lossy(&body)
+            )),
+        }
+    }
+
+    pub async fn get(&self, path: &str) -> Result<Value, String> {
+        self.send(self.request(Method::GET, path)?).await
+    }
+
+    pub async fn put(&self, path: &str, body: Option<Value>) -> Result<Value, String> {
+        let mut request = self.request(Method::PUT, path)?;
+        if let Some(body) = body {
+            request = request.json(&body);
+        }
+        self.send(request).await
+    }
+
+    pub async fn delete(&self, path: &str) -> Result<Value, String> {
+        self.send(self.request(Method::DELETE, path)?).await
+    }
+
+    /// Stream raw response body to `out` (eg. logs export).
+    pub async fn dump(
+        &self,
+        method: Method,
+        path: &str,
+        out: &mut impl Write,
+    ) -> Result<(), String> {
+        let mut res = self
+            .request(method, path)?
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+        if !res.status().is_success() {
+            let status = res.status();
+            return Err(Self::parse(res)
+                .await
+                .err()
+                .unwrap_or_else(|| format!("unexpected response ({status})")));
+        }
+
+        while let Some(chunk) = res.chunk().await.map_err(|e| e.to_string())? {
+            out.write_all(&chunk).map_err(|e| e.to_string())?;
+        }
+        out.flush().map_err(|e| e.to_string())
+    }
+}
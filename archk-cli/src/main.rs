@@ -0,0 +1,485 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    process::ExitCode,
+    str::FromStr,
+};
+
+use archk::v1::{
+    auth::{Token, TokenTy},
+    service::ServiceAccountTy,
+    user::UserID,
+    validate,
+};
+use archk_api::app::AppConfig;
+use clap::{Parser, Subcommand, ValueEnum};
+use reqwest::Method;
+use serde_json::json;
+use sqlx::{sqlite::SqliteConnectOptions, SqlitePool};
+
+mod archive;
+mod client;
+
+use client::Client;
+
+/// Administration tool for `archk` instance
+#[derive(Parser, Debug)]
+#[command(about, long_about = None)]
+struct Args {
+    /// Base URL of API
+    #[arg(
+        long,
+        env = "ARCHK_URL",
+        default_value = "http://127.0.0.1:8000/api/v1"
+    )]
+    url: String,
+    /// Bearer token used for API requests
+    #[arg(long, env = "ARCHK_TOKEN", hide_env_values = true)]
+    token: Option<String>,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Create first admin user directly in database and print their token.
+    /// Fails if instance already has users
+    Bootstrap {
+        /// Path to server config, used to get admin role and database
+        #[arg(long, env = "CONFIG_PATH", default_value = "config.yml")]
+        config: String,
+        /// Database url, overrides one from config
+        #[arg(long)]
+        database: Option<String>,
+        #[arg(long)]
+        username: String,
+        #[arg(long, env = "ARCHK_PASSWORD", hide_env_values = true)]
+        password: String,
+    },
+    /// Manage own invites
+    #[command(subcommand)]
+    Invite(InviteCommand),
+    /// Manage services
+    #[command(subcommand)]
+    Service(ServiceCommand),
+    /// Manage tokens of service
+    #[command(subcommand)]
+    Token(TokenCommand),
+    /// Download database snapshot to file. Requires `backup` permission
+    Backup { out: PathBuf },
+    /// Replace database with snapshot made by `backup`. Stop server before restoring
+    Restore {
+        file: PathBuf,
+        /// Path to server config, used to get database
+        #[arg(long, env = "CONFIG_PATH", default_value = "config.yml")]
+        config: String,
+        /// Database url, overrides one from config
+        #[arg(long)]
+        database: Option<String>,
+        /// Overwrite existing database
+        #[arg(long)]
+        force: bool,
+    },
+    /// Export all instance data to versioned NDJSON archive, directly from database
+    Export {
+        out: PathBuf,
+        /// Path to server config, used to get database
+        #[arg(long, env = "CONFIG_PATH", default_value = "config.yml")]
+        config: String,
+        /// Database url, overrides one from config
+        #[arg(long)]
+        database: Option<String>,
+    },
+    /// Import archive made by `export` directly into database. Objects get new IDs,
+    /// tokens are not imported
+    Import {
+        file: PathBuf,
+        /// Path to server config, used to get database
+        #[arg(long, env = "CONFIG_PATH", default_value = "config.yml")]
+        config: String,
+        /// Database url, overrides one from config
+        #[arg(long)]
+        database: Option<String>,
+    },
+    /// Dump full space log to stdout
+    Logs {
+        space_id: String,
+        #[arg(long, value_enum, default_value_t = LogsFormat::Jsonl)]
+        format: LogsFormat,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum InviteCommand {
+    /// List unused invites
+    List,
+    /// Create invite
+    Create,
+}
+
+#[derive(Subcommand, Debug)]
+enum ServiceCommand {
+    /// List admin services, or all services with `--all`
+    List {
+        #[arg(long)]
+        all: bool,
+    },
+    /// Create service
+    Create {
+        #[arg(long, value_enum)]
+        ty: ServiceTy,
+        /// Space of service. Omit to create admin service
+        #[arg(long)]
+        space_id: Option<String>,
+        #[arg(long)]
+        name: String,
+        #[arg(long)]
+        description: Option<String>,
+    },
+    /// Delete service
+    Delete { service_id: String },
+}
+
+#[derive(Subcommand, Debug)]
+enum TokenCommand {
+    /// List tokens of service
+    List { service_id: String },
+    /// Issue new token
+    Issue {
+        service_id: String,
+        #[arg(long)]
+        label: Option<String>,
+    },
+    /// Revoke single token by `iat` and `rnd` fields of token, or all tokens with `--all`
+    Revoke {
+        service_id: String,
+        #[arg(long, requires = "rnd", conflicts_with = "all")]
+        iat: Option<i64>,
+        #[arg(long, requires = "iat")]
+        rnd: Option<u128>,
+        #[arg(long, required_unless_present = "iat")]
+        all: bool,
+    },
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum ServiceTy {
+    SshAuthority,
+    SpaceEventWatcher,
+    SpaceActor,
+    SpaceManager,
+}
+
+impl From<ServiceTy> for ServiceAccountTy {
+    fn from(value: ServiceTy) -> Self {
+        match value {
+            ServiceTy::SshAuthority => Self::SSHAuthority,
+            ServiceTy::SpaceEventWatcher => Self::SpaceEventWatcher,
+            ServiceTy::SpaceActor => Self::SpaceActor,
+            ServiceTy::SpaceManager => Self::SpaceManager,
+        }
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum LogsFormat {
+    Jsonl,
+    Csv,
+}
+
+fn read_config(config: &str) -> Result<AppConfig, String> {
+    let cfg =
+        fs::read_to_string(config).map_err(|e| format!("Failed to read config `{config}`: {e}"))?;
+    serde_yaml::from_str(&cfg).map_err(|e| format!("Failed to deserialize config `{config}`: {e}"))
+}
+
+/// Connect to database from `--database` or config and apply migrations.
+async fn connect(config: &str, database: Option<String>) -> Result<SqlitePool, String> {
+    let database = match database {
+        Some(v) => v,
+        None => read_config(config)?.server.database,
+    };
+    let db = SqlitePool::connect(&database)
+        .await
+        .map_err(|e| format!("Failed to connect to `{database}`: {e}"))?;
+    archk_api::apply_migrations(&db)
+        .await
+        .map_err(|e| format!("Failed to migrate `{database}`: {e}"))?;
+    Ok(db)
+}
+
+/// Create first user with level of admin role. Returns token of user.
+async fn bootstrap(
+    config: &str,
+    database: Option<String>,
+    username: &str,
+    password: &str,
+) -> Result<String, String> {
+    validate::username(username).map_err(|e| e.to_string())?;
+    validate::password(password).map_err(|e| e.to_string())?;
+
+    let AppConfig { server, auth, .. } = read_config(config)?;
+    let database = database.unwrap_or(server.database);
+    let level = server.roles.get_max().level;
+
+    let db = SqlitePool::connect(&database)
+        .await
+        .map_err(|e| format!("Failed to connect to `{database}`: {e}"))?;
+    archk_api::apply_migrations(&db)
+        .await
+        .map_err(|e| format!("Failed to migrate `{database}`: {e}"))?;
+
+    let password = bcrypt::hash(password, archk_api::app::BCRYPT_COST).expect("bcrypt");
+    let user_id = UserID::new();
+    let user_id_str: &str = &user_id;
+    let token = Token::with_format(TokenTy::Personal, auth.token_format);
+    let iat = token.iat as i64;
+    let hash = archk_api::tokens::hash(&token);
+
+    let mut tx = db.begin().await.map_err(|e| e.to_string())?;
+
+    let users = sqlx::query!("SELECT COUNT(1) AS cnt FROM users")
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(|e| e.to_string())?
+        .cnt;
+    if users != 0 {
+        return Err("Instance already has users, use invites to register new ones".into());
+    }
+
+    sqlx::query!(
+        "INSERT INTO users(id, name, level, password_hash) VALUES (?, ?, ?, ?)",
+        user_id_str,
+        username,
+        level,
+        password
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| e.to_string())?;
+    sqlx::query!(
+        "INSERT INTO tokens(iat, hash, user_id) VALUES (?, ?, ?)",
+        iat,
+        hash,
+        user_id_str
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| e.to_string())?;
+
+    tx.commit().await.map_err(|e| e.to_string())?;
+
+    Ok(token.to_string())
+}
+
+/// Check snapshot and replace database file with it, then apply migrations
+/// in case snapshot was made by older version.
+async fn restore(file: &Path, database: &str, force: bool) -> Result<(), String> {
+    let snapshot =
+        SqlitePool::connect_with(SqliteConnectOptions::new().filename(file).read_only(true))
+            .await
+            .map_err(|e| format!("Failed to open snapshot `{}`: {e}", file.display()))?;
+    let check: String = sqlx::query_scalar("PRAGMA integrity_check")
+        .fetch_one(&snapshot)
+        .await
+        .map_err(|e| format!("Failed to check snapshot `{}`: {e}", file.display()))?;
+    let migrated: bool = sqlx::query_scalar(
+        "SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = '_sqlx_migrations')",
+    )
+    .fetch_one(&snapshot)
+    .await
+    .map_err(|e| format!("Failed to check snapshot `{}`: {e}", file.display()))?;
+    snapshot.close().await;
+    if check != "ok" {
+        return Err(format!(
+            "Snapshot `{}` is corrupted: {check}",
+            file.display()
+        ));
+    }
+    if !migrated {
+        return Err(format!(
+            "`{}` is not a snapshot of archk database",
+            file.display()
+        ));
+    }
+
+    let target = SqliteConnectOptions::from_str(database)
+        .map_err(|e| format!("Invalid database url `{database}`: {e}"))?
+        .get_filename()
+        .to_path_buf();
+    if target.exists() && !force {
+        return Err(format!(
+            "Database `{}` already exists, pass `--force` to overwrite it",
+            target.display()
+        ));
+    }
+
+    // copy near target first, so database is replaced atomically
+    let tmp = target.with_extension("restore");
+    fs::copy(file, &tmp).map_err(|e| format!("Failed to copy snapshot: {e}"))?;
+    fs::rename(&tmp, &target).map_err(|e| format!("Failed to replace database: {e}"))?;
+    for suffix in ["-wal", "-shm"] {
+        let mut path = target.clone().into_os_string();
+        path.push(suffix);
+        match fs::remove_file(path) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => {
+                return Err(format!("Failed to remove `{suffix}` file of database: {e}"))
+            }
+            _ => (),
+        }
+    }
+
+    let db = SqlitePool::connect(database)
+        .await
+        .map_err(|e| format!("Failed to connect to `{database}`: {e}"))?;
+    archk_api::apply_migrations(&db)
+        .await
+        .map_err(|e| format!("Failed to migrate `{database}`: {e}"))
+}
+
+async fn run(args: Args) -> Result<(), String> {
+    let client = Client::new(args.url, args.token);
+
+    let res = match args.command {
+        Command::Bootstrap {
+            config,
+            database,
+            username,
+            password,
+        } => {
+            let token = bootstrap(&config, database, &username, &password).await?;
+            println!("{token}");
+            return Ok(());
+        }
+        Command::Backup { out } => {
+            let mut file = fs::File::create(&out)
+                .map_err(|e| format!("Failed to create `{}`: {e}", out.display()))?;
+            let res = client.dump(Method::POST, "/admin/backup", &mut file).await;
+            if res.is_err() {
+                let _ = fs::remove_file(&out);
+            }
+            return res;
+        }
+        Command::Restore {
+            file,
+            config,
+            database,
+            force,
+        } => {
+            let database = match database {
+                Some(v) => v,
+                None => read_config(&config)?.server.database,
+            };
+            return restore(&file, &database, force).await;
+        }
+        Command::Export {
+            out,
+            config,
+            database,
+        } => {
+            let db = connect(&config, database).await?;
+            let file = fs::File::create(&out)
+                .map_err(|e| format!("Failed to create `{}`: {e}", out.display()))?;
+            let count = archive::export(&db, &mut io::BufWriter::new(file))
+                .await
+                .map_err(|e| format!("Failed to export: {e}"))?;
+            eprintln!("Exported {count} records");
+            return Ok(());
+        }
+        Command::Import {
+            file,
+            config,
+            database,
+        } => {
+            let db = connect(&config, database).await?;
+            let input = fs::File::open(&file)
+                .map_err(|e| format!("Failed to open `{}`: {e}", file.display()))?;
+            let count = archive::import(&db, io::BufReader::new(input))
+                .await
+                .map_err(|e| format!("Failed to import, nothing is imported: {e}"))?;
+            eprintln!("Imported {count} records");
+            return Ok(());
+        }
+        Command::Logs { space_id, format } => {
+            let format = match format {
+                LogsFormat::Jsonl => "jsonl",
+                LogsFormat::Csv => "csv",
+            };
+            return client
+                .dump(
+                    Method::GET,
+                    &format!("/space/{space_id}/logs/export?format={format}"),
+                    &mut io::stdout().lock(),
+                )
+                .await;
+        }
+
+        Command::Invite(InviteCommand::List) => client.get("/user/invites").await?,
+        Command::Invite(InviteCommand::Create) => client.put("/user/invites", None).await?,
+
+        Command::Service(ServiceCommand::List { all }) => {
+            client.get(&format!("/service?all={all}")).await?
+        }
+        Command::Service(ServiceCommand::Create {
+            ty,
+            space_id,
+            name,
+            description,
+        }) => {
+            let ty: i64 = ServiceAccountTy::from(ty).into();
+            let body = json!({
+                "ty": ty,
+                "space_id": space_id,
+                "name": name,
+                "description": description,
+            });
+            client.put("/service", Some(body)).await?
+        }
+        Command::Service(ServiceCommand::Delete { service_id }) => {
+            client.delete(&format!("/service/{service_id}")).await?
+        }
+
+        Command::Token(TokenCommand::List { service_id }) => {
+            client.get(&format!("/service/{service_id}/tokens")).await?
+        }
+        Command::Token(TokenCommand::Issue { service_id, label }) => {
+            let body = json!({ "label": label });
+            client
+                .put(&format!("/service/{service_id}/tokens"), Some(body))
+                .await?
+        }
+        Command::Token(TokenCommand::Revoke {
+            service_id,
+            iat: Some(iat),
+            rnd: Some(rnd),
+            ..
+        }) => {
+            client
+                .delete(&format!("/service/{service_id}/tokens/{iat}/{rnd}"))
+                .await?
+        }
+        Command::Token(TokenCommand::Revoke { service_id, .. }) => {
+            client
+                .delete(&format!("/service/{service_id}/tokens"))
+                .await?
+        }
+    };
+
+    println!(
+        "{}",
+        serde_json::to_string_pretty(&res).expect("json value")
+    );
+    Ok(())
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    match run(Args::parse()).await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,12 @@
+fn main() {
+    tonic_build::configure()
+        .build_client(true)
+        .type_attribute(".archk.v1.LogEntry", "#[derive(serde::Deserialize)]")
+        .field_attribute(
+            ".archk.v1.LogEntry.act",
+            "#[serde(deserialize_with = \"crate::log_action_code\")]",
+        )
+        .type_attribute(".archk.v1.UnlockResult", "#[derive(serde::Deserialize)]")
+        .compile_protos(&["proto/archk.proto"], &["proto/"])
+        .expect("protobuf compilation");
+}
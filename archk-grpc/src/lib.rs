@@ -0,0 +1,269 @@
+//! gRPC interface for space services (see `proto/archk.proto`).
+//!
+//! Calls are served by the same router as HTTP API v1, so services share
+//! [`AppState`], tokens and permissions with it:
+//!
+//! ```ignore
+//! let grpc = archk_grpc::SpaceService::new(state.clone(), body_limit).into_server();
+//! tonic::transport::Server::builder()
+//!     .add_service(grpc)
+//!     .serve(addr)
+//!     .await?;
+//! ```
+
+use std::time::Duration;
+
+use archk::v1::{api, space::LogAction};
+use archk_api::app::AppState;
+use axum::{
+    body::Body,
+    http::{header::AUTHORIZATION, header::CONTENT_TYPE, Method, Request},
+    Router,
+};
+use http_body_util::BodyExt;
+use serde::{de::DeserializeOwned, Deserialize, Deserializer};
+use serde_json::json;
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::{metadata::MetadataMap, Status};
+use tower::ServiceExt;
+
+pub mod pb {
+    tonic::include_proto!("archk.v1");
+}
+
+use pb::{actor_event::Event, actor_event_result::Result as EventResult, space_server};
+
+/// Maximum amount of log entries returned by `GET /service/_/space/logs`
+const LOGS_PAGE: usize = 50;
+
+#[derive(Clone)]
+pub struct SpaceService {
+    router: Router,
+    poll_interval: Duration,
+}
+
+impl SpaceService {
+    /// Creates service over API v1 routes, see [`archk_api::v1::get_routes`].
+    pub fn new(state: AppState, body_limit: usize) -> Self {
+        Self {
+            router: archk_api::v1::get_routes(state.clone(), None, body_limit, None)
+                .with_state(state),
+            poll_interval: Duration::from_secs(1),
+        }
+    }
+
+    /// Interval of checking new log entries in `StreamLogs`, one second by default.
+    pub fn with_poll_interval(mut self, poll_interval: Duration) -> Self {
+        self.poll_interval = poll_interval;
+        self
+    }
+
+    pub fn into_server(self) -> space_server::SpaceServer<Self> {
+        space_server::SpaceServer::new(self)
+    }
+
+    /// Calls API v1 endpoint with `authorization` from `metadata`.
+    async fn call<T: DeserializeOwned>(
+        &self,
+        metadata: &MetadataMap,
+        method: Method,
+        uri: &str,
+        body: Option<serde_json::Value>,
+    ) -> Result<T, Status> {
+        let mut request = Request::builder().method(method).uri(uri);
+        if let Some(Ok(v)) = metadata.get("authorization").map(|v| v.to_str()) {
+            request = request.header(AUTHORIZATION, v);
+        }
+        let request = match body {
+            Some(body) => request
+                .header(CONTENT_TYPE, "application/json")
+                .body(Body::from(body.to_string())),
+            None => request.body(Body::empty()),
+        }
+        .map_err(|e| Status::invalid_argument(e.to_string()))?;
+
+        let response = self
+            .router
+            .clone()
+            .oneshot(request)
+            .await
+            .unwrap_or_else(|e| match e {});
+        let body = response
+            .into_body()
+            .collect()
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?
+            .to_bytes();
+
+        match serde_json::from_slice(&body) {
+            Ok(api::Response::Success(v)) => Ok(v),
+            Ok(api::Response::Failture(e)) => Err(status(e)),
+            Err(e) => Err(Status::internal(format!("invalid API response: {e}"))),
+        }
+    }
+}
+
+/// Converts API error to gRPC status. Original error code is kept in
+/// `archk-error-code` metadata.
+fn status(e: api::ErrorData) -> Status {
+    use api::Error;
+    use tonic::Code;
+
+    let code = match e.code {
+        Error::ObjectNotFound | Error::NoEndpoint => Code::NotFound,
+        Error::MalformedData | Error::ProcessingError | Error::PayloadTooLarge => {
+            Code::InvalidArgument
+        }
+        Error::Conflict | Error::Gone => Code::FailedPrecondition,
+        Error::Forbidden => Code::PermissionDenied,
+        Error::Internal => Code::Internal,
+        Error::Unauthorized => Code::Unauthenticated,
+        Error::RateLimited | Error::QuotaExceeded => Code::ResourceExhausted,
+        Error::ServiceUnavailable => Code::Unavailable,
+        #[allow(unreachable_patterns)]
+        _ => Code::Unknown,
+    };
+    let message = match e.detail {
+        Some(v) => v.into_owned(),
+        None => e.code.description().into(),
+    };
+
+    let mut status = Status::new(code, message);
+    let error_code: u16 = e.code.into();
+    status
+        .metadata_mut()
+        .insert("archk-error-code", error_code.into());
+    status
+}
+
+/// JSON body of `POST /service/_/space/events`.
+fn event_body(event: Event) -> serde_json::Value {
+    match event {
+        Event::Unlock(v) => json!({ "unlock": { "pl_id": v.pl_id } }),
+        Event::Report(v) => json!({
+            "report": { "pl_id": v.pl_id, "item_id": v.item_id, "detail": v.detail }
+        }),
+        Event::Take(v) => json!({
+            "take": { "pl_id": v.pl_id, "item_id": v.item_id, "due_at": v.due_at }
+        }),
+        Event::Return(v) => json!({ "return": { "item_id": v.item_id } }),
+    }
+}
+
+/// Uri of `GET /service/_/space/logs` with page cursor.
+fn logs_uri(after: i64, after_id: &Option<String>) -> String {
+    match after_id {
+        Some(id) => format!("/service/_/space/logs?after={after}&after_id={id}"),
+        None => format!("/service/_/space/logs?after={after}"),
+    }
+}
+
+/// Code of `act` of API log entry, which is serialized as [`LogAction`].
+fn log_action_code<'de, D: Deserializer<'de>>(deserializer: D) -> Result<i64, D::Error> {
+    LogAction::deserialize(deserializer).map(|v| v.code())
+}
+
+/// Response of `POST /service/_/space/events`.
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum EventResponse {
+    Unlock(pb::UnlockResult),
+    Report(pb::LogEntry),
+    Take(pb::LogEntry),
+    Return(pb::LogEntry),
+}
+
+impl From<EventResponse> for EventResult {
+    fn from(v: EventResponse) -> Self {
+        match v {
+            EventResponse::Unlock(v) => Self::Unlock(v),
+            EventResponse::Report(v) => Self::Report(v),
+            EventResponse::Take(v) => Self::Take(v),
+            EventResponse::Return(v) => Self::Return(v),
+        }
+    }
+}
+
+#[tonic::async_trait]
+impl space_server::Space for SpaceService {
+    async fn submit_event(
+        &self,
+        request: tonic::Request<pb::ActorEvent>,
+    ) -> Result<tonic::Response<pb::ActorEventResult>, Status> {
+        let (metadata, _, event) = request.into_parts();
+        let Some(event) = event.event else {
+            return Err(Status::invalid_argument("expected event"));
+        };
+
+        let res: EventResponse = self
+            .call(
+                &metadata,
+                Method::POST,
+                "/service/_/space/events",
+                Some(event_body(event)),
+            )
+            .await?;
+
+        Ok(tonic::Response::new(pb::ActorEventResult {
+            result: Some(res.into()),
+        }))
+    }
+
+    type StreamLogsStream = ReceiverStream<Result<pb::LogEntry, Status>>;
+
+    async fn stream_logs(
+        &self,
+        request: tonic::Request<pb::StreamLogsRequest>,
+    ) -> Result<tonic::Response<Self::StreamLogsStream>, Status> {
+        let (
+            metadata,
+            _,
+            pb::StreamLogsRequest {
+                mut after,
+                mut after_id,
+            },
+        ) = request.into_parts();
+
+        // first page is fetched before responding, so invalid token or
+        // service type fails whole call instead of stream
+        let mut entries: Vec<pb::LogEntry> = self
+            .call(&metadata, Method::GET, &logs_uri(after, &after_id), None)
+            .await?;
+
+        let (tx, rx) = mpsc::channel(LOGS_PAGE);
+        let this = self.clone();
+        tokio::spawn(async move {
+            loop {
+                let full_page = entries.len() >= LOGS_PAGE;
+                for entry in entries {
+                    // entries are ordered by `(created_at, id)`, which is cursor of next page
+                    after = entry.created_at;
+                    after_id = Some(entry.id.clone());
+                    if tx.send(Ok(entry)).await.is_err() {
+                        return;
+                    }
+                }
+                if !full_page {
+                    tokio::time::sleep(this.poll_interval).await;
+                }
+                if tx.is_closed() {
+                    return;
+                }
+
+                entries = match this
+                    .call(&metadata, Method::GET, &logs_uri(after, &after_id), None)
+                    .await
+                {
+                    Ok(v) => v,
+                    Err(e) => {
+                        _ = tx.send(Err(e)).await;
+                        return;
+                    }
+                };
+            }
+        });
+
+        Ok(tonic::Response::new(ReceiverStream::new(rx)))
+    }
+}
@@ -0,0 +1,233 @@
+//! gRPC calls round-trip through API v1: actor events and streamed logs.
+
+use std::{sync::Arc, time::Duration};
+
+use arc_swap::ArcSwap;
+use archk::v1::{
+    auth::{Token, TokenTy},
+    service::ServiceAccountTy,
+    user::UserID,
+};
+use archk_api::{app::AppState, roles::UserRoles};
+use archk_grpc::{
+    pb::{
+        self, actor_event::Event, actor_event_result::Result as EventResult, space_server::Space,
+    },
+    SpaceService,
+};
+use axum::{
+    body::Body,
+    http::{header::AUTHORIZATION, header::CONTENT_TYPE, Method, Request},
+    Router,
+};
+use http_body_util::BodyExt;
+use serde_json::{json, Value};
+use sqlx::sqlite::SqlitePoolOptions;
+use tokio_stream::StreamExt;
+use tower::ServiceExt;
+
+const ROLES: &str = "[{ name: user, level: 10, permissions: [space.create, service.create] }]";
+
+/// Empty migrated in-memory database and state over it.
+async fn state() -> AppState {
+    // one connection, otherwise every connection gets its own database
+    let db = SqlitePoolOptions::new()
+        .max_connections(1)
+        .connect("sqlite::memory:")
+        .await
+        .expect("database");
+    archk_api::apply_migrations(&db).await.expect("migrations");
+    let roles: UserRoles = serde_yaml::from_str(ROLES).expect("roles");
+
+    AppState {
+        db,
+        db_read: None,
+        roles: Arc::new(ArcSwap::from_pointee(roles)),
+        oidc: None,
+        lockout: Default::default(),
+        token_format: Default::default(),
+        proxy_auth: None,
+        invite_waves: None,
+        services: Default::default(),
+        cache: Default::default(),
+        client_cert_header: None,
+        trusted_proxies: Arc::new([]),
+        attachments: None,
+        notifier: None,
+    }
+}
+
+/// Calls API v1 endpoint, expects success and returns `response`.
+async fn ok(router: &Router, method: Method, uri: &str, token: &str, body: Option<Value>) -> Value {
+    let request = Request::builder()
+        .method(method)
+        .uri(uri)
+        .header(AUTHORIZATION, format!("Bearer {token}"));
+    let request = match body {
+        Some(body) => request
+            .header(CONTENT_TYPE, "application/json")
+            .body(Body::from(body.to_string())),
+        None => request.body(Body::empty()),
+    }
+    .unwrap();
+
+    let response = router.clone().oneshot(request).await.unwrap();
+    let status = response.status();
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    let body: Value = serde_json::from_slice(&body).unwrap();
+    assert!(status.is_success(), "{uri}: {status} {body}");
+    body["response"].clone()
+}
+
+/// Space with registered account `tg:42`, returns its ID, actor and watcher tokens.
+async fn space(state: &AppState) -> (String, String, String) {
+    let user_id = UserID::new().to_string();
+    sqlx::query("INSERT INTO users(id, name, level, password_hash) VALUES (?, 'greg', 10, '')")
+        .bind(&user_id)
+        .execute(&state.db)
+        .await
+        .unwrap();
+    let token = Token::new(TokenTy::Personal);
+    sqlx::query("INSERT INTO tokens(iat, hash, user_id) VALUES (?, ?, ?)")
+        .bind(token.iat as i64)
+        .bind(archk_api::tokens::hash(&token))
+        .bind(&user_id)
+        .execute(&state.db)
+        .await
+        .unwrap();
+    let token = token.to_string();
+
+    let router =
+        archk_api::v1::get_routes(state.clone(), None, 1024 * 1024, None).with_state(state.clone());
+    let space = ok(
+        &router,
+        Method::PUT,
+        "/space",
+        &token,
+        Some(json!({ "title": "Lab" })),
+    )
+    .await["id"]
+        .as_str()
+        .unwrap()
+        .to_string();
+    ok(
+        &router,
+        Method::PUT,
+        &format!("/space/{space}/account"),
+        &token,
+        Some(json!({ "pl_id": "tg:42", "pl_name": null, "pl_displayname": null })),
+    )
+    .await;
+
+    let mut tokens = Vec::new();
+    for ty in [
+        ServiceAccountTy::SpaceActor,
+        ServiceAccountTy::SpaceEventWatcher,
+    ] {
+        let service = ok(
+            &router,
+            Method::PUT,
+            "/service",
+            &token,
+            Some(json!({ "ty": ty as i64, "space_id": space, "name": "Door" })),
+        )
+        .await;
+        let id = service["id"].as_str().unwrap();
+        let res = ok(
+            &router,
+            Method::PUT,
+            &format!("/service/{id}/tokens"),
+            &token,
+            None,
+        )
+        .await;
+        tokens.push(res["token"].as_str().unwrap().to_string());
+    }
+    let watcher = tokens.pop().unwrap();
+    let actor = tokens.pop().unwrap();
+
+    (space, actor, watcher)
+}
+
+fn request<T>(message: T, token: &str) -> tonic::Request<T> {
+    let mut request = tonic::Request::new(message);
+    request
+        .metadata_mut()
+        .insert("authorization", format!("Bearer {token}").parse().unwrap());
+    request
+}
+
+#[tokio::test]
+async fn submit_event_and_stream_logs() {
+    let state = state().await;
+    let (space, actor, watcher) = space(&state).await;
+    let service =
+        SpaceService::new(state.clone(), 1024 * 1024).with_poll_interval(Duration::from_millis(10));
+
+    // more than a page of entries created at the same millisecond
+    for i in 0..60 {
+        sqlx::query(
+            "INSERT INTO spaces_logs(id, space_id, created_at, act, detail) VALUES (?, ?, 1000, 600, ?)",
+        )
+        .bind(format!("log{i:02}"))
+        .bind(&space)
+        .bind(format!("report {i}"))
+        .execute(&state.db)
+        .await
+        .unwrap();
+    }
+
+    let event = pb::ActorEvent {
+        event: Some(Event::Report(pb::ReportEvent {
+            pl_id: Some("tg:42".into()),
+            item_id: None,
+            detail: "door is broken".into(),
+        })),
+    };
+    let res = service
+        .submit_event(request(event, &actor))
+        .await
+        .unwrap()
+        .into_inner();
+    let Some(EventResult::Report(report)) = res.result else {
+        panic!("expected report result, got {res:?}");
+    };
+    assert_eq!(report.detail.as_deref(), Some("door is broken"));
+    assert_eq!(report.sp_acc_id.as_deref(), Some("tg:42"));
+
+    let mut stream = service
+        .stream_logs(request(
+            pb::StreamLogsRequest {
+                after: 0,
+                after_id: None,
+            },
+            &watcher,
+        ))
+        .await
+        .unwrap()
+        .into_inner();
+    let mut ids = Vec::new();
+    while ids.len() < 61 {
+        let entry = tokio::time::timeout(Duration::from_secs(5), stream.next())
+            .await
+            .expect("stream stalled")
+            .expect("stream ended")
+            .unwrap();
+        ids.push(entry.id);
+    }
+    let mut expected: Vec<_> = (0..60).map(|i| format!("log{i:02}")).collect();
+    expected.push(report.id);
+    assert_eq!(ids, expected);
+
+    // watcher token can not submit events
+    let event = pb::ActorEvent {
+        event: Some(Event::Unlock(pb::UnlockEvent {
+            pl_id: "tg:42".into(),
+        })),
+    };
+    let status = service
+        .submit_event(request(event, &watcher))
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), tonic::Code::PermissionDenied);
+}
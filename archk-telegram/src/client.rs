@@ -0,0 +1,215 @@
+//! Client of API v1 used by bot. Requests are made on behalf of linked users.
+
+use std::fmt;
+
+use archk::v1::{
+    api::{self, Response},
+    user::User,
+};
+use reqwest::Method;
+use serde::{de::DeserializeOwned, Deserialize};
+use serde_json::{json, Value};
+
+#[derive(Debug)]
+pub enum ClientError {
+    /// Error returned by API
+    Api(api::ErrorData),
+    /// Request failed or response is not API response
+    Http(String),
+}
+
+impl ClientError {
+    /// Is token revoked or expired?
+    pub fn is_unauthorized(&self) -> bool {
+        matches!(self, Self::Api(e) if e.code == api::Error::Unauthorized)
+    }
+}
+
+impl fmt::Display for ClientError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Api(api::ErrorData {
+                code,
+                detail: Some(detail),
+            }) => write!(f, "{}: {detail}", code.description()),
+            Self::Api(e) => f.write_str(e.code.description()),
+            Self::Http(e) => f.write_str(e),
+        }
+    }
+}
+
+/// Space of `GET /user/spaces`.
+#[derive(Deserialize, Clone, Debug)]
+pub struct Space {
+    pub id: String,
+    pub title: String,
+    pub archived: bool,
+}
+
+/// Log entry of `GET /space/:space_id/unlock-requests`.
+#[derive(Deserialize, Clone, Debug)]
+pub struct UnlockRequest {
+    pub id: String,
+    pub sp_acc_id: Option<String>,
+}
+
+pub struct Client {
+    http: reqwest::Client,
+    url: String,
+}
+
+impl Client {
+    pub fn new(url: String) -> Self {
+        Self {
+            http: reqwest::Client::new(),
+            url: url.trim_end_matches('/').to_string(),
+        }
+    }
+
+    async fn request<T: DeserializeOwned>(
+        &self,
+        method: Method,
+        path: &str,
+        token: Option<&str>,
+        body: Option<Value>,
+    ) -> Result<T, ClientError> {
+        let mut request = self.http.request(method, format!("{}{path}", self.url));
+        if let Some(token) = token {
+            request = request.bearer_auth(token);
+        }
+        if let Some(body) = body {
+            request = request.json(&body);
+        }
+
+        let res = request
+            .send()
+            .await
+            .map_err(|e| ClientError::Http(e.to_string()))?;
+        let status = res.status();
+        let body = res
+            .bytes()
+            .await
+            .map_err(|e| ClientError::Http(e.to_string()))?;
+        match serde_json::from_slice(&body) {
+            Ok(Response::Success(v)) => Ok(v),
+            Ok(Response::Failture(e)) => Err(ClientError::Api(e)),
+            Err(_) => Err(ClientError::Http(format!(
+                "unexpected response ({status}): {}",
+                String::from_utf8_lossy(&body)
+            ))),
+        }
+    }
+
+    /// Exchange code of `POST /user/telegram` for token, see `POST /auth/telegram`.
+    pub async fn telegram_authorize(
+        &self,
+        code: &str,
+        chat_id: i64,
+    ) -> Result<String, ClientError> {
+        #[derive(Deserialize)]
+        struct AuthorizationResponse {
+            token: String,
+        }
+
+        let res: AuthorizationResponse = self
+            .request(
+                Method::POST,
+                "/auth/telegram",
+                None,
+                Some(json!({ "code": code, "chat_id": chat_id.to_string() })),
+            )
+            .await?;
+        Ok(res.token)
+    }
+
+    pub async fn get_self(&self, token: &str) -> Result<User, ClientError> {
+        #[derive(Deserialize)]
+        struct SelfResponse {
+            user: User,
+        }
+
+        let res: SelfResponse = self
+            .request(Method::GET, "/user", Some(token), None)
+            .await?;
+        Ok(res.user)
+    }
+
+    /// Spaces owned by user, without archived ones.
+    pub async fn get_spaces(&self, token: &str) -> Result<Vec<Space>, ClientError> {
+        let mut res = Vec::new();
+        for page in 0.. {
+            let spaces: Vec<Space> = self
+                .request(
+                    Method::GET,
+                    &format!("/user/spaces?page={page}"),
+                    Some(token),
+                    None,
+                )
+                .await?;
+            let last = spaces.len() < 50;
+            res.extend(spaces);
+            if last {
+                break;
+            }
+        }
+        Ok(res)
+    }
+
+    /// First page of undecided unlock requests of space.
+    pub async fn get_unlock_requests(
+        &self,
+        token: &str,
+        space_id: &str,
+    ) -> Result<Vec<UnlockRequest>, ClientError> {
+        self.request(
+            Method::GET,
+            &format!("/space/{space_id}/unlock-requests"),
+            Some(token),
+            None,
+        )
+        .await
+    }
+
+    pub async fn decide_unlock_request(
+        &self,
+        token: &str,
+        space_id: &str,
+        log_id: &str,
+        approve: bool,
+    ) -> Result<(), ClientError> {
+        let _: Value = self
+            .request(
+                Method::POST,
+                &format!("/space/{space_id}/unlock-requests/{log_id}"),
+                Some(token),
+                Some(json!({ "approve": approve, "reason": "decided in Telegram" })),
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// Revoke token of bot and stop notifications to chat.
+    pub async fn unlink(&self, token: &str, iat: u64) -> Result<(), ClientError> {
+        // fails if notifications are not configured on server, which is fine
+        if let Err(err) = self
+            .request::<Value>(
+                Method::PATCH,
+                "/user/notifications",
+                Some(token),
+                Some(json!({ "telegram_chat_id": null })),
+            )
+            .await
+        {
+            tracing::debug!(%err, "Failed to reset chat of notifications");
+        }
+        let _: Value = self
+            .request(
+                Method::DELETE,
+                &format!("/user/sessions/{iat}"),
+                Some(token),
+                None,
+            )
+            .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,461 @@
+//! Telegram bot of `archk` instance.
+//!
+//! User links chat by code of `POST /api/v1/user/telegram`: sends `/start <code>` to
+//! bot or opens `https://t.me/<bot username>?start=<code>`. Then bot:
+//! - sends undecided unlock requests of spaces owned by user with buttons to approve
+//!   or deny them;
+//! - gets notifications of server (filed reports, denied unlocks, overdue items)
+//!   delivered to chat, if `server.notifications.telegram` uses token of this bot.
+//!
+//! Tokens of linked users are kept in state file, so it must be readable only by bot.
+
+use std::{
+    collections::{HashMap, HashSet},
+    fs::{OpenOptions, Permissions},
+    io::{self, Write},
+    os::unix::fs::{OpenOptionsExt, PermissionsExt},
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+
+use archk::v1::auth::Token;
+use clap::Parser;
+use serde::{Deserialize, Serialize};
+use tokio::sync::Mutex;
+
+mod client;
+mod telegram;
+
+use client::Client;
+use telegram::{Bot, Update};
+
+const HELP: &str = "Commands:\n\
+    /start <code> - link chat to archk user, get code by `POST /api/v1/user/telegram`\n\
+    /spaces - list owned spaces\n\
+    /unlink - unlink chat and revoke token of bot";
+
+/// Telegram bot linking chats to `archk` users and deciding unlock requests
+#[derive(Parser, Debug)]
+#[command(about, long_about = None)]
+struct Args {
+    /// Base URL of API
+    #[arg(
+        long,
+        env = "ARCHK_URL",
+        default_value = "http://127.0.0.1:8000/api/v1"
+    )]
+    url: String,
+    /// Bot token given by @BotFather
+    #[arg(long, env = "TELEGRAM_BOT_TOKEN", hide_env_values = true)]
+    bot_token: String,
+    /// Bot API server
+    #[arg(
+        long,
+        env = "TELEGRAM_API_URL",
+        default_value = "https://api.telegram.org"
+    )]
+    api_url: String,
+    /// File with linked chats, created if not exists
+    #[arg(
+        long,
+        env = "ARCHK_TELEGRAM_STATE",
+        default_value = "archk-telegram.json"
+    )]
+    state: PathBuf,
+    /// How often unlock requests are checked, in seconds
+    #[arg(long, default_value_t = 10)]
+    poll_interval: u64,
+}
+
+/// Persistent state of bot.
+#[derive(Serialize, Deserialize, Default)]
+struct State {
+    /// Linked chats by their IDs
+    chats: HashMap<i64, LinkedChat>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct LinkedChat {
+    /// Token of user issued by `POST /auth/telegram`
+    token: String,
+    username: String,
+    /// Unlock requests sent to chat: space ID by log entry ID
+    #[serde(default)]
+    requests: HashMap<String, String>,
+}
+
+impl State {
+    fn load(path: &Path) -> io::Result<Self> {
+        match std::fs::read(path) {
+            Ok(v) => serde_json::from_slice(&v).map_err(io::Error::other),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Write state to temporary file and rename it, so state is never partially written.
+    /// File is readable only by owner, since it keeps tokens of users.
+    fn save(&self, path: &Path) {
+        let tmp = path.with_extension("tmp");
+        let write = || {
+            let mut file = OpenOptions::new()
+                .write(true)
+                .create(true)
+                .truncate(true)
+                .mode(0o600)
+                .open(&tmp)?;
+            // mode is applied only to created file
+            file.set_permissions(Permissions::from_mode(0o600))?;
+            file.write_all(&serde_json::to_vec(self).expect("json"))?;
+            file.sync_all()?;
+            std::fs::rename(&tmp, path)
+        };
+        let res = write();
+        if let Err(err) = res {
+            tracing::error!(%err, path = %path.display(), "Failed to save state");
+        }
+    }
+}
+
+struct App {
+    bot: Bot,
+    client: Client,
+    state: Mutex<State>,
+    state_path: PathBuf,
+}
+
+/// Command of message text: name without bot username and argument.
+fn parse_command(text: &str) -> Option<(&str, &str)> {
+    let text = text.trim().strip_prefix('/')?;
+    let (command, arg) = text.split_once(char::is_whitespace).unwrap_or((text, ""));
+    let command = command.split('@').next().unwrap_or_default();
+    Some((command, arg.trim()))
+}
+
+#[tokio::main]
+async fn main() {
+    tracing_subscriber::fmt::init();
+    let args = Args::parse();
+
+    let state = match State::load(&args.state) {
+        Ok(v) => v,
+        Err(err) => {
+            eprintln!("Failed to load state `{}`: {err}", args.state.display());
+            panic!("failed to load state: {err}");
+        }
+    };
+    let app = Arc::new(App {
+        bot: Bot::new(&args.api_url, &args.bot_token),
+        client: Client::new(args.url),
+        state: Mutex::new(state),
+        state_path: args.state,
+    });
+
+    let poller = app.clone();
+    let interval = Duration::from_secs(args.poll_interval.max(1));
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(interval);
+        loop {
+            interval.tick().await;
+            poller.send_unlock_requests().await;
+        }
+    });
+
+    let mut offset = 0;
+    loop {
+        let updates = match app.bot.get_updates(offset).await {
+            Ok(v) => v,
+            Err(err) => {
+                tracing::warn!(%err, "Failed to get updates");
+                tokio::time::sleep(Duration::from_secs(5)).await;
+                continue;
+            }
+        };
+        for update in updates {
+            offset = offset.max(update.update_id + 1);
+            app.handle_update(update).await;
+        }
+    }
+}
+
+impl App {
+    async fn reply(&self, chat_id: i64, text: &str) {
+        if let Err(err) = self.bot.send_message(chat_id, text, &[]).await {
+            tracing::warn!(%err, chat_id, "Failed to send message");
+        }
+    }
+
+    async fn handle_update(&self, update: Update) {
+        if let Some(query) = update.callback_query {
+            let Some(message) = query.message else {
+                return;
+            };
+            let text = match query.data.as_deref().and_then(|v| v.split_once(':')) {
+                Some(("a", log_id)) => self.decide(message.chat.id, log_id, true).await,
+                Some(("d", log_id)) => self.decide(message.chat.id, log_id, false).await,
+                _ => "Unknown button".into(),
+            };
+            if let Err(err) = self.bot.answer_callback_query(&query.id, &text).await {
+                tracing::warn!(%err, "Failed to answer callback query");
+            }
+            if let Err(err) = self
+                .bot
+                .edit_message_text(message.chat.id, message.message_id, &text)
+                .await
+            {
+                tracing::debug!(%err, "Failed to edit message");
+            }
+            return;
+        }
+
+        let Some(message) = update.message else {
+            return;
+        };
+        let chat_id = message.chat.id;
+        let Some((command, arg)) = message.text.as_deref().and_then(parse_command) else {
+            return;
+        };
+        match command {
+            "start" | "link" if !arg.is_empty() => self.link(chat_id, arg).await,
+            "spaces" => self.list_spaces(chat_id).await,
+            "unlink" => self.unlink(chat_id).await,
+            _ => self.reply(chat_id, HELP).await,
+        }
+    }
+
+    async fn link(&self, chat_id: i64, code: &str) {
+        let token = match self.client.telegram_authorize(code, chat_id).await {
+            Ok(v) => v,
+            Err(err) => {
+                return self
+                    .reply(chat_id, &format!("Failed to link chat: {err}"))
+                    .await
+            }
+        };
+        let username = match self.client.get_self(&token).await {
+            Ok(v) => v.name,
+            Err(err) => {
+                return self
+                    .reply(chat_id, &format!("Failed to get user: {err}"))
+                    .await
+            }
+        };
+
+        let mut state = self.state.lock().await;
+        let previous = state.chats.insert(
+            chat_id,
+            LinkedChat {
+                token,
+                username: username.clone(),
+                requests: HashMap::new(),
+            },
+        );
+        state.save(&self.state_path);
+        drop(state);
+        if let Some(previous) = previous {
+            self.revoke(&previous.token).await;
+        }
+
+        self.reply(
+            chat_id,
+            &format!(
+                "Chat is linked to {username}. Unlock requests of your spaces will be sent here"
+            ),
+        )
+        .await;
+    }
+
+    async fn unlink(&self, chat_id: i64) {
+        let mut state = self.state.lock().await;
+        let chat = state.chats.remove(&chat_id);
+        state.save(&self.state_path);
+        drop(state);
+
+        match chat {
+            Some(chat) => {
+                self.revoke(&chat.token).await;
+                self.reply(chat_id, "Chat is unlinked").await;
+            }
+            None => self.reply(chat_id, "Chat is not linked").await,
+        }
+    }
+
+    /// Revoke token of bot, errors are only logged.
+    async fn revoke(&self, token: &str) {
+        let Ok(parsed) = Token::parse(token) else {
+            return;
+        };
+        if let Err(err) = self.client.unlink(token, parsed.iat).await {
+            tracing::warn!(%err, "Failed to revoke token of unlinked chat");
+        }
+    }
+
+    async fn list_spaces(&self, chat_id: i64) {
+        let Some(token) = self.token(chat_id).await else {
+            return self.reply(chat_id, HELP).await;
+        };
+        let text = match self.client.get_spaces(&token).await {
+            Ok(spaces) if spaces.is_empty() => "You don't own any space".into(),
+            Ok(spaces) => spaces
+                .iter()
+                .map(|v| format!("{} ({})", v.title, v.id))
+                .collect::<Vec<_>>()
+                .join("\n"),
+            Err(err) => format!("Failed to get spaces: {err}"),
+        };
+        self.reply(chat_id, &text).await;
+    }
+
+    async fn token(&self, chat_id: i64) -> Option<String> {
+        let state = self.state.lock().await;
+        state.chats.get(&chat_id).map(|v| v.token.clone())
+    }
+
+    /// Approve or deny unlock request sent to chat. Returns text for user.
+    async fn decide(&self, chat_id: i64, log_id: &str, approve: bool) -> String {
+        let state = self.state.lock().await;
+        let Some((token, space_id)) = state
+            .chats
+            .get(&chat_id)
+            .and_then(|v| Some((v.token.clone(), v.requests.get(log_id)?.clone())))
+        else {
+            return "Request is already decided or chat is unlinked".into();
+        };
+        drop(state);
+
+        let res = self
+            .client
+            .decide_unlock_request(&token, &space_id, log_id, approve)
+            .await;
+        let mut state = self.state.lock().await;
+        if let Some(chat) = state.chats.get_mut(&chat_id) {
+            chat.requests.remove(log_id);
+        }
+        state.save(&self.state_path);
+
+        match res {
+            Ok(()) if approve => "Unlock request approved".into(),
+            Ok(()) => "Unlock request denied".into(),
+            Err(err) => format!("Failed to decide unlock request: {err}"),
+        }
+    }
+
+    /// Send new unlock requests of spaces owned by linked users.
+    async fn send_unlock_requests(&self) {
+        let chats: Vec<_> = {
+            let state = self.state.lock().await;
+            state
+                .chats
+                .iter()
+                .map(|(id, v)| (*id, v.token.clone()))
+                .collect()
+        };
+
+        for (chat_id, token) in chats {
+            let spaces = match self.client.get_spaces(&token).await {
+                Ok(v) => v,
+                Err(err) if err.is_unauthorized() => {
+                    let mut state = self.state.lock().await;
+                    state.chats.remove(&chat_id);
+                    state.save(&self.state_path);
+                    drop(state);
+                    self.reply(chat_id, "Token of bot was revoked, chat is unlinked")
+                        .await;
+                    continue;
+                }
+                Err(err) => {
+                    tracing::warn!(%err, chat_id, "Failed to get spaces of linked user");
+                    continue;
+                }
+            };
+
+            // requests decided elsewhere are forgotten if all spaces were checked
+            let mut pending = Some(HashSet::new());
+            for space in spaces.into_iter().filter(|v| !v.archived) {
+                let requests = match self.client.get_unlock_requests(&token, &space.id).await {
+                    Ok(v) => v,
+                    Err(err) => {
+                        tracing::warn!(%err, space_id = space.id, "Failed to get unlock requests");
+                        pending = None;
+                        continue;
+                    }
+                };
+                for request in requests {
+                    if let Some(pending) = &mut pending {
+                        pending.insert(request.id.clone());
+                    }
+                    let sent = {
+                        let state = self.state.lock().await;
+                        state
+                            .chats
+                            .get(&chat_id)
+                            .is_none_or(|v| v.requests.contains_key(&request.id))
+                    };
+                    if sent {
+                        continue;
+                    }
+
+                    let text = format!(
+                        "Unlock request in {}\nAccount: {}",
+                        space.title,
+                        request.sp_acc_id.as_deref().unwrap_or("unknown")
+                    );
+                    let buttons = [
+                        ("Approve".into(), format!("a:{}", request.id)),
+                        ("Deny".into(), format!("d:{}", request.id)),
+                    ];
+                    if let Err(err) = self.bot.send_message(chat_id, &text, &buttons).await {
+                        tracing::warn!(%err, chat_id, "Failed to send unlock request");
+                        continue;
+                    }
+
+                    let mut state = self.state.lock().await;
+                    if let Some(chat) = state.chats.get_mut(&chat_id) {
+                        chat.requests.insert(request.id, space.id.clone());
+                    }
+                    state.save(&self.state_path);
+                }
+            }
+
+            if let Some(pending) = pending {
+                let mut state = self.state.lock().await;
+                if let Some(chat) = state.chats.get_mut(&chat_id) {
+                    chat.requests.retain(|k, _| pending.contains(k));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn commands() {
+        assert_eq!(parse_command("/start abc"), Some(("start", "abc")));
+        assert_eq!(
+            parse_command("/start@archk_bot  abc "),
+            Some(("start", "abc"))
+        );
+        assert_eq!(parse_command("/spaces"), Some(("spaces", "")));
+        assert_eq!(parse_command("hello"), None);
+    }
+
+    #[test]
+    fn state_is_private() {
+        let dir = std::env::temp_dir().join(format!("archk-telegram-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("state.json");
+        // left by older version with default permissions
+        std::fs::write(path.with_extension("tmp"), "").unwrap();
+        std::fs::set_permissions(path.with_extension("tmp"), Permissions::from_mode(0o644))
+            .unwrap();
+
+        State::default().save(&path);
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+}
@@ -0,0 +1,155 @@
+//! Minimal client of Telegram Bot API.
+
+use std::time::Duration;
+
+use serde::{de::DeserializeOwned, Deserialize};
+use serde_json::{json, Value};
+
+/// Timeout of long polling in seconds
+const POLL_TIMEOUT: u64 = 30;
+
+#[derive(Deserialize, Debug)]
+pub struct Update {
+    pub update_id: i64,
+    #[serde(default)]
+    pub message: Option<Message>,
+    #[serde(default)]
+    pub callback_query: Option<CallbackQuery>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Message {
+    pub message_id: i64,
+    pub chat: Chat,
+    #[serde(default)]
+    pub text: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Chat {
+    pub id: i64,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct CallbackQuery {
+    pub id: String,
+    #[serde(default)]
+    pub message: Option<Message>,
+    #[serde(default)]
+    pub data: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct BotResponse<T> {
+    ok: bool,
+    #[serde(default = "Option::default")]
+    result: Option<T>,
+    #[serde(default)]
+    description: Option<String>,
+}
+
+/// Button of inline keyboard: text and callback data.
+pub type Button = (String, String);
+
+pub struct Bot {
+    http: reqwest::Client,
+    /// Bot API URL with token, eg. `https://api.telegram.org/bot<token>`
+    url: String,
+}
+
+impl Bot {
+    pub fn new(api_url: &str, token: &str) -> Self {
+        Self {
+            http: reqwest::Client::builder()
+                .timeout(Duration::from_secs(POLL_TIMEOUT * 2))
+                .build()
+                .expect("http client"),
+            url: format!("{}/bot{token}", api_url.trim_end_matches('/')),
+        }
+    }
+
+    async fn call<T: DeserializeOwned>(&self, method: &str, body: Value) -> Result<T, String> {
+        let res = self
+            .http
+            .post(format!("{}/{method}", self.url))
+            .json(&body)
+            .send()
+            .await
+            // error contains URL with bot token
+            .map_err(|e| e.without_url().to_string())?;
+        let res: BotResponse<T> = res.json().await.map_err(|e| e.without_url().to_string())?;
+        match res {
+            BotResponse {
+                ok: true,
+                result: Some(v),
+                ..
+            } => Ok(v),
+            BotResponse { description, .. } => Err(format!(
+                "`{method}` failed: {}",
+                description.unwrap_or_default()
+            )),
+        }
+    }
+
+    /// Wait for updates after `offset`.
+    pub async fn get_updates(&self, offset: i64) -> Result<Vec<Update>, String> {
+        self.call(
+            "getUpdates",
+            json!({
+                "offset": offset,
+                "timeout": POLL_TIMEOUT,
+                "allowed_updates": ["message", "callback_query"],
+            }),
+        )
+        .await
+    }
+
+    /// Send message with optional inline keyboard, one button per row. Returns ID of
+    /// sent message.
+    pub async fn send_message(
+        &self,
+        chat_id: i64,
+        text: &str,
+        buttons: &[Button],
+    ) -> Result<i64, String> {
+        let mut body = json!({ "chat_id": chat_id, "text": text });
+        if !buttons.is_empty() {
+            body["reply_markup"] = keyboard(buttons);
+        }
+        let message: Message = self.call("sendMessage", body).await?;
+        Ok(message.message_id)
+    }
+
+    /// Replace text of message, removing its keyboard.
+    pub async fn edit_message_text(
+        &self,
+        chat_id: i64,
+        message_id: i64,
+        text: &str,
+    ) -> Result<(), String> {
+        self.call::<Value>(
+            "editMessageText",
+            json!({ "chat_id": chat_id, "message_id": message_id, "text": text }),
+        )
+        .await
+        .map(drop)
+    }
+
+    /// Stop loading indicator of button, showing `text` to user.
+    pub async fn answer_callback_query(&self, id: &str, text: &str) -> Result<(), String> {
+        self.call::<Value>(
+            "answerCallbackQuery",
+            json!({ "callback_query_id": id, "text": text }),
+        )
+        .await
+        .map(drop)
+    }
+}
+
+fn keyboard(buttons: &[Button]) -> Value {
+    let rows: Vec<_> = buttons
+        .iter()
+        .map(|(text, data)| json!([{ "text": text, "callback_data": data }]))
+        .collect();
+    json!({ "inline_keyboard": rows })
+}
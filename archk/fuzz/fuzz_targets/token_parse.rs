@@ -0,0 +1,18 @@
+//! `Token::parse` should never panic, and every parsed token should be printed back
+//! to the same string.
+//!
+//! Run with `cargo +nightly fuzz run token_parse` from `archk/`.
+
+#![no_main]
+
+use archk::v1::auth::Token;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(s) = std::str::from_utf8(data) else {
+        return;
+    };
+    if let Ok(token) = Token::parse(s) {
+        assert_eq!(token.to_string(), s);
+    }
+});
@@ -2,4 +2,4 @@
 pub mod v1;
 
 #[cfg(feature = "derive")]
-pub use documentation_macro::Documentation;
+pub use documentation_macro::{Documentation, Validate};
@@ -1,7 +1,10 @@
 use std::borrow::Cow;
 
+use documentation_macro::Documentation;
 use serde::{Deserialize, Serialize};
 
+use super::docs::impl_documentation;
+
 #[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Debug)]
 pub enum Response<T = NeverSerialize> {
     #[serde(rename = "response")]
@@ -14,7 +17,7 @@ pub enum Response<T = NeverSerialize> {
 pub enum NeverSerialize {}
 
 /// Full error data, including details of error
-#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Debug)]
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Debug, Documentation)]
 pub struct ErrorData {
     /// Error code
     pub code: Error,
@@ -42,22 +45,39 @@ impl ErrorData {
 }
 
 macro_rules! impl_error {
-    ( $(#[$a:meta])* pub enum $e:ident { $( $(#[$b:meta])* $var:ident = $code:literal : $http:literal ),* $(,)? } ) => {
+    ( $(#[$a:meta])* pub enum $e:ident { $( $(#[doc = $doc:literal])* $var:ident = $code:literal : $http:literal ),* $(,)? } ) => {
         $(#[$a])*
         pub enum $e {
             $(
-                $(#[$b])*
+                $(#[doc = $doc])*
                 $var = $code,
             )*
         }
 
         impl $e {
+            /// All error variants.
+            pub const ALL: &'static [Self] = &[ $( Self::$var, )* ];
+
             /// Returns HTTP code by error.
             pub const fn http_code(self) -> u16 {
                 match self {
                     $( Self::$var => $http, )*
                 }
             }
+
+            /// Returns name of variant, eg. `NoEndpoint`.
+            pub const fn name(self) -> &'static str {
+                match self {
+                    $( Self::$var => stringify!($var), )*
+                }
+            }
+
+            /// Returns description of error from its documentation.
+            pub fn description(self) -> &'static str {
+                match self {
+                    $( Self::$var => concat!( $( $doc, "\n", )* ).trim(), )*
+                }
+            }
         }
 
         impl From<$e> for u16 {
@@ -102,6 +122,10 @@ impl_error!(
         Conflict = 4002 : 409,
         /// Access forbidden for resource
         Forbidden = 4003 : 403,
+        /// Requested object existed, but no longer available (eg. expired)
+        Gone = 4004 : 410,
+        /// Quota of user role exceeded (eg. too many spaces)
+        QuotaExceeded = 4005 : 403,
 
         /// Endpoint does not exists
         NoEndpoint = 5001 : 404,
@@ -111,9 +135,17 @@ impl_error!(
         ProcessingError = 5003 : 415,
         /// Invalid token passed or no token passed
         Unauthorized = 5004 : 401,
+        /// Too many requests, try again later
+        RateLimited = 5005 : 429,
+        /// Request body is too large
+        PayloadTooLarge = 5006 : 413,
+        /// Server is temporarily unable to handle request
+        ServiceUnavailable = 5007 : 503,
     }
 );
 
+impl_documentation!(Error as u16);
+
 pub mod errs {
     #[derive(Copy, Clone, PartialEq, Eq, Debug)]
     pub struct InvalidValue(pub(crate) ());
@@ -146,6 +178,42 @@ impl Error {
     }
 }
 
+/// Description of [`Error`] variant, see [`Error::describe`].
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Debug, Documentation)]
+pub struct ErrorDescription {
+    /// Error code
+    pub code: u16,
+    /// Name of error (eg. `NoEndpoint`)
+    pub name: Cow<'static, str>,
+    /// HTTP status code of responses with this error
+    pub http_code: u16,
+    /// Human readable description
+    pub description: Cow<'static, str>,
+}
+
+impl Error {
+    /// Describe error for error catalogue.
+    ///
+    /// # Example
+    /// ```
+    /// use archk::v1::api::Error;
+    ///
+    /// let desc = Error::NoEndpoint.describe();
+    /// assert_eq!(desc.code, 5001);
+    /// assert_eq!(desc.http_code, 404);
+    /// assert_eq!(desc.name, "NoEndpoint");
+    /// assert_eq!(desc.description, "Endpoint does not exists");
+    /// ```
+    pub fn describe(self) -> ErrorDescription {
+        ErrorDescription {
+            code: self.into(),
+            name: self.name().into(),
+            http_code: self.http_code(),
+            description: self.description().into(),
+        }
+    }
+}
+
 impl From<Error> for ErrorData {
     fn from(code: Error) -> Self {
         Self { code, detail: None }
@@ -171,3 +239,26 @@ impl<T: Serialize> IntoResponse for Response<T> {
         j
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn error_code_round_trip() {
+        for &err in Error::ALL {
+            let code: u16 = err.into();
+            assert_eq!(Error::try_from(code), Ok(err), "Code: {code}");
+        }
+        assert!(Error::try_from(0).is_err());
+    }
+
+    #[test]
+    fn error_serializes_as_code() {
+        for &err in Error::ALL {
+            let s = serde_json::to_string(&err).expect("json");
+            assert_eq!(s, (err as u16).to_string());
+            assert_eq!(serde_json::from_str::<Error>(&s).expect("json"), err);
+        }
+    }
+}
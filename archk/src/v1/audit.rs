@@ -0,0 +1,99 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use documentation_macro::Documentation;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use super::{docs::impl_documentation, macros::impl_try_from_enum};
+
+impl_try_from_enum!(
+    /// Administrative action recorded in audit log
+    #[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+    #[serde(into = "i64", try_from = "i64")]
+    pub enum AuditAction : repr(i64) {
+        /// Password of user reset
+        PasswordReset = 100,
+        /// All tokens of user revoked
+        UserLoggedOut = 101,
+        /// Login of user locked until timestamp in `detail`
+        LoginLocked = 102,
+        /// Login of user unlocked
+        LoginUnlocked = 103,
+        /// User deleted by admin
+        UserDeleted = 104,
+        /// Admin issued token of user, its expiration timestamp in `detail`
+        UserImpersonated = 105,
+        /// Maintenance mode enabled, its message in `detail`
+        MaintenanceEnabled = 106,
+        /// Maintenance mode disabled
+        MaintenanceDisabled = 107,
+    }
+);
+
+// On serialization AuditAction is actually integer
+impl_documentation!(AuditAction as i64);
+
+/// Audit log entry.
+///
+/// # Example
+/// ```
+/// use archk::v1::{audit::{AuditAction, AuditLog}, user::UserID};
+///
+/// let log = AuditLog::new(UserID::new().to_string(), AuditAction::UserLoggedOut)
+///     .with_target(UserID::new().to_string());
+/// assert_eq!(log.detail, None);
+/// ```
+#[derive(Serialize, Deserialize, Clone, Debug, Documentation)]
+pub struct AuditLog {
+    /// Global audit log ID (usually represent as UUIDv4)
+    pub id: String,
+    /// Creation timestamp
+    pub created_at: i64,
+
+    /// User performed action, `null` if deleted
+    pub actor_id: Option<String>,
+    /// Action
+    pub act: AuditAction,
+    /// ID of object (eg. user) action performed on, if any
+    pub target_id: Option<String>,
+    /// Free-form details, if any
+    pub detail: Option<String>,
+    /// IP address of actor, if known
+    pub ip: Option<String>,
+}
+
+impl AuditLog {
+    /// Creates empty log record. See [`AuditLog`] docs for more
+    pub fn new(actor_id: String, act: AuditAction) -> Self {
+        Self {
+            id: Uuid::new_v4().to_string(),
+            created_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .expect("Time since UNIX EPOCH")
+                .as_millis() as i64,
+            actor_id: Some(actor_id),
+            act,
+            target_id: None,
+            detail: None,
+            ip: None,
+        }
+    }
+
+    /// Assigns `target_id`. See [`AuditLog`] docs for more
+    pub fn with_target(mut self, target_id: String) -> Self {
+        self.target_id = Some(target_id);
+        self
+    }
+
+    /// Assigns `detail`. See [`AuditLog`] docs for more
+    pub fn with_detail(mut self, detail: String) -> Self {
+        self.detail = Some(detail);
+        self
+    }
+
+    /// Assigns `ip`. See [`AuditLog`] docs for more
+    pub fn with_ip(mut self, ip: Option<String>) -> Self {
+        self.ip = ip;
+        self
+    }
+}
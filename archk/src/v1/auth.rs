@@ -18,11 +18,22 @@
 //! let verified_token = Token::parse(&token_str);
 //! assert_eq!(Ok(token), verified_token);
 //! ```
+//!
+//! ## Formats
+//!
+//! Tokens of [`TokenFormat::V1`] (eg. `acp_...`) contain 32 bits of random data, tokens
+//! of [`TokenFormat::V2`] (eg. `acp2_...`) contain 128 bits. [`Token::parse`] accepts both.
 
 use core::fmt;
 use std::time::{SystemTime, UNIX_EPOCH};
 
 use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use serde::Deserialize;
+
+/// Maximum length of token string: 4 bytes of prefix, `_` and 28 bytes of
+/// [`TokenFormat::V2`] data in base64. Longer strings are rejected by [`Token::parse`]
+/// before decoding.
+pub const TOKEN_MAX_LEN: usize = 43;
 
 /// Type of token, used in prefixes
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -69,82 +80,131 @@ impl TokenTy {
     }
 }
 
-/// Token raw data. Can be generated through [`Token::new`], converted to string via [`Token::to_string`]
-/// and parsed by [`Token::parse`].
+/// Format of token string.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TokenFormat {
+    /// 32 bits of random data, prefix without version (eg. `acp`)
+    V1,
+    /// 128 bits of random data, prefix with `2` suffix (eg. `acp2`)
+    #[default]
+    V2,
+}
+
+impl TokenFormat {
+    /// Length of decoded data: `iat`, `rnd` and checksum.
+    fn data_len(self) -> usize {
+        match self {
+            Self::V1 => 8 + 4 + 4,
+            Self::V2 => 8 + 16 + 4,
+        }
+    }
+}
+
+/// Token raw data. Can be generated through [`Token::new`] or [`Token::new_v2`], converted
+/// to string via [`Token::to_string`] and parsed by [`Token::parse`].
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Token {
     /// Type of token
     pub ty: TokenTy,
+    /// Format of token
+    pub format: TokenFormat,
     /// "Issued at", timestamp in milliseconds
     pub iat: u64,
-    /// Random data
-    pub rnd: u32,
+    /// Random data. Only lower 32 bits are used by [`TokenFormat::V1`]
+    pub rnd: u128,
 }
 
 impl Token {
-    /// Generate new token with given [`TokenTy`].
+    /// Generate new token of [`TokenFormat::V1`] with given [`TokenTy`].
     pub fn new(ty: TokenTy) -> Self {
+        Self::with_format(ty, TokenFormat::V1)
+    }
+
+    /// Generate new token of [`TokenFormat::V2`] with given [`TokenTy`].
+    pub fn new_v2(ty: TokenTy) -> Self {
+        Self::with_format(ty, TokenFormat::V2)
+    }
+
+    /// Generate new token with given [`TokenTy`] and [`TokenFormat`].
+    pub fn with_format(ty: TokenTy, format: TokenFormat) -> Self {
         Self {
             ty,
+            format,
             iat: SystemTime::now()
                 .duration_since(UNIX_EPOCH)
                 .expect("Current system time less than unix epoch")
                 .as_millis() as u64,
-            rnd: rand::random(),
+            rnd: match format {
+                TokenFormat::V1 => rand::random::<u32>().into(),
+                TokenFormat::V2 => rand::random(),
+            },
         }
     }
 
     /// Parse token string to [`Token`]. Token should contain prefix
     pub fn parse(token: &str) -> Result<Self, Error> {
+        if token.len() > TOKEN_MAX_LEN {
+            return Err(Error::TooLong);
+        }
         let Some((prefix, token)) = token.split_once('_') else {
             return Err(Error::MissingPrefix);
         };
 
+        let (prefix, format) = match prefix.strip_suffix('2') {
+            Some(prefix) => (prefix, TokenFormat::V2),
+            None => (prefix, TokenFormat::V1),
+        };
         let Some(ty) = TokenTy::from_prefix(prefix) else {
             return Err(Error::UnknownPrefix);
         };
 
         let data = match URL_SAFE_NO_PAD.decode(token) {
-            Ok(data) if data.len() != 16 => return Err(Error::MalformedData),
+            Ok(data) if data.len() != format.data_len() => return Err(Error::MalformedData),
             Ok(data) => data,
             Err(e) => return Err(Error::DecodeError(e)),
         };
-        let checksum = {
-            let mut buff = [0; 4];
-            buff.copy_from_slice(&data[12..]);
-            u32::from_le_bytes(buff)
-        };
+        let (payload, checksum) = data.split_at(data.len() - 4);
+        let checksum = u32::from_le_bytes(checksum.try_into().expect("4 bytes"));
 
-        if checksum != crc32fast::hash(&data[..12]) {
+        if checksum != crc32fast::hash(payload) {
             return Err(Error::ChecksumError);
         }
 
-        let iat = {
-            let mut buff = [0; 8];
-            buff.copy_from_slice(&data[..8]);
-            u64::from_le_bytes(buff)
-        };
-        let rnd = {
-            let mut buff = [0; 4];
-            buff.copy_from_slice(&data[8..12]);
-            u32::from_le_bytes(buff)
+        let (iat, rnd) = payload.split_at(8);
+        let iat = u64::from_le_bytes(iat.try_into().expect("8 bytes"));
+        let rnd = match format {
+            TokenFormat::V1 => u32::from_le_bytes(rnd.try_into().expect("4 bytes")).into(),
+            TokenFormat::V2 => u128::from_le_bytes(rnd.try_into().expect("16 bytes")),
         };
 
-        Ok(Self { ty, iat, rnd })
+        Ok(Self {
+            ty,
+            format,
+            iat,
+            rnd,
+        })
     }
 }
 impl fmt::Display for Token {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let mut data = Vec::new();
         data.extend_from_slice(&self.iat.to_le_bytes());
-        data.extend_from_slice(&self.rnd.to_le_bytes());
+        match self.format {
+            TokenFormat::V1 => data.extend_from_slice(&(self.rnd as u32).to_le_bytes()),
+            TokenFormat::V2 => data.extend_from_slice(&self.rnd.to_le_bytes()),
+        }
 
         let checksum = crc32fast::hash(&data);
         data.extend_from_slice(&checksum.to_le_bytes());
 
         let token_str = URL_SAFE_NO_PAD.encode(&data);
 
-        write!(f, "{}_{token_str}", self.ty.prefix())
+        let version = match self.format {
+            TokenFormat::V1 => "",
+            TokenFormat::V2 => "2",
+        };
+        write!(f, "{}{version}_{token_str}", self.ty.prefix())
     }
 }
 
@@ -162,15 +222,109 @@ pub enum Error {
     DecodeError(base64::DecodeError),
     /// Invalid checksum
     ChecksumError,
+    /// Token string is longer than [`TOKEN_MAX_LEN`]
+    TooLong,
 }
 
 #[cfg(test)]
 mod test {
+    use proptest::prelude::*;
+
     use super::*;
 
+    fn token_ty() -> impl Strategy<Value = TokenTy> {
+        prop_oneof![Just(TokenTy::Personal), Just(TokenTy::Service)]
+    }
+
+    fn token() -> impl Strategy<Value = Token> {
+        let v1 = (token_ty(), any::<u64>(), any::<u32>()).prop_map(|(ty, iat, rnd)| Token {
+            ty,
+            format: TokenFormat::V1,
+            iat,
+            rnd: rnd.into(),
+        });
+        let v2 = (token_ty(), any::<u64>(), any::<u128>()).prop_map(|(ty, iat, rnd)| Token {
+            ty,
+            format: TokenFormat::V2,
+            iat,
+            rnd,
+        });
+        prop_oneof![v1, v2]
+    }
+
+    proptest! {
+        #[test]
+        fn round_trip(token in token()) {
+            let s = token.to_string();
+            prop_assert!(s.len() <= TOKEN_MAX_LEN);
+            prop_assert_eq!(Token::parse(&s), Ok(token));
+        }
+
+        #[test]
+        fn any_string_does_not_panic(s in "\\PC*") {
+            let _ = Token::parse(&s);
+        }
+
+        #[test]
+        fn bit_flip_is_detected(token in token(), bit in any::<usize>()) {
+            let s = token.to_string();
+            let (prefix, data) = s.split_once('_').unwrap();
+            let mut data = URL_SAFE_NO_PAD.decode(data).unwrap();
+            let bit = bit % (data.len() * 8);
+            data[bit / 8] ^= 1 << (bit % 8);
+            let s = format!("{prefix}_{}", URL_SAFE_NO_PAD.encode(&data));
+            prop_assert_eq!(Token::parse(&s), Err(Error::ChecksumError));
+        }
+
+        #[test]
+        fn wrong_length_is_rejected(prefix in "ac[ps]2?", data in prop::collection::vec(any::<u8>(), 0..40)) {
+            let expected = if prefix.ends_with('2') { 28 } else { 16 };
+            prop_assume!(data.len() != expected);
+            let s = format!("{prefix}_{}", URL_SAFE_NO_PAD.encode(&data));
+            let res = Token::parse(&s);
+            prop_assert!(matches!(res, Err(Error::MalformedData | Error::TooLong)), "{:?}", res);
+        }
+
+        #[test]
+        fn malformed_base64_is_rejected(token in token(), pos in any::<usize>(), c in "[^A-Za-z0-9_-]") {
+            let mut s = token.to_string();
+            let start = s.find('_').unwrap() + 1;
+            let pos = start + pos % (s.len() - start);
+            s.replace_range(pos..pos + 1, &c);
+            prop_assert!(Token::parse(&s).is_err());
+        }
+    }
+
+    #[test]
+    fn oversize_is_rejected_early() {
+        let s = format!("acp_{}", "A".repeat(1 << 20));
+        assert_eq!(Token::parse(&s), Err(Error::TooLong));
+    }
+
+    #[test]
+    fn formats_are_distinguished() {
+        let v1 = Token::new(TokenTy::Personal).to_string();
+        let v2 = Token::new_v2(TokenTy::Service).to_string();
+        assert!(v1.starts_with("acp_"), "{v1}");
+        assert!(v2.starts_with("acs2_"), "{v2}");
+        assert_eq!(v2.len(), TOKEN_MAX_LEN);
+
+        // data of one format under prefix of another
+        let (_, data) = v2.split_once('_').unwrap();
+        assert_eq!(
+            Token::parse(&format!("acs_{data}")),
+            Err(Error::MalformedData)
+        );
+    }
+
     #[test]
     fn generate_and_verify_token() {
-        let tokens: Vec<_> = (0..16).map(|_| Token::new(TokenTy::Personal)).collect();
+        let tokens: Vec<_> = (0..16)
+            .map(|i| match i % 2 {
+                0 => Token::new(TokenTy::Personal),
+                _ => Token::new_v2(TokenTy::Personal),
+            })
+            .collect();
         let strings: Vec<_> = tokens.iter().map(|v| v.to_string()).collect();
         let verified = strings.iter().map(|v| Token::parse(v));
 
@@ -16,6 +16,24 @@
 //! - [`Vec<T>`] if `T: Documentation`: array
 //! - [`Option<T>`] if `T: Documentation`: nullable
 //! - [`MayIgnored<T>`] if `T: Documentation`: if may ignored in serde
+//! - `[T; N]` if `T: Documentation`: array with exactly `N` elements
+//! - [`HashMap<String, V>`](std::collections::HashMap) and [`BTreeMap<String, V>`](std::collections::BTreeMap)
+//!   if `V: Documentation`: `Map` object, type of values is in [`DocumentationObject::items`]
+//! - `(A, B)` and `(A, B, C)`: `Tuple` (JSON array), types of elements are in [`DocumentationObject::items`]
+//!
+//! ```
+//! use std::collections::HashMap;
+//! use archk::v1::docs::Documentation;
+//!
+//! let object = <HashMap<String, Vec<u32>> as Documentation>::DOCUMENTATION_OBJECT;
+//! assert_eq!(object.name, "Map");
+//! assert_eq!(object.items[0].name, "u32");
+//! assert!(object.items[0].is_array);
+//!
+//! let object = <(String, [i64; 2]) as Documentation>::DOCUMENTATION_OBJECT;
+//! assert_eq!(object.name, "Tuple");
+//! assert_eq!(object.items[1].max, Some(2));
+//! ```
 //!
 //! ## Examples
 //!
@@ -47,6 +65,41 @@
 //! assert_eq!(object.fields.len(), 3);
 //! ```
 //!
+//! ### Documentate enum
+//! Enums are documented as union of variants (see [`DocumentationObject::one_of`]).
+//! Serde `tag`, `content`, `untagged`, `rename` and `rename_all` attributes are respected.
+//! ```ignore
+//! #[derive(serde::Deserialize, archk::Documentation)]
+//! #[serde(tag = "ty", rename_all = "snake_case")]
+//! pub enum Event {
+//!     /// Item taken
+//!     TakeItem { item_id: String },
+//!     /// Item returned
+//!     ReturnItem { item_id: String },
+//! }
+//!
+//! let one_of = <Event as Documentation>::DOCUMENTATION_OBJECT.one_of.unwrap();
+//! assert_eq!(one_of.tagging, DocumentationTagging::Internal { tag: "ty" });
+//! assert_eq!(one_of.variants[0].name, "take_item");
+//! ```
+//!
+//! ### Documentate validation rules
+//! Fields may have example value and constraints, they are rendered in docgen output.
+//! `doc_min` and `doc_max` are value of number or length of string or array.
+//! ```ignore
+//! #[derive(archk::Documentation)]
+//! pub struct Credentials {
+//!     /// Username
+//!     #[doc_example = "greg.b42"]
+//!     #[doc_min = 3]
+//!     #[doc_max = 31]
+//!     pub username: String,
+//! }
+//!
+//! let object = <Credentials as Documentation>::DOCUMENTATION_OBJECT;
+//! assert_eq!(object.fields[0].documentation.example, Some("greg.b42"));
+//! ```
+//!
 
 use serde::Serialize;
 
@@ -61,6 +114,33 @@ pub struct DocumentationField {
     pub documentation: DocumentationObject,
 }
 
+/// How variants of enum are represented in JSON. Same as serde
+/// [enum representations](https://serde.rs/enum-representations.html).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DocumentationTagging {
+    /// `{ "variant": { ... } }`, unit variants are just `"variant"`
+    External,
+    /// `{ "<tag>": "variant", ... }`
+    Internal { tag: &'static str },
+    /// `{ "<tag>": "variant", "<content>": { ... } }`
+    Adjacent {
+        tag: &'static str,
+        content: &'static str,
+    },
+    /// Variant content without any tag
+    Untagged,
+}
+
+/// Union of variants, see [`DocumentationObject::one_of`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub struct DocumentationOneOf {
+    /// Representation of variants
+    pub tagging: DocumentationTagging,
+    /// Variants. `name` is tag value of variant, `documentation` is its content
+    pub variants: &'static [DocumentationField],
+}
+
 /// Represents [`Documentation`] object. Contains basic information about type.
 ///
 /// # Example
@@ -81,6 +161,10 @@ pub struct DocumentationObject {
     pub description: &'static str,
     /// Struct fields
     pub fields: &'static [DocumentationField],
+    /// Type parameters of container: type of values for `Map` or types of elements for `Tuple`
+    pub items: &'static [DocumentationObject],
+    /// Variants if type is union (enum), see [`DocumentationObject::one_of`]
+    pub one_of: Option<DocumentationOneOf>,
 
     /// Is this type array? Usually covered into [`Vec`]
     pub is_array: bool,
@@ -89,6 +173,13 @@ pub struct DocumentationObject {
     /// Is this type may not exists in object?
     /// See [`MayIgnored`] for more.
     pub is_may_ignored: bool,
+
+    /// Example value of field, if any
+    pub example: Option<&'static str>,
+    /// Minimum value of number or minimum length of string or array, if any
+    pub min: Option<i64>,
+    /// Maximum value of number or maximum length of string or array, if any
+    pub max: Option<i64>,
 }
 
 impl DocumentationObject {
@@ -103,12 +194,47 @@ impl DocumentationObject {
             name,
             description,
             fields,
+            items: &[],
+            one_of: None,
             is_array: false,
             is_option: false,
             is_may_ignored: false,
+            example: None,
+            min: None,
+            max: None,
         }
     }
 
+    /// Creates union of `variants` (eg. enum body). Usually generated by [`archk::Documentation`]
+    /// derive macro from serde `tag`, `content` and `untagged` attributes.
+    ///
+    /// # Example
+    /// ```
+    /// use archk::v1::docs::{Documentation, DocumentationField, DocumentationObject, DocumentationTagging};
+    ///
+    /// const OBJECT: DocumentationObject = DocumentationObject::one_of(
+    ///     "Event",
+    ///     "",
+    ///     DocumentationTagging::Untagged,
+    ///     &[DocumentationField { name: "id", documentation: String::DOCUMENTATION_OBJECT }],
+    /// );
+    /// assert_eq!(OBJECT.one_of.unwrap().variants.len(), 1);
+    /// ```
+    pub const fn one_of(
+        name: &'static str,
+        description: &'static str,
+        tagging: DocumentationTagging,
+        variants: &'static [DocumentationField],
+    ) -> Self {
+        let mut object = Self::new(name, description, &[]);
+        object.one_of = Some(DocumentationOneOf { tagging, variants });
+        object
+    }
+    /// Constructor set. See [`DocumentationObject`] documentation for more.
+    pub const fn set_items(mut self, items: &'static [DocumentationObject]) -> Self {
+        self.items = items;
+        self
+    }
     /// Constructor set. See [`DocumentationObject`] documentation for more.
     pub const fn set_array(mut self, is_array: bool) -> Self {
         self.is_array = is_array;
@@ -129,6 +255,21 @@ impl DocumentationObject {
         self.description = description;
         self
     }
+    /// Constructor set. See [`DocumentationObject`] documentation for more.
+    pub const fn set_example(mut self, example: &'static str) -> Self {
+        self.example = Some(example);
+        self
+    }
+    /// Constructor set. See [`DocumentationObject`] documentation for more.
+    pub const fn set_min(mut self, min: i64) -> Self {
+        self.min = Some(min);
+        self
+    }
+    /// Constructor set. See [`DocumentationObject`] documentation for more.
+    pub const fn set_max(mut self, max: i64) -> Self {
+        self.max = Some(max);
+        self
+    }
 }
 
 /// Described type or struct.
@@ -174,10 +315,50 @@ impl<T: Documentation> Documentation for Option<T> {
     const DOCUMENTATION_OBJECT: DocumentationObject = T::DOCUMENTATION_OBJECT.set_option(true);
 }
 
+impl Documentation for std::borrow::Cow<'static, str> {
+    const DOCUMENTATION_OBJECT: DocumentationObject = String::DOCUMENTATION_OBJECT;
+}
+
+impl<T: Documentation, const N: usize> Documentation for [T; N] {
+    const DOCUMENTATION_OBJECT: DocumentationObject = T::DOCUMENTATION_OBJECT
+        .set_array(true)
+        .set_min(N as i64)
+        .set_max(N as i64);
+}
+
+impl<V: Documentation> Documentation for std::collections::BTreeMap<String, V> {
+    const DOCUMENTATION_OBJECT: DocumentationObject =
+        DocumentationObject::new("Map", "", &[]).set_items(&[V::DOCUMENTATION_OBJECT]);
+}
+
+impl<V: Documentation, S> Documentation for std::collections::HashMap<String, V, S> {
+    const DOCUMENTATION_OBJECT: DocumentationObject =
+        DocumentationObject::new("Map", "", &[]).set_items(&[V::DOCUMENTATION_OBJECT]);
+}
+
+impl<A: Documentation, B: Documentation> Documentation for (A, B) {
+    const DOCUMENTATION_OBJECT: DocumentationObject = DocumentationObject::new("Tuple", "", &[])
+        .set_items(&[A::DOCUMENTATION_OBJECT, B::DOCUMENTATION_OBJECT]);
+}
+
+impl<A: Documentation, B: Documentation, C: Documentation> Documentation for (A, B, C) {
+    const DOCUMENTATION_OBJECT: DocumentationObject = DocumentationObject::new("Tuple", "", &[])
+        .set_items(&[
+            A::DOCUMENTATION_OBJECT,
+            B::DOCUMENTATION_OBJECT,
+            C::DOCUMENTATION_OBJECT,
+        ]);
+}
+
 impl<T: Documentation> Documentation for MayIgnored<T> {
     const DOCUMENTATION_OBJECT: DocumentationObject = T::DOCUMENTATION_OBJECT.set_may_ignored(true);
 }
 
+/// Marker of endpoint without request body or without JSON response (eg. file download).
+/// Documents that absence is intended.
+pub struct Empty;
+impl_documentation!(Empty);
+
 /// Represents endpoint method used in autogenerated documentation.
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
 pub enum EndpointMethod {
@@ -199,6 +380,27 @@ impl std::fmt::Display for EndpointMethod {
     }
 }
 
+/// Kind of token required by endpoint.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EndpointAuth {
+    /// No token required
+    None,
+    /// Personal token of user
+    User,
+    /// Token of service account
+    Service,
+}
+impl std::fmt::Display for EndpointAuth {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self {
+            Self::None => write!(f, "none"),
+            Self::User => write!(f, "user"),
+            Self::Service => write!(f, "service"),
+        }
+    }
+}
+
 /// Describes API endpoint in `v1`.
 #[derive(Clone, Debug, Serialize)]
 pub struct Endpoint {
@@ -208,10 +410,50 @@ pub struct Endpoint {
     pub path: &'static str,
     /// Endpoint description. Supports markdown
     pub description: &'static str,
+    /// Path parameters documentation if any. Fields are named as `:segments` of `path`
+    pub params: Option<DocumentationObject>,
+    /// Query parameters documentation if any
+    pub query: Option<DocumentationObject>,
     /// Body documentation if required
     pub body: Option<DocumentationObject>,
     /// Response documentation if available
     pub response: Option<DocumentationObject>,
+    /// Kind of token required to call endpoint
+    pub auth: EndpointAuth,
+    /// Role permissions checked by endpoint. Usually any of them grants access and
+    /// owners of objects (eg. spaces) don't need them, see description for details
+    pub permissions: &'static [&'static str],
+}
+
+impl Endpoint {
+    /// Parts of endpoint that are not documented: `"params"` if path has parameters,
+    /// `"body"` for `POST`, `PUT` and `PATCH` methods and `"response"`.
+    /// Use [`Empty`] to document that endpoint has no body or JSON response.
+    ///
+    /// # Example
+    /// ```
+    /// use archk::v1::docs::{Endpoint, EndpointMethod, _EMPTY_ENDPOINT};
+    ///
+    /// let endpoint = Endpoint { method: EndpointMethod::PUT, path: "/user/:user_id", .._EMPTY_ENDPOINT };
+    /// assert_eq!(endpoint.missing_documentation(), ["params", "body", "response"]);
+    /// ```
+    pub fn missing_documentation(&self) -> Vec<&'static str> {
+        let mut res = Vec::new();
+        if self.params.is_none() && self.path.contains(':') {
+            res.push("params");
+        }
+        let has_body = matches!(
+            self.method,
+            EndpointMethod::POST | EndpointMethod::PUT | EndpointMethod::PATCH
+        );
+        if self.body.is_none() && has_body {
+            res.push("body");
+        }
+        if self.response.is_none() {
+            res.push("response");
+        }
+        res
+    }
 }
 
 // Pseudo-Default implementation of Endpoint. `method`, `path` and `description` should be filled.
@@ -221,6 +463,10 @@ pub const _EMPTY_ENDPOINT: Endpoint = Endpoint {
     method: EndpointMethod::GET,
     path: "",
     description: "",
+    params: None,
+    query: None,
     body: None,
     response: None,
+    auth: EndpointAuth::User,
+    permissions: &[],
 };
@@ -16,9 +16,9 @@ macro_rules! impl_cuid {
                 Self::new()
             }
         }
-        impl Into<String> for $v {
-            fn into(self) -> String {
-                self.0
+        impl From<$v> for String {
+            fn from(v: $v) -> String {
+                v.0
             }
         }
         impl TryFrom<String> for $v {
@@ -28,6 +28,13 @@ macro_rules! impl_cuid {
                 $v::from(v).ok_or(crate::v1::errors::StringIsNotCUID(()))
             }
         }
+        impl std::str::FromStr for $v {
+            type Err = crate::v1::errors::StringIsNotCUID;
+
+            fn from_str(v: &str) -> Result<Self, Self::Err> {
+                v.to_string().try_into()
+            }
+        }
         impl std::ops::Deref for $v {
             type Target = str;
 
@@ -61,9 +68,9 @@ macro_rules! impl_try_from_enum {
             }
         }
 
-        impl Into<$i> for $name {
-            fn into(self) -> $i {
-                self as $i
+        impl From<$name> for $i {
+            fn from(v: $name) -> $i {
+                v as $i
             }
         }
     };
@@ -1,5 +1,9 @@
+/// Audit log models
+pub mod audit;
 /// Authorization models
 pub mod auth;
+/// Organization models
+pub mod org;
 /// Service accounts models
 pub mod service;
 /// Space models
@@ -13,6 +17,8 @@ pub mod models;
 /// Declaration of API response structure
 pub mod api;
 pub mod docs;
+/// Validation of user input
+pub mod validate;
 
 /// Errors used in some models
 pub mod errors {
@@ -40,6 +46,19 @@ pub mod errors {
             write!(f, "expected valid CUID string")
         }
     }
+
+    /// Invalid time of day string.
+    ///
+    /// Example: attempt to call [`TryFrom::try_from`] on string that not `HH:MM` time.
+    /// Used in [`super::space::TimeOfDay`].
+    #[derive(Debug)]
+    pub struct InvalidTimeOfDay(pub(crate) ());
+
+    impl std::fmt::Display for InvalidTimeOfDay {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(f, "expected time in `HH:MM` format")
+        }
+    }
 }
 
 mod macros;
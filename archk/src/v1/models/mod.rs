@@ -47,10 +47,11 @@ use serde::{Deserialize, Serialize};
 ///     "bar": null
 /// }
 /// ```
-#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Debug)]
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Debug, Default)]
 #[serde(untagged)]
 pub enum MayIgnored<T> {
     Value(T),
+    #[default]
     Ignored,
 }
 
@@ -89,9 +90,3 @@ impl<T> MayIgnored<T> {
         }
     }
 }
-
-impl<T> Default for MayIgnored<T> {
-    fn default() -> Self {
-        Self::Ignored
-    }
-}
@@ -0,0 +1,57 @@
+use documentation_macro::Documentation;
+use serde::{Deserialize, Serialize};
+
+use super::{
+    docs::impl_documentation,
+    macros::{impl_cuid, impl_try_from_enum},
+    user::UserID,
+};
+
+/// Represents ID of organization (CUID)
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Debug)]
+#[serde(into = "String", try_from = "String")]
+#[repr(transparent)]
+pub struct OrgID(String);
+impl_cuid!(OrgID);
+impl_documentation!(OrgID);
+
+impl_try_from_enum!(
+    /// Role of user in organization. Roles are ordered, higher role can do everything
+    /// lower role can
+    #[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+    #[serde(into = "i64", try_from = "i64")]
+    pub enum OrgRole : repr(i64) {
+        /// Sees organization, its members and spaces
+        Member = 0,
+        /// Manages spaces of organization and members with lower role
+        Admin = 10,
+        /// Deletes organization. Organization always has at least one owner
+        Owner = 20,
+    }
+);
+
+// On serialization OrgRole is actually integer
+impl_documentation!(OrgRole as i64);
+
+/// Represents organization object. Organization groups spaces and users, eg. departments
+/// of company
+#[derive(Serialize, Deserialize, Clone, Debug, Documentation)]
+pub struct Organization {
+    /// Organization ID
+    pub id: OrgID,
+    /// Organization title
+    pub title: String,
+    /// Creation timestamp in milliseconds
+    pub created_at: i64,
+}
+
+/// Member of organization
+#[derive(Serialize, Deserialize, Clone, Debug, Documentation)]
+pub struct OrgMember {
+    /// User ID
+    pub user_id: UserID,
+    /// Username
+    pub name: String,
+    /// Role of user in organization
+    pub role: OrgRole,
+}
@@ -1,6 +1,8 @@
+use documentation_macro::Documentation;
 use serde::{Deserialize, Serialize};
 
 use super::{
+    docs::impl_documentation,
     macros::{impl_cuid, impl_try_from_enum},
     space::SpaceID,
 };
@@ -11,6 +13,7 @@ use super::{
 #[repr(transparent)]
 pub struct ServiceAccountID(String);
 impl_cuid!(ServiceAccountID);
+impl_documentation!(ServiceAccountID);
 
 impl_try_from_enum!(
     /// Type of service account independ of it's space
@@ -24,25 +27,70 @@ impl_try_from_enum!(
         SpaceEventWatcher = 1000,
         /// Can report any supported type of action
         SpaceActor = 1001,
+        /// Can request item registration, approve or deny unlock requests
+        /// and read reports of space
+        SpaceManager = 1002,
     }
 );
 
+impl_documentation!(ServiceAccountTy as i64);
+
 impl ServiceAccountTy {
     /// Is space required to this type?
     pub fn is_space_required(self) -> bool {
-        matches!(self, Self::SpaceEventWatcher | Self::SpaceActor)
+        matches!(
+            self,
+            Self::SpaceEventWatcher | Self::SpaceActor | Self::SpaceManager
+        )
     }
 
     /// Is can be created only by instance admins?
     pub fn is_admin(self) -> bool {
         matches!(self, Self::SSHAuthority)
     }
+
+    /// Name of type in snake case, as in config.
+    ///
+    /// # Example
+    /// ```
+    /// use archk::v1::service::ServiceAccountTy;
+    ///
+    /// assert_eq!(ServiceAccountTy::SpaceActor.name(), "space_actor");
+    /// assert_eq!(ServiceAccountTy::from_name("space_actor"), Some(ServiceAccountTy::SpaceActor));
+    /// ```
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::SSHAuthority => "ssh_authority",
+            Self::SpaceEventWatcher => "space_event_watcher",
+            Self::SpaceActor => "space_actor",
+            Self::SpaceManager => "space_manager",
+        }
+    }
+
+    /// Get type by [`Self::name`]
+    pub fn from_name(name: &str) -> Option<Self> {
+        [
+            Self::SSHAuthority,
+            Self::SpaceEventWatcher,
+            Self::SpaceActor,
+            Self::SpaceManager,
+        ]
+        .into_iter()
+        .find(|v| v.name() == name)
+    }
 }
 
 /// Represents service account
-#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Debug)]
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Debug, Documentation)]
 pub struct ServiceAccount {
+    /// Service ID
     pub id: ServiceAccountID,
+    /// Space ID service belongs to, `null` for admin services
     pub space_id: Option<SpaceID>,
+    /// Service type, see `archk::v1::service::ServiceAccountTy`
     pub ty: ServiceAccountTy,
+    /// Service name
+    pub name: String,
+    /// Service description, eg. where device is placed
+    pub description: Option<String>,
 }
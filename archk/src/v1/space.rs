@@ -1,9 +1,14 @@
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::{
+    collections::BTreeMap,
+    time::{SystemTime, UNIX_EPOCH},
+};
 
+use documentation_macro::Documentation;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
 use super::{
+    docs::{self, impl_documentation},
     macros::{impl_cuid, impl_try_from_enum},
     user::UserID,
 };
@@ -14,23 +19,69 @@ use super::{
 #[repr(transparent)]
 pub struct SpaceID(String);
 impl_cuid!(SpaceID);
+impl_documentation!(SpaceID);
 
 /// Represents ID of item in space (CUID)
 #[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Debug)]
+#[serde(into = "String", try_from = "String")]
 #[repr(transparent)]
 pub struct SpaceItemID(String);
 impl_cuid!(SpaceItemID);
+impl_documentation!(SpaceItemID);
+
+/// Represents ID of tag in space (CUID)
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Debug)]
+#[serde(into = "String", try_from = "String")]
+#[repr(transparent)]
+pub struct SpaceTagID(String);
+impl_cuid!(SpaceTagID);
+impl_documentation!(SpaceTagID);
 
 /// Represents space object
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug, Documentation)]
 pub struct Space {
+    /// Space ID
     pub id: SpaceID,
+    /// Space title
     pub title: String,
+    /// ID of user owning space
     pub owner_id: UserID,
+    /// Logs older than this number of days are removed. Logs are kept forever if `None`
+    pub logs_retention_days: Option<u32>,
+    /// Is space archived? Archived spaces are read-only and don't accept service events
+    pub archived: bool,
+    /// Is space template? Templates can be duplicated by any user allowed to create spaces
+    pub template: bool,
+    /// Record version, incremented on every change
+    pub version: i64,
+}
+
+/// Custom key/value data attached to items and accounts.
+///
+/// See [`crate::v1::validate::metadata`] for limits.
+pub type Metadata = BTreeMap<String, String>;
+
+/// Maximum number of keys in [`Metadata`]
+pub const METADATA_MAX_KEYS: usize = 32;
+/// Maximum length of [`Metadata`] key in bytes
+pub const METADATA_MAX_KEY_LEN: usize = 64;
+/// Maximum length of [`Metadata`] value in bytes
+pub const METADATA_MAX_VALUE_LEN: usize = 256;
+
+/// Checks [`Metadata`] limits
+#[deprecated(since = "0.1.0", note = "use `archk::v1::validate::metadata`")]
+pub fn validate_metadata(meta: &Metadata) -> Result<(), String> {
+    super::validate::metadata(meta).map_err(|err| err.to_string())
+}
+
+/// Checks [`Metadata`] key
+#[deprecated(since = "0.1.0", note = "use `archk::v1::validate::metadata_key`")]
+pub fn is_valid_metadata_key(key: &str) -> bool {
+    super::validate::metadata_key(key).is_ok()
 }
 
 /// Represents account in space
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug, Documentation)]
 pub struct SpaceAccount {
     /// Account unique ID given by platform.
     /// ID unique only in current space.
@@ -42,6 +93,24 @@ pub struct SpaceAccount {
     pub pl_name: Option<String>,
     /// Display name given by platform
     pub pl_displayname: Option<String>,
+    /// Is account active? Deactivated accounts keep their items and logs
+    /// but can't unlock space
+    pub active: bool,
+    /// Custom key/value data
+    pub metadata: Metadata,
+    /// Record version, incremented on every change
+    pub version: i64,
+}
+
+/// Represents tag (category) of items in space
+#[derive(Serialize, Deserialize, Clone, Debug, Documentation)]
+pub struct SpaceTag {
+    /// Tag ID
+    pub id: SpaceTagID,
+    /// Tag title, unique in space
+    pub title: String,
+    /// Space ID
+    pub space_id: SpaceID,
 }
 
 impl_try_from_enum!(
@@ -77,6 +146,9 @@ impl SpaceItemTy {
         }
     }
 }
+// On serialization SpaceItemTy is actually integer
+impl_documentation!(SpaceItemTy as i64);
+
 impl std::fmt::Display for SpaceItemTy {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -87,7 +159,7 @@ impl std::fmt::Display for SpaceItemTy {
 }
 
 /// Represents item in space
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug, Documentation)]
 pub struct SpaceItem {
     /// Global item ID in all spaces
     pub id: SpaceItemID,
@@ -103,19 +175,233 @@ pub struct SpaceItem {
     pub owner_id: Option<String>,
     /// Space ID of item and it's owner
     pub space_id: SpaceID,
+
+    /// Platform ID of account currently holding (took) item, if any
+    pub current_holder: Option<String>,
+    /// Timestamp in milliseconds when item should be returned, if any
+    pub due_at: Option<i64>,
+    /// Custom key/value data
+    pub metadata: Metadata,
+    /// Item status, see [`ItemStatus`]
+    pub status: String,
+    /// Record version, incremented on every change
+    pub version: i64,
+}
+
+/// Status of new items in spaces with [default](ItemStatus::defaults) statuses
+pub const DEFAULT_ITEM_STATUS: &str = "available";
+
+/// Item status (condition) configured per space, eg. `available`, `maintenance` or
+/// `lost`. Statuses are ordered, new items get first status of space.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Documentation)]
+pub struct ItemStatus {
+    /// Status name, unique in space
+    pub name: String,
+    /// Names of statuses item may be changed to from this one
+    #[serde(default)]
+    pub next: Vec<String>,
+    /// May item in this status be taken
+    #[serde(default)]
+    pub takeable: bool,
+}
+
+impl ItemStatus {
+    fn new(name: &str, next: &[&str], takeable: bool) -> Self {
+        Self {
+            name: name.into(),
+            next: next.iter().map(|&v| v.into()).collect(),
+            takeable,
+        }
+    }
+
+    /// Statuses of spaces which do not configure own ones: `available` (takeable),
+    /// `maintenance` and `lost`.
+    ///
+    /// # Example
+    /// ```
+    /// use archk::v1::space::{ItemStatus, DEFAULT_ITEM_STATUS};
+    ///
+    /// let statuses = ItemStatus::defaults();
+    /// assert_eq!(statuses[0].name, DEFAULT_ITEM_STATUS);
+    /// assert!(statuses[0].allows("lost"));
+    /// assert!(!statuses[2].takeable);
+    /// ```
+    pub fn defaults() -> Vec<Self> {
+        vec![
+            Self::new(DEFAULT_ITEM_STATUS, &["maintenance", "lost"], true),
+            Self::new("maintenance", &[DEFAULT_ITEM_STATUS, "lost"], false),
+            Self::new("lost", &[DEFAULT_ITEM_STATUS], false),
+        ]
+    }
+
+    /// Is transition from this status to `to` allowed?
+    pub fn allows(&self, to: &str) -> bool {
+        self.next.iter().any(|v| v == to)
+    }
 }
 
 impl_try_from_enum!(
     /// Action from space logs
-    #[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+    #[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
     #[serde(into = "i64", try_from = "i64")]
     pub enum SpaceLogAction : repr(i64) {
         KeycardScanned = 100,
         ItemTaken = 200,
         ItemReturned = 300,
+
+        /// Service asks space owner to register new item. Item data stored in `detail`
+        ItemRegistrationRequested = 400,
+
+        /// Someone asked to unlock space
+        UnlockRequested = 500,
+        /// Unlock request approved. Refers to [`SpaceLogAction::UnlockRequested`] entry
+        UnlockApproved = 501,
+        /// Unlock request denied. Refers to [`SpaceLogAction::UnlockRequested`] entry
+        UnlockDenied = 502,
+
+        /// Report (eg. broken item) filed
+        ReportFiled = 600,
+        /// Report resolved. Refers to [`SpaceLogAction::ReportFiled`] entry
+        ReportResolved = 601,
+
+        /// Account added to unlock allow or deny list of space, or removed from them.
+        /// `detail` is new [`AccountAccess`] code (or `default`) and reason if any,
+        /// eg. `deny: lost keycard`
+        AccountAccessChanged = 700,
+
+        /// Item status changed, see [`ItemStatus`]. `detail` is old and new status
+        /// with reason if any, eg. `available -> lost: left in bus`
+        ItemStatusChanged = 800,
     }
 );
 
+// On serialization SpaceLogAction is actually integer
+impl_documentation!(SpaceLogAction as i64);
+
+impl SpaceLogAction {
+    /// Action name as used in API responses, see [`LogAction`].
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::KeycardScanned => "keycard_scanned",
+            Self::ItemTaken => "item_taken",
+            Self::ItemReturned => "item_returned",
+            Self::ItemRegistrationRequested => "item_registration_requested",
+            Self::UnlockRequested => "unlock_requested",
+            Self::UnlockApproved => "unlock_approved",
+            Self::UnlockDenied => "unlock_denied",
+            Self::ReportFiled => "report_filed",
+            Self::ReportResolved => "report_resolved",
+            Self::AccountAccessChanged => "account_access_changed",
+            Self::ItemStatusChanged => "item_status_changed",
+        }
+    }
+}
+
+/// Action of log entry in API responses, serialized as numeric code with name, eg.
+/// `{"code": 501, "name": "unlock_approved"}`. Bare numeric code is accepted too.
+///
+/// Actions added in newer versions deserialize into [`LogAction::Unknown`] instead of
+/// failing, so old clients keep working.
+///
+/// # Example
+/// ```
+/// use archk::v1::space::{LogAction, SpaceLogAction};
+///
+/// let act: LogAction = serde_json::from_str(r#"{"code": 501, "name": "unlock_approved"}"#).unwrap();
+/// assert_eq!(act, LogAction::Known(SpaceLogAction::UnlockApproved));
+///
+/// let act: LogAction = serde_json::from_str(r#"{"code": 9001, "name": "future"}"#).unwrap();
+/// assert_eq!(act.code(), 9001);
+/// assert_eq!(act.name(), "future");
+/// ```
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Debug)]
+#[serde(into = "LogActionObject", from = "LogActionRepr")]
+pub enum LogAction {
+    Known(SpaceLogAction),
+    Unknown { code: i64, name: String },
+}
+
+impl LogAction {
+    pub fn code(&self) -> i64 {
+        match self {
+            Self::Known(v) => (*v).into(),
+            Self::Unknown { code, .. } => *code,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        match self {
+            Self::Known(v) => v.name(),
+            Self::Unknown { name, .. } => name,
+        }
+    }
+}
+
+impl From<SpaceLogAction> for LogAction {
+    fn from(v: SpaceLogAction) -> Self {
+        Self::Known(v)
+    }
+}
+
+impl From<i64> for LogAction {
+    fn from(code: i64) -> Self {
+        match SpaceLogAction::try_from(code) {
+            Ok(v) => Self::Known(v),
+            Err(_) => Self::Unknown {
+                code,
+                name: "unknown".into(),
+            },
+        }
+    }
+}
+
+impl docs::Documentation for LogAction {
+    const DOCUMENTATION_OBJECT: docs::DocumentationObject = docs::DocumentationObject {
+        name: "LogAction",
+        ..LogActionObject::DOCUMENTATION_OBJECT
+    };
+}
+
+/// Serialized [`LogAction`]
+#[derive(Serialize, Deserialize, Documentation)]
+struct LogActionObject {
+    /// Action code, see `archk::v1::space::SpaceLogAction`
+    code: i64,
+    /// Action name, eg. `unlock_approved`
+    name: String,
+}
+
+/// Deserialized [`LogAction`]: object or bare code
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum LogActionRepr {
+    Object(LogActionObject),
+    Code(i64),
+}
+
+impl From<LogAction> for LogActionObject {
+    fn from(v: LogAction) -> Self {
+        Self {
+            code: v.code(),
+            name: v.name().into(),
+        }
+    }
+}
+
+impl From<LogActionRepr> for LogAction {
+    fn from(v: LogActionRepr) -> Self {
+        match v {
+            LogActionRepr::Code(code) => code.into(),
+            LogActionRepr::Object(LogActionObject { code, name }) => {
+                match SpaceLogAction::try_from(code) {
+                    Ok(v) => Self::Known(v),
+                    Err(_) => Self::Unknown { code, name },
+                }
+            }
+        }
+    }
+}
+
 /// Space log entry.
 ///
 /// # Example
@@ -145,6 +431,11 @@ pub struct SpaceLog {
     pub sp_acc_id: Option<String>,
     /// Item ID if any
     pub sp_item_id: Option<SpaceItemID>,
+
+    /// ID of log entry this entry refers to (eg. unlock request of approval) if any
+    pub ref_id: Option<String>,
+    /// Free-form details (eg. reason of denial) if any
+    pub detail: Option<String>,
 }
 
 impl SpaceLog {
@@ -160,6 +451,8 @@ impl SpaceLog {
             act,
             sp_acc_id: None,
             sp_item_id: None,
+            ref_id: None,
+            detail: None,
         }
     }
 
@@ -174,4 +467,495 @@ impl SpaceLog {
         self.sp_item_id = Some(sp_item_id);
         self
     }
+
+    /// Assigns `ref_id`. See [`SpaceLog`] docs for more
+    pub fn with_ref(mut self, ref_id: String) -> Self {
+        self.ref_id = Some(ref_id);
+        self
+    }
+
+    /// Assigns `detail`. See [`SpaceLog`] docs for more
+    pub fn with_detail(mut self, detail: String) -> Self {
+        self.detail = Some(detail);
+        self
+    }
+}
+
+/// Storage of [`SpaceLog`] entries (eg. database of API server), used to flush events
+/// buffered by actor devices at once.
+pub trait SpaceLogStore {
+    /// Error of underlying storage
+    type Error;
+
+    /// Inserts all entries or none of them. Returns number of inserted entries
+    fn insert_many(
+        &mut self,
+        logs: &[SpaceLog],
+    ) -> impl std::future::Future<Output = Result<u64, Self::Error>> + Send;
+
+    /// Inserts one entry, same as [`SpaceLogStore::insert_many`] with one entry
+    fn insert(
+        &mut self,
+        log: &SpaceLog,
+    ) -> impl std::future::Future<Output = Result<u64, Self::Error>> + Send {
+        self.insert_many(std::slice::from_ref(log))
+    }
+}
+
+/// Per-space unlock policy used by [`UnlockPolicy::decide`].
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Documentation)]
+pub struct UnlockPolicy {
+    /// Require account to own at least one keycard item
+    pub require_keycard: bool,
+    /// Deny unlock while items owned or held by account have unresolved reports
+    pub deny_on_open_reports: bool,
+    /// Leave requests failing keycard or open reports checks pending, so `SpaceManager`
+    /// service decides them instead of denying
+    #[serde(default)]
+    pub ask_manager: bool,
+}
+
+impl Default for UnlockPolicy {
+    fn default() -> Self {
+        Self {
+            require_keycard: true,
+            deny_on_open_reports: false,
+            ask_manager: false,
+        }
+    }
+}
+
+/// Explicit unlock access of account, overrides [`UnlockPolicy`] checks.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AccountAccess {
+    /// Account always allowed to unlock space
+    Allow,
+    /// Account never allowed to unlock space
+    Deny,
+}
+
+impl AccountAccess {
+    /// Access code as used in serialization and log details.
+    pub fn code(self) -> &'static str {
+        match self {
+            Self::Allow => "allow",
+            Self::Deny => "deny",
+        }
+    }
+}
+
+impl_documentation!(AccountAccess as String);
+
+impl_try_from_enum!(
+    /// Day of week
+    #[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+    #[serde(rename_all = "lowercase")]
+    pub enum Weekday : repr(u8) {
+        Mon = 0,
+        Tue = 1,
+        Wed = 2,
+        Thu = 3,
+        Fri = 4,
+        Sat = 5,
+        Sun = 6,
+    }
+);
+
+impl Weekday {
+    /// All days of week starting from Monday
+    pub const ALL: [Self; 7] = [
+        Self::Mon,
+        Self::Tue,
+        Self::Wed,
+        Self::Thu,
+        Self::Fri,
+        Self::Sat,
+        Self::Sun,
+    ];
+
+    /// Day before this one.
+    pub fn pred(self) -> Self {
+        Self::ALL[(self as usize + 6) % 7]
+    }
+}
+
+impl_documentation!(Weekday as String);
+
+/// Time of day with minute precision, serialized as `HH:MM`.
+///
+/// # Example
+/// ```
+/// use archk::v1::space::TimeOfDay;
+///
+/// let time = TimeOfDay::try_from("08:30".to_string()).unwrap();
+/// assert_eq!(time.minutes(), 8 * 60 + 30);
+/// assert_eq!(String::from(time), "08:30");
+/// assert!(TimeOfDay::try_from("24:00".to_string()).is_err());
+/// ```
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(into = "String", try_from = "String")]
+pub struct TimeOfDay(u16);
+
+impl TimeOfDay {
+    /// Returns `None` if `minutes` is not less than 24 hours.
+    pub fn from_minutes(minutes: u16) -> Option<Self> {
+        (minutes < 24 * 60).then_some(Self(minutes))
+    }
+
+    /// Minutes since midnight
+    pub fn minutes(self) -> u16 {
+        self.0
+    }
+}
+
+impl From<TimeOfDay> for String {
+    fn from(v: TimeOfDay) -> String {
+        format!("{:02}:{:02}", v.0 / 60, v.0 % 60)
+    }
+}
+
+impl TryFrom<String> for TimeOfDay {
+    type Error = super::errors::InvalidTimeOfDay;
+
+    fn try_from(v: String) -> Result<Self, Self::Error> {
+        let err = || super::errors::InvalidTimeOfDay(());
+        let (hours, minutes) = v.split_once(':').ok_or_else(err)?;
+        if hours.len() != 2 || minutes.len() != 2 {
+            return Err(err());
+        }
+        let hours: u16 = hours.parse().map_err(|_| err())?;
+        let minutes: u16 = minutes.parse().map_err(|_| err())?;
+        if minutes >= 60 {
+            return Err(err());
+        }
+        Self::from_minutes(hours * 60 + minutes).ok_or_else(err)
+    }
+}
+
+impl_documentation!(TimeOfDay as String);
+
+/// Weekly recurring time window when accounts may unlock space, eg. weekdays from
+/// `08:00` to `20:00`.
+///
+/// Window ending before it starts spans midnight and `days` are days it starts on.
+/// Window with same start and end lasts whole day.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Documentation)]
+pub struct AccessWindow {
+    /// Days of week window starts on
+    pub days: Vec<Weekday>,
+    /// Local start time, eg. `08:00`
+    pub start: TimeOfDay,
+    /// Local end time (exclusive), eg. `20:00`
+    pub end: TimeOfDay,
+    /// IANA name of timezone of `start` and `end`, eg. `Europe/Berlin`
+    pub timezone: String,
+}
+
+impl AccessWindow {
+    /// Is local `time` of `day` within window? Converting to timezone of window is
+    /// up to caller.
+    ///
+    /// # Example
+    /// ```
+    /// use archk::v1::space::{AccessWindow, TimeOfDay, Weekday};
+    ///
+    /// let time = |v: &str| TimeOfDay::try_from(v.to_string()).unwrap();
+    /// let night = AccessWindow {
+    ///     days: vec![Weekday::Fri],
+    ///     start: time("22:00"),
+    ///     end: time("06:00"),
+    ///     timezone: "UTC".to_string(),
+    /// };
+    /// assert!(night.contains(Weekday::Fri, time("23:00")));
+    /// assert!(night.contains(Weekday::Sat, time("05:59")));
+    /// assert!(!night.contains(Weekday::Fri, time("05:00")));
+    /// ```
+    pub fn contains(&self, day: Weekday, time: TimeOfDay) -> bool {
+        let starts_on = |day| self.days.contains(&day);
+        match self.start.cmp(&self.end) {
+            std::cmp::Ordering::Equal => starts_on(day),
+            std::cmp::Ordering::Less => starts_on(day) && self.start <= time && time < self.end,
+            std::cmp::Ordering::Greater => {
+                (starts_on(day) && self.start <= time) || (starts_on(day.pred()) && time < self.end)
+            }
+        }
+    }
+}
+
+/// Facts about account collected before unlock decision.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct UnlockFacts {
+    /// Is account exists in space?
+    pub account_exists: bool,
+    /// Is account in allow or deny list of space?
+    pub access: Option<AccountAccess>,
+    /// Does account (or space, if account has none) have access windows and none of
+    /// them contains current time?
+    pub outside_access_windows: bool,
+    /// Number of keycard items owned by account
+    pub keycards: u64,
+    /// Number of unresolved reports about items owned or held by account. Reports filed
+    /// by account itself are not counted
+    pub open_reports: u64,
+}
+
+/// Reason of unlock decision.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum UnlockReason {
+    /// Account owns keycard
+    Keycard,
+    /// Policy does not require keycard
+    Policy,
+    /// Account does not exists in space
+    UnknownAccount,
+    /// Account does not own any keycard
+    NoKeycard,
+    /// Account has unresolved reports
+    OpenReports,
+    /// Account is in allow list of space
+    AllowList,
+    /// Account is in deny list of space
+    DenyList,
+    /// Current time is outside of access windows
+    OutsideAccessWindow,
+}
+
+impl UnlockReason {
+    /// Reason code as used in serialization and log details.
+    pub fn code(self) -> &'static str {
+        match self {
+            Self::Keycard => "keycard",
+            Self::Policy => "policy",
+            Self::UnknownAccount => "unknown_account",
+            Self::NoKeycard => "no_keycard",
+            Self::OpenReports => "open_reports",
+            Self::AllowList => "allow_list",
+            Self::DenyList => "deny_list",
+            Self::OutsideAccessWindow => "outside_access_window",
+        }
+    }
+}
+
+impl_documentation!(UnlockReason as String);
+
+/// Unlock decision.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum UnlockDecision {
+    Allow,
+    Deny,
+    /// Request is left for `SpaceManager` service, see [`UnlockPolicy::ask_manager`]
+    Pending,
+}
+
+impl_documentation!(UnlockDecision as String);
+
+impl UnlockPolicy {
+    /// Decide whether account can unlock space. Allow and deny lists are consulted
+    /// before other checks, but unknown accounts are always denied. Access windows
+    /// are checked next. Only keycard and open reports checks are left pending if
+    /// [`UnlockPolicy::ask_manager`] is set.
+    ///
+    /// # Example
+    /// ```
+    /// use archk::v1::space::{UnlockDecision, UnlockFacts, UnlockPolicy, UnlockReason};
+    ///
+    /// let policy = UnlockPolicy::default();
+    /// let facts = UnlockFacts {
+    ///     account_exists: true,
+    ///     access: None,
+    ///     outside_access_windows: false,
+    ///     keycards: 1,
+    ///     open_reports: 0,
+    /// };
+    /// assert_eq!(policy.decide(&facts), (UnlockDecision::Allow, UnlockReason::Keycard));
+    /// ```
+    pub fn decide(&self, facts: &UnlockFacts) -> (UnlockDecision, UnlockReason) {
+        if !facts.account_exists {
+            return (UnlockDecision::Deny, UnlockReason::UnknownAccount);
+        }
+        match facts.access {
+            Some(AccountAccess::Deny) => return (UnlockDecision::Deny, UnlockReason::DenyList),
+            Some(AccountAccess::Allow) => return (UnlockDecision::Allow, UnlockReason::AllowList),
+            None => (),
+        }
+        if facts.outside_access_windows {
+            return (UnlockDecision::Deny, UnlockReason::OutsideAccessWindow);
+        }
+        let deny = match self.ask_manager {
+            true => UnlockDecision::Pending,
+            false => UnlockDecision::Deny,
+        };
+        if self.deny_on_open_reports && facts.open_reports > 0 {
+            return (deny, UnlockReason::OpenReports);
+        }
+        match (self.require_keycard, facts.keycards) {
+            (true, 0) => (deny, UnlockReason::NoKeycard),
+            (true, _) => (UnlockDecision::Allow, UnlockReason::Keycard),
+            (false, _) => (UnlockDecision::Allow, UnlockReason::Policy),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ids_are_validated() {
+        let id = SpaceItemID::new();
+        assert_eq!(id.parse::<SpaceItemID>().ok(), Some(id.clone()));
+        assert!("x".parse::<SpaceTagID>().is_err());
+
+        let json = serde_json::to_string(&id).unwrap();
+        assert_eq!(serde_json::from_str::<SpaceItemID>(&json).ok(), Some(id));
+        assert!(serde_json::from_str::<SpaceItemID>(r#""not a cuid!""#).is_err());
+    }
+
+    #[test]
+    fn unlock_policy_decisions() {
+        let facts = |account_exists, keycards, open_reports| UnlockFacts {
+            account_exists,
+            access: None,
+            outside_access_windows: false,
+            keycards,
+            open_reports,
+        };
+        let listed = |access| UnlockFacts {
+            access: Some(access),
+            ..facts(true, 0, 1)
+        };
+        let strict = UnlockPolicy {
+            require_keycard: true,
+            deny_on_open_reports: true,
+            ask_manager: false,
+        };
+        let open = UnlockPolicy {
+            require_keycard: false,
+            deny_on_open_reports: false,
+            ask_manager: false,
+        };
+        let managed = UnlockPolicy {
+            ask_manager: true,
+            ..strict
+        };
+
+        let cases = [
+            (
+                strict,
+                facts(false, 1, 0),
+                UnlockDecision::Deny,
+                UnlockReason::UnknownAccount,
+            ),
+            (
+                strict,
+                facts(true, 1, 1),
+                UnlockDecision::Deny,
+                UnlockReason::OpenReports,
+            ),
+            (
+                strict,
+                facts(true, 0, 0),
+                UnlockDecision::Deny,
+                UnlockReason::NoKeycard,
+            ),
+            (
+                strict,
+                facts(true, 2, 0),
+                UnlockDecision::Allow,
+                UnlockReason::Keycard,
+            ),
+            (
+                open,
+                facts(false, 0, 0),
+                UnlockDecision::Deny,
+                UnlockReason::UnknownAccount,
+            ),
+            (
+                open,
+                facts(true, 0, 3),
+                UnlockDecision::Allow,
+                UnlockReason::Policy,
+            ),
+            (
+                strict,
+                listed(AccountAccess::Allow),
+                UnlockDecision::Allow,
+                UnlockReason::AllowList,
+            ),
+            (
+                open,
+                listed(AccountAccess::Deny),
+                UnlockDecision::Deny,
+                UnlockReason::DenyList,
+            ),
+            (
+                strict,
+                UnlockFacts {
+                    account_exists: false,
+                    ..listed(AccountAccess::Allow)
+                },
+                UnlockDecision::Deny,
+                UnlockReason::UnknownAccount,
+            ),
+            (
+                open,
+                UnlockFacts {
+                    outside_access_windows: true,
+                    ..facts(true, 1, 0)
+                },
+                UnlockDecision::Deny,
+                UnlockReason::OutsideAccessWindow,
+            ),
+            (
+                open,
+                UnlockFacts {
+                    outside_access_windows: true,
+                    ..listed(AccountAccess::Allow)
+                },
+                UnlockDecision::Allow,
+                UnlockReason::AllowList,
+            ),
+            (
+                managed,
+                facts(true, 0, 0),
+                UnlockDecision::Pending,
+                UnlockReason::NoKeycard,
+            ),
+            (
+                managed,
+                facts(true, 1, 1),
+                UnlockDecision::Pending,
+                UnlockReason::OpenReports,
+            ),
+            (
+                managed,
+                facts(true, 1, 0),
+                UnlockDecision::Allow,
+                UnlockReason::Keycard,
+            ),
+            (
+                managed,
+                facts(false, 1, 0),
+                UnlockDecision::Deny,
+                UnlockReason::UnknownAccount,
+            ),
+            (
+                managed,
+                listed(AccountAccess::Deny),
+                UnlockDecision::Deny,
+                UnlockReason::DenyList,
+            ),
+        ];
+
+        for (policy, facts, decision, reason) in cases {
+            assert_eq!(
+                policy.decide(&facts),
+                (decision, reason),
+                "{policy:?} {facts:?}"
+            );
+        }
+    }
 }
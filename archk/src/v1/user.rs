@@ -1,14 +1,13 @@
 use std::time::{SystemTime, UNIX_EPOCH};
 
 use documentation_macro::Documentation;
-use once_cell::sync::Lazy;
-use regex::Regex;
 use serde::{Deserialize, Serialize};
 
 use super::{docs::impl_documentation, macros::impl_cuid};
 
 /// Represents ID of user (CUID)
 #[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Debug)]
+#[serde(into = "String", try_from = "String")]
 #[repr(transparent)]
 pub struct UserID(String);
 impl_cuid!(UserID);
@@ -27,6 +26,7 @@ pub struct User {
 }
 /// Represents ID of telegram user authorization request (CUID)
 #[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Debug)]
+#[serde(into = "String", try_from = "String")]
 #[repr(transparent)]
 pub struct UserTelegramAuthID(String);
 impl_cuid!(UserTelegramAuthID);
@@ -40,7 +40,7 @@ pub struct UserTelegramAuth {
 
 impl UserTelegramAuth {
     /// Max wait time of request
-    const WAIT_TIME_MS: u64 = 1000 * 60 * 10; // 10 min
+    pub const WAIT_TIME_MS: u64 = 1000 * 60 * 10; // 10 min
 
     /// Generate new code
     pub fn new(user_id: UserID) -> Self {
@@ -65,23 +65,9 @@ impl UserTelegramAuth {
 }
 
 /// Check is username valid
-///
-/// # Examples
-/// ```
-/// use archk::v1::user::is_valid_username;
-///
-/// assert!(is_valid_username("greg")); // a valid username
-/// assert!(is_valid_username("greg.b42")); // also valid username
-///
-/// assert!(!is_valid_username("gr")); // too small
-/// assert!(!is_valid_username("aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa")); // too long (>31 symbols)
-/// assert!(!is_valid_username("he-llo world")); // incorrect chars
-/// ```
+#[deprecated(since = "0.1.0", note = "use `archk::v1::validate::username`")]
 pub fn is_valid_username(v: &str) -> bool {
-    static RE: Lazy<Regex> =
-        Lazy::new(|| Regex::new(r"^[a-zA-Z0-9\.]{3,31}$").expect("regex user::is_valid_username"));
-
-    RE.is_match(v)
+    super::validate::username(v).is_ok()
 }
 
 #[cfg(feature = "ssh")]
@@ -97,6 +83,7 @@ pub mod ssh {
 
     /// Represents ID of user ssh key (CUID)
     #[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Debug)]
+    #[serde(into = "String", try_from = "String")]
     #[repr(transparent)]
     pub struct UserSSHKeyID(String);
     impl_cuid!(UserSSHKeyID);
@@ -0,0 +1,582 @@
+//! Validation of user input.
+//!
+//! Used by server handlers, clients may use it to validate data before sending.
+//! Every error converts into [`api::ErrorData`] with [`api::Error::MalformedData`] code
+//! and human readable detail.
+//!
+//! # Example
+//! ```
+//! use archk::v1::{api, validate};
+//!
+//! assert!(validate::username("greg.b42").is_ok());
+//!
+//! let err: api::ErrorData = validate::password("short").unwrap_err().into();
+//! assert_eq!(err.code, api::Error::MalformedData);
+//! ```
+//!
+//! Request bodies may derive [`Validate`] by [`archk::Validate`] macro (required `derive`
+//! feature) instead of calling checks by hand. Bounds of `#[doc_min]` and `#[doc_max]` are
+//! checked (so documented limits are enforced ones) together with functions of
+//! `#[validate(with = ...)]`. Wrap body into [`Valid`] to validate it on deserialization:
+//!
+//! ```ignore
+//! use archk::v1::validate::{self, Valid, Validate};
+//! use serde::Deserialize;
+//!
+//! #[derive(Deserialize, archk::Validate)]
+//! struct Body {
+//!     #[validate(with = validate::title)]
+//!     title: String,
+//!     #[doc_min = 1]
+//!     #[doc_max = 8]
+//!     tags: Vec<String>,
+//! }
+//!
+//! let err = serde_json::from_str::<Valid<Body>>(r#"{"title": " ", "tags": ["a"]}"#);
+//! assert!(err.unwrap_err().to_string().contains("`title`"));
+//!
+//! let body = Body { title: "Room".into(), tags: Vec::new() };
+//! assert_eq!(body.validate().unwrap_err().field, "tags");
+//! ```
+
+use std::ops::Deref;
+
+use serde::{Deserialize, Deserializer};
+
+use super::{
+    api, docs,
+    models::MayIgnored,
+    space::{
+        ItemStatus, Metadata, METADATA_MAX_KEYS, METADATA_MAX_KEY_LEN, METADATA_MAX_VALUE_LEN,
+    },
+};
+
+/// Minimum length of username in characters
+pub const USERNAME_MIN_LEN: usize = 3;
+/// Maximum length of username in characters
+pub const USERNAME_MAX_LEN: usize = 31;
+/// Minimum length of password in bytes
+pub const PASSWORD_MIN_LEN: usize = 8;
+/// Maximum length of password in bytes
+pub const PASSWORD_MAX_LEN: usize = 32;
+/// Maximum length of space, item or tag title in characters
+pub const TITLE_MAX_LEN: usize = 128;
+/// Maximum length of item `pl_serial` in bytes
+pub const PL_SERIAL_MAX_LEN: usize = 64;
+/// Maximum length of description in characters
+pub const DESCRIPTION_MAX_LEN: usize = 1024;
+/// Maximum length of account `pl_id` in characters
+pub const PL_ID_MAX_LEN: usize = 128;
+/// Maximum number of item statuses in space
+pub const ITEM_STATUSES_MAX: usize = 16;
+/// Maximum length of item status name in bytes
+pub const ITEM_STATUS_MAX_LEN: usize = 32;
+
+macro_rules! impl_validation_error {
+    ($($ty:ident)+) => {
+        $(
+            impl std::error::Error for $ty {}
+
+            impl From<$ty> for api::ErrorData {
+                fn from(value: $ty) -> Self {
+                    api::Error::MalformedData.detail(value.to_string().into())
+                }
+            }
+        )+
+    };
+}
+
+/// Error of [`username`]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum UsernameError {
+    /// Username is shorter than [`USERNAME_MIN_LEN`] or longer than [`USERNAME_MAX_LEN`]
+    Length,
+    /// Username contains characters other than ASCII letters, digits and dots
+    Characters,
+}
+
+impl std::fmt::Display for UsernameError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Length => write!(
+                f,
+                "username should be {USERNAME_MIN_LEN} to {USERNAME_MAX_LEN} characters long"
+            ),
+            Self::Characters => write!(
+                f,
+                "username should contain only ASCII letters, digits and dots"
+            ),
+        }
+    }
+}
+
+/// Checks username.
+///
+/// # Example
+/// ```
+/// use archk::v1::validate::{username, UsernameError};
+///
+/// assert!(username("greg").is_ok()); // a valid username
+/// assert!(username("greg.b42").is_ok()); // also valid username
+///
+/// assert_eq!(username("gr"), Err(UsernameError::Length)); // too small
+/// assert_eq!(username("aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa"), Err(UsernameError::Length)); // too long (>31 symbols)
+/// assert_eq!(username("he-llo world"), Err(UsernameError::Characters)); // incorrect chars
+/// ```
+pub fn username(v: &str) -> Result<(), UsernameError> {
+    if !v.bytes().all(|c| c.is_ascii_alphanumeric() || c == b'.') {
+        return Err(UsernameError::Characters);
+    }
+    if !(USERNAME_MIN_LEN..=USERNAME_MAX_LEN).contains(&v.len()) {
+        return Err(UsernameError::Length);
+    }
+    Ok(())
+}
+
+/// Error of [`password`]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PasswordError {
+    /// Password is shorter than [`PASSWORD_MIN_LEN`] or longer than [`PASSWORD_MAX_LEN`] bytes
+    Length,
+}
+
+impl std::fmt::Display for PasswordError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Length => write!(
+                f,
+                "password should be {PASSWORD_MIN_LEN} to {PASSWORD_MAX_LEN} bytes long"
+            ),
+        }
+    }
+}
+
+/// Checks plain password.
+pub fn password(v: &str) -> Result<(), PasswordError> {
+    if !(PASSWORD_MIN_LEN..=PASSWORD_MAX_LEN).contains(&v.len()) {
+        return Err(PasswordError::Length);
+    }
+    Ok(())
+}
+
+/// Error of [`title`]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum TitleError {
+    /// Title is empty or contains only whitespaces
+    Empty,
+    /// Title is longer than [`TITLE_MAX_LEN`] characters
+    TooLong,
+}
+
+impl std::fmt::Display for TitleError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Empty => write!(f, "title should not be empty"),
+            Self::TooLong => write!(f, "title is longer than {TITLE_MAX_LEN} characters"),
+        }
+    }
+}
+
+/// Checks title of space, item or tag.
+///
+/// # Example
+/// ```
+/// use archk::v1::validate::{title, TitleError};
+///
+/// assert!(title("Room 301").is_ok());
+/// assert_eq!(title("  "), Err(TitleError::Empty));
+/// ```
+pub fn title(v: &str) -> Result<(), TitleError> {
+    if v.trim().is_empty() {
+        return Err(TitleError::Empty);
+    }
+    if v.chars().count() > TITLE_MAX_LEN {
+        return Err(TitleError::TooLong);
+    }
+    Ok(())
+}
+
+/// Error of [`pl_serial`]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SerialError {
+    /// Serial is empty
+    Empty,
+    /// Serial is longer than [`PL_SERIAL_MAX_LEN`] bytes
+    TooLong,
+    /// Serial contains whitespace or control characters
+    Characters,
+}
+
+impl std::fmt::Display for SerialError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Empty => write!(f, "`pl_serial` should not be empty"),
+            Self::TooLong => write!(f, "`pl_serial` is longer than {PL_SERIAL_MAX_LEN} bytes"),
+            Self::Characters => write!(
+                f,
+                "`pl_serial` should not contain whitespace or control characters"
+            ),
+        }
+    }
+}
+
+/// Checks serial ID of item given by platform (`pl_serial`).
+///
+/// # Example
+/// ```
+/// use archk::v1::validate::{pl_serial, SerialError};
+///
+/// assert!(pl_serial("KEY-0042").is_ok());
+/// assert_eq!(pl_serial("KEY 0042"), Err(SerialError::Characters));
+/// ```
+pub fn pl_serial(v: &str) -> Result<(), SerialError> {
+    if v.is_empty() {
+        return Err(SerialError::Empty);
+    }
+    if v.len() > PL_SERIAL_MAX_LEN {
+        return Err(SerialError::TooLong);
+    }
+    if v.chars().any(|c| c.is_whitespace() || c.is_control()) {
+        return Err(SerialError::Characters);
+    }
+    Ok(())
+}
+
+/// Error of [`description`]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum DescriptionError {
+    /// Description is longer than [`DESCRIPTION_MAX_LEN`] characters
+    TooLong,
+}
+
+impl std::fmt::Display for DescriptionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::TooLong => write!(
+                f,
+                "description is longer than {DESCRIPTION_MAX_LEN} characters"
+            ),
+        }
+    }
+}
+
+/// Checks free-form description, eg. of service. Description may be empty.
+pub fn description(v: &str) -> Result<(), DescriptionError> {
+    if v.chars().count() > DESCRIPTION_MAX_LEN {
+        return Err(DescriptionError::TooLong);
+    }
+    Ok(())
+}
+
+/// Error of [`metadata`] and [`metadata_key`]
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum MetadataError {
+    /// More than [`METADATA_MAX_KEYS`] keys, contains number of keys
+    TooManyKeys(usize),
+    /// Key is invalid, see [`metadata_key`]
+    InvalidKey(String),
+    /// Value of key is longer than [`METADATA_MAX_VALUE_LEN`] bytes
+    ValueTooLong(String),
+}
+
+impl std::fmt::Display for MetadataError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::TooManyKeys(n) => {
+                write!(
+                    f,
+                    "expected at most {METADATA_MAX_KEYS} metadata keys, got {n}"
+                )
+            }
+            Self::InvalidKey(key) => write!(f, "invalid metadata key {key:?}"),
+            Self::ValueTooLong(key) => write!(
+                f,
+                "metadata value of {key:?} is longer than {METADATA_MAX_VALUE_LEN} bytes"
+            ),
+        }
+    }
+}
+
+/// Checks [`Metadata`] limits. Keys should be valid, see [`metadata_key`].
+///
+/// # Example
+/// ```
+/// use archk::v1::{space::Metadata, validate};
+///
+/// let mut meta = Metadata::new();
+/// meta.insert("room".into(), "301".into());
+/// assert!(validate::metadata(&meta).is_ok());
+///
+/// meta.insert("bad key".into(), "".into());
+/// assert!(validate::metadata(&meta).is_err());
+/// ```
+pub fn metadata(meta: &Metadata) -> Result<(), MetadataError> {
+    if meta.len() > METADATA_MAX_KEYS {
+        return Err(MetadataError::TooManyKeys(meta.len()));
+    }
+    for (key, value) in meta {
+        metadata_key(key)?;
+        if value.len() > METADATA_MAX_VALUE_LEN {
+            return Err(MetadataError::ValueTooLong(key.clone()));
+        }
+    }
+    Ok(())
+}
+
+/// Checks [`Metadata`] key. Keys should be non-empty, not longer than
+/// [`METADATA_MAX_KEY_LEN`] and contain only ASCII alphanumerics, `_` or `-`.
+pub fn metadata_key(key: &str) -> Result<(), MetadataError> {
+    let valid = !key.is_empty()
+        && key.len() <= METADATA_MAX_KEY_LEN
+        && key
+            .bytes()
+            .all(|c| c.is_ascii_alphanumeric() || c == b'_' || c == b'-');
+    if !valid {
+        return Err(MetadataError::InvalidKey(key.into()));
+    }
+    Ok(())
+}
+
+/// Error of [`pl_id`]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PlatformIDError {
+    /// ID contains `/`, whitespace or control characters
+    Characters,
+}
+
+impl std::fmt::Display for PlatformIDError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Characters => write!(
+                f,
+                "should not contain `/`, whitespace or control characters"
+            ),
+        }
+    }
+}
+
+/// Checks characters of account ID given by platform (`pl_id`), which is used in paths.
+/// Length is bounded by documentation of field, see [`PL_ID_MAX_LEN`].
+///
+/// # Example
+/// ```
+/// use archk::v1::validate::{pl_id, PlatformIDError};
+///
+/// assert!(pl_id("tg:123456").is_ok());
+/// assert_eq!(pl_id("a/b"), Err(PlatformIDError::Characters));
+/// ```
+pub fn pl_id(v: &str) -> Result<(), PlatformIDError> {
+    if v.chars()
+        .any(|c| c == '/' || c.is_whitespace() || c.is_control())
+    {
+        return Err(PlatformIDError::Characters);
+    }
+    Ok(())
+}
+
+/// Error of [`item_statuses`]
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum ItemStatusError {
+    /// No statuses
+    Empty,
+    /// More than [`ITEM_STATUSES_MAX`] statuses, contains number of statuses
+    TooMany(usize),
+    /// Name is invalid, see [`item_statuses`]
+    InvalidName(String),
+    /// Name is used by several statuses
+    Duplicate(String),
+    /// Status refers to unknown status in `next`, contains both names
+    UnknownNext(String, String),
+}
+
+impl std::fmt::Display for ItemStatusError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Empty => write!(f, "expected at least one item status"),
+            Self::TooMany(n) => {
+                write!(
+                    f,
+                    "expected at most {ITEM_STATUSES_MAX} item statuses, got {n}"
+                )
+            }
+            Self::InvalidName(name) => write!(f, "invalid item status name {name:?}"),
+            Self::Duplicate(name) => write!(f, "item status {name:?} is defined twice"),
+            Self::UnknownNext(name, next) => {
+                write!(f, "item status {name:?} refers to unknown status {next:?}")
+            }
+        }
+    }
+}
+
+/// Checks item statuses of space. There should be at least one status, names should be
+/// unique, not longer than [`ITEM_STATUS_MAX_LEN`] and contain only ASCII alphanumerics,
+/// `_` or `-`, and `next` should refer to existing statuses.
+///
+/// # Example
+/// ```
+/// use archk::v1::{space::ItemStatus, validate::{self, ItemStatusError}};
+///
+/// let mut statuses = ItemStatus::defaults();
+/// assert!(validate::item_statuses(&statuses).is_ok());
+///
+/// statuses[0].next.push("broken".into());
+/// assert_eq!(
+///     validate::item_statuses(&statuses),
+///     Err(ItemStatusError::UnknownNext("available".into(), "broken".into()))
+/// );
+/// ```
+pub fn item_statuses(statuses: &[ItemStatus]) -> Result<(), ItemStatusError> {
+    if statuses.is_empty() {
+        return Err(ItemStatusError::Empty);
+    }
+    if statuses.len() > ITEM_STATUSES_MAX {
+        return Err(ItemStatusError::TooMany(statuses.len()));
+    }
+    for (i, status) in statuses.iter().enumerate() {
+        let name = &status.name;
+        let valid = !name.is_empty()
+            && name.len() <= ITEM_STATUS_MAX_LEN
+            && name
+                .bytes()
+                .all(|c| c.is_ascii_alphanumeric() || c == b'_' || c == b'-');
+        if !valid {
+            return Err(ItemStatusError::InvalidName(name.clone()));
+        }
+        if statuses[..i].iter().any(|v| &v.name == name) {
+            return Err(ItemStatusError::Duplicate(name.clone()));
+        }
+    }
+    for status in statuses {
+        if let Some(next) = status
+            .next
+            .iter()
+            .find(|&next| !statuses.iter().any(|v| &v.name == next))
+        {
+            return Err(ItemStatusError::UnknownNext(
+                status.name.clone(),
+                next.clone(),
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Invalid field of [`Validate`] object.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct FieldError {
+    /// Name of field
+    pub field: &'static str,
+    /// What is wrong with value
+    pub detail: String,
+}
+
+impl FieldError {
+    pub fn new(field: &'static str, detail: impl std::fmt::Display) -> Self {
+        Self {
+            field,
+            detail: detail.to_string(),
+        }
+    }
+}
+
+impl std::fmt::Display for FieldError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "invalid `{}`: {}", self.field, self.detail)
+    }
+}
+
+/// Object with checked fields, usually derived by [`archk::Validate`].
+pub trait Validate {
+    /// Checks fields in order, returns first invalid one.
+    fn validate(&self) -> Result<(), FieldError>;
+}
+
+/// Value bounded by `#[doc_min]` and `#[doc_max]`: number itself or length of string
+/// (in characters) or array. Used by [`archk::Validate`] derive.
+pub trait Bounded {
+    fn check_bounds(&self, min: Option<i64>, max: Option<i64>) -> Result<(), String>;
+}
+
+fn check_bounds(v: i64, min: Option<i64>, max: Option<i64>, unit: &str) -> Result<(), String> {
+    match (min, max) {
+        (Some(min), Some(max)) if !(min..=max).contains(&v) => {
+            Err(format!("should be {min} to {max}{unit}"))
+        }
+        (Some(min), _) if v < min => Err(format!("should be at least {min}{unit}")),
+        (_, Some(max)) if v > max => Err(format!("should be at most {max}{unit}")),
+        _ => Ok(()),
+    }
+}
+
+impl Bounded for String {
+    fn check_bounds(&self, min: Option<i64>, max: Option<i64>) -> Result<(), String> {
+        let len = self.chars().count() as i64;
+        check_bounds(len, min, max, " characters long")
+    }
+}
+
+impl<T> Bounded for Vec<T> {
+    fn check_bounds(&self, min: Option<i64>, max: Option<i64>) -> Result<(), String> {
+        check_bounds(self.len() as i64, min, max, " items long")
+    }
+}
+
+impl<T: Bounded> Bounded for Option<T> {
+    fn check_bounds(&self, min: Option<i64>, max: Option<i64>) -> Result<(), String> {
+        match self {
+            Some(v) => v.check_bounds(min, max),
+            None => Ok(()),
+        }
+    }
+}
+
+impl<T: Bounded> Bounded for MayIgnored<T> {
+    fn check_bounds(&self, min: Option<i64>, max: Option<i64>) -> Result<(), String> {
+        match self {
+            MayIgnored::Value(v) => v.check_bounds(min, max),
+            MayIgnored::Ignored => Ok(()),
+        }
+    }
+}
+
+macro_rules! impl_bounded_number {
+    ($($ty:ident)+) => {
+        $(
+            impl Bounded for $ty {
+                fn check_bounds(&self, min: Option<i64>, max: Option<i64>) -> Result<(), String> {
+                    let v = i64::try_from(*self).unwrap_or(i64::MAX);
+                    check_bounds(v, min, max, "")
+                }
+            }
+        )+
+    };
+}
+
+impl_bounded_number!(i32 i64 u32 u64);
+
+/// Object validated on deserialization, so invalid field fails whole body as any other
+/// deserialization error.
+#[derive(Clone, Debug)]
+pub struct Valid<T>(pub T);
+
+impl<'de, T: Deserialize<'de> + Validate> Deserialize<'de> for Valid<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let v = T::deserialize(deserializer)?;
+        v.validate().map_err(serde::de::Error::custom)?;
+        Ok(Self(v))
+    }
+}
+
+impl<T> Deref for Valid<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T: docs::Documentation> docs::Documentation for Valid<T> {
+    const DOCUMENTATION_OBJECT: docs::DocumentationObject = T::DOCUMENTATION_OBJECT;
+}
+
+impl_validation_error!(UsernameError PasswordError TitleError SerialError DescriptionError MetadataError PlatformIDError ItemStatusError FieldError);
@@ -1,14 +1,149 @@
 use proc_macro::TokenStream;
 use quote::{quote, ToTokens};
-use syn::{parse_macro_input, DeriveInput, Expr, Lit, Meta, MetaNameValue};
+use syn::{parse_macro_input, DeriveInput, Expr, Lit, LitStr, Meta, MetaNameValue};
 
-#[proc_macro_derive(Documentation)]
+#[proc_macro_derive(Documentation, attributes(doc_example, doc_min, doc_max))]
 pub fn documentation_derive(input: TokenStream) -> TokenStream {
     let input = parse_macro_input!(input as DeriveInput);
 
     impl_documentation(&input)
 }
 
+/// Implements `archk::v1::validate::Validate`. Fields are checked in order by
+/// `#[doc_min = N]` and `#[doc_max = N]` (same bounds as in documentation) and by
+/// `#[validate(with = path::to::fn)]`, where function returns `Result<(), impl Display>`.
+#[proc_macro_derive(Validate, attributes(doc_min, doc_max, validate))]
+pub fn validate_derive(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    match impl_validate(&input) {
+        Ok(v) => v.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+/// Setter call for `#[doc_example = "..."]`, `#[doc_min = N]` and `#[doc_max = N]` field attributes.
+fn constraint(attr: &syn::Attribute) -> Option<proc_macro2::TokenStream> {
+    let Meta::NameValue(MetaNameValue { path, value, .. }) = &attr.meta else {
+        return None;
+    };
+
+    if path.is_ident("doc_example") {
+        Some(quote! { .set_example(#value) })
+    } else if path.is_ident("doc_min") {
+        Some(quote! { .set_min(#value) })
+    } else if path.is_ident("doc_max") {
+        Some(quote! { .set_max(#value) })
+    } else {
+        None
+    }
+}
+
+/// Concatenated `///` comments.
+fn doc(attrs: &[syn::Attribute]) -> String {
+    attrs
+        .iter()
+        .flat_map(|attr| {
+            if attr.path().is_ident("doc") {
+                let Meta::NameValue(MetaNameValue { value, .. }) = &attr.meta else {
+                    return None;
+                };
+                let Expr::Lit(syn::ExprLit {
+                    lit: Lit::Str(s), ..
+                }) = value
+                else {
+                    return None;
+                };
+
+                Some(s.value())
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+/// Serde attributes of enum (or its variant) relevant to documentation.
+#[derive(Default)]
+struct SerdeAttrs {
+    tag: Option<String>,
+    content: Option<String>,
+    untagged: bool,
+    rename: Option<String>,
+    rename_all: Option<String>,
+}
+
+fn serde_attrs(attrs: &[syn::Attribute]) -> SerdeAttrs {
+    let mut res = SerdeAttrs::default();
+    for attr in attrs.iter().filter(|attr| attr.path().is_ident("serde")) {
+        // Unknown or complex attributes are none of our business, serde checks them
+        let _ = attr.parse_nested_meta(|meta| {
+            let string = || -> syn::Result<String> { Ok(meta.value()?.parse::<LitStr>()?.value()) };
+            if meta.path.is_ident("tag") {
+                res.tag = Some(string()?);
+            } else if meta.path.is_ident("content") {
+                res.content = Some(string()?);
+            } else if meta.path.is_ident("rename") {
+                res.rename = Some(string()?);
+            } else if meta.path.is_ident("rename_all") {
+                res.rename_all = Some(string()?);
+            } else if meta.path.is_ident("untagged") {
+                res.untagged = true;
+            } else if meta.input.peek(syn::Token![=]) {
+                meta.value()?.parse::<Expr>()?;
+            }
+            Ok(())
+        });
+    }
+    res
+}
+
+/// Apply serde `rename_all` rule to `PascalCase` variant name.
+fn rename(name: &str, rule: &str) -> String {
+    let words = |sep: &str| {
+        let mut res = String::new();
+        for (i, c) in name.char_indices() {
+            if i != 0 && c.is_uppercase() {
+                res.push_str(sep);
+            }
+            res.push(c);
+        }
+        res
+    };
+
+    match rule {
+        "lowercase" => name.to_lowercase(),
+        "UPPERCASE" => name.to_uppercase(),
+        "camelCase" => name[..1].to_lowercase() + &name[1..],
+        "snake_case" => words("_").to_lowercase(),
+        "SCREAMING_SNAKE_CASE" => words("_").to_uppercase(),
+        "kebab-case" => words("-").to_lowercase(),
+        "SCREAMING-KEBAB-CASE" => words("-").to_uppercase(),
+        _ => name.to_string(),
+    }
+}
+
+fn fields(fields: &syn::Fields, crate_: &proc_macro2::TokenStream) -> Vec<proc_macro2::TokenStream> {
+    fields
+        .iter()
+        .map(|field| {
+            let doc = doc(&field.attrs);
+            let ty = field.ty.to_token_stream();
+            let name = field.ident.as_ref().map(|v| v.to_string()).unwrap_or_else(|| "0".into());
+            let constraints = field.attrs.iter().flat_map(constraint);
+
+            quote! { 
+                #crate_::v1::docs::DocumentationField {
+                    name: #name,
+                    documentation:
+                        <#ty as #crate_::v1::docs::Documentation>::DOCUMENTATION_OBJECT.set_description(#doc)
+                            #(#constraints)*
+                }
+            }
+        })
+        .collect()
+}
+
 fn impl_documentation(ast: &DeriveInput) -> TokenStream {
     let crate_ = match std::env::var("CARGO_PKG_NAME") {
         Ok(v) if v == "archk" => quote! { crate },
@@ -16,61 +151,164 @@ fn impl_documentation(ast: &DeriveInput) -> TokenStream {
     };
     
     let name = &ast.ident;
+    let name_str = name.to_string();
     // TODO: description
 
-    let fields = if let syn::Data::Struct(data) = &ast.data {
-        data.fields
-            .iter()
-            .map(|field| {
-                let doc: String = field
-                    .attrs
-                    .iter()
-                    .flat_map(|attr| {
-                        if attr.path().is_ident("doc") {
-                            let Meta::NameValue(MetaNameValue { value, .. }) = &attr.meta else {
-                                return None;
-                            };
-                            let Expr::Lit(syn::ExprLit {
-                                lit: Lit::Str(s), ..
-                            }) = value
-                            else {
-                                return None;
-                            };
-
-                            Some(s.value())
-                        } else {
-                            None
-                        }
-                    })
-                    .collect();
-                let ty = field.ty.to_token_stream();
-                let name = field.ident.as_ref().map(|v| v.to_string()).unwrap_or_else(|| "0".into());
+    let object = match &ast.data {
+        syn::Data::Enum(data) => {
+            let attrs = serde_attrs(&ast.attrs);
+            let tagging = match (&attrs.tag, &attrs.content) {
+                _ if attrs.untagged => quote! { Untagged },
+                (Some(tag), Some(content)) => quote! { Adjacent { tag: #tag, content: #content } },
+                (Some(tag), None) => quote! { Internal { tag: #tag } },
+                _ => quote! { External },
+            };
+
+            let variants = data.variants.iter().map(|variant| {
+                let variant_attrs = serde_attrs(&variant.attrs);
+                let ident = variant.ident.to_string();
+                let name = variant_attrs.rename.unwrap_or_else(|| match &attrs.rename_all {
+                    Some(rule) => rename(&ident, rule),
+                    None => ident.clone(),
+                });
+                let doc = doc(&variant.attrs);
+
+                let documentation = match &variant.fields {
+                    syn::Fields::Named(_) => {
+                        let fields = fields(&variant.fields, &crate_);
+                        quote! { #crate_::v1::docs::DocumentationObject::new(#ident, #doc, &[ #(#fields),* ]) }
+                    }
+                    syn::Fields::Unnamed(unnamed) if unnamed.unnamed.len() == 1 => {
+                        let ty = unnamed.unnamed[0].ty.to_token_stream();
+                        quote! { <#ty as #crate_::v1::docs::Documentation>::DOCUMENTATION_OBJECT.set_description(#doc) }
+                    }
+                    syn::Fields::Unnamed(unnamed) => {
+                        let tys = unnamed.unnamed.iter().map(|v| v.ty.to_token_stream());
+                        quote! { <( #(#tys),* ) as #crate_::v1::docs::Documentation>::DOCUMENTATION_OBJECT.set_description(#doc) }
+                    }
+                    syn::Fields::Unit => {
+                        quote! { #crate_::v1::docs::DocumentationObject::new("Unit", #doc, &[]) }
+                    }
+                };
 
-                quote! { 
+                quote! {
                     #crate_::v1::docs::DocumentationField {
                         name: #name,
-                        documentation:
-                            <#ty as #crate_::v1::docs::Documentation>::DOCUMENTATION_OBJECT.set_description(#doc)
+                        documentation: #documentation
                     }
                 }
-            })
-            .collect::<Vec<_>>()
-    } else {
-        Vec::new()
-    };
+            });
 
-    let name_str = name.to_string();
+            quote! {
+                #crate_::v1::docs::DocumentationObject::one_of(
+                    #name_str,
+                    "", // description
+                    #crate_::v1::docs::DocumentationTagging::#tagging,
+                    &[
+                        #(#variants),*
+                    ]
+                )
+            }
+        }
+        syn::Data::Struct(data) => {
+            let fields = fields(&data.fields, &crate_);
+            quote! {
+                #crate_::v1::docs::DocumentationObject::new(
+                    #name_str,
+                    "", // description
+                    &[
+                        #(#fields),*
+                    ]
+                )
+            }
+        }
+        syn::Data::Union(_) => quote! { #crate_::v1::docs::DocumentationObject::new(#name_str, "", &[]) },
+    };
 
     let gen = quote! {
         impl #crate_::v1::docs::Documentation for #name {
-            const DOCUMENTATION_OBJECT: #crate_::v1::docs::DocumentationObject = #crate_::v1::docs::DocumentationObject::new(
-                #name_str,
-                "", // description
-                &[
-                    #(#fields),*
-                ]
-            );
+            const DOCUMENTATION_OBJECT: #crate_::v1::docs::DocumentationObject = #object;
         }
     };
     gen.into()
 }
+
+fn option(v: Option<&Expr>) -> proc_macro2::TokenStream {
+    match v {
+        Some(v) => quote! { Some(#v) },
+        None => quote! { None },
+    }
+}
+
+fn impl_validate(ast: &DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+    let crate_ = match std::env::var("CARGO_PKG_NAME") {
+        Ok(v) if v == "archk" => quote! { crate },
+        _ => quote!(::archk),
+    };
+    let syn::Data::Struct(data) = &ast.data else {
+        return Err(syn::Error::new_spanned(
+            ast,
+            "`Validate` can be derived only for structs",
+        ));
+    };
+
+    let mut checks = Vec::new();
+    for field in &data.fields {
+        let Some(ident) = &field.ident else {
+            return Err(syn::Error::new_spanned(
+                field,
+                "`Validate` requires named fields",
+            ));
+        };
+        let name = ident.to_string();
+
+        let mut min = None;
+        let mut max = None;
+        let mut with = Vec::new();
+        for attr in &field.attrs {
+            match &attr.meta {
+                Meta::NameValue(MetaNameValue { path, value, .. }) if path.is_ident("doc_min") => {
+                    min = Some(value);
+                }
+                Meta::NameValue(MetaNameValue { path, value, .. }) if path.is_ident("doc_max") => {
+                    max = Some(value);
+                }
+                _ if attr.path().is_ident("validate") => {
+                    attr.parse_nested_meta(|meta| {
+                        if meta.path.is_ident("with") {
+                            with.push(meta.value()?.parse::<syn::Path>()?);
+                            Ok(())
+                        } else {
+                            Err(meta.error("expected `with = path::to::fn`"))
+                        }
+                    })?;
+                }
+                _ => {}
+            }
+        }
+
+        if min.is_some() || max.is_some() {
+            let min = option(min);
+            let max = option(max);
+            checks.push(quote! {
+                #crate_::v1::validate::Bounded::check_bounds(&self.#ident, #min, #max)
+                    .map_err(|e| #crate_::v1::validate::FieldError::new(#name, e))?;
+            });
+        }
+        for with in with {
+            checks.push(quote! {
+                #with(&self.#ident).map_err(|e| #crate_::v1::validate::FieldError::new(#name, e))?;
+            });
+        }
+    }
+
+    let name = &ast.ident;
+    Ok(quote! {
+        impl #crate_::v1::validate::Validate for #name {
+            fn validate(&self) -> Result<(), #crate_::v1::validate::FieldError> {
+                #(#checks)*
+                Ok(())
+            }
+        }
+    })
+}